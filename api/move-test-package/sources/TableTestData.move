@@ -60,4 +60,23 @@ module TestAccount::TableTestData {
 
         move_to(&account, test_tables);
     }
+
+    /// A resource too large to render as JSON under a small render limit,
+    /// with an equally large table item.
+    struct LargeResource has key {
+        data: vector<u64>,
+        table: Table<u8, vector<u64>>,
+    }
+
+    public entry fun make_large_resource(account: signer, len: u64) {
+        let data = vector::empty<u64>();
+        let i = 0;
+        while (i < len) {
+            vector::push_back(&mut data, i);
+            i = i + 1;
+        };
+        let table = table::new();
+        table::add(&mut table, 1, copy data);
+        move_to(&account, LargeResource { data, table });
+    }
 }
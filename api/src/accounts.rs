@@ -6,13 +6,10 @@ use crate::{
     failpoint::fail_point,
     metrics::metrics,
     param::{AddressParam, LedgerVersionParam, MoveIdentifierParam, MoveStructTagParam},
-    version::Version,
+    version::{StateReadCaching, Version},
 };
 
-use aptos_api_types::{
-    AccountData, Address, AsConverter, Error, LedgerInfo, MoveModuleBytecode, Response,
-    TransactionId,
-};
+use aptos_api_types::{AccountData, Address, AsConverter, Error, LedgerInfo, MoveModuleBytecode};
 use aptos_types::{
     account_config::AccountResource,
     account_state::AccountState,
@@ -28,13 +25,19 @@ use move_deps::move_core_types::{
     value::MoveValue,
 };
 use std::convert::TryInto;
-use warp::{filters::BoxedFilter, Filter, Rejection, Reply};
+use warp::{filters::BoxedFilter, http::header::IF_NONE_MATCH, Filter, Rejection, Reply};
 
 // GET /accounts/<address>
 pub fn get_account(context: Context) -> BoxedFilter<(impl Reply,)> {
     warp::path!("accounts" / AddressParam)
         .and(warp::get())
         .and(context.filter())
+        .and(warp::query::<Version>())
+        .and(warp::header::optional::<String>(IF_NONE_MATCH.as_str()))
+        .map(|address, ctx, version: Version, if_none_match| {
+            (version.ledger_version(), if_none_match, address, ctx)
+        })
+        .untuple_one()
         .and_then(handle_get_account)
         .with(metrics("get_account"))
         .boxed()
@@ -46,7 +49,10 @@ pub fn get_account_resources(context: Context) -> BoxedFilter<(impl Reply,)> {
         .and(warp::get())
         .and(context.filter())
         .and(warp::query::<Version>())
-        .map(|address, ctx, version: Version| (version.version, address, ctx))
+        .and(warp::header::optional::<String>(IF_NONE_MATCH.as_str()))
+        .map(|address, ctx, version: Version, if_none_match| {
+            (version.ledger_version(), if_none_match, address, ctx)
+        })
         .untuple_one()
         .and_then(handle_get_account_resources)
         .with(metrics("get_account_resources"))
@@ -59,7 +65,10 @@ pub fn get_account_modules(context: Context) -> BoxedFilter<(impl Reply,)> {
         .and(warp::get())
         .and(context.filter())
         .and(warp::query::<Version>())
-        .map(|address, ctx, version: Version| (version.version, address, ctx))
+        .and(warp::header::optional::<String>(IF_NONE_MATCH.as_str()))
+        .map(|address, ctx, version: Version, if_none_match| {
+            (version.ledger_version(), if_none_match, address, ctx)
+        })
         .untuple_one()
         .and_then(handle_get_account_modules)
         .with(metrics("get_account_modules"))
@@ -67,36 +76,51 @@ pub fn get_account_modules(context: Context) -> BoxedFilter<(impl Reply,)> {
 }
 
 async fn handle_get_account(
+    ledger_version: Option<LedgerVersionParam>,
+    if_none_match: Option<String>,
     address: AddressParam,
     context: Context,
 ) -> Result<impl Reply, Rejection> {
     fail_point("endpoint_get_account")?;
-    Ok(Account::new(None, address, context)?.account()?)
+    let caching = StateReadCaching::new(&ledger_version, if_none_match);
+    Ok(Account::new(ledger_version, address, context)?
+        .with_caching(caching)
+        .account()?)
 }
 
 async fn handle_get_account_resources(
     ledger_version: Option<LedgerVersionParam>,
+    if_none_match: Option<String>,
     address: AddressParam,
     context: Context,
 ) -> Result<impl Reply, Rejection> {
     fail_point("endpoint_get_account_resources")?;
-    Ok(Account::new(ledger_version, address, context)?.resources()?)
+    let caching = StateReadCaching::new(&ledger_version, if_none_match);
+    Ok(Account::new(ledger_version, address, context)?
+        .with_caching(caching)
+        .resources()?)
 }
 
 async fn handle_get_account_modules(
     ledger_version: Option<LedgerVersionParam>,
+    if_none_match: Option<String>,
     address: AddressParam,
     context: Context,
 ) -> Result<impl Reply, Rejection> {
     fail_point("endpoint_get_account_modules")?;
-    Ok(Account::new(ledger_version, address, context)?.modules()?)
+    let caching = StateReadCaching::new(&ledger_version, if_none_match);
+    Ok(Account::new(ledger_version, address, context)?
+        .with_caching(caching)
+        .modules()?)
 }
 
 pub(crate) struct Account {
     ledger_version: u64,
     address: Address,
-    latest_ledger_info: LedgerInfo,
+    // The ledger info as of `ledger_version`
+    ledger_info: LedgerInfo,
     context: Context,
+    caching: StateReadCaching,
 }
 
 impl Account {
@@ -105,27 +129,25 @@ impl Account {
         address: AddressParam,
         context: Context,
     ) -> Result<Self, Error> {
-        let latest_ledger_info = context.get_latest_ledger_info()?;
         let ledger_version = ledger_version
             .map(|v| v.parse("ledger version"))
-            .unwrap_or_else(|| Ok(latest_ledger_info.version()))?;
-
-        if ledger_version > latest_ledger_info.version() {
-            return Err(Error::not_found(
-                "ledger",
-                TransactionId::Version(ledger_version),
-                latest_ledger_info.version(),
-            ));
-        }
+            .transpose()?;
+        let ledger_info = context.get_ledger_info_at_version(ledger_version)?;
 
         Ok(Self {
-            ledger_version,
-            address: address.parse("account address")?,
-            latest_ledger_info,
+            ledger_version: ledger_info.version(),
+            address: address.parse_address("account address")?,
+            ledger_info,
             context,
+            caching: StateReadCaching::default(),
         })
     }
 
+    pub fn with_caching(mut self, caching: StateReadCaching) -> Self {
+        self.caching = caching;
+        self
+    }
+
     pub fn account(self) -> Result<impl Reply, Error> {
         let state_key = StateKey::AccessPath(AccessPath::resource_access_path(ResourceKey::new(
             self.address.into(),
@@ -144,16 +166,16 @@ impl Account {
 
         let account_data: AccountData = account_resource.into();
 
-        Response::new(self.latest_ledger_info, &account_data)
+        self.caching.respond(self.ledger_info, &account_data)
     }
 
     pub fn resources(self) -> Result<impl Reply, Error> {
         let resources = self
             .context
-            .move_resolver()?
+            .move_resolver_at_version(self.ledger_version)?
             .as_converter()
             .try_into_resources(self.account_state()?.get_resources())?;
-        Response::new(self.latest_ledger_info, &resources)
+        self.caching.respond(self.ledger_info, &resources)
     }
 
     pub fn modules(self) -> Result<impl Reply, Error> {
@@ -163,7 +185,7 @@ impl Account {
             .map(MoveModuleBytecode::new)
             .map(|m| m.try_parse_abi())
             .collect::<Result<Vec<MoveModuleBytecode>>>()?;
-        Response::new(self.latest_ledger_info, &modules)
+        self.caching.respond(self.ledger_info, &modules)
     }
 
     pub fn find_event_key(
@@ -204,7 +226,7 @@ impl Account {
             .ok_or_else(|| self.resource_not_found(struct_tag))?;
         Ok(self
             .context
-            .move_resolver()?
+            .move_resolver_at_version(self.ledger_version)?
             .as_converter()
             .move_struct_fields(&typ, data)?)
     }
@@ -224,7 +246,7 @@ impl Account {
                 "address({}) and ledger version({})",
                 self.address, self.ledger_version,
             ),
-            self.latest_ledger_info.version(),
+            self.ledger_info.version(),
         )
     }
 
@@ -235,7 +257,7 @@ impl Account {
                 "address({}), struct tag({}) and ledger version({})",
                 self.address, struct_tag, self.ledger_version,
             ),
-            self.latest_ledger_info.version(),
+            self.ledger_info.version(),
         )
     }
 
@@ -246,7 +268,7 @@ impl Account {
                 "address({}), struct tag({}), field name({}) and ledger version({})",
                 self.address, struct_tag, field_name, self.ledger_version,
             ),
-            self.latest_ledger_info.version(),
+            self.ledger_info.version(),
         )
     }
 }
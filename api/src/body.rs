@@ -0,0 +1,175 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use aptos_api_types::{mime_types::JSON, Error};
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+use warp::{
+    http::{header::CONTENT_TYPE, StatusCode},
+    Filter, Rejection,
+};
+
+/// Extracts the body of a POST to an endpoint that accepts the `accepted`
+/// media types, if its Content-Type is `media_type`.
+///
+/// Endpoints accepting several media types have a route for each of them.
+/// A request with another accepted media type is left for the endpoint's
+/// other routes, while every route rejects a Content-Type the endpoint
+/// doesn't accept (or a missing one) with the same 415, naming the accepted
+/// types. The Content-Type is checked before the body is read, so routes
+/// that don't match leave the body for the one that does.
+pub fn body(
+    accepted: &'static [&'static str],
+    media_type: &'static str,
+    limit: u64,
+) -> impl Filter<Extract = (Bytes,), Error = Rejection> + Clone {
+    content_type(accepted, media_type, limit).and(warp::body::bytes())
+}
+
+/// Like `body`, for JSON bodies, which are deserialized into `T`.
+pub fn json_body<T: DeserializeOwned + Send>(
+    accepted: &'static [&'static str],
+    limit: u64,
+) -> impl Filter<Extract = (T,), Error = Rejection> + Clone {
+    content_type(accepted, JSON, limit).and(warp::body::json::<T>())
+}
+
+fn content_type(
+    accepted: &'static [&'static str],
+    media_type: &'static str,
+    limit: u64,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::body::content_length_limit(limit)
+        .and(warp::header::optional::<String>(CONTENT_TYPE.as_str()))
+        .and_then(move |content_type: Option<String>| async move {
+            match match_media_type(accepted, content_type.as_deref()) {
+                Ok(matched) if matched == media_type => Ok(()),
+                // Another route of the endpoint takes this one.
+                Ok(_) => Err(warp::reject::not_found()),
+                Err(err) => Err(warp::reject::custom(err)),
+            }
+        })
+        .untuple_one()
+}
+
+/// Returns which of the `accepted` media types the Content-Type is. Media
+/// types are compared case insensitively, and JSON types may have a UTF-8
+/// charset parameter, which some clients always send. Anything else is a 415.
+fn match_media_type(
+    accepted: &[&'static str],
+    content_type: Option<&str>,
+) -> Result<&'static str, Error> {
+    let content_type = match content_type {
+        Some(content_type) => content_type,
+        None => {
+            return Err(unsupported_media_type(
+                "The request has no Content-Type",
+                accepted,
+            ))
+        }
+    };
+
+    let mut parts = content_type.split(';');
+    let essence = parts.next().unwrap_or_default().trim();
+    let media_type = accepted
+        .iter()
+        .find(|media_type| media_type.eq_ignore_ascii_case(essence))
+        .copied()
+        .ok_or_else(|| {
+            unsupported_media_type(
+                &format!("Unsupported Content-Type '{}'", content_type),
+                accepted,
+            )
+        })?;
+
+    for parameter in parts.map(str::trim).filter(|p| !p.is_empty()) {
+        let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+        let is_utf8_charset = name.trim().eq_ignore_ascii_case("charset")
+            && value.trim().trim_matches('"').eq_ignore_ascii_case("utf-8");
+        if media_type != JSON || !is_utf8_charset {
+            return Err(unsupported_media_type(
+                &format!(
+                    "Unsupported parameter '{}' in Content-Type '{}'",
+                    parameter, content_type
+                ),
+                accepted,
+            ));
+        }
+    }
+    Ok(media_type)
+}
+
+fn unsupported_media_type(reason: &str, accepted: &[&str]) -> Error {
+    Error::new(
+        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        format!("{}, expected one of: {}", reason, accepted.join(", ")),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_api_types::mime_types::BCS_SIGNED_TRANSACTION;
+
+    const ACCEPTED: &[&str] = &[JSON, BCS_SIGNED_TRANSACTION];
+
+    fn rejection(content_type: Option<&str>) -> String {
+        let err = match_media_type(ACCEPTED, content_type).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        err.message
+    }
+
+    #[test]
+    fn test_accepted_media_types_match() {
+        assert_eq!(match_media_type(ACCEPTED, Some(JSON)).unwrap(), JSON);
+        assert_eq!(
+            match_media_type(ACCEPTED, Some("Application/JSON")).unwrap(),
+            JSON
+        );
+        assert_eq!(
+            match_media_type(ACCEPTED, Some(BCS_SIGNED_TRANSACTION)).unwrap(),
+            BCS_SIGNED_TRANSACTION
+        );
+    }
+
+    #[test]
+    fn test_json_tolerates_a_utf8_charset() {
+        for content_type in [
+            "application/json; charset=utf-8",
+            "application/json;charset=UTF-8",
+            "application/json; charset=\"utf-8\"",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                match_media_type(ACCEPTED, Some(*content_type)).unwrap(),
+                JSON
+            );
+        }
+    }
+
+    #[test]
+    fn test_unsupported_content_types_are_rejected() {
+        assert_eq!(
+            rejection(None),
+            "The request has no Content-Type, expected one of: application/json, \
+             application/x.aptos.signed_transaction+bcs"
+        );
+        assert_eq!(
+            rejection(Some("text/plain")),
+            "Unsupported Content-Type 'text/plain', expected one of: application/json, \
+             application/x.aptos.signed_transaction+bcs"
+        );
+        assert_eq!(
+            rejection(Some("application/json; charset=latin1")),
+            "Unsupported parameter 'charset=latin1' in Content-Type \
+             'application/json; charset=latin1', expected one of: application/json, \
+             application/x.aptos.signed_transaction+bcs"
+        );
+        // Only JSON bodies have a charset.
+        assert!(rejection(Some(
+            "application/x.aptos.signed_transaction+bcs; charset=utf-8"
+        ))
+        .starts_with("Unsupported parameter 'charset=utf-8'"));
+    }
+}
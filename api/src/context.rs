@@ -2,13 +2,17 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use anyhow::{anyhow, ensure, format_err, Context as AnyhowContext, Result};
-use aptos_api_types::{AsConverter, BlockInfo, Error, LedgerInfo, TransactionOnChainData, U64};
-use aptos_config::config::{NodeConfig, RoleType};
+use aptos_api_types::{
+    AsConverter, BlockInfo, Error, LedgerInfo, TransactionId, TransactionOnChainData, U64,
+};
+use aptos_config::config::{ApiConfig, NodeConfig, RoleType};
 use aptos_crypto::HashValue;
-use aptos_mempool::{MempoolClientRequest, MempoolClientSender, SubmissionStatus};
+use aptos_mempool::{
+    MempoolClientRequest, MempoolClientSender, PendingTransactionInfo, SubmissionStatus,
+};
 use aptos_state_view::StateView;
 use aptos_types::{
-    access_path::Path,
+    access_path::{AccessPath, Path},
     account_address::AccountAddress,
     account_config::CORE_CODE_ADDRESS,
     account_state::AccountState,
@@ -16,22 +20,32 @@ use aptos_types::{
     contract_event::ContractEvent,
     event::EventKey,
     ledger_info::LedgerInfoWithSignatures,
+    on_chain_config::ConfigurationResource,
     state_store::{state_key::StateKey, state_key_prefix::StateKeyPrefix, state_value::StateValue},
     transaction::{SignedTransaction, TransactionWithProof, Version},
     write_set::WriteOp,
 };
 use aptos_vm::data_cache::{IntoMoveResolver, RemoteStorageOwned};
 use futures::{channel::oneshot, SinkExt};
-use move_deps::move_core_types::ident_str;
+use move_deps::move_core_types::{
+    ident_str, language_storage::ResourceKey, move_resource::MoveStructType,
+};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, convert::Infallible, sync::Arc};
+use state_sync_driver::sync_status::SyncStatusHandle;
+use std::{collections::HashMap, convert::Infallible, sync::Arc, time::Duration};
 use storage_interface::{
     state_view::{DbStateView, DbStateViewAtVersion, LatestDbStateCheckpointView},
     DbReader, Order,
 };
 use warp::{filters::BoxedFilter, Filter, Reply};
 
-use crate::poem_backend::{AptosErrorCode, InternalError};
+use crate::{
+    poem_backend::{
+        build_not_found, AptosErrorCode, AptosErrorResponse, GoneError, InternalError,
+        NotFoundError, PageSizeLimits, ShutdownHandle,
+    },
+    sender_rate_limit::SenderRateLimiter,
+};
 
 // Context holds application scope context
 #[derive(Clone)]
@@ -40,6 +54,9 @@ pub struct Context {
     db: Arc<dyn DbReader>,
     mp_sender: MempoolClientSender,
     node_config: NodeConfig,
+    sync_status_handle: SyncStatusHandle,
+    shutdown_handle: ShutdownHandle,
+    sender_rate_limiter: SenderRateLimiter,
 }
 
 impl Context {
@@ -48,12 +65,20 @@ impl Context {
         db: Arc<dyn DbReader>,
         mp_sender: MempoolClientSender,
         node_config: NodeConfig,
+        sync_status_handle: SyncStatusHandle,
     ) -> Self {
+        let shutdown_handle = ShutdownHandle::new(Duration::from_millis(
+            node_config.api.shutdown_drain_delay_ms,
+        ));
+        let sender_rate_limiter = SenderRateLimiter::from_config(&node_config.api);
         Self {
             chain_id,
             db,
             mp_sender,
             node_config,
+            sync_status_handle,
+            shutdown_handle,
+            sender_rate_limiter,
         }
     }
 
@@ -75,6 +100,26 @@ impl Context {
         self.db.state_view_at_version(Some(version))
     }
 
+    pub fn move_resolver_at_version(
+        &self,
+        version: Version,
+    ) -> Result<RemoteStorageOwned<DbStateView>> {
+        self.state_view_at_version(version)
+            .map(|state_view| state_view.into_move_resolver())
+    }
+
+    pub fn move_resolver_at_version_poem<E: InternalError>(
+        &self,
+        version: Version,
+    ) -> Result<RemoteStorageOwned<DbStateView>, E> {
+        self.move_resolver_at_version(version)
+            .context(format!(
+                "Failed to read state at version {} from DB",
+                version
+            ))
+            .map_err(|e| E::internal(e).error_code(AptosErrorCode::ReadFromStorageError))
+    }
+
     pub fn chain_id(&self) -> ChainId {
         self.chain_id
     }
@@ -83,10 +128,62 @@ impl Context {
         self.node_config.base.role
     }
 
+    pub fn api_config(&self) -> &ApiConfig {
+        &self.node_config.api
+    }
+
     pub fn content_length_limit(&self) -> u64 {
         self.node_config.api.content_length_limit()
     }
 
+    pub fn submission_content_length_limit(&self) -> u64 {
+        self.node_config.api.submission_content_length_limit()
+    }
+
+    pub fn table_item_content_length_limit(&self) -> u64 {
+        self.node_config.api.table_item_content_length_limit()
+    }
+
+    pub fn log_filter_localhost_only(&self) -> bool {
+        self.node_config.api.log_filter_localhost_only
+    }
+
+    pub fn view_max_gas(&self) -> u64 {
+        self.node_config.api.view_max_gas
+    }
+
+    pub fn max_json_render_bytes(&self) -> u64 {
+        self.node_config.api.max_json_render_bytes
+    }
+
+    pub fn max_bcs_render_bytes(&self) -> u64 {
+        self.node_config.api.max_bcs_render_bytes
+    }
+
+    pub fn sync_status_handle(&self) -> &SyncStatusHandle {
+        &self.sync_status_handle
+    }
+
+    pub fn shutdown_handle(&self) -> &ShutdownHandle {
+        &self.shutdown_handle
+    }
+
+    pub fn sender_rate_limiter(&self) -> &SenderRateLimiter {
+        &self.sender_rate_limiter
+    }
+
+    pub fn readiness_max_ledger_staleness(&self) -> Duration {
+        Duration::from_secs(self.node_config.api.readiness_max_ledger_staleness_secs)
+    }
+
+    pub fn readiness_storage_timeout(&self) -> Duration {
+        Duration::from_millis(self.node_config.api.readiness_storage_timeout_ms)
+    }
+
+    pub(crate) fn page_size_limits(&self) -> PageSizeLimits {
+        PageSizeLimits::from_config(&self.node_config.api)
+    }
+
     pub fn filter(self) -> impl Filter<Extract = (Context,), Error = Infallible> + Clone {
         warp::any().map(move || self.clone())
     }
@@ -134,6 +231,116 @@ impl Context {
         }
     }
 
+    /// Returns the ledger info as of the given version, validating that the
+    /// state at that version can be read: it must be neither newer than the
+    /// latest ledger version (404) nor older than the pruning floor (410).
+    /// The latest ledger info is returned if no version is given.
+    pub fn get_ledger_info_at_version(
+        &self,
+        requested_version: Option<u64>,
+    ) -> Result<LedgerInfo, Error> {
+        let latest_ledger_info = self.get_latest_ledger_info()?;
+        let version = match requested_version {
+            Some(version) => version,
+            None => return Ok(latest_ledger_info),
+        };
+
+        if version > latest_ledger_info.version() {
+            return Err(Error::not_found(
+                "ledger",
+                TransactionId::Version(version),
+                latest_ledger_info.version(),
+            ));
+        }
+        let oldest_version = self.get_oldest_state_version(&latest_ledger_info)?;
+        if version < oldest_version {
+            return Err(Error::gone(pruned_version_message(
+                version,
+                oldest_version,
+                latest_ledger_info.version(),
+            ))
+            .aptos_ledger_version(latest_ledger_info.version()));
+        }
+
+        Ok(self.build_ledger_info_at_version(&latest_ledger_info, version)?)
+    }
+
+    pub fn get_ledger_info_at_version_poem<E: NotFoundError + GoneError + InternalError>(
+        &self,
+        requested_version: Option<u64>,
+    ) -> Result<LedgerInfo, E> {
+        let latest_ledger_info = self.get_latest_ledger_info_poem()?;
+        let version = match requested_version {
+            Some(version) => version,
+            None => return Ok(latest_ledger_info),
+        };
+
+        if version > latest_ledger_info.version() {
+            return Err(build_not_found(
+                "ledger",
+                TransactionId::Version(version),
+                latest_ledger_info.version(),
+            ));
+        }
+        let oldest_version = self
+            .get_oldest_state_version(&latest_ledger_info)
+            .map_err(|e| E::internal(e).error_code(AptosErrorCode::ReadFromStorageError))?;
+        if version < oldest_version {
+            return Err(E::gone_str(&pruned_version_message(
+                version,
+                oldest_version,
+                latest_ledger_info.version(),
+            ))
+            .error_code(AptosErrorCode::LedgerVersionPruned)
+            .aptos_ledger_version(latest_ledger_info.version()));
+        }
+
+        self.build_ledger_info_at_version(&latest_ledger_info, version)
+            .context("Failed to read ledger info at the requested version")
+            .map_err(|e| E::internal(e).error_code(AptosErrorCode::ReadFromStorageError))
+    }
+
+    /// Returns the oldest version whose state hasn't been pruned yet.
+    fn get_oldest_state_version(&self, latest_ledger_info: &LedgerInfo) -> Result<u64> {
+        let oldest_ledger_version: u64 = latest_ledger_info.oldest_ledger_version.into();
+        Ok(match self.db.get_state_prune_window()? {
+            Some(prune_window) => latest_ledger_info
+                .version()
+                .saturating_sub(prune_window as u64)
+                .max(oldest_ledger_version),
+            None => oldest_ledger_version,
+        })
+    }
+
+    /// Builds the ledger info describing the given (readable) version, so
+    /// that the ledger headers of historical reads match the data returned.
+    fn build_ledger_info_at_version(
+        &self,
+        latest_ledger_info: &LedgerInfo,
+        version: u64,
+    ) -> Result<LedgerInfo> {
+        if version == latest_ledger_info.version() {
+            return Ok(latest_ledger_info.clone());
+        }
+
+        let config_key = StateKey::AccessPath(AccessPath::resource_access_path(ResourceKey::new(
+            CORE_CODE_ADDRESS,
+            ConfigurationResource::struct_tag(),
+        )));
+        let config_bytes = self
+            .get_state_value(&config_key, version)?
+            .ok_or_else(|| format_err!("Configuration resource not found at {}", version))?;
+        let config: ConfigurationResource = bcs::from_bytes(&config_bytes)?;
+
+        Ok(LedgerInfo {
+            chain_id: latest_ledger_info.chain_id,
+            epoch: config.epoch().into(),
+            ledger_version: version.into(),
+            oldest_ledger_version: latest_ledger_info.oldest_ledger_version,
+            ledger_timestamp: self.get_block_timestamp(version)?.into(),
+        })
+    }
+
     pub fn get_latest_ledger_info_with_signatures(&self) -> Result<LedgerInfoWithSignatures> {
         self.db.get_latest_ledger_info()
     }
@@ -366,6 +573,47 @@ impl Context {
         callback.await.map_err(anyhow::Error::from)
     }
 
+    /// Looks up a transaction in mempool by hash, along with mempool's
+    /// metadata about it. Committed storage is never read.
+    pub async fn get_pending_transaction_info_by_hash(
+        &self,
+        hash: HashValue,
+    ) -> Result<Option<PendingTransactionInfo>> {
+        let (req_sender, callback) = oneshot::channel();
+
+        self.mp_sender
+            .clone()
+            .send(MempoolClientRequest::GetPendingTransactionByHash(
+                hash, req_sender,
+            ))
+            .await
+            .map_err(anyhow::Error::from)?;
+
+        callback.await.map_err(anyhow::Error::from)
+    }
+
+    /// Lists up to `limit` of the account's transactions in mempool,
+    /// ordered by sequence number.
+    pub async fn get_pending_transactions_by_account(
+        &self,
+        address: AccountAddress,
+        limit: u16,
+    ) -> Result<Vec<PendingTransactionInfo>> {
+        let (req_sender, callback) = oneshot::channel();
+
+        self.mp_sender
+            .clone()
+            .send(MempoolClientRequest::GetPendingTransactionsByAccount(
+                address,
+                limit as usize,
+                req_sender,
+            ))
+            .await
+            .map_err(anyhow::Error::from)?;
+
+        callback.await.map_err(anyhow::Error::from)
+    }
+
     pub fn get_transaction_by_version(
         &self,
         version: u64,
@@ -417,6 +665,13 @@ impl Context {
     }
 }
 
+fn pruned_version_message(version: u64, oldest_version: u64, latest_version: u64) -> String {
+    format!(
+        "Ledger version({}) has been pruned, the available versions are [{}, {}]",
+        version, oldest_version, latest_version
+    )
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BlockMetadataState {
     epoch_internal: U64,
@@ -133,20 +133,22 @@ impl Events {
     }
 
     pub fn list(self, page: Page, accept_type: AcceptType) -> Result<impl Reply, Error> {
+        let limit = page.limit(self.context.page_size_limits())?;
         let contract_events = self.context.get_events(
             &self.key,
             page.start(0, u64::MAX)?,
-            page.limit()?,
+            limit,
             self.ledger_info.version(),
         )?;
 
-        match accept_type {
+        let response = match accept_type {
             AcceptType::Json => {
                 let resolver = self.context.move_resolver()?;
                 let events = resolver.as_converter().try_into_events(&contract_events)?;
                 Response::new(self.ledger_info, &events)
             }
             AcceptType::Bcs => Response::new_bcs(self.ledger_info, &contract_events),
-        }
+        }?;
+        Ok(response.with_page_size(limit))
     }
 }
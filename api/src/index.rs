@@ -8,6 +8,7 @@ use crate::{
     failpoint::fail_point,
     log,
     metrics::{metrics, status_metrics},
+    sender_rate_limit::SenderRateLimited,
     state, transactions,
 };
 use aptos_api_types::{Error, IndexResponse, Response};
@@ -35,7 +36,11 @@ pub fn routes(context: Context) -> impl Filter<Extract = impl Reply, Error = Inf
         .or(transactions::get_json_transaction(context.clone()))
         .or(transactions::get_bcs_transactions(context.clone()))
         .or(transactions::get_json_transactions(context.clone()))
+        .or(transactions::get_pending_transaction(context.clone()))
         .or(transactions::get_account_transactions(context.clone()))
+        .or(transactions::get_account_pending_transactions(
+            context.clone(),
+        ))
         .or(transactions::simulate_bcs_transactions(context.clone()))
         .or(transactions::simulate_json_transactions(context.clone()))
         .or(transactions::submit_bcs_transactions(context.clone()))
@@ -45,9 +50,11 @@ pub fn routes(context: Context) -> impl Filter<Extract = impl Reply, Error = Inf
         .or(events::get_json_events_by_event_key(context.clone()))
         .or(events::get_bcs_events_by_event_handle(context.clone()))
         .or(events::get_json_events_by_event_handle(context.clone()))
-        .or(state::get_account_resource(context.clone()))
+        .or(state::get_bcs_account_resource(context.clone()))
+        .or(state::get_json_account_resource(context.clone()))
         .or(state::get_account_module(context.clone()))
-        .or(state::get_table_item(context.clone()))
+        .or(state::get_bcs_table_item(context.clone()))
+        .or(state::get_json_table_item(context.clone()))
         .or(context.health_check_route().with(metrics("health_check")))
         .with(
             warp::cors()
@@ -97,6 +104,7 @@ pub async fn handle_index(context: Context) -> Result<impl Reply, Rejection> {
 async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
     let code;
     let body;
+    let mut retry_after = None;
 
     if err.is_not_found() {
         code = StatusCode::NOT_FOUND;
@@ -119,6 +127,10 @@ async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
     } else if let Some(cause) = err.find::<UnsupportedMediaType>() {
         code = StatusCode::UNSUPPORTED_MEDIA_TYPE;
         body = reply::json(&Error::new(code, cause.to_string()));
+    } else if let Some(cause) = err.find::<SenderRateLimited>() {
+        code = StatusCode::TOO_MANY_REQUESTS;
+        body = reply::json(&Error::new(code, cause.to_string()));
+        retry_after = Some(cause.retry_after_secs());
     } else if let Some(cause) = err.find::<MethodNotAllowed>() {
         code = StatusCode::METHOD_NOT_ALLOWED;
         body = reply::json(&Error::new(code, cause.to_string()));
@@ -129,6 +141,10 @@ async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
     let mut rep = reply::with_status(body, code).into_response();
     rep.headers_mut()
         .insert("access-control-allow-origin", HeaderValue::from_static("*"));
+    if let Some(retry_after) = retry_after {
+        rep.headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    }
     Ok(rep)
 }
 
@@ -3,6 +3,7 @@
 
 mod accept_type;
 mod accounts;
+mod body;
 pub mod context;
 mod events;
 mod health_check;
@@ -13,6 +14,7 @@ mod page;
 pub mod param;
 mod poem_backend;
 pub mod runtime;
+mod sender_rate_limit;
 mod state;
 mod transactions;
 pub(crate) mod version;
@@ -1,12 +1,15 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use aptos_metrics_core::{register_histogram_vec, HistogramVec};
+use aptos_metrics_core::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge_vec,
+    HistogramVec, IntCounter, IntCounterVec, IntGaugeVec,
+};
 
 use once_cell::sync::Lazy;
 use warp::log::{custom, Info, Log};
 
-static HISTOGRAM: Lazy<HistogramVec> = Lazy::new(|| {
+pub static HISTOGRAM: Lazy<HistogramVec> = Lazy::new(|| {
     register_histogram_vec!(
         "aptos_api_requests",
         "API requests latency grouped by method, operation_id and status",
@@ -24,6 +27,41 @@ pub static RESPONSE_STATUS: Lazy<HistogramVec> = Lazy::new(|| {
     .unwrap()
 });
 
+pub static REQUESTS_IN_FLIGHT: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "aptos_api_requests_in_flight",
+        "Number of API requests currently holding a concurrency limit slot, by class",
+        &["class"]
+    )
+    .unwrap()
+});
+
+pub static REQUESTS_SHED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_api_requests_shed",
+        "Number of API requests rejected with 429 by the concurrency limiter, by class",
+        &["class"]
+    )
+    .unwrap()
+});
+
+pub static REQUESTS_TIMED_OUT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_api_requests_timed_out",
+        "Number of API requests that exceeded their timeout budget, by route group",
+        &["group"]
+    )
+    .unwrap()
+});
+
+pub static SUBMISSIONS_RATE_LIMITED: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "aptos_api_submissions_rate_limited",
+        "Number of transaction submissions rejected with 429 by the per sender rate limiter"
+    )
+    .unwrap()
+});
+
 // Record metrics by method, operation_id and status.
 // The operation_id is the id for the request handler.
 // Should use same `operationId` defined in `openapi.yaml` whenever possible.
@@ -1,7 +1,10 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::param::{Param, TransactionVersionParam};
+use crate::{
+    param::{Param, TransactionVersionParam},
+    poem_backend::PageSizeLimits,
+};
 
 use aptos_api_types::{Error, TransactionId};
 
@@ -9,9 +12,6 @@ use anyhow::Result;
 use serde::Deserialize;
 use std::num::NonZeroU16;
 
-const DEFAULT_PAGE_SIZE: u16 = 25;
-const MAX_PAGE_SIZE: u16 = 1000;
-
 #[derive(Clone, Debug, Deserialize)]
 pub(crate) struct Page {
     start: Option<TransactionVersionParam>,
@@ -35,19 +35,13 @@ impl Page {
         Ok(version)
     }
 
-    pub fn limit(&self) -> Result<u16, Error> {
+    pub fn limit(&self, limits: PageSizeLimits) -> Result<u16, Error> {
         let limit = self
             .limit
             .clone()
             .map(|v| v.parse("limit"))
-            .unwrap_or_else(|| Ok(NonZeroU16::new(DEFAULT_PAGE_SIZE).unwrap()))?
-            .get();
-        if limit > MAX_PAGE_SIZE {
-            return Err(Error::invalid_param(
-                "limit",
-                format!("{}, exceed limit {}", limit, MAX_PAGE_SIZE),
-            ));
-        }
-        Ok(limit)
+            .transpose()?
+            .map(NonZeroU16::get);
+        Ok(limits.effective_page_size(limit))
     }
 }
@@ -1,7 +1,9 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use aptos_api_types::{Address, Error, EventKey, MoveStructTag, TransactionId};
+use aptos_api_types::{
+    parse_address, Address, Error, EventKey, HashValue, MoveStructTag, TransactionId,
+};
 use move_deps::move_core_types::identifier::Identifier;
 use percent_encoding::percent_decode_str;
 use serde::{Deserialize, Deserializer};
@@ -11,6 +13,7 @@ use std::{convert::Infallible, str::FromStr};
 
 pub type AddressParam = Param<Address>;
 pub type EventKeyParam = Param<EventKey>;
+pub type HashValueParam = Param<HashValue>;
 pub type LedgerVersionParam = Param<u64>;
 pub type MoveStructTagParam = Param<MoveStructTag>;
 pub type MoveIdentifierParam = Param<Identifier>;
@@ -50,6 +53,20 @@ impl<T: FromStr> Param<T> {
     }
 }
 
+impl Param<Address> {
+    /// Parses an address, unlike `parse` the error says why the address is
+    /// invalid, since there are many forms of address users may try.
+    pub fn parse_address(self, name: &str) -> Result<Address, Error> {
+        let decoded = percent_decode_str(&self.data)
+            .decode_utf8()
+            .map_err(|_| Error::invalid_param(name, &self.data))?;
+
+        parse_address(&decoded)
+            .map(Address::from)
+            .map_err(|e| Error::invalid_param(name, format!("{}, {}", decoded, e)))
+    }
+}
+
 /// `Deserialize` is required for parsing `warp` query string parameter into `Param` type.
 impl<'de, T: FromStr> Deserialize<'de> for Param<T> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
@@ -3,19 +3,19 @@
 
 use anyhow::Context as AnyhowContext;
 use std::convert::TryInto;
-use std::fmt::Display;
 use std::sync::Arc;
 
 use super::accept_type::{parse_accept, AcceptType};
+use super::RequestSpanExt;
 use super::{
-    ApiTags, AptosErrorResponse, BadRequestError, BasicResponse, BasicResponseStatus,
-    InternalError, NotFoundError,
+    build_not_found, ApiTags, AptosErrorResponse, BadRequestError, BasicResponse,
+    BasicResponseStatus, Cancellation, GoneError, InternalError, NotFoundError,
 };
-use super::{AptosErrorCode, BasicErrorWith404, BasicResultWith404};
+use super::{AptosErrorCode, BasicResultWith404And410};
 use crate::context::Context;
 use crate::failpoint::fail_point_poem;
-use aptos_api_types::{AccountData, Address, AsConverter, MoveStructTag, TransactionId};
-use aptos_api_types::{LedgerInfo, MoveModuleBytecode, MoveResource};
+use aptos_api_types::{AccountData, Address, AsConverter, MoveStructTag};
+use aptos_api_types::{LedgerInfo, MoveModuleBytecode, MoveResource, U64};
 use aptos_types::access_path::AccessPath;
 use aptos_types::account_config::AccountResource;
 use aptos_types::account_state::AccountState;
@@ -31,6 +31,7 @@ use move_deps::move_core_types::{
 use poem::web::Accept;
 use poem_openapi::param::Query;
 use poem_openapi::{param::Path, OpenApi};
+use tracing::Span;
 
 pub struct AccountsApi {
     pub context: Arc<Context>,
@@ -41,109 +42,155 @@ impl AccountsApi {
     /// Get account
     ///
     /// Return high level information about an account such as its sequence number.
+    /// The account is read at the given ledger version if one is given,
+    /// otherwise the latest version is used.
     #[oai(
         path = "/accounts/:address",
         method = "get",
         operation_id = "get_account",
-        tag = "ApiTags::General"
+        tag = "ApiTags::Accounts"
     )]
     async fn get_account(
         &self,
         accept: Accept,
+        /// Address of the account, as a hex encoded string with or without
+        /// a leading 0x, e.g. 0x1.
         address: Path<Address>,
-        ledger_version: Query<Option<u64>>,
-    ) -> BasicResultWith404<AccountData> {
+        /// Ledger version to read the account at. Defaults to the latest
+        /// version.
+        ledger_version: Query<Option<U64>>,
+    ) -> BasicResultWith404And410<AccountData> {
         fail_point_poem("endpoint_get_account")?;
         let accept_type = parse_accept(&accept)?;
-        let account = Account::new(self.context.clone(), address.0, ledger_version.0)?;
+        let account = Account::new(
+            self.context.clone(),
+            address.0,
+            ledger_version.0.map(u64::from),
+        )?;
         account.account(&accept_type)
     }
 
     /// Get account resources
     ///
     /// This API returns account resources for a specific ledger version (AKA transaction version).
-    /// If not present, the latest version is used. <---- TODO Update this comment
-    /// The Aptos nodes prune account state history, via a configurable time window (link).
-    /// If the requested data has been pruned, the server responds with a 404
+    /// If not present, the latest version is used.
+    /// The Aptos nodes prune account state history, via a configurable time window.
+    /// If the requested version has been pruned, the server responds with a 410
+    /// whose message includes the range of versions that are still available.
     #[oai(
         path = "/accounts/:address/resources",
         method = "get",
         operation_id = "get_account_resources",
-        tag = "ApiTags::General"
+        tag = "ApiTags::Accounts"
     )]
     async fn get_account_resources(
         &self,
         accept: Accept,
+        /// Address of the account, as a hex encoded string with or without
+        /// a leading 0x, e.g. 0x1.
         address: Path<Address>,
-        ledger_version: Query<Option<u64>>,
-    ) -> BasicResultWith404<Vec<MoveResource>> {
+        /// Ledger version to read the account at. Defaults to the latest
+        /// version.
+        ledger_version: Query<Option<U64>>,
+    ) -> BasicResultWith404And410<Vec<MoveResource>> {
         fail_point_poem("endpoint_get_account_resources")?;
         let accept_type = parse_accept(&accept)?;
-        let account = Account::new(self.context.clone(), address.0, ledger_version.0)?;
+        let account = Account::new(
+            self.context.clone(),
+            address.0,
+            ledger_version.0.map(u64::from),
+        )?;
         account.resources(&accept_type)
     }
 
     /// Get account modules
     ///
-    /// This API returns account resources for a specific ledger version (AKA transaction version).
-    /// If not present, the latest version is used. <---- TODO Update this comment
-    /// The Aptos nodes prune account state history, via a configurable time window (link).
-    /// If the requested data has been pruned, the server responds with a 404
+    /// This API returns account modules for a specific ledger version (AKA transaction version).
+    /// If not present, the latest version is used.
+    /// The Aptos nodes prune account state history, via a configurable time window.
+    /// If the requested version has been pruned, the server responds with a 410
+    /// whose message includes the range of versions that are still available.
     #[oai(
         path = "/accounts/:address/modules",
         method = "get",
         operation_id = "get_account_modules",
-        tag = "ApiTags::General"
+        tag = "ApiTags::Accounts"
     )]
     async fn get_account_modules(
         &self,
         accept: Accept,
+        /// Address of the account, as a hex encoded string with or without
+        /// a leading 0x, e.g. 0x1.
         address: Path<Address>,
-        ledger_version: Query<Option<u64>>,
-    ) -> BasicResultWith404<Vec<MoveModuleBytecode>> {
+        /// Ledger version to read the account at. Defaults to the latest
+        /// version.
+        ledger_version: Query<Option<U64>>,
+        cancellation: Cancellation,
+    ) -> BasicResultWith404And410<Vec<MoveModuleBytecode>> {
         fail_point_poem("endpoint_get_account_modules")?;
         let accept_type = parse_accept(&accept)?;
-        let account = Account::new(self.context.clone(), address.0, ledger_version.0)?;
-        account.modules(&accept_type)
+        let account = Account::new(
+            self.context.clone(),
+            address.0,
+            ledger_version.0.map(u64::from),
+        )?;
+        account.modules(&accept_type, &cancellation)
     }
 }
 
+fn record_account_in_span(address: &Address, ledger_info: &LedgerInfo) {
+    let span = Span::current();
+    span.record_address(address);
+    span.record_version(ledger_info.version());
+}
+
 pub struct Account {
     context: Arc<Context>,
     address: Address,
-    ledger_version: u64,
-    latest_ledger_info: LedgerInfo,
+    /// The ledger info as of the version the account is read at.
+    ledger_info: LedgerInfo,
 }
 
 impl Account {
-    pub fn new(
+    /// Reads the account at the requested ledger version, or at the latest
+    /// version if none is given.
+    pub fn new<E: NotFoundError + GoneError + InternalError>(
         context: Arc<Context>,
         address: Address,
         requested_ledger_version: Option<u64>,
-    ) -> Result<Self, BasicErrorWith404> {
-        let latest_ledger_info = context.get_latest_ledger_info_poem()?;
-        let ledger_version: u64 =
-            requested_ledger_version.unwrap_or_else(|| latest_ledger_info.version());
-
-        if ledger_version > latest_ledger_info.version() {
-            return Err(Self::not_found(
-                "ledger",
-                TransactionId::Version(ledger_version),
-                latest_ledger_info.version(),
-            ));
-        }
+    ) -> Result<Self, E> {
+        let ledger_info = context.get_ledger_info_at_version_poem(requested_ledger_version)?;
+        record_account_in_span(&address, &ledger_info);
 
         Ok(Self {
             context,
             address,
-            ledger_version,
-            latest_ledger_info,
+            ledger_info,
         })
     }
 
+    /// Reads the account at the latest ledger version.
+    pub fn latest<E: InternalError>(context: Arc<Context>, address: Address) -> Result<Self, E> {
+        let ledger_info = context.get_latest_ledger_info_poem()?;
+        record_account_in_span(&address, &ledger_info);
+
+        Ok(Self {
+            context,
+            address,
+            ledger_info,
+        })
+    }
+
+    fn ledger_version(&self) -> u64 {
+        self.ledger_info.version()
+    }
+
     // These functions map directly to endpoint functions.
 
-    pub fn account(self, accept_type: &AcceptType) -> BasicResultWith404<AccountData> {
+    pub fn account<E: NotFoundError + InternalError>(
+        self,
+        accept_type: &AcceptType,
+    ) -> Result<BasicResponse<AccountData>, E> {
         let state_key = StateKey::AccessPath(AccessPath::resource_access_path(ResourceKey::new(
             self.address.into(),
             AccountResource::struct_tag(),
@@ -151,7 +198,7 @@ impl Account {
 
         let state_value = self
             .context
-            .get_state_value_poem(&state_key, self.ledger_version)?;
+            .get_state_value_poem(&state_key, self.ledger_version())?;
 
         let state_value = match state_value {
             Some(state_value) => state_value,
@@ -160,50 +207,62 @@ impl Account {
 
         let account_resource: AccountResource = bcs::from_bytes(&state_value)
             .context("Internal error deserializing response from DB")
-            .map_err(BasicErrorWith404::internal)?;
+            .map_err(E::internal)?;
         let account_data: AccountData = account_resource.into();
 
         BasicResponse::try_from_rust_value((
             account_data,
-            &self.latest_ledger_info,
+            &self.ledger_info,
             BasicResponseStatus::Ok,
             accept_type,
         ))
     }
 
-    pub fn resources(self, accept_type: &AcceptType) -> BasicResultWith404<Vec<MoveResource>> {
+    pub fn resources<E: NotFoundError + InternalError>(
+        self,
+        accept_type: &AcceptType,
+    ) -> Result<BasicResponse<Vec<MoveResource>>, E> {
         let account_state = self.account_state()?;
         let resources = account_state.get_resources();
-        let move_resolver = self.context.move_resolver_poem()?;
+        let move_resolver = self
+            .context
+            .move_resolver_at_version_poem(self.ledger_version())?;
         let converted_resources = move_resolver
             .as_converter()
             .try_into_resources(resources)
             .context("Failed to build move resource response from data in DB")
-            .map_err(BasicErrorWith404::internal)
+            .map_err(E::internal)
             .map_err(|e| e.error_code(AptosErrorCode::InvalidBcsInStorageError))?;
 
         BasicResponse::try_from_rust_value((
             converted_resources,
-            &self.latest_ledger_info,
+            &self.ledger_info,
             BasicResponseStatus::Ok,
             accept_type,
         ))
     }
 
-    pub fn modules(self, accept_type: &AcceptType) -> BasicResultWith404<Vec<MoveModuleBytecode>> {
+    pub fn modules<E: NotFoundError + InternalError>(
+        self,
+        accept_type: &AcceptType,
+        cancellation: &Cancellation,
+    ) -> Result<BasicResponse<Vec<MoveModuleBytecode>>, E> {
         let mut modules = Vec::new();
         for module in self.account_state()?.into_modules() {
+            // Parsing the ABI of big modules is slow, so stop early if the
+            // client won't get the response anyway.
+            cancellation.check::<E>()?;
             modules.push(
                 MoveModuleBytecode::new(module)
                     .try_parse_abi()
                     .context("Failed to parse move module ABI")
-                    .map_err(BasicErrorWith404::internal)
+                    .map_err(E::internal)
                     .map_err(|e| e.error_code(AptosErrorCode::InvalidBcsInStorageError))?,
             );
         }
         BasicResponse::try_from_rust_value((
             modules,
-            &self.latest_ledger_info,
+            &self.ledger_info,
             BasicResponseStatus::Ok,
             accept_type,
         ))
@@ -211,11 +270,11 @@ impl Account {
 
     // Helpers for processing account state.
 
-    fn account_state(&self) -> Result<AccountState, BasicErrorWith404> {
+    fn account_state<E: NotFoundError + InternalError>(&self) -> Result<AccountState, E> {
         let state = self
             .context
-            .get_account_state(self.address.into(), self.ledger_version)
-            .map_err(BasicErrorWith404::internal)
+            .get_account_state(self.address.into(), self.ledger_version())
+            .map_err(E::internal)
             .map_err(|e| e.error_code(AptosErrorCode::ReadFromStorageError))?
             .ok_or_else(|| self.account_not_found())?;
 
@@ -224,49 +283,46 @@ impl Account {
 
     // Helpers for building errors.
 
-    pub fn not_found<S: Display>(
-        resource: &str,
-        identifier: S,
-        ledger_version: u64,
-    ) -> BasicErrorWith404 {
-        BasicErrorWith404::not_found_str(&format!("{} not found by {}", resource, identifier))
-            .aptos_ledger_version(ledger_version)
-    }
-
-    fn account_not_found(&self) -> BasicErrorWith404 {
-        Self::not_found(
+    fn account_not_found<E: NotFoundError>(&self) -> E {
+        build_not_found(
             "account",
             format!(
                 "address({}) and ledger version({})",
-                self.address, self.ledger_version
+                self.address,
+                self.ledger_version()
             ),
-            self.latest_ledger_info.version(),
+            self.ledger_version(),
         )
     }
 
-    fn resource_not_found(&self, struct_tag: &StructTag) -> BasicErrorWith404 {
-        Self::not_found(
+    fn resource_not_found<E: NotFoundError>(&self, struct_tag: &StructTag) -> E {
+        build_not_found(
             "resource",
             format!(
                 "address({}), struct tag({}) and ledger version({})",
-                self.address, struct_tag, self.ledger_version
+                self.address,
+                struct_tag,
+                self.ledger_version()
             ),
-            self.latest_ledger_info.version(),
+            self.ledger_version(),
         )
     }
 
-    fn field_not_found(
+    fn field_not_found<E: NotFoundError>(
         &self,
         struct_tag: &StructTag,
         field_name: &Identifier,
-    ) -> BasicErrorWith404 {
-        Self::not_found(
+    ) -> E {
+        build_not_found(
             "resource",
             format!(
                 "address({}), struct tag({}), field name({}) and ledger version({})",
-                self.address, struct_tag, field_name, self.ledger_version
+                self.address,
+                struct_tag,
+                field_name,
+                self.ledger_version()
             ),
-            self.latest_ledger_info.version(),
+            self.ledger_version(),
         )
     }
 
@@ -275,15 +331,15 @@ impl Account {
 
     // Events specific stuff.
 
-    pub fn find_event_key(
+    pub fn find_event_key<E: BadRequestError + NotFoundError + InternalError>(
         &self,
         event_handle: MoveStructTag,
         field_name: Identifier,
-    ) -> Result<EventKey, BasicErrorWith404> {
+    ) -> Result<EventKey, E> {
         let struct_tag: StructTag = event_handle
             .try_into()
             .context("Given event handle was invalid")
-            .map_err(BasicErrorWith404::bad_request)?;
+            .map_err(E::bad_request)?;
 
         let resource = self.find_resource(&struct_tag)?;
 
@@ -295,32 +351,34 @@ impl Account {
         // Serialization should not fail, otherwise it's internal bug
         let event_handle_bytes = bcs::to_bytes(&value)
             .context("Failed to serialize event handle, this is an internal bug")
-            .map_err(BasicErrorWith404::internal)?;
+            .map_err(E::internal)?;
         // Deserialization may fail because the bytes are not EventHandle struct type.
         let event_handle: EventHandle = bcs::from_bytes(&event_handle_bytes)
             .context(format!(
                 "Deserialization error, field({}) type is not EventHandle struct",
                 field_name
             ))
-            .map_err(BasicErrorWith404::bad_request)?;
+            .map_err(E::bad_request)?;
         Ok(*event_handle.key())
     }
 
-    fn find_resource(
+    fn find_resource<E: NotFoundError + InternalError>(
         &self,
         struct_tag: &StructTag,
-    ) -> Result<Vec<(Identifier, MoveValue)>, BasicErrorWith404> {
+    ) -> Result<Vec<(Identifier, MoveValue)>, E> {
         let account_state = self.account_state()?;
         let (typ, data) = account_state
             .get_resources()
             .find(|(tag, _data)| tag == struct_tag)
             .ok_or_else(|| self.resource_not_found(struct_tag))?;
-        let move_resolver = self.context.move_resolver_poem()?;
+        let move_resolver = self
+            .context
+            .move_resolver_at_version_poem(self.ledger_version())?;
         move_resolver
             .as_converter()
             .move_struct_fields(&typ, data)
             .context("Failed to convert move structs")
-            .map_err(BasicErrorWith404::internal)
+            .map_err(E::internal)
     }
 }
 
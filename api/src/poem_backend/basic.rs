@@ -20,7 +20,8 @@ pub struct BasicApi {
 impl BasicApi {
     /// Show OpenAPI explorer
     ///
-    /// Provides a UI that you can use to explore the API. You can also retrieve the API directly at `/openapi.yaml` and `/openapi.json`.
+    /// Provides a UI that you can use to explore the API. You can also
+    /// retrieve the spec directly at `/spec.yaml` and `/spec.json`.
     #[oai(
         path = "/spec",
         method = "get",
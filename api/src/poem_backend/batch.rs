@@ -0,0 +1,410 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::Arc;
+
+use super::{AptosError, AptosErrorCode};
+use aptos_api_types::{BatchRequestItem, BatchResponseItem};
+use aptos_config::config::ApiConfig;
+use futures::{stream, StreamExt};
+use poem::{
+    http::{self, header, uri::Scheme, Method, StatusCode, Uri},
+    web::{LocalAddr, RemoteAddr},
+    Endpoint, IntoResponse, Request, Response, Result,
+};
+use poem_openapi::payload::Json;
+use serde_json::Value;
+use tokio::io::AsyncReadExt;
+
+/// The path batches are served at.
+pub const BATCH_PATH: &str = "/batch";
+
+/// The only Content-Type a batch may have.
+const JSON: &str = "application/json";
+
+/// Requests with these paths are read only even though they are POSTs.
+const READ_ONLY_POST_PATHS: &[&str] = &["/view"];
+
+/// Endpoint for POST "/batch", which makes several read only requests in one
+/// round trip. The body is a JSON array of `BatchRequestItem`s, and the
+/// response is a JSON array with a `BatchResponseItem` for each of them, in
+/// the same order.
+///
+/// Each request is sent to `inner` on its own, so it goes through the same
+/// middleware as if the client had made it directly, e.g. it counts towards
+/// the concurrency limit and gets its own timeout. Hence `inner` should be
+/// the API with its per request middleware, but not this endpoint itself.
+/// A request that fails only fails its own item, never the whole batch.
+///
+/// The batch itself must be JSON, and its body may be at most
+/// `max_body_bytes` long, like the bodies of other POSTs.
+pub struct BatchEndpoint<E> {
+    inner: Arc<E>,
+    max_requests: usize,
+    max_parallelism: usize,
+    max_body_bytes: u64,
+}
+
+impl<E: Endpoint> BatchEndpoint<E> {
+    pub fn new(
+        inner: Arc<E>,
+        max_requests: usize,
+        max_parallelism: usize,
+        max_body_bytes: u64,
+    ) -> Self {
+        Self {
+            inner,
+            max_requests,
+            max_parallelism: max_parallelism.max(1),
+            max_body_bytes,
+        }
+    }
+
+    pub fn from_config(config: &ApiConfig, inner: Arc<E>) -> Self {
+        Self::new(
+            inner,
+            config.batch_max_requests,
+            config.batch_max_parallelism,
+            config.content_length_limit(),
+        )
+    }
+
+    /// Reads the body of the batch, or returns the error response if it's
+    /// too large. Bodies without a Content-Length are read up to the limit,
+    /// so they can't be used to get around it.
+    async fn read_body(&self, request: &mut Request) -> Result<Result<Vec<u8>, Response>> {
+        let too_large = || {
+            error_response(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("A batch may be at most {} bytes long", self.max_body_bytes),
+                AptosErrorCode::InvalidBatchRequest,
+            )
+        };
+        let content_length = request
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok()?.parse::<u64>().ok());
+        if matches!(content_length, Some(length) if length > self.max_body_bytes) {
+            return Ok(Err(too_large()));
+        }
+
+        let mut body = Vec::new();
+        request
+            .take_body()
+            .into_async_read()
+            .take(self.max_body_bytes.saturating_add(1))
+            .read_to_end(&mut body)
+            .await
+            .map_err(|err| poem::Error::new(err, StatusCode::BAD_REQUEST))?;
+        if body.len() as u64 > self.max_body_bytes {
+            return Ok(Err(too_large()));
+        }
+        Ok(Ok(body))
+    }
+
+    async fn call_item(
+        &self,
+        item: BatchRequestItem,
+        remote_addr: &RemoteAddr,
+    ) -> BatchResponseItem {
+        match build_item_request(item, remote_addr) {
+            Ok(request) => into_response_item(self.inner.get_response(request).await).await,
+            Err(message) => {
+                into_response_item(error_response(
+                    StatusCode::BAD_REQUEST,
+                    message,
+                    AptosErrorCode::NotBatchable,
+                ))
+                .await
+            }
+        }
+    }
+}
+
+#[poem::async_trait]
+impl<E: Endpoint> Endpoint for BatchEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, mut request: Request) -> Result<Self::Output> {
+        if let Err(message) = check_content_type(request.content_type()) {
+            return Ok(error_response(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                message,
+                AptosErrorCode::InvalidBatchRequest,
+            ));
+        }
+        let body = match self.read_body(&mut request).await? {
+            Ok(body) => body,
+            Err(response) => return Ok(response),
+        };
+        let items: Vec<BatchRequestItem> = match serde_json::from_slice(&body) {
+            Ok(items) => items,
+            Err(err) => {
+                return Ok(error_response(
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid batch: {}", err),
+                    AptosErrorCode::InvalidBatchRequest,
+                ))
+            }
+        };
+        if items.len() > self.max_requests {
+            return Ok(error_response(
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "A batch may contain at most {} requests, but this one contains {}",
+                    self.max_requests,
+                    items.len()
+                ),
+                AptosErrorCode::InvalidBatchRequest,
+            ));
+        }
+
+        let remote_addr = request.remote_addr().clone();
+        // `buffered` keeps the responses in the order of the requests.
+        let responses: Vec<BatchResponseItem> = stream::iter(items)
+            .map(|item| self.call_item(item, &remote_addr))
+            .buffered(self.max_parallelism)
+            .collect()
+            .await;
+        Ok(Json(responses).into_response())
+    }
+}
+
+/// Checks that a batch is JSON, which may have a UTF-8 charset parameter, as
+/// for other POSTs.
+fn check_content_type(content_type: Option<&str>) -> Result<(), String> {
+    let content_type = match content_type {
+        Some(content_type) => content_type,
+        None => {
+            return Err(format!(
+                "The request has no Content-Type, expected one of: {}",
+                JSON
+            ))
+        }
+    };
+    let mut parts = content_type.split(';').map(str::trim);
+    let is_json = parts.next().unwrap_or_default().eq_ignore_ascii_case(JSON)
+        && parts.filter(|p| !p.is_empty()).all(|parameter| {
+            let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+            name.trim().eq_ignore_ascii_case("charset")
+                && value.trim().trim_matches('"').eq_ignore_ascii_case("utf-8")
+        });
+    if is_json {
+        Ok(())
+    } else {
+        Err(format!(
+            "Unsupported Content-Type '{}', expected one of: {}",
+            content_type, JSON
+        ))
+    }
+}
+
+/// Builds the request for an item of a batch, or returns why it can't be
+/// made as part of a batch.
+fn build_item_request(item: BatchRequestItem, remote_addr: &RemoteAddr) -> Result<Request, String> {
+    let method = Method::from_bytes(item.method.to_ascii_uppercase().as_bytes())
+        .map_err(|_| format!("Invalid method: {}", item.method))?;
+    let uri: Uri = item
+        .path
+        .parse()
+        .map_err(|_| format!("Invalid path: {}", item.path))?;
+    if !uri.path().starts_with('/') || uri.host().is_some() {
+        return Err(format!(
+            "The path of a batched request must start with '/': {}",
+            item.path
+        ));
+    }
+    if !is_read_only(&method, uri.path()) {
+        return Err(format!(
+            "Only read only requests may be batched, but {} {} is not one",
+            method,
+            uri.path()
+        ));
+    }
+
+    let mut builder = http::Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::ACCEPT, "application/json");
+    let body = match item.body {
+        Some(body) => {
+            builder = builder.header(header::CONTENT_TYPE, "application/json");
+            hyper::Body::from(body.to_string())
+        }
+        None => hyper::Body::empty(),
+    };
+    let request = builder.body(body).map_err(|err| err.to_string())?;
+    Ok(Request::from((
+        request,
+        LocalAddr::default(),
+        remote_addr.clone(),
+        Scheme::HTTP,
+    )))
+}
+
+fn is_read_only(method: &Method, path: &str) -> bool {
+    if path == BATCH_PATH {
+        return false;
+    }
+    *method == Method::GET || (*method == Method::POST && READ_ONLY_POST_PATHS.contains(&path))
+}
+
+async fn into_response_item(mut response: Response) -> BatchResponseItem {
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let body = match response.take_body().into_vec().await {
+        Ok(body) => serde_json::from_slice(&body)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).into_owned())),
+        Err(err) => Value::String(format!("Failed to read the response body: {}", err)),
+    };
+    BatchResponseItem {
+        status: response.status().as_u16(),
+        headers,
+        body,
+    }
+}
+
+fn error_response(status: StatusCode, message: String, error_code: AptosErrorCode) -> Response {
+    Json(AptosError::new(message).error_code(error_code))
+        .with_status(status)
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        poem_backend::{AccountsApi, IndexApi, ViewApi},
+        tests::new_test_context,
+    };
+    use poem::IntoEndpoint;
+    use poem_openapi::OpenApiService;
+    use serde_json::json;
+
+    fn build_endpoint(name: &str, max_requests: usize) -> impl Endpoint<Output = Response> {
+        let context = Arc::new(new_test_context(name).context.clone());
+        let inner = OpenApiService::new(
+            (
+                AccountsApi {
+                    context: context.clone(),
+                },
+                IndexApi {
+                    context: context.clone(),
+                },
+                ViewApi { context },
+            ),
+            "test",
+            "0.1.0",
+        )
+        .into_endpoint();
+        BatchEndpoint::new(Arc::new(inner), max_requests, 2, 1024)
+    }
+
+    async fn call_batch(ep: &impl Endpoint<Output = Response>, body: Value) -> (StatusCode, Value) {
+        call_batch_raw(ep, Some("application/json"), body.to_string()).await
+    }
+
+    async fn call_batch_raw(
+        ep: &impl Endpoint<Output = Response>,
+        content_type: Option<&str>,
+        body: String,
+    ) -> (StatusCode, Value) {
+        let mut builder = Request::builder().method(Method::POST).uri_str(BATCH_PATH);
+        if let Some(content_type) = content_type {
+            builder = builder.content_type(content_type);
+        }
+        let mut resp = ep.get_response(builder.body(body)).await;
+        let body = resp.take_body().into_vec().await.unwrap();
+        (resp.status(), serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_batch_keeps_order_and_isolates_failures() {
+        let ep = build_endpoint("test_batch_keeps_order_and_isolates_failures", 10);
+        let (status, body) = call_batch(
+            &ep,
+            json!([
+                {"method": "GET", "path": "/"},
+                {"method": "GET", "path": "/accounts/0xzz"},
+                {"method": "get", "path": "/accounts/0x1"},
+                {"method": "POST", "path": "/accounts/0x1"},
+                {"method": "GET", "path": "/no/such/path"},
+                {
+                    "method": "POST",
+                    "path": "/view",
+                    "body": {
+                        "function": "0x1::coin::name",
+                        "type_arguments": ["0x1::aptos_coin::AptosCoin"],
+                        "arguments": []
+                    }
+                },
+                {"method": "POST", "path": "/batch", "body": []},
+            ]),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let items = body.as_array().unwrap();
+        let statuses: Vec<u64> = items
+            .iter()
+            .map(|item| item["status"].as_u64().unwrap())
+            .collect();
+        assert_eq!(statuses, vec![200, 400, 200, 400, 404, 200, 400]);
+
+        assert!(items[0]["body"]["chain_id"].is_number());
+        assert!(items[1]["body"].is_string());
+        assert!(items[2]["body"]["sequence_number"].is_string());
+        assert!(items[2]["headers"]
+            .as_object()
+            .unwrap()
+            .contains_key("x-aptos-ledger-version"));
+        assert_eq!(items[3]["body"]["error_code"], "NotBatchable");
+        assert_eq!(items[5]["body"], json!(["Aptos Coin"]));
+        // Batches can't be nested.
+        assert_eq!(items[6]["body"]["error_code"], "NotBatchable");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_batches_are_rejected() {
+        let ep = build_endpoint("test_invalid_batches_are_rejected", 2);
+
+        let (status, body) = call_batch(&ep, json!({"method": "GET", "path": "/"})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error_code"], "InvalidBatchRequest");
+
+        let request = json!({"method": "GET", "path": "/"});
+        let (status, body) = call_batch(&ep, json!([request, request, request])).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body["message"],
+            "A batch may contain at most 2 requests, but this one contains 3"
+        );
+
+        let (status, body) = call_batch(&ep, json!([])).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_batches_must_be_small_json() {
+        let ep = build_endpoint("test_batches_must_be_small_json", 2);
+        let batch = json!([{"method": "GET", "path": "/"}]).to_string();
+
+        for content_type in [None, Some("text/plain"), Some("application/x-bcs")].iter() {
+            let (status, body) = call_batch_raw(&ep, *content_type, batch.clone()).await;
+            assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE, "{}", body);
+            assert_eq!(body["error_code"], "InvalidBatchRequest");
+        }
+        let (status, _) = call_batch_raw(&ep, Some("application/json; charset=utf-8"), batch).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Padding the array with whitespace keeps it valid JSON.
+        let batch = format!("[{}]", " ".repeat(1024));
+        let (status, body) = call_batch_raw(&ep, Some("application/json"), batch).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE, "{}", body);
+        assert_eq!(body["error_code"], "InvalidBatchRequest");
+    }
+}
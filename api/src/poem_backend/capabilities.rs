@@ -0,0 +1,140 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::Arc;
+
+use super::{api_version, ApiTags, PageSizeLimits};
+use crate::context::Context;
+use aptos_api_types::{ApiFeature, BuildInfo, Capabilities};
+use aptos_config::config::ApiConfig;
+use poem_openapi::{payload::Json, OpenApi};
+
+/// The content types the API accepts request bodies as, and can return
+/// responses as.
+const CONTENT_TYPES: &[&str] = &["application/json", "application/x-bcs"];
+
+pub struct CapabilitiesApi {
+    pub context: Arc<Context>,
+}
+
+#[OpenApi]
+impl CapabilitiesApi {
+    /// Get API capabilities
+    ///
+    /// Get the optional features of the API this node has enabled, along
+    /// with the content types and page sizes it supports, so clients can
+    /// adapt to the node rather than probing it with failing requests. The
+    /// response only changes when the node's config (or software) does.
+    #[oai(
+        path = "/-/capabilities",
+        method = "get",
+        operation_id = "get_capabilities",
+        tag = "ApiTags::General"
+    )]
+    async fn get_capabilities(&self) -> Json<Capabilities> {
+        Json(build_capabilities(self.context.api_config()))
+    }
+}
+
+fn build_capabilities(config: &ApiConfig) -> Capabilities {
+    let page_size_limits = PageSizeLimits::from_config(config);
+    Capabilities {
+        api_version: api_version(),
+        build: BuildInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            commit_hash: option_env!("GIT_SHA").map(str::to_string),
+        },
+        request_content_types: CONTENT_TYPES.iter().map(|t| t.to_string()).collect(),
+        response_content_types: CONTENT_TYPES.iter().map(|t| t.to_string()).collect(),
+        default_page_size: page_size_limits.effective_page_size(None),
+        max_page_size: page_size_limits.max_page_size(),
+        batch_max_requests: config.batch_max_requests as u64,
+        features: enabled_features(config),
+    }
+}
+
+fn enabled_features(config: &ApiConfig) -> Vec<ApiFeature> {
+    let mut features = vec![ApiFeature::Simulation, ApiFeature::View];
+    if config.batch_max_requests > 0 {
+        features.push(ApiFeature::Batch);
+    }
+    if cfg!(feature = "failpoints") {
+        features.push(ApiFeature::Failpoints);
+    }
+    if config.metrics_enabled {
+        features.push(ApiFeature::Metrics);
+    }
+    if config.sender_submission_burst > 0 {
+        features.push(ApiFeature::SenderRateLimit);
+    }
+    if config.tls_cert_path.is_some() && config.tls_key_path.is_some() {
+        features.push(ApiFeature::Tls);
+    }
+    features.sort_unstable();
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::new_test_context_with_config;
+    use aptos_config::config::NodeConfig;
+    use poem::{http::StatusCode, Endpoint, IntoEndpoint, Request};
+    use poem_openapi::OpenApiService;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_features_follow_config() {
+        let mut config = ApiConfig::default();
+        config.metrics_enabled = false;
+        let features = enabled_features(&config);
+        assert!(!features.contains(&ApiFeature::Metrics));
+        assert!(!features.contains(&ApiFeature::Tls));
+        assert!(features.contains(&ApiFeature::Batch));
+
+        config.metrics_enabled = true;
+        config.tls_cert_path = Some("cert.pem".to_string());
+        config.tls_key_path = Some("key.pem".to_string());
+        config.batch_max_requests = 0;
+        let features = enabled_features(&config);
+        assert!(features.contains(&ApiFeature::Metrics));
+        assert!(features.contains(&ApiFeature::Tls));
+        assert!(!features.contains(&ApiFeature::Batch));
+
+        // Always in declaration order, whatever the config.
+        let mut sorted = features.clone();
+        sorted.sort_unstable();
+        assert_eq!(features, sorted);
+    }
+
+    #[tokio::test]
+    async fn test_get_capabilities() {
+        let mut node_config = NodeConfig::default();
+        node_config.api.metrics_enabled = true;
+        node_config.api.sender_submission_burst = 0;
+        node_config.api.default_page_size = 50;
+        node_config.api.max_page_size = 20;
+        let context = new_test_context_with_config("test_get_capabilities", node_config);
+        let context = Arc::new(context.context.clone());
+
+        let ep = OpenApiService::new(CapabilitiesApi { context }, "test", "0.1.0").into_endpoint();
+        let mut resp = ep
+            .get_response(Request::builder().uri_str("/-/capabilities").finish())
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: Value =
+            serde_json::from_slice(&resp.take_body().into_vec().await.unwrap()).unwrap();
+
+        assert_eq!(
+            body["response_content_types"],
+            json!(["application/json", "application/x-bcs"])
+        );
+        // The default page size is clamped to the max, as paginated
+        // endpoints do.
+        assert_eq!(body["default_page_size"], json!(20));
+        assert_eq!(body["max_page_size"], json!(20));
+        let features = body["features"].as_array().unwrap();
+        assert!(features.contains(&json!("metrics")));
+        assert!(!features.contains(&json!("sender_rate_limit")));
+    }
+}
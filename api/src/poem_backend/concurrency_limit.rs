@@ -0,0 +1,318 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use super::AptosError;
+use crate::metrics::{REQUESTS_IN_FLIGHT, REQUESTS_SHED};
+use aptos_config::config::ApiConfig;
+use poem::{
+    http::{header, StatusCode},
+    Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+use poem_openapi::payload::Json;
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::{timeout_at, Instant},
+};
+
+/// How long we tell a shed client to wait before trying again.
+const RETRY_AFTER_SECS: u64 = 1;
+
+/// The API counts as shedding load for this long after it last rejected a
+/// request.
+const SHEDDING_WINDOW: Duration = Duration::from_secs(1);
+
+/// Requests to paths with these prefixes are never limited, so that health
+/// checks and metrics scraping keep working while the node is shedding load.
+const BYPASS_PATH_PREFIXES: &[&str] = &["/-/", "/metrics"];
+
+/// What kind of limiting a request is subject to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RequestClass {
+    Bypass,
+    Normal,
+    Expensive,
+}
+
+impl RequestClass {
+    fn of(path: &str) -> Self {
+        if BYPASS_PATH_PREFIXES
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+        {
+            RequestClass::Bypass
+        } else if path.ends_with("/resources") || path.ends_with("/transactions/simulate") {
+            RequestClass::Expensive
+        } else {
+            RequestClass::Normal
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            RequestClass::Bypass => "bypass",
+            RequestClass::Normal => "normal",
+            RequestClass::Expensive => "expensive",
+        }
+    }
+}
+
+/// Middleware that bounds the number of requests executing at once. Requests
+/// that can't get a slot within `max_wait` are rejected with a 429 and a
+/// `Retry-After` header. Known-expensive requests additionally need a slot
+/// from a smaller, separate pool.
+#[derive(Clone)]
+pub struct ConcurrencyLimit {
+    all: Arc<Semaphore>,
+    expensive: Arc<Semaphore>,
+    max_wait: Duration,
+    created: Instant,
+    // When (in milliseconds since `created`, plus one) a request was last
+    // shed, or zero if none has been.
+    last_shed_ms: Arc<AtomicU64>,
+}
+
+impl ConcurrencyLimit {
+    pub fn new(
+        max_concurrent_requests: usize,
+        max_concurrent_expensive_requests: usize,
+        max_wait: Duration,
+    ) -> Self {
+        Self {
+            all: Arc::new(Semaphore::new(max_concurrent_requests)),
+            expensive: Arc::new(Semaphore::new(max_concurrent_expensive_requests)),
+            max_wait,
+            created: Instant::now(),
+            last_shed_ms: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn from_config(config: &ApiConfig) -> Self {
+        Self::new(
+            config.max_concurrent_requests,
+            config.max_concurrent_expensive_requests,
+            Duration::from_millis(config.concurrency_limit_wait_ms),
+        )
+    }
+
+    /// Whether any request has been shed recently.
+    pub fn is_shedding(&self) -> bool {
+        match self.last_shed_ms.load(Ordering::Relaxed) {
+            0 => false,
+            last_shed_ms => {
+                self.created.elapsed().as_millis() as u64 + 1 - last_shed_ms
+                    < SHEDDING_WINDOW.as_millis() as u64
+            }
+        }
+    }
+
+    fn record_shed(&self) {
+        self.last_shed_ms.store(
+            self.created.elapsed().as_millis() as u64 + 1,
+            Ordering::Relaxed,
+        );
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for ConcurrencyLimit {
+    type Output = ConcurrencyLimitEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        ConcurrencyLimitEndpoint {
+            inner: ep,
+            limit: self.clone(),
+        }
+    }
+}
+
+pub struct ConcurrencyLimitEndpoint<E> {
+    inner: E,
+    limit: ConcurrencyLimit,
+}
+
+#[poem::async_trait]
+impl<E: Endpoint> Endpoint for ConcurrencyLimitEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, request: Request) -> Result<Self::Output> {
+        let class = RequestClass::of(request.uri().path());
+        if class == RequestClass::Bypass {
+            return Ok(self.inner.call(request).await?.into_response());
+        }
+
+        let _guard = match self.acquire(class).await {
+            Some(guard) => guard,
+            None => {
+                REQUESTS_SHED.with_label_values(&[class.label()]).inc();
+                self.limit.record_shed();
+                return Ok(too_many_requests());
+            }
+        };
+
+        Ok(self.inner.call(request).await?.into_response())
+    }
+}
+
+impl<E> ConcurrencyLimitEndpoint<E> {
+    /// Waits up to the configured deadline for all the permits this class of
+    /// request needs. Returns None if they couldn't all be acquired in time.
+    async fn acquire(&self, class: RequestClass) -> Option<InFlightGuard> {
+        let deadline = Instant::now() + self.limit.max_wait;
+        let mut permits = Vec::with_capacity(2);
+        if class == RequestClass::Expensive {
+            permits.push(acquire_before(&self.limit.expensive, deadline).await?);
+        }
+        permits.push(acquire_before(&self.limit.all, deadline).await?);
+        Some(InFlightGuard::new(class, permits))
+    }
+}
+
+async fn acquire_before(
+    semaphore: &Arc<Semaphore>,
+    deadline: Instant,
+) -> Option<OwnedSemaphorePermit> {
+    timeout_at(deadline, semaphore.clone().acquire_owned())
+        .await
+        .ok()?
+        .ok()
+}
+
+fn too_many_requests() -> Response {
+    Json(AptosError::new(
+        "The server is overloaded, please retry later".to_string(),
+    ))
+    .with_status(StatusCode::TOO_MANY_REQUESTS)
+    .with_header(header::RETRY_AFTER, RETRY_AFTER_SECS)
+    .into_response()
+}
+
+/// Holds the permits for a request while it executes and keeps the in flight
+/// gauge up to date.
+struct InFlightGuard {
+    class: RequestClass,
+    _permits: Vec<OwnedSemaphorePermit>,
+}
+
+impl InFlightGuard {
+    fn new(class: RequestClass, permits: Vec<OwnedSemaphorePermit>) -> Self {
+        REQUESTS_IN_FLIGHT.with_label_values(&[class.label()]).inc();
+        Self {
+            class,
+            _permits: permits,
+        }
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        REQUESTS_IN_FLIGHT
+            .with_label_values(&[self.class.label()])
+            .dec();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use poem::{get, handler, EndpointExt, Route};
+
+    #[handler]
+    async fn slow() -> &'static str {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        "ok"
+    }
+
+    #[handler]
+    fn fast() -> &'static str {
+        "ok"
+    }
+
+    fn build_endpoint(limit: ConcurrencyLimit) -> Arc<impl Endpoint<Output = Response>> {
+        Arc::new(
+            Route::new()
+                .at("/slow", get(slow))
+                .at("/accounts/:address/resources", get(slow))
+                .at("/-/healthy", get(fast))
+                .with(limit),
+        )
+    }
+
+    fn request(path: &str) -> Request {
+        Request::builder().uri_str(path).finish()
+    }
+
+    fn spawn_requests<E: Endpoint<Output = Response> + 'static>(
+        ep: &Arc<E>,
+        path: &'static str,
+        count: usize,
+    ) -> Vec<tokio::task::JoinHandle<StatusCode>> {
+        (0..count)
+            .map(|_| {
+                let ep = ep.clone();
+                tokio::spawn(async move { ep.get_response(request(path)).await.status() })
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_sheds_when_saturated_and_recovers() {
+        let limit = ConcurrencyLimit::new(2, 1, Duration::from_millis(10));
+        let ep = build_endpoint(limit.clone());
+
+        let in_flight = spawn_requests(&ep, "/slow", 2);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(!limit.is_shedding());
+        let resp = ep.get_response(request("/slow")).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers()[header::RETRY_AFTER], "1");
+        assert!(limit.is_shedding());
+
+        // Health checks bypass the limiter entirely.
+        let resp = ep.get_response(request("/-/healthy")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        for handle in in_flight {
+            assert_eq!(handle.await.unwrap(), StatusCode::OK);
+        }
+
+        let resp = ep.get_response(request("/slow")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_expensive_requests_have_a_separate_limit() {
+        let ep = build_endpoint(ConcurrencyLimit::new(4, 1, Duration::from_millis(10)));
+
+        let in_flight = spawn_requests(&ep, "/accounts/0x1/resources", 1);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let resp = ep.get_response(request("/accounts/0x1/resources")).await;
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // There is still room for normal requests.
+        let resp = ep.get_response(request("/slow")).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        for handle in in_flight {
+            assert_eq!(handle.await.unwrap(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_queued_requests_are_admitted_within_the_wait() {
+        let ep = build_endpoint(ConcurrencyLimit::new(1, 1, Duration::from_secs(5)));
+
+        let statuses = spawn_requests(&ep, "/slow", 3);
+        for handle in statuses {
+            assert_eq!(handle.await.unwrap(), StatusCode::OK);
+        }
+    }
+}
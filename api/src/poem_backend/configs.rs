@@ -0,0 +1,289 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::Arc;
+
+use super::accept_type::{parse_accept, AcceptType};
+use super::{
+    ApiTags, AptosErrorCode, AptosErrorResponse, BasicErrorWith404And410, BasicResponse,
+    BasicResponseStatus, BasicResultWith404And410, InternalError, NotFoundError,
+};
+use crate::context::Context;
+use crate::failpoint::fail_point_poem;
+use aptos_api_types::{LedgerInfo, OnChainConfigs, U64};
+use aptos_types::{
+    access_path::AccessPath,
+    account_config::CORE_CODE_ADDRESS,
+    block_metadata::BlockResource,
+    on_chain_config::{
+        access_path_for_config, ConfigID, ConfigStorage, OnChainConfig, OnChainConsensusConfig,
+        VMConfig, ValidatorSet, Version,
+    },
+};
+use move_deps::move_core_types::move_resource::{MoveResource, MoveStructType};
+use poem::web::Accept;
+use poem_openapi::{
+    param::{Path, Query},
+    OpenApi,
+};
+
+const GAS_SCHEDULE: &str = "gas_schedule";
+const EPOCH_INTERVAL_USECS: &str = "epoch_interval_usecs";
+const VALIDATOR_SET_SIZE: &str = "validator_set_size";
+const VERSION: &str = "version";
+const CONSENSUS_CONFIG: &str = "consensus_config";
+
+/// The names of the configs the API serves, in the order they are listed.
+const CONFIG_NAMES: &[&str] = &[
+    GAS_SCHEDULE,
+    EPOCH_INTERVAL_USECS,
+    VALIDATOR_SET_SIZE,
+    VERSION,
+    CONSENSUS_CONFIG,
+];
+
+pub struct ConfigsApi {
+    pub context: Arc<Context>,
+}
+
+#[OpenApi]
+impl ConfigsApi {
+    /// Get on-chain configs
+    ///
+    /// Get the on-chain configuration clients most often need, such as the
+    /// gas schedule, at the given ledger version, or the latest version if
+    /// none is given.
+    #[oai(
+        path = "/configs",
+        method = "get",
+        operation_id = "get_configs",
+        tag = "ApiTags::Configs"
+    )]
+    async fn get_configs(
+        &self,
+        accept: Accept,
+        /// Ledger version to get the configs at. Defaults to the latest
+        /// version.
+        ledger_version: Query<Option<U64>>,
+    ) -> BasicResultWith404And410<OnChainConfigs> {
+        fail_point_poem("endpoint_get_configs")?;
+        let accept_type = parse_accept(&accept)?;
+        let ledger_info = self
+            .context
+            .get_ledger_info_at_version_poem(ledger_version.0.map(u64::from))?;
+        self.configs(CONFIG_NAMES, &ledger_info, &accept_type)
+    }
+
+    /// Get on-chain config
+    ///
+    /// Get a single on-chain config by name, e.g. "gas_schedule", at the
+    /// given ledger version, or the latest version if none is given. Only
+    /// that config is set in the response.
+    #[oai(
+        path = "/configs/:name",
+        method = "get",
+        operation_id = "get_config",
+        tag = "ApiTags::Configs"
+    )]
+    async fn get_config(
+        &self,
+        accept: Accept,
+        /// Name of the config, one of "gas_schedule", "epoch_interval_usecs",
+        /// "validator_set_size", "version" and "consensus_config".
+        name: Path<String>,
+        /// Ledger version to get the config at. Defaults to the latest
+        /// version.
+        ledger_version: Query<Option<U64>>,
+    ) -> BasicResultWith404And410<OnChainConfigs> {
+        fail_point_poem("endpoint_get_config")?;
+        let accept_type = parse_accept(&accept)?;
+        let name = match CONFIG_NAMES.iter().find(|known| **known == name.0) {
+            Some(name) => *name,
+            None => {
+                return Err(BasicErrorWith404And410::not_found_str(&format!(
+                    "Unknown config '{}', the known configs are: {}",
+                    name.0,
+                    CONFIG_NAMES.join(", ")
+                ))
+                .error_code(AptosErrorCode::UnknownConfig))
+            }
+        };
+        let ledger_info = self
+            .context
+            .get_ledger_info_at_version_poem(ledger_version.0.map(u64::from))?;
+        self.configs(&[name], &ledger_info, &accept_type)
+    }
+}
+
+impl ConfigsApi {
+    fn configs(
+        &self,
+        names: &[&str],
+        ledger_info: &LedgerInfo,
+        accept_type: &AcceptType,
+    ) -> BasicResultWith404And410<OnChainConfigs> {
+        let storage = self
+            .context
+            .move_resolver_at_version_poem::<BasicErrorWith404And410>(ledger_info.version())?;
+
+        let mut configs = OnChainConfigs::default();
+        for name in names {
+            match *name {
+                GAS_SCHEDULE => {
+                    let vm_config: VMConfig = fetch_config(&storage)?;
+                    configs.gas_schedule = Some((&vm_config.gas_schedule.gas_constants).into());
+                }
+                EPOCH_INTERVAL_USECS => {
+                    // The block resource isn't an on-chain config, but it is
+                    // stored alongside them.
+                    let bytes = storage
+                        .fetch_config(AccessPath::new(
+                            CORE_CODE_ADDRESS,
+                            BlockResource::resource_path(),
+                        ))
+                        .ok_or_else(|| missing_config(BlockResource::struct_tag()))?;
+                    let block_resource: BlockResource = bcs::from_bytes(&bytes)
+                        .map_err(BasicErrorWith404And410::internal)
+                        .map_err(|e| e.error_code(AptosErrorCode::InvalidBcsInStorageError))?;
+                    configs.epoch_interval_usecs = Some(block_resource.epoch_interval().into());
+                }
+                VALIDATOR_SET_SIZE => {
+                    let validator_set: ValidatorSet = fetch_config(&storage)?;
+                    configs.validator_set_size =
+                        Some((validator_set.payload().count() as u64).into());
+                }
+                VERSION => {
+                    configs.version =
+                        Some(fetch_config_bytes(&storage, Version::CONFIG_ID)?.into());
+                }
+                CONSENSUS_CONFIG => {
+                    configs.consensus_config = Some(
+                        fetch_config_bytes(&storage, OnChainConsensusConfig::CONFIG_ID)?.into(),
+                    );
+                }
+                _ => unreachable!("Unknown config {}", name),
+            }
+        }
+
+        BasicResponse::try_from_rust_value((
+            configs,
+            ledger_info,
+            BasicResponseStatus::Ok,
+            accept_type,
+        ))
+    }
+}
+
+fn fetch_config<T: OnChainConfig>(
+    storage: &impl ConfigStorage,
+) -> Result<T, BasicErrorWith404And410> {
+    T::fetch_config(storage).ok_or_else(|| missing_config(T::CONFIG_ID))
+}
+
+fn fetch_config_bytes(
+    storage: &impl ConfigStorage,
+    config_id: ConfigID,
+) -> Result<Vec<u8>, BasicErrorWith404And410> {
+    storage
+        .fetch_config(access_path_for_config(config_id))
+        .ok_or_else(|| missing_config(config_id))
+}
+
+/// Every config the API serves is created at genesis, so one missing (or
+/// failing to deserialize) means something is wrong with the node.
+fn missing_config(config: impl std::fmt::Display) -> BasicErrorWith404And410 {
+    BasicErrorWith404And410::internal_str(&format!("Failed to read {} from storage", config))
+        .error_code(AptosErrorCode::ReadFromStorageError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::new_test_context;
+    use aptos_types::transaction::{ScriptFunction, TransactionPayload};
+    use move_deps::move_core_types::{ident_str, language_storage::ModuleId};
+    use poem::{http::StatusCode, Endpoint, IntoEndpoint, Request};
+    use poem_openapi::OpenApiService;
+    use serde_json::{json, Value};
+
+    async fn get(context: Arc<Context>, path: &str) -> (StatusCode, Value) {
+        let ep = OpenApiService::new(ConfigsApi { context }, "test", "0.1.0").into_endpoint();
+        let mut resp = ep
+            .get_response(Request::builder().uri_str(path).finish())
+            .await;
+        let body = resp.take_body().into_vec().await.unwrap();
+        (resp.status(), serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_get_configs() {
+        let context = new_test_context("test_get_configs");
+        let (status, configs) = get(Arc::new(context.context.clone()), "/configs").await;
+        assert_eq!(status, StatusCode::OK);
+
+        assert_eq!(configs["gas_schedule"]["min_transaction_gas_units"], "600");
+        assert_eq!(configs["gas_schedule"]["max_price_per_gas_unit"], "10000");
+        // The test genesis uses a one day epoch.
+        assert_eq!(configs["epoch_interval_usecs"], "86400000000");
+        assert_eq!(configs["validator_set_size"], "1");
+        assert!(configs["version"].as_str().unwrap().starts_with("0x"));
+        assert!(configs["consensus_config"]
+            .as_str()
+            .unwrap()
+            .starts_with("0x"));
+    }
+
+    #[tokio::test]
+    async fn test_get_config_by_name() {
+        let context = Arc::new(new_test_context("test_get_config_by_name").context.clone());
+
+        let (status, configs) = get(context.clone(), "/configs/validator_set_size").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(configs, json!({"validator_set_size": "1"}));
+
+        let (status, body) = get(context, "/configs/feature_flags").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error_code"], "UnknownConfig");
+        assert_eq!(
+            body["message"],
+            "Unknown config 'feature_flags', the known configs are: gas_schedule, \
+             epoch_interval_usecs, validator_set_size, version, consensus_config"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_configs_at_a_previous_epoch() {
+        let mut context = new_test_context("test_get_configs_at_a_previous_epoch");
+        let before = context.get_latest_ledger_info().version();
+
+        // Changing the gas constants starts a new epoch.
+        let args: Vec<u64> = vec![4, 9, 700, 600, 8, 4000000, 0, 20000, 262144, 1000, 800];
+        let mut root_account = context.root_account();
+        let txn = root_account.sign_with_transaction_builder(
+            context
+                .transaction_factory()
+                .payload(TransactionPayload::ScriptFunction(ScriptFunction::new(
+                    ModuleId::new(CORE_CODE_ADDRESS, ident_str!("vm_config").to_owned()),
+                    ident_str!("set_gas_constants").to_owned(),
+                    vec![],
+                    args.iter().map(|arg| bcs::to_bytes(arg).unwrap()).collect(),
+                )))
+                .expiration_timestamp_secs(u64::MAX),
+        );
+        context.commit_block(&[txn]).await;
+        let context = Arc::new(context.context.clone());
+
+        let (_, latest) = get(context.clone(), "/configs/gas_schedule").await;
+        assert_eq!(latest["gas_schedule"]["min_transaction_gas_units"], "700");
+        assert_eq!(latest["gas_schedule"]["max_price_per_gas_unit"], "20000");
+
+        let (status, previous) = get(
+            context,
+            &format!("/configs/gas_schedule?ledger_version={}", before),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(previous["gas_schedule"]["min_transaction_gas_units"], "600");
+        assert_eq!(previous["gas_schedule"]["max_price_per_gas_unit"], "10000");
+    }
+}
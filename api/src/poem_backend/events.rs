@@ -14,7 +14,7 @@ use crate::context::Context;
 use crate::failpoint::fail_point_poem;
 use anyhow::Context as AnyhowContext;
 use aptos_api_types::{Address, EventKey, IdentifierWrapper, MoveStructTagWrapper};
-use aptos_api_types::{AsConverter, Event};
+use aptos_api_types::{AsConverter, Event, U64};
 use poem::web::Accept;
 use poem_openapi::param::Query;
 use poem_openapi::{param::Path, OpenApi};
@@ -27,12 +27,13 @@ pub struct EventsApi {
 impl EventsApi {
     /// Get events by event key
     ///
-    /// todo
+    /// Returns a page of the events in the event stream identified by the
+    /// given event key, in order of their sequence number.
     #[oai(
         path = "/events/:event_key",
         method = "get",
         operation_id = "get_events_by_event_key",
-        tag = "ApiTags::General"
+        tag = "ApiTags::Events"
     )]
     async fn get_events_by_event_key(
         &self,
@@ -40,13 +41,23 @@ impl EventsApi {
         // TODO: Make this a little smarter, in the spec this just looks like a string.
         // Consider unpacking the inner EventKey type and taking two params, the creation
         // number and the address.
+        /// Event key of the event stream, i.e. the hex encoded creation
+        /// number followed by the address of the account that created it.
         event_key: Path<EventKey>,
-        start: Query<Option<u64>>,
+        /// Sequence number of the first event to return. Defaults to 0.
+        start: Query<Option<U64>>,
+        /// Max number of items to return. Limits above the node's configured
+        /// max page size are clamped to it, so clients should rely on the
+        /// X-Aptos-Page-Size header for the page size that was actually used.
         limit: Query<Option<u16>>,
     ) -> BasicResultWith404<Vec<Event>> {
         fail_point_poem("endpoint_get_events_by_event_key")?;
         let accept_type = parse_accept(&accept)?;
-        let page = Page::new(start.0, limit.0);
+        let page = Page::new(
+            start.0.map(u64::from),
+            limit.0,
+            self.context.page_size_limits(),
+        );
         self.list(&accept_type, page, event_key.0)
     }
 
@@ -59,21 +70,34 @@ impl EventsApi {
         path = "/accounts/:address/events/:event_handle/:field_name",
         method = "get",
         operation_id = "get_events_by_event_handle",
-        tag = "ApiTags::General"
+        tag = "ApiTags::Events"
     )]
     async fn get_events_by_event_handle(
         &self,
         accept: Accept,
+        /// Address of the account, as a hex encoded string with or without
+        /// a leading 0x, e.g. 0x1.
         address: Path<Address>,
+        /// Struct tag of the resource holding the event handle, e.g.
+        /// 0x1::coin::CoinStore<0x1::aptos_coin::AptosCoin>.
         event_handle: Path<MoveStructTagWrapper>,
+        /// Name of the field in that resource holding the event handle.
         field_name: Path<IdentifierWrapper>,
-        start: Query<Option<u64>>,
+        /// Sequence number of the first event to return. Defaults to 0.
+        start: Query<Option<U64>>,
+        /// Max number of items to return. Limits above the node's configured
+        /// max page size are clamped to it, so clients should rely on the
+        /// X-Aptos-Page-Size header for the page size that was actually used.
         limit: Query<Option<u16>>,
     ) -> BasicResultWith404<Vec<Event>> {
         fail_point_poem("endpoint_get_events_by_event_handle")?;
         let accept_type = parse_accept(&accept)?;
-        let page = Page::new(start.0, limit.0);
-        let account = Account::new(self.context.clone(), address.0, None)?;
+        let page = Page::new(
+            start.0.map(u64::from),
+            limit.0,
+            self.context.page_size_limits(),
+        );
+        let account = Account::latest(self.context.clone(), address.0)?;
         let key = account
             .find_event_key(event_handle.0.into(), field_name.0.into())?
             .into();
@@ -89,12 +113,13 @@ impl EventsApi {
         event_key: EventKey,
     ) -> BasicResultWith404<Vec<Event>> {
         let latest_ledger_info = self.context.get_latest_ledger_info_poem()?;
+        let limit = page.limit()?;
         let contract_events = self
             .context
             .get_events(
                 &event_key.into(),
                 page.start(0, u64::MAX)?,
-                page.limit()?,
+                limit,
                 latest_ledger_info.version(),
             )
             // TODO: Previously this was a 500, but I'm making this a 400. I suspect
@@ -115,5 +140,6 @@ impl EventsApi {
             BasicResponseStatus::Ok,
             accept_type,
         ))
+        .map(|response| response.with_page_size(limit))
     }
 }
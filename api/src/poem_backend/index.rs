@@ -19,7 +19,9 @@ pub struct IndexApi {
 impl IndexApi {
     /// Get ledger info
     ///
-    /// Get the latest ledger information, including data such as chain ID, role type, ledger versions, epoch, etc.
+    /// Get the latest ledger information, including data such as chain ID,
+    /// role type, ledger versions, epoch, etc. The same ledger information
+    /// is returned in the X-Aptos-* headers of every response.
     #[oai(
         path = "/",
         method = "get",
@@ -1,16 +1,28 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
-use crate::metrics::RESPONSE_STATUS;
+use super::accept_type::parse_accept;
+use super::{
+    ApiTags, AptosErrorCode, AptosErrorResponse, AptosPost, BadRequestError, BasicErrorWith403,
+    BasicResponse, BasicResponseStatus, BasicResult, BasicResultWith403, ForbiddenError,
+    InternalError,
+};
+use crate::{context::Context, metrics::RESPONSE_STATUS};
+use aptos_api_types::{LogFilter, LogFilterChange};
 use aptos_logger::{
-    debug, error,
+    debug, error, info,
     prelude::{sample, SampleRate},
     sample::Sampling,
-    Schema,
+    Filter, Schema,
+};
+use poem::{
+    http::header,
+    web::{Accept, RemoteAddr},
+    Endpoint, Request, Response, Result,
 };
-use poem::{http::header, Endpoint, Request, Response, Result};
+use poem_openapi::OpenApi;
 
 /// Logs information about the request and response if the response status code
 /// is >= 500, to help us debug since this will be an error on our side.
@@ -58,6 +70,107 @@ pub async fn middleware_log<E: Endpoint>(next: E, request: Request) -> Result<Re
     Ok(response)
 }
 
+pub struct LogFilterApi {
+    pub context: Arc<Context>,
+}
+
+#[OpenApi]
+impl LogFilterApi {
+    /// Get log filter
+    ///
+    /// Get the filter currently controlling what the node logs locally.
+    #[oai(
+        path = "/log/filter",
+        method = "get",
+        operation_id = "get_log_filter",
+        tag = "ApiTags::Operations"
+    )]
+    async fn get_log_filter(&self, accept: Accept) -> BasicResult<LogFilter> {
+        let accept_type = parse_accept(&accept)?;
+        let ledger_info = self.context.get_latest_ledger_info_poem()?;
+        let filter = current_filter()?;
+
+        BasicResponse::try_from_rust_value((
+            LogFilter {
+                filter: filter.to_string(),
+            },
+            &ledger_info,
+            BasicResponseStatus::Ok,
+            &accept_type,
+        ))
+    }
+
+    /// Set log filter
+    ///
+    /// Replace the filter controlling what the node logs locally, e.g. with
+    /// `debug,state_sync=trace`. The change takes effect immediately and
+    /// lasts until the node restarts. An invalid filter is rejected and
+    /// leaves the current one in place. This is only served on the admin
+    /// address (`api.admin_address`) and, unless the node is configured
+    /// otherwise, only to clients connecting from a loopback address.
+    #[oai(
+        path = "/log/filter",
+        method = "post",
+        operation_id = "set_log_filter",
+        tag = "ApiTags::Operations"
+    )]
+    async fn set_log_filter(
+        &self,
+        accept: Accept,
+        remote_addr: &RemoteAddr,
+        log_filter: AptosPost<LogFilter>,
+    ) -> BasicResultWith403<LogFilterChange> {
+        let accept_type = parse_accept(&accept)?;
+        if self.context.log_filter_localhost_only() && !is_loopback(remote_addr) {
+            return Err(BasicErrorWith403::forbidden_str(
+                "The log filter may only be changed from localhost",
+            ));
+        }
+
+        let log_filter = match log_filter {
+            AptosPost::Json(payload) => payload.0,
+            AptosPost::Bcs(payload) => payload.0,
+        };
+        let filter: Filter = log_filter.filter.parse().map_err(|e| {
+            BasicErrorWith403::bad_request_str(&format!("{}", e))
+                .error_code(AptosErrorCode::InvalidLogFilter)
+        })?;
+        let ledger_info = self.context.get_latest_ledger_info_poem()?;
+
+        let previous =
+            aptos_logger::replace_local_filter(filter.clone()).ok_or_else(filter_unsupported)?;
+        info!("Log filter changed from \"{}\" to \"{}\"", previous, filter);
+
+        BasicResponse::try_from_rust_value((
+            LogFilterChange {
+                previous: previous.to_string(),
+                current: filter.to_string(),
+            },
+            &ledger_info,
+            BasicResponseStatus::Ok,
+            &accept_type,
+        ))
+    }
+}
+
+fn current_filter<E: InternalError>() -> Result<Filter, E> {
+    aptos_logger::local_filter().ok_or_else(filter_unsupported)
+}
+
+fn filter_unsupported<E: InternalError>() -> E {
+    E::internal_str("The logger does not support changing its filter at runtime")
+}
+
+// Requests that come through the warp reverse proxy have the proxy's address
+// rather than the client's, which is why `/log/filter` is only served on the
+// admin address (see `attach_admin_api_to_runtime`).
+fn is_loopback(remote_addr: &RemoteAddr) -> bool {
+    remote_addr
+        .as_socket_addr()
+        .map(|addr| addr.ip().is_loopback())
+        .unwrap_or(false)
+}
+
 // TODO: Figure out how to have certain fields be borrowed, like in the
 // original implementation.
 #[derive(Schema)]
@@ -73,3 +186,171 @@ pub struct HttpRequestLog {
     pub elapsed: std::time::Duration,
     forwarded: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::new_test_context;
+    use aptos_infallible::Mutex;
+    use aptos_logger::{Level, Logger, Writer};
+    use once_cell::sync::Lazy;
+    use poem::{
+        http::{self, uri::Scheme, StatusCode},
+        web::LocalAddr,
+        Addr, IntoEndpoint,
+    };
+    use poem_openapi::OpenApiService;
+    use serde_json::{json, Value};
+    use std::net::SocketAddr;
+
+    /// Collects every line the global logger prints, so tests can check
+    /// what is actually emitted.
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<Mutex<Vec<String>>>);
+
+    impl CapturedLogs {
+        fn contains(&self, needle: &str) -> bool {
+            self.0.lock().iter().any(|line| line.contains(needle))
+        }
+    }
+
+    impl Writer for CapturedLogs {
+        fn write(&self, log: String) {
+            self.0.lock().push(log);
+        }
+    }
+
+    // The global logger can only be set once per process, and no other test
+    // in this crate sets one.
+    static CAPTURED_LOGS: Lazy<CapturedLogs> = Lazy::new(|| {
+        let logs = CapturedLogs::default();
+        Logger::builder()
+            .is_async(false)
+            .level(Level::Info)
+            .printer(Box::new(logs.clone()))
+            .build();
+        logs
+    });
+
+    /// Puts back the filter the global logger had when it was created, even
+    /// if the test fails, so other tests log as usual.
+    struct RestoreFilter(Filter);
+
+    impl Drop for RestoreFilter {
+        fn drop(&mut self) {
+            aptos_logger::replace_local_filter(self.0.clone());
+        }
+    }
+
+    fn request_from(remote_addr: &str, method: http::Method, body: Option<Value>) -> Request {
+        let request = http::Request::builder()
+            .method(method)
+            .uri("/log/filter")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(hyper::Body::from(
+                body.map(|body| body.to_string()).unwrap_or_default(),
+            ))
+            .unwrap();
+        let remote_addr: SocketAddr = remote_addr.parse().unwrap();
+        Request::from((
+            request,
+            LocalAddr::default(),
+            RemoteAddr(Addr::SocketAddr(remote_addr)),
+            Scheme::HTTP,
+        ))
+    }
+
+    async fn call(context: &Arc<Context>, request: Request) -> (StatusCode, Value) {
+        let ep = OpenApiService::new(
+            LogFilterApi {
+                context: context.clone(),
+            },
+            "test",
+            "0.1.0",
+        )
+        .into_endpoint();
+        let mut resp = ep.get_response(request).await;
+        let body = resp.take_body().into_vec().await.unwrap();
+        (resp.status(), serde_json::from_slice(&body).unwrap())
+    }
+
+    async fn set_filter(context: &Arc<Context>, filter: &str) -> (StatusCode, Value) {
+        call(
+            context,
+            request_from(
+                "127.0.0.1:1234",
+                http::Method::POST,
+                Some(json!({ "filter": filter })),
+            ),
+        )
+        .await
+    }
+
+    async fn get_filter(context: &Arc<Context>) -> String {
+        let (status, resp) = call(
+            context,
+            request_from("127.0.0.1:1234", http::Method::GET, None),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        resp["filter"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_set_log_filter() {
+        let logs = CAPTURED_LOGS.clone();
+        let _restore = RestoreFilter(aptos_logger::local_filter().unwrap());
+        let context = Arc::new(new_test_context("test_set_log_filter").context.clone());
+        let original = get_filter(&context).await;
+
+        let (status, resp) = set_filter(&context, "warn").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(resp["previous"], json!(original));
+        assert_eq!(resp["current"], json!("warn"));
+        debug!("test_set_log_filter: filtered out");
+        assert!(!logs.contains("test_set_log_filter: filtered out"));
+
+        let (status, resp) = set_filter(&context, "warn,aptos_api=debug").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(resp["previous"], json!("warn"));
+        assert_eq!(resp["current"], json!("warn,aptos_api=debug"));
+        debug!("test_set_log_filter: let through");
+        assert!(logs.contains("test_set_log_filter: let through"));
+
+        // An invalid directive is rejected and leaves the filter untouched.
+        let (status, resp) = set_filter(&context, "info,aptos_api=loud").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            resp["message"],
+            json!("invalid log filter directive \"aptos_api=loud\"")
+        );
+        assert_eq!(get_filter(&context).await, "warn,aptos_api=debug");
+
+        let (status, _) = set_filter(&context, &original).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(get_filter(&context).await, original);
+    }
+
+    #[tokio::test]
+    async fn test_set_log_filter_is_localhost_only() {
+        let context = Arc::new(
+            new_test_context("test_set_log_filter_is_localhost_only")
+                .context
+                .clone(),
+        );
+        let (status, resp) = call(
+            &context,
+            request_from(
+                "10.0.0.1:1234",
+                http::Method::POST,
+                Some(json!({ "filter": "trace" })),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(
+            resp["message"],
+            json!("The log filter may only be changed from localhost")
+        );
+    }
+}
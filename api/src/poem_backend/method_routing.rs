@@ -0,0 +1,235 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::Arc;
+
+use super::{AptosError, RouteTemplates};
+use hyper::body::HttpBody;
+use poem::{
+    http::{header, HeaderValue, Method, StatusCode},
+    Body, Endpoint, IntoResponse, Middleware, Request, Response, Result,
+};
+use poem_openapi::payload::Json;
+
+/// Middleware that handles the methods of API routes centrally:
+///
+/// - Requests using a method the path doesn't support get a 405 with an
+///   `Allow` header, rather than a 404.
+/// - HEAD is supported on every GET route by running the GET handler and
+///   dropping the body, keeping the headers (and the length of the body).
+/// - OPTIONS requests that aren't CORS preflights (which the CORS middleware
+///   answers before they get here) get a 204 with an `Allow` header.
+///
+/// Paths that aren't API routes, e.g. the spec, are passed through untouched.
+#[derive(Clone)]
+pub struct MethodRouting {
+    routes: Arc<RouteTemplates>,
+}
+
+impl MethodRouting {
+    pub fn new(routes: RouteTemplates) -> Self {
+        Self {
+            routes: Arc::new(routes),
+        }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for MethodRouting {
+    type Output = MethodRoutingEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        MethodRoutingEndpoint {
+            inner: ep,
+            routes: self.routes.clone(),
+        }
+    }
+}
+
+pub struct MethodRoutingEndpoint<E> {
+    inner: E,
+    routes: Arc<RouteTemplates>,
+}
+
+#[poem::async_trait]
+impl<E: Endpoint> Endpoint for MethodRoutingEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, mut request: Request) -> Result<Self::Output> {
+        let methods = self.routes.allowed_methods(request.uri().path());
+        let method = request.method().clone();
+        if methods.is_empty() || methods.contains(&method) {
+            return Ok(self.inner.call(request).await?.into_response());
+        }
+
+        if method == Method::HEAD && methods.contains(&Method::GET) {
+            request.set_method(Method::GET);
+            let response = self.inner.call(request).await?.into_response();
+            return Ok(strip_body(response));
+        }
+
+        let allow = allow_header(&methods);
+        if method == Method::OPTIONS {
+            return Ok(Response::builder()
+                .status(StatusCode::NO_CONTENT)
+                .header(header::ALLOW, allow)
+                .finish());
+        }
+
+        Ok(Json(AptosError::new(format!(
+            "Method {} is not allowed for {}, the allowed methods are: {}",
+            method,
+            request.uri().path(),
+            allow
+        )))
+        .with_status(StatusCode::METHOD_NOT_ALLOWED)
+        .with_header(header::ALLOW, allow)
+        .into_response())
+    }
+}
+
+/// Lists the given methods, plus the ones we support centrally.
+fn allow_header(methods: &[Method]) -> String {
+    let mut allow: Vec<&str> = methods.iter().map(Method::as_str).collect();
+    if methods.contains(&Method::GET) {
+        allow.push(Method::HEAD.as_str());
+    }
+    allow.push(Method::OPTIONS.as_str());
+    allow.join(", ")
+}
+
+/// Drops the body of a response to a HEAD request. If the length of the body
+/// is known without reading it, it is kept as the content length.
+fn strip_body(mut response: Response) -> Response {
+    let body: hyper::Body = response.take_body().into();
+    if let Some(len) = body.size_hint().exact() {
+        if !response.headers().contains_key(header::CONTENT_LENGTH) {
+            response
+                .headers_mut()
+                .insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+        }
+    }
+    response.set_body(Body::empty());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        poem_backend::{IndexApi, LogFilterApi},
+        tests::new_test_context,
+    };
+    use poem::{middleware::Cors, EndpointExt, IntoEndpoint};
+    use poem_openapi::OpenApiService;
+
+    fn build_endpoint(name: &str) -> impl Endpoint<Output = Response> {
+        let context = Arc::new(new_test_context(name).context.clone());
+        let service = OpenApiService::new(
+            (
+                IndexApi {
+                    context: context.clone(),
+                },
+                LogFilterApi { context },
+            ),
+            "test",
+            "0.1.0",
+        );
+        let routes = RouteTemplates::from_spec(&service.spec()).unwrap();
+        service
+            .into_endpoint()
+            .with(MethodRouting::new(routes))
+            .with(
+                Cors::new()
+                    .allow_methods(vec![Method::GET, Method::POST])
+                    .allow_headers(vec![header::CONTENT_TYPE, header::ACCEPT]),
+            )
+    }
+
+    fn request(method: Method, path: &str) -> Request {
+        Request::builder().method(method).uri_str(path).finish()
+    }
+
+    #[tokio::test]
+    async fn test_post_to_get_route() {
+        let ep = build_endpoint("test_post_to_get_route");
+        let mut resp = ep.get_response(request(Method::POST, "/")).await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(resp.headers()[header::ALLOW], "GET, HEAD, OPTIONS");
+        let body: serde_json::Value =
+            serde_json::from_slice(&resp.take_body().into_vec().await.unwrap()).unwrap();
+        assert_eq!(
+            body["message"],
+            "Method POST is not allowed for /, the allowed methods are: GET, HEAD, OPTIONS"
+        );
+
+        let resp = ep.get_response(request(Method::PUT, "/log/filter")).await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(resp.headers()[header::ALLOW], "GET, POST, HEAD, OPTIONS");
+
+        // Unknown paths are still a 404.
+        let resp = ep.get_response(request(Method::POST, "/unknown")).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_head_on_index() {
+        let ep = build_endpoint("test_head_on_index");
+        let mut get = ep.get_response(request(Method::GET, "/")).await;
+        let get_body = get.take_body().into_vec().await.unwrap();
+
+        let mut head = ep.get_response(request(Method::HEAD, "/")).await;
+        assert_eq!(head.status(), StatusCode::OK);
+        assert!(head.take_body().into_vec().await.unwrap().is_empty());
+        assert_eq!(
+            head.headers()[header::CONTENT_LENGTH],
+            get_body.len().to_string()
+        );
+        for name in ["X-Aptos-Chain-Id", "X-Aptos-Ledger-Version", "content-type"] {
+            assert_eq!(head.headers()[name], get.headers()[name]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_options() {
+        let ep = build_endpoint("test_options");
+
+        // Without CORS headers we just list the allowed methods.
+        let resp = ep
+            .get_response(request(Method::OPTIONS, "/log/filter"))
+            .await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(resp.headers()[header::ALLOW], "GET, POST, HEAD, OPTIONS");
+
+        // CORS preflights are answered by the CORS middleware.
+        let resp = ep
+            .get_response(
+                Request::builder()
+                    .method(Method::OPTIONS)
+                    .uri_str("/log/filter")
+                    .header(header::ORIGIN, "https://example.com")
+                    .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                    .finish(),
+            )
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(resp
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_METHODS));
+        assert!(!resp.headers().contains_key(header::ALLOW));
+
+        // Other CORS requests still get their 405.
+        let resp = ep
+            .get_response(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri_str("/")
+                    .header(header::ORIGIN, "https://example.com")
+                    .finish(),
+            )
+            .await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert!(resp
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+}
@@ -0,0 +1,125 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use std::io;
+
+use aptos_config::config::ApiConfig;
+use aptos_metrics_core::{gather, Encoder, TextEncoder};
+use bytes::Bytes;
+use futures::stream;
+use poem::{Body, Endpoint, Request, Response, Result};
+
+/// Serves the node's default Prometheus registry in the text exposition
+/// format, for deployments that only expose the API port and therefore can't
+/// scrape the inspection service.
+#[derive(Clone, Debug)]
+pub struct MetricsEndpoint {
+    allowed_prefixes: Vec<String>,
+}
+
+impl MetricsEndpoint {
+    /// Only metric families whose name starts with one of the given prefixes
+    /// are exposed. If there are no prefixes, every family is exposed.
+    pub fn new(allowed_prefixes: Vec<String>) -> Self {
+        Self { allowed_prefixes }
+    }
+
+    pub fn from_config(config: &ApiConfig) -> Self {
+        Self::new(config.metrics_allowed_prefixes.clone())
+    }
+
+    fn is_allowed(&self, name: &str) -> bool {
+        self.allowed_prefixes.is_empty()
+            || self
+                .allowed_prefixes
+                .iter()
+                .any(|prefix| name.starts_with(prefix.as_str()))
+    }
+}
+
+#[poem::async_trait]
+impl Endpoint for MetricsEndpoint {
+    type Output = Response;
+
+    async fn call(&self, _request: Request) -> Result<Self::Output> {
+        let metric_families: Vec<_> = gather()
+            .into_iter()
+            .filter(|family| self.is_allowed(family.get_name()))
+            .collect();
+
+        // Encode one family per chunk as the body is polled, so we never
+        // hold the whole rendered registry in memory at once.
+        let encoder = TextEncoder::new();
+        let content_type = encoder.format_type().to_string();
+        let chunks = stream::iter(metric_families.into_iter().map(move |family| {
+            let mut buffer = vec![];
+            encoder
+                .encode(&[family], &mut buffer)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            Ok::<_, io::Error>(Bytes::from(buffer))
+        }));
+
+        Ok(Response::builder()
+            .content_type(&content_type)
+            .body(Body::from_bytes_stream(chunks)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::REQUESTS_IN_FLIGHT;
+    use aptos_metrics_core::{register_int_counter, IntCounter};
+    use once_cell::sync::Lazy;
+    use poem::{
+        get,
+        http::{header, StatusCode},
+        Route,
+    };
+
+    static NOT_ALLOWED: Lazy<IntCounter> = Lazy::new(|| {
+        register_int_counter!(
+            "test_metrics_endpoint_not_allowed",
+            "A metric outside of the allowed prefixes"
+        )
+        .unwrap()
+    });
+
+    async fn scrape(metrics: MetricsEndpoint) -> String {
+        // Make sure both families have a value to report.
+        REQUESTS_IN_FLIGHT.with_label_values(&["bypass"]);
+        NOT_ALLOWED.inc();
+
+        let route = Route::new().at("/-/metrics", get(metrics));
+        let mut resp = route
+            .get_response(Request::builder().uri_str("/-/metrics").finish())
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers()[header::CONTENT_TYPE],
+            TextEncoder::new().format_type()
+        );
+        resp.take_body().into_string().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_scrape_metrics() {
+        let body = scrape(MetricsEndpoint::new(vec![])).await;
+        assert!(body.contains("# TYPE aptos_api_requests_in_flight gauge"));
+        assert!(body.contains("aptos_api_requests_in_flight{class=\"bypass\"}"));
+        assert!(body.contains("# TYPE test_metrics_endpoint_not_allowed counter"));
+    }
+
+    #[tokio::test]
+    async fn test_scrape_metrics_with_allowed_prefixes() {
+        let body = scrape(MetricsEndpoint::new(vec!["aptos_api_".to_string()])).await;
+        assert!(body.contains("# TYPE aptos_api_requests_in_flight gauge"));
+        assert!(!body.contains("test_metrics_endpoint_not_allowed"));
+        for line in body.lines().filter(|line| !line.is_empty()) {
+            let name = line
+                .trim_start_matches("# HELP ")
+                .trim_start_matches("# TYPE ");
+            assert!(name.starts_with("aptos_api_"), "unexpected line: {}", line);
+        }
+    }
+}
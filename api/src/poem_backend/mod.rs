@@ -6,32 +6,83 @@ use poem_openapi::Tags;
 mod accept_type;
 mod accounts;
 mod basic;
+mod batch;
 mod bcs_payload;
+mod capabilities;
+mod concurrency_limit;
+mod configs;
 mod events;
 mod index;
 mod log;
+mod method_routing;
+mod metrics;
+mod node_info;
 mod page;
 mod post;
+mod readiness;
+mod request_span;
+mod request_timeout;
 mod response;
+mod route_templates;
 mod runtime;
 mod transactions;
+mod view;
 
 #[derive(Tags)]
 pub enum ApiTags {
-    /// General information.
+    /// General information about the node and the API itself.
     General,
+
+    /// Access to accounts, their resources and modules.
+    Accounts,
+
+    /// Access to transactions, both committed and pending.
+    Transactions,
+
+    /// Access to events, either by event key or by the event handle in an
+    /// account resource.
+    Events,
+
+    /// Access to blocks and the transactions within them.
+    Blocks,
+
+    /// Access to items in Move tables.
+    Tables,
+
+    /// Read only execution of Move view functions.
+    View,
+
+    /// Access to on-chain configuration, e.g. the gas schedule.
+    Configs,
+
+    /// Endpoints for node operators, e.g. to inspect state sync or adjust
+    /// logging. Some of these are only available from localhost.
+    Operations,
 }
 
 pub use accept_type::AcceptType;
 pub use accounts::AccountsApi;
 pub use basic::BasicApi;
+pub use batch::{BatchEndpoint, BATCH_PATH};
+pub use capabilities::CapabilitiesApi;
+pub use concurrency_limit::ConcurrencyLimit;
+pub use configs::ConfigsApi;
 pub use events::EventsApi;
 pub use index::IndexApi;
-pub use log::middleware_log;
+pub use log::{middleware_log, LogFilterApi};
+pub use method_routing::MethodRouting;
+pub use metrics::MetricsEndpoint;
+pub use node_info::NodeInfoApi;
+pub(crate) use page::PageSizeLimits;
 pub use post::AptosPost;
+pub use readiness::{ReadinessApi, ShutdownHandle};
+pub use request_span::{RequestSpanExt, RequestTracing};
+pub use request_timeout::{Cancellation, RequestTimeouts};
 pub use response::*;
-pub use runtime::attach_poem_to_runtime;
+pub use route_templates::RouteTemplates;
+pub use runtime::{api_version, attach_admin_api_to_runtime, attach_poem_to_runtime};
 pub use transactions::TransactionsApi;
+pub use view::ViewApi;
 
 // TODO: Move these impls throughout each of the files in the parent directory.
 // The only reason I do it here right now is the existing handler functions return
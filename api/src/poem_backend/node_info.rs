@@ -0,0 +1,199 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::accept_type::parse_accept;
+use super::ApiTags;
+use super::{BasicResponse, BasicResponseStatus, BasicResult};
+use crate::context::Context;
+use aptos_api_types::{LedgerInfo, NodeInfo, SyncState};
+use poem::web::Accept;
+use poem_openapi::OpenApi;
+use state_sync_driver::sync_status::{DriverState, SyncStatus};
+
+pub struct NodeInfoApi {
+    pub context: Arc<Context>,
+}
+
+#[OpenApi]
+impl NodeInfoApi {
+    /// Get node info
+    ///
+    /// Get operational details about the node, such as how far along state
+    /// sync is. Fields the node doesn't know yet are omitted, e.g. while
+    /// state sync is starting up only the data in storage is reported.
+    #[oai(
+        path = "/-/node_info",
+        method = "get",
+        operation_id = "get_node_info",
+        tag = "ApiTags::Operations"
+    )]
+    async fn get_node_info(&self, accept: Accept) -> BasicResult<NodeInfo> {
+        let accept_type = parse_accept(&accept)?;
+        let ledger_info = self.context.get_latest_ledger_info_poem()?;
+
+        let node_info = build_node_info(
+            self.context.sync_status_handle().get_status(),
+            &ledger_info,
+            now_usecs(),
+        );
+
+        BasicResponse::try_from_rust_value((
+            node_info,
+            &ledger_info,
+            BasicResponseStatus::Ok,
+            &accept_type,
+        ))
+    }
+}
+
+/// Builds the node info from the status published by state sync. If state
+/// sync hasn't published anything yet, we fall back to what's in storage.
+fn build_node_info(
+    status: Option<SyncStatus>,
+    ledger_info: &LedgerInfo,
+    now_usecs: u64,
+) -> NodeInfo {
+    match status {
+        Some(status) => NodeInfo {
+            sync_state: Some(match status.driver_state {
+                DriverState::Bootstrapping => SyncState::Bootstrapping,
+                DriverState::CatchingUp => SyncState::CatchingUp,
+                DriverState::CaughtUp => SyncState::CaughtUp,
+            }),
+            synced_version: Some(status.synced_version.into()),
+            synced_epoch: Some(status.synced_epoch.into()),
+            sync_lag_secs: Some(lag_secs(status.synced_timestamp_usecs, now_usecs).into()),
+            highest_advertised_version: status.highest_advertised_version.map(Into::into),
+            snapshot_sync_progress_percent: status.snapshot_sync_progress_percent,
+        },
+        None => NodeInfo {
+            synced_version: Some(ledger_info.ledger_version),
+            synced_epoch: Some(ledger_info.epoch),
+            sync_lag_secs: Some(lag_secs(ledger_info.timestamp(), now_usecs).into()),
+            ..NodeInfo::default()
+        },
+    }
+}
+
+fn lag_secs(synced_timestamp_usecs: u64, now_usecs: u64) -> u64 {
+    now_usecs.saturating_sub(synced_timestamp_usecs) / 1_000_000
+}
+
+fn now_usecs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_micros() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::new_test_context;
+    use poem::{http::StatusCode, Endpoint, IntoEndpoint, Request};
+    use poem_openapi::OpenApiService;
+    use serde_json::{json, Value};
+
+    const SECOND_USECS: u64 = 1_000_000;
+
+    fn sync_status(driver_state: DriverState) -> SyncStatus {
+        SyncStatus {
+            driver_state,
+            synced_version: 100,
+            synced_epoch: 2,
+            synced_timestamp_usecs: 10 * SECOND_USECS,
+            highest_advertised_version: Some(150),
+            snapshot_sync_progress_percent: None,
+        }
+    }
+
+    async fn get_node_info(context: Arc<Context>) -> Value {
+        let ep = OpenApiService::new(NodeInfoApi { context }, "test", "0.1.0").into_endpoint();
+        let mut resp = ep
+            .get_response(Request::builder().uri_str("/-/node_info").finish())
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        serde_json::from_slice(&resp.take_body().into_vec().await.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_node_info_from_sync_status() {
+        let ledger_info =
+            new_test_context("test_node_info_from_sync_status").get_latest_ledger_info();
+        let now = 15 * SECOND_USECS + 500_000;
+
+        let mut status = sync_status(DriverState::Bootstrapping);
+        status.snapshot_sync_progress_percent = Some(42);
+        let node_info = build_node_info(Some(status), &ledger_info, now);
+        assert_eq!(node_info.sync_state, Some(SyncState::Bootstrapping));
+        assert_eq!(node_info.snapshot_sync_progress_percent, Some(42));
+        assert_eq!(node_info.sync_lag_secs, Some(5.into()));
+
+        let node_info = build_node_info(
+            Some(sync_status(DriverState::CatchingUp)),
+            &ledger_info,
+            now,
+        );
+        assert_eq!(node_info.sync_state, Some(SyncState::CatchingUp));
+        assert_eq!(node_info.synced_version, Some(100.into()));
+        assert_eq!(node_info.synced_epoch, Some(2.into()));
+        assert_eq!(node_info.highest_advertised_version, Some(150.into()));
+        assert_eq!(node_info.snapshot_sync_progress_percent, None);
+
+        // A synced timestamp ahead of our clock must not underflow
+        let node_info = build_node_info(
+            Some(sync_status(DriverState::CaughtUp)),
+            &ledger_info,
+            SECOND_USECS,
+        );
+        assert_eq!(node_info.sync_state, Some(SyncState::CaughtUp));
+        assert_eq!(node_info.sync_lag_secs, Some(0.into()));
+    }
+
+    #[tokio::test]
+    async fn test_get_node_info_before_sync_status_is_published() {
+        let context = new_test_context("test_get_node_info_before_sync_status_is_published");
+        let ledger_info = context.get_latest_ledger_info();
+
+        let resp = get_node_info(Arc::new(context.context.clone())).await;
+        assert_eq!(resp["sync_state"], Value::Null);
+        assert_eq!(resp["highest_advertised_version"], Value::Null);
+        assert_eq!(resp["snapshot_sync_progress_percent"], Value::Null);
+        assert_eq!(
+            resp["synced_version"],
+            json!(ledger_info.version().to_string())
+        );
+        assert_eq!(resp["synced_epoch"], json!(ledger_info.epoch.to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_get_node_info_follows_sync_status() {
+        let context = new_test_context("test_get_node_info_follows_sync_status");
+        let handle = context.context.sync_status_handle().clone();
+        let context = Arc::new(context.context.clone());
+
+        let mut status = sync_status(DriverState::Bootstrapping);
+        status.snapshot_sync_progress_percent = Some(42);
+        handle.update_status(status);
+        let resp = get_node_info(context.clone()).await;
+        assert_eq!(resp["sync_state"], json!("bootstrapping"));
+        assert_eq!(resp["snapshot_sync_progress_percent"], json!(42));
+
+        handle.update_status(sync_status(DriverState::CatchingUp));
+        let resp = get_node_info(context.clone()).await;
+        assert_eq!(resp["sync_state"], json!("catching_up"));
+        assert_eq!(resp["synced_version"], json!("100"));
+        assert_eq!(resp["synced_epoch"], json!("2"));
+        assert_eq!(resp["highest_advertised_version"], json!("150"));
+        assert_eq!(resp["snapshot_sync_progress_percent"], Value::Null);
+
+        handle.update_status(sync_status(DriverState::CaughtUp));
+        let resp = get_node_info(context).await;
+        assert_eq!(resp["sync_state"], json!("caught_up"));
+    }
+}
@@ -2,20 +2,59 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::{AptosErrorCode, BadRequestError};
-use serde::Deserialize;
+use aptos_config::config::ApiConfig;
 
-const DEFAULT_PAGE_SIZE: u16 = 25;
-const MAX_PAGE_SIZE: u16 = 1000;
+/// The page sizes this node is configured to serve. This is the one place
+/// that decides how big a page paginated endpoints return.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct PageSizeLimits {
+    default_page_size: u16,
+    max_page_size: u16,
+}
+
+impl PageSizeLimits {
+    pub fn new(default_page_size: u16, max_page_size: u16) -> Self {
+        // A max of zero would make every endpoint return nothing, so we
+        // always allow at least a single item per page.
+        let max_page_size = max_page_size.max(1);
+        Self {
+            default_page_size: default_page_size.clamp(1, max_page_size),
+            max_page_size,
+        }
+    }
+
+    pub fn from_config(config: &ApiConfig) -> Self {
+        Self::new(config.default_page_size, config.max_page_size)
+    }
+
+    pub fn max_page_size(&self) -> u16 {
+        self.max_page_size
+    }
 
-#[derive(Clone, Debug, Deserialize)]
+    /// Returns the page size to use given the (non-zero) limit requested by
+    /// the client, if any. Limits above the max are clamped rather than
+    /// rejected.
+    pub fn effective_page_size(&self, requested: Option<u16>) -> u16 {
+        requested
+            .unwrap_or(self.default_page_size)
+            .min(self.max_page_size)
+    }
+}
+
+#[derive(Clone, Debug)]
 pub(crate) struct Page {
     start: Option<u64>,
     limit: Option<u16>,
+    limits: PageSizeLimits,
 }
 
 impl Page {
-    pub fn new(start: Option<u64>, limit: Option<u16>) -> Self {
-        Self { start, limit }
+    pub fn new(start: Option<u64>, limit: Option<u16>, limits: PageSizeLimits) -> Self {
+        Self {
+            start,
+            limit,
+            limits,
+        }
     }
 
     pub fn start<E: BadRequestError>(&self, default: u64, max: u64) -> Result<u64, E> {
@@ -30,22 +69,77 @@ impl Page {
         Ok(start)
     }
 
+    /// Returns the number of items to return in this page. This is the
+    /// limit given by the client clamped to the max page size, so callers
+    /// should report it back to the client via the page size header.
     pub fn limit<E: BadRequestError>(&self) -> Result<u16, E> {
-        let limit = self.limit.unwrap_or(DEFAULT_PAGE_SIZE);
-        if limit == 0 {
-            return Err(E::bad_request_str(&format!(
-                "Given limit value ({}) must not be zero",
-                limit
-            ))
-            .error_code(AptosErrorCode::InvalidLimitParam));
-        }
-        if limit > MAX_PAGE_SIZE {
-            return Err(E::bad_request_str(&format!(
-                "Given limit value ({}) is too large, it must be < {}",
-                limit, MAX_PAGE_SIZE
-            ))
-            .error_code(AptosErrorCode::InvalidLimitParam));
+        if self.limit == Some(0) {
+            return Err(E::bad_request_str("Given limit value (0) must not be zero")
+                .error_code(AptosErrorCode::InvalidLimitParam));
         }
-        Ok(limit)
+        Ok(self.limits.effective_page_size(self.limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poem_backend::BasicError;
+
+    #[test]
+    fn test_default_page_size_is_used_without_limit() {
+        let limits = PageSizeLimits::new(25, 1000);
+        let page = Page::new(None, None, limits);
+        assert_eq!(page.limit::<BasicError>().unwrap(), 25);
+    }
+
+    #[test]
+    fn test_limit_is_clamped_to_max_page_size() {
+        let limits = PageSizeLimits::new(25, 100);
+        assert_eq!(
+            Page::new(None, Some(100), limits)
+                .limit::<BasicError>()
+                .unwrap(),
+            100
+        );
+        assert_eq!(
+            Page::new(None, Some(101), limits)
+                .limit::<BasicError>()
+                .unwrap(),
+            100
+        );
+        assert_eq!(
+            Page::new(None, Some(u16::MAX), limits)
+                .limit::<BasicError>()
+                .unwrap(),
+            100
+        );
+    }
+
+    #[test]
+    fn test_zero_limit_is_rejected() {
+        let limits = PageSizeLimits::new(25, 100);
+        assert!(Page::new(None, Some(0), limits)
+            .limit::<BasicError>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_default_page_size_never_exceeds_max() {
+        let limits = PageSizeLimits::new(50, 10);
+        assert_eq!(limits.effective_page_size(None), 10);
+        assert_eq!(limits.max_page_size(), 10);
+
+        let limits = PageSizeLimits::new(0, 0);
+        assert_eq!(limits.effective_page_size(None), 1);
+    }
+
+    #[test]
+    fn test_start_is_bounded() {
+        let page = Page::new(Some(11), None, PageSizeLimits::new(25, 100));
+        assert_eq!(page.start::<BasicError>(0, 11).unwrap(), 11);
+        assert!(page.start::<BasicError>(0, 10).is_err());
+        let page = Page::new(None, None, PageSizeLimits::new(25, 100));
+        assert_eq!(page.start::<BasicError>(5, 10).unwrap(), 5);
     }
 }
@@ -0,0 +1,353 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use super::{ApiTags, ConcurrencyLimit};
+use crate::context::Context;
+use aptos_api_types::{FailedReadinessCheck, Readiness, ReadinessCheck};
+use poem_openapi::{payload::Json, ApiResponse, OpenApi};
+use state_sync_driver::sync_status::{DriverState, SyncStatus};
+use tokio::sync::Notify;
+
+/// Lets whoever owns the API shut it down gracefully. Once a shutdown starts
+/// the readiness probe fails, but the server keeps accepting connections
+/// for the drain delay, so load balancers have time to notice and stop
+/// sending traffic.
+#[derive(Clone, Debug)]
+pub struct ShutdownHandle {
+    drain_delay: Duration,
+    shutting_down: Arc<AtomicBool>,
+    stop: Arc<Notify>,
+}
+
+impl ShutdownHandle {
+    pub fn new(drain_delay: Duration) -> Self {
+        Self {
+            drain_delay,
+            shutting_down: Arc::new(AtomicBool::new(false)),
+            stop: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::Relaxed)
+    }
+
+    /// Starts a graceful shutdown and returns once the server has been told
+    /// to stop accepting connections, i.e. after the drain delay. Requests
+    /// in flight at that point still get some time to finish.
+    pub async fn shutdown(&self) {
+        if self.shutting_down.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        tokio::time::sleep(self.drain_delay).await;
+        self.stop.notify_one();
+    }
+
+    /// Resolves once the server should stop accepting connections.
+    pub(crate) async fn stopped(&self) {
+        self.stop.notified().await
+    }
+}
+
+pub struct ReadinessApi {
+    pub context: Arc<Context>,
+    pub concurrency_limit: ConcurrencyLimit,
+}
+
+#[derive(ApiResponse)]
+pub enum ReadinessResponse {
+    /// The node is ready to receive traffic.
+    #[oai(status = 200)]
+    Ready(Json<Readiness>),
+
+    /// The node isn't ready to receive traffic. The body lists the checks
+    /// that failed.
+    #[oai(status = 503)]
+    NotReady(Json<Readiness>),
+}
+
+#[OpenApi]
+impl ReadinessApi {
+    /// Check readiness
+    ///
+    /// Check whether the node is ready to receive traffic, i.e. storage is
+    /// responsive, state sync has bootstrapped, the latest ledger info is
+    /// recent, and the API is neither shedding load nor shutting down. This
+    /// differs from "/-/healthy", which only says the process is alive.
+    #[oai(
+        path = "/-/ready",
+        method = "get",
+        operation_id = "get_readiness",
+        tag = "ApiTags::Operations"
+    )]
+    async fn get_readiness(&self) -> ReadinessResponse {
+        let readiness = check_readiness(
+            self.latest_ledger_timestamp_usecs().await,
+            self.context.sync_status_handle().get_status(),
+            self.concurrency_limit.is_shedding(),
+            self.context.shutdown_handle().is_shutting_down(),
+            self.context.readiness_max_ledger_staleness(),
+            now_usecs(),
+        );
+        if readiness.ready {
+            ReadinessResponse::Ready(Json(readiness))
+        } else {
+            ReadinessResponse::NotReady(Json(readiness))
+        }
+    }
+}
+
+impl ReadinessApi {
+    /// Reads the timestamp of the latest ledger info, giving up if storage
+    /// takes too long to answer.
+    async fn latest_ledger_timestamp_usecs(&self) -> Result<u64, String> {
+        let context = self.context.clone();
+        let timeout = self.context.readiness_storage_timeout();
+        let read =
+            tokio::task::spawn_blocking(move || context.get_latest_ledger_info_with_signatures());
+        match tokio::time::timeout(timeout, read).await {
+            Ok(Ok(Ok(ledger_info))) => Ok(ledger_info.ledger_info().timestamp_usecs()),
+            Ok(Ok(Err(err))) => Err(format!(
+                "Failed to read the latest ledger info from storage: {}",
+                err
+            )),
+            Ok(Err(err)) => Err(format!(
+                "Failed to read the latest ledger info from storage: {}",
+                err
+            )),
+            Err(_) => Err(format!(
+                "Storage didn't return the latest ledger info within {}ms",
+                timeout.as_millis()
+            )),
+        }
+    }
+}
+
+/// Runs every readiness check. The staleness of the ledger can only be
+/// checked if storage answered.
+fn check_readiness(
+    latest_ledger_timestamp_usecs: Result<u64, String>,
+    sync_status: Option<SyncStatus>,
+    is_shedding: bool,
+    is_shutting_down: bool,
+    max_ledger_staleness: Duration,
+    now_usecs: u64,
+) -> Readiness {
+    let mut failed_checks = vec![];
+    let mut fail =
+        |check, message: String| failed_checks.push(FailedReadinessCheck { check, message });
+
+    match latest_ledger_timestamp_usecs {
+        Ok(timestamp_usecs) => {
+            let staleness = Duration::from_micros(now_usecs.saturating_sub(timestamp_usecs));
+            if staleness > max_ledger_staleness {
+                fail(
+                    ReadinessCheck::LedgerStaleness,
+                    format!(
+                        "The latest ledger info is {}s old, more than the allowed {}s",
+                        staleness.as_secs(),
+                        max_ledger_staleness.as_secs()
+                    ),
+                );
+            }
+        }
+        Err(message) => fail(ReadinessCheck::Storage, message),
+    }
+
+    match sync_status.map(|status| status.driver_state) {
+        None => fail(
+            ReadinessCheck::StateSync,
+            "State sync hasn't reported its status yet".to_string(),
+        ),
+        Some(DriverState::Bootstrapping) => fail(
+            ReadinessCheck::StateSync,
+            "State sync is still bootstrapping".to_string(),
+        ),
+        Some(DriverState::CatchingUp) | Some(DriverState::CaughtUp) => (),
+    }
+
+    if is_shedding {
+        fail(
+            ReadinessCheck::LoadShedding,
+            "The API is shedding load".to_string(),
+        );
+    }
+    if is_shutting_down {
+        fail(
+            ReadinessCheck::Shutdown,
+            "The API is shutting down".to_string(),
+        );
+    }
+
+    Readiness {
+        ready: failed_checks.is_empty(),
+        failed_checks,
+    }
+}
+
+fn now_usecs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_micros() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::new_test_context_with_config;
+    use aptos_config::config::{ApiConfig, NodeConfig};
+    use poem::{http::StatusCode, Endpoint, IntoEndpoint, Request};
+    use poem_openapi::OpenApiService;
+    use serde_json::Value;
+
+    const SECOND_USECS: u64 = 1_000_000;
+    const MAX_STALENESS: Duration = Duration::from_secs(60);
+    const NOW_USECS: u64 = 1_000 * SECOND_USECS;
+
+    fn sync_status(driver_state: DriverState) -> Option<SyncStatus> {
+        Some(SyncStatus {
+            driver_state,
+            synced_version: 100,
+            synced_epoch: 2,
+            synced_timestamp_usecs: NOW_USECS,
+            highest_advertised_version: Some(100),
+            snapshot_sync_progress_percent: None,
+        })
+    }
+
+    fn failed_checks(readiness: &Readiness) -> Vec<ReadinessCheck> {
+        readiness
+            .failed_checks
+            .iter()
+            .map(|failed| failed.check)
+            .collect()
+    }
+
+    #[test]
+    fn test_ready_when_every_check_passes() {
+        for driver_state in [DriverState::CatchingUp, DriverState::CaughtUp].iter() {
+            let readiness = check_readiness(
+                Ok(NOW_USECS - SECOND_USECS),
+                sync_status(*driver_state),
+                false,
+                false,
+                MAX_STALENESS,
+                NOW_USECS,
+            );
+            assert!(readiness.ready);
+            assert!(readiness.failed_checks.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_each_check_fails_independently() {
+        let check = |timestamp: Result<u64, String>, sync_status, is_shedding, is_shutting_down| {
+            check_readiness(
+                timestamp,
+                sync_status,
+                is_shedding,
+                is_shutting_down,
+                MAX_STALENESS,
+                NOW_USECS,
+            )
+        };
+        let caught_up = || sync_status(DriverState::CaughtUp);
+
+        let readiness = check(
+            Err("storage is down".to_string()),
+            caught_up(),
+            false,
+            false,
+        );
+        assert!(!readiness.ready);
+        assert_eq!(failed_checks(&readiness), vec![ReadinessCheck::Storage]);
+        assert_eq!(readiness.failed_checks[0].message, "storage is down");
+
+        let readiness = check(Ok(NOW_USECS), None, false, false);
+        assert_eq!(failed_checks(&readiness), vec![ReadinessCheck::StateSync]);
+        let readiness = check(
+            Ok(NOW_USECS),
+            sync_status(DriverState::Bootstrapping),
+            false,
+            false,
+        );
+        assert_eq!(failed_checks(&readiness), vec![ReadinessCheck::StateSync]);
+
+        let readiness = check(Ok(NOW_USECS - 61 * SECOND_USECS), caught_up(), false, false);
+        assert_eq!(
+            failed_checks(&readiness),
+            vec![ReadinessCheck::LedgerStaleness]
+        );
+        assert_eq!(
+            readiness.failed_checks[0].message,
+            "The latest ledger info is 61s old, more than the allowed 60s"
+        );
+
+        let readiness = check(Ok(NOW_USECS), caught_up(), true, false);
+        assert_eq!(
+            failed_checks(&readiness),
+            vec![ReadinessCheck::LoadShedding]
+        );
+
+        let readiness = check(Ok(NOW_USECS), caught_up(), false, true);
+        assert_eq!(failed_checks(&readiness), vec![ReadinessCheck::Shutdown]);
+    }
+
+    #[tokio::test]
+    async fn test_get_readiness() {
+        let mut config = NodeConfig::default();
+        // The test ledger's timestamps are close to the epoch.
+        config.api.readiness_max_ledger_staleness_secs = u64::MAX;
+        config.api.shutdown_drain_delay_ms = 100;
+        let context = new_test_context_with_config("test_get_readiness", config);
+        let context = Arc::new(context.context.clone());
+        let ep = OpenApiService::new(
+            ReadinessApi {
+                context: context.clone(),
+                concurrency_limit: ConcurrencyLimit::from_config(&ApiConfig::default()),
+            },
+            "test",
+            "0.1.0",
+        )
+        .into_endpoint();
+        let get_readiness = || async {
+            let mut resp = ep
+                .get_response(Request::builder().uri_str("/-/ready").finish())
+                .await;
+            let body: Value =
+                serde_json::from_slice(&resp.take_body().into_vec().await.unwrap()).unwrap();
+            (resp.status(), body)
+        };
+
+        // State sync hasn't reported anything yet.
+        let (status, body) = get_readiness().await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["ready"], false);
+        assert_eq!(body["failed_checks"][0]["check"], "state_sync");
+
+        let status = sync_status(DriverState::CaughtUp).unwrap();
+        context.sync_status_handle().update_status(status);
+        let (status, body) = get_readiness().await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["failed_checks"], Value::Array(vec![]));
+
+        let shutdown_handle = context.shutdown_handle().clone();
+        let shutdown = tokio::spawn(async move { shutdown_handle.shutdown().await });
+        while !context.shutdown_handle().is_shutting_down() {
+            tokio::task::yield_now().await;
+        }
+        let (status, body) = get_readiness().await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["failed_checks"][0]["check"], "shutdown");
+        shutdown.await.unwrap();
+    }
+}
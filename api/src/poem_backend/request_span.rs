@@ -0,0 +1,372 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{collections::BTreeMap, sync::Arc, time::Instant};
+
+use super::RouteTemplates;
+use crate::metrics::HISTOGRAM;
+use anyhow::Context as AnyhowContext;
+use aptos_api_types::{Address, HashValue};
+use aptos_config::config::ApiConfig;
+use opentelemetry::{sdk::trace, sdk::Resource, KeyValue};
+use poem::{http::HeaderValue, Endpoint, Middleware, Request, Response, Result};
+use tracing::{field, info_span, instrument::WithSubscriber, Dispatch, Instrument, Span};
+use tracing_subscriber::layer::SubscriberExt;
+
+/// The header clients can use to give their own request id. It is echoed
+/// back in the response either way.
+const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Request ids given by the client longer than this are replaced.
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+/// The route (and operation id) used for requests that don't match any
+/// route in the spec, so unknown paths don't blow up metric cardinality.
+const UNKNOWN_ROUTE: &str = "unknown";
+
+/// Middleware that wraps (a configurable sample of) requests in a tracing
+/// span carrying the route template and a request id. Handlers add what they
+/// are querying to the span via `RequestSpanExt`. Regardless of sampling,
+/// how long each request took is recorded in the per operation latency
+/// histogram.
+#[derive(Clone)]
+pub struct RequestTracing {
+    routes: Arc<RouteTemplates>,
+    sample_rate: f64,
+    route_sample_rates: Arc<BTreeMap<String, f64>>,
+    // If set, spans are sent here rather than to the global subscriber.
+    dispatch: Option<Dispatch>,
+}
+
+impl RequestTracing {
+    pub fn new(
+        routes: RouteTemplates,
+        sample_rate: f64,
+        route_sample_rates: BTreeMap<String, f64>,
+        dispatch: Option<Dispatch>,
+    ) -> Self {
+        Self {
+            routes: Arc::new(routes),
+            sample_rate,
+            route_sample_rates: Arc::new(route_sample_rates),
+            dispatch,
+        }
+    }
+
+    /// Builds the middleware for the API with the given routes. If an
+    /// OTLP endpoint is configured this must be called within a Tokio
+    /// runtime, since the exporter runs on it.
+    pub fn from_config(config: &ApiConfig, routes: RouteTemplates) -> anyhow::Result<Self> {
+        let dispatch = match &config.tracing_otlp_endpoint {
+            Some(endpoint) => Some(otlp_dispatch(endpoint)?),
+            None => None,
+        };
+        Ok(Self::new(
+            routes,
+            config.tracing_sample_rate,
+            config.tracing_route_sample_rates.clone(),
+            dispatch,
+        ))
+    }
+
+    fn is_sampled(&self, template: &str) -> bool {
+        let sample_rate = self
+            .route_sample_rates
+            .get(template)
+            .copied()
+            .unwrap_or(self.sample_rate);
+        sample_rate >= 1.0 || (sample_rate > 0.0 && rand::random::<f64>() < sample_rate)
+    }
+}
+
+fn otlp_dispatch(endpoint: &str) -> anyhow::Result<Dispatch> {
+    use opentelemetry_otlp::WithExportConfig;
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(
+            trace::config().with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                "aptos-api",
+            )])),
+        )
+        .install_batch(opentelemetry::runtime::Tokio)
+        .context("Failed to start the OTLP span exporter")?;
+
+    Ok(Dispatch::new(
+        tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer)),
+    ))
+}
+
+impl<E: Endpoint> Middleware<E> for RequestTracing {
+    type Output = RequestTracingEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        RequestTracingEndpoint {
+            inner: ep,
+            tracing: self.clone(),
+        }
+    }
+}
+
+pub struct RequestTracingEndpoint<E> {
+    inner: E,
+    tracing: RequestTracing,
+}
+
+#[poem::async_trait]
+impl<E: Endpoint> Endpoint for RequestTracingEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, request: Request) -> Result<Self::Output> {
+        let start = Instant::now();
+        let method = request.method().clone();
+        let (template, operation_id) = match self.tracing.routes.find(&method, request.uri().path())
+        {
+            Some(route) => (route.template.as_str(), route.operation_id.as_str()),
+            None => (UNKNOWN_ROUTE, UNKNOWN_ROUTE),
+        };
+        let request_id = request_id(&request);
+
+        let mut response = if self.tracing.is_sampled(template) {
+            let new_span = || {
+                info_span!(
+                    "api_request",
+                    route = template,
+                    method = method.as_str(),
+                    request_id = request_id.as_str(),
+                    status = field::Empty,
+                    account_address = field::Empty,
+                    txn_hash = field::Empty,
+                    version = field::Empty,
+                )
+            };
+            let span = match &self.tracing.dispatch {
+                Some(dispatch) => tracing::dispatcher::with_default(dispatch, new_span),
+                None => new_span(),
+            };
+            let response = self.inner.get_response(request).instrument(span.clone());
+            let response = match &self.tracing.dispatch {
+                Some(dispatch) => response.with_subscriber(dispatch.clone()).await,
+                None => response.await,
+            };
+            span.record("status", &response.status().as_u16());
+            response
+        } else {
+            self.inner.get_response(request).await
+        };
+
+        HISTOGRAM
+            .with_label_values(&[method.as_str(), operation_id, response.status().as_str()])
+            .observe(start.elapsed().as_secs_f64());
+
+        if let Ok(value) = HeaderValue::from_str(&request_id) {
+            response.headers_mut().insert(REQUEST_ID_HEADER, value);
+        }
+        Ok(response)
+    }
+}
+
+/// Returns the request id given by the client, or a new random one if the
+/// client didn't give a usable one.
+fn request_id(request: &Request) -> String {
+    request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("{:016x}", rand::random::<u64>()))
+}
+
+/// Lets handlers add what they are querying to the current request span,
+/// e.g. `Span::current().record_address(&address)`. Fields that don't apply
+/// to a request are left empty. If the request wasn't sampled these do
+/// nothing.
+pub trait RequestSpanExt {
+    fn record_address(&self, address: &Address);
+
+    fn record_txn_hash(&self, hash: &HashValue);
+
+    fn record_version(&self, version: u64);
+}
+
+impl RequestSpanExt for Span {
+    fn record_address(&self, address: &Address) {
+        self.record("account_address", &field::display(address));
+    }
+
+    fn record_txn_hash(&self, hash: &HashValue) {
+        self.record("txn_hash", &field::display(hash));
+    }
+
+    fn record_version(&self, version: u64) {
+        self.record("version", &version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        poem_backend::{AccountsApi, TransactionsApi},
+        tests::new_test_context,
+    };
+    use poem::{EndpointExt, IntoEndpoint};
+    use poem_openapi::OpenApiService;
+    use std::{collections::HashMap, sync::Mutex};
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Subscriber,
+    };
+    use tracing_subscriber::{layer::Context as LayerContext, Layer};
+
+    type SpanFields = HashMap<String, String>;
+
+    /// Captures the fields of every span, in order of creation.
+    #[derive(Clone, Default)]
+    struct CaptureSpans {
+        spans: Arc<Mutex<Vec<(Id, SpanFields)>>>,
+    }
+
+    impl CaptureSpans {
+        fn fields(&self) -> Vec<SpanFields> {
+            self.spans
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(_, fields)| fields.clone())
+                .collect()
+        }
+    }
+
+    struct FieldVisitor<'a>(&'a mut SpanFields);
+
+    impl<'a> Visit for FieldVisitor<'a> {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for CaptureSpans {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _ctx: LayerContext<'_, S>) {
+            let mut fields = SpanFields::new();
+            attrs.record(&mut FieldVisitor(&mut fields));
+            self.spans.lock().unwrap().push((id.clone(), fields));
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: LayerContext<'_, S>) {
+            let mut spans = self.spans.lock().unwrap();
+            if let Some((_, fields)) = spans.iter_mut().rev().find(|(span_id, _)| span_id == id) {
+                values.record(&mut FieldVisitor(fields));
+            }
+        }
+    }
+
+    async fn call_traced(
+        name: &str,
+        route_sample_rates: BTreeMap<String, f64>,
+        request: Request,
+    ) -> (Response, Vec<SpanFields>) {
+        let context = Arc::new(new_test_context(name).context.clone());
+        let service = OpenApiService::new(
+            (
+                AccountsApi {
+                    context: context.clone(),
+                },
+                TransactionsApi { context },
+            ),
+            "test",
+            "0.1.0",
+        );
+        let routes = RouteTemplates::from_spec(&service.spec()).unwrap();
+        let ep = service.into_endpoint().with(RequestTracing::new(
+            routes,
+            1.0,
+            route_sample_rates,
+            None,
+        ));
+
+        let capture = CaptureSpans::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(capture.clone()));
+        let response = ep.get_response(request).await;
+        (response, capture.fields())
+    }
+
+    #[tokio::test]
+    async fn test_get_account_span() {
+        let (response, spans) = call_traced(
+            "test_get_account_span",
+            BTreeMap::new(),
+            Request::builder()
+                .uri_str("/accounts/0x1?ledger_version=0")
+                .finish(),
+        )
+        .await;
+        assert!(response.status().is_success());
+
+        let span = &spans[0];
+        assert_eq!(span["route"], "/accounts/{address}");
+        assert_eq!(span["method"], "GET");
+        assert_eq!(span["status"], "200");
+        assert_eq!(span["version"], "0");
+        assert_eq!(
+            span["account_address"],
+            "0x0000000000000000000000000000000000000000000000000000000000000001"
+        );
+        assert_eq!(
+            response.headers()[REQUEST_ID_HEADER].to_str().unwrap(),
+            span["request_id"]
+        );
+        assert!(!span.contains_key("txn_hash"));
+    }
+
+    #[tokio::test]
+    async fn test_get_transactions_span() {
+        let (response, spans) = call_traced(
+            "test_get_transactions_span",
+            BTreeMap::new(),
+            Request::builder()
+                .uri_str("/transactions?start=0")
+                .header(REQUEST_ID_HEADER, "my-request")
+                .finish(),
+        )
+        .await;
+        assert!(response.status().is_success());
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "my-request");
+
+        let span = &spans[0];
+        assert_eq!(span["route"], "/transactions");
+        assert_eq!(span["request_id"], "my-request");
+        assert_eq!(span["version"], "0");
+        assert!(!span.contains_key("account_address"));
+    }
+
+    #[tokio::test]
+    async fn test_unsampled_route_has_no_span() {
+        let (response, spans) = call_traced(
+            "test_unsampled_route_has_no_span",
+            vec![("/transactions".to_string(), 0.0)]
+                .into_iter()
+                .collect(),
+            Request::builder().uri_str("/transactions").finish(),
+        )
+        .await;
+        assert!(response.status().is_success());
+        // The request id is still returned.
+        assert!(response.headers().contains_key(REQUEST_ID_HEADER));
+        assert!(spans.is_empty());
+    }
+}
@@ -0,0 +1,363 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use super::{route_templates::RouteTemplate, AptosError, AptosErrorCode, InternalError};
+use super::{AptosErrorResponse, RouteTemplates};
+use crate::metrics::REQUESTS_TIMED_OUT;
+use aptos_config::config::{ApiConfig, RouteGroup};
+use poem::{
+    http::{Method, StatusCode},
+    Endpoint, FromRequest, IntoResponse, Middleware, Request, RequestBody, Response, Result,
+};
+use poem_openapi::payload::Json;
+
+/// Returns the timeout budget group a route belongs to.
+fn route_group(route: &RouteTemplate) -> RouteGroup {
+    let template = route.template.as_str();
+    if route.method == Method::POST {
+        if template.ends_with("/simulate") {
+            RouteGroup::Simulation
+        } else if template.starts_with("/transactions") {
+            RouteGroup::Submission
+        } else {
+            RouteGroup::Reads
+        }
+    } else if template == "/transactions"
+        || template.ends_with("/transactions")
+        || template.ends_with("/resources")
+        || template.ends_with("/modules")
+        || template.contains("/events/")
+    {
+        RouteGroup::Listings
+    } else {
+        RouteGroup::Reads
+    }
+}
+
+/// Middleware that bounds how long API requests may take, with a separate
+/// budget per route group. Requests that take longer get a 408 naming the
+/// budget they exceeded, even if the handler finished after the deadline.
+///
+/// Handlers doing a lot of work, e.g. iterating over storage, should take a
+/// `Cancellation` and stop once it is cancelled, since a handler blocked on
+/// storage can't be interrupted by the middleware. Paths that aren't API
+/// routes are passed through untouched.
+#[derive(Clone)]
+pub struct RequestTimeouts {
+    routes: Arc<RouteTemplates>,
+    budgets: Arc<BTreeMap<RouteGroup, Duration>>,
+}
+
+impl RequestTimeouts {
+    pub fn new(routes: RouteTemplates, budgets: BTreeMap<RouteGroup, Duration>) -> Self {
+        Self {
+            routes: Arc::new(routes),
+            budgets: Arc::new(budgets),
+        }
+    }
+
+    pub fn from_config(config: &ApiConfig, routes: RouteTemplates) -> Self {
+        Self::new(
+            routes,
+            RouteGroup::ALL
+                .iter()
+                .map(|group| (*group, config.request_timeout(*group)))
+                .collect(),
+        )
+    }
+
+    fn budget(&self, group: RouteGroup) -> Duration {
+        self.budgets
+            .get(&group)
+            .copied()
+            .unwrap_or_else(|| Duration::from_millis(group.default_timeout_ms()))
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for RequestTimeouts {
+    type Output = RequestTimeoutsEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        RequestTimeoutsEndpoint {
+            inner: ep,
+            timeouts: self.clone(),
+        }
+    }
+}
+
+pub struct RequestTimeoutsEndpoint<E> {
+    inner: E,
+    timeouts: RequestTimeouts,
+}
+
+#[poem::async_trait]
+impl<E: Endpoint> Endpoint for RequestTimeoutsEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, mut request: Request) -> Result<Self::Output> {
+        let group = match self
+            .timeouts
+            .routes
+            .find(request.method(), request.uri().path())
+        {
+            Some(route) => route_group(route),
+            None => return Ok(self.inner.call(request).await?.into_response()),
+        };
+        let budget = self.timeouts.budget(group);
+
+        let cancellation = Cancellation::with_deadline(Instant::now() + budget);
+        // If the client goes away the server drops this future, which
+        // cancels the handler too.
+        let _cancel_on_drop = CancelOnDrop(cancellation.clone());
+        request.extensions_mut().insert(cancellation.clone());
+
+        match tokio::time::timeout(budget, self.inner.call(request)).await {
+            Ok(result) if !cancellation.is_cancelled() => Ok(result?.into_response()),
+            _ => {
+                REQUESTS_TIMED_OUT
+                    .with_label_values(&[group.as_str()])
+                    .inc();
+                Ok(request_timeout(group, budget))
+            }
+        }
+    }
+}
+
+fn request_timeout(group: RouteGroup, budget: Duration) -> Response {
+    Json(
+        AptosError::new(format!(
+            "The request exceeded the {} timeout budget of {}ms",
+            group.as_str(),
+            budget.as_millis()
+        ))
+        .error_code(AptosErrorCode::RequestTimedOut),
+    )
+    .with_status(StatusCode::REQUEST_TIMEOUT)
+    .into_response()
+}
+
+/// A cooperative cancellation signal for a request. It is cancelled once the
+/// request's timeout budget runs out or the client goes away. Handlers get
+/// it as an extractor; if the timeout middleware isn't in use it is never
+/// cancelled.
+#[derive(Clone, Debug, Default)]
+pub struct Cancellation {
+    state: Option<Arc<CancellationState>>,
+}
+
+#[derive(Debug)]
+struct CancellationState {
+    deadline: Instant,
+    cancelled: AtomicBool,
+}
+
+impl Cancellation {
+    fn with_deadline(deadline: Instant) -> Self {
+        Self {
+            state: Some(Arc::new(CancellationState {
+                deadline,
+                cancelled: AtomicBool::new(false),
+            })),
+        }
+    }
+
+    fn cancel(&self) {
+        if let Some(state) = &self.state {
+            state.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        match &self.state {
+            Some(state) => {
+                state.cancelled.load(Ordering::Relaxed) || Instant::now() >= state.deadline
+            }
+            None => false,
+        }
+    }
+
+    /// Returns an error if the request has been cancelled, so handlers can
+    /// bail out with `?`. The client never sees this error, since the
+    /// middleware responds with a 408 instead.
+    pub fn check<E: InternalError>(&self) -> Result<(), E> {
+        if self.is_cancelled() {
+            return Err(E::internal_str("The request was cancelled")
+                .error_code(AptosErrorCode::RequestTimedOut));
+        }
+        Ok(())
+    }
+}
+
+#[poem::async_trait]
+impl<'a> FromRequest<'a> for Cancellation {
+    async fn from_request(request: &'a Request, _body: &mut RequestBody) -> Result<Self> {
+        Ok(request
+            .extensions()
+            .get::<Cancellation>()
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+struct CancelOnDrop(Cancellation);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poem_backend::BasicError;
+    use poem::{handler, post, web::Query, EndpointExt, Route};
+    use serde::Deserialize;
+    use serde_json::Value;
+
+    #[derive(Deserialize)]
+    struct Work {
+        // How long the handler takes if it isn't cancelled.
+        ms: u64,
+    }
+
+    /// A handler that works in 10ms steps for as long as it is asked to.
+    /// Like a storage iterator, it blocks between checks.
+    #[handler]
+    fn slow(Query(work): Query<Work>, cancellation: Cancellation) -> Result<String> {
+        let start = Instant::now();
+        let mut steps = 0;
+        while start.elapsed() < Duration::from_millis(work.ms) {
+            cancellation.check::<BasicError>()?;
+            std::thread::sleep(Duration::from_millis(10));
+            steps += 1;
+        }
+        Ok(steps.to_string())
+    }
+
+    fn routes() -> RouteTemplates {
+        RouteTemplates::from_spec(
+            &serde_json::json!({
+                "paths": {
+                    "/accounts/{address}": {"get": {"operationId": "get_account"}},
+                    "/accounts/{address}/resources": {"get": {"operationId": "get_account_resources"}},
+                    "/transactions": {"post": {"operationId": "submit_transaction"}},
+                    "/transactions/simulate": {"post": {"operationId": "simulate_transaction"}},
+                }
+            })
+            .to_string(),
+        )
+        .unwrap()
+    }
+
+    fn build_endpoint() -> impl Endpoint<Output = Response> {
+        let budgets = vec![
+            (RouteGroup::Reads, Duration::from_millis(100)),
+            (RouteGroup::Listings, Duration::from_millis(200)),
+            (RouteGroup::Submission, Duration::from_millis(300)),
+            (RouteGroup::Simulation, Duration::from_millis(400)),
+        ];
+        Route::new()
+            .at("/accounts/:address", slow)
+            .at("/accounts/:address/resources", slow)
+            .at("/transactions", post(slow))
+            .at("/transactions/simulate", post(slow))
+            .at("/unknown", slow)
+            .with(RequestTimeouts::new(
+                routes(),
+                budgets.into_iter().collect(),
+            ))
+    }
+
+    async fn call(
+        ep: &impl Endpoint<Output = Response>,
+        method: Method,
+        path: &str,
+        ms: u64,
+    ) -> (StatusCode, Value) {
+        let mut resp = ep
+            .get_response(
+                Request::builder()
+                    .method(method)
+                    .uri_str(&format!("{}?ms={}", path, ms))
+                    .finish(),
+            )
+            .await;
+        let body = resp.take_body().into_vec().await.unwrap();
+        (
+            resp.status(),
+            serde_json::from_slice(&body).unwrap_or(Value::Null),
+        )
+    }
+
+    #[test]
+    fn test_route_groups() {
+        let routes = routes();
+        let group = |method: Method, path: &str| route_group(routes.find(&method, path).unwrap());
+        assert_eq!(group(Method::GET, "/accounts/0x1"), RouteGroup::Reads);
+        assert_eq!(
+            group(Method::GET, "/accounts/0x1/resources"),
+            RouteGroup::Listings
+        );
+        assert_eq!(group(Method::POST, "/transactions"), RouteGroup::Submission);
+        assert_eq!(
+            group(Method::POST, "/transactions/simulate"),
+            RouteGroup::Simulation
+        );
+    }
+
+    #[tokio::test]
+    async fn test_each_group_has_its_own_budget() {
+        let ep = build_endpoint();
+        let groups = [
+            (Method::GET, "/accounts/0x1", "reads", 100),
+            (Method::GET, "/accounts/0x1/resources", "listings", 200),
+            (Method::POST, "/transactions", "submission", 300),
+            (Method::POST, "/transactions/simulate", "simulation", 400),
+        ];
+        for (method, path, group, budget_ms) in groups.iter().cloned() {
+            // Within the budget.
+            let (status, _) = call(&ep, method.clone(), path, budget_ms - 50).await;
+            assert_eq!(status, StatusCode::OK, "{} {}", method, path);
+
+            // Over the budget.
+            let (status, body) = call(&ep, method.clone(), path, budget_ms + 50).await;
+            assert_eq!(status, StatusCode::REQUEST_TIMEOUT, "{} {}", method, path);
+            assert_eq!(
+                body["message"],
+                format!(
+                    "The request exceeded the {} timeout budget of {}ms",
+                    group, budget_ms
+                )
+            );
+            assert_eq!(body["error_code"], "RequestTimedOut");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cooperative_handlers_stop_early() {
+        let ep = build_endpoint();
+        let start = Instant::now();
+        let (status, _) = call(&ep, Method::GET, "/accounts/0x1", 5_000).await;
+        assert_eq!(status, StatusCode::REQUEST_TIMEOUT);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_non_api_paths_have_no_budget() {
+        let ep = build_endpoint();
+        let resp = ep
+            .get_response(Request::builder().uri_str("/unknown?ms=200").finish())
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
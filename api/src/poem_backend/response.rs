@@ -27,8 +27,6 @@
 //! returned by each function and its callers is enforced at compile time.
 //! See generate_error_traits and its invocations for more on this topic.
 
-// TODO: Pending further discussion with the team, migrate back to U64 over u64.
-
 use std::fmt::Display;
 
 use super::accept_type::AcceptType;
@@ -94,6 +92,48 @@ pub enum AptosErrorCode {
 
     /// The limit param given for paging is invalid.
     InvalidLimitParam = 5,
+
+    /// The requested ledger version is older than the oldest version whose
+    /// state is still available, i.e. it has been pruned.
+    LedgerVersionPruned = 6,
+
+    /// The given logger filter could not be parsed.
+    InvalidLogFilter = 7,
+
+    /// The request took longer than the timeout budget of its route group.
+    RequestTimedOut = 8,
+
+    /// The function given to POST "/view" (or its module) doesn't exist.
+    FunctionNotFound = 9,
+
+    /// The function given to POST "/view" isn't a view function.
+    NotAViewFunction = 10,
+
+    /// The (type) arguments given to a view function couldn't be encoded
+    /// for the function.
+    InvalidViewArguments = 11,
+
+    /// The view function aborted.
+    MoveAbort = 12,
+
+    /// The view function failed for a reason other than an abort, e.g. it
+    /// ran out of gas.
+    ViewFunctionFailed = 13,
+
+    /// The body of POST "/batch" isn't a valid batch, e.g. it has too many
+    /// requests.
+    InvalidBatchRequest = 14,
+
+    /// A request within a batch isn't one that may be batched, i.e. it
+    /// isn't read only.
+    NotBatchable = 15,
+
+    /// The on-chain config asked for isn't one the API knows about.
+    UnknownConfig = 16,
+
+    /// The response would be larger than the node renders, e.g. a huge
+    /// Move value as JSON. It may still be available as BCS.
+    ResponseTooLarge = 17,
 }
 
 #[derive(ResponseContent)]
@@ -247,6 +287,9 @@ macro_rules! generate_success_response {
                 #[oai(header = "X-Aptos-Ledger-Oldest-Version")] U64,
                 #[oai(header = "X-Aptos-Ledger-TimestampUsec")] U64,
                 #[oai(header = "X-Aptos-Epoch")] U64,
+                /// For paginated endpoints, the number of items the page was
+                /// limited to, after clamping to the node's max page size.
+                #[oai(header = "X-Aptos-Page-Size")] Option<u16>,
             ),
             )*
 
@@ -301,7 +344,8 @@ macro_rules! generate_success_response {
                             ledger_info.ledger_version,
                             ledger_info.oldest_ledger_version,
                             ledger_info.ledger_timestamp,
-                            ledger_info.epoch.into(),
+                            ledger_info.epoch,
+                            None,
                         )
                     },
                     )*
@@ -309,6 +353,19 @@ macro_rules! generate_success_response {
             }
         }
 
+        impl<T: poem_openapi::types::ToJSON + Send + Sync> $enum_name<T> {
+            // Set the page size header, for use by paginated endpoints.
+            pub fn with_page_size(mut self, page_size: u16) -> Self {
+                match &mut self {
+                    $(
+                    $enum_name::$name(_, _, _, _, _, _, header) => *header = Some(page_size),
+                    )*
+                    $enum_name::BadRequest(_) => {}
+                }
+                self
+            }
+        }
+
         // Generate a From impl that builds a response from a Json<T> and friends.
         impl<T: poem_openapi::types::ToJSON + Send + Sync> From<(poem_openapi::payload::Json<T>, &aptos_api_types::LedgerInfo, [<$enum_name Status>])>
             for $enum_name<T>
@@ -378,7 +435,9 @@ generate_success_response!(BasicResponse, (200, Ok));
 // The error response then impls these traits for each status type they mention.
 generate_error_traits!(
     BadRequest,
+    Forbidden,
     NotFound,
+    Gone,
     PayloadTooLarge,
     UnsupportedMediaType,
     Internal,
@@ -400,7 +459,39 @@ generate_error_response!(
 );
 pub type BasicResultWith404<T> = poem::Result<BasicResponse<T>, BasicErrorWith404>;
 
-#[allow(dead_code)]
+// As above but with 410, for endpoints that read state at a given version,
+// which may have been pruned.
+generate_error_response!(
+    BasicErrorWith404And410,
+    (400, BadRequest),
+    (404, NotFound),
+    (410, Gone),
+    (500, Internal)
+);
+pub type BasicResultWith404And410<T> = poem::Result<BasicResponse<T>, BasicErrorWith404And410>;
+
+// As above but with 413, for endpoints whose responses may be too large to
+// render.
+generate_error_response!(
+    BasicErrorWith404And410And413,
+    (400, BadRequest),
+    (404, NotFound),
+    (410, Gone),
+    (413, PayloadTooLarge),
+    (500, Internal)
+);
+pub type BasicResultWith404And410And413<T> =
+    poem::Result<BasicResponse<T>, BasicErrorWith404And410And413>;
+
+// As above but with 403, for endpoints that only some clients may use.
+generate_error_response!(
+    BasicErrorWith403,
+    (400, BadRequest),
+    (403, Forbidden),
+    (500, Internal)
+);
+pub type BasicResultWith403<T> = poem::Result<BasicResponse<T>, BasicErrorWith403>;
+
 // Just this one helper for a specific kind of 404.
 pub fn build_not_found<S: Display, E: NotFoundError>(
     resource: &str,
@@ -0,0 +1,151 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::Context as AnyhowContext;
+use poem::http::Method;
+use serde_json::Value;
+
+/// A route from the OpenAPI spec, e.g. "/accounts/{address}".
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteTemplate {
+    pub template: String,
+    pub method: Method,
+    pub operation_id: String,
+}
+
+impl RouteTemplate {
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        self.method == method && self.matches_path(path)
+    }
+
+    fn matches_path(&self, path: &str) -> bool {
+        let mut segments = path.split('/').filter(|s| !s.is_empty());
+        let mut template_segments = self.template.split('/').filter(|s| !s.is_empty());
+        loop {
+            match (template_segments.next(), segments.next()) {
+                (None, None) => return true,
+                (Some(template), Some(segment)) => {
+                    if !template.starts_with('{') && template != segment {
+                        return false;
+                    }
+                }
+                _ => return false,
+            }
+        }
+    }
+
+    fn param_count(&self) -> usize {
+        self.template.matches('{').count()
+    }
+}
+
+/// All of the routes in the OpenAPI spec, used to find the template a
+/// request path was routed by.
+#[derive(Clone, Debug, Default)]
+pub struct RouteTemplates {
+    // Sorted so that routes with fewer params, i.e. more literal segments,
+    // are tried first.
+    routes: Vec<RouteTemplate>,
+}
+
+impl RouteTemplates {
+    /// Builds the templates from the JSON OpenAPI spec.
+    pub fn from_spec(spec: &str) -> anyhow::Result<Self> {
+        let spec: Value = serde_json::from_str(spec).context("Failed to parse OpenAPI spec")?;
+        let paths = spec["paths"]
+            .as_object()
+            .context("OpenAPI spec has no paths")?;
+
+        let mut routes = vec![];
+        for (template, operations) in paths {
+            for (method, operation) in operations.as_object().into_iter().flatten() {
+                let method = Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                    .context("Invalid method in OpenAPI spec")?;
+                let operation_id = operation["operationId"]
+                    .as_str()
+                    .unwrap_or(template)
+                    .to_string();
+                routes.push(RouteTemplate {
+                    template: template.clone(),
+                    method,
+                    operation_id,
+                });
+            }
+        }
+        routes.sort_by_key(RouteTemplate::param_count);
+        Ok(Self { routes })
+    }
+
+    /// Returns the route a request with the given method and path is routed
+    /// by, if any.
+    pub fn find(&self, method: &Method, path: &str) -> Option<&RouteTemplate> {
+        self.routes.iter().find(|route| route.matches(method, path))
+    }
+
+    /// Returns the methods the route matching the given path supports, or
+    /// nothing if no route matches it.
+    pub fn allowed_methods(&self, path: &str) -> Vec<Method> {
+        let template = match self.routes.iter().find(|route| route.matches_path(path)) {
+            Some(route) => &route.template,
+            None => return vec![],
+        };
+        let mut methods: Vec<Method> = self
+            .routes
+            .iter()
+            .filter(|route| &route.template == template)
+            .map(|route| route.method.clone())
+            .collect();
+        methods.dedup();
+        methods
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_templates() {
+        let templates = RouteTemplates::from_spec(
+            &serde_json::json!({
+                "paths": {
+                    "/accounts/{address}": {"get": {"operationId": "get_account"}},
+                    "/log/filter": {
+                        "get": {"operationId": "get_log_filter"},
+                        "post": {"operationId": "set_log_filter"},
+                    },
+                    "/accounts/{address}/resources": {"get": {"operationId": "get_account_resources"}},
+                    "/accounts/{address}/{field}": {"get": {"operationId": "catch_all"}},
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let find = |method: Method, path: &str| {
+            templates
+                .find(&method, path)
+                .map(|route| route.operation_id.as_str())
+        };
+        assert_eq!(find(Method::GET, "/accounts/0x1"), Some("get_account"));
+        assert_eq!(
+            find(Method::GET, "/accounts/0x1/resources"),
+            Some("get_account_resources")
+        );
+        assert_eq!(
+            find(Method::GET, "/accounts/0x1/modules"),
+            Some("catch_all")
+        );
+        assert_eq!(find(Method::POST, "/accounts/0x1"), None);
+        assert_eq!(find(Method::GET, "/accounts"), None);
+
+        assert_eq!(
+            templates.allowed_methods("/accounts/0x1"),
+            vec![Method::GET]
+        );
+        assert!(templates.allowed_methods("/accounts").is_empty());
+        assert_eq!(
+            templates.allowed_methods("/log/filter"),
+            vec![Method::GET, Method::POST]
+        );
+    }
+}
@@ -1,31 +1,41 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
 
-use super::{middleware_log, AccountsApi, BasicApi, EventsApi, IndexApi};
+use super::{
+    middleware_log, AccountsApi, BasicApi, BatchEndpoint, CapabilitiesApi, ConcurrencyLimit,
+    ConfigsApi, EventsApi, IndexApi, LogFilterApi, MethodRouting, MetricsEndpoint, NodeInfoApi,
+    ReadinessApi, RequestTimeouts, RequestTracing, RouteTemplates,
+};
 
-use crate::{context::Context, poem_backend::TransactionsApi};
+use crate::{
+    context::Context,
+    poem_backend::{TransactionsApi, ViewApi, BATCH_PATH},
+};
 use anyhow::Context as AnyhowContext;
 use aptos_config::config::NodeConfig;
 use aptos_logger::info;
 use poem::{
+    get,
     http::{header, Method},
-    listener::{Listener, RustlsCertificate, RustlsConfig, TcpListener},
+    listener::{Acceptor, AcceptorExt, Listener, RustlsCertificate, RustlsConfig, TcpListener},
     middleware::Cors,
-    EndpointExt, Route, Server,
+    post, EndpointExt, Route, Server,
 };
-use poem_openapi::{ContactObject, LicenseObject, OpenApiService};
+use poem_openapi::{ContactObject, LicenseObject, OpenApi, OpenApiService};
 use tokio::runtime::Runtime;
 
-/// Returns address it is running at.
-pub fn attach_poem_to_runtime(
-    runtime: &Runtime,
-    context: Context,
-    config: &NodeConfig,
-) -> anyhow::Result<SocketAddr> {
-    let context = Arc::new(context);
+/// How long requests still in flight get to finish once the server stops
+/// accepting connections during a graceful shutdown.
+const IN_FLIGHT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// Builds the OpenAPI service holding every API we serve. The readiness
+/// probe reports whether `concurrency_limit` is shedding load.
+pub fn build_api_service(
+    context: Arc<Context>,
+    concurrency_limit: ConcurrencyLimit,
+) -> OpenApiService<impl OpenApi, ()> {
     let apis = (
         AccountsApi {
             context: context.clone(),
@@ -33,16 +43,31 @@ pub fn attach_poem_to_runtime(
         BasicApi {
             context: context.clone(),
         },
+        CapabilitiesApi {
+            context: context.clone(),
+        },
+        ConfigsApi {
+            context: context.clone(),
+        },
         EventsApi {
             context: context.clone(),
         },
         IndexApi {
             context: context.clone(),
         },
-        TransactionsApi { context },
+        NodeInfoApi {
+            context: context.clone(),
+        },
+        ReadinessApi {
+            context: context.clone(),
+            concurrency_limit,
+        },
+        TransactionsApi {
+            context: context.clone(),
+        },
+        ViewApi { context },
     );
 
-    let version = std::env::var("CARGO_PKG_VERSION").unwrap_or_else(|_| "0.1.0".to_string());
     let license =
         LicenseObject::new("Apache 2.0").url("https://www.apache.org/licenses/LICENSE-2.0.html");
     let contact = ContactObject::new()
@@ -50,22 +75,89 @@ pub fn attach_poem_to_runtime(
         .url("https://github.com/aptos-labs/aptos-core");
 
     // These APIs get merged.
-    let api_service = OpenApiService::new(apis, "Aptos Node API", version)
+    OpenApiService::new(apis, "Aptos Node API", api_version())
         .description("The Aptos Node API is a RESTful API for client applications to interact with the Aptos blockchain.")
         .license(license)
         .contact(contact)
-        .external_document("https://github.com/aptos-labs/aptos-core");
+        .external_document("https://github.com/aptos-labs/aptos-core")
+}
+
+/// The version of the API, as given in the OpenAPI spec.
+pub fn api_version() -> String {
+    std::env::var("CARGO_PKG_VERSION").unwrap_or_else(|_| "0.1.0".to_string())
+}
+
+/// Serves the operator endpoints (see `LogFilterApi`) on `admin_address`, if
+/// the node has one configured. Returns the address they are running at.
+pub fn attach_admin_api_to_runtime(
+    runtime: &Runtime,
+    context: Context,
+    config: &NodeConfig,
+) -> anyhow::Result<Option<SocketAddr>> {
+    let address = match config.api.admin_address {
+        Some(address) => address,
+        None => return Ok(None),
+    };
+    let shutdown_handle = context.shutdown_handle().clone();
+    let api_service = OpenApiService::new(
+        LogFilterApi {
+            context: Arc::new(context),
+        },
+        "Aptos Node Admin API",
+        api_version(),
+    );
+
+    let acceptor = runtime
+        .block_on(TcpListener::bind(address).into_acceptor())
+        .with_context(|| format!("Failed to bind the admin API to address {}", address))?;
+    let actual_address = acceptor.local_addr()[0]
+        .as_socket_addr()
+        .cloned()
+        .context("Failed to get socket addr from local addr for the admin API")?;
+
+    runtime.spawn(async move {
+        let route = Route::new().nest("/", api_service).around(middleware_log);
+        Server::new_with_acceptor(acceptor)
+            .run_with_graceful_shutdown(
+                route,
+                async move { shutdown_handle.stopped().await },
+                Some(IN_FLIGHT_SHUTDOWN_TIMEOUT),
+            )
+            .await
+            .map_err(anyhow::Error::msg)
+    });
+
+    info!("The admin API is running at {}", actual_address);
+
+    Ok(Some(actual_address))
+}
+
+/// Serves the API on every configured address. Returns the addresses it is
+/// running at, in the same order as in the config.
+pub fn attach_poem_to_runtime(
+    runtime: &Runtime,
+    context: Context,
+    config: &NodeConfig,
+) -> anyhow::Result<Vec<SocketAddr>> {
+    let shutdown_handle = context.shutdown_handle().clone();
+    let concurrency_limit = ConcurrencyLimit::from_config(&config.api);
+    let api_service = build_api_service(Arc::new(context), concurrency_limit.clone());
 
     let spec_json = api_service.spec_endpoint();
     let spec_yaml = api_service.spec_endpoint_yaml();
 
-    let mut address = config.api.address;
+    let routes = RouteTemplates::from_spec(&api_service.spec())?;
+    let method_routing = MethodRouting::new(routes.clone());
+    let request_timeouts = RequestTimeouts::from_config(&config.api, routes.clone());
 
-    // TODO: This is temporary while we serve both APIs simulatenously.
-    // Doing this means the OS assigns it an unused port.
-    address.set_port(0);
+    // The OTLP exporter (if any) needs to be started within the runtime.
+    let request_tracing = {
+        let _guard = runtime.enter();
+        RequestTracing::from_config(&config.api, routes)
+            .context("Failed to set up request tracing")?
+    };
 
-    let listener = match (&config.api.tls_cert_path, &config.api.tls_key_path) {
+    let tls_cert_and_key = match (&config.api.tls_cert_path, &config.api.tls_key_path) {
         (Some(tls_cert_path), Some(tls_key_path)) => {
             info!("Using TLS for API");
             let cert = std::fs::read_to_string(tls_cert_path).context(format!(
@@ -76,45 +168,290 @@ pub fn attach_poem_to_runtime(
                 "Failed to read TLS key from path: {}",
                 tls_key_path
             ))?;
-            let rustls_certificate = RustlsCertificate::new().cert(cert).key(key);
-            let rustls_config = RustlsConfig::new().fallback(rustls_certificate);
-            TcpListener::bind(address).rustls(rustls_config).boxed()
+            Some((cert, key))
         }
         _ => {
             info!("Not using TLS for API");
-            TcpListener::bind(address).boxed()
+            None
         }
     };
 
-    let acceptor = runtime
-        .block_on(async move { listener.into_acceptor().await })
-        .context("Failed to bind Poem to address with OS assigned port")?;
+    let mut acceptors = vec![];
+    for mut address in config.api.addresses() {
+        // TODO: This is temporary while we serve both APIs simulatenously.
+        // Doing this means the OS assigns it an unused port.
+        address.set_port(0);
 
-    let actual_address = &acceptor.local_addr()[0];
-    let actual_address = *actual_address
-        .as_socket_addr()
-        .context("Failed to get socket addr from local addr for Poem webserver")?;
+        let listener = match &tls_cert_and_key {
+            Some((cert, key)) => {
+                let rustls_certificate =
+                    RustlsCertificate::new().cert(cert.clone()).key(key.clone());
+                let rustls_config = RustlsConfig::new().fallback(rustls_certificate);
+                TcpListener::bind(address).rustls(rustls_config).boxed()
+            }
+            None => TcpListener::bind(address).boxed(),
+        };
+        let acceptor = runtime
+            .block_on(listener.into_acceptor())
+            .with_context(|| format!("Failed to bind Poem to address {}", address))?;
+        acceptors.push(acceptor);
+    }
+
+    let mut actual_addresses = vec![];
+    for acceptor in &acceptors {
+        for local_addr in acceptor.local_addr() {
+            actual_addresses.push(
+                *local_addr
+                    .as_socket_addr()
+                    .context("Failed to get socket addr from local addr for Poem webserver")?,
+            );
+        }
+    }
+
+    // Serve the same routes on every address.
+    let acceptor = acceptors
+        .into_iter()
+        .reduce(|a, b| a.combine(b).boxed())
+        .context("No addresses configured for the API")?;
+
+    let batch_config = config.api.clone();
+    let metrics = if config.api.metrics_enabled {
+        Some(MetricsEndpoint::from_config(&config.api))
+    } else {
+        None
+    };
 
     runtime.spawn(async move {
         let cors = Cors::new()
             .allow_methods(vec![Method::GET, Method::POST])
             .allow_headers(vec![header::CONTENT_TYPE, header::ACCEPT]);
-        let route = Route::new()
+        let mut route = Route::new()
             .nest("/", api_service)
             .at("/spec.json", spec_json)
-            .at("/spec.yaml", spec_yaml)
+            .at("/spec.yaml", spec_yaml);
+        if let Some(metrics) = metrics {
+            route = route.at("/-/metrics", get(metrics));
+        }
+        // Requests within a batch go through the same per request
+        // middleware as direct requests, so the batch itself doesn't.
+        let api = Arc::new(
+            route
+                .with(method_routing)
+                .with(request_timeouts)
+                .with(concurrency_limit),
+        );
+        let batch = BatchEndpoint::from_config(&batch_config, api.clone());
+        let route = Route::new()
+            .at(BATCH_PATH, post(batch))
+            .nest("/", api)
             .with(cors)
-            .around(middleware_log);
+            .around(middleware_log)
+            .with(request_tracing);
         Server::new_with_acceptor(acceptor)
-            .run(route)
+            .run_with_graceful_shutdown(
+                route,
+                async move { shutdown_handle.stopped().await },
+                Some(IN_FLIGHT_SHUTDOWN_TIMEOUT),
+            )
             .await
             .map_err(anyhow::Error::msg)
     });
 
     info!(
-        "Poem is running at {}, behind the reverse proxy at the API port",
-        actual_address
+        "Poem is running at {:?}, behind the reverse proxy at the API port",
+        actual_addresses
     );
 
-    Ok(actual_address)
+    Ok(actual_addresses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{new_test_context, new_test_context_with_config};
+    use serde_json::Value;
+    use std::collections::HashMap;
+    use std::net::TcpListener as StdTcpListener;
+
+    // These tags don't have any endpoints in the Poem API yet.
+    const TAGS_WITHOUT_OPERATIONS: &[&str] = &["Blocks", "Tables"];
+
+    #[test]
+    fn test_every_operation_is_tagged() {
+        let context = new_test_context("test_every_operation_is_tagged");
+        let spec: Value = serde_json::from_str(
+            &build_api_service(
+                Arc::new(context.context.clone()),
+                ConcurrencyLimit::from_config(&NodeConfig::default().api),
+            )
+            .spec(),
+        )
+        .unwrap();
+
+        let mut operations_by_tag: HashMap<String, usize> = HashMap::new();
+        for (path, methods) in spec["paths"].as_object().unwrap() {
+            for (method, operation) in methods.as_object().unwrap() {
+                let tags = operation["tags"].as_array().unwrap();
+                assert_eq!(tags.len(), 1, "{} {} should have one tag", method, path);
+                *operations_by_tag
+                    .entry(tags[0].as_str().unwrap().to_string())
+                    .or_default() += 1;
+            }
+        }
+
+        let mut tags = vec![];
+        for tag in spec["tags"].as_array().unwrap() {
+            let name = tag["name"].as_str().unwrap();
+            assert!(
+                !tag["description"].as_str().unwrap_or_default().is_empty(),
+                "tag {} has no description",
+                name
+            );
+            assert!(
+                operations_by_tag.contains_key(name) || TAGS_WITHOUT_OPERATIONS.contains(&name),
+                "tag {} has no operations",
+                name
+            );
+            tags.push(name);
+        }
+        tags.sort_unstable();
+        assert_eq!(
+            tags,
+            vec![
+                "Accounts",
+                "Blocks",
+                "Configs",
+                "Events",
+                "General",
+                "Operations",
+                "Tables",
+                "Transactions",
+                "View"
+            ]
+        );
+    }
+
+    fn new_runtime() -> Runtime {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_serve_on_every_address() {
+        let mut addresses: Vec<SocketAddr> = vec!["127.0.0.1:0".parse().unwrap()];
+        if StdTcpListener::bind("[::1]:0").is_ok() {
+            addresses.push("[::1]:0".parse().unwrap());
+        } else {
+            eprintln!("IPv6 is unavailable, only testing IPv4");
+        }
+        let mut config = NodeConfig::default();
+        config.api.address = addresses[0];
+        config.api.additional_addresses = addresses[1..].to_vec();
+
+        let context = new_test_context("test_serve_on_every_address");
+        let runtime = new_runtime();
+        let bound = attach_poem_to_runtime(&runtime, context.context.clone(), &config).unwrap();
+        assert_eq!(bound.len(), addresses.len());
+
+        for (bound, requested) in bound.iter().zip(&addresses) {
+            assert_eq!(bound.ip(), requested.ip());
+            let resp = runtime
+                .block_on(reqwest::get(format!("http://{}/", bound)))
+                .unwrap();
+            assert_eq!(resp.status(), reqwest::StatusCode::OK);
+        }
+    }
+
+    #[test]
+    fn test_failing_to_bind_any_address_is_fatal() {
+        let mut config = NodeConfig::default();
+        config.api.address = "127.0.0.1:0".parse().unwrap();
+        // An address from TEST-NET-1, which is never assigned to a local interface.
+        config.api.additional_addresses = vec!["192.0.2.1:0".parse().unwrap()];
+
+        let context = new_test_context("test_failing_to_bind_any_address_is_fatal");
+        let err =
+            attach_poem_to_runtime(&new_runtime(), context.context.clone(), &config).unwrap_err();
+        assert!(format!("{:#}", err).contains("Failed to bind Poem to address 192.0.2.1:0"));
+    }
+
+    #[test]
+    fn test_log_filter_is_only_served_on_the_admin_address() {
+        let mut config = NodeConfig::default();
+        config.api.address = "127.0.0.1:0".parse().unwrap();
+        config.api.admin_address = Some("127.0.0.1:0".parse().unwrap());
+
+        let context = new_test_context("test_log_filter_is_only_served_on_the_admin_address");
+        let runtime = new_runtime();
+        let api_address =
+            attach_poem_to_runtime(&runtime, context.context.clone(), &config).unwrap()[0];
+        let admin_address = attach_admin_api_to_runtime(&runtime, context.context.clone(), &config)
+            .unwrap()
+            .unwrap();
+        assert_ne!(admin_address.port(), 0);
+
+        // An invalid filter is rejected without touching the logger.
+        let set_filter = |address: SocketAddr| {
+            let resp = runtime
+                .block_on(
+                    reqwest::Client::new()
+                        .post(format!("http://{}/log/filter", address))
+                        .json(&serde_json::json!({ "filter": "info,aptos_api=loud" }))
+                        .send(),
+                )
+                .unwrap();
+            resp.status()
+        };
+        assert_eq!(set_filter(admin_address), reqwest::StatusCode::BAD_REQUEST);
+        assert_eq!(set_filter(api_address), reqwest::StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_no_admin_api_without_an_admin_address() {
+        let config = NodeConfig::default();
+        let context = new_test_context("test_no_admin_api_without_an_admin_address");
+        let address =
+            attach_admin_api_to_runtime(&new_runtime(), context.context.clone(), &config).unwrap();
+        assert_eq!(address, None);
+    }
+
+    #[test]
+    fn test_readiness_fails_before_graceful_shutdown_stops_the_server() {
+        let mut config = NodeConfig::default();
+        config.api.address = "127.0.0.1:0".parse().unwrap();
+        config.api.shutdown_drain_delay_ms = 500;
+        let context = new_test_context_with_config(
+            "test_readiness_fails_before_graceful_shutdown_stops_the_server",
+            config.clone(),
+        );
+        let shutdown_handle = context.context.shutdown_handle().clone();
+
+        let runtime = new_runtime();
+        let address =
+            attach_poem_to_runtime(&runtime, context.context.clone(), &config).unwrap()[0];
+        let url = format!("http://{}/-/ready", address);
+
+        let shutdown = {
+            let shutdown_handle = shutdown_handle.clone();
+            runtime.spawn(async move { shutdown_handle.shutdown().await })
+        };
+        while !shutdown_handle.is_shutting_down() {
+            std::thread::yield_now();
+        }
+        // The server still accepts connections while draining, but says it
+        // isn't ready.
+        let resp = runtime.block_on(reqwest::get(&url)).unwrap();
+        assert_eq!(resp.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        let body: Value = runtime.block_on(resp.json()).unwrap();
+        let failed_checks = body["failed_checks"].as_array().unwrap();
+        assert!(failed_checks
+            .iter()
+            .any(|failed| failed["check"] == "shutdown"));
+
+        runtime.block_on(shutdown).unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(runtime.block_on(reqwest::get(&url)).is_err());
+    }
 }
@@ -11,15 +11,16 @@ use super::page::Page;
 use super::AptosErrorCode;
 use super::{
     ApiTags, AptosErrorResponse, BasicErrorWith404, BasicResponse, BasicResponseStatus,
-    BasicResultWith404, InternalError,
+    BasicResultWith404, Cancellation, InternalError, RequestSpanExt,
 };
 use crate::context::Context;
 use crate::failpoint::fail_point_poem;
 use anyhow::Context as AnyhowContext;
-use aptos_api_types::{AsConverter, LedgerInfo, Transaction, TransactionOnChainData};
+use aptos_api_types::{AsConverter, LedgerInfo, Transaction, TransactionOnChainData, U64};
 use poem::web::Accept;
 use poem_openapi::param::Query;
 use poem_openapi::OpenApi;
+use tracing::Span;
 
 pub struct TransactionsApi {
     pub context: Arc<Context>,
@@ -29,28 +30,44 @@ pub struct TransactionsApi {
 impl TransactionsApi {
     /// Get transactions
     ///
-    /// todo
+    /// Returns a page of committed transactions, in order of their ledger
+    /// version. Without a start version the latest transactions are returned.
     #[oai(
         path = "/transactions",
         method = "get",
         operation_id = "get_transactions",
-        tag = "ApiTags::General"
+        tag = "ApiTags::Transactions"
     )]
     async fn get_transactions(
         &self,
         accept: Accept,
-        start: Query<Option<u64>>,
+        /// Ledger version of the first transaction to return. Defaults to
+        /// the start of the last page, i.e. the latest transactions.
+        start: Query<Option<U64>>,
+        /// Max number of items to return. Limits above the node's configured
+        /// max page size are clamped to it, so clients should rely on the
+        /// X-Aptos-Page-Size header for the page size that was actually used.
         limit: Query<Option<u16>>,
+        cancellation: Cancellation,
     ) -> BasicResultWith404<Vec<Transaction>> {
         fail_point_poem("endppoint_get_transactions")?;
         let accept_type = parse_accept(&accept)?;
-        let page = Page::new(start.0, limit.0);
-        self.list(&accept_type, page)
+        let page = Page::new(
+            start.0.map(u64::from),
+            limit.0,
+            self.context.page_size_limits(),
+        );
+        self.list(&accept_type, page, &cancellation)
     }
 }
 
 impl TransactionsApi {
-    fn list(&self, accept_type: &AcceptType, page: Page) -> BasicResultWith404<Vec<Transaction>> {
+    fn list(
+        &self,
+        accept_type: &AcceptType,
+        page: Page,
+        cancellation: &Cancellation,
+    ) -> BasicResultWith404<Vec<Transaction>> {
         let latest_ledger_info = self.context.get_latest_ledger_info_poem()?;
         let ledger_version = latest_ledger_info.version();
         let limit = page.limit()?;
@@ -60,6 +77,7 @@ impl TransactionsApi {
             0
         };
         let start_version = page.start(last_page_start, ledger_version)?;
+        Span::current().record_version(start_version);
 
         let data = self
             .context
@@ -68,7 +86,8 @@ impl TransactionsApi {
             .map_err(BasicErrorWith404::internal)
             .map_err(|e| e.error_code(AptosErrorCode::InvalidBcsInStorageError))?;
 
-        self.render_transactions(data, accept_type, &latest_ledger_info)
+        self.render_transactions(data, accept_type, &latest_ledger_info, cancellation)
+            .map(|response| response.with_page_size(limit))
     }
 
     fn render_transactions(
@@ -76,6 +95,7 @@ impl TransactionsApi {
         data: Vec<TransactionOnChainData>,
         accept_type: &AcceptType,
         latest_ledger_info: &LedgerInfo,
+        cancellation: &Cancellation,
     ) -> BasicResultWith404<Vec<Transaction>> {
         if data.is_empty() {
             let data: Vec<Transaction> = vec![];
@@ -89,17 +109,20 @@ impl TransactionsApi {
 
         let resolver = self.context.move_resolver_poem()?;
         let converter = resolver.as_converter();
-        let txns: Vec<Transaction> = data
-            .into_iter()
-            .map(|t| {
-                let version = t.version;
-                let timestamp = self.context.get_block_timestamp(version)?;
-                let txn = converter.try_into_onchain_transaction(timestamp, t)?;
-                Ok(txn)
-            })
-            .collect::<Result<_, anyhow::Error>>()
-            .context("Failed to convert transaction data from storage")
-            .map_err(BasicErrorWith404::internal)?;
+        let mut txns: Vec<Transaction> = Vec::with_capacity(data.len());
+        for t in data {
+            // Each transaction needs another read from storage, so stop
+            // early if the client won't get the response anyway.
+            cancellation.check::<BasicErrorWith404>()?;
+            let version = t.version;
+            let txn = self
+                .context
+                .get_block_timestamp(version)
+                .and_then(|timestamp| converter.try_into_onchain_transaction(timestamp, t))
+                .context("Failed to convert transaction data from storage")
+                .map_err(BasicErrorWith404::internal)?;
+            txns.push(txn);
+        }
 
         BasicResponse::try_from_rust_value((
             txns,
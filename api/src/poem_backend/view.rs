@@ -0,0 +1,579 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use std::convert::TryInto;
+use std::sync::Arc;
+
+use super::accept_type::{parse_accept, AcceptType};
+use super::bcs_payload::Bcs;
+use super::{
+    ApiTags, AptosErrorCode, AptosErrorResponse, BadRequestError, BasicErrorWith404And410And413,
+    BasicResponse, BasicResponseStatus, BasicResultWith404And410And413, InternalError,
+    NotFoundError, PayloadTooLargeError,
+};
+use crate::context::Context;
+use crate::failpoint::fail_point_poem;
+use anyhow::Context as AnyhowContext;
+use aptos_api_types::{
+    check_json_size, AsConverter, BcsViewRequest, JsonRenderError, LedgerInfo, MoveConverter,
+    MoveFunction, MoveFunctionVisibility, MoveModuleBytecode, MoveModuleId, MoveType, MoveValue,
+    ViewRequest, U64,
+};
+use aptos_types::{
+    access_path::AccessPath,
+    state_store::state_key::StateKey,
+    transaction::ExecutionStatus,
+    vm_status::{AbortLocation, StatusCode, VMStatus},
+};
+use aptos_vm::{move_vm_ext::MoveResolverExt, AptosVM};
+use move_deps::move_core_types::{
+    identifier::Identifier,
+    language_storage::{ModuleId, TypeTag},
+};
+use poem::web::Accept;
+use poem_openapi::{param::Query, payload::Json, ApiRequest, OpenApi};
+
+/// VM status codes that mean the arguments didn't match the function.
+const ARGUMENT_ERRORS: &[StatusCode] = &[
+    StatusCode::FAILED_TO_DESERIALIZE_ARGUMENT,
+    StatusCode::NUMBER_OF_ARGUMENTS_MISMATCH,
+    StatusCode::NUMBER_OF_TYPE_ARGUMENTS_MISMATCH,
+    StatusCode::TYPE_MISMATCH,
+];
+
+pub struct ViewApi {
+    pub context: Arc<Context>,
+}
+
+/// The body of a call to a view function, with the arguments encoded as
+/// either JSON or BCS.
+#[derive(ApiRequest)]
+pub enum ViewRequestBody {
+    #[oai(content_type = "application/x-bcs")]
+    Bcs(Bcs<BcsViewRequest>),
+
+    #[oai(content_type = "application/json")]
+    Json(Json<ViewRequest>),
+}
+
+#[OpenApi]
+impl ViewApi {
+    /// Call view function
+    ///
+    /// Executes a view function against the state at the given ledger
+    /// version, or the latest version if none is given, and returns its
+    /// return values. Nothing the function changes is committed. A view
+    /// function is a public function that takes no signer and returns at
+    /// least one value, e.g. "0x1::coin::balance". Execution may use at most
+    /// the node's configured view gas budget.
+    ///
+    /// The arguments can be given as JSON, or as BCS by using the
+    /// "application/x-bcs" content type. If BCS is accepted, the return
+    /// values are returned as a BCS vector of the BCS encoding of each value.
+    #[oai(
+        path = "/view",
+        method = "post",
+        operation_id = "view",
+        tag = "ApiTags::View"
+    )]
+    async fn view(
+        &self,
+        accept: Accept,
+        request: ViewRequestBody,
+        /// Ledger version to read the state at. Defaults to the latest
+        /// version.
+        ledger_version: Query<Option<U64>>,
+    ) -> BasicResultWith404And410And413<Vec<MoveValue>> {
+        fail_point_poem("endpoint_view")?;
+        let accept_type = parse_accept(&accept)?;
+        let ledger_info = self
+            .context
+            .get_ledger_info_at_version_poem(ledger_version.0.map(u64::from))?;
+        self.call(request, &ledger_info, &accept_type)
+    }
+}
+
+/// The arguments of a view function call, as given by the client.
+enum ViewArguments {
+    Json(Vec<serde_json::Value>),
+    Bcs(Vec<Vec<u8>>),
+}
+
+impl ViewApi {
+    fn call(
+        &self,
+        request: ViewRequestBody,
+        ledger_info: &LedgerInfo,
+        accept_type: &AcceptType,
+    ) -> BasicResultWith404And410And413<Vec<MoveValue>> {
+        let version = ledger_info.version();
+        let (module_id, name, type_args, arguments): (
+            ModuleId,
+            Identifier,
+            Vec<TypeTag>,
+            ViewArguments,
+        ) = match request {
+            ViewRequestBody::Json(Json(request)) => (
+                request.function.module.into(),
+                request.function.name.into(),
+                request
+                    .type_arguments
+                    .into_iter()
+                    .map(TryInto::try_into)
+                    .collect::<anyhow::Result<Vec<TypeTag>>>()
+                    .context("Invalid type arguments")
+                    .map_err(BasicErrorWith404And410And413::bad_request)
+                    .map_err(|e| e.error_code(AptosErrorCode::InvalidViewArguments))?,
+                ViewArguments::Json(request.arguments),
+            ),
+            ViewRequestBody::Bcs(Bcs(request)) => (
+                request.module,
+                request.function,
+                request.type_arguments,
+                ViewArguments::Bcs(request.arguments),
+            ),
+        };
+
+        let function_id = format!("{}::{}", MoveModuleId::from(module_id.clone()), name);
+        let function = self.find_view_function(&module_id, &name, &function_id, version)?;
+        if function.generic_type_params.len() != type_args.len() {
+            return Err(BasicErrorWith404And410And413::bad_request_str(&format!(
+                "Expected {} type arguments for view function {}, but got {}",
+                function.generic_type_params.len(),
+                function_id,
+                type_args.len()
+            ))
+            .error_code(AptosErrorCode::InvalidViewArguments));
+        }
+        let move_type_args: Vec<MoveType> = type_args.iter().cloned().map(Into::into).collect();
+
+        let resolver = self.context.move_resolver_at_version_poem(version)?;
+        let converter = resolver.as_converter();
+        let arguments = match arguments {
+            ViewArguments::Json(arguments) => {
+                encode_json_arguments(&converter, &function, &move_type_args, arguments)?
+            }
+            ViewArguments::Bcs(arguments) => {
+                if arguments.len() != function.params.len() {
+                    return Err(BasicErrorWith404And410And413::bad_request_str(&format!(
+                        "Expected {} arguments for view function {}, but got {}",
+                        function.params.len(),
+                        function_id,
+                        arguments.len()
+                    ))
+                    .error_code(AptosErrorCode::InvalidViewArguments));
+                }
+                arguments
+            }
+        };
+
+        let state_view = self
+            .context
+            .state_view_at_version(version)
+            .context(format!(
+                "Failed to read state at version {} from DB",
+                version
+            ))
+            .map_err(BasicErrorWith404And410And413::internal)
+            .map_err(|e| e.error_code(AptosErrorCode::ReadFromStorageError))?;
+        let return_values = AptosVM::execute_view_function(
+            &state_view,
+            &module_id,
+            &name,
+            type_args,
+            arguments,
+            self.context.view_max_gas(),
+        )
+        .map_err(|status| self.view_function_error(&converter, status))?;
+
+        if accept_type == &AcceptType::Bcs {
+            let bytes = bcs::to_bytes(&return_values)
+                .context("Failed to serialize the return values of the view function")
+                .map_err(BasicErrorWith404And410And413::internal)
+                .map_err(|e| e.error_code(AptosErrorCode::BcsSerializationError))?;
+            let limit = self.context.max_bcs_render_bytes();
+            if bytes.len() as u64 > limit {
+                return Err(
+                    BasicErrorWith404And410And413::payload_too_large_str(&format!(
+                        "Return values too large to return ({} bytes of BCS, more than {} bytes)",
+                        bytes.len(),
+                        limit
+                    ))
+                    .error_code(AptosErrorCode::ResponseTooLarge),
+                );
+            }
+            return Ok(BasicResponse::from((
+                Bcs(bytes),
+                ledger_info,
+                BasicResponseStatus::Ok,
+            )));
+        }
+
+        let return_values = function
+            .return_
+            .iter()
+            .zip(return_values)
+            .map(|(typ, bytes)| {
+                let type_tag: TypeTag = typ.substitute(&move_type_args).try_into()?;
+                converter.try_into_move_value(&type_tag, &bytes)
+            })
+            .collect::<anyhow::Result<Vec<MoveValue>>>()
+            .context("Failed to convert the return values of the view function")
+            .map_err(BasicErrorWith404And410And413::internal)?;
+        // The JSON is rendered by the framework after this returns, so the
+        // size is checked without keeping the rendering around.
+        let limit = self.context.max_json_render_bytes();
+        match check_json_size(&return_values, limit) {
+            Ok(()) => {}
+            Err(JsonRenderError::TooLarge) => {
+                return Err(
+                    BasicErrorWith404And410And413::payload_too_large_str(&format!(
+                        "Return values too large to render as JSON (more than {} bytes), \
+                         request them as BCS instead",
+                        limit
+                    ))
+                    .error_code(AptosErrorCode::ResponseTooLarge),
+                )
+            }
+            Err(JsonRenderError::Json(err)) => {
+                return Err(BasicErrorWith404And410And413::internal(err.into()))
+            }
+        }
+        BasicResponse::try_from_rust_value((
+            return_values,
+            ledger_info,
+            BasicResponseStatus::Ok,
+            accept_type,
+        ))
+    }
+
+    /// Finds the ABI of the given function, making sure it is a view
+    /// function.
+    fn find_view_function<E: BadRequestError + NotFoundError + InternalError>(
+        &self,
+        module_id: &ModuleId,
+        name: &Identifier,
+        function_id: &str,
+        version: u64,
+    ) -> Result<MoveFunction, E> {
+        let not_found = || {
+            E::not_found_str(&format!("Function {} not found", function_id))
+                .error_code(AptosErrorCode::FunctionNotFound)
+                .aptos_ledger_version(version)
+        };
+
+        let state_key = StateKey::AccessPath(AccessPath::code_access_path(module_id.clone()));
+        let bytes = self
+            .context
+            .get_state_value_poem(&state_key, version)?
+            .ok_or_else(not_found)?;
+        let module = MoveModuleBytecode::new(bytes)
+            .try_parse_abi()
+            .context("Failed to parse move module ABI")
+            .map_err(E::internal)
+            .map_err(|e| e.error_code(AptosErrorCode::InvalidBcsInStorageError))?;
+        let function = module
+            .abi
+            .into_iter()
+            .flat_map(|abi| abi.exposed_functions)
+            .find(|function| &function.name.0 == name)
+            .ok_or_else(not_found)?;
+
+        if !is_view_function(&function) {
+            return Err(E::bad_request_str(&format!(
+                "Function {} is not a view function, view functions must be public, \
+                 take no signer and return at least one value",
+                function_id
+            ))
+            .error_code(AptosErrorCode::NotAViewFunction));
+        }
+        Ok(function)
+    }
+
+    fn view_function_error<R: MoveResolverExt>(
+        &self,
+        converter: &MoveConverter<R>,
+        status: VMStatus,
+    ) -> BasicErrorWith404And410And413 {
+        match status {
+            VMStatus::MoveAbort(location, code) => {
+                let module = match &location {
+                    AbortLocation::Module(module_id) => {
+                        MoveModuleId::from(module_id.clone()).to_string()
+                    }
+                    AbortLocation::Script => "script".to_string(),
+                };
+                let explanation =
+                    converter.explain_vm_status(&ExecutionStatus::MoveAbort { location, code });
+                BasicErrorWith404And410And413::bad_request_str(&format!(
+                    "View function aborted with code {} in {}: {}",
+                    code, module, explanation
+                ))
+                .error_code(AptosErrorCode::MoveAbort)
+            }
+            status if ARGUMENT_ERRORS.contains(&status.status_code()) => {
+                BasicErrorWith404And410And413::bad_request_str(&format!(
+                    "The arguments don't match the view function: {:?}",
+                    status
+                ))
+                .error_code(AptosErrorCode::InvalidViewArguments)
+            }
+            status if status.status_code() == StatusCode::OUT_OF_GAS => {
+                BasicErrorWith404And410And413::bad_request_str(&format!(
+                    "View function ran out of gas, it may use at most {} gas",
+                    self.context.view_max_gas()
+                ))
+                .error_code(AptosErrorCode::ViewFunctionFailed)
+            }
+            status => BasicErrorWith404And410And413::bad_request_str(&format!(
+                "View function failed: {:?}",
+                status
+            ))
+            .error_code(AptosErrorCode::ViewFunctionFailed),
+        }
+    }
+}
+
+/// There is no annotation for view functions in Move bytecode, so we treat
+/// every public function that can be called without a signer and returns
+/// something as one. Entry functions can't return values, so they're never
+/// view functions.
+fn is_view_function(function: &MoveFunction) -> bool {
+    function.visibility == MoveFunctionVisibility::Public
+        && !function.return_.is_empty()
+        && !function.params.iter().any(MoveType::is_signer)
+}
+
+/// Encodes JSON arguments as BCS, using the function's param types with the
+/// given type arguments filled in.
+fn encode_json_arguments<R: MoveResolverExt>(
+    converter: &MoveConverter<R>,
+    function: &MoveFunction,
+    type_args: &[MoveType],
+    arguments: Vec<serde_json::Value>,
+) -> Result<Vec<Vec<u8>>, BasicErrorWith404And410And413> {
+    let function = MoveFunction {
+        params: function
+            .params
+            .iter()
+            .map(|param| param.substitute(type_args))
+            .collect(),
+        ..function.clone()
+    };
+    converter
+        .try_into_vm_values(function, arguments)
+        .and_then(|values| {
+            values
+                .iter()
+                .map(|value| bcs::to_bytes(value).map_err(Into::into))
+                .collect::<anyhow::Result<_>>()
+        })
+        .context("Invalid arguments")
+        .map_err(BasicErrorWith404And410And413::bad_request)
+        .map_err(|e| e.error_code(AptosErrorCode::InvalidViewArguments))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::new_test_context_with_config;
+    use aptos_config::config::NodeConfig;
+    use aptos_types::account_config::CORE_CODE_ADDRESS;
+    use move_deps::move_core_types::parser::parse_struct_tag;
+    use poem::{
+        http::{header, Method, StatusCode as HttpStatusCode},
+        Endpoint, IntoEndpoint, Request,
+    };
+    use poem_openapi::OpenApiService;
+    use serde_json::{json, Value};
+
+    const APTOS_COIN: &str = "0x1::aptos_coin::AptosCoin";
+
+    async fn call(name: &'static str, request: Request) -> (HttpStatusCode, Vec<u8>) {
+        call_with_config(name, NodeConfig::default(), request).await
+    }
+
+    async fn call_with_config(
+        name: &'static str,
+        node_config: NodeConfig,
+        request: Request,
+    ) -> (HttpStatusCode, Vec<u8>) {
+        let context = Arc::new(
+            new_test_context_with_config(name, node_config)
+                .context
+                .clone(),
+        );
+        let ep = OpenApiService::new(ViewApi { context }, "test", "0.1.0").into_endpoint();
+        let mut resp = ep.get_response(request).await;
+        (resp.status(), resp.take_body().into_vec().await.unwrap())
+    }
+
+    async fn view(name: &'static str, body: Value) -> (HttpStatusCode, Value) {
+        let (status, body) = call(
+            name,
+            Request::builder()
+                .method(Method::POST)
+                .uri_str("/view")
+                .content_type("application/json")
+                .body(body.to_string()),
+        )
+        .await;
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_view_function() {
+        let (status, body) = view(
+            "test_view_function",
+            json!({
+                "function": "0x1::coin::name",
+                "type_arguments": [APTOS_COIN],
+                "arguments": [],
+            }),
+        )
+        .await;
+        assert_eq!(status, HttpStatusCode::OK, "{}", body);
+        assert_eq!(body, json!(["Aptos Coin"]));
+    }
+
+    #[tokio::test]
+    async fn test_view_function_with_bcs() {
+        let request = BcsViewRequest {
+            module: ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("coin").unwrap()),
+            function: Identifier::new("is_account_registered").unwrap(),
+            type_arguments: vec![TypeTag::Struct(parse_struct_tag(APTOS_COIN).unwrap())],
+            arguments: vec![bcs::to_bytes(&CORE_CODE_ADDRESS).unwrap()],
+        };
+        let (status, body) = call(
+            "test_view_function_with_bcs",
+            Request::builder()
+                .method(Method::POST)
+                .uri_str("/view")
+                .content_type("application/x-bcs")
+                .header(header::ACCEPT, "application/x-bcs")
+                .body(bcs::to_bytes(&request).unwrap()),
+        )
+        .await;
+        assert_eq!(status, HttpStatusCode::OK);
+        let return_values: Vec<Vec<u8>> = bcs::from_bytes(&body).unwrap();
+        assert_eq!(return_values.len(), 1);
+        let _registered: bool = bcs::from_bytes(&return_values[0]).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_view_function_not_found() {
+        for function in &["0x1::coin::no_such_function", "0x1::no_such_module::name"] {
+            let (status, body) = view(
+                "test_view_function_not_found",
+                json!({ "function": function, "type_arguments": [], "arguments": [] }),
+            )
+            .await;
+            assert_eq!(status, HttpStatusCode::NOT_FOUND, "{}", body);
+            assert_eq!(body["error_code"], "FunctionNotFound");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_not_a_view_function() {
+        // Takes a signer and returns nothing.
+        let (status, body) = view(
+            "test_not_a_view_function",
+            json!({
+                "function": "0x1::coin::transfer",
+                "type_arguments": [APTOS_COIN],
+                "arguments": ["0x1", "1"],
+            }),
+        )
+        .await;
+        assert_eq!(status, HttpStatusCode::BAD_REQUEST, "{}", body);
+        assert_eq!(body["error_code"], "NotAViewFunction");
+    }
+
+    #[tokio::test]
+    async fn test_view_function_with_invalid_arguments() {
+        let requests = vec![
+            // Not an address
+            json!({
+                "function": "0x1::coin::balance",
+                "type_arguments": [APTOS_COIN],
+                "arguments": ["not an address"],
+            }),
+            // Too many arguments
+            json!({
+                "function": "0x1::coin::balance",
+                "type_arguments": [APTOS_COIN],
+                "arguments": ["0x1", "0x2"],
+            }),
+            // Missing type argument
+            json!({
+                "function": "0x1::coin::balance",
+                "type_arguments": [],
+                "arguments": ["0x1"],
+            }),
+        ];
+        for request in requests {
+            let (status, body) =
+                view("test_view_function_with_invalid_arguments", request.clone()).await;
+            assert_eq!(status, HttpStatusCode::BAD_REQUEST, "{}", request);
+            assert_eq!(body["error_code"], "InvalidViewArguments");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_view_function_abort() {
+        // The account has no coin store, so this aborts.
+        let (status, body) = view(
+            "test_view_function_abort",
+            json!({
+                "function": "0x1::coin::balance",
+                "type_arguments": [APTOS_COIN],
+                "arguments": ["0x12345"],
+            }),
+        )
+        .await;
+        assert_eq!(status, HttpStatusCode::BAD_REQUEST, "{}", body);
+        assert_eq!(body["error_code"], "MoveAbort");
+        assert!(body["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("View function aborted with code 393220 in 0x1::coin"));
+    }
+
+    #[tokio::test]
+    async fn test_view_function_response_too_large() {
+        // Renders as `["Aptos Coin"]`, or 13 bytes of BCS.
+        let request = json!({
+            "function": "0x1::coin::name",
+            "type_arguments": [APTOS_COIN],
+            "arguments": [],
+        });
+        let post = |accept: &str| {
+            Request::builder()
+                .method(Method::POST)
+                .uri_str("/view")
+                .content_type("application/json")
+                .header(header::ACCEPT, accept)
+                .body(request.to_string())
+        };
+
+        let mut node_config = NodeConfig::default();
+        node_config.api.max_json_render_bytes = 10;
+        let name = "test_view_function_response_too_large";
+        let (status, body) =
+            call_with_config(name, node_config.clone(), post("application/json")).await;
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(status, HttpStatusCode::PAYLOAD_TOO_LARGE, "{}", body);
+        assert_eq!(body["error_code"], "ResponseTooLarge");
+
+        // BCS has its own, larger, limit.
+        let (status, _) =
+            call_with_config(name, node_config.clone(), post("application/x-bcs")).await;
+        assert_eq!(status, HttpStatusCode::OK);
+
+        node_config.api.max_bcs_render_bytes = 10;
+        let (status, body) = call_with_config(name, node_config, post("application/x-bcs")).await;
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(status, HttpStatusCode::PAYLOAD_TOO_LARGE, "{}", body);
+        assert_eq!(body["error_code"], "ResponseTooLarge");
+    }
+}
@@ -1,11 +1,17 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{context::Context, index, poem_backend::attach_poem_to_runtime};
+use crate::{
+    context::Context,
+    index,
+    poem_backend::{attach_admin_api_to_runtime, attach_poem_to_runtime},
+};
 use anyhow::Context as AnyhowContext;
 use aptos_config::config::{ApiConfig, NodeConfig};
 use aptos_mempool::MempoolClientSender;
 use aptos_types::chain_id::ChainId;
+use futures::future::{self, BoxFuture, FutureExt};
+use state_sync_driver::sync_status::SyncStatusHandle;
 use std::{convert::Infallible, net::SocketAddr, sync::Arc};
 use storage_interface::DbReader;
 use tokio::runtime::{Builder, Runtime};
@@ -22,53 +28,68 @@ pub fn bootstrap(
     chain_id: ChainId,
     db: Arc<dyn DbReader>,
     mp_sender: MempoolClientSender,
+    sync_status_handle: SyncStatusHandle,
 ) -> anyhow::Result<Runtime> {
     let runtime = Builder::new_multi_thread()
         .thread_name("api")
         .enable_all()
         .build()
         .context("[api] failed to create runtime")?;
-    let context = Context::new(chain_id, db, mp_sender, config.clone());
+    let context = Context::new(chain_id, db, mp_sender, config.clone(), sync_status_handle);
 
     // Poem will run on a different port.
+    // The proxy only needs to reach Poem on one of its addresses.
     let poem_address = attach_poem_to_runtime(&runtime, context.clone(), config)
-        .context("Failed to attach poem to runtime")?;
+        .context("Failed to attach poem to runtime")?[0];
+    attach_admin_api_to_runtime(&runtime, context.clone(), config)
+        .context("Failed to attach the admin API to runtime")?;
 
     let api = WebServer::from(config.api.clone());
-    runtime.spawn(async move {
+    let server = {
+        // Binding needs to happen within the runtime.
+        let _guard = runtime.enter();
         // TODO: This proxy is temporary while we have both APIs running.
         let proxy = warp::path!("v1" / ..).and(reverse_proxy_filter(
             "v1".to_string(),
             format!("http://{}", poem_address),
         ));
-        let routes = proxy.or(index::routes(context));
-        api.serve(routes).await;
-    });
+        // Behind the proxy every request appears to come from localhost, so
+        // the log filter is only served on the admin address.
+        let log_filter = warp::path!("v1" / "log" / ..).map(|| {
+            warp::reply::with_status(
+                "The log filter is not available through this port",
+                warp::http::StatusCode::FORBIDDEN,
+            )
+        });
+        let routes = log_filter.or(proxy).or(index::routes(context));
+        api.bind(routes)?.1
+    };
+    runtime.spawn(server);
 
     Ok(runtime)
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct WebServer {
-    pub address: SocketAddr,
+    pub addresses: Vec<SocketAddr>,
     pub tls_cert_path: Option<String>,
     pub tls_key_path: Option<String>,
 }
 
 impl From<ApiConfig> for WebServer {
     fn from(cfg: ApiConfig) -> Self {
-        Self::new(cfg.address, cfg.tls_cert_path, cfg.tls_key_path)
+        Self::new(cfg.addresses(), cfg.tls_cert_path, cfg.tls_key_path)
     }
 }
 
 impl WebServer {
     pub fn new(
-        address: SocketAddr,
+        addresses: Vec<SocketAddr>,
         tls_cert_path: Option<String>,
         tls_key_path: Option<String>,
     ) -> Self {
         Self {
-            address,
+            addresses,
             tls_cert_path,
             tls_key_path,
         }
@@ -79,29 +100,62 @@ impl WebServer {
         F: Filter<Error = Infallible> + Clone + Sync + Send + 'static,
         F::Extract: Reply,
     {
-        match &self.tls_cert_path {
-            None => warp::serve(routes).bind(self.address).await,
-            Some(cert_path) => {
-                warp::serve(routes)
-                    .tls()
-                    .cert_path(cert_path)
-                    .key_path(self.tls_key_path.as_ref().unwrap())
-                    .bind(self.address)
-                    .await
-            }
+        let (_, server) = self.bind(routes).expect("Failed to bind the web server");
+        server.await
+    }
+
+    /// Binds the routes to every address, failing on the first one that
+    /// can't be bound. Returns the addresses bound (in the same order) and
+    /// the future serving them. Must be called within a Tokio runtime.
+    pub fn bind<F>(&self, routes: F) -> anyhow::Result<(Vec<SocketAddr>, BoxFuture<'static, ()>)>
+    where
+        F: Filter<Error = Infallible> + Clone + Sync + Send + 'static,
+        F::Extract: Reply,
+    {
+        let mut bound = vec![];
+        let mut servers = vec![];
+        for address in &self.addresses {
+            let bind_error = || format!("Failed to bind the API to address {}", address);
+            let (address, server) = match &self.tls_cert_path {
+                None => {
+                    let (address, server) = warp::serve(routes.clone())
+                        .try_bind_ephemeral(*address)
+                        .with_context(bind_error)?;
+                    (address, server.boxed())
+                }
+                Some(cert_path) => {
+                    // Warp panics if it can't bind with TLS, so make sure
+                    // the address is available first.
+                    std::net::TcpListener::bind(address).with_context(bind_error)?;
+                    let (address, server) = warp::serve(routes.clone())
+                        .tls()
+                        .cert_path(cert_path)
+                        .key_path(self.tls_key_path.as_ref().unwrap())
+                        .bind_ephemeral(*address);
+                    (address, server.boxed())
+                }
+            };
+            bound.push(address);
+            servers.push(server);
         }
+        let server = future::join_all(servers).map(|_| ()).boxed();
+        Ok((bound, server))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::time::Duration;
+    use std::{
+        net::{SocketAddr, TcpListener as StdTcpListener},
+        time::Duration,
+    };
 
     use aptos_config::config::NodeConfig;
     use aptos_types::chain_id::ChainId;
+    use warp::Filter;
 
     use crate::{
-        runtime::bootstrap,
+        runtime::{bootstrap, WebServer},
         tests::{new_test_context, TestContext},
     };
 
@@ -112,6 +166,62 @@ mod tests {
         bootstrap_with_config(cfg);
     }
 
+    #[test]
+    fn test_bootstrap_serves_every_address() {
+        let mut cfg = NodeConfig::default();
+        cfg.randomize_ports();
+        let port = cfg.api.address.port();
+        if StdTcpListener::bind(("::1", port)).is_ok() {
+            cfg.api.additional_addresses = vec![SocketAddr::new("::1".parse().unwrap(), port)];
+        } else {
+            eprintln!("IPv6 is unavailable, only testing IPv4");
+        }
+        bootstrap_with_config(cfg.clone());
+
+        let client = reqwest::blocking::Client::new();
+        for address in cfg.api.addresses() {
+            let resp = client.get(format!("http://{}", address)).send().unwrap();
+            assert_eq!(resp.status(), 200);
+        }
+    }
+
+    #[test]
+    fn test_failing_to_bind_any_address_is_fatal() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        // An address from TEST-NET-1, which is never assigned to a local interface.
+        let api = WebServer::new(
+            vec![
+                "127.0.0.1:0".parse().unwrap(),
+                "192.0.2.1:8080".parse().unwrap(),
+            ],
+            None,
+            None,
+        );
+        let err = api.bind(warp::any().map(warp::reply)).err().unwrap();
+        assert!(format!("{:#}", err).contains("Failed to bind the API to address 192.0.2.1:8080"));
+    }
+
+    #[test]
+    fn test_log_filter_is_not_proxied() {
+        let mut cfg = NodeConfig::default();
+        cfg.randomize_ports();
+        let port = cfg.api.address.port();
+        bootstrap_with_config(cfg);
+
+        let url = format!("http://localhost:{}/v1/log/filter", port);
+        let client = reqwest::blocking::Client::new();
+        let resp = client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .body(r#"{"filter": "trace"}"#)
+            .send()
+            .unwrap();
+        assert_eq!(resp.status(), 403);
+        let resp = client.get(&url).send().unwrap();
+        assert_eq!(resp.status(), 403);
+    }
+
     pub fn bootstrap_with_config(cfg: NodeConfig) {
         let runtime = tokio::runtime::Runtime::new().unwrap();
         let context = runtime.block_on(new_test_context_async(
@@ -122,6 +232,7 @@ mod tests {
             ChainId::test(),
             context.db.clone(),
             context.mempool.ac_client.clone(),
+            context.context.sync_status_handle().clone(),
         );
         assert!(ret.is_ok());
 
@@ -0,0 +1,214 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::metrics::SUBMISSIONS_RATE_LIMITED;
+use aptos_config::config::ApiConfig;
+use aptos_infallible::Mutex;
+use aptos_types::account_address::AccountAddress;
+use lru::LruCache;
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use warp::reject::Reject;
+
+/// Limits how fast each sender may submit transactions, with a token bucket
+/// per sender. This stops a single client from flooding mempool through
+/// many source addresses, and it runs before the transaction is sent to
+/// mempool, so rejected submissions cost mempool nothing.
+///
+/// Only the most recently seen senders are tracked, so a sender that is
+/// forgotten starts over with a full burst.
+#[derive(Clone)]
+pub struct SenderRateLimiter {
+    rate_per_sec: f64,
+    burst: f64,
+    buckets: Option<Arc<Mutex<LruCache<AccountAddress, Bucket>>>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl SenderRateLimiter {
+    /// Creates a limiter letting each sender submit `burst` transactions at
+    /// once, and `rate_per_sec` on average after that. A `burst` of 0
+    /// disables the limiter.
+    pub fn new(rate_per_sec: f64, burst: u64, max_senders: usize) -> Self {
+        let buckets = if burst == 0 {
+            None
+        } else {
+            Some(Arc::new(Mutex::new(LruCache::new(max_senders.max(1)))))
+        };
+        Self {
+            rate_per_sec,
+            burst: burst as f64,
+            buckets,
+        }
+    }
+
+    pub fn from_config(config: &ApiConfig) -> Self {
+        Self::new(
+            config.sender_submission_rate_per_sec,
+            config.sender_submission_burst,
+            config.sender_rate_limit_max_senders,
+        )
+    }
+
+    /// Takes a token from the sender's bucket, or says how long until one
+    /// is available.
+    pub fn check(&self, sender: AccountAddress) -> Result<(), SenderRateLimited> {
+        let result = self.check_at(sender, Instant::now());
+        if result.is_err() {
+            SUBMISSIONS_RATE_LIMITED.inc();
+        }
+        result
+    }
+
+    fn check_at(&self, sender: AccountAddress, now: Instant) -> Result<(), SenderRateLimited> {
+        let buckets = match &self.buckets {
+            Some(buckets) => buckets,
+            None => return Ok(()),
+        };
+        let mut buckets = buckets.lock();
+        if buckets.get(&sender).is_none() {
+            buckets.put(
+                sender,
+                Bucket {
+                    tokens: self.burst,
+                    last_refill: now,
+                },
+            );
+        }
+        let bucket = buckets.get_mut(&sender).expect("the bucket was just added");
+
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * self.rate_per_sec).min(self.burst);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        // Tiny rates would need a longer wait than a `Duration` can hold, so
+        // they are treated like a rate of 0, which never refills.
+        let retry_after_secs = (1.0 - bucket.tokens) / self.rate_per_sec;
+        let retry_after = if (0.0..(u64::MAX / 2) as f64).contains(&retry_after_secs) {
+            Duration::from_secs_f64(retry_after_secs)
+        } else {
+            Duration::MAX
+        };
+        Err(SenderRateLimited {
+            sender,
+            retry_after,
+        })
+    }
+}
+
+/// Rejection for a submission whose sender is over its rate limit.
+#[derive(Debug)]
+pub struct SenderRateLimited {
+    pub sender: AccountAddress,
+    pub retry_after: Duration,
+}
+
+impl SenderRateLimited {
+    /// The value of the `Retry-After` header, in whole seconds.
+    pub fn retry_after_secs(&self) -> u64 {
+        let secs = self.retry_after.as_secs();
+        if self.retry_after.subsec_nanos() > 0 {
+            secs.saturating_add(1)
+        } else {
+            secs
+        }
+    }
+}
+
+impl fmt::Display for SenderRateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Sender {} is submitting transactions too fast, retry after {}s",
+            self.sender.to_hex_literal(),
+            self.retry_after_secs()
+        )
+    }
+}
+
+impl Reject for SenderRateLimited {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sender(byte: u8) -> AccountAddress {
+        AccountAddress::new([byte; AccountAddress::LENGTH])
+    }
+
+    #[test]
+    fn test_burst_then_rate() {
+        let limiter = SenderRateLimiter::new(2.0, 3, 10);
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.check_at(sender(1), start).unwrap();
+        }
+        let limited = limiter.check_at(sender(1), start).unwrap_err();
+        assert_eq!(limited.sender, sender(1));
+        assert_eq!(limited.retry_after, Duration::from_millis(500));
+        assert_eq!(limited.retry_after_secs(), 1);
+
+        // Two tokens a second come back, but never more than the burst.
+        let later = start + Duration::from_millis(500);
+        limiter.check_at(sender(1), later).unwrap();
+        limiter.check_at(sender(1), later).unwrap_err();
+        let much_later = start + Duration::from_secs(60);
+        for _ in 0..3 {
+            limiter.check_at(sender(1), much_later).unwrap();
+        }
+        limiter.check_at(sender(1), much_later).unwrap_err();
+    }
+
+    #[test]
+    fn test_senders_are_limited_separately() {
+        let limiter = SenderRateLimiter::new(1.0, 1, 10);
+        let now = Instant::now();
+        limiter.check_at(sender(1), now).unwrap();
+        limiter.check_at(sender(1), now).unwrap_err();
+        limiter.check_at(sender(2), now).unwrap();
+    }
+
+    #[test]
+    fn test_least_recently_seen_senders_are_forgotten() {
+        let limiter = SenderRateLimiter::new(1.0, 1, 2);
+        let now = Instant::now();
+        limiter.check_at(sender(1), now).unwrap();
+        limiter.check_at(sender(2), now).unwrap();
+        limiter.check_at(sender(3), now).unwrap();
+        // Sender 1 was evicted by sender 3, so it starts over.
+        limiter.check_at(sender(1), now).unwrap();
+        limiter.check_at(sender(3), now).unwrap_err();
+    }
+
+    #[test]
+    fn test_tiny_or_zero_rates_never_refill() {
+        for rate in [1e-20, 0.0, -1.0, f64::NAN].iter() {
+            let limiter = SenderRateLimiter::new(*rate, 1, 10);
+            let now = Instant::now();
+            limiter.check_at(sender(1), now).unwrap();
+            let limited = limiter.check_at(sender(1), now).unwrap_err();
+            assert_eq!(limited.retry_after, Duration::MAX);
+            assert_eq!(limited.retry_after_secs(), u64::MAX);
+        }
+    }
+
+    #[test]
+    fn test_zero_burst_disables_the_limiter() {
+        let limiter = SenderRateLimiter::new(1.0, 0, 10);
+        let now = Instant::now();
+        for _ in 0..100 {
+            limiter.check_at(sender(1), now).unwrap();
+        }
+    }
+}
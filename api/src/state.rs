@@ -2,17 +2,20 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
+    accept_type::AcceptType,
+    body,
     context::Context,
     failpoint::fail_point,
     metrics::metrics,
     param::{
         AddressParam, LedgerVersionParam, MoveIdentifierParam, MoveStructTagParam, TableHandleParam,
     },
-    version::Version,
+    version::{StateReadCaching, Version},
 };
 use anyhow::anyhow;
 use aptos_api_types::{
-    AsConverter, Error, LedgerInfo, MoveModuleBytecode, Response, TableItemRequest, TransactionId,
+    mime_types::{BCS, JSON},
+    AsConverter, Error, LedgerInfo, MoveModuleBytecode, TableItemRequest,
 };
 use aptos_state_view::StateView;
 use aptos_types::state_store::table::TableHandle;
@@ -25,123 +28,220 @@ use move_deps::move_core_types::{
 };
 use std::convert::TryInto;
 use storage_interface::state_view::DbStateView;
-use warp::{filters::BoxedFilter, Filter, Rejection, Reply};
+use warp::{
+    filters::BoxedFilter,
+    http::header::{ACCEPT, IF_NONE_MATCH},
+    Filter, Rejection, Reply,
+};
 
 // GET /accounts/<address>/resource/<resource_type>
-pub fn get_account_resource(context: Context) -> BoxedFilter<(impl Reply,)> {
+pub fn get_json_account_resource(context: Context) -> BoxedFilter<(impl Reply,)> {
     warp::path!("accounts" / AddressParam / "resource" / MoveStructTagParam)
         .and(warp::get())
         .and(context.filter())
         .and(warp::query::<Version>())
-        .map(|address, struct_tag, ctx, version: Version| {
-            (version.version, address, struct_tag, ctx)
-        })
+        .and(warp::header::optional::<String>(IF_NONE_MATCH.as_str()))
+        .map(
+            |address, struct_tag, ctx, version: Version, if_none_match| {
+                (
+                    version.ledger_version(),
+                    if_none_match,
+                    address,
+                    struct_tag,
+                    ctx,
+                    AcceptType::Json,
+                )
+            },
+        )
         .untuple_one()
         .and_then(handle_get_account_resource)
         .with(metrics("get_account_resource"))
         .boxed()
 }
 
+// GET /accounts/<address>/resource/<resource_type>
+pub fn get_bcs_account_resource(context: Context) -> BoxedFilter<(impl Reply,)> {
+    warp::path!("accounts" / AddressParam / "resource" / MoveStructTagParam)
+        .and(warp::get())
+        .and(warp::header::exact_ignore_case(ACCEPT.as_str(), BCS))
+        .and(context.filter())
+        .and(warp::query::<Version>())
+        .and(warp::header::optional::<String>(IF_NONE_MATCH.as_str()))
+        .map(
+            |address, struct_tag, ctx, version: Version, if_none_match| {
+                (
+                    version.ledger_version(),
+                    if_none_match,
+                    address,
+                    struct_tag,
+                    ctx,
+                    AcceptType::Bcs,
+                )
+            },
+        )
+        .untuple_one()
+        .and_then(handle_get_account_resource)
+        .with(metrics("get_bcs_account_resource"))
+        .boxed()
+}
+
 // GET /state/module/<address>/<module_name>
 pub fn get_account_module(context: Context) -> BoxedFilter<(impl Reply,)> {
     warp::path!("accounts" / AddressParam / "module" / MoveIdentifierParam)
         .and(warp::get())
         .and(context.filter())
         .and(warp::query::<Version>())
-        .map(|address, name, ctx, version: Version| (version.version, address, name, ctx))
+        .and(warp::header::optional::<String>(IF_NONE_MATCH.as_str()))
+        .map(|address, name, ctx, version: Version, if_none_match| {
+            (version.ledger_version(), if_none_match, address, name, ctx)
+        })
         .untuple_one()
         .and_then(handle_get_account_module)
         .with(metrics("get_account_module"))
         .boxed()
 }
 
-// GET /tables/<table_handle>/item
-pub fn get_table_item(context: Context) -> BoxedFilter<(impl Reply,)> {
+// POST /tables/<table_handle>/item
+pub fn get_json_table_item(context: Context) -> BoxedFilter<(impl Reply,)> {
     warp::path!("tables" / TableHandleParam / "item")
         .and(warp::post())
-        .and(warp::body::content_length_limit(
-            context.content_length_limit(),
+        .and(body::json_body::<TableItemRequest>(
+            &[JSON],
+            context.table_item_content_length_limit(),
         ))
-        .and(warp::body::json::<TableItemRequest>())
         .and(context.filter())
         .and(warp::query::<Version>())
-        .map(|handle, body, ctx, version: Version| (version.version, handle, body, ctx))
+        .and(warp::header::optional::<String>(IF_NONE_MATCH.as_str()))
+        .map(|handle, body, ctx, version: Version, if_none_match| {
+            (
+                version.ledger_version(),
+                if_none_match,
+                handle,
+                body,
+                ctx,
+                AcceptType::Json,
+            )
+        })
         .untuple_one()
         .and_then(handle_get_table_item)
         .with(metrics("get_table_item"))
         .boxed()
 }
 
+// POST /tables/<table_handle>/item
+pub fn get_bcs_table_item(context: Context) -> BoxedFilter<(impl Reply,)> {
+    // The Accept header is checked before the body is read, so JSON
+    // requests are left for `get_json_table_item`.
+    warp::path!("tables" / TableHandleParam / "item")
+        .and(warp::post())
+        .and(warp::header::exact_ignore_case(ACCEPT.as_str(), BCS))
+        .and(body::json_body::<TableItemRequest>(
+            &[JSON],
+            context.table_item_content_length_limit(),
+        ))
+        .and(context.filter())
+        .and(warp::query::<Version>())
+        .and(warp::header::optional::<String>(IF_NONE_MATCH.as_str()))
+        .map(|handle, body, ctx, version: Version, if_none_match| {
+            (
+                version.ledger_version(),
+                if_none_match,
+                handle,
+                body,
+                ctx,
+                AcceptType::Bcs,
+            )
+        })
+        .untuple_one()
+        .and_then(handle_get_table_item)
+        .with(metrics("get_bcs_table_item"))
+        .boxed()
+}
+
 async fn handle_get_account_resource(
     ledger_version: Option<LedgerVersionParam>,
+    if_none_match: Option<String>,
     address: AddressParam,
     struct_tag: MoveStructTagParam,
     context: Context,
+    accept_type: AcceptType,
 ) -> anyhow::Result<impl Reply, Rejection> {
     fail_point("endpoint_query_resource")?;
     let struct_tag = struct_tag.parse("struct tag")?;
-    Ok(State::new(ledger_version, context)?.resource(
-        address.parse("account address")?.into(),
+    let caching = StateReadCaching::new(&ledger_version, if_none_match);
+    Ok(State::new(ledger_version, caching, context)?.resource(
+        address.parse_address("account address")?.into(),
         struct_tag
             .clone()
             .try_into()
             .map_err(|_| Error::invalid_param("resource_type", struct_tag))?,
+        accept_type,
     )?)
 }
 
 async fn handle_get_account_module(
     ledger_version: Option<LedgerVersionParam>,
+    if_none_match: Option<String>,
     address: AddressParam,
     name: MoveIdentifierParam,
     context: Context,
 ) -> anyhow::Result<impl Reply, Rejection> {
     fail_point("endpoint_get_account_module")?;
-    Ok(State::new(ledger_version, context)?.module(
-        address.parse("account address")?.into(),
+    let caching = StateReadCaching::new(&ledger_version, if_none_match);
+    Ok(State::new(ledger_version, caching, context)?.module(
+        address.parse_address("account address")?.into(),
         name.parse("module name")?,
     )?)
 }
 
 async fn handle_get_table_item(
     ledger_version: Option<LedgerVersionParam>,
+    if_none_match: Option<String>,
     handle: TableHandleParam,
     body: TableItemRequest,
     context: Context,
+    accept_type: AcceptType,
 ) -> Result<impl Reply, Rejection> {
     fail_point("endpoint_get_table_item")?;
-    Ok(State::new(ledger_version, context)?.table_item(handle.parse("table handle")?, body)?)
+    let caching = StateReadCaching::new(&ledger_version, if_none_match);
+    Ok(State::new(ledger_version, caching, context)?.table_item(
+        handle.parse("table handle")?,
+        body,
+        accept_type,
+    )?)
 }
 
 pub(crate) struct State {
     state_view: DbStateView,
     ledger_version: aptos_types::transaction::Version,
-    latest_ledger_info: LedgerInfo,
+    // The ledger info as of `ledger_version`
+    ledger_info: LedgerInfo,
+    caching: StateReadCaching,
+    max_json_render_bytes: u64,
+    max_bcs_render_bytes: u64,
 }
 
 impl State {
     pub fn new(
         ledger_version: Option<LedgerVersionParam>,
+        caching: StateReadCaching,
         context: Context,
     ) -> Result<Self, Error> {
-        let latest_ledger_info = context.get_latest_ledger_info()?;
         let ledger_version = ledger_version
             .map(|v| v.parse("ledger version"))
-            .unwrap_or_else(|| Ok(latest_ledger_info.version()))?;
-
-        if ledger_version > latest_ledger_info.version() {
-            return Err(Error::not_found(
-                "ledger",
-                TransactionId::Version(ledger_version),
-                latest_ledger_info.version(),
-            ));
-        }
+            .transpose()?;
+        let ledger_info = context.get_ledger_info_at_version(ledger_version)?;
+        let ledger_version = ledger_info.version();
 
         let state_view = context.state_view_at_version(ledger_version)?;
 
         Ok(Self {
             state_view,
             ledger_version,
-            latest_ledger_info,
+            ledger_info,
+            caching,
+            max_json_render_bytes: context.max_json_render_bytes(),
+            max_bcs_render_bytes: context.max_bcs_render_bytes(),
         })
     }
 
@@ -149,6 +249,7 @@ impl State {
         self,
         address: AccountAddress,
         struct_tag: StructTag,
+        accept_type: AcceptType,
     ) -> Result<impl Reply, Error> {
         let resource_key = ResourceKey::new(address, struct_tag.clone());
         let access_path = AccessPath::resource_access_path(resource_key.clone());
@@ -157,13 +258,26 @@ impl State {
             .state_view
             .get_state_value(&state_key)?
             .ok_or_else(|| Error::not_found("Resource", resource_key, self.ledger_version))?;
+        if accept_type == AcceptType::Bcs {
+            return self.caching.respond_bcs(
+                self.ledger_info,
+                bytes,
+                self.max_bcs_render_bytes,
+                "Resource",
+            );
+        }
 
         let resource = self
             .state_view
             .as_move_resolver()
             .as_converter()
             .try_into_resource(&struct_tag, &bytes)?;
-        Response::new(self.latest_ledger_info, &resource)
+        self.caching.respond_with_limit(
+            self.ledger_info,
+            &resource,
+            self.max_json_render_bytes,
+            "Resource",
+        )
     }
 
     pub fn module(self, address: AccountAddress, name: Identifier) -> Result<impl Reply, Error> {
@@ -178,13 +292,14 @@ impl State {
         let module = MoveModuleBytecode::new(bytes)
             .try_parse_abi()
             .map_err(Error::internal)?;
-        Response::new(self.latest_ledger_info, &module)
+        self.caching.respond(self.ledger_info, &module)
     }
 
     pub fn table_item(
         self,
         handle: TableHandle,
         body: TableItemRequest,
+        accept_type: AcceptType,
     ) -> Result<impl Reply, Error> {
         let TableItemRequest {
             key_type,
@@ -210,8 +325,21 @@ impl State {
             .state_view
             .get_state_value(&state_key)?
             .ok_or_else(|| Error::not_found("table handle or item", key, self.ledger_version))?;
+        if accept_type == AcceptType::Bcs {
+            return self.caching.respond_bcs(
+                self.ledger_info,
+                bytes,
+                self.max_bcs_render_bytes,
+                "Table item",
+            );
+        }
 
         let move_value = converter.try_into_move_value(&value_type, &bytes)?;
-        Response::new(self.latest_ledger_info, &move_value)
+        self.caching.respond_with_limit(
+            self.ledger_info,
+            &move_value,
+            self.max_json_render_bytes,
+            "Table item",
+        )
     }
 }
@@ -5,6 +5,7 @@ use crate::{
     current_function_name,
     tests::{find_value, new_test_context},
 };
+use aptos_api_types::X_APTOS_LEDGER_VERSION;
 use serde_json::json;
 
 #[tokio::test]
@@ -29,9 +30,13 @@ async fn test_get_account_resources_by_address_0x0() {
 }
 
 #[tokio::test]
-async fn test_get_account_resources_by_invalid_address_missing_0x_prefix() {
+async fn test_get_account_resources_by_invalid_address() {
     let mut context = new_test_context(current_function_name!());
-    let invalid_addresses = vec!["1", "0xzz", "01"];
+    let invalid_addresses = vec![
+        "0xzz",
+        "0x",
+        "0x00000000000000000000000000000000000000000000000000000000000000001",
+    ];
     for invalid_address in &invalid_addresses {
         let resp = context
             .expect_status_code(400)
@@ -44,9 +49,23 @@ async fn test_get_account_resources_by_invalid_address_missing_0x_prefix() {
 #[tokio::test]
 async fn test_get_account_resources_by_valid_account_address() {
     let context = new_test_context(current_function_name!());
-    let addresses = vec!["0x1", "0x00000000000000000000000000000001"];
+    let addresses = vec![
+        "0x1",
+        "0x00000000000000000000000000000001",
+        "1",
+        "01",
+        "0x0000000000000000000000000000000000000000000000000000000000000001",
+        "0000000000000000000000000000000000000000000000000000000000000001",
+    ];
+    let expected = context.get(&account_resources("0x1")).await;
     for address in &addresses {
-        context.get(&account_resources(address)).await;
+        assert_eq!(context.get(&account_resources(address)).await, expected);
+    }
+
+    // Mixed case is the same address
+    let expected = context.get(&account_resources("0xa550c18")).await;
+    for address in &["0xA550C18", "A550c18"] {
+        assert_eq!(context.get(&account_resources(address)).await, expected);
     }
 }
 
@@ -134,6 +153,42 @@ async fn test_get_account_resources_by_ledger_version() {
     assert_eq!(root_account["data"]["sequence_number"], "0");
 }
 
+#[tokio::test]
+async fn test_get_core_account_data_by_ledger_version() {
+    let mut context = new_test_context(current_function_name!());
+    let account = context.gen_account();
+    let txn = context.create_user_account(&account);
+    context.commit_block(&vec![txn.clone()]).await;
+    let root_address = context.root_account().address().to_hex_literal();
+
+    let resp = context.get(&format!("/accounts/{}", root_address)).await;
+    assert_eq!(resp["sequence_number"], "1");
+
+    let resp = context
+        .get(&format!("/accounts/{}?ledger_version=0", root_address))
+        .await;
+    assert_eq!(resp["sequence_number"], "0");
+}
+
+#[tokio::test]
+async fn test_ledger_version_param_takes_precedence_over_version() {
+    let mut context = new_test_context(current_function_name!());
+    let account = context.gen_account();
+    let txn = context.create_user_account(&account);
+    context.commit_block(&vec![txn.clone()]).await;
+    let latest_version = context.get_latest_ledger_info().version();
+
+    let resp = context
+        .reply(warp::test::request().method("GET").path(&format!(
+            "{}?version={}&ledger_version=0",
+            account_resources(&context.root_account().address().to_hex_literal()),
+            latest_version,
+        )))
+        .await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()[X_APTOS_LEDGER_VERSION], "0");
+}
+
 #[tokio::test]
 async fn test_get_account_resources_by_ledger_version_is_too_large() {
     let mut context = new_test_context(current_function_name!());
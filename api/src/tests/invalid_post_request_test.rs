@@ -47,7 +47,7 @@ async fn test_invalid_script_function_argument_address_string() {
 #[tokio::test]
 async fn test_missing_script_function_arguments() {
     let mut req = signing_message_request();
-    req["payload"]["arguments"] = json!(["0"]);
+    req["payload"]["arguments"] = json!([]);
 
     response_error_msg(req, current_function_name!()).await;
 }
@@ -14,7 +14,7 @@ mod transaction_vector_test;
 mod transactions_test;
 
 use serde_json::Value;
-pub use test_context::{new_test_context, TestContext};
+pub use test_context::{new_test_context, new_test_context_with_config, TestContext};
 
 pub fn find_value(val: &Value, filter: for<'r> fn(&'r &Value) -> bool) -> Value {
     let resources = val
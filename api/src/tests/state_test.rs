@@ -3,16 +3,22 @@
 
 use crate::{
     current_function_name,
-    tests::{new_test_context, TestContext},
+    tests::{new_test_context, new_test_context_with_config, TestContext},
 };
+use aptos_api_types::{
+    mime_types::BCS, IMMUTABLE_CACHE_CONTROL, UNCACHEABLE_CACHE_CONTROL, X_APTOS_LEDGER_VERSION,
+};
+use aptos_config::config::NodeConfig;
 use aptos_sdk::move_types::parser::parse_type_tag;
 use aptos_sdk::types::LocalAccount;
 use aptos_types::state_store::table::TableHandle;
 use move_deps::{move_core_types::account_address::AccountAddress, move_package::BuildConfig};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 use serde::Serialize;
 use serde_json::{json, Value};
 use std::{convert::TryInto, path::PathBuf};
 use storage_interface::DbReader;
+use warp::http::header::{ACCEPT, CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH};
 
 #[tokio::test]
 async fn test_get_account_resource() {
@@ -23,10 +29,99 @@ async fn test_get_account_resource() {
     context.check_golden_output(resp);
 }
 
+#[tokio::test]
+async fn test_get_account_resource_at_historical_ledger_versions() {
+    let mut context = new_test_context(current_function_name!());
+    let account = context.gen_account();
+    let factory = context.transaction_factory();
+    let mut root_account = context.root_account();
+
+    let create_account_txn = context.create_user_account_by(&mut root_account, &account);
+    let mint_txn =
+        root_account.sign_with_transaction_builder(factory.mint(account.address(), 1000));
+    context.commit_block(&[create_account_txn, mint_txn]).await;
+    let first_version = context.get_latest_ledger_info().version();
+
+    let mint_txn = root_account.sign_with_transaction_builder(factory.mint(account.address(), 500));
+    context.commit_block(&[mint_txn]).await;
+    let second_version = context.get_latest_ledger_info().version();
+
+    let first_balance = get_balance_at_version(&context, &account, first_version).await;
+    let second_balance = get_balance_at_version(&context, &account, second_version).await;
+    assert!(first_balance >= 1000);
+    assert_eq!(second_balance, first_balance + 500);
+}
+
+#[tokio::test]
+async fn test_get_account_resource_is_only_cacheable_at_explicit_ledger_version() {
+    let context = new_test_context(current_function_name!());
+    let path = get_account_resource("0xA550C18", "0x1::guid::Generator");
+
+    let resp = context
+        .reply(warp::test::request().method("GET").path(&path))
+        .await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()[CACHE_CONTROL], UNCACHEABLE_CACHE_CONTROL);
+    assert!(resp.headers().get(ETAG).is_none());
+
+    let version = context.get_latest_ledger_info().version();
+    let path = format!("{}?ledger_version={}", path, version);
+    let resp = context
+        .reply(warp::test::request().method("GET").path(&path))
+        .await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()[CACHE_CONTROL], IMMUTABLE_CACHE_CONTROL);
+    let etag = resp.headers()[ETAG].to_str().unwrap().to_owned();
+
+    let resp = context
+        .reply(
+            warp::test::request()
+                .method("GET")
+                .path(&path)
+                .header(IF_NONE_MATCH, &etag),
+        )
+        .await;
+    assert_eq!(resp.status(), 304);
+    assert!(resp.body().is_empty());
+    assert_eq!(resp.headers()[X_APTOS_LEDGER_VERSION], version.to_string());
+}
+
+async fn get_balance_at_version(
+    context: &TestContext,
+    account: &LocalAccount,
+    version: u64,
+) -> u64 {
+    let path = format!(
+        "{}?ledger_version={}",
+        get_account_resource(
+            &account.address().to_hex_literal(),
+            &utf8_percent_encode(
+                "0x1::coin::CoinStore<0x1::aptos_coin::AptosCoin>",
+                NON_ALPHANUMERIC
+            )
+            .to_string(),
+        ),
+        version
+    );
+    let resp = context
+        .reply(warp::test::request().method("GET").path(&path))
+        .await;
+    assert_eq!(resp.status(), 200);
+    // The ledger headers describe the version that was read, not the latest
+    assert_eq!(resp.headers()[X_APTOS_LEDGER_VERSION], version.to_string());
+
+    let resource: Value = serde_json::from_slice(resp.body()).unwrap();
+    resource["data"]["coin"]["value"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
 #[tokio::test]
 async fn test_get_account_resource_by_invalid_address() {
     let mut context = new_test_context(current_function_name!());
-    let invalid_addresses = vec!["1", "0xzz", "01"];
+    let invalid_addresses = vec!["0xzz", "0x", "0x1g"];
     for invalid_address in &invalid_addresses {
         let resp = context
             .expect_status_code(400)
@@ -81,7 +176,7 @@ async fn test_get_account_module_by_invalid_address() {
     let mut context = new_test_context(current_function_name!());
     let resp = context
         .expect_status_code(400)
-        .get(&get_account_module("1", "guid"))
+        .get(&get_account_module("0xzz", "guid"))
         .await;
     context.check_golden_output(resp);
 }
@@ -128,7 +223,7 @@ async fn test_get_table_item() {
         "address",
         "address",
         "0x1",
-        "0x1",
+        "0x0000000000000000000000000000000000000000000000000000000000000001",
     )
     .await;
     assert_table_item(
@@ -183,6 +278,116 @@ async fn test_get_table_item() {
     assert_table_item(ctx, &nested_table, "u8", "u8", 2, 3).await;
 }
 
+#[tokio::test]
+async fn test_get_table_item_rejects_non_json_content_type() {
+    let mut context = new_test_context(current_function_name!());
+    let req = warp::test::request()
+        .header("content-type", "application/x.aptos.signed_transaction+bcs")
+        .method("POST")
+        .body("key")
+        .path(&get_table_item(1));
+
+    let resp = context.expect_status_code(415).execute(req).await;
+    context.check_golden_output(resp);
+}
+
+#[tokio::test]
+async fn test_get_table_item_rejects_payload_too_large() {
+    let mut context = new_test_context(current_function_name!());
+    let limit = context.context.table_item_content_length_limit() as usize;
+    let resp = context
+        .expect_status_code(413)
+        .post(
+            &get_table_item(1),
+            json!({
+                "key_type": "vector<u8>",
+                "value_type": "u8",
+                "key": "0".repeat(limit),
+            }),
+        )
+        .await;
+    context.check_golden_output(resp);
+}
+
+#[tokio::test]
+async fn test_resource_and_table_item_too_large_for_json_are_returned_as_bcs() {
+    let mut node_config = NodeConfig::default();
+    node_config.api.max_json_render_bytes = 1_000;
+    node_config.api.max_bcs_render_bytes = 100_000;
+    let mut context = new_test_context_with_config(current_function_name!(), node_config);
+    let ctx = &mut context;
+    let mut account = ctx.gen_account();
+    let acc = &mut account;
+    let txn = ctx.create_user_account(acc);
+    ctx.commit_block(&vec![txn.clone()]).await;
+    let module = build_test_module(acc.address()).await;
+    ctx.api_publish_module(acc, module.try_into().unwrap())
+        .await;
+    ctx.api_execute_script_function(
+        acc,
+        "TableTestData::make_large_resource",
+        json!([]),
+        json!(["1000"]),
+    )
+    .await;
+    let data: Vec<u64> = (0..1000).collect();
+
+    let resource_path = get_account_resource(
+        &acc.address().to_hex_literal(),
+        &format!(
+            "{}::TableTestData::LargeResource",
+            acc.address().to_hex_literal()
+        ),
+    );
+    let resp = ctx.expect_status_code(413).get(&resource_path).await;
+    assert_eq!(
+        resp["message"],
+        "Resource too large to render as JSON (more than 1000 bytes), request it as BCS instead"
+    );
+
+    let resp = ctx
+        .reply(
+            warp::test::request()
+                .method("GET")
+                .header(ACCEPT, BCS)
+                .path(&resource_path),
+        )
+        .await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()[CONTENT_TYPE], BCS);
+    // The table is its handle and length.
+    let (resource_data, handle, _length): (Vec<u64>, u128, u64) =
+        bcs::from_bytes(resp.body()).unwrap();
+    assert_eq!(resource_data, data);
+
+    let table_item = json!({
+        "key_type": "u8",
+        "value_type": "vector<u64>",
+        "key": 1,
+    });
+    let resp = ctx
+        .expect_status_code(413)
+        .post(&get_table_item(handle), table_item.clone())
+        .await;
+    assert_eq!(
+        resp["message"],
+        "Table item too large to render as JSON (more than 1000 bytes), request it as BCS instead"
+    );
+
+    let resp = ctx
+        .reply(
+            warp::test::request()
+                .method("POST")
+                .header(ACCEPT, BCS)
+                .path(&get_table_item(handle))
+                .json(&table_item),
+        )
+        .await;
+    assert_eq!(resp.status(), 200);
+    let item: Vec<u64> = bcs::from_bytes(resp.body()).unwrap();
+    assert_eq!(item, data);
+}
+
 fn get_account_resource(address: &str, struct_tag: &str) -> String {
     format!("/accounts/{}/resource/{}", address, struct_tag)
 }
@@ -44,12 +44,20 @@ use aptos_config::keys::ConfigKey;
 use aptos_crypto::ed25519::Ed25519PrivateKey;
 use rand::SeedableRng;
 use serde_json::{json, Value};
+use state_sync_driver::sync_status::SyncStatusHandle;
 use std::{boxed::Box, collections::BTreeMap, iter::once, sync::Arc};
 use storage_interface::state_view::DbStateView;
 use vm_validator::vm_validator::VMValidator;
 use warp::http::header::CONTENT_TYPE;
 
 pub fn new_test_context(test_name: &'static str) -> TestContext {
+    new_test_context_with_config(test_name, NodeConfig::default())
+}
+
+pub fn new_test_context_with_config(
+    test_name: &'static str,
+    node_config: NodeConfig,
+) -> TestContext {
     let tmp_dir = TempPath::new();
     tmp_dir.create_as_dir().unwrap();
 
@@ -91,7 +99,8 @@ pub fn new_test_context(test_name: &'static str) -> TestContext {
             ChainId::test(),
             db.clone(),
             mempool.ac_client.clone(),
-            NodeConfig::default(),
+            node_config,
+            SyncStatusHandle::new(),
         ),
         rng,
         root_key,
@@ -260,7 +269,7 @@ impl TestContext {
             compute_status.pop();
         }
         // But the rest of the txns must be Kept.
-        for st in result.compute_status() {
+        for st in &compute_status {
             match st {
                 TransactionStatus::Discard(st) => panic!("transaction is discarded: {:?}", st),
                 TransactionStatus::Retry => panic!("should not retry"),
@@ -271,7 +280,7 @@ impl TestContext {
         self.executor
             .commit_blocks(
                 vec![metadata.id()],
-                self.new_ledger_info(&metadata, result.root_hash(), txns.len()),
+                self.new_ledger_info(&metadata, result.root_hash(), compute_status.len()),
             )
             .unwrap();
 
@@ -402,12 +411,20 @@ impl TestContext {
         );
 
         if self.expect_status_code < 300 {
-            let ledger_info = self.get_latest_ledger_info();
+            // Reads at a historical ledger version report that version in
+            // their ledger headers, so we check the headers against the
+            // ledger info as of the version they report.
+            let version: u64 = headers[X_APTOS_LEDGER_VERSION]
+                .to_str()
+                .unwrap()
+                .parse()
+                .unwrap();
+            assert!(version <= self.get_latest_ledger_info().version());
+            let ledger_info = self
+                .context
+                .get_ledger_info_at_version(Some(version))
+                .unwrap();
             assert_eq!(headers[X_APTOS_CHAIN_ID], "4");
-            assert_eq!(
-                headers[X_APTOS_LEDGER_VERSION],
-                ledger_info.version().to_string()
-            );
             assert_eq!(
                 headers[X_APTOS_LEDGER_TIMESTAMP],
                 ledger_info.timestamp().to_string()
@@ -3,10 +3,14 @@
 
 use crate::{
     current_function_name,
-    tests::{assert_json, new_test_context, pretty, TestContext},
+    tests::{assert_json, new_test_context, new_test_context_with_config, pretty, TestContext},
 };
 
-use aptos_api_types::HexEncodedBytes;
+use aptos_api_types::{
+    mime_types::BCS_SIGNED_TRANSACTION, HexEncodedBytes, IMMUTABLE_CACHE_CONTROL,
+    UNCACHEABLE_CACHE_CONTROL, X_APTOS_LEDGER_VERSION, X_APTOS_PAGE_SIZE,
+};
+use aptos_config::config::NodeConfig;
 use aptos_crypto::{
     multi_ed25519::{MultiEd25519PrivateKey, MultiEd25519PublicKey},
     PrivateKey, SigningKey, Uniform,
@@ -30,7 +34,8 @@ use move_deps::move_core_types::{
     language_storage::{ModuleId, StructTag, TypeTag},
 };
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
-use serde_json::json;
+use serde_json::{json, Value};
+use warp::http::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH, RETRY_AFTER};
 
 #[tokio::test]
 async fn test_deserialize_genesis_transaction() {
@@ -105,12 +110,39 @@ async fn test_get_transactions_with_zero_limit() {
 
 #[tokio::test]
 async fn test_get_transactions_param_limit_exceeds_limit() {
-    let mut context = new_test_context(current_function_name!());
+    let context = new_test_context(current_function_name!());
     let resp = context
-        .expect_status_code(400)
-        .get("/transactions?limit=2000")
+        .reply(
+            warp::test::request()
+                .method("GET")
+                .path("/transactions?limit=2000"),
+        )
         .await;
-    context.check_golden_output(resp);
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()[X_APTOS_PAGE_SIZE], "1000");
+}
+
+#[tokio::test]
+async fn test_get_transactions_limit_is_clamped_to_max_page_size() {
+    let mut node_config = NodeConfig::default();
+    node_config.api.max_page_size = 2;
+    let mut context = new_test_context_with_config(current_function_name!(), node_config);
+    context.commit_block(&[]).await;
+
+    let resp = context
+        .reply(
+            warp::test::request()
+                .method("GET")
+                .path("/transactions?start=0&limit=10"),
+        )
+        .await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()[X_APTOS_PAGE_SIZE], "2");
+    let txns: Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(txns.as_array().unwrap().len(), 2);
+
+    let txns = context.get("/transactions?start=0").await;
+    assert_eq!(txns.as_array().unwrap().len(), 2);
 }
 
 #[tokio::test]
@@ -153,7 +185,7 @@ async fn test_get_transactions_output_user_transaction_with_module_payload() {
                 {
                     "bytecode": format!("0x{}", code),
                     "abi": {
-                        "address": "0xb1e55ed",
+                        "address": "0x000000000000000000000000000000000000000000000000000000000b1e55ed",
                         "name": "MyModule",
                         "friends": [],
                         "exposed_functions": [
@@ -323,7 +355,7 @@ async fn test_multi_agent_signed_transaction() {
                 "signature": format!("0x{}", hex::encode(sender.signature_bytes())),
             },
             "secondary_signer_addresses": [
-                secondary.address().to_hex_literal(),
+                format!("0x{}", secondary.address().to_hex()),
             ],
             "secondary_signers": [
                 {
@@ -476,6 +508,57 @@ async fn test_get_transaction_by_version() {
     assert_json(resp, txns[0].clone())
 }
 
+#[tokio::test]
+async fn test_get_transaction_by_version_is_cacheable() {
+    let mut context = new_test_context(current_function_name!());
+    let account = context.gen_account();
+    let txn = context.create_user_account(&account);
+    context.commit_block(&vec![txn.clone()]).await;
+
+    let resp = context
+        .reply(warp::test::request().path("/transactions/2"))
+        .await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()[CACHE_CONTROL], IMMUTABLE_CACHE_CONTROL);
+    let etag = resp.headers()[ETAG].to_str().unwrap().to_owned();
+
+    // The same transaction fetched by hash has the same ETag
+    let txn: Value = serde_json::from_slice(resp.body()).unwrap();
+    let resp = context
+        .reply(
+            warp::test::request().path(&format!("/transactions/{}", txn["hash"].as_str().unwrap())),
+        )
+        .await;
+    assert_eq!(resp.headers()[ETAG], etag.as_str());
+
+    let resp = context
+        .reply(
+            warp::test::request()
+                .path("/transactions/2")
+                .header(IF_NONE_MATCH, &etag),
+        )
+        .await;
+    assert_eq!(resp.status(), 304);
+    assert!(resp.body().is_empty());
+    assert_eq!(resp.headers()[ETAG], etag.as_str());
+    let ledger_info = context.get_latest_ledger_info();
+    assert_eq!(
+        resp.headers()[X_APTOS_LEDGER_VERSION],
+        ledger_info.version().to_string()
+    );
+
+    // A stale ETag gets the full response
+    let resp = context
+        .reply(
+            warp::test::request()
+                .path("/transactions/1")
+                .header(IF_NONE_MATCH, &etag),
+        )
+        .await;
+    assert_eq!(resp.status(), 200);
+    assert_ne!(resp.headers()[ETAG], etag.as_str());
+}
+
 #[tokio::test]
 async fn test_get_pending_transaction_by_hash() {
     let mut context = new_test_context(current_function_name!());
@@ -492,6 +575,18 @@ async fn test_get_pending_transaction_by_hash() {
     let txn = context.get(&format!("/transactions/{}", txn_hash)).await;
     assert_json(txn, pending_txn);
 
+    // Pending transactions can still change, so they must never be cached
+    let resp = context
+        .reply(
+            warp::test::request()
+                .path(&format!("/transactions/{}", txn_hash))
+                .header(IF_NONE_MATCH, "*"),
+        )
+        .await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()[CACHE_CONTROL], UNCACHEABLE_CACHE_CONTROL);
+    assert!(resp.headers().get(ETAG).is_none());
+
     let not_found = context
         .expect_status_code(404)
         .get("/transactions/0xdadfeddcca7cb6396c735e9094c76c6e4e9cb3e3ef814730693aed59bd87b31d")
@@ -499,6 +594,117 @@ async fn test_get_pending_transaction_by_hash() {
     context.check_golden_output(not_found);
 }
 
+#[tokio::test]
+async fn test_get_pending_transaction() {
+    let mut context = new_test_context(current_function_name!());
+    let account = context.gen_account();
+    let txn = context.create_user_account(&account);
+    let body = bcs::to_bytes(&txn).unwrap();
+    let pending_txn = context
+        .expect_status_code(202)
+        .post_bcs_txn("/transactions", body)
+        .await;
+    let txn_hash = pending_txn["hash"].as_str().unwrap();
+
+    let mempool_txn = context
+        .get(&format!("/transactions/pending/{}", txn_hash))
+        .await;
+    assert_eq!(mempool_txn["hash"], pending_txn["hash"]);
+    assert_eq!(mempool_txn["sender"], pending_txn["sender"]);
+    assert_eq!(mempool_txn["payload"], pending_txn["payload"]);
+    let insertion_timestamp_usecs: u64 = mempool_txn["insertion_timestamp_usecs"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(insertion_timestamp_usecs > 0);
+    assert!(mempool_txn["ranking_score"].is_string());
+
+    let not_found = context
+        .expect_status_code(404)
+        .get("/transactions/pending/0xdadfeddcca7cb6396c735e9094c76c6e4e9cb3e3ef814730693aed59bd87b31d")
+        .await;
+    assert!(not_found["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("pending transaction not found"));
+
+    // Once committed, the transaction is only found in storage
+    context.commit_mempool_txns(1).await;
+    context
+        .expect_status_code(404)
+        .get(&format!("/transactions/pending/{}", txn_hash))
+        .await;
+    let committed_txn = context.get(&format!("/transactions/{}", txn_hash)).await;
+    assert_eq!(committed_txn["type"], "user_transaction");
+}
+
+#[tokio::test]
+async fn test_get_account_pending_transactions() {
+    let mut context = new_test_context(current_function_name!());
+    let accounts: Vec<_> = (0..3).map(|_| context.gen_account()).collect();
+    let mut root_account = context.root_account();
+    let txns: Vec<_> = accounts
+        .iter()
+        .map(|account| context.create_user_account_by(&mut root_account, account))
+        .collect();
+
+    // Skip the first sequence number, so the others are parked in mempool,
+    // and insert them out of order.
+    context
+        .mempool
+        .add_txns(vec![txns[2].clone(), txns[1].clone()])
+        .unwrap();
+
+    let path = format!(
+        "/accounts/{}/transactions/pending",
+        root_account.address().to_hex_literal()
+    );
+    let pending_txns = context.get(&path).await;
+    let sequence_numbers: Vec<_> = pending_txns
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|txn| txn["sequence_number"].as_str().unwrap())
+        .collect();
+    assert_eq!(sequence_numbers, vec!["1", "2"]);
+    assert_eq!(
+        pending_txns[0]["hash"],
+        json!(txns[1].clone().committed_hash().to_hex_literal())
+    );
+    assert_eq!(
+        pending_txns[0]["ranking_score"],
+        json!(txns[1].gas_unit_price().to_string())
+    );
+
+    // Parked transactions can be looked up by hash too
+    let pending_txn = context
+        .get(&format!(
+            "/transactions/pending/{}",
+            txns[2].clone().committed_hash().to_hex_literal()
+        ))
+        .await;
+    assert_eq!(pending_txn["sequence_number"], "2");
+
+    let resp = context
+        .reply(warp::test::request().path(&format!("{}?limit=1", path)))
+        .await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()[X_APTOS_PAGE_SIZE], "1");
+    let limited: Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(limited.as_array().unwrap().len(), 1);
+    assert_eq!(limited[0]["sequence_number"], "1");
+
+    // An account with nothing in mempool has no pending transactions
+    let none = context
+        .get(&format!(
+            "/accounts/{}/transactions/pending",
+            accounts[0].address().to_hex_literal()
+        ))
+        .await;
+    assert_eq!(none, json!([]));
+}
+
 #[tokio::test]
 async fn test_signing_message_with_script_function_payload() {
     let mut context = new_test_context(current_function_name!());
@@ -770,6 +976,35 @@ async fn test_get_account_transactions_filter_transactions_by_limit() {
     assert_eq!(txns.as_array().unwrap().len(), 2);
 }
 
+#[tokio::test]
+async fn test_get_account_transactions_limit_is_clamped_to_max_page_size() {
+    let mut node_config = NodeConfig::default();
+    node_config.api.max_page_size = 1;
+    let mut context = new_test_context_with_config(current_function_name!(), node_config);
+    let mut root_account = context.root_account();
+    let account1 = context.gen_account();
+    let txn1 = context.create_user_account_by(&mut root_account, &account1);
+    let account2 = context.gen_account();
+    let txn2 = context.create_user_account_by(&mut root_account, &account2);
+    context.commit_block(&vec![txn1, txn2]).await;
+
+    let resp = context
+        .reply(
+            warp::test::request().method("GET").path(
+                format!(
+                    "/accounts/{}/transactions?start=0&limit=2",
+                    context.root_account().address()
+                )
+                .as_str(),
+            ),
+        )
+        .await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()[X_APTOS_PAGE_SIZE], "1");
+    let txns: Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(txns.as_array().unwrap().len(), 1);
+}
+
 #[tokio::test]
 async fn test_get_txn_execute_failed_by_invalid_module_payload_bytecode() {
     let context = new_test_context(current_function_name!());
@@ -1063,7 +1298,7 @@ async fn test_submit_transaction_rejects_payload_too_large_bcs_txn_body() {
         .expect_status_code(413)
         .post_bcs_txn(
             "/transactions",
-            gen_string(context.context.content_length_limit() + 1).as_bytes(),
+            gen_string(context.context.submission_content_length_limit() + 1).as_bytes(),
         )
         .await;
     context.check_golden_output(resp);
@@ -1078,7 +1313,7 @@ async fn test_submit_transaction_rejects_payload_too_large_json_body() {
         .post(
             "/transactions",
             json!({
-                "data": gen_string(context.context.content_length_limit()+1).as_bytes(),
+                "data": gen_string(context.context.submission_content_length_limit()+1).as_bytes(),
             }),
         )
         .await;
@@ -1098,6 +1333,148 @@ async fn test_submit_transaction_rejects_invalid_content_type() {
     context.check_golden_output(resp);
 }
 
+#[tokio::test]
+async fn test_submit_transaction_rejects_missing_content_type() {
+    let mut context = new_test_context(current_function_name!());
+    let req = warp::test::request()
+        .method("POST")
+        .body("text")
+        .path("/transactions");
+
+    let resp = context.expect_status_code(415).execute(req).await;
+    context.check_golden_output(resp);
+}
+
+#[tokio::test]
+async fn test_submit_transaction_rejects_charset_on_bcs_body() {
+    let mut context = new_test_context(current_function_name!());
+    let account = context.gen_account();
+    let txn = context.create_user_account(&account);
+    let req = warp::test::request()
+        .header(
+            "content-type",
+            "application/x.aptos.signed_transaction+bcs; charset=utf-8",
+        )
+        .method("POST")
+        .body(bcs::to_bytes(&txn).unwrap())
+        .path("/transactions");
+
+    let resp = context.expect_status_code(415).execute(req).await;
+    context.check_golden_output(resp);
+}
+
+#[tokio::test]
+async fn test_submit_transaction_accepts_json_with_charset() {
+    let mut context = new_test_context(current_function_name!());
+    let account = context.gen_account();
+    let txn = context.create_user_account(&account);
+    let body = context
+        .expect_status_code(202)
+        .post_bcs_txn("/transactions", bcs::to_bytes(&txn).unwrap())
+        .await;
+
+    let req = warp::test::request()
+        .header("content-type", "application/json; charset=utf-8")
+        .method("POST")
+        .body(serde_json::to_vec(&body).unwrap())
+        .path("/transactions");
+    context.expect_status_code(202).execute(req).await;
+}
+
+fn sender_rate_limited_config(burst: u64) -> NodeConfig {
+    let mut node_config = NodeConfig::default();
+    // Slow enough that no tokens come back while the test runs.
+    node_config.api.sender_submission_rate_per_sec = 0.001;
+    node_config.api.sender_submission_burst = burst;
+    node_config
+}
+
+#[tokio::test]
+async fn test_submit_transactions_past_the_per_sender_limit() {
+    let mut context =
+        new_test_context_with_config(current_function_name!(), sender_rate_limited_config(3));
+    let mut root_account = context.root_account();
+    for _ in 0..3 {
+        let account = context.gen_account();
+        let txn = context.create_user_account_by(&mut root_account, &account);
+        context
+            .expect_status_code(202)
+            .post_bcs_txn("/transactions", bcs::to_bytes(&txn).unwrap())
+            .await;
+    }
+
+    let account = context.gen_account();
+    let txn = context.create_user_account_by(&mut root_account, &account);
+    let resp = context
+        .reply(
+            warp::test::request()
+                .method("POST")
+                .path("/transactions")
+                .header("content-type", BCS_SIGNED_TRANSACTION)
+                .body(bcs::to_bytes(&txn).unwrap()),
+        )
+        .await;
+    assert_eq!(resp.status(), 429);
+    assert_eq!(resp.headers()[RETRY_AFTER], "1000");
+    let body: Value = serde_json::from_slice(resp.body()).unwrap();
+    assert_eq!(
+        body["message"],
+        format!(
+            "Sender {} is submitting transactions too fast, retry after 1000s",
+            root_account.address().to_hex_literal()
+        )
+    );
+
+    // The rejected transaction never reached mempool.
+    assert_eq!(context.mempool.get_txns(10).len(), 3);
+}
+
+#[tokio::test]
+async fn test_forged_submissions_do_not_use_up_the_senders_limit() {
+    let mut context =
+        new_test_context_with_config(current_function_name!(), sender_rate_limited_config(1));
+    for _ in 0..3 {
+        let forged = context.create_invalid_signature_transaction();
+        let resp = context
+            .expect_status_code(400)
+            .post_bcs_txn("/transactions", bcs::to_bytes(&forged).unwrap())
+            .await;
+        assert_eq!(
+            resp["message"],
+            json!("invalid transaction: INVALID_SIGNATURE")
+        );
+    }
+
+    let account = context.gen_account();
+    let txn = context.create_user_account(&account);
+    context
+        .expect_status_code(202)
+        .post_bcs_txn("/transactions", bcs::to_bytes(&txn).unwrap())
+        .await;
+}
+
+#[tokio::test]
+async fn test_submit_transactions_from_distinct_senders_below_the_limit() {
+    let mut context =
+        new_test_context_with_config(current_function_name!(), sender_rate_limited_config(1));
+    let mut root_account = context.root_account();
+    let mut senders: Vec<LocalAccount> = (0..3).map(|_| context.gen_account()).collect();
+    let txns: Vec<SignedTransaction> = senders
+        .iter()
+        .map(|sender| context.create_user_account_by(&mut root_account, sender))
+        .collect();
+    context.commit_block(&txns).await;
+
+    for sender in senders.iter_mut() {
+        let account = context.gen_account();
+        let txn = context.create_user_account_by(sender, &account);
+        context
+            .expect_status_code(202)
+            .post_bcs_txn("/transactions", bcs::to_bytes(&txn).unwrap())
+            .await;
+    }
+}
+
 #[tokio::test]
 async fn test_submit_transaction_rejects_invalid_json() {
     let mut context = new_test_context(current_function_name!());
@@ -3,45 +3,54 @@
 
 use crate::{
     accept_type::AcceptType,
+    body,
     context::Context,
     failpoint::fail_point,
     metrics::metrics,
     page::Page,
-    param::{AddressParam, TransactionIdParam},
+    param::{AddressParam, HashValueParam, TransactionIdParam},
 };
 
 use aptos_api_types::{
-    mime_types::{BCS, BCS_SIGNED_TRANSACTION},
-    AsConverter, Error, LedgerInfo, Response, Transaction, TransactionData, TransactionId,
-    TransactionOnChainData, TransactionSigningMessage, UserCreateSigningMessageRequest,
-    UserTransactionRequest,
+    mime_types::{BCS, BCS_SIGNED_TRANSACTION, JSON},
+    AsConverter, Error, LedgerInfo, MempoolTransaction, Response, Transaction, TransactionData,
+    TransactionId, TransactionOnChainData, TransactionSigningMessage,
+    UserCreateSigningMessageRequest, UserTransactionRequest,
 };
 use aptos_crypto::signing_message;
 use aptos_types::{
     mempool_status::MempoolStatusCode,
     transaction::{RawTransaction, RawTransactionWithData, SignedTransaction},
+    vm_status::DiscardedVMStatus,
 };
 
 use aptos_crypto::HashValue;
+use aptos_mempool::PendingTransactionInfo;
 use aptos_vm::AptosVM;
 
 use anyhow::Result;
 use aptos_types::transaction::{ExecutionStatus, TransactionInfo, TransactionStatus};
+use std::time::UNIX_EPOCH;
 use warp::{
     filters::BoxedFilter,
     http::{
-        header::{ACCEPT, CONTENT_TYPE},
+        header::{ACCEPT, IF_NONE_MATCH},
         StatusCode,
     },
     reply, Filter, Rejection, Reply,
 };
 
+/// The media types transactions can be submitted and simulated in. Each has
+/// its own route.
+const SUBMISSION_MEDIA_TYPES: &[&str] = &[JSON, BCS_SIGNED_TRANSACTION];
+
 // GET /transactions/{txn-hash / version}
 pub fn get_json_transaction(context: Context) -> BoxedFilter<(impl Reply,)> {
     warp::path!("transactions" / TransactionIdParam)
         .and(warp::get())
+        .and(warp::header::optional::<String>(IF_NONE_MATCH.as_str()))
         .and(context.filter())
-        .map(|id, context| (id, context, AcceptType::Json))
+        .map(|id, if_none_match, context| (id, if_none_match, context, AcceptType::Json))
         .untuple_one()
         .and_then(handle_get_transaction)
         .with(metrics("get_json_transaction"))
@@ -53,8 +62,9 @@ pub fn get_bcs_transaction(context: Context) -> BoxedFilter<(impl Reply,)> {
     warp::path!("transactions" / TransactionIdParam)
         .and(warp::get())
         .and(warp::header::exact_ignore_case(ACCEPT.as_str(), BCS))
+        .and(warp::header::optional::<String>(IF_NONE_MATCH.as_str()))
         .and(context.filter())
-        .map(|id, context| (id, context, AcceptType::Bcs))
+        .map(|id, if_none_match, context| (id, if_none_match, context, AcceptType::Bcs))
         .untuple_one()
         .and_then(handle_get_transaction)
         .with(metrics("get_bcs_transaction"))
@@ -99,14 +109,35 @@ pub fn get_account_transactions(context: Context) -> BoxedFilter<(impl Reply,)>
         .boxed()
 }
 
+// GET /transactions/pending/{txn-hash}
+pub fn get_pending_transaction(context: Context) -> BoxedFilter<(impl Reply,)> {
+    warp::path!("transactions" / "pending" / HashValueParam)
+        .and(warp::get())
+        .and(context.filter())
+        .and_then(handle_get_pending_transaction)
+        .with(metrics("get_pending_transaction"))
+        .boxed()
+}
+
+// GET /accounts/{address}/transactions/pending?limit={u16}
+pub fn get_account_pending_transactions(context: Context) -> BoxedFilter<(impl Reply,)> {
+    warp::path!("accounts" / AddressParam / "transactions" / "pending")
+        .and(warp::get())
+        .and(warp::query::<Page>())
+        .and(context.filter())
+        .and_then(handle_get_account_pending_transactions)
+        .with(metrics("get_account_pending_transactions"))
+        .boxed()
+}
+
 // POST /transactions/simulate with JSON
 pub fn simulate_json_transactions(context: Context) -> BoxedFilter<(impl Reply,)> {
     warp::path!("transactions" / "simulate")
         .and(warp::post())
-        .and(warp::body::content_length_limit(
-            context.content_length_limit(),
+        .and(body::json_body::<UserTransactionRequest>(
+            SUBMISSION_MEDIA_TYPES,
+            context.submission_content_length_limit(),
         ))
-        .and(warp::body::json::<UserTransactionRequest>())
         .and(context.filter())
         .and_then(handle_simulate_json_transactions)
         .with(metrics("simulate_json_transactions"))
@@ -117,14 +148,11 @@ pub fn simulate_json_transactions(context: Context) -> BoxedFilter<(impl Reply,)
 pub fn simulate_bcs_transactions(context: Context) -> BoxedFilter<(impl Reply,)> {
     warp::path!("transactions" / "simulate")
         .and(warp::post())
-        .and(warp::body::content_length_limit(
-            context.content_length_limit(),
-        ))
-        .and(warp::header::exact(
-            CONTENT_TYPE.as_str(),
+        .and(body::body(
+            SUBMISSION_MEDIA_TYPES,
             BCS_SIGNED_TRANSACTION,
+            context.submission_content_length_limit(),
         ))
-        .and(warp::body::bytes())
         .and(context.filter())
         .and_then(handle_simulate_bcs_transactions)
         .with(metrics("simulate_bcs_transactions"))
@@ -135,10 +163,10 @@ pub fn simulate_bcs_transactions(context: Context) -> BoxedFilter<(impl Reply,)>
 pub fn submit_json_transactions(context: Context) -> BoxedFilter<(impl Reply,)> {
     warp::path!("transactions")
         .and(warp::post())
-        .and(warp::body::content_length_limit(
-            context.content_length_limit(),
+        .and(body::json_body::<UserTransactionRequest>(
+            SUBMISSION_MEDIA_TYPES,
+            context.submission_content_length_limit(),
         ))
-        .and(warp::body::json::<UserTransactionRequest>())
         .and(context.filter())
         .and_then(handle_submit_json_transactions)
         .with(metrics("submit_json_transactions"))
@@ -147,21 +175,13 @@ pub fn submit_json_transactions(context: Context) -> BoxedFilter<(impl Reply,)>
 
 // POST /transactions with BCS
 pub fn submit_bcs_transactions(context: Context) -> BoxedFilter<(impl Reply,)> {
-    // The `warp::body::bytes` does not check content-type like `warp::body::json`,
-    // so we used `warp::header::exact` to ensure only BCS signed txn matches this route.
-    // When the content-type is invalid (not json / bcs signed txn), `submit_json_transactions`
-    // route will emit correct rejection (UnsupportedMediaType) which will be handled by recover
-    // handler, the invalid header error should be ignored.
     warp::path!("transactions")
         .and(warp::post())
-        .and(warp::body::content_length_limit(
-            context.content_length_limit(),
-        ))
-        .and(warp::header::exact(
-            CONTENT_TYPE.as_str(),
+        .and(body::body(
+            SUBMISSION_MEDIA_TYPES,
             BCS_SIGNED_TRANSACTION,
+            context.submission_content_length_limit(),
         ))
-        .and(warp::body::bytes())
         .and(context.filter())
         .and_then(handle_submit_bcs_transactions)
         .with(metrics("submit_bcs_transactions"))
@@ -172,10 +192,10 @@ pub fn submit_bcs_transactions(context: Context) -> BoxedFilter<(impl Reply,)> {
 pub fn create_signing_message(context: Context) -> BoxedFilter<(impl Reply,)> {
     warp::path!("transactions" / "signing_message")
         .and(warp::post())
-        .and(warp::body::content_length_limit(
+        .and(body::json_body::<UserCreateSigningMessageRequest>(
+            &[JSON],
             context.content_length_limit(),
         ))
-        .and(warp::body::json::<UserCreateSigningMessageRequest>())
         .and(context.filter())
         .and_then(handle_create_signing_message)
         .with(metrics("create_signing_message"))
@@ -184,12 +204,17 @@ pub fn create_signing_message(context: Context) -> BoxedFilter<(impl Reply,)> {
 
 async fn handle_get_transaction(
     id: TransactionIdParam,
+    if_none_match: Option<String>,
     context: Context,
     accept_type: AcceptType,
 ) -> Result<impl Reply, Rejection> {
     fail_point("endpoint_get_transaction")?;
     Ok(Transactions::new(context)?
-        .get_transaction(id.parse("transaction hash or version")?, accept_type)
+        .get_transaction(
+            id.parse("transaction hash or version")?,
+            accept_type,
+            if_none_match,
+        )
         .await?)
 }
 
@@ -211,14 +236,36 @@ async fn handle_get_account_transactions(
     Ok(Transactions::new(context)?.list_by_account(address, page)?)
 }
 
+async fn handle_get_pending_transaction(
+    hash: HashValueParam,
+    context: Context,
+) -> Result<impl Reply, Rejection> {
+    fail_point("endpoint_get_pending_transaction")?;
+    Ok(Transactions::new(context)?
+        .get_pending_transaction(hash.parse("transaction hash")?)
+        .await?)
+}
+
+async fn handle_get_account_pending_transactions(
+    address: AddressParam,
+    page: Page,
+    context: Context,
+) -> Result<impl Reply, Rejection> {
+    fail_point("endpoint_get_account_pending_transactions")?;
+    Ok(Transactions::new(context)?
+        .list_pending_by_account(address, page)
+        .await?)
+}
+
 async fn handle_submit_json_transactions(
     body: UserTransactionRequest,
     context: Context,
 ) -> Result<impl Reply, Rejection> {
     fail_point("endpoint_submit_json_transactions")?;
-    Ok(Transactions::new(context)?
-        .create_from_request(body)
-        .await?)
+    let transactions = Transactions::new(context)?;
+    let txn = transactions.signed_transaction_from_request(body)?;
+    check_sender_rate_limit(&transactions.context, &txn)?;
+    Ok(transactions.create(txn).await?)
 }
 
 async fn handle_simulate_json_transactions(
@@ -236,11 +283,27 @@ async fn handle_submit_bcs_transactions(
     context: Context,
 ) -> Result<impl Reply, Rejection> {
     fail_point("endpoint_submit_bcs_transactions")?;
-    let txn = bcs::from_bytes(&body)
+    let txn: SignedTransaction = bcs::from_bytes(&body)
         .map_err(|err| Error::invalid_request_body(format!("deserialize error: {}", err)))?;
+    check_sender_rate_limit(&context, &txn)?;
     Ok(Transactions::new(context)?.create(txn).await?)
 }
 
+/// Takes a token from the sender's submission rate limit. Transactions that
+/// aren't signed by the sender are rejected first, as mempool would, so that
+/// nobody can use up someone else's limit by submitting in their name.
+fn check_sender_rate_limit(context: &Context, txn: &SignedTransaction) -> Result<(), Rejection> {
+    if txn.clone().check_signature().is_err() {
+        return Err(Error::bad_request(format!(
+            "invalid transaction: {:?}",
+            DiscardedVMStatus::INVALID_SIGNATURE
+        ))
+        .into());
+    }
+    context.sender_rate_limiter().check(txn.sender())?;
+    Ok(())
+}
+
 async fn handle_simulate_bcs_transactions(
     body: bytes::Bytes,
     context: Context,
@@ -273,12 +336,11 @@ impl Transactions {
         })
     }
 
-    pub async fn create_from_request(
-        self,
+    fn signed_transaction_from_request(
+        &self,
         req: UserTransactionRequest,
-    ) -> Result<impl Reply, Error> {
-        let txn = self
-            .context
+    ) -> Result<SignedTransaction, Error> {
+        self.context
             .move_resolver()?
             .as_converter()
             .try_into_signed_transaction(req, self.context.chain_id())
@@ -287,8 +349,7 @@ impl Transactions {
                     "failed to create SignedTransaction from UserTransactionRequest: {}",
                     e
                 ))
-            })?;
-        self.create(txn).await
+            })
     }
 
     pub async fn simulate_from_request(
@@ -370,7 +431,7 @@ impl Transactions {
 
     pub fn list(self, page: Page, accept_type: AcceptType) -> Result<impl Reply, Error> {
         let ledger_version = self.ledger_info.version();
-        let limit = page.limit()?;
+        let limit = page.limit(self.context.page_size_limits())?;
         let last_page_start = if ledger_version > (limit as u64) {
             ledger_version - (limit as u64)
         } else {
@@ -382,24 +443,99 @@ impl Transactions {
             .context
             .get_transactions(start_version, limit, ledger_version)?;
 
-        self.render_transactions(data, accept_type)
+        Ok(self
+            .render_transactions(data, accept_type)?
+            .with_page_size(limit))
     }
 
     pub fn list_by_account(self, address: AddressParam, page: Page) -> Result<impl Reply, Error> {
+        let limit = page.limit(self.context.page_size_limits())?;
         let data = self.context.get_account_transactions(
-            address.parse("account address")?.into(),
+            address.parse_address("account address")?.into(),
             page.start(0, u64::MAX)?,
-            page.limit()?,
+            limit,
             self.ledger_info.version(),
         )?;
-        self.render_transactions(data, AcceptType::Json)
+        Ok(self
+            .render_transactions(data, AcceptType::Json)?
+            .with_page_size(limit))
+    }
+
+    /// Only mempool is searched, a transaction that has already been
+    /// committed is not found. Use `get_transaction` to look up both.
+    pub async fn get_pending_transaction(
+        self,
+        hash: aptos_api_types::HashValue,
+    ) -> Result<impl Reply, Error> {
+        let info = self
+            .context
+            .get_pending_transaction_info_by_hash(hash.into())
+            .await?
+            .ok_or_else(|| {
+                Error::not_found("pending transaction", hash, self.ledger_info.version())
+            })?;
+        let txn = self.render_mempool_transactions(vec![info])?.remove(0);
+        Response::new_with_limit(
+            self.ledger_info,
+            &txn,
+            self.context.max_json_render_bytes(),
+            "Pending transaction",
+        )
+    }
+
+    pub async fn list_pending_by_account(
+        self,
+        address: AddressParam,
+        page: Page,
+    ) -> Result<impl Reply, Error> {
+        let limit = page.limit(self.context.page_size_limits())?;
+        let infos = self
+            .context
+            .get_pending_transactions_by_account(
+                address.parse_address("account address")?.into(),
+                limit,
+            )
+            .await?;
+        let txns = self.render_mempool_transactions(infos)?;
+        Ok(Response::new_with_limit(
+            self.ledger_info,
+            &txns,
+            self.context.max_json_render_bytes(),
+            "Pending transactions",
+        )?
+        .with_page_size(limit))
+    }
+
+    fn render_mempool_transactions(
+        &self,
+        infos: Vec<PendingTransactionInfo>,
+    ) -> Result<Vec<MempoolTransaction>, Error> {
+        let resolver = self.context.move_resolver()?;
+        let converter = resolver.as_converter();
+        infos
+            .into_iter()
+            .map(|info| {
+                let insertion_timestamp_usecs = info
+                    .insertion_time
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_micros() as u64;
+                converter
+                    .try_into_mempool_transaction(
+                        info.txn,
+                        insertion_timestamp_usecs,
+                        info.ranking_score,
+                    )
+                    .map_err(Error::from)
+            })
+            .collect()
     }
 
     fn render_transactions(
         self,
         data: Vec<TransactionOnChainData>,
         accept_type: AcceptType,
-    ) -> Result<impl Reply, Error> {
+    ) -> Result<Response, Error> {
         if accept_type == AcceptType::Bcs {
             return Response::new_bcs(self.ledger_info, &data);
         }
@@ -421,35 +557,46 @@ impl Transactions {
         Response::new(self.ledger_info, &txns)
     }
 
+    /// Committed transactions never change, so their responses are marked
+    /// immutable (cacheable). Pending transactions are never cacheable.
     pub async fn get_transaction(
         self,
         id: TransactionId,
         accept_type: AcceptType,
+        if_none_match: Option<String>,
     ) -> Result<impl Reply, Error> {
         let txn_data = match id.clone() {
             TransactionId::Hash(hash) => self.get_by_hash(hash.into()).await?,
             TransactionId::Version(version) => self.get_by_version(version)?,
         }
         .ok_or_else(|| self.transaction_not_found(id))?;
+        let committed_txn = match &txn_data {
+            TransactionData::OnChain(txn) => Some(txn.clone()),
+            TransactionData::Pending(_) => None,
+        };
 
-        if accept_type == AcceptType::Bcs {
-            return Response::new_bcs(self.ledger_info, &txn_data);
-        }
-
-        let resolver = self.context.move_resolver()?;
-        let txn = match txn_data {
-            TransactionData::OnChain(txn) => {
-                let timestamp = self.context.get_block_timestamp(txn.version)?;
-                resolver
-                    .as_converter()
-                    .try_into_onchain_transaction(timestamp, txn)?
-            }
-            TransactionData::Pending(txn) => {
-                resolver.as_converter().try_into_pending_transaction(*txn)?
-            }
+        let response = if accept_type == AcceptType::Bcs {
+            Response::new_bcs(self.ledger_info, &txn_data)?
+        } else {
+            let resolver = self.context.move_resolver()?;
+            let txn = match txn_data {
+                TransactionData::OnChain(txn) => {
+                    let timestamp = self.context.get_block_timestamp(txn.version)?;
+                    resolver
+                        .as_converter()
+                        .try_into_onchain_transaction(timestamp, txn)?
+                }
+                TransactionData::Pending(txn) => {
+                    resolver.as_converter().try_into_pending_transaction(*txn)?
+                }
+            };
+            Response::new(self.ledger_info, &txn)?
         };
 
-        Response::new(self.ledger_info, &txn)
+        match committed_txn {
+            Some(txn) => response.immutable(&txn, if_none_match.as_deref()),
+            None => Ok(response),
+        }
     }
 
     pub fn signing_message(
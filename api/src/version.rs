@@ -3,9 +3,87 @@
 
 use crate::param::LedgerVersionParam;
 
-use serde::Deserialize;
+use aptos_api_types::{Error, LedgerInfo, Response};
+use serde::{Deserialize, Serialize};
 
+/// The query params selecting the ledger version a state read is evaluated
+/// at. `version` is the legacy name of `ledger_version`, which wins if both
+/// are given.
 #[derive(Clone, Debug, Deserialize)]
 pub(crate) struct Version {
+    pub(crate) ledger_version: Option<LedgerVersionParam>,
     pub(crate) version: Option<LedgerVersionParam>,
 }
+
+impl Version {
+    pub(crate) fn ledger_version(self) -> Option<LedgerVersionParam> {
+        self.ledger_version.or(self.version)
+    }
+}
+
+/// Decides whether the response to a state read may be cached. State read
+/// at an explicitly requested ledger version never changes, so it gets an
+/// ETag and is cacheable, while reads of the latest state are not.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StateReadCaching {
+    immutable: bool,
+    if_none_match: Option<String>,
+}
+
+impl StateReadCaching {
+    pub(crate) fn new(
+        ledger_version: &Option<LedgerVersionParam>,
+        if_none_match: Option<String>,
+    ) -> Self {
+        Self {
+            immutable: ledger_version.is_some(),
+            if_none_match,
+        }
+    }
+
+    pub(crate) fn respond<T: Serialize>(
+        &self,
+        ledger_info: LedgerInfo,
+        payload: &T,
+    ) -> Result<Response, Error> {
+        self.cache(Response::new(ledger_info, payload)?, payload)
+    }
+
+    /// Like `respond`, but fails with a 413 instead of rendering more than
+    /// `limit` bytes of JSON.
+    pub(crate) fn respond_with_limit<T: Serialize>(
+        &self,
+        ledger_info: LedgerInfo,
+        payload: &T,
+        limit: u64,
+        what: &str,
+    ) -> Result<Response, Error> {
+        let response = Response::new_with_limit(ledger_info, payload, limit, what)?;
+        self.cache(response, payload)
+    }
+
+    /// Responds with state exactly as it was read from storage, i.e. as
+    /// BCS, failing with a 413 if it is larger than `limit` bytes.
+    pub(crate) fn respond_bcs(
+        &self,
+        ledger_info: LedgerInfo,
+        bytes: Vec<u8>,
+        limit: u64,
+        what: &str,
+    ) -> Result<Response, Error> {
+        let response = Response::from_bcs_bytes(ledger_info, bytes, limit, what)?;
+        if self.immutable {
+            Ok(response.immutable_bcs(self.if_none_match.as_deref()))
+        } else {
+            Ok(response)
+        }
+    }
+
+    fn cache<T: Serialize>(&self, response: Response, payload: &T) -> Result<Response, Error> {
+        if self.immutable {
+            response.immutable(payload, self.if_none_match.as_deref())
+        } else {
+            Ok(response)
+        }
+    }
+}
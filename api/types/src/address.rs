@@ -6,6 +6,10 @@ use aptos_types::account_address::AccountAddress;
 use move_deps::move_core_types;
 use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
 use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+/// The number of hex digits in the long form of an address.
+const ADDRESS_HEX_LENGTH: usize = AccountAddress::LENGTH * 2;
 
 #[derive(Clone, Debug, PartialEq, Copy)]
 pub struct Address(AccountAddress);
@@ -16,9 +20,11 @@ impl Address {
     }
 }
 
+/// Addresses are always rendered in the canonical long form, i.e. `0x`
+/// followed by all 64 hex digits, so that clients can compare them as strings.
 impl fmt::Display for Address {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.0.to_hex_literal())
+        write!(f, "{:#x}", self.0)
     }
 }
 
@@ -26,16 +32,50 @@ impl FromStr for Address {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> anyhow::Result<Self, anyhow::Error> {
-        let mut ret = AccountAddress::from_hex_literal(s);
-        if ret.is_err() {
-            ret = AccountAddress::from_hex(s)
-        }
-        Ok(Self(ret.map_err(|_| {
-            anyhow::format_err!("invalid account address {:?}", s)
-        })?))
+        let address = parse_address(s)
+            .map_err(|e| anyhow::format_err!("invalid account address {:?}: {}", s, e))?;
+        Ok(Self(address))
     }
 }
 
+/// Why a string isn't a valid account address.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum AddressParseError {
+    #[error("no hex digits given")]
+    Empty,
+    #[error("invalid character {character:?} at position {position}")]
+    InvalidCharacter { character: char, position: usize },
+    #[error("too long, {0} hex digits given but an address has at most 64")]
+    TooLong(usize),
+}
+
+/// Parses an account address from hex, with or without the `0x` prefix and
+/// in any case. Leading zeros may be left out (e.g. `0x1`), in which case the
+/// address is padded to its full width. This is the only place the API parses
+/// addresses, so that every endpoint accepts the same forms.
+pub fn parse_address(s: &str) -> Result<AccountAddress, AddressParseError> {
+    let (prefix_len, digits) = match s.strip_prefix("0x") {
+        Some(digits) => (2, digits),
+        None => (0, s),
+    };
+    if let Some((index, character)) = digits.char_indices().find(|(_, c)| !c.is_ascii_hexdigit()) {
+        return Err(AddressParseError::InvalidCharacter {
+            character,
+            position: prefix_len + index,
+        });
+    }
+    if digits.is_empty() {
+        return Err(AddressParseError::Empty);
+    }
+    if digits.len() > ADDRESS_HEX_LENGTH {
+        return Err(AddressParseError::TooLong(digits.len()));
+    }
+
+    let padded = format!("{:0>width$}", digits, width = ADDRESS_HEX_LENGTH);
+    // Every character is a hex digit and the length is right, so this can't fail
+    Ok(AccountAddress::from_hex(padded).expect("padded address must be valid hex"))
+}
+
 impl From<AccountAddress> for Address {
     fn from(address: AccountAddress) -> Self {
         Self(address)
@@ -81,26 +121,64 @@ impl_poem_parameter!(Address);
 
 #[cfg(test)]
 mod tests {
-    use crate::address::Address;
+    use crate::address::{parse_address, Address, AddressParseError};
 
     use aptos_types::account_address::AccountAddress;
 
+    use proptest::prelude::*;
     use serde_json::{json, Value};
 
+    const ONE_LONG_FORM: &str =
+        "0x0000000000000000000000000000000000000000000000000000000000000001";
+
     #[test]
     fn test_from_and_to_string() {
-        let valid_addresses = vec!["0x1", "0x001", "0x00000000000000000000000000000001"];
+        let valid_addresses = vec![
+            "0x1",
+            "0x001",
+            "0x00000000000000000000000000000001",
+            "1",
+            "01",
+            &ONE_LONG_FORM[2..],
+            ONE_LONG_FORM,
+        ];
         for address in valid_addresses {
-            assert_eq!(address.parse::<Address>().unwrap().to_string(), "0x1");
+            assert_eq!(
+                address.parse::<Address>().unwrap().to_string(),
+                ONE_LONG_FORM
+            );
         }
 
-        let invalid_addresses = vec!["invalid", "00x1", "x1", "01", "1"];
-        for address in invalid_addresses {
+        assert_eq!(
+            "0xA550C18".parse::<Address>().unwrap(),
+            "0xa550c18".parse::<Address>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_invalid_addresses_explain_why() {
+        let invalid_addresses = vec![
+            ("", "no hex digits given"),
+            ("0x", "no hex digits given"),
+            ("invalid", "invalid character 'i' at position 0"),
+            ("00x1", "invalid character 'x' at position 1"),
+            ("x1", "invalid character 'x' at position 0"),
+            ("0xzz", "invalid character 'z' at position 2"),
+            ("0X1", "invalid character 'X' at position 1"),
+            (" 0x1", "invalid character ' ' at position 0"),
+        ];
+        for (address, reason) in invalid_addresses {
             assert_eq!(
-                format!("invalid account address {:?}", address),
+                format!("invalid account address {:?}: {}", address, reason),
                 address.parse::<Address>().unwrap_err().to_string()
             );
         }
+
+        let too_long = format!("0x1{}", &ONE_LONG_FORM[2..]);
+        assert_eq!(
+            parse_address(&too_long).unwrap_err(),
+            AddressParseError::TooLong(65)
+        );
     }
 
     #[test]
@@ -109,7 +187,7 @@ mod tests {
         assert_eq!(address, "0x1".parse().unwrap());
 
         let val: Value = serde_json::to_value(address).unwrap();
-        assert_eq!(val, json!("0x1"));
+        assert_eq!(val, json!(ONE_LONG_FORM));
     }
 
     #[test]
@@ -133,4 +211,63 @@ mod tests {
         let new_address: Address = account_address.into();
         assert_eq!(new_address, address);
     }
+
+    /// Strips the `0x` prefix and any leading zeros, and randomizes the case,
+    /// giving all the other forms we accept for the same address.
+    fn address_forms() -> impl Strategy<Value = (AccountAddress, String)> {
+        (
+            any::<[u8; AccountAddress::LENGTH]>(),
+            any::<bool>(),
+            any::<bool>(),
+            any::<u64>(),
+        )
+            .prop_map(|(bytes, with_prefix, strip_zeros, case_seed)| {
+                let address = AccountAddress::new(bytes);
+                let mut digits = address.to_hex();
+                if strip_zeros {
+                    digits = digits.trim_start_matches('0').to_owned();
+                    if digits.is_empty() {
+                        digits = "0".to_owned();
+                    }
+                }
+                let digits: String = digits
+                    .chars()
+                    .enumerate()
+                    .map(|(i, c)| {
+                        if case_seed >> (i % 64) & 1 == 1 {
+                            c.to_ascii_uppercase()
+                        } else {
+                            c
+                        }
+                    })
+                    .collect();
+                let input = if with_prefix {
+                    format!("0x{}", digits)
+                } else {
+                    digits
+                };
+                (address, input)
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn test_parse_then_render_is_canonical((address, input) in address_forms()) {
+            let parsed: Address = input.parse().unwrap();
+            prop_assert_eq!(parsed.inner(), &address);
+
+            let rendered = parsed.to_string();
+            prop_assert_eq!(&rendered, &format!("0x{}", address.to_hex()));
+
+            // Rendering is stable, the canonical form parses back to itself
+            let reparsed: Address = rendered.parse().unwrap();
+            prop_assert_eq!(reparsed, parsed);
+            prop_assert_eq!(reparsed.to_string(), rendered);
+        }
+
+        #[test]
+        fn test_parse_never_panics(input in ".*") {
+            let _ = input.parse::<Address>();
+        }
+    }
 }
@@ -0,0 +1,33 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// One request within a batch (i.e., POST "/batch"). Only read only requests
+/// may be batched.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct BatchRequestItem {
+    /// The HTTP method, e.g. "GET"
+    pub method: String,
+    /// The path of the request, including the query string if any, e.g.
+    /// "/accounts/0x1/resources?ledger_version=10"
+    pub path: String,
+    /// The JSON body of the request, if it has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+/// The response to one request within a batch. Responses are returned in
+/// the same order as the requests.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct BatchResponseItem {
+    /// The HTTP status code
+    pub status: u16,
+    /// The response headers, e.g. "x-aptos-ledger-version"
+    pub headers: BTreeMap<String, String>,
+    /// The body of the response. JSON bodies are inlined, anything else is
+    /// given as a string.
+    pub body: Value,
+}
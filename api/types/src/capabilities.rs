@@ -0,0 +1,61 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use poem_openapi::{Enum as PoemEnum, Object as PoemObject};
+use serde::{Deserialize, Serialize};
+
+/// An optional feature of the API, which the node may or may not have
+/// enabled. Features are always listed in the order they are declared here.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, PoemEnum, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+pub enum ApiFeature {
+    /// Several read only requests can be made at once with POST "/batch"
+    Batch,
+    /// Failpoints are compiled in, so requests may fail on purpose
+    Failpoints,
+    /// The node's Prometheus metrics are served at "/-/metrics"
+    Metrics,
+    /// Each sender's transaction submissions are rate limited
+    SenderRateLimit,
+    /// Transactions can be simulated with POST "/transactions/simulate"
+    Simulation,
+    /// The API is served over TLS
+    Tls,
+    /// View functions can be called with POST "/view"
+    View,
+}
+
+/// The version of the node software serving the API.
+#[derive(Clone, Debug, Deserialize, PartialEq, PoemObject, Serialize)]
+pub struct BuildInfo {
+    /// The version of the API crate
+    pub version: String,
+    /// The git commit the node was built from, if known
+    pub commit_hash: Option<String>,
+}
+
+/// The struct returned to the client by the capabilities endpoint (i.e.,
+/// GET "/-/capabilities"), describing what the node's API supports so
+/// clients don't have to probe for it.
+///
+/// Everything is derived from the node's config, and every list has a
+/// fixed order, so the response only changes when the config does.
+#[derive(Clone, Debug, Deserialize, PartialEq, PoemObject, Serialize)]
+pub struct Capabilities {
+    /// The version of the API, as in the OpenAPI spec
+    pub api_version: String,
+    pub build: BuildInfo,
+    /// Content types request bodies may have
+    pub request_content_types: Vec<String>,
+    /// Content types responses may be requested as, with the Accept header
+    pub response_content_types: Vec<String>,
+    /// The page size paginated endpoints use when none is given
+    pub default_page_size: u16,
+    /// The largest page size paginated endpoints return
+    pub max_page_size: u16,
+    /// The most requests a batch may contain
+    pub batch_max_requests: u64,
+    /// The optional features the node has enabled
+    pub features: Vec<ApiFeature>,
+}
@@ -6,10 +6,11 @@ use crate::{
         DeleteModule, DeleteResource, DeleteTableItem, ModuleBundlePayload,
         StateCheckpointTransaction, WriteModule, WriteResource, WriteTableItem,
     },
-    Bytecode, DirectWriteSet, Event, HexEncodedBytes, MoveFunction, MoveModuleBytecode,
-    MoveResource, MoveScriptBytecode, MoveValue, ScriptFunctionId, ScriptFunctionPayload,
-    ScriptPayload, ScriptWriteSet, Transaction, TransactionInfo, TransactionOnChainData,
-    TransactionPayload, UserTransactionRequest, WriteSet, WriteSetChange, WriteSetPayload,
+    Bytecode, DirectWriteSet, Event, HexEncodedBytes, MempoolTransaction, MoveFunction,
+    MoveModuleBytecode, MoveResource, MoveScriptBytecode, MoveValue, ScriptFunctionId,
+    ScriptFunctionPayload, ScriptPayload, ScriptWriteSet, Transaction, TransactionInfo,
+    TransactionOnChainData, TransactionPayload, UserTransactionRequest, WriteSet, WriteSetChange,
+    WriteSetPayload,
 };
 use anyhow::{bail, ensure, format_err, Result};
 use aptos_crypto::{hash::CryptoHash, HashValue};
@@ -80,6 +81,21 @@ impl<'a, R: MoveResolverExt + ?Sized> MoveConverter<'a, R> {
         Ok((txn, payload).into())
     }
 
+    pub fn try_into_mempool_transaction(
+        &self,
+        txn: SignedTransaction,
+        insertion_timestamp_usecs: u64,
+        ranking_score: u64,
+    ) -> Result<MempoolTransaction> {
+        let payload = self.try_into_transaction_payload(txn.payload().clone())?;
+        Ok(MempoolTransaction {
+            request: (&txn, payload).into(),
+            hash: txn.committed_hash().into(),
+            insertion_timestamp_usecs: insertion_timestamp_usecs.into(),
+            ranking_score: ranking_score.into(),
+        })
+    }
+
     pub fn try_into_onchain_transaction(
         &self,
         timestamp: u64,
@@ -571,7 +587,7 @@ impl<'a, R: MoveResolverExt + ?Sized> MoveConverter<'a, R> {
         ))
     }
 
-    fn explain_vm_status(&self, status: &ExecutionStatus) -> String {
+    pub fn explain_vm_status(&self, status: &ExecutionStatus) -> String {
         match status {
             ExecutionStatus::MoveAbort { location, code} => match &location {
                 AbortLocation::Module(module_id) => {
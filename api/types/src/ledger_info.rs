@@ -11,7 +11,7 @@ use serde::{Deserialize, Serialize};
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, PoemObject)]
 pub struct LedgerInfo {
     pub chain_id: u8,
-    pub epoch: u64,
+    pub epoch: U64,
     pub ledger_version: U64,
     pub oldest_ledger_version: U64,
     pub ledger_timestamp: U64,
@@ -26,7 +26,7 @@ impl LedgerInfo {
         let ledger_info = info.ledger_info();
         Self {
             chain_id: chain_id.id(),
-            epoch: ledger_info.epoch(),
+            epoch: ledger_info.epoch().into(),
             ledger_version: ledger_info.version().into(),
             oldest_ledger_version: oldest_ledger_version.into(),
             ledger_timestamp: ledger_info.timestamp_usecs().into(),
@@ -37,6 +37,10 @@ impl LedgerInfo {
         self.ledger_version.into()
     }
 
+    pub fn epoch(&self) -> u64 {
+        self.epoch.into()
+    }
+
     pub fn timestamp(&self) -> u64 {
         self.ledger_timestamp.into()
     }
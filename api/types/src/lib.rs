@@ -3,46 +3,63 @@
 
 mod account;
 mod address;
+mod batch;
 mod block;
 mod bytecode;
+mod capabilities;
 mod convert;
 mod error;
 mod event_key;
 mod hash;
 mod index;
 mod ledger_info;
+mod log_filter;
 pub mod mime_types;
 mod move_types;
+mod node_info;
+mod on_chain_config;
+mod readiness;
+mod render_limit;
 mod response;
 mod table;
 mod transaction;
+mod view_function;
 mod wrappers;
 
 pub use account::AccountData;
-pub use address::Address;
+pub use address::{parse_address, Address, AddressParseError};
+pub use batch::{BatchRequestItem, BatchResponseItem};
 pub use block::BlockInfo;
 pub use bytecode::Bytecode;
+pub use capabilities::{ApiFeature, BuildInfo, Capabilities};
 pub use convert::{new_vm_utf8_string, AsConverter, MoveConverter};
 pub use error::Error;
 pub use event_key::EventKey;
 pub use hash::HashValue;
 pub use index::IndexResponse;
 pub use ledger_info::LedgerInfo;
+pub use log_filter::{LogFilter, LogFilterChange};
 pub use move_types::{
-    HexEncodedBytes, MoveFunction, MoveModule, MoveModuleBytecode, MoveModuleId, MoveResource,
-    MoveScriptBytecode, MoveStructTag, MoveStructValue, MoveType, MoveValue, ScriptFunctionId,
-    U128, U64,
+    HexEncodedBytes, MoveFunction, MoveFunctionVisibility, MoveModule, MoveModuleBytecode,
+    MoveModuleId, MoveResource, MoveScriptBytecode, MoveStructTag, MoveStructValue, MoveType,
+    MoveValue, ScriptFunctionId, U128, U64,
 };
+pub use node_info::{NodeInfo, SyncState};
+pub use on_chain_config::{GasSchedule, OnChainConfigs};
+pub use readiness::{FailedReadinessCheck, Readiness, ReadinessCheck};
+pub use render_limit::{check_json_size, to_json_with_limit, JsonRenderError};
 pub use response::{
-    Response, X_APTOS_CHAIN_ID, X_APTOS_EPOCH, X_APTOS_LEDGER_TIMESTAMP, X_APTOS_LEDGER_VERSION,
+    Response, IMMUTABLE_CACHE_CONTROL, UNCACHEABLE_CACHE_CONTROL, X_APTOS_CHAIN_ID, X_APTOS_EPOCH,
+    X_APTOS_LEDGER_TIMESTAMP, X_APTOS_LEDGER_VERSION, X_APTOS_PAGE_SIZE,
 };
 pub use table::TableItemRequest;
 pub use transaction::{
     BlockMetadataTransaction, DeleteModule, DeleteResource, DeleteTableItem, DirectWriteSet, Event,
-    GenesisTransaction, PendingTransaction, ScriptFunctionPayload, ScriptPayload, ScriptWriteSet,
-    Transaction, TransactionData, TransactionId, TransactionInfo, TransactionOnChainData,
-    TransactionPayload, TransactionSigningMessage, UserCreateSigningMessageRequest,
-    UserTransaction, UserTransactionRequest, WriteModule, WriteResource, WriteSet, WriteSetChange,
-    WriteSetPayload, WriteTableItem,
+    GenesisTransaction, MempoolTransaction, PendingTransaction, ScriptFunctionPayload,
+    ScriptPayload, ScriptWriteSet, Transaction, TransactionData, TransactionId, TransactionInfo,
+    TransactionOnChainData, TransactionPayload, TransactionSigningMessage,
+    UserCreateSigningMessageRequest, UserTransaction, UserTransactionRequest, WriteModule,
+    WriteResource, WriteSet, WriteSetChange, WriteSetPayload, WriteTableItem,
 };
+pub use view_function::{BcsViewRequest, ViewRequest};
 pub use wrappers::{IdentifierWrapper, MoveStructTagWrapper};
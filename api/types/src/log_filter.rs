@@ -0,0 +1,25 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use poem_openapi::Object as PoemObject;
+use serde::{Deserialize, Serialize};
+
+/// A logger filter, given as a comma separated list of directives. Each
+/// directive is either a level (e.g. `debug`), a module (e.g. `state_sync`,
+/// which logs everything from that module) or both (e.g.
+/// `state_sync=trace`). Levels are `off`, `error`, `warn`, `info`, `debug`
+/// and `trace`.
+#[derive(Clone, Debug, Deserialize, PartialEq, PoemObject, Serialize)]
+pub struct LogFilter {
+    pub filter: String,
+}
+
+/// The struct returned to the client after changing the logger filter
+/// (i.e., POST "/log/filter").
+#[derive(Clone, Debug, Deserialize, PartialEq, PoemObject, Serialize)]
+pub struct LogFilterChange {
+    /// The filter that was in effect before the change
+    pub previous: String,
+    /// The filter now in effect
+    pub current: String,
+}
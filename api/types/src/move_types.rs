@@ -23,8 +23,11 @@ use move_deps::{
     move_resource_viewer::{AnnotatedMoveStruct, AnnotatedMoveValue},
 };
 
-use poem_openapi::{Enum, NewType, Object};
-use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use poem_openapi::{Enum, Object};
+use serde::{
+    de::{self, Error as _, Unexpected, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 use std::{
     collections::BTreeMap,
     convert::{From, Into, TryFrom, TryInto},
@@ -52,7 +55,10 @@ impl TryFrom<AnnotatedMoveStruct> for MoveResource {
     }
 }
 
-#[derive(Clone, Debug, Default, Eq, PartialEq, Copy, NewType)]
+/// A u64 that is represented as a decimal string in JSON, since JavaScript
+/// clients can't represent the full range of a u64 as a number. Numbers are
+/// still accepted as input, as long as they are integers in range.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Copy)]
 pub struct U64(pub u64);
 
 impl U64 {
@@ -114,11 +120,13 @@ impl<'de> Deserialize<'de> for U64 {
     where
         D: Deserializer<'de>,
     {
-        let s = <String>::deserialize(deserializer)?;
-        s.parse().map_err(D::Error::custom)
+        deserialize_uint(deserializer, "u64").map(U64)
     }
 }
 
+/// As with [`U64`], a u128 that is represented as a decimal string in JSON.
+/// Note that numbers above u64::MAX can only be given as strings, since
+/// they aren't integers by the time they've been parsed as JSON.
 #[derive(Clone, Debug, Default, PartialEq, Copy)]
 pub struct U128(u128);
 
@@ -134,6 +142,24 @@ impl From<u128> for U128 {
     }
 }
 
+impl FromStr for U128 {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let data = s
+            .parse::<u128>()
+            .map_err(|e| format_err!("parse u128 string {:?} failed, caused by error: {}", s, e))?;
+
+        Ok(U128(data))
+    }
+}
+
+impl fmt::Display for U128 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", &self.0)
+    }
+}
+
 impl From<U128> for u128 {
     fn from(d: U128) -> Self {
         d.0
@@ -157,13 +183,142 @@ impl<'de> Deserialize<'de> for U128 {
     where
         D: Deserializer<'de>,
     {
-        let s = <String>::deserialize(deserializer)?;
-        let data = s.parse::<u128>().map_err(D::Error::custom)?;
+        deserialize_uint(deserializer, "u128").map(U128)
+    }
+}
+
+/// Deserializes an unsigned integer from either a decimal string or a JSON
+/// integer. Floats (e.g. `1e5`), negative and out of range values are
+/// rejected rather than truncated. Formats that aren't self describing (e.g.
+/// BCS) only ever contain the string form, since that is what we serialize.
+fn deserialize_uint<'de, D, T>(deserializer: D, type_name: &'static str) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr + TryFrom<u64> + TryFrom<u128>,
+    <T as FromStr>::Err: fmt::Display,
+{
+    let visitor = UintVisitor {
+        type_name,
+        phantom: std::marker::PhantomData,
+    };
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_any(visitor)
+    } else {
+        deserializer.deserialize_string(visitor)
+    }
+}
 
-        Ok(U128(data))
+struct UintVisitor<T> {
+    type_name: &'static str,
+    phantom: std::marker::PhantomData<T>,
+}
+
+impl<'de, T> Visitor<'de> for UintVisitor<T>
+where
+    T: FromStr + TryFrom<u64> + TryFrom<u128>,
+    <T as FromStr>::Err: fmt::Display,
+{
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a {} as a string or an integer", self.type_name)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+        v.parse().map_err(|e| {
+            E::custom(format!(
+                "parse {} string {:?} failed, caused by error: {}",
+                self.type_name, v, e
+            ))
+        })
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+        T::try_from(v).map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))
+    }
+
+    fn visit_u128<E: de::Error>(self, v: u128) -> Result<T, E> {
+        T::try_from(v)
+            .map_err(|_| E::custom(format!("{} is out of range for {}", v, self.type_name)))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+        if v < 0 {
+            return Err(E::invalid_value(Unexpected::Signed(v), &self));
+        }
+        self.visit_u64(v as u64)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<T, E> {
+        Err(E::invalid_type(Unexpected::Float(v), &self))
     }
 }
 
+/// Implements the poem-openapi traits for our integer wrappers, so that the
+/// OpenAPI spec describes them as strings, the same as they're serialized.
+macro_rules! impl_poem_uint {
+    ($(($ty:ty, $format:literal)),*) => {
+        $(
+        impl poem_openapi::types::Type for $ty {
+            const IS_REQUIRED: bool = true;
+
+            type RawValueType = Self;
+
+            type RawElementValueType = Self;
+
+            fn name() -> std::borrow::Cow<'static, str> {
+                format!("string({})", $format).into()
+            }
+
+            fn schema_ref() -> poem_openapi::registry::MetaSchemaRef {
+                poem_openapi::registry::MetaSchemaRef::Inline(Box::new(
+                    poem_openapi::registry::MetaSchema::new_with_format("string", $format),
+                ))
+            }
+
+            fn as_raw_value(&self) -> Option<&Self::RawValueType> {
+                Some(self)
+            }
+
+            fn raw_element_iter<'a>(
+                &'a self,
+            ) -> Box<dyn Iterator<Item = &'a Self::RawElementValueType> + 'a> {
+                Box::new(self.as_raw_value().into_iter())
+            }
+        }
+
+        impl poem_openapi::types::ParseFromJSON for $ty {
+            fn parse_from_json(
+                value: Option<serde_json::Value>,
+            ) -> poem_openapi::types::ParseResult<Self> {
+                Ok(serde_json::from_value(value.unwrap_or_default())?)
+            }
+        }
+
+        impl poem_openapi::types::ParseFromParameter for $ty {
+            fn parse_from_parameter(value: &str) -> poem_openapi::types::ParseResult<Self> {
+                value.parse().map_err(poem_openapi::types::ParseError::custom)
+            }
+        }
+
+        impl poem_openapi::types::ToJSON for $ty {
+            fn to_json(&self) -> Option<serde_json::Value> {
+                Some(serde_json::Value::String(self.to_string()))
+            }
+        }
+
+        // Headers are plain text, so there's no need to quote the value.
+        impl poem_openapi::types::ToHeader for $ty {
+            fn to_header(&self) -> Option<poem::http::HeaderValue> {
+                poem::http::HeaderValue::from_str(&self.to_string()).ok()
+            }
+        }
+        )*
+    };
+}
+
+impl_poem_uint!((U64, "uint64"), (U128, "uint128"));
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct HexEncodedBytes(Vec<u8>);
 
@@ -398,7 +553,14 @@ impl From<StructTag> for MoveStructTag {
 
 impl fmt::Display for MoveStructTag {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}::{}::{}", self.address, self.module, self.name)?;
+        // Type names use Move's short form for addresses, e.g. 0x1::coin::Coin
+        write!(
+            f,
+            "{}::{}::{}",
+            self.address.inner().to_hex_literal(),
+            self.module,
+            self.name
+        )?;
         if let Some(first_ty) = self.generic_type_params.first() {
             write!(f, "<")?;
             write!(f, "{}", first_ty)?;
@@ -543,6 +705,34 @@ impl MoveType {
             _ => false,
         }
     }
+
+    /// Replaces the generic type params in this type with the given type
+    /// arguments, e.g. to get the concrete types of a generic function's
+    /// params. Params without a matching type argument are left as they are.
+    pub fn substitute(&self, type_args: &[MoveType]) -> MoveType {
+        match self {
+            MoveType::GenericTypeParam { index } => type_args
+                .get(*index as usize)
+                .cloned()
+                .unwrap_or_else(|| self.clone()),
+            MoveType::Vector { items } => MoveType::Vector {
+                items: Box::new(items.substitute(type_args)),
+            },
+            MoveType::Struct(tag) => MoveType::Struct(MoveStructTag {
+                generic_type_params: tag
+                    .generic_type_params
+                    .iter()
+                    .map(|param| param.substitute(type_args))
+                    .collect(),
+                ..tag.clone()
+            }),
+            MoveType::Reference { mutable, to } => MoveType::Reference {
+                mutable: *mutable,
+                to: Box::new(to.substitute(type_args)),
+            },
+            _ => self.clone(),
+        }
+    }
 }
 
 impl From<TypeTag> for MoveType {
@@ -647,7 +837,12 @@ impl From<MoveModuleId> for ModuleId {
 
 impl fmt::Display for MoveModuleId {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}::{}", self.address, self.name)
+        write!(
+            f,
+            "{}::{}",
+            self.address.inner().to_hex_literal(),
+            self.name
+        )
     }
 }
 
@@ -923,7 +1118,9 @@ impl MoveScriptBytecode {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Object)]
+/// A function id, e.g. "0x1::coin::transfer". It is a string in JSON, so
+/// it is exposed to the spec as one (see `impl_poem_type!` below).
+#[derive(Clone, Debug, PartialEq)]
 pub struct ScriptFunctionId {
     pub module: MoveModuleId,
     pub name: IdentifierWrapper,
@@ -1062,11 +1259,14 @@ mod tests {
                     "field_u64": "7",
                     "field_u128": "7",
                     "field_bool": true,
-                    "field_address": "0xdd",
+                    "field_address": long_address("0xdd"),
                     "field_vector": ["128"],
                     "field_bytes": "0x0909",
                     "field_struct": {
-                        "nested_vector": [{"address1": "0x0", "address2": "0x123"}]
+                        "nested_vector": [{
+                            "address1": long_address("0x0"),
+                            "address2": long_address("0x123"),
+                        }]
                     },
                 }
             }),
@@ -1089,7 +1289,7 @@ mod tests {
             json!({
                 "type": "0x1::type::Values",
                 "data": {
-                    "address_0x0": "0x0",
+                    "address_0x0": long_address("0x0"),
                 }
             }),
         );
@@ -1105,6 +1305,58 @@ mod tests {
         test_serialize_deserialize(U128::from(u128::MAX), json!(u128::MAX.to_string()))
     }
 
+    #[test]
+    fn test_deserialize_uint_from_number() {
+        assert_eq!(
+            serde_json::from_value::<U64>(json!(u64::MAX)).unwrap(),
+            U64::from(u64::MAX)
+        );
+        assert_eq!(
+            serde_json::from_str::<U128>(&u64::MAX.to_string()).unwrap(),
+            U128::from(u64::MAX as u128)
+        );
+        assert_eq!(
+            serde_json::from_value::<U128>(json!(u128::MAX.to_string())).unwrap(),
+            U128::from(u128::MAX)
+        );
+    }
+
+    #[test]
+    fn test_deserialize_uint_rejects_floats_and_out_of_range_values() {
+        assert!(serde_json::from_str::<U64>("1e5").is_err());
+        assert!(serde_json::from_str::<U64>("100000.0").is_err());
+        assert!(serde_json::from_value::<U64>(json!("1e5")).is_err());
+        assert!(serde_json::from_value::<U64>(json!(-1)).is_err());
+        assert!(serde_json::from_value::<U64>(json!("18446744073709551616")).is_err());
+        assert!(serde_json::from_str::<U128>("1e5").is_err());
+        assert!(serde_json::from_value::<U128>(json!("-1")).is_err());
+        assert!(serde_json::from_value::<U64>(json!(null)).is_err());
+    }
+
+    #[test]
+    fn test_poem_uint_is_a_string() {
+        use poem_openapi::types::{ParseFromJSON, ToJSON};
+
+        assert_eq!(
+            U64::from(u64::MAX).to_json(),
+            Some(json!(u64::MAX.to_string()))
+        );
+        assert_eq!(
+            U128::from(u128::MAX).to_json(),
+            Some(json!(u128::MAX.to_string()))
+        );
+        assert_eq!(
+            U64::parse_from_json(Some(json!("42"))).unwrap(),
+            U64::from(42)
+        );
+        assert_eq!(
+            U64::parse_from_json(Some(json!(42))).unwrap(),
+            U64::from(42)
+        );
+        assert!(U64::parse_from_json(Some(json!(1e5))).is_err());
+        assert!(U128::parse_from_json(Some(json!(1e5))).is_err());
+    }
+
     #[test]
     fn test_serialize_deserialize_move_module_id() {
         test_serialize_deserialize(
@@ -1276,6 +1528,10 @@ mod tests {
         }
     }
 
+    fn long_address(hex: &str) -> String {
+        format!("{:#x}", address(hex))
+    }
+
     fn address(hex: &str) -> AccountAddress {
         AccountAddress::from_hex_literal(hex).unwrap()
     }
@@ -1316,5 +1572,11 @@ mod tests {
 // with great caution, since it essentially rewrites the type to be a string
 // from the perspective of the OpenAPI spec, potentially losing some useful
 // type information that the client could use.
-impl_poem_type!(MoveAbility, MoveStructValue, MoveType, HexEncodedBytes);
+impl_poem_type!(
+    MoveAbility,
+    MoveStructValue,
+    MoveType,
+    HexEncodedBytes,
+    ScriptFunctionId
+);
 impl_poem_parameter!(HexEncodedBytes);
@@ -0,0 +1,41 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::U64;
+use poem_openapi::{Enum as PoemEnum, Object as PoemObject};
+use serde::{Deserialize, Serialize};
+
+/// The high level state of state sync on the node.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, PoemEnum, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+pub enum SyncState {
+    /// The node is still bootstrapping and can't serve up to date data
+    Bootstrapping,
+    /// The node has bootstrapped but its peers advertise newer data
+    CatchingUp,
+    /// The node is synced to the highest version advertised by its peers
+    CaughtUp,
+}
+
+/// The struct holding all data returned to the client by the
+/// node info endpoint (i.e., GET "/-/node_info").
+///
+/// Fields that the node doesn't know yet (e.g., because state sync hasn't
+/// reported its status) are omitted rather than failing the request.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, PoemObject, Serialize)]
+pub struct NodeInfo {
+    pub sync_state: Option<SyncState>,
+    /// The latest version synced to storage
+    pub synced_version: Option<U64>,
+    /// The epoch of the latest synced version
+    pub synced_epoch: Option<U64>,
+    /// How far (in seconds) the latest synced ledger info lags behind the
+    /// node's wall clock
+    pub sync_lag_secs: Option<U64>,
+    /// The highest version advertised by the node's peers
+    pub highest_advertised_version: Option<U64>,
+    /// The progress (0 to 100) of the state snapshot download. Only set
+    /// while the node is fast syncing.
+    pub snapshot_sync_progress_percent: Option<u8>,
+}
@@ -0,0 +1,76 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{HexEncodedBytes, U64};
+use move_deps::move_core_types::gas_schedule::GasConstants;
+use poem_openapi::Object as PoemObject;
+use serde::{Deserialize, Serialize};
+
+/// The on-chain configuration returned by the configs endpoints (i.e., GET
+/// "/configs" and GET "/configs/{name}"). When getting a single config,
+/// only that one is set.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, PoemObject, Serialize)]
+#[oai(skip_serializing_if_is_none)]
+pub struct OnChainConfigs {
+    /// The gas constants of the gas schedule
+    pub gas_schedule: Option<GasSchedule>,
+    /// How long (in microseconds) each epoch lasts
+    pub epoch_interval_usecs: Option<U64>,
+    /// The number of validators in the validator set
+    pub validator_set_size: Option<U64>,
+    /// The BCS encoded `0x1::version::Version` resource
+    pub version: Option<HexEncodedBytes>,
+    /// The BCS encoded `0x1::consensus_config::ConsensusConfig` resource
+    pub consensus_config: Option<HexEncodedBytes>,
+}
+
+/// The gas constants of the on-chain gas schedule. Gas units are internal
+/// units unless noted otherwise; divide by `gas_unit_scaling_factor` to get
+/// the units transactions are charged in.
+#[derive(Clone, Debug, Deserialize, PartialEq, PoemObject, Serialize)]
+pub struct GasSchedule {
+    /// The cost per byte read from global storage
+    pub global_memory_per_byte_cost: U64,
+    /// The cost per byte written to global storage
+    pub global_memory_per_byte_write_cost: U64,
+    /// The flat minimum amount of gas required for any transaction
+    pub min_transaction_gas_units: U64,
+    /// Transactions larger than this (in bytes) are charged an additional
+    /// amount per byte
+    pub large_transaction_cutoff: U64,
+    /// The gas charged per byte over `large_transaction_cutoff`
+    pub intrinsic_gas_per_byte: U64,
+    /// The most gas (in external units) a transaction may use
+    pub maximum_number_of_gas_units: U64,
+    /// The lowest gas unit price a transaction may be submitted with
+    pub min_price_per_gas_unit: U64,
+    /// The highest gas unit price a transaction may be submitted with
+    pub max_price_per_gas_unit: U64,
+    /// The largest a transaction may be, in bytes
+    pub max_transaction_size_in_bytes: U64,
+    /// The number of internal gas units per external gas unit
+    pub gas_unit_scaling_factor: U64,
+    /// The size (in bytes) accounts are charged for
+    pub default_account_size: U64,
+}
+
+impl From<&GasConstants> for GasSchedule {
+    fn from(constants: &GasConstants) -> Self {
+        Self {
+            global_memory_per_byte_cost: constants.global_memory_per_byte_cost.get().into(),
+            global_memory_per_byte_write_cost: constants
+                .global_memory_per_byte_write_cost
+                .get()
+                .into(),
+            min_transaction_gas_units: constants.min_transaction_gas_units.get().into(),
+            large_transaction_cutoff: constants.large_transaction_cutoff.get().into(),
+            intrinsic_gas_per_byte: constants.intrinsic_gas_per_byte.get().into(),
+            maximum_number_of_gas_units: constants.maximum_number_of_gas_units.get().into(),
+            min_price_per_gas_unit: constants.min_price_per_gas_unit.get().into(),
+            max_price_per_gas_unit: constants.max_price_per_gas_unit.get().into(),
+            max_transaction_size_in_bytes: constants.max_transaction_size_in_bytes.into(),
+            gas_unit_scaling_factor: constants.gas_unit_scaling_factor.into(),
+            default_account_size: constants.default_account_size.get().into(),
+        }
+    }
+}
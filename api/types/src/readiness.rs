@@ -0,0 +1,38 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use poem_openapi::{Enum as PoemEnum, Object as PoemObject};
+use serde::{Deserialize, Serialize};
+
+/// A check the readiness probe makes before saying the node is ready.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, PoemEnum, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[oai(rename_all = "snake_case")]
+pub enum ReadinessCheck {
+    /// Storage answers in time
+    Storage,
+    /// State sync has finished bootstrapping
+    StateSync,
+    /// The latest ledger info is recent enough
+    LedgerStaleness,
+    /// The API isn't shedding load
+    LoadShedding,
+    /// The API isn't shutting down
+    Shutdown,
+}
+
+/// A readiness check that failed, and why.
+#[derive(Clone, Debug, Deserialize, PartialEq, PoemObject, Serialize)]
+pub struct FailedReadinessCheck {
+    pub check: ReadinessCheck,
+    pub message: String,
+}
+
+/// The struct returned to the client by the readiness probe (i.e., GET
+/// "/-/ready").
+#[derive(Clone, Debug, Deserialize, PartialEq, PoemObject, Serialize)]
+pub struct Readiness {
+    /// Whether the node is ready to receive traffic, i.e. no check failed
+    pub ready: bool,
+    pub failed_checks: Vec<FailedReadinessCheck>,
+}
@@ -0,0 +1,105 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// Why rendering a value as JSON failed.
+#[derive(Debug)]
+pub enum JsonRenderError {
+    /// The rendered JSON would be larger than the limit.
+    TooLarge,
+    Json(serde_json::Error),
+}
+
+/// Serializes `value` as JSON, giving up as soon as the output grows past
+/// `limit` bytes. The limit is checked as the output is written, so no
+/// more than `limit` bytes are ever buffered, however large `value` is.
+pub fn to_json_with_limit<T: Serialize + ?Sized>(
+    value: &T,
+    limit: u64,
+) -> Result<Vec<u8>, JsonRenderError> {
+    let mut body = Vec::new();
+    write_json_with_limit(&mut body, value, limit)?;
+    Ok(body)
+}
+
+/// Checks that `value` renders to at most `limit` bytes of JSON, without
+/// keeping the rendered JSON around. For responses whose JSON is rendered
+/// later by the framework.
+pub fn check_json_size<T: Serialize + ?Sized>(
+    value: &T,
+    limit: u64,
+) -> Result<(), JsonRenderError> {
+    write_json_with_limit(io::sink(), value, limit)
+}
+
+fn write_json_with_limit<W: Write, T: Serialize + ?Sized>(
+    writer: W,
+    value: &T,
+    limit: u64,
+) -> Result<(), JsonRenderError> {
+    let mut writer = LimitedWriter {
+        inner: writer,
+        remaining: limit,
+        exceeded: false,
+    };
+    match serde_json::to_writer(&mut writer, value) {
+        Ok(()) => Ok(()),
+        Err(_) if writer.exceeded => Err(JsonRenderError::TooLarge),
+        Err(err) => Err(JsonRenderError::Json(err)),
+    }
+}
+
+/// A writer that fails once more than `remaining` bytes are written to it.
+struct LimitedWriter<W> {
+    inner: W,
+    remaining: u64,
+    exceeded: bool,
+}
+
+impl<W: Write> Write for LimitedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() as u64 > self.remaining {
+            self.exceeded = true;
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "the rendered JSON is too large",
+            ));
+        }
+        let written = self.inner.write(buf)?;
+        self.remaining -= written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_json_with_limit() {
+        let value = vec!["abc"; 10];
+        let json = serde_json::to_vec(&value).unwrap();
+
+        assert_eq!(to_json_with_limit(&value, json.len() as u64).unwrap(), json);
+        assert!(matches!(
+            to_json_with_limit(&value, json.len() as u64 - 1),
+            Err(JsonRenderError::TooLarge)
+        ));
+    }
+
+    #[test]
+    fn test_check_json_size() {
+        let value = vec![u64::MAX; 1000];
+        assert!(check_json_size(&value, 1_000_000).is_ok());
+        assert!(matches!(
+            check_json_size(&value, 1000),
+            Err(JsonRenderError::TooLarge)
+        ));
+    }
+}
@@ -3,12 +3,14 @@
 
 use crate::{
     mime_types::{BCS, JSON},
+    render_limit::{to_json_with_limit, JsonRenderError},
     Error, LedgerInfo,
 };
 use anyhow::Result;
+use aptos_crypto::HashValue;
 use serde::Serialize;
 use warp::{
-    http::header::{HeaderValue, CONTENT_TYPE},
+    http::header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE, ETAG},
     hyper::StatusCode,
 };
 
@@ -17,11 +19,24 @@ pub const X_APTOS_EPOCH: &str = "X-Aptos-Epoch";
 pub const X_APTOS_LEDGER_VERSION: &str = "X-Aptos-Ledger-Version";
 pub const X_APTOS_LEDGER_OLDEST_VERSION: &str = "X-Aptos-Ledger-Oldest-Version";
 pub const X_APTOS_LEDGER_TIMESTAMP: &str = "X-Aptos-Ledger-TimestampUsec";
+pub const X_APTOS_PAGE_SIZE: &str = "X-Aptos-Page-Size";
+
+/// The Cache-Control header value for responses that never change, e.g.
+/// committed transactions and state read at an explicit ledger version.
+pub const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=86400";
+/// The Cache-Control header value for everything else, e.g. latest state.
+pub const UNCACHEABLE_CACHE_CONTROL: &str = "no-store";
 
 pub struct Response {
     pub ledger_info: LedgerInfo,
     pub body: Vec<u8>,
     pub is_bcs_response: bool,
+    pub page_size: Option<u16>,
+    /// Only set for immutable responses, see [`Response::immutable`].
+    pub etag: Option<String>,
+    /// Whether the client already has this response, in which case we reply
+    /// with a 304 and no body.
+    pub not_modified: bool,
 }
 
 impl Response {
@@ -30,6 +45,9 @@ impl Response {
             ledger_info,
             body: serde_json::to_vec(body)?,
             is_bcs_response: false,
+            page_size: None,
+            etag: None,
+            not_modified: false,
         })
     }
 
@@ -43,19 +61,160 @@ impl Response {
                 )
             })?,
             is_bcs_response: true,
+            page_size: None,
+            etag: None,
+            not_modified: false,
+        })
+    }
+
+    /// Like `new`, but fails with a 413 instead of rendering more than
+    /// `limit` bytes of JSON. `what` names the data in the error, e.g.
+    /// "Resource".
+    pub fn new_with_limit<T: Serialize>(
+        ledger_info: LedgerInfo,
+        body: &T,
+        limit: u64,
+        what: &str,
+    ) -> Result<Self, Error> {
+        let body = to_json_with_limit(body, limit).map_err(|err| match err {
+            JsonRenderError::TooLarge => Error::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!(
+                    "{} too large to render as JSON (more than {} bytes), request it as BCS instead",
+                    what, limit
+                ),
+            ),
+            JsonRenderError::Json(err) => err.into(),
+        })?;
+        Ok(Self {
+            ledger_info,
+            body,
+            is_bcs_response: false,
+            page_size: None,
+            etag: None,
+            not_modified: false,
         })
     }
+
+    /// Builds a BCS response from bytes that are already BCS, e.g. read
+    /// straight from storage, failing with a 413 if there are more than
+    /// `limit` of them.
+    pub fn from_bcs_bytes(
+        ledger_info: LedgerInfo,
+        body: Vec<u8>,
+        limit: u64,
+        what: &str,
+    ) -> Result<Self, Error> {
+        if body.len() as u64 > limit {
+            return Err(Error::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!(
+                    "{} too large to return ({} bytes of BCS, more than {} bytes)",
+                    what,
+                    body.len(),
+                    limit
+                ),
+            ));
+        }
+        Ok(Self {
+            ledger_info,
+            body,
+            is_bcs_response: true,
+            page_size: None,
+            etag: None,
+            not_modified: false,
+        })
+    }
+
+    /// Sets the page size header, for responses from paginated endpoints.
+    pub fn with_page_size(mut self, page_size: u16) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    /// Marks the response as immutable, making it cacheable. Its ETag is the
+    /// hash of the canonical BCS of `payload`, the data the response renders.
+    /// If the client already has that ETag (per `if_none_match`), the
+    /// response becomes a 304 without a body.
+    pub fn immutable<T: Serialize>(
+        self,
+        payload: &T,
+        if_none_match: Option<&str>,
+    ) -> Result<Self, Error> {
+        let bytes = bcs::to_bytes(payload).map_err(|_| {
+            Error::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "data serialization error".to_string(),
+            )
+        })?;
+        let etag = self.etag_of(&bytes);
+        Ok(self.with_etag(etag, if_none_match))
+    }
+
+    /// Like `immutable`, for BCS responses built with `from_bcs_bytes`,
+    /// whose body already is the canonical BCS of the data.
+    pub fn immutable_bcs(self, if_none_match: Option<&str>) -> Self {
+        let etag = self.etag_of(&self.body);
+        self.with_etag(etag, if_none_match)
+    }
+
+    fn etag_of(&self, bytes: &[u8]) -> String {
+        // The JSON and BCS renderings of the same data are different
+        // representations, so they must not share an ETag.
+        format!(
+            "\"{}-{}\"",
+            if self.is_bcs_response { "bcs" } else { "json" },
+            HashValue::sha3_256_of(bytes).to_hex()
+        )
+    }
+
+    fn with_etag(mut self, etag: String, if_none_match: Option<&str>) -> Self {
+        self.not_modified = if_none_match.map_or(false, |tags| etag_matches(tags, &etag));
+        self.etag = Some(etag);
+        self
+    }
+}
+
+/// Returns whether the given If-None-Match header value matches the ETag.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
 }
 
 impl warp::Reply for Response {
     fn into_response(self) -> warp::reply::Response {
-        let mut res = warp::reply::Response::new(self.body.into());
+        let mut res = if self.not_modified {
+            let mut res = warp::reply::Response::new(Vec::new().into());
+            *res.status_mut() = StatusCode::NOT_MODIFIED;
+            res
+        } else {
+            warp::reply::Response::new(self.body.into())
+        };
         let headers = res.headers_mut();
 
-        if self.is_bcs_response {
-            headers.insert(CONTENT_TYPE, HeaderValue::from_static(BCS));
-        } else {
-            headers.insert(CONTENT_TYPE, HeaderValue::from_static(JSON));
+        // A 304 has no body, so there is no content type to describe
+        if !self.not_modified {
+            let content_type = if self.is_bcs_response { BCS } else { JSON };
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        }
+        match self.etag {
+            Some(etag) => {
+                if let Ok(etag) = HeaderValue::from_str(&etag) {
+                    headers.insert(ETAG, etag);
+                }
+                headers.insert(
+                    CACHE_CONTROL,
+                    HeaderValue::from_static(IMMUTABLE_CACHE_CONTROL),
+                );
+            }
+            None => {
+                headers.insert(
+                    CACHE_CONTROL,
+                    HeaderValue::from_static(UNCACHEABLE_CACHE_CONTROL),
+                );
+            }
         }
         headers.insert(X_APTOS_CHAIN_ID, (self.ledger_info.chain_id as u16).into());
         headers.insert(
@@ -71,6 +230,9 @@ impl warp::Reply for Response {
             self.ledger_info.ledger_timestamp.into(),
         );
         headers.insert(X_APTOS_EPOCH, self.ledger_info.epoch.into());
+        if let Some(page_size) = self.page_size {
+            headers.insert(X_APTOS_PAGE_SIZE, page_size.into());
+        }
 
         res
     }
@@ -306,6 +306,21 @@ pub struct PendingTransaction {
     pub request: UserTransactionRequest,
 }
 
+/// A transaction waiting in the node's mempool, along with the mempool's
+/// metadata about it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Object)]
+pub struct MempoolTransaction {
+    pub hash: HashValue,
+    #[serde(flatten)]
+    #[oai(flatten)]
+    pub request: UserTransactionRequest,
+    /// When the transaction was inserted into the mempool, in microseconds
+    /// since the Unix epoch
+    pub insertion_timestamp_usecs: U64,
+    /// The score the mempool ranks the transaction by when forming blocks
+    pub ranking_score: U64,
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Object)]
 pub struct UserTransaction {
     #[serde(flatten)]
@@ -0,0 +1,38 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{MoveType, ScriptFunctionId};
+use aptos_openapi::impl_poem_type;
+use move_deps::move_core_types::{
+    identifier::Identifier,
+    language_storage::{ModuleId, TypeTag},
+};
+use poem_openapi::Object as PoemObject;
+use serde::{Deserialize, Serialize};
+
+/// A call to a view function (i.e., POST "/view"), with its arguments
+/// encoded as JSON.
+#[derive(Clone, Debug, Deserialize, PartialEq, PoemObject, Serialize)]
+pub struct ViewRequest {
+    /// The function to call, e.g. "0x1::coin::balance"
+    pub function: ScriptFunctionId,
+    /// The type arguments of the function, e.g. "0x1::aptos_coin::AptosCoin"
+    pub type_arguments: Vec<MoveType>,
+    /// The arguments of the function, encoded the same way as the arguments
+    /// of a script function payload
+    pub arguments: Vec<serde_json::Value>,
+}
+
+/// A call to a view function, with its arguments encoded as BCS. This is
+/// the body of a POST "/view" request with content type
+/// "application/x-bcs".
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct BcsViewRequest {
+    pub module: ModuleId,
+    pub function: Identifier,
+    pub type_arguments: Vec<TypeTag>,
+    /// The BCS encoding of each argument
+    pub arguments: Vec<Vec<u8>>,
+}
+
+impl_poem_type!(BcsViewRequest);
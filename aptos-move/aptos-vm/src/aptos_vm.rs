@@ -27,9 +27,11 @@ use aptos_logger::prelude::*;
 use aptos_module_verifier::module_init::verify_module_init_function;
 use aptos_state_view::StateView;
 use aptos_types::{
-    account_config,
+    access_path::AccessPath,
+    account_config::{self, AccountResource, CoinStoreResource},
     block_metadata::{new_block_event_key, BlockMetadata},
     on_chain_config::{new_epoch_event_key, VMConfig, VMPublishingOption, Version},
+    state_store::state_key::StateKey,
     transaction::{
         ChangeSet, ExecutionStatus, ModuleBundle, SignatureCheckedTransaction, SignedTransaction,
         Transaction, TransactionOutput, TransactionPayload, TransactionStatus, VMValidatorResult,
@@ -49,7 +51,9 @@ use move_deps::{
         account_address::AccountAddress,
         gas_schedule::{GasAlgebra, GasUnits},
         ident_str,
-        language_storage::ModuleId,
+        identifier::Identifier,
+        language_storage::{ModuleId, TypeTag},
+        move_resource::MoveResource,
         transaction_argument::convert_txn_args,
         value::{serialize_values, MoveValue},
     },
@@ -918,6 +922,41 @@ impl AptosVM {
         simulation_vm.simulate_signed_transaction(&state_view.as_move_resolver(), txn, &log_context)
     }
 
+    /// Executes a view function, i.e. a read only Move function, against the
+    /// given state and returns its BCS serialized return values. Execution is
+    /// metered with at most `max_gas_amount` gas units and any changes the
+    /// function makes are discarded.
+    pub fn execute_view_function(
+        state_view: &impl StateView,
+        module_id: &ModuleId,
+        func_name: &Identifier,
+        type_args: Vec<TypeTag>,
+        arguments: Vec<Vec<u8>>,
+        max_gas_amount: u64,
+    ) -> Result<Vec<Vec<u8>>, VMStatus> {
+        let vm = AptosVM::new(state_view);
+        let log_context = AdapterLogSchema::new(state_view.id(), 0);
+        let gas_schedule = vm.0.get_gas_schedule(&log_context)?;
+        let mut gas_status = GasStatus::new(gas_schedule, GasUnits::new(max_gas_amount));
+
+        let resolver = state_view.as_move_resolver();
+        let mut session = vm.0.new_session(&resolver, SessionId::void());
+        let return_values = session
+            .execute_function_bypass_visibility(
+                module_id,
+                func_name,
+                type_args,
+                arguments,
+                &mut gas_status,
+            )
+            .map_err(|err| err.into_vm_status())?
+            .return_values;
+        Ok(return_values
+            .into_iter()
+            .map(|(bytes, _layout)| bytes)
+            .collect())
+    }
+
     fn run_prologue_with_payload<S: MoveResolverExt>(
         &self,
         session: &mut SessionExt<S>,
@@ -979,6 +1018,26 @@ impl VMExecutor for AptosVM {
                 .collect())
         }
     }
+
+    /// The account and coin store of the sender of each user transaction, which the prologue and
+    /// the epilogue read.
+    fn keys_to_prefetch(transactions: &[Transaction]) -> Vec<StateKey> {
+        transactions
+            .iter()
+            .filter_map(|txn| match txn {
+                Transaction::UserTransaction(txn) => Some(txn.sender()),
+                _ => None,
+            })
+            .flat_map(|sender| {
+                vec![
+                    AccountResource::resource_path(),
+                    CoinStoreResource::resource_path(),
+                ]
+                .into_iter()
+                .map(move |path| StateKey::AccessPath(AccessPath::new(sender, path)))
+            })
+            .collect()
+    }
 }
 
 // VMValidator external API
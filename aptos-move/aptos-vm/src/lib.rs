@@ -128,6 +128,7 @@ pub use crate::aptos_vm::AptosVM;
 use aptos_state_view::StateView;
 use aptos_types::{
     access_path::AccessPath,
+    state_store::state_key::StateKey,
     transaction::{SignedTransaction, Transaction, TransactionOutput, VMValidatorResult},
     vm_status::VMStatus,
 };
@@ -158,6 +159,12 @@ pub trait VMExecutor: Send + Sync {
         transactions: Vec<Transaction>,
         state_view: &impl StateView,
     ) -> Result<Vec<TransactionOutput>, VMStatus>;
+
+    /// Returns keys `execute_block` is known to read for `transactions`, so they can be fetched
+    /// from storage in one batch beforehand. This is only a hint: by default, there are none.
+    fn keys_to_prefetch(_transactions: &[Transaction]) -> Vec<StateKey> {
+        vec![]
+    }
 }
 
 /// Get the AccessPath to a resource stored under `address` with type name `tag`
@@ -40,6 +40,7 @@ use mempool_notifications::MempoolNotificationSender;
 use network::application::storage::PeerMetadataStorage;
 use network_builder::builder::NetworkBuilder;
 use rand::{rngs::StdRng, SeedableRng};
+use state_sync_driver::metadata_storage::PersistentMetadataStorage;
 use state_sync_multiplexer::{
     state_sync_v1_network_config, StateSyncMultiplexer, StateSyncRuntimes,
 };
@@ -353,12 +354,20 @@ fn create_state_sync_runtimes<M: MempoolNotificationSender + 'static>(
     )?;
 
     // Create the chunk executor
-    let chunk_executor = Arc::new(ChunkExecutor::<AptosVM>::new(db_rw.clone()));
+    let mut chunk_executor = ChunkExecutor::<AptosVM>::new(db_rw.clone());
+    if let Some(sub_chunk_size) = node_config.execution.sub_chunk_size {
+        chunk_executor = chunk_executor.with_sub_chunk_size(sub_chunk_size);
+    }
+    let chunk_executor = Arc::new(chunk_executor);
+
+    // Create the metadata storage used to persist state sync progress
+    let metadata_storage = PersistentMetadataStorage::new(&node_config.storage.dir());
 
     // Create the state sync multiplexer
     let state_sync_multiplexer = StateSyncMultiplexer::new(
         state_sync_network_handles,
         mempool_notifier,
+        metadata_storage,
         consensus_listener,
         db_rw,
         chunk_executor,
@@ -654,7 +663,13 @@ pub fn setup_environment(node_config: NodeConfig) -> anyhow::Result<AptosHandle>
 
     let (mp_client_sender, mp_client_events) = channel(AC_SMP_CHANNEL_BUFFER_SIZE);
 
-    let api_runtime = bootstrap_api(&node_config, chain_id, aptos_db, mp_client_sender)?;
+    let api_runtime = bootstrap_api(
+        &node_config,
+        chain_id,
+        aptos_db,
+        mp_client_sender,
+        state_sync_runtimes.sync_status_handle(),
+    )?;
 
     let mut consensus_runtime = None;
     let (consensus_to_mempool_sender, consensus_to_mempool_receiver) =
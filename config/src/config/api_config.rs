@@ -3,7 +3,7 @@
 
 use crate::utils;
 use serde::{Deserialize, Serialize};
-use std::net::SocketAddr;
+use std::{collections::BTreeMap, net::SocketAddr, time::Duration};
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(default, deny_unknown_fields)]
@@ -11,6 +11,10 @@ pub struct ApiConfig {
     #[serde(default = "default_enabled")]
     pub enabled: bool,
     pub address: SocketAddr,
+    /// More addresses to serve the API on besides `address`, e.g. "[::]:8080"
+    /// for a dual-stack host. Note that on most systems binding to "[::]"
+    /// also accepts IPv4 connections unless the OS is configured otherwise.
+    pub additional_addresses: Vec<SocketAddr>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tls_cert_path: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -18,11 +22,159 @@ pub struct ApiConfig {
     // optional for compatible with old configuration
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub content_length_limit: Option<u64>,
+    /// Overrides `content_length_limit` for submitting and simulating
+    /// transactions, since transactions publishing modules can be large.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub submission_content_length_limit: Option<u64>,
+    /// Overrides `content_length_limit` for table item lookups (i.e., POST
+    /// "/tables/:table_handle/item"), whose bodies are only ever a key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table_item_content_length_limit: Option<u64>,
+    /// Maximum number of requests that may be executing at the same time.
+    pub max_concurrent_requests: usize,
+    /// Maximum number of known-expensive requests (e.g. listing resources,
+    /// simulating transactions) that may be executing at the same time.
+    /// These also count towards `max_concurrent_requests`.
+    pub max_concurrent_expensive_requests: usize,
+    /// How long a request may wait for a free slot before being rejected
+    /// with a 429.
+    pub concurrency_limit_wait_ms: u64,
+    /// The page size used by paginated endpoints when the client doesn't
+    /// give a limit.
+    pub default_page_size: u16,
+    /// The largest page size paginated endpoints will return. Larger limits
+    /// given by the client are clamped to this.
+    pub max_page_size: u16,
+    /// If set, operator endpoints (currently GET and POST "/log/filter")
+    /// are served on this address, e.g. "127.0.0.1:8081". They are never
+    /// served on the public API addresses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_address: Option<SocketAddr>,
+    /// Only allow changing the logger filter at runtime (i.e., POST
+    /// "/log/filter") from loopback addresses, even on `admin_address`.
+    pub log_filter_localhost_only: bool,
+    /// Serve the node's Prometheus metrics at "/-/metrics" on the API port.
+    /// Off by default since the API is usually public.
+    pub metrics_enabled: bool,
+    /// If not empty, only metric families whose name starts with one of
+    /// these prefixes are served at "/-/metrics".
+    pub metrics_allowed_prefixes: Vec<String>,
+    /// The fraction (0 to 1) of requests that are wrapped in a tracing span.
+    pub tracing_sample_rate: f64,
+    /// Per route overrides of `tracing_sample_rate`, keyed by the route
+    /// template as it appears in the OpenAPI spec, e.g. "/transactions".
+    pub tracing_route_sample_rates: BTreeMap<String, f64>,
+    /// If set, request spans are exported to this OTLP (gRPC) endpoint
+    /// instead of the node's logger, e.g. "http://localhost:4317".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tracing_otlp_endpoint: Option<String>,
+    /// How long (in milliseconds) requests in each route group may take
+    /// before they are cancelled with a 408. Groups missing from the map
+    /// use their default budget.
+    pub request_timeouts_ms: BTreeMap<RouteGroup, u64>,
+    /// The most gas a call to a view function (i.e., POST "/view") may use.
+    pub view_max_gas: u64,
+    /// The most requests a batch (i.e., POST "/batch") may contain.
+    pub batch_max_requests: usize,
+    /// How many requests of a single batch may be executing at the same
+    /// time.
+    pub batch_max_parallelism: usize,
+    /// The readiness probe (i.e., GET "/-/ready") fails if the latest
+    /// ledger info is older than this.
+    pub readiness_max_ledger_staleness_secs: u64,
+    /// The readiness probe fails if reading the latest ledger info from
+    /// storage takes longer than this. Keep it below the budget of the
+    /// reads route group, or the probe times out instead.
+    pub readiness_storage_timeout_ms: u64,
+    /// How long the API keeps accepting connections after a graceful
+    /// shutdown starts. The readiness probe fails for this time, so load
+    /// balancers can stop sending traffic first.
+    pub shutdown_drain_delay_ms: u64,
+    /// How many transactions per second each sender may submit (i.e., POST
+    /// "/transactions") on average.
+    pub sender_submission_rate_per_sec: f64,
+    /// How many transactions a sender may submit at once before being held
+    /// to `sender_submission_rate_per_sec`. Set to 0 to disable the per
+    /// sender limit.
+    pub sender_submission_burst: u64,
+    /// The most senders whose submission rate is tracked. The least recently
+    /// seen senders are forgotten first, which resets their limit.
+    pub sender_rate_limit_max_senders: usize,
+    /// The largest JSON response the resource, table item and view
+    /// endpoints will render. Larger responses get a 413 asking for BCS
+    /// instead, since rendering huge Move values as JSON takes a lot of
+    /// memory.
+    pub max_json_render_bytes: u64,
+    /// The largest BCS response the resource, table item and view endpoints
+    /// will return. BCS costs little to produce, so this can be well above
+    /// `max_json_render_bytes`.
+    pub max_bcs_render_bytes: u64,
+}
+
+/// The groups of API routes that share a request timeout budget.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteGroup {
+    /// Point lookups, e.g. getting an account or a transaction by hash
+    Reads,
+    /// Paginated listings, e.g. getting transactions, events or the
+    /// resources of an account
+    Listings,
+    /// Submitting transactions
+    Submission,
+    /// Simulating transactions
+    Simulation,
+}
+
+impl RouteGroup {
+    pub const ALL: [RouteGroup; 4] = [
+        RouteGroup::Reads,
+        RouteGroup::Listings,
+        RouteGroup::Submission,
+        RouteGroup::Simulation,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RouteGroup::Reads => "reads",
+            RouteGroup::Listings => "listings",
+            RouteGroup::Submission => "submission",
+            RouteGroup::Simulation => "simulation",
+        }
+    }
+
+    pub fn default_timeout_ms(&self) -> u64 {
+        match self {
+            RouteGroup::Reads => 500,
+            RouteGroup::Listings => 2_000,
+            RouteGroup::Submission => 5_000,
+            RouteGroup::Simulation => 10_000,
+        }
+    }
 }
 
 pub const DEFAULT_ADDRESS: &str = "127.0.0.1";
 pub const DEFAULT_PORT: u16 = 8080;
 pub const DEFAULT_REQUEST_CONTENT_LENGTH_LIMIT: u64 = 4 * 1024 * 1024; // 4mb
+pub const DEFAULT_SUBMISSION_CONTENT_LENGTH_LIMIT: u64 = 8 * 1024 * 1024; // 8mb
+pub const DEFAULT_TABLE_ITEM_CONTENT_LENGTH_LIMIT: u64 = 64 * 1024; // 64kb
+pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 512;
+pub const DEFAULT_MAX_CONCURRENT_EXPENSIVE_REQUESTS: usize = 64;
+pub const DEFAULT_CONCURRENCY_LIMIT_WAIT_MS: u64 = 100;
+pub const DEFAULT_PAGE_SIZE: u16 = 25;
+pub const DEFAULT_MAX_PAGE_SIZE: u16 = 1000;
+pub const DEFAULT_TRACING_SAMPLE_RATE: f64 = 1.0;
+pub const DEFAULT_VIEW_MAX_GAS: u64 = 1_000_000;
+pub const DEFAULT_BATCH_MAX_REQUESTS: usize = 20;
+pub const DEFAULT_BATCH_MAX_PARALLELISM: usize = 4;
+pub const DEFAULT_READINESS_MAX_LEDGER_STALENESS_SECS: u64 = 60;
+pub const DEFAULT_READINESS_STORAGE_TIMEOUT_MS: u64 = 200;
+pub const DEFAULT_SHUTDOWN_DRAIN_DELAY_MS: u64 = 5_000;
+pub const DEFAULT_SENDER_SUBMISSION_RATE_PER_SEC: f64 = 10.0;
+pub const DEFAULT_SENDER_SUBMISSION_BURST: u64 = 50;
+pub const DEFAULT_SENDER_RATE_LIMIT_MAX_SENDERS: usize = 100_000;
+pub const DEFAULT_MAX_JSON_RENDER_BYTES: u64 = 8 * 1024 * 1024; // 8mb
+pub const DEFAULT_MAX_BCS_RENDER_BYTES: u64 = 64 * 1024 * 1024; // 64mb
 
 fn default_enabled() -> bool {
     true
@@ -35,9 +187,39 @@ impl Default for ApiConfig {
             address: format!("{}:{}", DEFAULT_ADDRESS, DEFAULT_PORT)
                 .parse()
                 .unwrap(),
+            additional_addresses: vec![],
             tls_cert_path: None,
             tls_key_path: None,
             content_length_limit: None,
+            submission_content_length_limit: None,
+            table_item_content_length_limit: None,
+            max_concurrent_requests: DEFAULT_MAX_CONCURRENT_REQUESTS,
+            max_concurrent_expensive_requests: DEFAULT_MAX_CONCURRENT_EXPENSIVE_REQUESTS,
+            concurrency_limit_wait_ms: DEFAULT_CONCURRENCY_LIMIT_WAIT_MS,
+            default_page_size: DEFAULT_PAGE_SIZE,
+            max_page_size: DEFAULT_MAX_PAGE_SIZE,
+            admin_address: None,
+            log_filter_localhost_only: true,
+            metrics_enabled: false,
+            metrics_allowed_prefixes: vec![],
+            tracing_sample_rate: DEFAULT_TRACING_SAMPLE_RATE,
+            tracing_route_sample_rates: BTreeMap::new(),
+            tracing_otlp_endpoint: None,
+            request_timeouts_ms: RouteGroup::ALL
+                .iter()
+                .map(|group| (*group, group.default_timeout_ms()))
+                .collect(),
+            view_max_gas: DEFAULT_VIEW_MAX_GAS,
+            batch_max_requests: DEFAULT_BATCH_MAX_REQUESTS,
+            batch_max_parallelism: DEFAULT_BATCH_MAX_PARALLELISM,
+            readiness_max_ledger_staleness_secs: DEFAULT_READINESS_MAX_LEDGER_STALENESS_SECS,
+            readiness_storage_timeout_ms: DEFAULT_READINESS_STORAGE_TIMEOUT_MS,
+            shutdown_drain_delay_ms: DEFAULT_SHUTDOWN_DRAIN_DELAY_MS,
+            sender_submission_rate_per_sec: DEFAULT_SENDER_SUBMISSION_RATE_PER_SEC,
+            sender_submission_burst: DEFAULT_SENDER_SUBMISSION_BURST,
+            sender_rate_limit_max_senders: DEFAULT_SENDER_RATE_LIMIT_MAX_SENDERS,
+            max_json_render_bytes: DEFAULT_MAX_JSON_RENDER_BYTES,
+            max_bcs_render_bytes: DEFAULT_MAX_BCS_RENDER_BYTES,
         }
     }
 }
@@ -45,6 +227,26 @@ impl Default for ApiConfig {
 impl ApiConfig {
     pub fn randomize_ports(&mut self) {
         self.address.set_port(utils::get_available_port());
+        if let Some(admin_address) = self.admin_address.as_mut() {
+            admin_address.set_port(utils::get_available_port());
+        }
+    }
+
+    /// Every address the API should be served on, starting with `address`.
+    pub fn addresses(&self) -> Vec<SocketAddr> {
+        std::iter::once(self.address)
+            .chain(self.additional_addresses.iter().copied())
+            .collect()
+    }
+
+    /// The timeout budget for requests in the given route group.
+    pub fn request_timeout(&self, group: RouteGroup) -> Duration {
+        Duration::from_millis(
+            self.request_timeouts_ms
+                .get(&group)
+                .copied()
+                .unwrap_or_else(|| group.default_timeout_ms()),
+        )
     }
 
     pub fn content_length_limit(&self) -> u64 {
@@ -53,4 +255,14 @@ impl ApiConfig {
             None => DEFAULT_REQUEST_CONTENT_LENGTH_LIMIT,
         }
     }
+
+    pub fn submission_content_length_limit(&self) -> u64 {
+        self.submission_content_length_limit
+            .unwrap_or(DEFAULT_SUBMISSION_CONTENT_LENGTH_LIMIT)
+    }
+
+    pub fn table_item_content_length_limit(&self) -> u64 {
+        self.table_item_content_length_limit
+            .unwrap_or(DEFAULT_TABLE_ITEM_CONTENT_LENGTH_LIMIT)
+    }
 }
@@ -21,6 +21,15 @@ pub struct ExecutionConfig {
     pub network_timeout_ms: u64,
     pub concurrency_level: u16,
     pub num_proof_reading_threads: u16,
+    /// If set, the chunks state sync executes (or applies) and commits in
+    /// one go are processed in sub-chunks of about this many transactions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub_chunk_size: Option<usize>,
+    /// If set, consensus' block executor keeps the outputs of this many of
+    /// the last blocks it executed, and reuses them when asked to execute
+    /// one of those blocks again on top of the same parent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_result_cache_capacity: Option<usize>,
 }
 
 impl std::fmt::Debug for ExecutionConfig {
@@ -49,6 +58,8 @@ impl Default for ExecutionConfig {
             // Sequential execution by default.
             concurrency_level: 1,
             num_proof_reading_threads: 32,
+            sub_chunk_size: None,
+            block_result_cache_capacity: None,
         }
     }
 }
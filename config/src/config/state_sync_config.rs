@@ -57,9 +57,10 @@ impl Default for StateSyncConfig {
 
 /// The bootstrapping mode determines how the node will bootstrap to the latest
 /// blockchain state, e.g., directly download the latest states.
-#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum BootstrappingMode {
     ApplyTransactionOutputsFromGenesis, // Applies transaction outputs (starting at genesis)
+    AutoSelect, // Selects one of the other modes based on how far behind the node is
     DownloadLatestStates, // Downloads the state keys and values (at the latest version)
     ExecuteTransactionsFromGenesis, // Executes transactions (starting at genesis)
 }
@@ -70,6 +71,7 @@ impl BootstrappingMode {
             BootstrappingMode::ApplyTransactionOutputsFromGenesis => {
                 "apply_transaction_outputs_from_genesis"
             }
+            BootstrappingMode::AutoSelect => "auto_select",
             BootstrappingMode::DownloadLatestStates => "download_latest_states",
             BootstrappingMode::ExecuteTransactionsFromGenesis => {
                 "execute_transactions_from_genesis"
@@ -96,17 +98,42 @@ impl ContinuousSyncingMode {
     }
 }
 
+/// The continuous streaming mode determines how the continuous syncer streams
+/// new data from the network, e.g., by subscribing to newly committed data.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ContinuousStreamingMode {
+    RequestResponse, // Creates new data streams whenever targets or chunk sizes change
+    Subscription, // Keeps a long-lived data stream (only reset on epoch changes or failures)
+}
+
 #[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct StateSyncDriverConfig {
+    pub auto_select_output_threshold: u64, // The number of versions behind (when auto selecting) above which outputs are applied
+    pub auto_select_snapshot_threshold: u64, // The number of versions behind (when auto selecting) above which states are downloaded
     pub bootstrapping_mode: BootstrappingMode, // The mode by which to bootstrap
-    pub enable_state_sync_v2: bool,            // If the node should sync with state sync v2
+    pub bootstrapping_target_version: Option<u64>, // The version to sync to and halt at (if any)
+    pub enable_chunk_timing_logs: bool, // If each committed chunk should log how long its stages took
+    pub enable_genesis_only_bootstrapping: bool, // If the node should bootstrap from local genesis when no peers are available
+    pub enable_state_sync_v2: bool,              // If the node should sync with state sync v2
+    pub continuous_streaming_mode: ContinuousStreamingMode, // The mode by which to stream new data after bootstrapping
     pub continuous_syncing_mode: ContinuousSyncingMode, // The mode by which to sync after bootstrapping
+    pub epoch_ending_stream_timeout_ms: u64, // The max time (ms) the epoch ending stream can stall before being reset
+    pub state_snapshot_stream_timeout_ms: u64, // The max time (ms) the state snapshot stream can stall before being reset
+    pub transaction_data_stream_timeout_ms: u64, // The max time (ms) the transaction data stream can stall before being reset
     pub progress_check_interval_ms: u64, // The interval (ms) at which to check state sync progress
     pub max_connection_deadline_secs: u64, // The max time (secs) to wait for connections from peers
     pub max_consecutive_stream_notifications: u64, // The max number of notifications to process per driver loop
-    pub max_pending_data_chunks: u64, // The max number of data chunks pending execution or commit
-    pub max_stream_wait_time_ms: u64, // The max time (ms) to wait for a data stream notification
+    pub max_continuous_chunk_size: u64, // The max num of transactions or outputs the continuous syncer requests per chunk
+    pub max_pending_data_chunks: u64,   // The max number of data chunks pending execution or commit
+    pub max_stream_wait_time_ms: u64,   // The max time (ms) to wait for a data stream notification
+    pub max_sync_lag_secs: u64, // The max seconds the node can fall behind its peers (once bootstrapped) before raising an alarm
+    pub max_sync_lag_versions: u64, // The max versions the node can fall behind its peers (once bootstrapped) before raising an alarm
+    pub min_continuous_chunk_size: u64, // The min num of transactions or outputs the continuous syncer requests per chunk
+    pub output_fallback_duration_secs: u64, // The min time (secs) to execute transactions after falling back from outputs
+    pub output_fallback_threshold: u64, // The number of consecutive unavailable output streams before falling back to transactions
+    pub sync_request_timeout_ms: u64, // The max time (ms) to satisfy a consensus sync request before it expires
+    pub target_chunk_latency_ms: u64, // The target time (ms) for storage to apply and commit a single chunk
 }
 
 /// The default state sync driver config will be the one that gets (and keeps)
@@ -114,14 +141,31 @@ pub struct StateSyncDriverConfig {
 impl Default for StateSyncDriverConfig {
     fn default() -> Self {
         Self {
+            auto_select_output_threshold: 100_000,
+            auto_select_snapshot_threshold: 10_000_000,
             bootstrapping_mode: BootstrappingMode::ApplyTransactionOutputsFromGenesis,
+            bootstrapping_target_version: None,
+            enable_chunk_timing_logs: false,
+            enable_genesis_only_bootstrapping: false,
             enable_state_sync_v2: true,
+            continuous_streaming_mode: ContinuousStreamingMode::RequestResponse,
             continuous_syncing_mode: ContinuousSyncingMode::ApplyTransactionOutputs,
+            epoch_ending_stream_timeout_ms: 30_000,
+            state_snapshot_stream_timeout_ms: 60_000,
+            transaction_data_stream_timeout_ms: 30_000,
             progress_check_interval_ms: 100,
             max_connection_deadline_secs: 10,
             max_consecutive_stream_notifications: 10,
+            max_continuous_chunk_size: 1000,
             max_pending_data_chunks: 100,
             max_stream_wait_time_ms: 5000,
+            max_sync_lag_secs: 60,
+            max_sync_lag_versions: 100_000,
+            min_continuous_chunk_size: 100,
+            output_fallback_duration_secs: 60,
+            output_fallback_threshold: 3,
+            sync_request_timeout_ms: 60_000,
+            target_chunk_latency_ms: 1000,
         }
     }
 }
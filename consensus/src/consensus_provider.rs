@@ -50,8 +50,12 @@ pub fn start_consensus(
         node_config.consensus.quorum_store_pull_timeout_ms,
     ));
 
+    let mut block_executor = BlockExecutor::<AptosVM>::new(aptos_db);
+    if let Some(capacity) = node_config.execution.block_result_cache_capacity {
+        block_executor = block_executor.with_result_cache(capacity);
+    }
     let state_computer = Arc::new(ExecutionProxy::new(
-        Arc::new(BlockExecutor::<AptosVM>::new(aptos_db)),
+        Arc::new(block_executor),
         txn_notifier,
         state_sync_notifier,
         commit_notifier.clone(),
@@ -448,6 +448,17 @@ impl Logger for AptosData {
             oneshot_receiver.recv().unwrap();
         }
     }
+
+    fn local_filter(&self) -> Option<Filter> {
+        Some(self.filter.read().local_filter.clone())
+    }
+
+    fn replace_local_filter(&self, filter: Filter) -> Option<Filter> {
+        Some(std::mem::replace(
+            &mut self.filter.write().local_filter,
+            filter,
+        ))
+    }
 }
 
 enum LoggerServiceEvent {
@@ -4,10 +4,23 @@
 //! Filtering definitions for controlling what modules and levels are logged
 
 use crate::{Level, Metadata};
-use std::{env, str::FromStr};
+use std::{env, fmt, str::FromStr};
 
 pub struct FilterParseError;
 
+/// The error returned when a directives string contains a directive that
+/// can't be parsed. Holds the offending directive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidDirective(String);
+
+impl fmt::Display for InvalidDirective {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid log filter directive \"{}\"", self.0)
+    }
+}
+
+impl std::error::Error for InvalidDirective {}
+
 /// A definition of the most verbose `Level` allowed, or completely off.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum LevelFilter {
@@ -40,6 +53,20 @@ impl FromStr for LevelFilter {
     }
 }
 
+impl fmt::Display for LevelFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            LevelFilter::Off => "off",
+            LevelFilter::Error => "error",
+            LevelFilter::Warn => "warn",
+            LevelFilter::Info => "info",
+            LevelFilter::Debug => "debug",
+            LevelFilter::Trace => "trace",
+        };
+        f.pad(name)
+    }
+}
+
 impl From<Level> for LevelFilter {
     fn from(level: Level) -> Self {
         match level {
@@ -102,6 +129,20 @@ impl Builder {
         self
     }
 
+    /// Parses a directives string like `parse`, but fails on the first
+    /// invalid directive instead of skipping it. Empty directives (e.g. from
+    /// a trailing comma) are ignored.
+    pub fn try_parse(&mut self, filters: &str) -> Result<&mut Self, InvalidDirective> {
+        for directive in filters.split(',').filter(|d| !d.trim().is_empty()) {
+            let parsed = Directive::from_str(directive)
+                .ok()
+                .filter(|parsed| parsed.name.as_deref() != Some(""))
+                .ok_or_else(|| InvalidDirective(directive.trim().to_string()))?;
+            self.directives.push(parsed);
+        }
+        Ok(self)
+    }
+
     pub fn build(&mut self) -> Filter {
         if self.directives.is_empty() {
             // Add the default filter if none exist
@@ -123,7 +164,7 @@ impl Builder {
 }
 
 /// A logging filter to determine which logs to keep or remove based on `Directive`s
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Filter {
     directives: Vec<Directive>,
 }
@@ -145,8 +186,36 @@ impl Filter {
     }
 }
 
+/// Strictly parses a directives string, e.g. `info,crate1::mod1=debug`.
+/// Unlike `Builder::parse`, any invalid directive fails the whole parse.
+impl FromStr for Filter {
+    type Err = InvalidDirective;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut builder = Builder::new();
+        builder.try_parse(s)?;
+        if builder.directives.is_empty() {
+            return Err(InvalidDirective(s.trim().to_string()));
+        }
+        Ok(builder.build())
+    }
+}
+
+/// Renders the filter as a directives string that parses back to the same filter.
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, directive) in self.directives.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", directive)?;
+        }
+        Ok(())
+    }
+}
+
 /// A `Filter` directive for which logs to keep based on a module `name` based filter
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 struct Directive {
     name: Option<String>,
     level: LevelFilter,
@@ -183,9 +252,18 @@ impl FromStr for Directive {
     }
 }
 
+impl fmt::Display for Directive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{}={}", name, self.level),
+            None => write!(f, "{}", self.level),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Builder, Level, LevelFilter, Metadata};
+    use super::{Builder, Filter, InvalidDirective, Level, LevelFilter, Metadata};
 
     fn make_metadata(level: Level, target: &'static str) -> Metadata {
         Metadata::new(level, target, target, "")
@@ -351,4 +429,33 @@ mod tests {
         assert_eq!(dirs[1].name.as_deref(), Some("crate2"));
         assert_eq!(dirs[1].level, LevelFilter::Debug);
     }
+
+    #[test]
+    fn try_parse_rejects_invalid_directives() {
+        for (filters, invalid) in [
+            (
+                "crate1::mod1=noNumber,crate2=debug",
+                "crate1::mod1=noNumber",
+            ),
+            ("info,crate1::mod1=warn=info", "crate1::mod1=warn=info"),
+            ("=debug", "=debug"),
+        ] {
+            let mut builder = Builder::new();
+            assert_eq!(
+                builder.try_parse(filters).err(),
+                Some(InvalidDirective(invalid.to_string()))
+            );
+        }
+        assert!("".parse::<Filter>().is_err());
+        assert!(" , ".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn filter_display_round_trips() {
+        let filter: Filter = "debug,crate1::mod1=off,crate2,".parse().unwrap();
+        assert_eq!(filter.to_string(), "debug,crate2=trace,crate1::mod1=off");
+        assert_eq!(filter.to_string().parse::<Filter>().unwrap(), filter);
+        assert!(filter.enabled(&make_metadata(Level::Debug, "crate3")));
+        assert!(!filter.enabled(&make_metadata(Level::Error, "crate1::mod1")));
+    }
 }
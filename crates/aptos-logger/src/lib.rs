@@ -158,8 +158,8 @@ mod struct_log;
 
 pub use crate::aptos_logger::{AptosData as Logger, AptosDataBuilder, Writer, CHANNEL_SIZE};
 pub use event::Event;
-pub use filter::{Filter, LevelFilter};
-pub use logger::flush;
+pub use filter::{Filter, InvalidDirective, LevelFilter};
+pub use logger::{flush, local_filter, replace_local_filter};
 pub use metadata::{Level, Metadata};
 
 pub use aptos_log_derive::Schema;
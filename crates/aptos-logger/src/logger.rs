@@ -3,7 +3,7 @@
 
 //! Global logger definition and functions
 
-use crate::{counters::STRUCT_LOG_COUNT, Event, Metadata};
+use crate::{counters::STRUCT_LOG_COUNT, Event, Filter, Metadata};
 
 use once_cell::sync::OnceCell;
 use std::sync::Arc;
@@ -22,6 +22,19 @@ pub trait Logger: Sync + Send + 'static {
 
     /// Flush any buffered events
     fn flush(&self);
+
+    /// Returns the filter controlling what is logged locally, if this logger
+    /// supports changing it at runtime
+    fn local_filter(&self) -> Option<Filter> {
+        None
+    }
+
+    /// Replaces the filter controlling what is logged locally, returning the
+    /// previous one. Returns None (and leaves the logger untouched) if this
+    /// logger doesn't support changing its filter at runtime.
+    fn replace_local_filter(&self, _filter: Filter) -> Option<Filter> {
+        None
+    }
 }
 
 /// Record a logging event to the global `Logger`
@@ -57,3 +70,18 @@ pub fn flush() {
         logger.flush();
     }
 }
+
+/// Returns the local filter of the global `Logger`, if one is set and it
+/// supports runtime filter changes
+pub fn local_filter() -> Option<Filter> {
+    LOGGER.get().and_then(|logger| logger.local_filter())
+}
+
+/// Replaces the local filter of the global `Logger`, returning the previous
+/// one. Returns None if no logger is set or it doesn't support runtime filter
+/// changes, in which case the filter is not applied.
+pub fn replace_local_filter(filter: Filter) -> Option<Filter> {
+    LOGGER
+        .get()
+        .and_then(|logger| logger.replace_local_filter(filter))
+}
@@ -0,0 +1,105 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use aptos_types::{
+    state_store::state_key::StateKey,
+    write_set::{WriteOp, WriteSet, WriteSetMut},
+};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use executor_types::in_memory_state_calculator::InMemoryStateCalculator;
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use storage_interface::{cached_state_view::StateCache, state_delta::StateDelta};
+
+/// Counts allocations, so the benchmark can report how many a run of the calculator makes.
+struct CountingAllocator;
+
+static NUM_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        NUM_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+pub const NUM_KEYS: usize = 100_000;
+pub const KEYS_PER_WRITE_SET: usize = 100;
+
+/// Write sets writing each key twice, once before and once after a checkpoint halfway through,
+/// along with the index of that checkpoint.
+fn write_sets(keys: &[StateKey]) -> (Vec<WriteSet>, usize) {
+    let write_set = |keys: &[StateKey], value: u8| {
+        WriteSetMut::new(
+            keys.iter()
+                .map(|key| (key.clone(), WriteOp::Value(vec![value; 32])))
+                .collect(),
+        )
+        .freeze()
+        .unwrap()
+    };
+    let mut write_sets: Vec<_> = keys
+        .chunks(KEYS_PER_WRITE_SET)
+        .map(|keys| write_set(keys, 1))
+        .collect();
+    let last_checkpoint_index = write_sets.len() - 1;
+    write_sets.extend(
+        keys.chunks(KEYS_PER_WRITE_SET)
+            .map(|keys| write_set(keys, 2)),
+    );
+    (write_sets, last_checkpoint_index)
+}
+
+fn state_calculator_benchmark(c: &mut Criterion) {
+    let keys: Vec<_> = (0..NUM_KEYS)
+        .map(|i| StateKey::Raw(format!("key_{}", i).into_bytes()))
+        .collect();
+    let (write_sets, last_checkpoint_index) = write_sets(&keys);
+    let base = StateDelta::new_empty();
+    let calculator = || {
+        // All the keys are known not to exist yet, so no proofs are needed.
+        let state_cache = StateCache {
+            frozen_base: base.current.clone().freeze(),
+            state_cache: keys.iter().map(|key| (key.clone(), None)).collect(),
+            proofs: Default::default(),
+            stats: Default::default(),
+        };
+        InMemoryStateCalculator::new(&base, state_cache)
+    };
+    let calculate = |calculator: InMemoryStateCalculator| {
+        calculator
+            .calculate_for_write_sets_after_snapshot(Some(last_checkpoint_index), &write_sets)
+            .unwrap()
+    };
+
+    let calculator_to_measure = calculator();
+    let num_allocations_before = NUM_ALLOCATIONS.load(Ordering::Relaxed);
+    let result = calculate(calculator_to_measure);
+    println!(
+        "bench_state_calculator_100k_keys: {} allocations for {} writes",
+        NUM_ALLOCATIONS.load(Ordering::Relaxed) - num_allocations_before,
+        2 * NUM_KEYS,
+    );
+    drop(result);
+
+    c.bench_function("bench_state_calculator_100k_keys", |bencher| {
+        bencher.iter_batched(&calculator, &calculate, BatchSize::LargeInput)
+    });
+}
+
+criterion_group!(
+    name = state_calculator_benches;
+    config = Criterion::default().sample_size(10);
+    targets = state_calculator_benchmark
+);
+
+criterion_main!(state_calculator_benches);
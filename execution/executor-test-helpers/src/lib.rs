@@ -3,6 +3,10 @@
 
 pub mod integration_test_impl;
 
+/// The failpoints crash recovery tests can activate in the executor (with its `failpoints`
+/// feature), at the boundaries of state calculation and chunk commit.
+pub use executor_types::failpoints;
+
 use aptos_config::config::NodeConfig;
 use aptos_crypto::{
     ed25519::{Ed25519PrivateKey, Ed25519PublicKey},
@@ -5,7 +5,7 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use aptos_crypto::HashValue;
-use aptos_types::transaction::Version;
+use aptos_types::{state_store::state_key::StateKey, transaction::Version};
 
 #[derive(Debug, Deserialize, Error, PartialEq, Serialize)]
 /// Different reasons for proposal rejection
@@ -33,6 +33,81 @@ pub enum Error {
 
     #[error("Received Empty Blocks")]
     EmptyBlocks,
+
+    #[error(
+        "Missing proof for key {:?} (hash {}) on top of state root {}, with {} proofs loaded",
+        state_key,
+        key_hash,
+        base_root_hash,
+        num_proofs
+    )]
+    MissingProof {
+        key_hash: HashValue,
+        /// The key being updated, if the calculator could tell which it was.
+        state_key: Option<StateKey>,
+        /// The root of the state the proofs were fetched from.
+        base_root_hash: HashValue,
+        num_proofs: usize,
+    },
+
+    #[error(
+        "new_epoch is {} but the chunk's last reconfig transaction is at version {:?}",
+        new_epoch,
+        reconfig_version
+    )]
+    NewEpochMismatch {
+        new_epoch: bool,
+        /// The version of the last reconfig transaction in the chunk, if there was one.
+        reconfig_version: Option<Version>,
+    },
+
+    #[error(
+        "Transactions start at version {} but the base state is followed by version {}",
+        next_version,
+        expected_next_version
+    )]
+    VersionMismatch {
+        /// The version right after the base state's.
+        expected_next_version: Version,
+        next_version: Version,
+    },
+
+    #[error(
+        "State checkpoint hash mismatch at version {}: expected {:?}, computed {:?}",
+        version,
+        expected,
+        computed
+    )]
+    StateCheckpointHashMismatch {
+        version: Version,
+        /// The hash in the transaction's `TransactionInfo`, if it's a state checkpoint.
+        expected: Option<HashValue>,
+        /// The hash the calculator produced, if the transaction is a state checkpoint.
+        computed: Option<HashValue>,
+    },
+
+    #[error(
+        "Cannot rebase the state calculation onto the state at version {:?}: {}",
+        base_version,
+        reason
+    )]
+    IncompatibleBase {
+        base_version: Option<Version>,
+        reason: String,
+    },
+
+    #[error(
+        "State keys {:?} and {:?} have the same hash {}",
+        existing_key,
+        new_key,
+        key_hash
+    )]
+    StateKeyHashCollision {
+        key_hash: HashValue,
+        /// The key hashed first.
+        existing_key: StateKey,
+        new_key: StateKey,
+    },
 }
 
 impl From<anyhow::Error> for Error {
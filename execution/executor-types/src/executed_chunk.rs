@@ -3,7 +3,10 @@
 
 #![forbid(unsafe_code)]
 
-use crate::{StateComputeResult, TransactionData};
+use crate::{
+    in_memory_state_calculator::StateUpdateStats, ChunkExecutionSummary, StateComputeResult,
+    TransactionData,
+};
 use anyhow::{bail, ensure, Result};
 use aptos_crypto::hash::{CryptoHash, TransactionAccumulatorHasher};
 use aptos_types::{
@@ -24,6 +27,11 @@ pub struct ExecutedChunk {
     /// If set, this is the new epoch info that should be changed to if this is committed.
     pub next_epoch_state: Option<EpochState>,
     pub ledger_info: Option<LedgerInfoWithSignatures>,
+    /// Totals over the transaction outputs the chunk was made from, including those discarded or
+    /// to be retried.
+    pub execution_summary: ChunkExecutionSummary,
+    /// What each transaction in `to_commit` did to the state.
+    pub state_update_stats: Vec<StateUpdateStats>,
 }
 
 impl ExecutedChunk {
@@ -133,59 +141,13 @@ impl ExecutedChunk {
         Ok(())
     }
 
-    pub fn maybe_select_chunk_ending_ledger_info(
-        &self,
-        verified_target_li: &LedgerInfoWithSignatures,
-        epoch_change_li: Option<&LedgerInfoWithSignatures>,
-    ) -> Result<Option<LedgerInfoWithSignatures>> {
-        let result_accumulator = self.result_view.txn_accumulator();
-
-        if verified_target_li.ledger_info().version() + 1 == result_accumulator.num_leaves() {
-            // If the chunk corresponds to the target LI, the target LI can be added to storage.
-            ensure!(
-                verified_target_li
-                    .ledger_info()
-                    .transaction_accumulator_hash()
-                    == result_accumulator.root_hash(),
-                "Root hash in target ledger info does not match local computation."
-            );
-            Ok(Some(verified_target_li.clone()))
-        } else if let Some(epoch_change_li) = epoch_change_li {
-            // If the epoch change LI is present, it must match the version of the chunk:
-
-            // Verify that the given ledger info corresponds to the new accumulator.
-            ensure!(
-                epoch_change_li.ledger_info().transaction_accumulator_hash()
-                    == result_accumulator.root_hash(),
-                "Root hash of a given epoch LI does not match local computation."
-            );
-            ensure!(
-                epoch_change_li.ledger_info().version() + 1 == result_accumulator.num_leaves(),
-                "Version of a given epoch LI does not match local computation."
-            );
-            ensure!(
-                epoch_change_li.ledger_info().ends_epoch(),
-                "Epoch change LI does not carry validator set"
-            );
-            ensure!(
-                epoch_change_li.ledger_info().next_epoch_state() == self.next_epoch_state.as_ref(),
-                "New validator set of a given epoch LI does not match local computation"
-            );
-            Ok(Some(epoch_change_li.clone()))
-        } else {
-            ensure!(
-                self.next_epoch_state.is_none(),
-                "End of epoch chunk based on local computation but no EoE LedgerInfo provided."
-            );
-            Ok(None)
-        }
-    }
-
     pub fn combine(self, rhs: Self) -> Result<Self> {
         let mut to_commit = self.to_commit;
         to_commit.extend(rhs.to_commit.into_iter());
         let mut status = self.status;
         status.extend(rhs.status.into_iter());
+        let mut state_update_stats = self.state_update_stats;
+        state_update_stats.extend(rhs.state_update_stats);
 
         Ok(Self {
             status,
@@ -193,6 +155,8 @@ impl ExecutedChunk {
             result_view: rhs.result_view,
             next_epoch_state: rhs.next_epoch_state,
             ledger_info: rhs.ledger_info,
+            execution_summary: self.execution_summary.combine(rhs.execution_summary),
+            state_update_stats,
         })
     }
 
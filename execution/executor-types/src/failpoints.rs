@@ -0,0 +1,22 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+//! The names of the failpoints at the boundaries of state calculation and chunk commit, for
+//! crash recovery tests to stop execution at. They are only compiled in with the `failpoints`
+//! feature, and with `return` as their action, make the step they're in fail with an error.
+
+/// In the state calculator, after the SMT of a checkpoint has been computed, before the
+/// calculator moves on to it.
+pub const STATE_CALCULATOR_CHECKPOINT: &str = "executor::state_calculator::checkpoint";
+
+/// In the state calculator, after the final SMT of a chunk has been computed, before the result
+/// state is returned.
+pub const STATE_CALCULATOR_FINISH: &str = "executor::state_calculator::finish";
+
+/// When applying the output of a chunk, after its state has been calculated, before the executed
+/// chunk is built and queued to be committed.
+pub const APPLY_CHUNK_OUTPUT: &str = "executor::apply_chunk_output";
+
+/// When committing a chunk, after it has been saved to the DB, before it leaves the commit queue
+/// and the commit notification is returned.
+pub const COMMIT_CHUNK_AFTER_SAVE: &str = "executor::commit_chunk::after_save";
@@ -1,29 +1,343 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use std::collections::{hash_map, HashMap};
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet},
+    env,
+    hash::Hasher,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use anyhow::{anyhow, bail, Result};
+use anyhow::{anyhow, bail, ensure, Result};
 use once_cell::sync::Lazy;
 
-use crate::{ParsedTransactionOutput, ProofReader};
+use crate::{
+    metrics::{
+        APTOS_EXECUTOR_CONFLICTING_KEYS, APTOS_EXECUTOR_CONFLICTING_TXNS_PERCENTAGE,
+        APTOS_EXECUTOR_MAX_WRITERS_PER_KEY, APTOS_EXECUTOR_MISSING_PROOFS,
+        APTOS_EXECUTOR_SMT_NEW_NODES, APTOS_EXECUTOR_SMT_RETAINED_GENERATIONS,
+        APTOS_EXECUTOR_SMT_RETAINED_NODES, APTOS_EXECUTOR_SMT_UPDATE_KEYS,
+        APTOS_EXECUTOR_SMT_UPDATE_SECONDS, APTOS_EXECUTOR_STATE_CACHE_BYTES,
+        APTOS_EXECUTOR_STATE_CACHE_PROOFS, APTOS_EXECUTOR_STATE_CACHE_READS,
+        APTOS_EXECUTOR_STATE_CACHE_STORAGE_READ_SECONDS, APTOS_EXECUTOR_STATE_KEY_HASH_COLLISIONS,
+    },
+    Error, ParsedTransactionOutput, ProofReader,
+};
 use aptos_crypto::{hash::CryptoHash, HashValue};
-use aptos_state_view::account_with_state_cache::AsAccountWithStateCache;
+use aptos_logger::{debug, warn};
 use aptos_types::{
+    access_path::Path,
+    account_address::AccountAddress,
     account_config::CORE_CODE_ADDRESS,
     account_view::AccountView,
     epoch_state::EpochState,
     event::EventKey,
-    on_chain_config,
-    state_store::{state_key::StateKey, state_value::StateValue},
-    transaction::{Transaction, TransactionPayload, Version},
+    on_chain_config::{
+        self, access_path_for_config, ConfigurationResource, OnChainConfig, ValidatorSet,
+    },
+    state_store::{
+        state_key::StateKey, state_storage_usage::StateStorageUsage, state_value::StateValue,
+    },
+    transaction::{Transaction, TransactionInfo, TransactionPayload, Version},
     write_set::{WriteOp, WriteSet},
 };
-use scratchpad::{FrozenSparseMerkleTree, SparseMerkleTree};
-use storage_interface::{cached_state_view::StateCache, state_delta::StateDelta};
+use fail::fail_point;
+use rayon::prelude::*;
+use scratchpad::{
+    FrozenSparseMerkleTree, NewNodeCounts, SparseMerkleTree, StateStoreStatus, UpdateError,
+};
+use storage_interface::{
+    cached_state_view::{StateCache, StateCacheStats},
+    state_delta::StateDelta,
+    state_value_or_deleted,
+};
 
 pub static NEW_EPOCH_EVENT_KEY: Lazy<EventKey> = Lazy::new(on_chain_config::new_epoch_event_key);
 
+// The resources the next epoch state is parsed from.
+static VALIDATOR_SET_KEY: Lazy<StateKey> =
+    Lazy::new(|| StateKey::AccessPath(access_path_for_config(ValidatorSet::CONFIG_ID)));
+static CONFIGURATION_KEY: Lazy<StateKey> =
+    Lazy::new(|| StateKey::AccessPath(ConfigurationResource::access_path()));
+
+/// By default, the fewest keys hashed by one rayon task when preparing SMT updates. Hashing a key
+/// is cheap, so smaller tasks cost more in scheduling than they save.
+pub const DEFAULT_PARALLEL_MIN_LEN: usize = 1024;
+
+/// By default, the fewest transactions in a chunk for their write sets to be applied to the state
+/// cache in parallel. Splitting the cache into shards and back costs more than it saves for small
+/// chunks.
+pub const DEFAULT_PARALLEL_WRITE_SETS_MIN_TXNS: usize = 1000;
+
+// The shards the state cache is split into to apply write sets in parallel.
+const NUM_WRITE_SHARDS: usize = 16;
+
+/// By default, how long an SMT update may take before the calculator logs a summary of its updates.
+pub const DEFAULT_SLOW_SMT_UPDATE_THRESHOLD: Duration = Duration::from_millis(500);
+
+/// If this environment variable is set when the process starts, the calculator hashes the keys
+/// and updates the SMT on a single thread, in key hash order, e.g. to replay a root hash mismatch.
+pub const DETERMINISTIC_ENV_VAR: &str = "APTOS_STATE_CALCULATOR_DETERMINISTIC";
+
+/// If this is set too, every write is logged with its key, value hash and version.
+pub const TRACE_WRITES_ENV_VAR: &str = "APTOS_STATE_CALCULATOR_TRACE_WRITES";
+
+/// If this environment variable is set when the process starts, the calculator returns a
+/// `ConflictReport` for each chunk or block by default.
+pub const CONFLICT_ANALYSIS_ENV_VAR: &str = "APTOS_STATE_CALCULATOR_CONFLICT_ANALYSIS";
+
+static DETERMINISTIC: Lazy<bool> = Lazy::new(|| env::var_os(DETERMINISTIC_ENV_VAR).is_some());
+static TRACE_WRITES: Lazy<bool> = Lazy::new(|| env::var_os(TRACE_WRITES_ENV_VAR).is_some());
+static CONFLICT_ANALYSIS: Lazy<bool> =
+    Lazy::new(|| env::var_os(CONFLICT_ANALYSIS_ENV_VAR).is_some());
+
+static DETERMINISTIC_POOL: Lazy<rayon::ThreadPool> = Lazy::new(|| {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .thread_name(|_| "state_calculator_deterministic".to_string())
+        .build()
+        .unwrap()
+});
+
+/// What a transaction's write set did to the state. The byte counts are of keys and values, as
+/// storage usage counts them, and leave out the writes of keys whose old value wasn't known, so
+/// they add up to the change in usage.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct StateUpdateStats {
+    pub keys_written: usize,
+    /// Keys which had no value before the transaction.
+    pub keys_created: usize,
+    /// Keys which had a value before the transaction, or whose value wasn't known.
+    pub keys_modified: usize,
+    pub keys_deleted: usize,
+    /// The bytes of the values written.
+    pub bytes_written: usize,
+    /// The bytes of the items created.
+    pub new_key_bytes: usize,
+    /// The change in the bytes of the items overwritten.
+    pub overwritten_bytes_delta: i64,
+    /// The bytes of the items deleted.
+    pub freed_bytes: usize,
+}
+
+/// Tells which category a key belongs to for the calculator's `CategoryStateChanges`, e.g. the
+/// Move module whose resource or code it holds, or `None` if the key isn't broken down.
+pub type KeyClassifier = Arc<dyn Fn(&StateKey) -> Option<String> + Send + Sync>;
+
+/// How the keys of one category changed between two checkpoints. Writes of keys whose old value
+/// wasn't known are counted, but left out of the deltas.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CategoryStateChange {
+    pub keys_written: usize,
+    /// The change in the number of items in the category.
+    pub items_delta: i64,
+    /// The change in the bytes of the keys and values in the category, as storage usage counts
+    /// them.
+    pub bytes_delta: i64,
+}
+
+pub type CategoryStateChanges = BTreeMap<String, CategoryStateChange>;
+
+/// The default `KeyClassifier`: resources and code are categorized by the module (as
+/// `0x<address>::<module>`) defining them, and table items by their table handle.
+pub fn classify_by_module(state_key: &StateKey) -> Option<String> {
+    match state_key {
+        StateKey::AccessPath(access_path) => match bcs::from_bytes(&access_path.path).ok()? {
+            Path::Code(module_id) => Some(format!(
+                "0x{}::{}",
+                module_id.address().short_str_lossless(),
+                module_id.name()
+            )),
+            Path::Resource(struct_tag) => Some(format!(
+                "0x{}::{}",
+                struct_tag.address.short_str_lossless(),
+                struct_tag.module
+            )),
+        },
+        StateKey::TableItem { handle, .. } => Some(format!("table::{:#x}", handle.0)),
+        StateKey::Raw(_) => None,
+    }
+}
+
+/// What the calculator returns for a chunk or block: the per transaction vectors have a slot for
+/// each transaction kept, in order.
+#[derive(Debug)]
+pub struct ChunkStateUpdateResult {
+    pub per_txn_state_updates: Vec<HashMap<StateKey, Option<Arc<StateValue>>>>,
+    pub per_txn_state_update_stats: Vec<StateUpdateStats>,
+    /// The root hash of the state after each transaction that's a state checkpoint.
+    pub per_txn_state_checkpoint_hashes: Vec<Option<HashValue>>,
+    /// The storage usage after each transaction that's a state checkpoint.
+    pub per_txn_state_checkpoint_usages: Vec<Option<StateStorageUsage>>,
+    /// The version and root hash of each state checkpoint, in order: the same hashes as in
+    /// `per_txn_state_checkpoint_hashes`, without having to work out their versions.
+    pub checkpoint_roots: Vec<(Version, HashValue)>,
+    pub result_state: StateDelta,
+    /// The epoch state after the chunk, if it ended an epoch.
+    pub next_epoch_state: Option<EpochState>,
+    /// Which transactions wrote the same keys, if the calculator was asked to analyze conflicts.
+    pub conflict_report: Option<ConflictReport>,
+    /// How many SMT nodes were created for each checkpoint, in the order of `checkpoint_roots`,
+    /// followed by how many were created after the last one (or in the whole chunk, if there's
+    /// no checkpoint).
+    pub new_node_counts: Vec<NewNodeCounts>,
+    /// How the keys of each category changed up to each checkpoint, in the order of
+    /// `checkpoint_roots`, followed by how they changed after the last one, if the calculator
+    /// was given a `KeyClassifier`.
+    pub category_changes: Option<Vec<CategoryStateChanges>>,
+}
+
+impl ChunkStateUpdateResult {
+    /// Fails unless each of the per transaction vectors has `num_txns` slots, and there's a
+    /// checkpoint root for each checkpoint hash.
+    pub fn new(
+        num_txns: usize,
+        per_txn_state_updates: Vec<HashMap<StateKey, Option<Arc<StateValue>>>>,
+        per_txn_state_update_stats: Vec<StateUpdateStats>,
+        per_txn_state_checkpoint_hashes: Vec<Option<HashValue>>,
+        per_txn_state_checkpoint_usages: Vec<Option<StateStorageUsage>>,
+        checkpoint_roots: Vec<(Version, HashValue)>,
+        result_state: StateDelta,
+        next_epoch_state: Option<EpochState>,
+    ) -> Result<Self> {
+        for (name, len) in [
+            ("per_txn_state_updates", per_txn_state_updates.len()),
+            (
+                "per_txn_state_update_stats",
+                per_txn_state_update_stats.len(),
+            ),
+            (
+                "per_txn_state_checkpoint_hashes",
+                per_txn_state_checkpoint_hashes.len(),
+            ),
+            (
+                "per_txn_state_checkpoint_usages",
+                per_txn_state_checkpoint_usages.len(),
+            ),
+        ] {
+            ensure!(
+                len == num_txns,
+                "{} has {} slots for {} transactions.",
+                name,
+                len,
+                num_txns,
+            );
+        }
+        let num_checkpoints = per_txn_state_checkpoint_hashes
+            .iter()
+            .filter(|hash| hash.is_some())
+            .count();
+        ensure!(
+            checkpoint_roots.len() == num_checkpoints,
+            "{} checkpoint roots for {} checkpoints.",
+            checkpoint_roots.len(),
+            num_checkpoints,
+        );
+
+        Ok(Self {
+            per_txn_state_updates,
+            per_txn_state_update_stats,
+            per_txn_state_checkpoint_hashes,
+            per_txn_state_checkpoint_usages,
+            checkpoint_roots,
+            result_state,
+            next_epoch_state,
+            conflict_report: None,
+            new_node_counts: Vec::new(),
+            category_changes: None,
+        })
+    }
+
+    fn with_new_node_counts(mut self, new_node_counts: Vec<NewNodeCounts>) -> Self {
+        self.new_node_counts = new_node_counts;
+        self
+    }
+
+    fn with_category_changes(
+        mut self,
+        category_changes: Option<Vec<CategoryStateChanges>>,
+    ) -> Self {
+        self.category_changes = category_changes;
+        self
+    }
+
+    // Adds the conflict report of the transactions, and exports its summary as metrics.
+    fn with_conflict_report(mut self) -> Self {
+        let report = ConflictReport::new(&self.per_txn_state_updates);
+        APTOS_EXECUTOR_CONFLICTING_KEYS.observe(report.num_conflicting_keys() as f64);
+        APTOS_EXECUTOR_MAX_WRITERS_PER_KEY.observe(report.max_writers_per_key() as f64);
+        APTOS_EXECUTOR_CONFLICTING_TXNS_PERCENTAGE.observe(report.conflicting_txns_percentage());
+        self.conflict_report = Some(report);
+        self
+    }
+
+    /// The root hash of the state after the last transaction, whether or not it's a checkpoint.
+    /// The SMT was already updated by the calculator, so this is free. It isn't a ledger state
+    /// checkpoint hash, unless the last transaction is a checkpoint: it's only for consumers that
+    /// want the speculative root.
+    pub fn latest_root_hash(&self) -> HashValue {
+        self.result_state.root_hash()
+    }
+}
+
+/// For each key written by more than one transaction of a chunk or block, which transactions wrote
+/// it, for parallel execution planning and fee markets. Transactions are identified by their
+/// index among the ones kept.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ConflictReport {
+    pub num_txns: usize,
+    /// The indices of the transactions writing each key written more than once, in order.
+    pub writers: HashMap<StateKey, Vec<usize>>,
+}
+
+impl ConflictReport {
+    /// Made from the keys each transaction updated, as the calculator attributes them.
+    pub fn new(per_txn_state_updates: &[HashMap<StateKey, Option<Arc<StateValue>>>]) -> Self {
+        let mut writers: HashMap<&StateKey, Vec<usize>> = HashMap::new();
+        for (idx, state_updates) in per_txn_state_updates.iter().enumerate() {
+            for state_key in state_updates.keys() {
+                writers.entry(state_key).or_default().push(idx);
+            }
+        }
+        Self {
+            num_txns: per_txn_state_updates.len(),
+            writers: writers
+                .into_iter()
+                .filter(|(_, txn_indices)| txn_indices.len() > 1)
+                .map(|(state_key, txn_indices)| (state_key.clone(), txn_indices))
+                .collect(),
+        }
+    }
+
+    pub fn num_conflicting_keys(&self) -> usize {
+        self.writers.len()
+    }
+
+    /// 0 if there are no conflicts.
+    pub fn max_writers_per_key(&self) -> usize {
+        self.writers.values().map(Vec::len).max().unwrap_or(0)
+    }
+
+    /// The transactions writing any key another transaction writes too.
+    pub fn num_conflicting_txns(&self) -> usize {
+        self.writers
+            .values()
+            .flatten()
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    pub fn conflicting_txns_percentage(&self) -> f64 {
+        if self.num_txns == 0 {
+            0.0
+        } else {
+            100.0 * self.num_conflicting_txns() as f64 / self.num_txns as f64
+        }
+    }
+}
+
 /// Helper class for calculating `InMemState` after a chunk or block of transactions are executed.
 ///
 /// A new SMT is spawned in two situations:
@@ -37,12 +351,24 @@ pub static NEW_EPOCH_EVENT_KEY: Lazy<EventKey> = Lazy::new(on_chain_config::new_
 ///                                                                          /
 ///                                (creates checkpoint SMT on checkpoint txn)
 ///                                        (creates "latest SMT" on finish())
+///
+/// The hashes of the new SMT nodes aren't collected here. That's left to the state snapshot
+/// committer, which only runs on the checkpoints being persisted, so speculative execution doesn't
+/// pay for it.
 pub struct InMemoryStateCalculator {
     // This makes sure all in-mem nodes seen while proofs were fetched stays in mem during the
-    // calculation
-    _frozen_base: FrozenSparseMerkleTree<StateValue>,
-    state_cache: HashMap<StateKey, StateValue>,
+    // calculation. Also used to look up the old values of keys written without being read.
+    frozen_base: FrozenSparseMerkleTree<StateValue>,
+    state_cache: HashMap<StateKey, Option<Arc<StateValue>>>,
     proof_reader: ProofReader,
+    // How the state cache was filled during execution.
+    state_cache_stats: StateCacheStats,
+    // The base state the calculation is on top of (its current SMT, the SMT and version of its
+    // checkpoint, and its current usage), kept to check and diff a new base against on `rebase()`.
+    base_current: SparseMerkleTree<StateValue>,
+    base_checkpoint: SparseMerkleTree<StateValue>,
+    base_checkpoint_version: Option<Version>,
+    base_current_usage: StateStorageUsage,
 
     checkpoint: SparseMerkleTree<StateValue>,
     checkpoint_version: Option<Version>,
@@ -50,18 +376,99 @@ pub struct InMemoryStateCalculator {
     // already, but frozen SMT is used here anyway to avoid exposing the `batch_update()` interface
     // on the non-frozen SMT.
     latest: FrozenSparseMerkleTree<StateValue>,
+    // The latest SMT as of the last checkpoint made (or the start), and the numbers of nodes
+    // created for each checkpoint since the start.
+    last_checkpoint_latest: FrozenSparseMerkleTree<StateValue>,
+    new_node_counts: Vec<NewNodeCounts>,
 
+    // The version of the first transaction added.
+    first_version: Version,
     next_version: Version,
-    updates_between_checkpoint_and_latest: HashMap<StateKey, StateValue>,
-    updates_after_latest: HashMap<StateKey, StateValue>,
+    // Shared with the base state until the first checkpoint, so calculators for sibling blocks
+    // don't each copy the updates since the base's checkpoint.
+    updates_between_checkpoint_and_latest: Arc<HashMap<StateKey, Option<Arc<StateValue>>>>,
+    updates_after_latest: HashMap<StateKey, Option<Arc<StateValue>>>,
+
+    checkpoint_usage: StateStorageUsage,
+    // The usage after the last transaction added.
+    usage: StateStorageUsage,
+
+    parallel_min_len: usize,
+    sharded_smt_updates: bool,
+    parallel_write_sets_min_txns: usize,
+    // Once `updates_after_latest` has this many keys, or about this many bytes of values, they're
+    // applied to the latest SMT without waiting for a checkpoint or `finish()`.
+    max_pending_keys: usize,
+    max_pending_bytes: usize,
+    // The bytes of the values written since the latest SMT was updated, counting overwritten
+    // values too.
+    pending_bytes: usize,
+    // The SMT nodes the calculator created, which stay in memory as long as the base does, even
+    // once a later update supersedes them. Counted as they're created rather than by walking the
+    // trees. Once there are `max_retained_nodes` of them, pending updates are no longer applied
+    // early.
+    num_retained_nodes: usize,
+    max_retained_nodes: usize,
+
+    // The version of the last transaction in the chunk writing the ValidatorSet or the
+    // Configuration resource, and the epoch state last parsed from them, along with that version
+    // at the time. `None` for the version means they're as in the base state.
+    epoch_resources_version: Option<Version>,
+    parsed_epoch_state: Option<(Option<Version>, EpochState)>,
+    // The epoch state after each reconfiguration transaction, with its version.
+    epoch_states: Vec<(Version, EpochState)>,
+    // The version and root hash of each checkpoint made.
+    checkpoint_roots: Vec<(Version, HashValue)>,
+    // The hashes of the keys written, filled in before each SMT update, and the other way around
+    // to catch two keys with the same hash.
+    key_hashes: HashMap<StateKey, HashValue>,
+    keys_by_hash: HashMap<HashValue, StateKey>,
+    // Only ever something other than `CryptoHash::hash` in tests.
+    key_hasher: fn(&StateKey) -> HashValue,
+
+    smt_update_timings: Vec<SmtUpdateTiming>,
+    slow_smt_update_threshold: Duration,
+
+    deterministic: bool,
+    trace_writes: bool,
+    conflict_analysis: bool,
+    // In fast replay mode, the updates made since the last checkpoint in the chunk which have been
+    // applied to the latest SMT already.
+    fast_replay: bool,
+    replay_updates: HashMap<StateKey, Option<Arc<StateValue>>>,
+
+    // If set, the keys written are categorized, and the changes of each category are collected
+    // for each checkpoint.
+    key_classifier: Option<KeyClassifier>,
+    pending_category_changes: CategoryStateChanges,
+    category_changes: Vec<CategoryStateChanges>,
 }
 
 impl InMemoryStateCalculator {
+    /// Like `new`, but fails unless the transactions to be added start at `next_version`, i.e.
+    /// right after the base state.
+    pub fn new_at_version(
+        base: &StateDelta,
+        state_cache: StateCache,
+        next_version: Version,
+    ) -> Result<Self> {
+        let calculator = Self::new(base, state_cache);
+        if calculator.next_version != next_version {
+            return Err(Error::VersionMismatch {
+                expected_next_version: calculator.next_version,
+                next_version,
+            }
+            .into());
+        }
+        Ok(calculator)
+    }
+
     pub fn new(base: &StateDelta, state_cache: StateCache) -> Self {
         let StateCache {
             frozen_base,
             state_cache,
             proofs,
+            stats: state_cache_stats,
         } = state_cache;
         let StateDelta {
             base,
@@ -69,128 +476,966 @@ impl InMemoryStateCalculator {
             current,
             current_version,
             updates_since_base,
-        } = base.clone();
+            base_usage,
+            current_usage,
+        } = base.branch();
+
+        let next_version = current_version.map_or(0, |v| v + 1);
+        let base_current = current.clone();
+        let latest = current.freeze();
 
         Self {
-            _frozen_base: frozen_base,
+            frozen_base,
             state_cache,
             proof_reader: ProofReader::new(proofs),
+            state_cache_stats,
+            base_current,
+            base_checkpoint: base.clone(),
+            base_checkpoint_version: base_version,
+            base_current_usage: current_usage,
             checkpoint: base,
             checkpoint_version: base_version,
-            latest: current.freeze(),
-            next_version: current_version.map_or(0, |v| v + 1),
+            last_checkpoint_latest: latest.clone(),
+            latest,
+            new_node_counts: Vec::new(),
+            first_version: next_version,
+            next_version,
             updates_between_checkpoint_and_latest: updates_since_base,
             updates_after_latest: HashMap::new(),
+            checkpoint_usage: base_usage,
+            usage: current_usage,
+            parallel_min_len: DEFAULT_PARALLEL_MIN_LEN,
+            sharded_smt_updates: false,
+            parallel_write_sets_min_txns: DEFAULT_PARALLEL_WRITE_SETS_MIN_TXNS,
+            max_pending_keys: usize::MAX,
+            max_pending_bytes: usize::MAX,
+            pending_bytes: 0,
+            num_retained_nodes: 0,
+            max_retained_nodes: usize::MAX,
+            epoch_resources_version: None,
+            parsed_epoch_state: None,
+            epoch_states: Vec::new(),
+            checkpoint_roots: Vec::new(),
+            key_hashes: HashMap::new(),
+            keys_by_hash: HashMap::new(),
+            key_hasher: <StateKey as CryptoHash>::hash,
+            smt_update_timings: Vec::new(),
+            slow_smt_update_threshold: DEFAULT_SLOW_SMT_UPDATE_THRESHOLD,
+            deterministic: *DETERMINISTIC,
+            trace_writes: *DETERMINISTIC && *TRACE_WRITES,
+            conflict_analysis: *CONFLICT_ANALYSIS,
+            fast_replay: false,
+            replay_updates: HashMap::new(),
+            key_classifier: None,
+            pending_category_changes: BTreeMap::new(),
+            category_changes: Vec::new(),
         }
     }
 
+    /// Sets the fewest keys hashed by one rayon task when preparing SMT updates.
+    pub fn with_parallel_min_len(mut self, parallel_min_len: usize) -> Self {
+        self.parallel_min_len = parallel_min_len;
+        self
+    }
+
+    /// If set, the SMT updates are sorted by key hash in 16 shards, by the top nibble, in
+    /// parallel. The SMT sorts its updates anyway, but serially, which dominates updates of a lot
+    /// of keys; the tree itself is already updated in parallel below the root.
+    pub fn with_sharded_smt_updates(mut self, sharded_smt_updates: bool) -> Self {
+        self.sharded_smt_updates = sharded_smt_updates;
+        self
+    }
+
+    /// Hashes the keys written with `key_hasher` rather than their `CryptoHash`, to inject hash
+    /// collisions.
+    #[cfg(test)]
+    pub(crate) fn with_key_hasher(mut self, key_hasher: fn(&StateKey) -> HashValue) -> Self {
+        self.key_hasher = key_hasher;
+        self
+    }
+
+    /// Sets the fewest transactions in a chunk for their write sets to be applied to the state
+    /// cache in parallel, by shards of keys. Only chunks whose sole reconfig (if any) is the last
+    /// transaction are, and never in deterministic mode.
+    pub fn with_parallel_write_sets_min_txns(mut self, min_txns: usize) -> Self {
+        self.parallel_write_sets_min_txns = min_txns;
+        self
+    }
+
+    /// Bounds the updates waiting to be applied to the latest SMT, so a huge chunk isn't applied in
+    /// one giant batch. This doesn't add checkpoints: the resulting state and checkpoint hashes
+    /// are the same either way.
+    pub fn with_max_pending_updates(mut self, max_keys: usize, max_bytes: usize) -> Self {
+        self.max_pending_keys = max_keys;
+        self.max_pending_bytes = max_bytes;
+        self
+    }
+
+    /// Soft limit on the SMT nodes kept in memory on top of the base state. Each early update of
+    /// the latest SMT (see `with_max_pending_updates`) creates nodes which the next update
+    /// supersedes, but which are retained until the base state is dropped. Once the limit is
+    /// reached, the pending updates wait for the next checkpoint or `finish()` instead, which
+    /// creates fewer nodes and gives the same result.
+    pub fn with_max_retained_nodes(mut self, max_nodes: usize) -> Self {
+        self.max_retained_nodes = max_nodes;
+        self
+    }
+
+    /// If set, for replaying verified history, the calculator doesn't keep the updates of each
+    /// transaction of a chunk: the slot of each checkpoint transaction in the returned
+    /// `per_txn_state_updates` has all the updates since the previous checkpoint (or the start of
+    /// the chunk), the last slot has the ones after the last checkpoint, and the other slots are
+    /// empty. Only for `calculate_for_transaction_chunk` and `calculate_for_output_chunk`.
+    pub fn with_fast_replay(mut self, fast_replay: bool) -> Self {
+        self.fast_replay = fast_replay;
+        self
+    }
+
+    /// If set, each key written is put in the category `key_classifier` gives it, and the result
+    /// has the changes of each category up to each checkpoint. Without it, keys aren't looked at
+    /// at all.
+    pub fn with_key_classifier(mut self, key_classifier: KeyClassifier) -> Self {
+        self.key_classifier = Some(key_classifier);
+        self
+    }
+
+    /// If set, the result has a `ConflictReport`. It's off by default, unless
+    /// `CONFLICT_ANALYSIS_ENV_VAR` is set, since it holds on to the keys written more than once.
+    pub fn with_conflict_analysis(mut self, conflict_analysis: bool) -> Self {
+        self.conflict_analysis = conflict_analysis;
+        self
+    }
+
+    /// How the state cache the calculator was created with was filled during execution.
+    pub fn state_cache_stats(&self) -> StateCacheStats {
+        self.state_cache_stats
+    }
+
+    /// Sets how long an SMT update may take before the calculator logs a summary of its updates
+    /// when it's done.
+    pub fn with_slow_smt_update_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_smt_update_threshold = threshold;
+        self
+    }
+
+    /// Overrides `DETERMINISTIC_ENV_VAR` and `TRACE_WRITES_ENV_VAR`. Writes are only traced in
+    /// deterministic mode.
+    pub fn with_deterministic(mut self, deterministic: bool, trace_writes: bool) -> Self {
+        self.deterministic = deterministic;
+        self.trace_writes = deterministic && trace_writes;
+        self
+    }
+
+    /// For adding transactions one at a time, as they finish executing, rather than a whole chunk
+    /// or block at once.
+    pub fn into_incremental(self) -> IncrementalStateCalculator {
+        IncrementalStateCalculator {
+            calculator: self,
+            num_txns: 0,
+            state_updates_vec: Vec::new(),
+            state_update_stats: Vec::new(),
+            state_checkpoint_hashes: Vec::new(),
+            state_checkpoint_usages: Vec::new(),
+        }
+    }
+
+    /// Moves the calculation onto `new_base`, e.g. the committed state of the parent block it was
+    /// started on speculatively, keeping the transactions added so far rather than starting over.
+    ///
+    /// `new_base` must be at the same version as the old base, with a checkpoint no older than
+    /// the old base's. The keys whose values differ between the two bases are found by diffing
+    /// their SMTs, and the cached ones are looked up again in `new_base`. That fails if a
+    /// transaction added wrote any of them, if a checkpoint has been made on top of the old base
+    /// already, or if the bases differ in subtrees which aren't in memory.
+    pub fn rebase(mut self, new_base: &StateDelta) -> Result<Self> {
+        let incompatible = |reason: String| -> anyhow::Error {
+            Error::IncompatibleBase {
+                base_version: new_base.current_version,
+                reason,
+            }
+            .into()
+        };
+        if new_base.current_version.map_or(0, |v| v + 1) != self.first_version {
+            return Err(incompatible(format!(
+                "the transactions added start at version {}",
+                self.first_version
+            )));
+        }
+        match new_base
+            .base_version
+            .map_or(0, |v| v + 1)
+            .cmp(&self.base_checkpoint_version.map_or(0, |v| v + 1))
+        {
+            Ordering::Less => {
+                return Err(incompatible(format!(
+                    "its checkpoint is older than the old base's, at version {:?}",
+                    self.base_checkpoint_version
+                )))
+            }
+            Ordering::Equal if !new_base.base.has_same_root_hash(&self.base_checkpoint) => {
+                return Err(incompatible(
+                    "its checkpoint differs from the old base's".to_string(),
+                ))
+            }
+            _ => (),
+        }
+
+        let changed_key_hashes: HashSet<HashValue> =
+            if new_base.current.has_same_root_hash(&self.base_current) {
+                HashSet::new()
+            } else {
+                self.base_current
+                    .clone()
+                    .freeze()
+                    .diff_leaf_keys(&new_base.current.clone().freeze())
+                    .ok_or_else(|| {
+                        incompatible("the bases differ in subtrees not in memory".to_string())
+                    })?
+                    .into_iter()
+                    .collect()
+            };
+        let made_checkpoint = self
+            .checkpoint_version
+            .map_or(false, |version| version >= self.first_version);
+        if made_checkpoint
+            && (!changed_key_hashes.is_empty() || new_base.current_usage != self.base_current_usage)
+        {
+            return Err(incompatible(
+                "checkpoints were made on top of the old base already".to_string(),
+            ));
+        }
+
+        // Every key written by the transactions added is hashed once it's pending.
+        self.hash_pending_keys()?;
+        if !changed_key_hashes.is_empty() {
+            if let Some((key, _)) = self
+                .key_hashes
+                .iter()
+                .find(|(_, key_hash)| changed_key_hashes.contains(*key_hash))
+            {
+                return Err(incompatible(format!(
+                    "{:?} was written on top of the old base",
+                    key
+                )));
+            }
+            let new_current = new_base.current.clone().freeze();
+            for (key, cached) in self.state_cache.iter_mut() {
+                if changed_key_hashes.contains(&key.hash()) {
+                    *cached = match new_current.get(key.hash()) {
+                        StateStoreStatus::ExistsInScratchPad(value) => Some(Arc::new(value)),
+                        StateStoreStatus::DoesNotExist => None,
+                        StateStoreStatus::ExistsInDB | StateStoreStatus::Unknown => {
+                            return Err(incompatible(format!(
+                                "the new value of {:?} is not in memory",
+                                key
+                            )))
+                        }
+                    };
+                }
+            }
+            // It may have been parsed from the old base.
+            self.parsed_epoch_state = None;
+        }
+
+        self.frozen_base = new_base.current.clone().freeze();
+        if !made_checkpoint {
+            // The updates already applied to the latest SMT are applied again on top of the new
+            // base, while the pending ones are left to be applied as usual.
+            let applied: HashMap<_, _> = self
+                .updates_between_checkpoint_and_latest
+                .iter()
+                .filter(|(key, _)| self.key_hashes.contains_key(*key))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+            let new_current = new_base.current.clone().freeze();
+            let latest = if applied.is_empty() {
+                new_current.clone()
+            } else {
+                let smt_updates = applied.iter().map(|(key, value)| {
+                    (
+                        key_hash(&self.key_hashes, key),
+                        state_value_or_deleted(value),
+                    )
+                });
+                new_current
+                    .batch_update(smt_updates, &self.proof_reader)
+                    .map_err(|error| self.update_error(error))?
+            };
+            // Only the nodes of the new latest SMT are retained on top of the new base.
+            let counts = latest.new_node_counts_since(&new_current);
+            self.num_retained_nodes = counts.num_internal_nodes + counts.num_leaves;
+            self.latest = latest;
+            self.last_checkpoint_latest = new_current;
+            self.checkpoint = new_base.base.clone();
+            self.checkpoint_version = new_base.base_version;
+            self.checkpoint_usage = new_base.base_usage;
+            self.updates_between_checkpoint_and_latest = new_base.updates_since_base.clone();
+            if !applied.is_empty() {
+                Arc::make_mut(&mut self.updates_between_checkpoint_and_latest).extend(applied);
+            }
+        }
+        self.usage = rebased_usage(self.usage, self.base_current_usage, new_base.current_usage);
+
+        self.base_current = new_base.current.clone();
+        self.base_checkpoint = new_base.base.clone();
+        self.base_checkpoint_version = new_base.base_version;
+        self.base_current_usage = new_base.current_usage;
+        Ok(self)
+    }
+
+    /// Besides the state updates, checkpoint root hashes, resulting state and epoch state, returns
+    /// each transaction's state update stats, and the storage usage at each checkpoint in the same
+    /// slots as the checkpoint hashes.
+    ///
+    /// If `expected_state_checkpoint_hashes` are given (one per transaction, e.g. from the
+    /// `TransactionInfo`s of a verified chunk), each transaction's checkpoint hash is compared as
+    /// soon as it's computed, so a divergence fails the chunk right away rather than once it's
+    /// all been applied.
     pub fn calculate_for_transaction_chunk(
-        mut self,
+        self,
         to_keep: &[(Transaction, ParsedTransactionOutput)],
         new_epoch: bool,
-    ) -> Result<(
-        Vec<HashMap<StateKey, StateValue>>,
-        Vec<Option<HashValue>>,
-        StateDelta,
-        Option<EpochState>,
-    )> {
+        expected_state_checkpoint_hashes: Option<Vec<Option<HashValue>>>,
+    ) -> Result<ChunkStateUpdateResult> {
+        if let Some(expected) = &expected_state_checkpoint_hashes {
+            ensure!(
+                expected.len() == to_keep.len(),
+                "{} expected state checkpoint hashes for {} transactions.",
+                expected.len(),
+                to_keep.len(),
+            );
+        }
+        self.calculate_for_chunk(
+            to_keep.iter().map(|(txn, txn_output)| {
+                (Some(txn), txn_output, is_state_checkpoint(txn, txn_output))
+            }),
+            new_epoch,
+            expected_state_checkpoint_hashes,
+        )
+    }
+
+    /// Like `calculate_for_transaction_chunk`, but for outputs whose transactions aren't at hand
+    /// (e.g. when state sync applies outputs): which outputs are state checkpoints is taken from
+    /// their `TransactionInfo`s. Without the transactions, writes to keys that weren't read can't
+    /// be checked, so the outputs are expected to have been verified against the infos already.
+    pub fn calculate_for_output_chunk(
+        self,
+        to_keep: &[(TransactionInfo, ParsedTransactionOutput)],
+        new_epoch: bool,
+    ) -> Result<ChunkStateUpdateResult> {
+        self.calculate_for_chunk(
+            to_keep.iter().map(|(txn_info, txn_output)| {
+                let is_state_checkpoint =
+                    txn_output.is_reconfig() || txn_info.is_state_checkpoint();
+                (None, txn_output, is_state_checkpoint)
+            }),
+            new_epoch,
+            None,
+        )
+    }
+
+    fn calculate_for_chunk<'a>(
+        mut self,
+        to_keep: impl Iterator<Item = (Option<&'a Transaction>, &'a ParsedTransactionOutput, bool)>,
+        new_epoch: bool,
+        expected_state_checkpoint_hashes: Option<Vec<Option<HashValue>>>,
+    ) -> Result<ChunkStateUpdateResult> {
         let mut state_updates_vec = Vec::new();
+        let mut state_update_stats = Vec::new();
         let mut state_checkpoint_hashes = Vec::new();
+        let mut state_checkpoint_usages = Vec::new();
+        let mut num_txns = 0;
+        let mut expected_state_checkpoint_hashes =
+            expected_state_checkpoint_hashes.map(Vec::into_iter);
+
+        let to_keep: Vec<_> = to_keep.collect();
+        // The next epoch state is parsed from the state cache right after the reconfig, which
+        // would already have the writes of the transactions after it if they were applied ahead.
+        let mut cached_writes = if !self.deterministic
+            && to_keep.len() >= self.parallel_write_sets_min_txns
+            && !to_keep
+                .iter()
+                .rev()
+                .skip(1)
+                .any(|(_, txn_output, _)| txn_output.is_reconfig())
+        {
+            let write_sets: Vec<_> = to_keep
+                .iter()
+                .map(|(_, txn_output, _)| txn_output.write_set())
+                .collect();
+            Some(self.cache_write_sets(&write_sets).into_iter())
+        } else {
+            None
+        };
 
-        for (txn, txn_output) in to_keep {
-            let (state_updates, state_checkpoint_hash) = self.add_transaction(txn, txn_output)?;
+        for (txn, txn_output, is_state_checkpoint) in to_keep {
+            num_txns += 1;
+            let (state_updates, stats, state_checkpoint_hash) = self.add_transaction(
+                txn,
+                txn_output,
+                is_state_checkpoint,
+                cached_writes.as_mut().and_then(Iterator::next),
+            )?;
+            if let Some(expected) = expected_state_checkpoint_hashes
+                .as_mut()
+                .and_then(Iterator::next)
+            {
+                if expected != state_checkpoint_hash {
+                    return Err(Error::StateCheckpointHashMismatch {
+                        version: self.next_version - 1,
+                        expected,
+                        computed: state_checkpoint_hash,
+                    }
+                    .into());
+                }
+            }
             state_updates_vec.push(state_updates);
+            state_update_stats.push(stats);
+            state_checkpoint_usages.push(state_checkpoint_hash.map(|_| self.usage));
             state_checkpoint_hashes.push(state_checkpoint_hash);
         }
-        let (result_state, accounts) = self.finish()?;
+        // The validator set was parsed when the reconfig transaction was applied, so the flag
+        // only has to agree with it.
+        let reconfig = self.epoch_states.last().cloned();
+        if new_epoch != reconfig.is_some() {
+            return Err(Error::NewEpochMismatch {
+                new_epoch,
+                reconfig_version: reconfig.map(|(version, _)| version),
+            }
+            .into());
+        }
+        let next_epoch_state = reconfig.map(|(_, epoch_state)| epoch_state);
+        if self.fast_replay {
+            let trailing_updates = self.take_replay_updates();
+            if let Some(last_updates) = state_updates_vec.last_mut() {
+                last_updates.extend(trailing_updates);
+            }
+        }
+        self.ensure_num_txns(num_txns)?;
+        let checkpoint_roots = std::mem::take(&mut self.checkpoint_roots);
+        let category_changes = self.take_category_changes();
+        let conflict_analysis = self.conflict_analysis;
+        let (result_state, new_node_counts) = self.finish()?;
 
-        // Get the updated validator set from updated account state.
-        let next_epoch_state = if new_epoch {
-            Some(Self::parse_validator_set(&accounts)?)
-        } else {
-            None
-        };
+        ChunkStateUpdateResult::new(
+            num_txns,
+            state_updates_vec,
+            state_update_stats,
+            state_checkpoint_hashes,
+            state_checkpoint_usages,
+            checkpoint_roots,
+            result_state,
+            next_epoch_state,
+        )
+        .map(|result| result.with_new_node_counts(new_node_counts))
+        .map(|result| result.with_category_changes(category_changes))
+        .map(|result| {
+            if conflict_analysis {
+                result.with_conflict_report()
+            } else {
+                result
+            }
+        })
+    }
 
-        Ok((
+    /// Like `calculate_for_transaction_chunk`, but for the (kept) transactions of a block: if
+    /// `append_state_checkpoint` is set, the end of the block is a state checkpoint even if the
+    /// last transaction isn't one (e.g. the block was cut short and no StateCheckpoint transaction
+    /// was appended). The block end checkpoint hash is in the last slot of the returned
+    /// `per_txn_state_checkpoint_hashes`.
+    pub fn calculate_for_block(
+        mut self,
+        to_keep: &[(Transaction, ParsedTransactionOutput)],
+        append_state_checkpoint: bool,
+    ) -> Result<ChunkStateUpdateResult> {
+        ensure!(
+            !self.fast_replay,
+            "Blocks can't be calculated in fast replay mode."
+        );
+        let mut state_updates_vec = Vec::with_capacity(to_keep.len());
+        let mut state_update_stats = Vec::with_capacity(to_keep.len());
+        let mut state_checkpoint_hashes = Vec::with_capacity(to_keep.len());
+        let mut state_checkpoint_usages = Vec::with_capacity(to_keep.len());
+
+        for (idx, (txn, txn_output)) in to_keep.iter().enumerate() {
+            let is_block_end = idx + 1 == to_keep.len();
+            let (state_updates, stats, state_checkpoint_hash) = if append_state_checkpoint
+                && is_block_end
+            {
+                let (state_updates, stats) = self.apply_transaction(Some(txn), txn_output, None)?;
+                (state_updates, stats, Some(self.make_checkpoint()?))
+            } else {
+                self.add_transaction(
+                    Some(txn),
+                    txn_output,
+                    is_state_checkpoint(txn, txn_output),
+                    None,
+                )?
+            };
+            state_updates_vec.push(state_updates);
+            state_update_stats.push(stats);
+            state_checkpoint_usages.push(state_checkpoint_hash.map(|_| self.usage));
+            state_checkpoint_hashes.push(state_checkpoint_hash);
+        }
+        // The validator set was parsed when the reconfig transaction was applied.
+        let next_epoch_state = self
+            .epoch_states
+            .last()
+            .map(|(_, epoch_state)| epoch_state.clone());
+        self.ensure_num_txns(to_keep.len())?;
+        let checkpoint_roots = std::mem::take(&mut self.checkpoint_roots);
+        let category_changes = self.take_category_changes();
+        let conflict_analysis = self.conflict_analysis;
+        let (result_state, new_node_counts) = self.finish()?;
+
+        ChunkStateUpdateResult::new(
+            to_keep.len(),
             state_updates_vec,
+            state_update_stats,
             state_checkpoint_hashes,
+            state_checkpoint_usages,
+            checkpoint_roots,
             result_state,
             next_epoch_state,
-        ))
+        )
+        .map(|result| result.with_new_node_counts(new_node_counts))
+        .map(|result| result.with_category_changes(category_changes))
+        .map(|result| {
+            if conflict_analysis {
+                result.with_conflict_report()
+            } else {
+                result
+            }
+        })
     }
 
     fn add_transaction(
         &mut self,
-        txn: &Transaction,
+        txn: Option<&Transaction>,
         txn_output: &ParsedTransactionOutput,
-    ) -> Result<(HashMap<StateKey, StateValue>, Option<HashValue>)> {
-        let updated_state_kvs = process_write_set(
-            Some(txn),
-            &mut self.state_cache,
-            txn_output.write_set().clone(),
-        )?;
-        self.updates_after_latest.extend(updated_state_kvs.clone());
-        self.next_version += 1;
+        is_state_checkpoint: bool,
+        cached_writes: Option<Vec<CachedWrite>>,
+    ) -> Result<(
+        HashMap<StateKey, Option<Arc<StateValue>>>,
+        StateUpdateStats,
+        Option<HashValue>,
+    )> {
+        let (mut updated_state_kvs, stats) =
+            self.apply_transaction(txn, txn_output, cached_writes)?;
+        let state_checkpoint_hash = if is_state_checkpoint {
+            if self.fast_replay {
+                updated_state_kvs = self.take_replay_updates();
+            }
+            Some(self.make_checkpoint()?)
+        } else {
+            None
+        };
+        Ok((updated_state_kvs, stats, state_checkpoint_hash))
+    }
 
-        if txn_output.is_reconfig() {
-            Ok((updated_state_kvs, Some(self.make_checkpoint()?)))
+    fn apply_transaction(
+        &mut self,
+        txn: Option<&Transaction>,
+        txn_output: &ParsedTransactionOutput,
+        cached_writes: Option<Vec<CachedWrite>>,
+    ) -> Result<(HashMap<StateKey, Option<Arc<StateValue>>>, StateUpdateStats)> {
+        let mut stats = StateUpdateStats::default();
+        // In fast replay mode, the updates only go to `updates_after_latest`, without a map of
+        // their own for the transaction.
+        let mut updated_state_kvs = HashMap::new();
+        if self.fast_replay {
+            self.process_write_set_into_pending(
+                txn,
+                txn_output.write_set(),
+                cached_writes,
+                &mut stats,
+            )?;
         } else {
-            match txn {
-                Transaction::BlockMetadata(_) | Transaction::UserTransaction(_) => {
-                    Ok((updated_state_kvs, None))
-                }
-                Transaction::GenesisTransaction(_) | Transaction::StateCheckpoint(_) => {
-                    Ok((updated_state_kvs, Some(self.make_checkpoint()?)))
-                }
-            }
+            self.process_write_set(
+                txn,
+                txn_output.write_set(),
+                cached_writes,
+                &mut stats,
+                &mut updated_state_kvs,
+            )?;
+            self.updates_after_latest.extend(updated_state_kvs.clone());
+        }
+        if txn_output.is_reconfig() {
+            let epoch_state = self.parse_validator_set()?;
+            self.epoch_states.push((self.next_version, epoch_state));
+        }
+        self.next_version += 1;
+        self.pending_bytes += stats.bytes_written;
+        if (self.updates_after_latest.len() >= self.max_pending_keys
+            || self.pending_bytes >= self.max_pending_bytes)
+            && self.num_retained_nodes < self.max_retained_nodes
+        {
+            self.update_latest()?;
         }
+        Ok((updated_state_kvs, stats))
     }
 
     fn make_checkpoint(&mut self) -> Result<HashValue> {
-        // Update SMT.
-        let smt_updates: Vec<_> = self
-            .updates_after_latest
-            .iter()
-            .map(|(key, value)| (key.hash(), value))
-            .collect();
-        let new_checkpoint = self.latest.batch_update(smt_updates, &self.proof_reader)?;
+        // Update SMT. The new values come from `updates_after_latest` itself rather than being
+        // looked up again, so there's no way for a key updated since the last checkpoint to be
+        // missing its value here.
+        self.hash_pending_keys()?;
+        let (new_checkpoint, timing) = self.updated_latest("make_checkpoint")?;
+        self.smt_update_timings.extend(timing);
+        self.record_retained_nodes(&new_checkpoint);
         let root_hash = new_checkpoint.root_hash();
+        let checkpoint_version = self
+            .next_version
+            .checked_sub(1)
+            .ok_or_else(|| anyhow!("State checkpoint before any transaction."))?;
+
+        self.record_new_nodes("make_checkpoint", &new_checkpoint);
+        fail_point!(crate::failpoints::STATE_CALCULATOR_CHECKPOINT, |_| {
+            Err(anyhow!("Injected error in make_checkpoint"))
+        });
 
         // Move self to the new checkpoint.
+        self.last_checkpoint_latest = new_checkpoint.clone();
         self.latest = new_checkpoint.clone();
         self.checkpoint = new_checkpoint.unfreeze();
-        self.checkpoint_version = self.next_version.checked_sub(1);
-        self.updates_between_checkpoint_and_latest = HashMap::new();
+        self.checkpoint_version = Some(checkpoint_version);
+        self.checkpoint_roots.push((checkpoint_version, root_hash));
+        if self.key_classifier.is_some() {
+            self.category_changes
+                .push(std::mem::take(&mut self.pending_category_changes));
+        }
+        self.checkpoint_usage = self.usage;
+        self.updates_between_checkpoint_and_latest = Arc::default();
         self.updates_after_latest = HashMap::new();
+        self.pending_bytes = 0;
 
         Ok(root_hash)
     }
 
-    fn parse_validator_set(state_cache: &HashMap<StateKey, StateValue>) -> Result<EpochState> {
-        let account_state_view = state_cache.as_account_with_state_cache(&CORE_CODE_ADDRESS);
+    // The category changes for each checkpoint followed by the ones after the last checkpoint, if
+    // keys are categorized.
+    fn take_category_changes(&mut self) -> Option<Vec<CategoryStateChanges>> {
+        self.key_classifier.as_ref()?;
+        let mut category_changes = std::mem::take(&mut self.category_changes);
+        category_changes.push(std::mem::take(&mut self.pending_category_changes));
+        Some(category_changes)
+    }
+
+    // In fast replay mode, returns the updates made since the last checkpoint in the chunk (or
+    // its start).
+    fn take_replay_updates(&mut self) -> HashMap<StateKey, Option<Arc<StateValue>>> {
+        self.save_replay_updates();
+        std::mem::take(&mut self.replay_updates)
+    }
+
+    // In fast replay mode, saves the pending updates before they're applied to the latest SMT.
+    fn save_replay_updates(&mut self) {
+        if self.fast_replay {
+            self.replay_updates.extend(
+                self.updates_after_latest
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone())),
+            );
+        }
+    }
+
+    // Applies the pending updates to the latest SMT, without making it a checkpoint.
+    fn update_latest(&mut self) -> Result<()> {
+        self.save_replay_updates();
+        self.hash_pending_keys()?;
+        let (latest, timing) = self.updated_latest("update_latest")?;
+        self.smt_update_timings.extend(timing);
+        self.record_retained_nodes(&latest);
+        self.latest = latest;
+        if !self.updates_after_latest.is_empty() {
+            Arc::make_mut(&mut self.updates_between_checkpoint_and_latest)
+                .extend(self.updates_after_latest.drain());
+        }
+        self.pending_bytes = 0;
+
+        Ok(())
+    }
+
+    // Hashes the pending keys which haven't been hashed before, in parallel unless in deterministic
+    // mode, so a key written again after a checkpoint isn't hashed again. Fails if a key has the
+    // same hash as another one, which the SMT would take for the same key.
+    fn hash_pending_keys(&mut self) -> Result<()> {
+        let key_hashes = &self.key_hashes;
+        let new_keys: Vec<_> = self
+            .updates_after_latest
+            .keys()
+            .filter(|key| !key_hashes.contains_key(*key))
+            .collect();
+        let key_hasher = self.key_hasher;
+        let hash = |key: &StateKey| (key.clone(), key_hasher(key));
+        let new_key_hashes: Vec<_> = if self.deterministic {
+            new_keys.into_iter().map(hash).collect()
+        } else {
+            new_keys
+                .into_par_iter()
+                .with_min_len(self.parallel_min_len)
+                .map(hash)
+                .collect()
+        };
+        for (key, key_hash) in new_key_hashes {
+            // Each key is hashed only once, so any key found under its hash is another one.
+            if let Some(existing_key) = self.keys_by_hash.insert(key_hash, key.clone()) {
+                APTOS_EXECUTOR_STATE_KEY_HASH_COLLISIONS.inc();
+                return Err(Error::StateKeyHashCollision {
+                    key_hash,
+                    existing_key,
+                    new_key: key,
+                }
+                .into());
+            }
+            self.key_hashes.insert(key, key_hash);
+        }
+        Ok(())
+    }
+
+    // Makes sure each transaction added got a version of its own.
+    fn ensure_num_txns(&self, num_txns: usize) -> Result<()> {
+        let num_versions = self.next_version - self.first_version;
+        ensure!(
+            num_versions == num_txns as Version,
+            "{} transactions were added, but they took {} versions starting at {}.",
+            num_txns,
+            num_versions,
+            self.first_version,
+        );
+        Ok(())
+    }
+
+    // Only parses the resources again if they've been written since they were last parsed.
+    fn parse_validator_set(&mut self) -> Result<EpochState> {
+        if let Some((version, epoch_state)) = &self.parsed_epoch_state {
+            if *version == self.epoch_resources_version {
+                return Ok(epoch_state.clone());
+            }
+        }
+
+        let account_state_view = LatestAccountView {
+            account_address: &CORE_CODE_ADDRESS,
+            state_cache: &self.state_cache,
+            frozen_base: &self.frozen_base,
+        };
         let validator_set = account_state_view
             .get_validator_set()?
-            .ok_or_else(|| anyhow!("ValidatorSet not touched on epoch change"))?;
+            .ok_or_else(|| anyhow!("ValidatorSet doesn't exist on epoch change"))?;
         let configuration = account_state_view
             .get_configuration_resource()?
-            .ok_or_else(|| anyhow!("Configuration resource not touched on epoch change"))?;
+            .ok_or_else(|| anyhow!("Configuration resource doesn't exist on epoch change"))?;
 
-        Ok(EpochState {
+        let epoch_state = EpochState {
             epoch: configuration.epoch(),
             verifier: (&validator_set).into(),
-        })
+        };
+        self.parsed_epoch_state = Some((self.epoch_resources_version, epoch_state.clone()));
+        Ok(epoch_state)
     }
 
-    fn finish(mut self) -> Result<(StateDelta, HashMap<StateKey, StateValue>)> {
-        let smt_updates: Vec<_> = self
-            .updates_after_latest
+    /// The SMT with all the transactions added so far, leaving `self.latest` as is. Also returns
+    /// how long the update took, if there was anything to update.
+    fn updated_latest(
+        &self,
+        call_site: &'static str,
+    ) -> Result<(FrozenSparseMerkleTree<StateValue>, Option<SmtUpdateTiming>)> {
+        // If the last transaction was a checkpoint, the latest SMT is the checkpoint SMT.
+        if self.updates_after_latest.is_empty() {
+            return Ok((self.latest.clone(), None));
+        }
+
+        let start = Instant::now();
+        let num_keys = self.updates_after_latest.len();
+        let updated = if self.deterministic {
+            let smt_updates = sorted_smt_updates(&self.updates_after_latest, &self.key_hashes);
+            let (latest, proof_reader) = (&self.latest, &self.proof_reader);
+            DETERMINISTIC_POOL.install(|| latest.batch_update(smt_updates, proof_reader))
+        } else if self.sharded_smt_updates {
+            let smt_updates = sharded_smt_updates(&self.updates_after_latest, &self.key_hashes);
+            self.latest.batch_update(smt_updates, &self.proof_reader)
+        } else {
+            // The SMT sorts the updates itself, so they're passed straight through.
+            let smt_updates = self.updates_after_latest.iter().map(|(key, value)| {
+                (
+                    key_hash(&self.key_hashes, key),
+                    state_value_or_deleted(value),
+                )
+            });
+            self.latest.batch_update(smt_updates, &self.proof_reader)
+        };
+        let updated = updated.map_err(|error| self.update_error(error))?;
+        let duration = start.elapsed();
+
+        APTOS_EXECUTOR_SMT_UPDATE_SECONDS
+            .with_label_values(&[call_site])
+            .observe(duration.as_secs_f64());
+        APTOS_EXECUTOR_SMT_UPDATE_KEYS
+            .with_label_values(&[call_site])
+            .observe(num_keys as f64);
+        Ok((
+            updated,
+            Some(SmtUpdateTiming {
+                call_site,
+                num_keys,
+                duration,
+            }),
+        ))
+    }
+
+    /// The root hash a checkpoint would have if it were made now, computed without updating the
+    /// latest SMT or creating any nodes, so nothing is carried over if it's thrown away.
+    pub fn preview_checkpoint_root(&self) -> Result<HashValue> {
+        let smt_updates = self.updates_after_latest.iter().map(|(key, value)| {
+            (
+                key_hash(&self.key_hashes, key),
+                state_value_or_deleted(value),
+            )
+        });
+        self.latest
+            .compute_root_hash_for_updates(smt_updates, &self.proof_reader)
+            .map_err(|error| self.update_error(error))
+    }
+
+    // Names the key if a proof was missing.
+    fn update_error(&self, error: UpdateError) -> anyhow::Error {
+        match error {
+            UpdateError::MissingProof { key } => {
+                APTOS_EXECUTOR_MISSING_PROOFS.inc();
+                Error::MissingProof {
+                    key_hash: key,
+                    state_key: self.keys_by_hash.get(&key).cloned(),
+                    base_root_hash: self.frozen_base.root_hash(),
+                    num_proofs: self.proof_reader.num_proofs(),
+                }
+                .into()
+            }
+            error => error.into(),
+        }
+    }
+
+    // Adds the nodes `updated` created on top of the latest SMT to the retained ones. Only the new
+    // nodes are visited.
+    fn record_retained_nodes(&mut self, updated: &FrozenSparseMerkleTree<StateValue>) {
+        let counts = updated.new_node_counts_since(&self.latest);
+        self.num_retained_nodes += counts.num_internal_nodes + counts.num_leaves;
+    }
+
+    // Logs a summary of the SMT updates made if any of them was slow.
+    fn log_slow_smt_updates(&self) {
+        let slowest = match self.smt_update_timings.iter().max_by_key(|t| t.duration) {
+            Some(slowest) if slowest.duration >= self.slow_smt_update_threshold => slowest,
+            _ => return,
+        };
+        let total: Duration = self.smt_update_timings.iter().map(|t| t.duration).sum();
+        warn!(
+            num_updates = self.smt_update_timings.len(),
+            total = ?total,
+            slowest_call_site = slowest.call_site,
+            slowest_num_keys = slowest.num_keys,
+            slowest = ?slowest.duration,
+            num_retained_nodes = self.num_retained_nodes,
+            "Slow SMT update in the state calculator."
+        );
+    }
+
+    // Counts the nodes `smt` created since the last checkpoint, and exports the numbers as metrics.
+    fn record_new_nodes(&mut self, call_site: &str, smt: &FrozenSparseMerkleTree<StateValue>) {
+        let counts = smt.new_node_counts_since(&self.last_checkpoint_latest);
+        for (kind, num_nodes) in [
+            ("node_hashes", counts.num_node_hashes),
+            ("internal", counts.num_internal_nodes),
+            ("leaf", counts.num_leaves),
+        ] {
+            APTOS_EXECUTOR_SMT_NEW_NODES
+                .with_label_values(&[call_site, kind])
+                .observe(num_nodes as f64);
+        }
+        self.new_node_counts.push(counts);
+    }
+
+    // Logs a summary of the chunk, with how its state was read and how many SMT nodes it created,
+    // and exports the former as metrics.
+    fn record_state_cache_stats(&self) {
+        let stats = &self.state_cache_stats;
+        let new_node_counts = self
+            .new_node_counts
             .iter()
-            .map(|(key, value)| (key.hash(), value))
-            .collect();
-        let latest = self.latest.batch_update(smt_updates, &self.proof_reader)?;
+            .fold(NewNodeCounts::default(), |total, counts| {
+                total.combine(*counts)
+            });
+        debug!(
+            first_version = self.first_version,
+            num_txns = self.next_version - self.first_version,
+            num_cache_hits = stats.num_cache_hits,
+            num_speculative_reads = stats.num_speculative_reads,
+            num_storage_reads = stats.num_storage_reads,
+            storage_read_time = ?stats.storage_read_time,
+            num_proofs = stats.num_proofs,
+            num_cached_keys = stats.num_cached_keys,
+            cached_bytes = stats.cached_bytes,
+            num_new_node_hashes = new_node_counts.num_node_hashes,
+            num_new_internal_nodes = new_node_counts.num_internal_nodes,
+            num_new_leaves = new_node_counts.num_leaves,
+            num_retained_nodes = self.num_retained_nodes,
+            "State calculator chunk summary."
+        );
+        for (source, num_reads) in [
+            ("cache", stats.num_cache_hits),
+            ("speculative", stats.num_speculative_reads),
+            ("storage", stats.num_storage_reads),
+        ] {
+            APTOS_EXECUTOR_STATE_CACHE_READS
+                .with_label_values(&[source])
+                .inc_by(num_reads as u64);
+        }
+        APTOS_EXECUTOR_STATE_CACHE_STORAGE_READ_SECONDS
+            .observe(stats.storage_read_time.as_secs_f64());
+        APTOS_EXECUTOR_STATE_CACHE_PROOFS.inc_by(stats.num_proofs as u64);
+        APTOS_EXECUTOR_STATE_CACHE_BYTES.observe(stats.cached_bytes as f64);
+    }
+
+    /// What `finish()` would return now.
+    fn snapshot(&self) -> Result<StateDelta> {
+        let (latest, _timing) = self.updated_latest("snapshot")?;
+        let mut updates_since_checkpoint = (*self.updates_between_checkpoint_and_latest).clone();
+        updates_since_checkpoint.extend(
+            self.updates_after_latest
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+
+        Ok(StateDelta::new(
+            self.checkpoint.clone(),
+            self.checkpoint_version,
+            latest.unfreeze(),
+            self.next_version.checked_sub(1),
+            updates_since_checkpoint,
+            self.checkpoint_usage,
+            self.usage,
+        ))
+    }
+
+    /// The state after the transactions added, and the numbers of SMT nodes created for each
+    /// checkpoint followed by the number created after the last one.
+    fn finish(mut self) -> Result<(StateDelta, Vec<NewNodeCounts>)> {
+        self.hash_pending_keys()?;
+        let (latest, timing) = self.updated_latest("finish")?;
+        self.smt_update_timings.extend(timing);
+        self.record_retained_nodes(&latest);
+        self.record_new_nodes("finish", &latest);
+        fail_point!(crate::failpoints::STATE_CALCULATOR_FINISH, |_| {
+            Err(anyhow!("Injected error in finish"))
+        });
+        APTOS_EXECUTOR_SMT_RETAINED_NODES.set(self.num_retained_nodes as i64);
+        APTOS_EXECUTOR_SMT_RETAINED_GENERATIONS.set(latest.num_retained_generations() as i64);
+        self.log_slow_smt_updates();
+        self.record_state_cache_stats();
 
-        self.updates_between_checkpoint_and_latest
-            .extend(self.updates_after_latest);
+        if !self.updates_after_latest.is_empty() {
+            Arc::make_mut(&mut self.updates_between_checkpoint_and_latest)
+                .extend(self.updates_after_latest);
+        }
 
         let result_state = StateDelta::new(
             self.checkpoint,
@@ -198,22 +1443,30 @@ impl InMemoryStateCalculator {
             latest.unfreeze(),
             self.next_version.checked_sub(1),
             self.updates_between_checkpoint_and_latest,
+            self.checkpoint_usage,
+            self.usage,
         );
 
-        Ok((result_state, self.state_cache))
+        Ok((result_state, self.new_node_counts))
     }
 
     pub fn calculate_for_write_sets_after_snapshot(
         mut self,
         last_checkpoint_index: Option<usize>,
         write_sets: &[WriteSet],
-    ) -> Result<(Option<HashMap<StateKey, StateValue>>, StateDelta)> {
+    ) -> Result<(
+        Option<HashMap<StateKey, Option<Arc<StateValue>>>>,
+        StateDelta,
+    )> {
         let idx_after_last_checkpoint = last_checkpoint_index.map_or(0, |idx| idx + 1);
         let updates_before_last_checkpoint = if idx_after_last_checkpoint != 0 {
             for write_set in write_sets[0..idx_after_last_checkpoint].iter() {
-                let state_updates =
-                    process_write_set(None, &mut self.state_cache, (*write_set).clone())?;
-                self.updates_after_latest.extend(state_updates.into_iter());
+                self.process_write_set_into_pending(
+                    None,
+                    write_set,
+                    None,
+                    &mut StateUpdateStats::default(),
+                )?;
                 self.next_version += 1;
             }
             let updates = self.updates_after_latest.clone();
@@ -223,56 +1476,504 @@ impl InMemoryStateCalculator {
             None
         };
         for write_set in write_sets[idx_after_last_checkpoint..].iter() {
-            let state_updates =
-                process_write_set(None, &mut self.state_cache, (*write_set).clone())?;
-            self.updates_after_latest.extend(state_updates.into_iter());
+            self.process_write_set_into_pending(
+                None,
+                write_set,
+                None,
+                &mut StateUpdateStats::default(),
+            )?;
             self.next_version += 1;
         }
-        let (result_state, _) = self.finish()?;
+        self.ensure_num_txns(write_sets.len())?;
+        let (result_state, _new_node_counts) = self.finish()?;
         Ok((updates_before_last_checkpoint, result_state))
     }
+
+    // Applies the write sets of a chunk to the `state_cache` ahead of processing them, in parallel:
+    // the cache is split into shards by key, and each shard applies the writes to its keys in
+    // transaction order, so each write sees the value written before it and the last write of a
+    // key wins, as when applied one transaction at a time.
+    // Returns each transaction's writes, in write set order.
+    fn cache_write_sets(&mut self, write_sets: &[&WriteSet]) -> Vec<Vec<CachedWrite>> {
+        let write_shards: Vec<Vec<usize>> = write_sets
+            .par_iter()
+            .map(|write_set| {
+                write_set
+                    .iter()
+                    .map(|(state_key, _)| write_shard(state_key))
+                    .collect()
+            })
+            .collect();
+        let mut shards: Vec<HashMap<_, _>> =
+            (0..NUM_WRITE_SHARDS).map(|_| HashMap::new()).collect();
+        for (state_key, value) in std::mem::take(&mut self.state_cache) {
+            shards[write_shard(&state_key)].insert(state_key, value);
+        }
+
+        let frozen_base = &self.frozen_base;
+        let shard_writes: Vec<Vec<(usize, usize, CachedWrite)>> = shards
+            .par_iter_mut()
+            .enumerate()
+            .map(|(shard, state_cache)| {
+                let mut writes = Vec::new();
+                for (txn_idx, (write_set, write_shards)) in
+                    write_sets.iter().zip(&write_shards).enumerate()
+                {
+                    for (write_idx, ((state_key, write_op), key_shard)) in
+                        write_set.iter().zip(write_shards).enumerate()
+                    {
+                        if *key_shard == shard {
+                            let write = cache_write(state_cache, frozen_base, state_key, write_op);
+                            writes.push((txn_idx, write_idx, write));
+                        }
+                    }
+                }
+                writes
+            })
+            .collect();
+        for shard in shards {
+            self.state_cache.extend(shard);
+        }
+
+        let mut cached_writes: Vec<Vec<Option<CachedWrite>>> = write_shards
+            .iter()
+            .map(|write_shards| write_shards.iter().map(|_| None).collect())
+            .collect();
+        for (txn_idx, write_idx, write) in shard_writes.into_iter().flatten() {
+            cached_writes[txn_idx][write_idx] = Some(write);
+        }
+        cached_writes
+            .into_iter()
+            .map(|writes| {
+                writes
+                    .into_iter()
+                    .map(|write| write.expect("Every write belongs to a shard."))
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Checks the write set is a subset of the read set.
+    // Updates the `state_cache` (unless `cached_writes` says how it was updated already) and the
+    // storage usage to reflect the latest value, and adds the write set to `stats`.
+    // Adds all state key-value pairs touched to `state_updates`.
+    fn process_write_set(
+        &mut self,
+        transaction: Option<&Transaction>,
+        write_set: &WriteSet,
+        cached_writes: Option<Vec<CachedWrite>>,
+        stats: &mut StateUpdateStats,
+        state_updates: &mut HashMap<StateKey, Option<Arc<StateValue>>>,
+    ) -> Result<()> {
+        // Find all keys this transaction touches while processing each write op.
+        match cached_writes {
+            Some(cached_writes) => {
+                for ((state_key, _), write) in write_set.iter().zip(cached_writes) {
+                    let (state_key, state_value) =
+                        self.process_state_key_write(transaction, state_key, write, stats)?;
+                    state_updates.insert(state_key, state_value);
+                }
+            }
+            None => {
+                for (state_key, write_op) in write_set.iter() {
+                    let write = cache_write(
+                        &mut self.state_cache,
+                        &self.frozen_base,
+                        state_key,
+                        write_op,
+                    );
+                    let (state_key, state_value) =
+                        self.process_state_key_write(transaction, state_key, write, stats)?;
+                    state_updates.insert(state_key, state_value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Like `process_write_set`, adding the pairs straight to `updates_after_latest`.
+    fn process_write_set_into_pending(
+        &mut self,
+        transaction: Option<&Transaction>,
+        write_set: &WriteSet,
+        cached_writes: Option<Vec<CachedWrite>>,
+        stats: &mut StateUpdateStats,
+    ) -> Result<()> {
+        let mut updates_after_latest = std::mem::take(&mut self.updates_after_latest);
+        let result = self.process_write_set(
+            transaction,
+            write_set,
+            cached_writes,
+            stats,
+            &mut updates_after_latest,
+        );
+        self.updates_after_latest = updates_after_latest;
+        result
+    }
+
+    fn process_state_key_write(
+        &mut self,
+        transaction: Option<&Transaction>,
+        state_key: &StateKey,
+        write: CachedWrite,
+        stats: &mut StateUpdateStats,
+    ) -> Result<(StateKey, Option<Arc<StateValue>>)> {
+        let CachedWrite {
+            state_value,
+            old_value,
+            was_vacant,
+        } = write;
+        if self.trace_writes {
+            debug!(
+                version = self.next_version,
+                state_key = ?state_key,
+                value_hash = ?state_value.as_ref().map(|value| value.hash()),
+                "State calculator write.",
+            );
+        }
+        if *state_key == *VALIDATOR_SET_KEY || *state_key == *CONFIGURATION_KEY {
+            self.epoch_resources_version = Some(self.next_version);
+        }
+        if let (true, Some(txn)) = (was_vacant, transaction) {
+            ensure_txn_valid_for_vacant_entry(txn)?;
+        }
+        // The sizes of the item before and after the write, if the old value is known.
+        let item_sizes = match &old_value {
+            Some(old_value) => Some((
+                item_size(state_key, old_value.as_deref())?,
+                item_size(state_key, state_value.as_deref())?,
+            )),
+            None => None,
+        };
+        if let Some(key_classifier) = &self.key_classifier {
+            if let Some(category) = key_classifier(state_key) {
+                let change = self.pending_category_changes.entry(category).or_default();
+                change.keys_written += 1;
+                if let Some((old_size, new_size)) = item_sizes {
+                    if let Some(bytes) = old_size {
+                        change.items_delta -= 1;
+                        change.bytes_delta -= bytes as i64;
+                    }
+                    if let Some(bytes) = new_size {
+                        change.items_delta += 1;
+                        change.bytes_delta += bytes as i64;
+                    }
+                }
+            }
+        }
+        match item_sizes {
+            Some((old_size, new_size)) if !self.usage.is_untracked() => {
+                if let Some(bytes) = old_size {
+                    self.usage.remove_item(bytes);
+                }
+                if let Some(bytes) = new_size {
+                    self.usage.add_item(bytes);
+                }
+            }
+            Some(_) => (),
+            None => self.usage = StateStorageUsage::new_untracked(),
+        }
+        match item_sizes {
+            Some((None, Some(new_size))) => stats.new_key_bytes += new_size,
+            Some((Some(old_size), Some(new_size))) => {
+                stats.overwritten_bytes_delta += new_size as i64 - old_size as i64
+            }
+            Some((Some(old_size), None)) => stats.freed_bytes += old_size,
+            Some((None, None)) | None => (),
+        }
+
+        stats.keys_written += 1;
+        match state_value
+            .as_ref()
+            .and_then(|value| value.maybe_bytes.as_ref())
+        {
+            Some(bytes) => {
+                stats.bytes_written += bytes.len();
+                let existed = old_value.map_or(true, |old_value| {
+                    old_value.map_or(false, |value| value.maybe_bytes.is_some())
+                });
+                if existed {
+                    stats.keys_modified += 1;
+                } else {
+                    stats.keys_created += 1;
+                }
+            }
+            None => stats.keys_deleted += 1,
+        }
+        Ok((state_key.clone(), state_value))
+    }
+}
+
+/// An `InMemoryStateCalculator` fed one transaction at a time, e.g. while the rest of a block is
+/// still executing. Transactions are checkpointed as `calculate_for_transaction_chunk` would, and
+/// `finish()` returns the same as it would have for all the transactions added.
+pub struct IncrementalStateCalculator {
+    calculator: InMemoryStateCalculator,
+    num_txns: usize,
+    state_updates_vec: Vec<HashMap<StateKey, Option<Arc<StateValue>>>>,
+    state_update_stats: Vec<StateUpdateStats>,
+    state_checkpoint_hashes: Vec<Option<HashValue>>,
+    state_checkpoint_usages: Vec<Option<StateStorageUsage>>,
 }
 
-// Checks the write set is a subset of the read set.
-// Updates the `state_cache` to reflect the latest value.
-// Returns all state key-value pair touched.
-pub fn process_write_set(
-    transaction: Option<&Transaction>,
-    state_cache: &mut HashMap<StateKey, StateValue>,
-    write_set: WriteSet,
-) -> Result<HashMap<StateKey, StateValue>> {
-    // Find all keys this transaction touches while processing each write op.
-    write_set
-        .into_iter()
-        .map(|(state_key, write_op)| {
-            process_state_key_write_op(transaction, state_cache, state_key, write_op)
+impl IncrementalStateCalculator {
+    /// Returns the state checkpoint hash if the transaction is a checkpoint.
+    pub fn add_transaction(
+        &mut self,
+        txn: &Transaction,
+        txn_output: &ParsedTransactionOutput,
+    ) -> Result<Option<HashValue>> {
+        ensure!(
+            !self.calculator.fast_replay,
+            "Transactions can't be added one at a time in fast replay mode."
+        );
+        let (state_updates, stats, state_checkpoint_hash) = self.calculator.add_transaction(
+            Some(txn),
+            txn_output,
+            is_state_checkpoint(txn, txn_output),
+            None,
+        )?;
+        self.num_txns += 1;
+        self.state_updates_vec.push(state_updates);
+        self.state_update_stats.push(stats);
+        self.state_checkpoint_usages
+            .push(state_checkpoint_hash.map(|_| self.calculator.usage));
+        self.state_checkpoint_hashes.push(state_checkpoint_hash);
+        Ok(state_checkpoint_hash)
+    }
+
+    /// Moves the calculation onto `new_base`, as `InMemoryStateCalculator::rebase()` does.
+    pub fn rebase(mut self, new_base: &StateDelta) -> Result<Self> {
+        self.calculator = self.calculator.rebase(new_base)?;
+        Ok(self)
+    }
+
+    /// The root hash of the state with all the transactions added so far, as the calculator's
+    /// `preview_checkpoint_root()`. Nothing is carried over to later calls.
+    pub fn peek_latest_root_hash(&self) -> Result<HashValue> {
+        self.calculator.preview_checkpoint_root()
+    }
+
+    /// The state with all the transactions added so far, as `finish()` would return it.
+    pub fn snapshot(&self) -> Result<StateDelta> {
+        self.calculator.snapshot()
+    }
+
+    /// The epoch state after each reconfiguration transaction added so far, with the transaction's
+    /// version.
+    pub fn epoch_states_for_chunk(&self) -> &[(Version, EpochState)] {
+        &self.calculator.epoch_states
+    }
+
+    /// The same as `calculate_for_transaction_chunk` returns, with the next epoch state if any
+    /// transaction added was a reconfiguration.
+    pub fn finish(mut self) -> Result<ChunkStateUpdateResult> {
+        let next_epoch_state = self
+            .calculator
+            .epoch_states
+            .last()
+            .map(|(_, epoch_state)| epoch_state.clone());
+        self.calculator.ensure_num_txns(self.num_txns)?;
+        let checkpoint_roots = std::mem::take(&mut self.calculator.checkpoint_roots);
+        let category_changes = self.calculator.take_category_changes();
+        let conflict_analysis = self.calculator.conflict_analysis;
+        let (result_state, new_node_counts) = self.calculator.finish()?;
+
+        ChunkStateUpdateResult::new(
+            self.num_txns,
+            self.state_updates_vec,
+            self.state_update_stats,
+            self.state_checkpoint_hashes,
+            self.state_checkpoint_usages,
+            checkpoint_roots,
+            result_state,
+            next_epoch_state,
+        )
+        .map(|result| result.with_new_node_counts(new_node_counts))
+        .map(|result| result.with_category_changes(category_changes))
+        .map(|result| {
+            if conflict_analysis {
+                result.with_conflict_report()
+            } else {
+                result
+            }
         })
-        .collect::<Result<_>>()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct SmtUpdateTiming {
+    call_site: &'static str,
+    num_keys: usize,
+    duration: Duration,
+}
+
+/// A write applied to the state cache, and what the cache had for the key before.
+struct CachedWrite {
+    state_value: Option<Arc<StateValue>>,
+    /// `None` if the old value isn't known.
+    old_value: Option<Option<Arc<StateValue>>>,
+    /// Whether the key wasn't in the cache, i.e. the chunk hadn't read or written it yet.
+    was_vacant: bool,
 }
 
-fn process_state_key_write_op(
-    transaction: Option<&Transaction>,
-    state_cache: &mut HashMap<StateKey, StateValue>,
-    state_key: StateKey,
-    write_op: WriteOp,
-) -> Result<(StateKey, StateValue)> {
+/// Writes `write_op` to `state_cache`. If the chunk hadn't read or written the key, its old value
+/// is looked up in `frozen_base`.
+fn cache_write(
+    state_cache: &mut HashMap<StateKey, Option<Arc<StateValue>>>,
+    frozen_base: &FrozenSparseMerkleTree<StateValue>,
+    state_key: &StateKey,
+    write_op: &WriteOp,
+) -> CachedWrite {
     let state_value = match write_op {
-        WriteOp::Value(new_value) => StateValue::from(new_value),
-        WriteOp::Deletion => StateValue::empty(),
+        WriteOp::Value(new_value) => Some(Arc::new(StateValue::from(new_value.clone()))),
+        WriteOp::Deletion => None,
         WriteOp::Delta(..) => unreachable!("deltas are only used in executor"),
     };
-    match state_cache.entry(state_key.clone()) {
-        hash_map::Entry::Occupied(mut entry) => {
-            entry.insert(state_value.clone());
+    // The VM reads keys before writing them, so they're usually cached already, in which case the
+    // key isn't cloned.
+    if let Some(cached) = state_cache.get_mut(state_key) {
+        return CachedWrite {
+            old_value: Some(std::mem::replace(cached, state_value.clone())),
+            state_value,
+            was_vacant: false,
+        };
+    }
+    state_cache.insert(state_key.clone(), state_value.clone());
+    let old_value = match frozen_base.get(state_key.hash()) {
+        StateStoreStatus::DoesNotExist => Some(None),
+        StateStoreStatus::ExistsInScratchPad(value) => Some(Some(Arc::new(value))),
+        StateStoreStatus::ExistsInDB | StateStoreStatus::Unknown => None,
+    };
+    CachedWrite {
+        state_value,
+        old_value,
+        was_vacant: true,
+    }
+}
+
+/// `usage`, tracked on top of `old_base_usage`, moved on top of `new_base_usage`.
+fn rebased_usage(
+    usage: StateStorageUsage,
+    old_base_usage: StateStorageUsage,
+    new_base_usage: StateStorageUsage,
+) -> StateStorageUsage {
+    match (usage, old_base_usage, new_base_usage) {
+        (
+            StateStorageUsage::Tracked { items, bytes },
+            StateStorageUsage::Tracked {
+                items: old_items,
+                bytes: old_bytes,
+            },
+            StateStorageUsage::Tracked {
+                items: new_items,
+                bytes: new_bytes,
+            },
+        ) => match (
+            (items + new_items).checked_sub(old_items),
+            (bytes + new_bytes).checked_sub(old_bytes),
+        ) {
+            (Some(items), Some(bytes)) => StateStorageUsage::new(items, bytes),
+            _ => StateStorageUsage::new_untracked(),
+        },
+        _ => StateStorageUsage::new_untracked(),
+    }
+}
+
+/// The shard of the state cache `state_key` is in when write sets are applied in parallel. Any
+/// stable split does; the key's (cryptographic) hash is only computed later, and costs more.
+fn write_shard(state_key: &StateKey) -> usize {
+    let mut hasher = DefaultHasher::new();
+    std::hash::Hash::hash(state_key, &mut hasher);
+    hasher.finish() as usize % NUM_WRITE_SHARDS
+}
+
+/// An account's state as of the last transaction added to the calculator: if the chunk didn't read
+/// or write a key, it's as in the base state.
+struct LatestAccountView<'a> {
+    account_address: &'a AccountAddress,
+    state_cache: &'a HashMap<StateKey, Option<Arc<StateValue>>>,
+    frozen_base: &'a FrozenSparseMerkleTree<StateValue>,
+}
+
+impl<'a> AccountView for LatestAccountView<'a> {
+    fn get_state_value(&self, state_key: &StateKey) -> Result<Option<Vec<u8>>> {
+        if let Some(value) = self.state_cache.get(state_key) {
+            return Ok(value.as_ref().and_then(|value| value.maybe_bytes.clone()));
         }
-        hash_map::Entry::Vacant(entry) => {
-            if let Some(txn) = transaction {
-                ensure_txn_valid_for_vacant_entry(txn)?;
-            }
-            entry.insert(state_value.clone());
+        match self.frozen_base.get(state_key.hash()) {
+            StateStoreStatus::ExistsInScratchPad(value) => Ok(value.maybe_bytes),
+            StateStoreStatus::DoesNotExist => Ok(None),
+            StateStoreStatus::ExistsInDB | StateStoreStatus::Unknown => bail!(
+                "{:?} wasn't read in the chunk and isn't in memory in the base state",
+                state_key
+            ),
         }
     }
-    Ok((state_key, state_value))
+
+    fn get_account_address(&self) -> Result<Option<AccountAddress>> {
+        Ok(Some(*self.account_address))
+    }
+}
+
+/// The hash of `key`, from `key_hashes` if it's been hashed before.
+fn key_hash(key_hashes: &HashMap<StateKey, HashValue>, key: &StateKey) -> HashValue {
+    key_hashes.get(key).copied().unwrap_or_else(|| key.hash())
+}
+
+/// The SMT updates for `updates`, sorted by key hash in 16 shards in parallel.
+fn sharded_smt_updates<'a>(
+    updates: &'a HashMap<StateKey, Option<Arc<StateValue>>>,
+    key_hashes: &HashMap<StateKey, HashValue>,
+) -> Vec<(HashValue, &'a StateValue)> {
+    let mut shards: Vec<Vec<_>> = (0..16)
+        .map(|_| Vec::with_capacity(updates.len() / 16))
+        .collect();
+    for (key, value) in updates {
+        let key_hash = key_hash(key_hashes, key);
+        shards[key_hash.nibble(0) as usize].push((key_hash, state_value_or_deleted(value)));
+    }
+    shards
+        .par_iter_mut()
+        .for_each(|shard| shard.sort_unstable_by_key(|(key_hash, _)| *key_hash));
+    let mut smt_updates = Vec::with_capacity(updates.len());
+    for shard in shards {
+        smt_updates.extend(shard);
+    }
+    smt_updates
+}
+
+/// The SMT updates for `updates`, sorted by key hash on this thread.
+fn sorted_smt_updates<'a>(
+    updates: &'a HashMap<StateKey, Option<Arc<StateValue>>>,
+    key_hashes: &HashMap<StateKey, HashValue>,
+) -> Vec<(HashValue, &'a StateValue)> {
+    let mut smt_updates = Vec::with_capacity(updates.len());
+    smt_updates.extend(
+        updates
+            .iter()
+            .map(|(key, value)| (key_hash(key_hashes, key), state_value_or_deleted(value))),
+    );
+    smt_updates.sort_unstable_by_key(|(key_hash, _)| *key_hash);
+    smt_updates
+}
+
+/// The bytes a state item takes in storage, or `None` if there's no item.
+fn item_size(state_key: &StateKey, state_value: Option<&StateValue>) -> Result<Option<usize>> {
+    match state_value.and_then(|value| value.maybe_bytes.as_ref()) {
+        Some(bytes) => Ok(Some(state_key.encode()?.len() + bytes.len())),
+        None => Ok(None),
+    }
+}
+
+fn is_state_checkpoint(transaction: &Transaction, txn_output: &ParsedTransactionOutput) -> bool {
+    if txn_output.is_reconfig() {
+        return true;
+    }
+    match transaction {
+        Transaction::BlockMetadata(_) | Transaction::UserTransaction(_) => false,
+        Transaction::GenesisTransaction(_) | Transaction::StateCheckpoint(_) => true,
+    }
 }
 
 fn ensure_txn_valid_for_vacant_entry(transaction: &Transaction) -> Result<()> {
@@ -0,0 +1,2409 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    in_memory_state_calculator::{
+        classify_by_module, CategoryStateChange, ChunkStateUpdateResult, ConflictReport,
+        InMemoryStateCalculator, StateUpdateStats, NEW_EPOCH_EVENT_KEY,
+    },
+    metrics::{
+        APTOS_EXECUTOR_SMT_NEW_NODES, APTOS_EXECUTOR_SMT_UPDATE_KEYS,
+        APTOS_EXECUTOR_SMT_UPDATE_SECONDS,
+    },
+    Error, ParsedTransactionOutput, ProofReader,
+};
+use aptos_crypto::{
+    ed25519::Ed25519PrivateKey,
+    hash::{CryptoHash, SPARSE_MERKLE_PLACEHOLDER_HASH},
+    HashValue, PrivateKey, Uniform,
+};
+use aptos_types::{
+    access_path::AccessPath,
+    account_address::AccountAddress,
+    account_config::CORE_CODE_ADDRESS,
+    contract_event::ContractEvent,
+    on_chain_config::{access_path_for_config, ConfigurationResource, OnChainConfig, ValidatorSet},
+    proof::{SparseMerkleLeafNode, SparseMerkleProof},
+    state_store::{
+        state_key::StateKey, state_storage_usage::StateStorageUsage, state_value::StateValue,
+        table::TableHandle,
+    },
+    test_helpers::transaction_test_helpers::get_test_signed_txn,
+    transaction::{
+        ExecutionStatus, Transaction, TransactionInfo, TransactionOutput, TransactionStatus,
+        Version,
+    },
+    write_set::{WriteOp, WriteSetMut},
+};
+use move_deps::move_core_types::{
+    identifier::Identifier,
+    language_storage::{ModuleId, StructTag, TypeTag},
+    move_resource::MoveStructType,
+};
+use proptest::{
+    collection::{btree_map, vec},
+    option,
+    prelude::*,
+};
+use scratchpad::{SparseMerkleTree, StateStoreStatus};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
+use storage_interface::{cached_state_view::StateCache, state_delta::StateDelta};
+
+fn key(k: &str) -> StateKey {
+    StateKey::Raw(k.as_bytes().to_vec())
+}
+
+fn value(v: &str) -> StateValue {
+    StateValue::from(v.as_bytes().to_vec())
+}
+
+/// A calculator on top of the empty state, where `keys` have been read (as the VM would before
+/// writing them).
+fn calculator(keys: &[StateKey]) -> InMemoryStateCalculator {
+    calculator_on(&StateDelta::new_empty(), keys)
+}
+
+/// A calculator on top of `base`, which holds all its state in memory, where `keys` have been read.
+fn calculator_on(base: &StateDelta, keys: &[StateKey]) -> InMemoryStateCalculator {
+    let frozen_base = base.current.clone().freeze();
+    let state_cache = keys
+        .iter()
+        .map(|key| {
+            let value = match frozen_base.get(key.hash()) {
+                StateStoreStatus::ExistsInScratchPad(value) => Some(Arc::new(value)),
+                _ => None,
+            };
+            (key.clone(), value)
+        })
+        .collect();
+    let state_cache = StateCache {
+        frozen_base,
+        state_cache,
+        proofs: HashMap::new(),
+        stats: Default::default(),
+    };
+    InMemoryStateCalculator::new(base, state_cache)
+}
+
+/// Writes of the empty value are deletions.
+fn output(writes: Vec<(StateKey, StateValue)>) -> ParsedTransactionOutput {
+    output_with_events(writes, vec![])
+}
+
+fn output_with_events(
+    writes: Vec<(StateKey, StateValue)>,
+    events: Vec<ContractEvent>,
+) -> ParsedTransactionOutput {
+    let write_set = WriteSetMut::new(
+        writes
+            .into_iter()
+            .map(|(key, value)| match value.maybe_bytes {
+                Some(bytes) => (key, WriteOp::Value(bytes)),
+                None => (key, WriteOp::Deletion),
+            })
+            .collect(),
+    )
+    .freeze()
+    .unwrap();
+    TransactionOutput::new(
+        write_set,
+        events,
+        0,
+        TransactionStatus::Keep(ExecutionStatus::Success),
+    )
+    .into()
+}
+
+fn user_txn(
+    sequence_number: u64,
+    writes: Vec<(StateKey, StateValue)>,
+) -> (Transaction, ParsedTransactionOutput) {
+    let private_key = Ed25519PrivateKey::generate_for_testing();
+    let txn = get_test_signed_txn(
+        AccountAddress::random(),
+        sequence_number,
+        &private_key,
+        private_key.public_key(),
+        None,
+    );
+    (Transaction::UserTransaction(txn), output(writes))
+}
+
+/// A user transaction emitting the new epoch event.
+fn reconfig_txn(
+    sequence_number: u64,
+    writes: Vec<(StateKey, StateValue)>,
+) -> (Transaction, ParsedTransactionOutput) {
+    let (txn, _) = user_txn(sequence_number, vec![]);
+    let event = ContractEvent::new(*NEW_EPOCH_EVENT_KEY, 0, TypeTag::Bool, vec![]);
+    (txn, output_with_events(writes, vec![event]))
+}
+
+fn state_checkpoint_txn() -> (Transaction, ParsedTransactionOutput) {
+    (
+        Transaction::StateCheckpoint(HashValue::random()),
+        output(vec![]),
+    )
+}
+
+/// A tree with all of `kvs`, built independently of the calculator. Deleted keys have the empty
+/// value, as that's how the trees record deletions.
+fn tree_of(kvs: &[(StateKey, StateValue)]) -> SparseMerkleTree<StateValue> {
+    SparseMerkleTree::new_empty()
+        .freeze()
+        .batch_update(
+            kvs.iter().map(|(key, value)| (key.hash(), value)).collect(),
+            &ProofReader::new_empty(),
+        )
+        .unwrap()
+        .unfreeze()
+}
+
+fn root_hash_of(kvs: &[(StateKey, StateValue)]) -> HashValue {
+    tree_of(kvs).root_hash()
+}
+
+/// The storage usage of `kvs`, counted from scratch.
+fn usage_of<'a>(
+    kvs: impl IntoIterator<Item = (&'a StateKey, &'a StateValue)>,
+) -> StateStorageUsage {
+    let mut usage = StateStorageUsage::zero();
+    for (key, value) in kvs {
+        if let Some(bytes) = &value.maybe_bytes {
+            usage.add_item(key.encode().unwrap().len() + bytes.len());
+        }
+    }
+    usage
+}
+
+/// A state at a checkpoint, with all of `kvs` in memory.
+fn state_of(kvs: &[(StateKey, StateValue)], version: Option<u64>) -> StateDelta {
+    let tree = tree_of(kvs);
+    let usage = usage_of(kvs.iter().map(|(key, value)| (key, value)));
+    StateDelta::new(
+        tree.clone(),
+        version,
+        tree,
+        version,
+        HashMap::new(),
+        usage,
+        usage,
+    )
+}
+
+#[test]
+fn test_calculate_for_block_appends_checkpoint() {
+    let kvs = vec![(key("a"), value("1")), (key("b"), value("2"))];
+    let keys: Vec<_> = kvs.iter().map(|(key, _)| key.clone()).collect();
+    let block = vec![
+        user_txn(0, vec![kvs[0].clone()]),
+        user_txn(1, vec![kvs[1].clone()]),
+    ];
+
+    let ChunkStateUpdateResult {
+        per_txn_state_updates: block_updates,
+        per_txn_state_checkpoint_hashes: block_hashes,
+        result_state: block_state,
+        ..
+    } = calculator(&keys).calculate_for_block(&block, true).unwrap();
+
+    // The same transactions replayed as a chunk, with the checkpoint made explicit.
+    let chunk = vec![
+        user_txn(0, vec![kvs[0].clone()]),
+        user_txn(1, vec![kvs[1].clone()]),
+        state_checkpoint_txn(),
+    ];
+    let ChunkStateUpdateResult {
+        per_txn_state_updates: chunk_updates,
+        per_txn_state_checkpoint_hashes: chunk_hashes,
+        result_state: chunk_state,
+        ..
+    } = calculator(&keys)
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+
+    let expected_root_hash = root_hash_of(&kvs);
+    assert_eq!(block_hashes, vec![None, Some(expected_root_hash)]);
+    assert_eq!(chunk_hashes, vec![None, None, Some(expected_root_hash)]);
+    assert_eq!(block_updates, chunk_updates[..2].to_vec());
+
+    // The block ends at a checkpoint, one version before the chunk does.
+    assert_eq!(block_state.base_version, Some(1));
+    assert_eq!(block_state.current_version, Some(1));
+    assert_eq!(chunk_state.base_version, Some(2));
+    assert_eq!(block_state.base_root_hash(), chunk_state.base_root_hash());
+    assert_eq!(block_state.current.root_hash(), expected_root_hash);
+    assert_eq!(chunk_state.current.root_hash(), expected_root_hash);
+    assert!(block_state.updates_since_base.is_empty());
+    assert!(chunk_state.updates_since_base.is_empty());
+}
+
+#[test]
+fn test_calculate_for_block_without_checkpoint() {
+    let kvs = vec![(key("a"), value("1")), (key("b"), value("2"))];
+    let keys: Vec<_> = kvs.iter().map(|(key, _)| key.clone()).collect();
+    let block = vec![
+        user_txn(0, vec![kvs[0].clone()]),
+        user_txn(1, vec![kvs[1].clone()]),
+    ];
+
+    let ChunkStateUpdateResult {
+        per_txn_state_checkpoint_hashes: block_hashes,
+        result_state: block_state,
+        ..
+    } = calculator(&keys)
+        .calculate_for_block(&block, false)
+        .unwrap();
+    let ChunkStateUpdateResult {
+        per_txn_state_checkpoint_hashes: chunk_hashes,
+        result_state: chunk_state,
+        ..
+    } = calculator(&keys)
+        .calculate_for_transaction_chunk(&block, false, None)
+        .unwrap();
+
+    assert_eq!(block_hashes, vec![None, None]);
+    assert_eq!(block_hashes, chunk_hashes);
+    assert_eq!(block_state.base_version, None);
+    assert_eq!(block_state.current_version, Some(1));
+    assert_eq!(
+        block_state.base_root_hash(),
+        *SPARSE_MERKLE_PLACEHOLDER_HASH
+    );
+    assert_eq!(block_state.current.root_hash(), root_hash_of(&kvs));
+    assert_eq!(
+        block_state.updates_since_base,
+        chunk_state.updates_since_base
+    );
+    assert_eq!(block_state.updates_since_base.len(), 2);
+}
+
+#[test]
+fn test_calculate_for_block_ending_with_checkpoint_txn() {
+    let kvs = vec![(key("a"), value("1"))];
+    let block = vec![user_txn(0, kvs.clone()), state_checkpoint_txn()];
+
+    let ChunkStateUpdateResult {
+        per_txn_state_checkpoint_hashes: block_hashes,
+        result_state: block_state,
+        ..
+    } = calculator(&[key("a")])
+        .calculate_for_block(&block, true)
+        .unwrap();
+    let ChunkStateUpdateResult {
+        per_txn_state_checkpoint_hashes: chunk_hashes,
+        result_state: chunk_state,
+        ..
+    } = calculator(&[key("a")])
+        .calculate_for_transaction_chunk(&block, false, None)
+        .unwrap();
+
+    // The checkpoint isn't made twice.
+    assert_eq!(block_hashes, vec![None, Some(root_hash_of(&kvs))]);
+    assert_eq!(block_hashes, chunk_hashes);
+    assert_eq!(block_state.base_version, chunk_state.base_version);
+    assert!(block_state.has_same_current_state(&chunk_state));
+}
+
+#[test]
+fn test_output_chunk_matches_transaction_chunk() {
+    let keys = vec![key("a"), key("b"), key("c")];
+    let chunk = vec![
+        user_txn(0, vec![(key("a"), value("1")), (key("b"), value("2"))]),
+        state_checkpoint_txn(),
+        user_txn(1, vec![(key("b"), StateValue::empty())]),
+        user_txn(2, vec![(key("c"), value("3"))]),
+        state_checkpoint_txn(),
+        user_txn(3, vec![(key("a"), value("4"))]),
+    ];
+
+    let ChunkStateUpdateResult {
+        per_txn_state_updates: txn_updates,
+        per_txn_state_update_stats: txn_stats,
+        per_txn_state_checkpoint_hashes: txn_hashes,
+        per_txn_state_checkpoint_usages: txn_usages,
+        result_state: txn_state,
+        ..
+    } = calculator(&keys)
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+
+    // Only the outputs, with infos marking the checkpoints as the ledger would.
+    let outputs: Vec<_> = chunk
+        .into_iter()
+        .zip(txn_hashes.iter())
+        .map(|((_, txn_output), state_checkpoint_hash)| {
+            let txn_info = TransactionInfo::new_placeholder(
+                0,
+                *state_checkpoint_hash,
+                ExecutionStatus::Success,
+            );
+            (txn_info, txn_output)
+        })
+        .collect();
+    let ChunkStateUpdateResult {
+        per_txn_state_updates: output_updates,
+        per_txn_state_update_stats: output_stats,
+        per_txn_state_checkpoint_hashes: output_hashes,
+        per_txn_state_checkpoint_usages: output_usages,
+        result_state: output_state,
+        ..
+    } = calculator(&keys)
+        .calculate_for_output_chunk(&outputs, false)
+        .unwrap();
+
+    assert_eq!(output_updates, txn_updates);
+    assert_eq!(output_stats, txn_stats);
+    assert_eq!(output_hashes, txn_hashes);
+    assert_eq!(output_usages, txn_usages);
+    assert_eq!(output_state.base_version, txn_state.base_version);
+    assert_eq!(output_state.base.root_hash(), txn_state.base.root_hash());
+    assert!(output_state.has_same_current_state(&txn_state));
+    assert_eq!(
+        output_state.updates_since_base,
+        txn_state.updates_since_base
+    );
+    assert_eq!(output_state.current_usage, txn_state.current_usage);
+}
+
+#[test]
+fn test_incremental_matches_chunk() {
+    let keys = vec![key("a"), key("b"), key("c")];
+    let writes = vec![
+        vec![(key("a"), value("1")), (key("b"), value("2"))],
+        vec![],
+        vec![(key("b"), StateValue::empty())],
+        vec![(key("c"), value("3"))],
+        vec![],
+        vec![(key("a"), value("4"))],
+    ];
+    let chunk: Vec<_> = writes
+        .iter()
+        .enumerate()
+        .map(|(i, writes)| {
+            if writes.is_empty() {
+                state_checkpoint_txn()
+            } else {
+                user_txn(i as u64, writes.clone())
+            }
+        })
+        .collect();
+
+    let mut incremental = calculator(&keys).into_incremental();
+    let mut written = HashMap::new();
+    for ((txn, txn_output), writes) in chunk.iter().zip(&writes) {
+        let state_checkpoint_hash = incremental.add_transaction(txn, txn_output).unwrap();
+        written.extend(writes.iter().cloned());
+        let expected: Vec<_> = written.clone().into_iter().collect();
+
+        // Peeking doesn't change what's peeked at next.
+        let root_hash = incremental.peek_latest_root_hash().unwrap();
+        assert_eq!(root_hash, root_hash_of(&expected));
+        assert_eq!(incremental.peek_latest_root_hash().unwrap(), root_hash);
+        assert_eq!(
+            incremental.snapshot().unwrap().current.root_hash(),
+            root_hash
+        );
+        if let Some(state_checkpoint_hash) = state_checkpoint_hash {
+            assert_eq!(state_checkpoint_hash, root_hash);
+        }
+    }
+    let ChunkStateUpdateResult {
+        per_txn_state_updates: inc_updates,
+        per_txn_state_update_stats: inc_stats,
+        per_txn_state_checkpoint_hashes: inc_hashes,
+        per_txn_state_checkpoint_usages: inc_usages,
+        result_state: inc_state,
+        next_epoch_state: inc_epoch_state,
+    } = incremental.finish().unwrap();
+
+    let ChunkStateUpdateResult {
+        per_txn_state_updates: updates,
+        per_txn_state_update_stats: stats,
+        per_txn_state_checkpoint_hashes: hashes,
+        per_txn_state_checkpoint_usages: usages,
+        result_state: state,
+        next_epoch_state: epoch_state,
+    } = calculator(&keys)
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+    assert_eq!(inc_updates, updates);
+    assert_eq!(inc_stats, stats);
+    assert_eq!(inc_hashes, hashes);
+    assert_eq!(inc_usages, usages);
+    assert_eq!(inc_state.base_version, state.base_version);
+    assert_eq!(inc_state.base.root_hash(), state.base.root_hash());
+    assert!(inc_state.has_same_current_state(&state));
+    assert_eq!(inc_state.updates_since_base, state.updates_since_base);
+    assert_eq!(inc_epoch_state, epoch_state);
+}
+
+#[test]
+fn test_smt_update_metrics() {
+    let keys = vec![key("a"), key("b")];
+    let chunk = vec![
+        user_txn(0, vec![(key("a"), value("1"))]),
+        state_checkpoint_txn(),
+        user_txn(1, vec![(key("b"), value("2"))]),
+    ];
+    let sample_count = |call_site| {
+        (
+            APTOS_EXECUTOR_SMT_UPDATE_SECONDS
+                .with_label_values(&[call_site])
+                .get_sample_count(),
+            APTOS_EXECUTOR_SMT_UPDATE_KEYS
+                .with_label_values(&[call_site])
+                .get_sample_count(),
+        )
+    };
+    // Other tests update the SMT too, so only count on the samples growing.
+    let (checkpoint_seconds, checkpoint_keys) = sample_count("make_checkpoint");
+    let (finish_seconds, finish_keys) = sample_count("finish");
+
+    calculator(&keys)
+        .with_slow_smt_update_threshold(Duration::from_secs(0))
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+
+    let (new_checkpoint_seconds, new_checkpoint_keys) = sample_count("make_checkpoint");
+    let (new_finish_seconds, new_finish_keys) = sample_count("finish");
+    assert!(new_checkpoint_seconds > checkpoint_seconds);
+    assert!(new_checkpoint_keys > checkpoint_keys);
+    assert!(new_finish_seconds > finish_seconds);
+    assert!(new_finish_keys > finish_keys);
+}
+
+#[test]
+fn test_new_node_counts() {
+    let base = state_of(&[(key("a"), value("0")), (key("b"), value("0"))], Some(0));
+    let keys = vec![key("a"), key("b"), key("c"), key("d")];
+    let chunk = vec![
+        user_txn(0, vec![(key("a"), value("1")), (key("c"), value("1"))]),
+        state_checkpoint_txn(),
+        user_txn(1, vec![(key("b"), value("2")), (key("d"), value("2"))]),
+    ];
+    let sample_count = |call_site| {
+        APTOS_EXECUTOR_SMT_NEW_NODES
+            .with_label_values(&[call_site, "node_hashes"])
+            .get_sample_count()
+    };
+    // Other tests update the SMT too, so only count on the samples growing.
+    let checkpoint_samples = sample_count("make_checkpoint");
+    let finish_samples = sample_count("finish");
+
+    let ChunkStateUpdateResult {
+        result_state,
+        new_node_counts,
+        ..
+    } = calculator_on(&base, &keys)
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+
+    assert!(sample_count("make_checkpoint") > checkpoint_samples);
+    assert!(sample_count("finish") > finish_samples);
+    // One for the checkpoint, and one for the transaction after it.
+    assert_eq!(new_node_counts.len(), 2);
+    let checkpoint = result_state.base.clone().freeze();
+    let latest = result_state.current.clone().freeze();
+    assert_eq!(
+        new_node_counts[0].num_node_hashes,
+        checkpoint
+            .new_node_hashes_since(&base.current.clone().freeze())
+            .len()
+    );
+    assert_eq!(
+        new_node_counts[1].num_node_hashes,
+        latest.new_node_hashes_since(&checkpoint).len()
+    );
+    assert_eq!(new_node_counts[0].num_leaves, 2);
+    assert_eq!(new_node_counts[1].num_leaves, 2);
+}
+
+#[test]
+fn test_chunk_state_update_result_checks_lengths() {
+    let new = |num_txns, num_updates, num_stats, num_hashes, num_usages| {
+        ChunkStateUpdateResult::new(
+            num_txns,
+            vec![HashMap::new(); num_updates],
+            vec![StateUpdateStats::default(); num_stats],
+            vec![None; num_hashes],
+            vec![None; num_usages],
+            vec![],
+            StateDelta::new_empty(),
+            None,
+        )
+    };
+
+    assert!(new(2, 2, 2, 2, 2).is_ok());
+    assert!(new(0, 0, 0, 0, 0).is_ok());
+    assert!(new(3, 2, 2, 2, 2).is_err());
+    assert!(new(2, 1, 2, 2, 2).is_err());
+    assert!(new(2, 2, 3, 2, 2).is_err());
+    assert!(new(2, 2, 2, 1, 2).is_err());
+    assert!(new(2, 2, 2, 2, 0).is_err());
+
+    // A checkpoint hash without a checkpoint root.
+    assert!(ChunkStateUpdateResult::new(
+        1,
+        vec![HashMap::new()],
+        vec![StateUpdateStats::default()],
+        vec![Some(HashValue::random())],
+        vec![None],
+        vec![],
+        StateDelta::new_empty(),
+        None,
+    )
+    .is_err());
+}
+
+#[test]
+fn test_checkpoint_roots() {
+    let base = state_of(&[(key("a"), value("0"))], Some(9));
+    let keys = vec![key("a"), key("b")];
+    let user = |i: u64| user_txn(i, vec![(key("b"), value(&format!("{}", i)))]);
+
+    for chunk in [
+        vec![user(0), user(1)],
+        vec![user(0), state_checkpoint_txn(), user(1)],
+        vec![
+            state_checkpoint_txn(),
+            user(0),
+            user(1),
+            state_checkpoint_txn(),
+            user(2),
+            state_checkpoint_txn(),
+        ],
+    ] {
+        let result = calculator_on(&base, &keys)
+            .calculate_for_transaction_chunk(&chunk, false, None)
+            .unwrap();
+
+        // The chunk starts at version 10.
+        let expected: Vec<_> = result
+            .per_txn_state_checkpoint_hashes
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, hash)| hash.map(|hash| (10 + idx as Version, hash)))
+            .collect();
+        assert_eq!(result.checkpoint_roots, expected);
+        if let Some((version, root_hash)) = result.checkpoint_roots.last() {
+            assert_eq!(result.result_state.base_version, Some(*version));
+            assert_eq!(result.result_state.base_root_hash(), *root_hash);
+        }
+    }
+}
+
+#[test]
+fn test_latest_root_hash_without_checkpoint() {
+    let keys = vec![key("a"), key("b")];
+    let chunk = vec![
+        user_txn(0, vec![(key("a"), value("1"))]),
+        state_checkpoint_txn(),
+        user_txn(1, vec![(key("b"), value("2"))]),
+    ];
+
+    let result = calculator(&keys)
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+    assert!(result.per_txn_state_checkpoint_hashes[2].is_none());
+
+    // The same as if the chunk had ended with a checkpoint.
+    let mut checkpointed_chunk = chunk;
+    checkpointed_chunk.push(state_checkpoint_txn());
+    let checkpointed = calculator(&keys)
+        .calculate_for_transaction_chunk(&checkpointed_chunk, false, None)
+        .unwrap();
+    assert_eq!(
+        Some(result.latest_root_hash()),
+        checkpointed.per_txn_state_checkpoint_hashes[3]
+    );
+    assert_eq!(
+        result.latest_root_hash(),
+        root_hash_of(&[(key("a"), value("1")), (key("b"), value("2"))])
+    );
+}
+
+#[test]
+fn test_new_at_version() {
+    let base = state_of(&[(key("a"), value("1"))], Some(9));
+    let state_cache = || StateCache {
+        frozen_base: base.current.clone().freeze(),
+        state_cache: HashMap::new(),
+        proofs: HashMap::new(),
+        stats: Default::default(),
+    };
+
+    assert!(InMemoryStateCalculator::new_at_version(&base, state_cache(), 10).is_ok());
+    for next_version in [9, 11, 1000] {
+        assert_eq!(
+            InMemoryStateCalculator::new_at_version(&base, state_cache(), next_version)
+                .err()
+                .unwrap()
+                .downcast::<Error>()
+                .unwrap(),
+            Error::VersionMismatch {
+                expected_next_version: 10,
+                next_version,
+            }
+        );
+    }
+}
+
+#[test]
+fn test_deterministic_mode_matches_parallel() {
+    let keys: Vec<_> = (0..100).map(|i| key(&format!("key_{}", i))).collect();
+    let mut chunk = Vec::new();
+    for (i, keys) in keys.chunks(10).enumerate() {
+        let writes = keys
+            .iter()
+            .map(|key| (key.clone(), value(&format!("{}", i))))
+            .collect();
+        chunk.push(user_txn(i as u64, writes));
+        if i % 3 == 0 {
+            chunk.push(state_checkpoint_txn());
+        }
+    }
+
+    let deterministic = calculator(&keys)
+        .with_deterministic(true, true)
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+    let parallel = calculator(&keys)
+        .with_deterministic(false, false)
+        .with_parallel_min_len(1)
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+
+    assert_eq!(
+        deterministic.per_txn_state_updates,
+        parallel.per_txn_state_updates
+    );
+    assert_eq!(
+        deterministic.per_txn_state_checkpoint_hashes,
+        parallel.per_txn_state_checkpoint_hashes
+    );
+    assert!(deterministic
+        .result_state
+        .has_same_current_state(&parallel.result_state));
+    assert_eq!(
+        deterministic.result_state.updates_since_base,
+        parallel.result_state.updates_since_base
+    );
+}
+
+#[test]
+fn test_missing_proof_error() {
+    let root_hash = HashValue::random();
+    let base = StateDelta::new_at_checkpoint(root_hash, Some(0));
+    // There are no proofs for the base state, which is all in the DB.
+    let chunk = vec![
+        user_txn(0, vec![(key("a"), value("1"))]),
+        state_checkpoint_txn(),
+    ];
+
+    let error = calculator_on(&base, &[key("a")])
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap_err();
+
+    assert_eq!(
+        error.downcast::<Error>().unwrap(),
+        Error::MissingProof {
+            key_hash: key("a").hash(),
+            state_key: Some(key("a")),
+            base_root_hash: root_hash,
+            num_proofs: 0,
+        }
+    );
+}
+
+#[test]
+fn test_expected_state_checkpoint_hashes() {
+    let keys: Vec<_> = (0..5).map(|i| key(&i.to_string())).collect();
+    // Each transaction writes a key, followed by a checkpoint.
+    let chunk: Vec<_> = keys
+        .iter()
+        .enumerate()
+        .flat_map(|(seq, k)| {
+            vec![
+                user_txn(seq as u64, vec![(k.clone(), value("v"))]),
+                state_checkpoint_txn(),
+            ]
+        })
+        .collect();
+    let ChunkStateUpdateResult {
+        per_txn_state_checkpoint_hashes: expected,
+        ..
+    } = calculator(&keys)
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+    assert_eq!(expected.iter().flatten().count(), 5);
+
+    // The same hashes are expected.
+    calculator(&keys)
+        .calculate_for_transaction_chunk(&chunk, false, Some(expected.clone()))
+        .unwrap();
+
+    // A checkpoint diverges mid-chunk.
+    let mut diverged = expected.clone();
+    let computed = diverged[3];
+    diverged[3] = Some(HashValue::random());
+    let error = calculator(&keys)
+        .calculate_for_transaction_chunk(&chunk, false, Some(diverged.clone()))
+        .unwrap_err();
+    assert_eq!(
+        error.downcast::<Error>().unwrap(),
+        Error::StateCheckpointHashMismatch {
+            version: 3,
+            expected: diverged[3],
+            computed,
+        }
+    );
+
+    // A checkpoint is expected where the chunk has none.
+    let mut diverged = expected.clone();
+    diverged[6] = Some(HashValue::random());
+    let error = calculator(&keys)
+        .calculate_for_transaction_chunk(&chunk, false, Some(diverged.clone()))
+        .unwrap_err();
+    assert_eq!(
+        error.downcast::<Error>().unwrap(),
+        Error::StateCheckpointHashMismatch {
+            version: 6,
+            expected: diverged[6],
+            computed: None,
+        }
+    );
+
+    // There must be an expectation for each transaction.
+    assert!(calculator(&keys)
+        .calculate_for_transaction_chunk(&chunk, false, Some(expected[1..].to_vec()))
+        .is_err());
+}
+
+#[test]
+fn test_proof_reader_verify_all_against() {
+    // A tree with a single leaf, which proves "a" in and "b" out.
+    let leaf = SparseMerkleLeafNode::new(key("a").hash(), value("1").hash());
+    let proof = SparseMerkleProof::new(Some(leaf), vec![]);
+    let proof_reader = ProofReader::new(
+        vec![(key("a").hash(), proof.clone()), (key("b").hash(), proof)]
+            .into_iter()
+            .collect(),
+    );
+
+    proof_reader.verify_all_against(leaf.hash()).unwrap();
+    assert!(proof_reader
+        .verify_all_against(*SPARSE_MERKLE_PLACEHOLDER_HASH)
+        .is_err());
+}
+
+#[test]
+fn test_large_values_are_shared_not_copied() {
+    // A few MBs worth of values, written once each and then overwritten by a later transaction.
+    let kvs: Vec<_> = (0..8u8)
+        .map(|i| {
+            (
+                key(&format!("large_{}", i)),
+                StateValue::from(vec![i; 512 * 1024]),
+            )
+        })
+        .collect();
+    let keys: Vec<_> = kvs.iter().map(|(key, _)| key.clone()).collect();
+    let overwritten = (key("large_0"), StateValue::from(vec![u8::MAX; 512 * 1024]));
+    let chunk = vec![
+        user_txn(0, kvs[..4].to_vec()),
+        user_txn(1, kvs[4..].to_vec()),
+        user_txn(2, vec![overwritten.clone()]),
+    ];
+
+    let ChunkStateUpdateResult {
+        per_txn_state_updates: state_updates_vec,
+        result_state,
+        ..
+    } = calculator(&keys)
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+
+    // Every value handed out for persistence is the very allocation the resulting state
+    // tracks, unless a later transaction in the chunk overwrote it.
+    let mut latest: HashMap<&StateKey, &Option<Arc<StateValue>>> = HashMap::new();
+    for state_updates in &state_updates_vec {
+        latest.extend(state_updates.iter());
+    }
+    assert_eq!(latest.len(), result_state.updates_since_base.len());
+    for (key, value) in &result_state.updates_since_base {
+        assert!(Arc::ptr_eq(
+            latest[key].as_ref().unwrap(),
+            value.as_ref().unwrap()
+        ));
+    }
+    assert_eq!(
+        result_state.updates_since_base[&overwritten.0].as_deref(),
+        Some(&overwritten.1)
+    );
+
+    let mut expected: HashMap<_, _> = kvs.into_iter().collect();
+    expected.insert(overwritten.0, overwritten.1);
+    let expected: Vec<_> = expected.into_iter().collect();
+    assert_eq!(result_state.current.root_hash(), root_hash_of(&expected));
+}
+
+#[test]
+fn test_chunks_chained_across_checkpoint() {
+    let keys = vec![key("a"), key("b")];
+    let first = vec![
+        user_txn(0, vec![(key("a"), value("1")), (key("b"), value("2"))]),
+        state_checkpoint_txn(),
+    ];
+    let second = vec![
+        user_txn(1, vec![(key("a"), value("3"))]),
+        state_checkpoint_txn(),
+        user_txn(2, vec![(key("b"), StateValue::empty())]),
+    ];
+
+    let ChunkStateUpdateResult {
+        per_txn_state_checkpoint_hashes: first_hashes,
+        result_state: first_state,
+        ..
+    } = calculator(&keys)
+        .calculate_for_transaction_chunk(&first, false, None)
+        .unwrap();
+    // Ending on a checkpoint, nothing is pending on top of it.
+    assert_eq!(first_state.base_version, Some(1));
+    assert_eq!(first_state.current_version, Some(1));
+    assert!(first_state.updates_since_base.is_empty());
+    assert_eq!(
+        first_state.current.root_hash(),
+        first_state.base.root_hash()
+    );
+    assert_eq!(first_hashes[1], Some(first_state.current.root_hash()));
+
+    let ChunkStateUpdateResult {
+        per_txn_state_checkpoint_hashes: second_hashes,
+        result_state: second_state,
+        ..
+    } = calculator_on(&first_state, &keys)
+        .calculate_for_transaction_chunk(&second, false, None)
+        .unwrap();
+
+    // The same as if both chunks were one.
+    let both: Vec<_> = first.into_iter().chain(second).collect();
+    let ChunkStateUpdateResult {
+        per_txn_state_checkpoint_hashes: both_hashes,
+        result_state: both_state,
+        ..
+    } = calculator(&keys)
+        .calculate_for_transaction_chunk(&both, false, None)
+        .unwrap();
+    assert_eq!(
+        first_hashes
+            .into_iter()
+            .chain(second_hashes)
+            .collect::<Vec<_>>(),
+        both_hashes
+    );
+    assert_eq!(second_state.base_version, both_state.base_version);
+    assert_eq!(second_state.current_version, both_state.current_version);
+    assert_eq!(second_state.base.root_hash(), both_state.base.root_hash());
+    assert_eq!(
+        second_state.current.root_hash(),
+        both_state.current.root_hash()
+    );
+    assert_eq!(
+        second_state.updates_since_base,
+        both_state.updates_since_base
+    );
+}
+
+#[test]
+fn test_sibling_calculators_on_branched_base() {
+    let keys = vec![key("a"), key("b"), key("c")];
+    let parent = || {
+        vec![
+            user_txn(0, vec![(key("a"), value("1")), (key("b"), value("1"))]),
+            state_checkpoint_txn(),
+            user_txn(1, vec![(key("b"), value("2"))]),
+        ]
+    };
+    // One sibling ends on a checkpoint, as blocks do, the other one doesn't.
+    let left = vec![
+        user_txn(2, vec![(key("a"), value("3")), (key("c"), value("3"))]),
+        state_checkpoint_txn(),
+    ];
+    let right = vec![user_txn(2, vec![(key("b"), StateValue::empty())])];
+
+    let base = calculator(&keys)
+        .calculate_for_transaction_chunk(&parent(), false, None)
+        .unwrap()
+        .result_state;
+    assert_eq!(base.updates_since_base.len(), 1);
+    let base_updates = base.updates_since_base.clone();
+
+    // Branches share the updates until they change them.
+    let branch = base.branch();
+    assert!(Arc::ptr_eq(
+        &branch.updates_since_base,
+        &base.updates_since_base
+    ));
+    let mut changed = base.branch();
+    changed.updates_since_base_mut().insert(key("c"), None);
+    assert!(!Arc::ptr_eq(
+        &changed.updates_since_base,
+        &base.updates_since_base
+    ));
+    assert_eq!(base.updates_since_base, base_updates);
+
+    let left_state = calculator_on(&base, &keys)
+        .calculate_for_transaction_chunk(&left, false, None)
+        .unwrap()
+        .result_state;
+    let right_state = calculator_on(&base, &keys)
+        .calculate_for_transaction_chunk(&right, false, None)
+        .unwrap()
+        .result_state;
+
+    // Neither sibling leaks into the base or into the other one.
+    assert!(Arc::ptr_eq(&base.updates_since_base, &base_updates));
+    assert_eq!(*base_updates, *branch.updates_since_base);
+    for (sibling, sibling_state) in [(left, left_state), (right, right_state)] {
+        let txns: Vec<_> = parent().into_iter().chain(sibling).collect();
+        let expected_state = calculator(&keys)
+            .calculate_for_transaction_chunk(&txns, false, None)
+            .unwrap()
+            .result_state;
+        assert_eq!(sibling_state.base_version, expected_state.base_version);
+        assert_eq!(
+            sibling_state.base.root_hash(),
+            expected_state.base.root_hash()
+        );
+        assert!(sibling_state.has_same_current_state(&expected_state));
+        assert_eq!(
+            sibling_state.updates_since_base,
+            expected_state.updates_since_base
+        );
+        assert_eq!(sibling_state.current_usage, expected_state.current_usage);
+    }
+}
+
+#[test]
+fn test_delete_and_recreate_in_chunk() {
+    let keys = vec![key("a"), key("b")];
+    let chunk = vec![
+        user_txn(0, vec![(key("a"), value("1")), (key("b"), value("2"))]),
+        user_txn(1, vec![(key("a"), StateValue::empty())]),
+        user_txn(2, vec![(key("a"), value("3"))]),
+        user_txn(3, vec![(key("b"), StateValue::empty())]),
+    ];
+
+    let ChunkStateUpdateResult {
+        per_txn_state_updates: state_updates_vec,
+        result_state,
+        ..
+    } = calculator(&keys)
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+
+    assert_eq!(state_updates_vec[1][&key("a")], None);
+    assert_eq!(
+        state_updates_vec[2][&key("a")].as_deref(),
+        Some(&value("3"))
+    );
+    assert_eq!(state_updates_vec[3][&key("b")], None);
+    assert_eq!(
+        result_state.updates_since_base[&key("a")].as_deref(),
+        Some(&value("3"))
+    );
+    assert_eq!(result_state.updates_since_base[&key("b")], None);
+    assert_eq!(
+        result_state.current.root_hash(),
+        root_hash_of(&[(key("a"), value("3")), (key("b"), StateValue::empty())])
+    );
+}
+
+#[test]
+fn test_delete_key_only_in_base() {
+    let base_kvs = vec![(key("a"), value("1")), (key("b"), value("2"))];
+    let base = state_of(&base_kvs, Some(0));
+    let chunk = vec![
+        user_txn(0, vec![(key("a"), StateValue::empty())]),
+        state_checkpoint_txn(),
+    ];
+
+    let ChunkStateUpdateResult {
+        per_txn_state_updates: state_updates_vec,
+        per_txn_state_checkpoint_hashes: state_checkpoint_hashes,
+        per_txn_state_checkpoint_usages: state_checkpoint_usages,
+        result_state,
+        ..
+    } = calculator_on(&base, &[key("a")])
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+
+    let expected_root_hash = root_hash_of(&[(key("a"), StateValue::empty()), base_kvs[1].clone()]);
+    assert_ne!(expected_root_hash, base.current.root_hash());
+    assert_eq!(state_updates_vec[0][&key("a")], None);
+    assert_eq!(
+        state_checkpoint_hashes,
+        vec![None, Some(expected_root_hash)]
+    );
+    assert_eq!(result_state.base_version, Some(2));
+    assert_eq!(result_state.current.root_hash(), expected_root_hash);
+
+    let expected_usage = usage_of(vec![(&base_kvs[1].0, &base_kvs[1].1)]);
+    assert_eq!(state_checkpoint_usages, vec![None, Some(expected_usage)]);
+    assert_eq!(result_state.base_usage, expected_usage);
+    assert_eq!(result_state.current_usage, expected_usage);
+}
+
+#[test]
+fn test_usage_stays_untracked() {
+    let mut base = state_of(&[(key("a"), value("1"))], Some(0));
+    base.base_usage = StateStorageUsage::new_untracked();
+    base.current_usage = StateStorageUsage::new_untracked();
+    let chunk = vec![
+        user_txn(0, vec![(key("a"), StateValue::empty())]),
+        state_checkpoint_txn(),
+    ];
+
+    let ChunkStateUpdateResult {
+        per_txn_state_checkpoint_usages: state_checkpoint_usages,
+        result_state,
+        ..
+    } = calculator_on(&base, &[key("a")])
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+
+    assert_eq!(
+        state_checkpoint_usages,
+        vec![None, Some(StateStorageUsage::new_untracked())]
+    );
+    assert!(result_state.current_usage.is_untracked());
+}
+
+#[test]
+fn test_state_update_stats() {
+    let base = state_of(&[(key("a"), value("1"))], Some(0));
+    let chunk = vec![
+        user_txn(0, vec![(key("a"), value("22")), (key("b"), value("333"))]),
+        // "b" was created by the previous transaction, so this modifies it.
+        user_txn(
+            1,
+            vec![(key("a"), StateValue::empty()), (key("b"), value("4444"))],
+        ),
+        // "a" was deleted by the previous transaction, so this creates it again.
+        user_txn(2, vec![(key("a"), value("55555"))]),
+        state_checkpoint_txn(),
+    ];
+
+    let ChunkStateUpdateResult {
+        per_txn_state_update_stats: state_update_stats,
+        ..
+    } = calculator_on(&base, &[key("a"), key("b")])
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+
+    let key_size = key("a").encode().unwrap().len();
+    assert_eq!(
+        state_update_stats,
+        vec![
+            StateUpdateStats {
+                keys_written: 2,
+                keys_created: 1,
+                keys_modified: 1,
+                keys_deleted: 0,
+                bytes_written: 5,
+                new_key_bytes: key_size + 3,
+                overwritten_bytes_delta: 1,
+                freed_bytes: 0,
+            },
+            StateUpdateStats {
+                keys_written: 2,
+                keys_created: 0,
+                keys_modified: 1,
+                keys_deleted: 1,
+                bytes_written: 4,
+                new_key_bytes: 0,
+                overwritten_bytes_delta: 1,
+                freed_bytes: key_size + 2,
+            },
+            StateUpdateStats {
+                keys_written: 1,
+                keys_created: 1,
+                keys_modified: 0,
+                keys_deleted: 0,
+                bytes_written: 5,
+                new_key_bytes: key_size + 5,
+                overwritten_bytes_delta: 0,
+                freed_bytes: 0,
+            },
+            StateUpdateStats::default(),
+        ]
+    );
+}
+
+#[test]
+fn test_conflict_report() {
+    let chunk = vec![
+        user_txn(0, vec![(key("a"), value("1")), (key("b"), value("1"))]),
+        user_txn(1, vec![(key("b"), value("2")), (key("c"), value("1"))]),
+        user_txn(2, vec![(key("d"), value("1"))]),
+        // Deleting counts as writing.
+        user_txn(3, vec![(key("a"), StateValue::empty())]),
+        user_txn(4, vec![(key("b"), value("3"))]),
+        state_checkpoint_txn(),
+    ];
+    let keys = [key("a"), key("b"), key("c"), key("d")];
+
+    let ChunkStateUpdateResult {
+        conflict_report, ..
+    } = calculator(&keys)
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+    assert_eq!(conflict_report, None);
+
+    let ChunkStateUpdateResult {
+        conflict_report, ..
+    } = calculator(&keys)
+        .with_conflict_analysis(true)
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+    let report = conflict_report.unwrap();
+    assert_eq!(
+        report,
+        ConflictReport {
+            num_txns: 6,
+            writers: vec![(key("a"), vec![0, 3]), (key("b"), vec![0, 1, 4])]
+                .into_iter()
+                .collect(),
+        }
+    );
+    assert_eq!(report.num_conflicting_keys(), 2);
+    assert_eq!(report.max_writers_per_key(), 3);
+    assert_eq!(report.num_conflicting_txns(), 4);
+    assert_eq!(report.conflicting_txns_percentage(), 100.0 * 4.0 / 6.0);
+
+    // The same for a block, and nothing conflicts in a chunk of one transaction.
+    let ChunkStateUpdateResult {
+        conflict_report, ..
+    } = calculator(&keys)
+        .with_conflict_analysis(true)
+        .calculate_for_block(&chunk, false)
+        .unwrap();
+    assert_eq!(conflict_report, Some(report));
+    let ChunkStateUpdateResult {
+        conflict_report, ..
+    } = calculator(&keys)
+        .with_conflict_analysis(true)
+        .calculate_for_transaction_chunk(&chunk[..1], false, None)
+        .unwrap();
+    let report = conflict_report.unwrap();
+    assert_eq!(report.num_conflicting_keys(), 0);
+    assert_eq!(report.max_writers_per_key(), 0);
+    assert_eq!(report.conflicting_txns_percentage(), 0.0);
+}
+
+#[test]
+fn test_fast_replay() {
+    let base = state_of(&[(key("a"), value("1"))], Some(0));
+    let chunk = vec![
+        user_txn(0, vec![(key("a"), value("2")), (key("b"), value("1"))]),
+        user_txn(1, vec![(key("b"), value("2"))]),
+        state_checkpoint_txn(),
+        user_txn(2, vec![(key("a"), StateValue::empty())]),
+        state_checkpoint_txn(),
+        user_txn(3, vec![(key("c"), value("1"))]),
+        user_txn(4, vec![(key("c"), value("2")), (key("b"), value("3"))]),
+    ];
+    let keys = [key("a"), key("b"), key("c")];
+
+    let ChunkStateUpdateResult {
+        per_txn_state_updates,
+        per_txn_state_checkpoint_hashes,
+        per_txn_state_checkpoint_usages,
+        result_state,
+        ..
+    } = calculator_on(&base, &keys)
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+    assert!(per_txn_state_updates
+        .iter()
+        .zip(&chunk)
+        .all(|(updates, (_, output))| updates.len() == output.write_set().iter().count()));
+
+    // Small pending updates, for some to be applied to the latest SMT between checkpoints.
+    let ChunkStateUpdateResult {
+        per_txn_state_updates: fast_per_txn_state_updates,
+        per_txn_state_checkpoint_hashes: fast_per_txn_state_checkpoint_hashes,
+        per_txn_state_checkpoint_usages: fast_per_txn_state_checkpoint_usages,
+        result_state: fast_result_state,
+        ..
+    } = calculator_on(&base, &keys)
+        .with_fast_replay(true)
+        .with_max_pending_updates(1, usize::MAX)
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+    assert_eq!(
+        fast_per_txn_state_checkpoint_hashes,
+        per_txn_state_checkpoint_hashes
+    );
+    assert_eq!(
+        fast_per_txn_state_checkpoint_usages,
+        per_txn_state_checkpoint_usages
+    );
+    assert_eq!(fast_result_state.root_hash(), result_state.root_hash());
+    assert_eq!(
+        fast_result_state.updates_since_base,
+        result_state.updates_since_base
+    );
+
+    let updates = |kvs: Vec<(StateKey, Option<StateValue>)>| -> HashMap<_, _> {
+        kvs.into_iter()
+            .map(|(key, value)| (key, value.map(Arc::new)))
+            .collect()
+    };
+    assert_eq!(
+        fast_per_txn_state_updates,
+        vec![
+            HashMap::new(),
+            HashMap::new(),
+            updates(vec![
+                (key("a"), Some(value("2"))),
+                (key("b"), Some(value("2"))),
+            ]),
+            HashMap::new(),
+            updates(vec![(key("a"), None)]),
+            HashMap::new(),
+            updates(vec![
+                (key("b"), Some(value("3"))),
+                (key("c"), Some(value("2"))),
+            ]),
+        ]
+    );
+
+    // Blocks are always calculated transaction by transaction.
+    assert!(calculator_on(&base, &keys)
+        .with_fast_replay(true)
+        .calculate_for_block(&chunk, true)
+        .is_err());
+}
+
+#[test]
+fn test_max_retained_nodes() {
+    let keys: Vec<_> = (0..100).map(|i| key(&format!("key_{}", i))).collect();
+    let chunk: Vec<_> = keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| user_txn(i as u64, vec![(key.clone(), value("1"))]))
+        .collect();
+    // Each calculator gets a base of its own, so the nodes retained on top of each base are its
+    // own only. The base is kept alive, as the caller's would be.
+    let calculate = |max_retained_nodes| {
+        let base = StateDelta::new_empty();
+        let result = calculator_on(&base, &keys)
+            .with_max_pending_updates(1, usize::MAX)
+            .with_max_retained_nodes(max_retained_nodes)
+            .calculate_for_transaction_chunk(&chunk, false, None)
+            .unwrap();
+        let num_retained_nodes = result
+            .result_state
+            .current
+            .clone()
+            .freeze()
+            .num_retained_nodes();
+        (base, result, num_retained_nodes)
+    };
+
+    let (_base, unbounded, unbounded_retained_nodes) = calculate(usize::MAX);
+    let (_bounded_base, bounded, bounded_retained_nodes) = calculate(10);
+    assert_eq!(
+        bounded.per_txn_state_updates,
+        unbounded.per_txn_state_updates
+    );
+    assert_eq!(
+        bounded.per_txn_state_checkpoint_hashes,
+        unbounded.per_txn_state_checkpoint_hashes
+    );
+    assert_eq!(
+        bounded.result_state.root_hash(),
+        unbounded.result_state.root_hash()
+    );
+    assert_eq!(
+        bounded.result_state.updates_since_base,
+        unbounded.result_state.updates_since_base
+    );
+    // Past the limit, the rest of the keys are applied in one update at the end rather than one
+    // at a time.
+    assert!(bounded_retained_nodes < unbounded_retained_nodes / 2);
+}
+
+fn arb_value() -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..16)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(20))]
+
+    #[test]
+    fn test_usage_matches_recount(
+        base_kvs in btree_map(0..6u8, arb_value(), 0..6),
+        // A small key space, so keys get created, modified, deleted and re-created within the
+        // chunk. `None` is a deletion.
+        txns_writes in vec(btree_map(0..6u8, option::of(arb_value()), 1..4), 1..12),
+        checkpoint_interval in 1..5usize,
+    ) {
+        let key_of = |k: &u8| key(&format!("key_{}", k));
+        let all_keys: Vec<_> = (0..6u8).map(|k| key_of(&k)).collect();
+        let base_kvs: Vec<_> = base_kvs
+            .iter()
+            .map(|(k, v)| (key_of(k), StateValue::from(v.clone())))
+            .collect();
+        let base = state_of(&base_kvs, Some(0));
+
+        // The state after each transaction, and whether it's a checkpoint.
+        let mut state: BTreeMap<_, _> = base_kvs.iter().cloned().collect();
+        let mut chunk = Vec::new();
+        let mut expected_usages = Vec::new();
+        for (i, writes) in txns_writes.iter().enumerate() {
+            let writes: Vec<_> = writes
+                .iter()
+                .map(|(k, v)| {
+                    let value = v.clone().map_or_else(StateValue::empty, StateValue::from);
+                    (key_of(k), value)
+                })
+                .collect();
+            for (key, value) in &writes {
+                match value.maybe_bytes {
+                    Some(_) => state.insert(key.clone(), value.clone()),
+                    None => state.remove(key),
+                };
+            }
+            chunk.push(user_txn(i as u64, writes));
+            expected_usages.push(None);
+            if (i + 1) % checkpoint_interval == 0 {
+                chunk.push(state_checkpoint_txn());
+                expected_usages.push(Some(usage_of(&state)));
+            }
+        }
+
+        let ChunkStateUpdateResult {
+            per_txn_state_update_stats: state_update_stats,
+            per_txn_state_checkpoint_usages: state_checkpoint_usages,
+            result_state,
+            ..
+        } = calculator_on(&base, &all_keys)
+            .calculate_for_transaction_chunk(&chunk, false, None)
+            .unwrap();
+
+        prop_assert_eq!(&state_checkpoint_usages, &expected_usages);
+        prop_assert_eq!(result_state.current_usage, usage_of(&state));
+        // The bytes each transaction created, overwrote and freed add up to the change in usage.
+        let bytes_of = |usage| match usage {
+            StateStorageUsage::Tracked { bytes, .. } => bytes as i64,
+            StateStorageUsage::Untracked => unreachable!(),
+        };
+        let bytes_delta: i64 = state_update_stats
+            .iter()
+            .map(|stats| {
+                stats.new_key_bytes as i64 + stats.overwritten_bytes_delta
+                    - stats.freed_bytes as i64
+            })
+            .sum();
+        prop_assert_eq!(
+            bytes_delta,
+            bytes_of(result_state.current_usage) - bytes_of(base.current_usage)
+        );
+        let last_checkpoint_usage = expected_usages
+            .iter()
+            .rev()
+            .find_map(|usage| *usage)
+            .unwrap_or(base.current_usage);
+        prop_assert_eq!(result_state.base_usage, last_checkpoint_usage);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(10))]
+
+    #[test]
+    fn test_parallel_smt_updates_match_sequential(
+        txns_writes in vec(btree_map(0..200u16, option::of(arb_value()), 1..50), 1..8),
+        checkpoint_interval in 1..4usize,
+    ) {
+        let key_of = |k: &u16| key(&format!("key_{}", k));
+        let all_keys: Vec<_> = (0..200u16).map(|k| key_of(&k)).collect();
+        let mut chunk = Vec::new();
+        let mut state = BTreeMap::new();
+        for (i, writes) in txns_writes.iter().enumerate() {
+            let writes: Vec<_> = writes
+                .iter()
+                .map(|(k, v)| {
+                    let value = v.clone().map_or_else(StateValue::empty, StateValue::from);
+                    (key_of(k), value)
+                })
+                .collect();
+            state.extend(writes.iter().cloned());
+            chunk.push(user_txn(i as u64, writes));
+            if (i + 1) % checkpoint_interval == 0 {
+                chunk.push(state_checkpoint_txn());
+            }
+        }
+
+        let ChunkStateUpdateResult {
+            per_txn_state_updates: parallel_updates,
+            per_txn_state_checkpoint_hashes: parallel_hashes,
+            result_state: parallel_state,
+            ..
+        } = calculator(&all_keys)
+            .with_parallel_min_len(1)
+            .calculate_for_transaction_chunk(&chunk, false, None)
+            .unwrap();
+        let ChunkStateUpdateResult {
+            per_txn_state_updates: sequential_updates,
+            per_txn_state_checkpoint_hashes: sequential_hashes,
+            result_state: sequential_state,
+            ..
+        } = calculator(&all_keys)
+            .with_parallel_min_len(usize::MAX)
+            .calculate_for_transaction_chunk(&chunk, false, None)
+            .unwrap();
+
+        prop_assert_eq!(parallel_updates, sequential_updates);
+        prop_assert_eq!(parallel_hashes, sequential_hashes);
+        prop_assert!(parallel_state.has_same_current_state(&sequential_state));
+        prop_assert_eq!(
+            parallel_state.base_root_hash(),
+            sequential_state.base_root_hash()
+        );
+        let state: Vec<_> = state.into_iter().collect();
+        prop_assert_eq!(parallel_state.current.root_hash(), root_hash_of(&state));
+    }
+
+    #[test]
+    fn test_sharded_smt_updates_match_unsharded(
+        txns_writes in vec(btree_map(0..5000u16, option::of(arb_value()), 1..1000), 1..4),
+        checkpoint_interval in 1..3usize,
+    ) {
+        let key_of = |k: &u16| key(&format!("key_{}", k));
+        let all_keys: Vec<_> = (0..5000u16).map(|k| key_of(&k)).collect();
+        let mut chunk = Vec::new();
+        for (i, writes) in txns_writes.iter().enumerate() {
+            let writes: Vec<_> = writes
+                .iter()
+                .map(|(k, v)| {
+                    let value = v.clone().map_or_else(StateValue::empty, StateValue::from);
+                    (key_of(k), value)
+                })
+                .collect();
+            chunk.push(user_txn(i as u64, writes));
+            if (i + 1) % checkpoint_interval == 0 {
+                chunk.push(state_checkpoint_txn());
+            }
+        }
+
+        let ChunkStateUpdateResult {
+            per_txn_state_updates: sharded_updates,
+            per_txn_state_checkpoint_hashes: sharded_hashes,
+            result_state: sharded_state,
+            ..
+        } = calculator(&all_keys)
+            .with_sharded_smt_updates(true)
+            .calculate_for_transaction_chunk(&chunk, false, None)
+            .unwrap();
+        let ChunkStateUpdateResult {
+            per_txn_state_updates: updates,
+            per_txn_state_checkpoint_hashes: hashes,
+            result_state: state,
+            ..
+        } = calculator(&all_keys)
+            .calculate_for_transaction_chunk(&chunk, false, None)
+            .unwrap();
+
+        prop_assert_eq!(sharded_updates, updates);
+        prop_assert_eq!(sharded_hashes, hashes);
+        prop_assert_eq!(sharded_state.base_root_hash(), state.base_root_hash());
+        prop_assert!(sharded_state.has_same_current_state(&state));
+        prop_assert_eq!(sharded_state.updates_since_base, state.updates_since_base);
+    }
+
+    #[test]
+    fn test_max_pending_updates_doesnt_change_result(
+        txns_writes in vec(btree_map(0..50u16, option::of(arb_value()), 1..20), 1..8),
+        checkpoint_interval in 1..4usize,
+        max_pending_keys in 1..30usize,
+        max_pending_bytes in 1..200usize,
+    ) {
+        let key_of = |k: &u16| key(&format!("key_{}", k));
+        let all_keys: Vec<_> = (0..50u16).map(|k| key_of(&k)).collect();
+        let mut chunk = Vec::new();
+        for (i, writes) in txns_writes.iter().enumerate() {
+            let writes: Vec<_> = writes
+                .iter()
+                .map(|(k, v)| {
+                    let value = v.clone().map_or_else(StateValue::empty, StateValue::from);
+                    (key_of(k), value)
+                })
+                .collect();
+            chunk.push(user_txn(i as u64, writes));
+            if (i + 1) % checkpoint_interval == 0 {
+                chunk.push(state_checkpoint_txn());
+            }
+        }
+
+        let ChunkStateUpdateResult {
+            per_txn_state_updates: bounded_updates,
+            per_txn_state_checkpoint_hashes: bounded_hashes,
+            per_txn_state_checkpoint_usages: bounded_usages,
+            result_state: bounded_state,
+            ..
+        } = calculator(&all_keys)
+            .with_max_pending_updates(max_pending_keys, max_pending_bytes)
+            .calculate_for_transaction_chunk(&chunk, false, None)
+            .unwrap();
+        let ChunkStateUpdateResult {
+            per_txn_state_updates: updates,
+            per_txn_state_checkpoint_hashes: hashes,
+            per_txn_state_checkpoint_usages: usages,
+            result_state: state,
+            ..
+        } = calculator(&all_keys)
+            .calculate_for_transaction_chunk(&chunk, false, None)
+            .unwrap();
+
+        // No checkpoints are added, and the updates applied in smaller batches add up to the
+        // same updates since the last checkpoint.
+        prop_assert_eq!(bounded_updates, updates);
+        prop_assert_eq!(bounded_hashes, hashes);
+        prop_assert_eq!(bounded_usages, usages);
+        prop_assert_eq!(bounded_state.base_version, state.base_version);
+        prop_assert_eq!(bounded_state.base_root_hash(), state.base_root_hash());
+        prop_assert!(bounded_state.has_same_current_state(&state));
+        prop_assert_eq!(bounded_state.updates_since_base, state.updates_since_base);
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(20))]
+
+    #[test]
+    fn test_parallel_write_sets_match_serial(
+        base_kvs in btree_map(0..8u8, arb_value(), 0..8),
+        // A small key space, so most transactions write keys others write too. `None` is a
+        // deletion.
+        txns_writes in vec(btree_map(0..8u8, option::of(arb_value()), 1..6), 1..40),
+        checkpoint_interval in 1..8usize,
+        read_all_keys in any::<bool>(),
+    ) {
+        let key_of = |k: &u8| key(&format!("key_{}", k));
+        let base_kvs: Vec<_> = base_kvs
+            .iter()
+            .map(|(k, v)| (key_of(k), StateValue::from(v.clone())))
+            .collect();
+        let base = state_of(&base_kvs, Some(0));
+        // If a key wasn't read, writing it fails the chunk, at the same transaction either way.
+        let read_keys: Vec<_> = (0..8u8)
+            .filter(|k| read_all_keys || *k != 0)
+            .map(|k| key_of(&k))
+            .collect();
+
+        let mut chunk = Vec::new();
+        for (i, writes) in txns_writes.iter().enumerate() {
+            let writes = writes
+                .iter()
+                .map(|(k, v)| {
+                    let value = v.clone().map_or_else(StateValue::empty, StateValue::from);
+                    (key_of(k), value)
+                })
+                .collect();
+            chunk.push(user_txn(i as u64, writes));
+            if (i + 1) % checkpoint_interval == 0 {
+                chunk.push(state_checkpoint_txn());
+            }
+        }
+
+        let calculate = |min_txns| {
+            calculator_on(&base, &read_keys)
+                .with_parallel_write_sets_min_txns(min_txns)
+                .calculate_for_transaction_chunk(&chunk, false, None)
+                .map(|result| {
+                    let ChunkStateUpdateResult {
+                        per_txn_state_updates,
+                        per_txn_state_update_stats,
+                        per_txn_state_checkpoint_hashes,
+                        per_txn_state_checkpoint_usages,
+                        checkpoint_roots,
+                        result_state,
+                        ..
+                    } = result;
+                    (
+                        per_txn_state_updates,
+                        per_txn_state_update_stats,
+                        per_txn_state_checkpoint_hashes,
+                        per_txn_state_checkpoint_usages,
+                        checkpoint_roots,
+                        result_state.current.root_hash(),
+                        result_state.current_usage,
+                    )
+                })
+                .map_err(|error| error.to_string())
+        };
+        prop_assert_eq!(calculate(0), calculate(usize::MAX));
+    }
+}
+
+fn validator_set_key() -> StateKey {
+    StateKey::AccessPath(access_path_for_config(ValidatorSet::CONFIG_ID))
+}
+
+fn configuration_key() -> StateKey {
+    StateKey::AccessPath(AccessPath::new(
+        CORE_CODE_ADDRESS,
+        ConfigurationResource::struct_tag().access_vector(),
+    ))
+}
+
+fn configuration(epoch: u64) -> StateValue {
+    let mut configuration = ConfigurationResource::default();
+    for _ in 0..epoch {
+        configuration = configuration.bump_epoch_for_test();
+    }
+    StateValue::from(bcs::to_bytes(&configuration).unwrap())
+}
+
+fn validator_set() -> StateValue {
+    StateValue::from(bcs::to_bytes(&ValidatorSet::new(vec![])).unwrap())
+}
+
+#[test]
+fn test_epoch_state_from_rewritten_resources() {
+    let base = state_of(
+        &[
+            (validator_set_key(), validator_set()),
+            (configuration_key(), configuration(1)),
+        ],
+        Some(0),
+    );
+    let chunk = vec![reconfig_txn(
+        0,
+        vec![
+            (validator_set_key(), validator_set()),
+            (configuration_key(), configuration(2)),
+        ],
+    )];
+
+    let ChunkStateUpdateResult {
+        next_epoch_state, ..
+    } = calculator_on(&base, &[validator_set_key(), configuration_key()])
+        .calculate_for_transaction_chunk(&chunk, true, None)
+        .unwrap();
+
+    // The values written win over the base ones.
+    assert_eq!(next_epoch_state.unwrap().epoch, 2);
+}
+
+#[test]
+fn test_parallel_write_sets_with_reconfig() {
+    let base = state_of(
+        &[
+            (validator_set_key(), validator_set()),
+            (configuration_key(), configuration(1)),
+        ],
+        Some(0),
+    );
+    let keys = [validator_set_key(), configuration_key()];
+    let epoch_of = |chunk: &[(Transaction, ParsedTransactionOutput)]| {
+        let ChunkStateUpdateResult {
+            next_epoch_state, ..
+        } = calculator_on(&base, &keys)
+            .with_parallel_write_sets_min_txns(0)
+            .calculate_for_transaction_chunk(chunk, true, None)
+            .unwrap();
+        next_epoch_state.unwrap().epoch
+    };
+
+    // The reconfig is last, so its epoch state is parsed after all the writes either way.
+    let chunk = vec![
+        user_txn(0, vec![(configuration_key(), configuration(2))]),
+        reconfig_txn(1, vec![(configuration_key(), configuration(3))]),
+    ];
+    assert_eq!(epoch_of(&chunk), 3);
+
+    // A later transaction rewrites the configuration, which the epoch state parsed at the reconfig
+    // mustn't see, so the write sets are applied one transaction at a time.
+    let chunk = vec![
+        reconfig_txn(0, vec![(configuration_key(), configuration(2))]),
+        user_txn(1, vec![(configuration_key(), configuration(3))]),
+    ];
+    assert_eq!(epoch_of(&chunk), 2);
+}
+
+#[test]
+fn test_epoch_state_from_base_resources() {
+    let base = state_of(
+        &[
+            (validator_set_key(), validator_set()),
+            (configuration_key(), configuration(1)),
+        ],
+        Some(0),
+    );
+    // Neither resource is read or written by the chunk.
+    let chunk = vec![reconfig_txn(0, vec![(key("a"), value("1"))])];
+
+    let ChunkStateUpdateResult {
+        next_epoch_state, ..
+    } = calculator_on(&base, &[key("a")])
+        .calculate_for_transaction_chunk(&chunk, true, None)
+        .unwrap();
+
+    assert_eq!(next_epoch_state.unwrap().epoch, 1);
+}
+
+#[test]
+fn test_epoch_state_without_resources() {
+    let base = state_of(&[(validator_set_key(), validator_set())], Some(0));
+    let chunk = vec![reconfig_txn(0, vec![(key("a"), value("1"))])];
+
+    let result =
+        calculator_on(&base, &[key("a")]).calculate_for_transaction_chunk(&chunk, true, None);
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_epoch_state_after_each_reconfig() {
+    let base = state_of(
+        &[
+            (validator_set_key(), validator_set()),
+            (configuration_key(), configuration(1)),
+        ],
+        Some(0),
+    );
+    let chunk = vec![
+        reconfig_txn(0, vec![(configuration_key(), configuration(2))]),
+        // Neither resource changes, so the last epoch state parsed is reused.
+        reconfig_txn(1, vec![(key("a"), value("1"))]),
+        reconfig_txn(
+            2,
+            vec![
+                (validator_set_key(), validator_set()),
+                (configuration_key(), configuration(3)),
+            ],
+        ),
+        user_txn(3, vec![(key("a"), value("2"))]),
+    ];
+    let keys = vec![validator_set_key(), configuration_key(), key("a")];
+
+    let mut incremental = calculator_on(&base, &keys).into_incremental();
+    for (txn, txn_output) in &chunk {
+        incremental.add_transaction(txn, txn_output).unwrap();
+    }
+    let epochs: Vec<_> = incremental
+        .epoch_states_for_chunk()
+        .iter()
+        .map(|(version, epoch_state)| (*version, epoch_state.epoch))
+        .collect();
+    assert_eq!(epochs, vec![(1, 2), (2, 2), (3, 3)]);
+    let ChunkStateUpdateResult {
+        next_epoch_state, ..
+    } = incremental.finish().unwrap();
+    assert_eq!(next_epoch_state.unwrap().epoch, 3);
+
+    let ChunkStateUpdateResult {
+        next_epoch_state, ..
+    } = calculator_on(&base, &keys)
+        .calculate_for_transaction_chunk(&chunk, true, None)
+        .unwrap();
+    assert_eq!(next_epoch_state.unwrap().epoch, 3);
+}
+
+#[test]
+fn test_new_epoch_flag_must_match_reconfig() {
+    let base = state_of(
+        &[
+            (validator_set_key(), validator_set()),
+            (configuration_key(), configuration(1)),
+        ],
+        Some(0),
+    );
+    let keys = vec![configuration_key(), key("a")];
+    let with_reconfig = vec![
+        user_txn(0, vec![(key("a"), value("1"))]),
+        reconfig_txn(1, vec![(configuration_key(), configuration(2))]),
+    ];
+    let without_reconfig = vec![
+        user_txn(0, vec![(key("a"), value("1"))]),
+        state_checkpoint_txn(),
+    ];
+
+    let ChunkStateUpdateResult {
+        next_epoch_state, ..
+    } = calculator_on(&base, &keys)
+        .calculate_for_transaction_chunk(&with_reconfig, true, None)
+        .unwrap();
+    assert_eq!(next_epoch_state.unwrap().epoch, 2);
+
+    let ChunkStateUpdateResult {
+        next_epoch_state, ..
+    } = calculator_on(&base, &keys)
+        .calculate_for_transaction_chunk(&without_reconfig, false, None)
+        .unwrap();
+    assert!(next_epoch_state.is_none());
+
+    let error = calculator_on(&base, &keys)
+        .calculate_for_transaction_chunk(&with_reconfig, false, None)
+        .unwrap_err();
+    assert_eq!(
+        error.downcast::<Error>().unwrap(),
+        Error::NewEpochMismatch {
+            new_epoch: false,
+            reconfig_version: Some(2),
+        }
+    );
+
+    let error = calculator_on(&base, &keys)
+        .calculate_for_transaction_chunk(&without_reconfig, true, None)
+        .unwrap_err();
+    assert_eq!(
+        error.downcast::<Error>().unwrap(),
+        Error::NewEpochMismatch {
+            new_epoch: true,
+            reconfig_version: None,
+        }
+    );
+}
+
+/// The parent block writes "a" on top of a genesis state with "a", "b" and "c". Returns the
+/// genesis state, and the parent's state as computed speculatively, without a checkpoint.
+fn speculative_parent() -> (StateDelta, StateDelta) {
+    let genesis = state_of(
+        &[
+            (key("a"), value("1")),
+            (key("b"), value("2")),
+            (key("c"), value("5")),
+        ],
+        Some(0),
+    );
+    let ChunkStateUpdateResult { result_state, .. } = calculator_on(&genesis, &[key("a")])
+        .calculate_for_block(&[user_txn(0, vec![(key("a"), value("3"))])], false)
+        .unwrap();
+    (genesis, result_state)
+}
+
+#[test]
+fn test_rebase_onto_committed_parent() {
+    let (_genesis, parent) = speculative_parent();
+    let keys = vec![key("b"), key("c")];
+    let block = vec![
+        user_txn(0, vec![(key("b"), value("4"))]),
+        user_txn(1, vec![(key("d"), value("6"))]),
+        state_checkpoint_txn(),
+    ];
+
+    // The parent as committed: the same state, or a sibling's where "c" differs, checkpointed.
+    for c in ["5", "7"] {
+        let committed = state_of(
+            &[
+                (key("a"), value("3")),
+                (key("b"), value("2")),
+                (key("c"), value(c)),
+            ],
+            Some(1),
+        );
+
+        // The first transaction is added (and applied to the latest SMT) on top of the
+        // speculative parent, the rest on top of the committed one.
+        let mut incremental = calculator_on(&parent, &keys)
+            .with_max_pending_updates(1, usize::MAX)
+            .into_incremental();
+        incremental
+            .add_transaction(&block[0].0, &block[0].1)
+            .unwrap();
+        let mut incremental = incremental.rebase(&committed).unwrap();
+        for (txn, txn_output) in &block[1..] {
+            incremental.add_transaction(txn, txn_output).unwrap();
+        }
+        let rebased = incremental.finish().unwrap();
+
+        let mut incremental = calculator_on(&committed, &keys).into_incremental();
+        for (txn, txn_output) in &block {
+            incremental.add_transaction(txn, txn_output).unwrap();
+        }
+        let expected = incremental.finish().unwrap();
+
+        assert_eq!(
+            rebased.per_txn_state_updates,
+            expected.per_txn_state_updates
+        );
+        assert_eq!(
+            rebased.per_txn_state_checkpoint_hashes,
+            expected.per_txn_state_checkpoint_hashes
+        );
+        assert_eq!(
+            rebased.per_txn_state_checkpoint_usages,
+            expected.per_txn_state_checkpoint_usages
+        );
+        assert_eq!(
+            rebased.result_state.root_hash(),
+            root_hash_of(&[
+                (key("a"), value("3")),
+                (key("b"), value("4")),
+                (key("c"), value(c)),
+                (key("d"), value("6")),
+            ])
+        );
+        assert_eq!(
+            rebased.result_state.root_hash(),
+            expected.result_state.root_hash()
+        );
+        assert_eq!(
+            rebased.result_state.current_usage,
+            expected.result_state.current_usage
+        );
+    }
+}
+
+#[test]
+fn test_rebase_onto_incompatible_base_fails() {
+    let (genesis, parent) = speculative_parent();
+    let committed_kvs = [
+        (key("a"), value("3")),
+        (key("b"), value("2")),
+        (key("c"), value("7")),
+    ];
+    let committed = state_of(&committed_kvs, Some(1));
+    fn assert_incompatible<T>(result: anyhow::Result<T>) {
+        assert!(matches!(
+            result.err().unwrap().downcast::<Error>().unwrap(),
+            Error::IncompatibleBase { .. }
+        ));
+    }
+
+    // Not at the parent's version.
+    assert_incompatible(calculator_on(&parent, &[]).rebase(&genesis));
+    assert_incompatible(calculator_on(&parent, &[]).rebase(&state_of(&committed_kvs, Some(2))));
+
+    // Checkpointed before the parent's checkpoint.
+    let older_checkpoint = StateDelta::new(
+        SparseMerkleTree::new_empty(),
+        None,
+        committed.current.clone(),
+        Some(1),
+        HashMap::new(),
+        StateStorageUsage::zero(),
+        committed.current_usage,
+    );
+    assert_incompatible(calculator_on(&parent, &[]).rebase(&older_checkpoint));
+
+    // Checkpointed at the parent's version, but a different checkpoint.
+    let other_checkpoint = StateDelta::new(
+        tree_of(&committed_kvs[..1]),
+        Some(0),
+        committed.current.clone(),
+        Some(1),
+        HashMap::new(),
+        StateStorageUsage::zero(),
+        committed.current_usage,
+    );
+    assert_incompatible(calculator_on(&parent, &[]).rebase(&other_checkpoint));
+
+    // "c" differs, but the block wrote it already.
+    let mut incremental = calculator_on(&parent, &[key("c")]).into_incremental();
+    let (txn, txn_output) = user_txn(0, vec![(key("c"), value("8"))]);
+    incremental.add_transaction(&txn, &txn_output).unwrap();
+    assert_incompatible(incremental.rebase(&committed));
+
+    // "c" differs, but a checkpoint was made on top of the old value.
+    let mut incremental = calculator_on(&parent, &[key("c")]).into_incremental();
+    let (txn, txn_output) = state_checkpoint_txn();
+    incremental.add_transaction(&txn, &txn_output).unwrap();
+    assert_incompatible(incremental.rebase(&committed));
+
+    // Nothing differs, so that's fine even once a checkpoint was made.
+    let mut incremental = calculator_on(&parent, &[key("c")]).into_incremental();
+    incremental.add_transaction(&txn, &txn_output).unwrap();
+    assert!(incremental.rebase(&parent).is_ok());
+}
+
+fn resource_key(address: AccountAddress, module: &str, name: &str) -> StateKey {
+    let struct_tag = StructTag {
+        address: CORE_CODE_ADDRESS,
+        module: Identifier::new(module).unwrap(),
+        name: Identifier::new(name).unwrap(),
+        type_params: vec![],
+    };
+    StateKey::AccessPath(AccessPath::new(
+        address,
+        AccessPath::resource_access_vec(struct_tag),
+    ))
+}
+
+#[test]
+fn test_category_changes() {
+    let coin_a = resource_key(AccountAddress::random(), "coin", "CoinStore");
+    let coin_b = resource_key(AccountAddress::random(), "coin", "CoinStore");
+    let module = StateKey::AccessPath(AccessPath::code_access_path(ModuleId::new(
+        AccountAddress::from_hex_literal("0x2").unwrap(),
+        Identifier::new("foo").unwrap(),
+    )));
+    let table_item = StateKey::TableItem {
+        handle: TableHandle(7),
+        key: b"x".to_vec(),
+    };
+    let keys = vec![
+        coin_a.clone(),
+        coin_b.clone(),
+        module.clone(),
+        table_item.clone(),
+        key("a"),
+    ];
+    let chunk = vec![
+        user_txn(
+            0,
+            vec![
+                (coin_a.clone(), value("100")),
+                (coin_b.clone(), value("200")),
+            ],
+        ),
+        user_txn(
+            1,
+            vec![(module.clone(), value("code")), (key("a"), value("1"))],
+        ),
+        state_checkpoint_txn(),
+        user_txn(
+            2,
+            vec![
+                (coin_a.clone(), StateValue::empty()),
+                (table_item.clone(), value("y")),
+            ],
+        ),
+    ];
+    let size = |key: &StateKey, v: &str| (key.encode().unwrap().len() + v.len()) as i64;
+
+    let ChunkStateUpdateResult {
+        category_changes, ..
+    } = calculator(&keys)
+        .with_key_classifier(Arc::new(classify_by_module))
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+    let category_changes = category_changes.unwrap();
+    assert_eq!(category_changes.len(), 2);
+    assert_eq!(
+        category_changes[0],
+        vec![
+            (
+                "0x1::coin".to_string(),
+                CategoryStateChange {
+                    keys_written: 2,
+                    items_delta: 2,
+                    bytes_delta: size(&coin_a, "100") + size(&coin_b, "200"),
+                }
+            ),
+            (
+                "0x2::foo".to_string(),
+                CategoryStateChange {
+                    keys_written: 1,
+                    items_delta: 1,
+                    bytes_delta: size(&module, "code"),
+                }
+            ),
+        ]
+        .into_iter()
+        .collect()
+    );
+    assert_eq!(
+        category_changes[1],
+        vec![
+            (
+                "0x1::coin".to_string(),
+                CategoryStateChange {
+                    keys_written: 1,
+                    items_delta: -1,
+                    bytes_delta: -size(&coin_a, "100"),
+                }
+            ),
+            (
+                "table::0x7".to_string(),
+                CategoryStateChange {
+                    keys_written: 1,
+                    items_delta: 1,
+                    bytes_delta: size(&table_item, "y"),
+                }
+            ),
+        ]
+        .into_iter()
+        .collect()
+    );
+
+    // Without a classifier, nothing is categorized.
+    let ChunkStateUpdateResult {
+        category_changes, ..
+    } = calculator(&keys)
+        .calculate_for_transaction_chunk(&chunk, false, None)
+        .unwrap();
+    assert!(category_changes.is_none());
+}
+
+/// A transaction for `test_matches_reference`, writing to a small key space so keys get
+/// overwritten, deleted and re-created. `None` values are deletions.
+#[derive(Clone, Debug)]
+enum TestTxn {
+    User(BTreeMap<u8, Option<Vec<u8>>>),
+    StateCheckpoint,
+    /// A user transaction which also bumps the epoch.
+    Reconfig(BTreeMap<u8, Option<Vec<u8>>>),
+}
+
+fn arb_test_txn() -> impl Strategy<Value = TestTxn> {
+    let arb_writes = || btree_map(0..8u8, option::of(arb_value()), 0..4);
+    prop_oneof![
+        6 => arb_writes().prop_map(TestTxn::User),
+        2 => Just(TestTxn::StateCheckpoint),
+        1 => arb_writes().prop_map(TestTxn::Reconfig),
+    ]
+}
+
+fn test_txn_key(k: u8) -> StateKey {
+    key(&format!("key_{}", k))
+}
+
+/// What the calculator should come up with, recomputed naively: all the state in one map, with the
+/// tree rebuilt from scratch whenever a root hash is needed.
+struct ReferenceState {
+    // Deleted keys have the empty value, as in the trees.
+    kvs: BTreeMap<StateKey, StateValue>,
+    next_version: Version,
+    checkpoint_version: Option<Version>,
+    checkpoint_root_hash: HashValue,
+    updated_since_checkpoint: HashMap<StateKey, Option<StateValue>>,
+    epoch: u64,
+}
+
+impl ReferenceState {
+    /// The state after a genesis transaction at version 0, with the epoch resources in place.
+    fn genesis() -> Self {
+        let kvs: BTreeMap<_, _> = vec![
+            (validator_set_key(), validator_set()),
+            (configuration_key(), configuration(1)),
+        ]
+        .into_iter()
+        .collect();
+        let mut reference = Self {
+            kvs,
+            next_version: 1,
+            checkpoint_version: Some(0),
+            checkpoint_root_hash: HashValue::zero(),
+            updated_since_checkpoint: HashMap::new(),
+            epoch: 1,
+        };
+        reference.checkpoint_root_hash = reference.root_hash();
+        reference
+    }
+
+    fn kvs(&self) -> Vec<(StateKey, StateValue)> {
+        self.kvs
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    fn root_hash(&self) -> HashValue {
+        root_hash_of(&self.kvs())
+    }
+
+    fn usage(&self) -> StateStorageUsage {
+        usage_of(self.kvs.iter())
+    }
+
+    /// Applies `test_txn`, returning it as the calculator gets it, along with the checkpoint root
+    /// hash and usage if it's a checkpoint.
+    fn add(
+        &mut self,
+        test_txn: &TestTxn,
+    ) -> (
+        (Transaction, ParsedTransactionOutput),
+        Option<HashValue>,
+        Option<StateStorageUsage>,
+    ) {
+        let to_writes = |writes: &BTreeMap<u8, Option<Vec<u8>>>| -> Vec<_> {
+            writes
+                .iter()
+                .map(|(k, v)| {
+                    let value = v.clone().map_or_else(StateValue::empty, StateValue::from);
+                    (test_txn_key(*k), value)
+                })
+                .collect()
+        };
+        let (txn, is_checkpoint) = match test_txn {
+            TestTxn::User(writes) => (user_txn(self.next_version, to_writes(writes)), false),
+            TestTxn::StateCheckpoint => (state_checkpoint_txn(), true),
+            TestTxn::Reconfig(writes) => {
+                self.epoch += 1;
+                let mut writes = to_writes(writes);
+                writes.push((configuration_key(), configuration(self.epoch)));
+                (reconfig_txn(self.next_version, writes), true)
+            }
+        };
+
+        for (key, value) in txn.1.write_set().iter() {
+            let value = match value {
+                WriteOp::Value(bytes) => Some(StateValue::from(bytes.clone())),
+                WriteOp::Deletion => None,
+                WriteOp::Delta(..) => unreachable!(),
+            };
+            self.kvs
+                .insert(key.clone(), value.clone().unwrap_or_else(StateValue::empty));
+            self.updated_since_checkpoint.insert(key.clone(), value);
+        }
+        self.next_version += 1;
+
+        if is_checkpoint {
+            self.checkpoint_version = Some(self.next_version - 1);
+            self.checkpoint_root_hash = self.root_hash();
+            self.updated_since_checkpoint.clear();
+            (txn, Some(self.checkpoint_root_hash), Some(self.usage()))
+        } else {
+            (txn, None, None)
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(50))]
+
+    /// Runs random transactions through the calculator, cut into chunks at random, and checks
+    /// every chunk's results against the naive recomputation.
+    #[test]
+    fn test_matches_reference(
+        test_txns in vec(arb_test_txn(), 1..40),
+        chunk_ends in vec(any::<prop::sample::Index>(), 0..4),
+        max_pending_keys in option::of(1..8usize),
+    ) {
+        let keys: Vec<_> = (0..8u8)
+            .map(test_txn_key)
+            .chain(vec![validator_set_key(), configuration_key()])
+            .collect();
+        let mut chunk_ends: Vec<_> = chunk_ends
+            .iter()
+            .map(|end| end.index(test_txns.len() + 1))
+            .chain(vec![0, test_txns.len()])
+            .collect();
+        chunk_ends.sort_unstable();
+        chunk_ends.dedup();
+
+        let mut reference = ReferenceState::genesis();
+        let mut state = state_of(&reference.kvs(), Some(0));
+        for chunk_range in chunk_ends.windows(2) {
+            let mut chunk = Vec::new();
+            let mut expected_hashes = Vec::new();
+            let mut expected_usages = Vec::new();
+            for test_txn in &test_txns[chunk_range[0]..chunk_range[1]] {
+                let (txn, state_checkpoint_hash, usage) = reference.add(test_txn);
+                chunk.push(txn);
+                expected_hashes.push(state_checkpoint_hash);
+                expected_usages.push(usage);
+            }
+            let new_epoch = chunk.iter().any(|(_, txn_output)| txn_output.is_reconfig());
+
+            let mut calculator = calculator_on(&state, &keys);
+            if let Some(max_pending_keys) = max_pending_keys {
+                calculator = calculator.with_max_pending_updates(max_pending_keys, usize::MAX);
+            }
+            let ChunkStateUpdateResult {
+                per_txn_state_checkpoint_hashes: hashes,
+                per_txn_state_checkpoint_usages: usages,
+                result_state,
+                next_epoch_state,
+                ..
+            } = calculator
+                .calculate_for_transaction_chunk(&chunk, new_epoch, None)
+                .unwrap();
+
+            prop_assert_eq!(hashes, expected_hashes);
+            prop_assert_eq!(usages, expected_usages);
+            prop_assert_eq!(result_state.base_version, reference.checkpoint_version);
+            prop_assert_eq!(result_state.base.root_hash(), reference.checkpoint_root_hash);
+            prop_assert_eq!(
+                result_state.current_version,
+                reference.next_version.checked_sub(1)
+            );
+            prop_assert_eq!(result_state.current.root_hash(), reference.root_hash());
+            let updates_since_base: HashMap<_, _> = result_state
+                .updates_since_base
+                .iter()
+                .map(|(key, value)| (key.clone(), value.as_deref().cloned()))
+                .collect();
+            prop_assert_eq!(&updates_since_base, &reference.updated_since_checkpoint);
+            prop_assert_eq!(result_state.current_usage, reference.usage());
+            prop_assert_eq!(
+                next_epoch_state.map(|epoch_state| epoch_state.epoch),
+                Some(reference.epoch).filter(|_| new_epoch)
+            );
+
+            state = result_state;
+        }
+    }
+}
+
+/// A key hashed as if it were `key("a")` when it's `key("b")`, to inject a hash collision.
+struct CollidingKey<'a>(&'a StateKey);
+
+impl CryptoHash for CollidingKey<'_> {
+    type Hasher = <StateKey as CryptoHash>::Hasher;
+
+    fn hash(&self) -> HashValue {
+        if *self.0 == key("b") {
+            key("a").hash()
+        } else {
+            self.0.hash()
+        }
+    }
+}
+
+#[test]
+fn test_state_key_hash_collision() {
+    let colliding_calculator = || {
+        calculator(&[key("a"), key("b")])
+            .with_key_hasher(|key| CollidingKey(key).hash())
+            .into_incremental()
+    };
+    fn assert_collision<T>(result: anyhow::Result<T>) {
+        match result.err().unwrap().downcast::<Error>().unwrap() {
+            Error::StateKeyHashCollision {
+                key_hash,
+                existing_key,
+                new_key,
+            } => {
+                assert_eq!(key_hash, key("a").hash());
+                let mut keys = [existing_key, new_key];
+                keys.sort();
+                assert_eq!(keys, [key("a"), key("b")]);
+            }
+            error => panic!("unexpected error {:?}", error),
+        }
+    }
+
+    // Both keys written before the same SMT update.
+    let mut incremental = colliding_calculator();
+    let (txn, txn_output) = user_txn(0, vec![(key("a"), value("1")), (key("b"), value("2"))]);
+    incremental.add_transaction(&txn, &txn_output).unwrap();
+    assert_collision(incremental.finish());
+
+    // The second key written after a checkpoint with the first one already in the SMT.
+    let mut incremental = colliding_calculator();
+    let (txn, txn_output) = user_txn(0, vec![(key("a"), value("1"))]);
+    incremental.add_transaction(&txn, &txn_output).unwrap();
+    let (txn, txn_output) = state_checkpoint_txn();
+    incremental.add_transaction(&txn, &txn_output).unwrap();
+    let (txn, txn_output) = user_txn(1, vec![(key("b"), value("2"))]);
+    incremental.add_transaction(&txn, &txn_output).unwrap();
+    assert_collision(incremental.finish());
+
+    // Without the collision, the same writes go through.
+    let mut incremental = calculator(&[key("a"), key("b")]).into_incremental();
+    let (txn, txn_output) = user_txn(0, vec![(key("a"), value("1")), (key("b"), value("2"))]);
+    incremental.add_transaction(&txn, &txn_output).unwrap();
+    assert!(incremental.finish().is_ok());
+}
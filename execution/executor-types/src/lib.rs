@@ -3,7 +3,7 @@
 
 #![forbid(unsafe_code)]
 
-use std::{cmp::max, collections::HashMap, sync::Arc};
+use std::{cmp::max, collections::HashMap, sync::Arc, time::Duration};
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
@@ -26,12 +26,16 @@ use aptos_types::{
 };
 pub use error::Error;
 pub use executed_chunk::ExecutedChunk;
-pub use parsed_transaction_output::ParsedTransactionOutput;
+pub use parsed_transaction_output::{ChunkExecutionSummary, ParsedTransactionOutput};
 use scratchpad::{ProofRead, SparseMerkleTree};
 
 mod error;
 mod executed_chunk;
+pub mod failpoints;
 pub mod in_memory_state_calculator;
+#[cfg(test)]
+mod in_memory_state_calculator_test;
+mod metrics;
 mod parsed_transaction_output;
 
 type SparseMerkleProof = aptos_types::proof::SparseMerkleProof;
@@ -79,6 +83,23 @@ pub trait ChunkExecutorTrait: Send + Sync {
 
     /// Finishes the chunk executor by releasing memory held by inner data structures(SMT).
     fn finish(&self);
+
+    /// Returns how long the stages of the most recently executed (or applied) chunk took, if the
+    /// executor keeps track of them.
+    fn last_chunk_stage_timings(&self) -> Option<ChunkStageTimings> {
+        None
+    }
+}
+
+/// How long the stages of executing (or applying) a chunk took.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ChunkStageTimings {
+    /// Verifying the chunk against the target ledger info and the local ledger
+    pub verification: Duration,
+    /// Executing the transactions, or parsing the outputs when applying them
+    pub execution: Duration,
+    /// Updating the state tree and accumulators with the results
+    pub state_tree_update: Duration,
 }
 
 pub struct StateSnapshotDelta {
@@ -148,6 +169,8 @@ pub struct ChunkCommitNotification {
     pub committed_events: Vec<ContractEvent>,
     pub committed_transactions: Vec<Transaction>,
     pub reconfiguration_occurred: bool,
+    /// How the transactions in the chunk were sorted into kept, discarded and retried ones.
+    pub execution_summary: ChunkExecutionSummary,
 }
 
 /// A structure that summarizes the result of the execution needed for consensus to agree on.
@@ -308,6 +331,22 @@ impl ProofReader {
     pub fn new_empty() -> Self {
         Self::new(HashMap::new())
     }
+
+    pub fn num_proofs(&self) -> usize {
+        self.proofs.len()
+    }
+
+    /// Checks every proof against `root_hash`, e.g. to validate test fixtures.
+    pub fn verify_all_against(&self, root_hash: HashValue) -> Result<()> {
+        for (key, proof) in &self.proofs {
+            let value_hash = proof
+                .leaf()
+                .filter(|leaf| leaf.key() == *key)
+                .map(|leaf| leaf.value_hash());
+            proof.verify_by_hash(root_hash, *key, value_hash)?;
+        }
+        Ok(())
+    }
 }
 
 impl ProofRead for ProofReader {
@@ -322,7 +361,7 @@ impl ProofRead for ProofReader {
 pub struct TransactionData {
     /// Each entry in this map represents the new value of a store store object touched by this
     /// transaction.
-    state_updates: HashMap<StateKey, StateValue>,
+    state_updates: HashMap<StateKey, Option<Arc<StateValue>>>,
 
     /// The writeset generated from this transaction.
     write_set: WriteSet,
@@ -351,7 +390,7 @@ pub struct TransactionData {
 
 impl TransactionData {
     pub fn new(
-        state_updates: HashMap<StateKey, StateValue>,
+        state_updates: HashMap<StateKey, Option<Arc<StateValue>>>,
         write_set: WriteSet,
         events: Vec<ContractEvent>,
         reconfig_events: Vec<ContractEvent>,
@@ -374,7 +413,7 @@ impl TransactionData {
         }
     }
 
-    pub fn state_updates(&self) -> &HashMap<StateKey, StateValue> {
+    pub fn state_updates(&self) -> &HashMap<StateKey, Option<Arc<StateValue>>> {
         &self.state_updates
     }
 
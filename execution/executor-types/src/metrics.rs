@@ -0,0 +1,165 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use aptos_metrics_core::{
+    exponential_buckets, register_histogram, register_histogram_vec, register_int_counter,
+    register_int_counter_vec, register_int_gauge, Histogram, HistogramVec, IntCounter,
+    IntCounterVec, IntGauge,
+};
+use once_cell::sync::Lazy;
+
+pub static APTOS_EXECUTOR_SMT_UPDATE_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        // metric name
+        "aptos_executor_smt_update_seconds",
+        // metric description
+        "The time spent in seconds updating the SMT in the state calculator",
+        // metric labels (dimensions)
+        &["call_site"],
+        exponential_buckets(/*start=*/ 1e-6, /*factor=*/ 2.0, /*count=*/ 24).unwrap(),
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_SMT_UPDATE_KEYS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        // metric name
+        "aptos_executor_smt_update_keys",
+        // metric description
+        "The number of keys in each SMT update in the state calculator",
+        // metric labels (dimensions)
+        &["call_site"],
+        exponential_buckets(/*start=*/ 1.0, /*factor=*/ 2.0, /*count=*/ 24).unwrap(),
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_SMT_RETAINED_NODES: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        // metric name
+        "aptos_executor_smt_retained_nodes",
+        // metric description
+        "The number of SMT nodes kept in memory on top of the base state by the state calculator"
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_SMT_RETAINED_GENERATIONS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        // metric name
+        "aptos_executor_smt_retained_generations",
+        // metric description
+        "The number of SMT generations the state calculator keeps in memory on top of the base state"
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_SMT_NEW_NODES: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        // metric name
+        "aptos_executor_smt_new_nodes",
+        // metric description
+        "The number of SMT nodes created between checkpoints by the state calculator, by kind",
+        // metric labels (dimensions)
+        &["call_site", "kind"],
+        exponential_buckets(/*start=*/ 1.0, /*factor=*/ 2.0, /*count=*/ 24).unwrap(),
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_MISSING_PROOFS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        // metric name
+        "aptos_executor_missing_proofs_total",
+        // metric description
+        "The number of SMT updates in the state calculator which failed on a missing proof"
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_STATE_KEY_HASH_COLLISIONS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        // metric name
+        "aptos_executor_state_key_hash_collisions_total",
+        // metric description
+        "The number of state key hash collisions found by the state calculator"
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_STATE_CACHE_READS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        // metric name
+        "aptos_executor_state_cache_reads_total",
+        // metric description
+        "The number of state reads during execution, by where they were served from",
+        // metric labels (dimensions)
+        &["source"]
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_STATE_CACHE_STORAGE_READ_SECONDS: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        // metric name
+        "aptos_executor_state_cache_storage_read_seconds",
+        // metric description
+        "The time spent in seconds reading state from storage while executing a chunk",
+        exponential_buckets(/*start=*/ 1e-6, /*factor=*/ 2.0, /*count=*/ 24).unwrap(),
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_STATE_CACHE_PROOFS: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        // metric name
+        "aptos_executor_state_cache_proofs_total",
+        // metric description
+        "The number of proofs fetched for the state read during execution"
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_STATE_CACHE_BYTES: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        // metric name
+        "aptos_executor_state_cache_bytes",
+        // metric description
+        "The bytes of the state values cached while executing a chunk",
+        exponential_buckets(/*start=*/ 1024.0, /*factor=*/ 2.0, /*count=*/ 24).unwrap(),
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_CONFLICTING_KEYS: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        // metric name
+        "aptos_executor_conflicting_keys",
+        // metric description
+        "The number of keys written by more than one transaction of a chunk",
+        exponential_buckets(/*start=*/ 1.0, /*factor=*/ 2.0, /*count=*/ 20).unwrap(),
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_MAX_WRITERS_PER_KEY: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        // metric name
+        "aptos_executor_max_writers_per_key",
+        // metric description
+        "The most transactions of a chunk writing the same key",
+        exponential_buckets(/*start=*/ 1.0, /*factor=*/ 2.0, /*count=*/ 20).unwrap(),
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_CONFLICTING_TXNS_PERCENTAGE: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        // metric name
+        "aptos_executor_conflicting_txns_percentage",
+        // metric description
+        "The percentage of the transactions of a chunk writing a key another one writes too",
+        vec![0.0, 1.0, 5.0, 10.0, 25.0, 50.0, 75.0, 90.0, 100.0],
+    )
+    .unwrap()
+});
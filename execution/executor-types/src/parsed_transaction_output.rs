@@ -5,9 +5,10 @@ use crate::in_memory_state_calculator::NEW_EPOCH_EVENT_KEY;
 use aptos_types::{
     contract_event::ContractEvent,
     transaction::{TransactionOutput, TransactionStatus},
-    write_set::WriteSet,
+    write_set::{WriteOp, WriteSet},
 };
 use std::ops::Deref;
+use storage_interface::cached_state_view::StateCacheStats;
 
 pub struct ParsedTransactionOutput {
     output: TransactionOutput,
@@ -60,3 +61,69 @@ impl ParsedTransactionOutput {
         (write_set, events, reconfig_events, gas_used, status)
     }
 }
+
+/// Totals over the outputs of a chunk (or block) of transactions, gathered while the outputs are
+/// sorted by status, so reporting them doesn't take another pass.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ChunkExecutionSummary {
+    pub num_kept: usize,
+    pub num_discarded: usize,
+    pub num_retried: usize,
+    /// The transactions after a reconfiguration, which are retried in the next epoch whatever
+    /// their status. Included in `num_retried`.
+    pub num_retried_for_new_epoch: usize,
+    /// The gas used by the transactions kept.
+    pub gas_used: u64,
+    /// The events emitted by the transactions kept.
+    pub num_events: usize,
+    /// The write ops of the transactions kept.
+    pub num_write_ops: usize,
+    /// The bytes of the values written by the transactions kept.
+    pub write_set_bytes: usize,
+    /// Whether any transaction kept is a reconfiguration.
+    pub has_reconfig: bool,
+    /// How the state was read during execution.
+    pub state_cache: StateCacheStats,
+}
+
+impl ChunkExecutionSummary {
+    /// Adds an output, which only counts towards the totals if it's kept.
+    pub fn add_output(&mut self, output: &ParsedTransactionOutput) {
+        match output.status() {
+            TransactionStatus::Keep(_) => {
+                self.num_kept += 1;
+                self.gas_used += output.gas_used();
+                self.num_events += output.events().len();
+                self.num_write_ops += output.write_set().iter().count();
+                self.write_set_bytes += output
+                    .write_set()
+                    .iter()
+                    .map(|(_, write_op)| match write_op {
+                        WriteOp::Value(value) => value.len(),
+                        WriteOp::Deletion | WriteOp::Delta(..) => 0,
+                    })
+                    .sum::<usize>();
+                self.has_reconfig |= output.is_reconfig();
+            }
+            TransactionStatus::Discard(_) => self.num_discarded += 1,
+            TransactionStatus::Retry => self.num_retried += 1,
+        }
+    }
+
+    /// The totals over both chunks.
+    pub fn combine(self, rhs: Self) -> Self {
+        Self {
+            num_kept: self.num_kept + rhs.num_kept,
+            num_discarded: self.num_discarded + rhs.num_discarded,
+            num_retried: self.num_retried + rhs.num_retried,
+            num_retried_for_new_epoch: self.num_retried_for_new_epoch
+                + rhs.num_retried_for_new_epoch,
+            gas_used: self.gas_used + rhs.gas_used,
+            num_events: self.num_events + rhs.num_events,
+            num_write_ops: self.num_write_ops + rhs.num_write_ops,
+            write_set_bytes: self.write_set_bytes + rhs.write_set_bytes,
+            has_reconfig: self.has_reconfig || rhs.has_reconfig,
+            state_cache: self.state_cache.combine(rhs.state_cache),
+        }
+    }
+}
@@ -21,11 +21,13 @@ use std::{marker::PhantomData, sync::Arc};
 use storage_interface::{async_proof_fetcher::AsyncProofFetcher, proof_fetcher::ProofFetcher};
 
 use crate::{
-    components::{block_tree::BlockTree, chunk_output::ChunkOutput},
+    components::{
+        block_result_cache::BlockResultCache, block_tree::BlockTree, chunk_output::ChunkOutput,
+    },
     metrics::{
-        APTOS_EXECUTOR_COMMIT_BLOCKS_SECONDS, APTOS_EXECUTOR_EXECUTE_BLOCK_SECONDS,
-        APTOS_EXECUTOR_SAVE_TRANSACTIONS_SECONDS, APTOS_EXECUTOR_TRANSACTIONS_SAVED,
-        APTOS_EXECUTOR_VM_EXECUTE_BLOCK_SECONDS,
+        record_execution_summary, record_state_write_bytes, APTOS_EXECUTOR_COMMIT_BLOCKS_SECONDS,
+        APTOS_EXECUTOR_EXECUTE_BLOCK_SECONDS, APTOS_EXECUTOR_SAVE_TRANSACTIONS_SECONDS,
+        APTOS_EXECUTOR_TRANSACTIONS_SAVED, APTOS_EXECUTOR_VM_EXECUTE_BLOCK_SECONDS,
     },
 };
 use storage_interface::DbReaderWriter;
@@ -33,6 +35,7 @@ use storage_interface::DbReaderWriter;
 pub struct BlockExecutor<V> {
     pub db: DbReaderWriter,
     inner: RwLock<Option<BlockExecutorInner<V>>>,
+    result_cache: Option<Arc<BlockResultCache>>,
 }
 
 impl<V> BlockExecutor<V>
@@ -43,9 +46,18 @@ where
         Self {
             db,
             inner: RwLock::new(None),
+            result_cache: None,
         }
     }
 
+    /// Keeps the outputs of the last `capacity` blocks executed, so that executing one of them
+    /// again on top of the same parent, e.g. after a reset, reuses the output instead of
+    /// re-executing it. Outputs on top of states forked off by a commit are dropped.
+    pub fn with_result_cache(mut self, capacity: usize) -> Self {
+        self.result_cache = Some(Arc::new(BlockResultCache::new(capacity)));
+        self
+    }
+
     pub fn root_smt(&self) -> SparseMerkleTree<StateValue> {
         self.inner
             .read()
@@ -75,7 +87,10 @@ where
     }
 
     fn reset(&self) {
-        *self.inner.write() = Some(BlockExecutorInner::new(self.db.clone()));
+        *self.inner.write() = Some(BlockExecutorInner::new(
+            self.db.clone(),
+            self.result_cache.clone(),
+        ));
     }
 
     fn execute_block(
@@ -113,6 +128,7 @@ struct BlockExecutorInner<V> {
     db: DbReaderWriter,
     block_tree: BlockTree,
     proof_fetcher: Arc<dyn ProofFetcher>,
+    result_cache: Option<Arc<BlockResultCache>>,
     phantom: PhantomData<V>,
 }
 
@@ -120,17 +136,27 @@ impl<V> BlockExecutorInner<V>
 where
     V: VMExecutor,
 {
-    pub fn new(db: DbReaderWriter) -> Self {
+    pub fn new(db: DbReaderWriter, result_cache: Option<Arc<BlockResultCache>>) -> Self {
         let block_tree = BlockTree::new(&db.reader).expect("Block tree failed to init.");
         let proof_fetcher = Arc::new(AsyncProofFetcher::new(db.reader.clone()));
         Self {
             db,
             block_tree,
             proof_fetcher,
+            result_cache,
             phantom: PhantomData,
         }
     }
 
+    /// Drops the cached outputs that were computed on top of states no longer in the block tree.
+    /// Only done once a commit has pruned the tree: the blocks dropped by a reset are usually
+    /// executed again right after it, on top of the same states.
+    fn evict_stale_results(&self) {
+        if let Some(result_cache) = &self.result_cache {
+            result_cache.retain_live(&self.block_tree.live_state_root_hashes());
+        }
+    }
+
     fn root_smt(&self) -> SparseMerkleTree<StateValue> {
         self.block_tree
             .root_block()
@@ -179,7 +205,17 @@ where
                 LogSchema::new(LogEntry::BlockExecutor).block_id(block_id),
                 "reconfig_descendant_block_received"
             );
-            parent_output.reconfig_suffix()
+            Arc::new(parent_output.reconfig_suffix())
+        } else if let Some(output) = self
+            .result_cache
+            .as_ref()
+            .and_then(|result_cache| result_cache.get(parent_view, block_id))
+        {
+            info!(
+                LogSchema::new(LogEntry::BlockExecutor).block_id(block_id),
+                "cached_block_output_reused"
+            );
+            output
         } else {
             info!(
                 LogSchema::new(LogEntry::BlockExecutor).block_id(block_id),
@@ -191,6 +227,7 @@ where
                 Arc::clone(&self.db.reader),
                 Arc::clone(&self.proof_fetcher),
             )?;
+            state_view.prefetch(&V::keys_to_prefetch(&transactions))?;
 
             let chunk_output = {
                 let _timer = APTOS_EXECUTOR_VM_EXECUTE_BLOCK_SECONDS.start_timer();
@@ -204,6 +241,12 @@ where
             chunk_output.trace_log_transaction_status();
 
             let (output, _, _) = chunk_output.apply_to_ledger(parent_view)?;
+            record_execution_summary("block", &output.execution_summary);
+            record_state_write_bytes(&output.state_update_stats);
+            let output = Arc::new(output);
+            if let Some(result_cache) = &self.result_cache {
+                result_cache.insert(parent_view, block_id, output.clone());
+            }
             output
         };
         output.ensure_ends_with_state_checkpoint()?;
@@ -282,6 +325,7 @@ where
         self.block_tree
             .prune(ledger_info_with_sigs.ledger_info())
             .expect("Failure pruning block tree.");
+        self.evict_stale_results();
 
         Ok(())
     }
@@ -8,11 +8,14 @@ use crate::{
         apply_chunk_output::{ensure_no_discard, ensure_no_retry},
         chunk_commit_queue::ChunkCommitQueue,
         chunk_output::ChunkOutput,
+        epoch_ending::select_chunk_ending_ledger_info,
     },
     logging::{LogEntry, LogSchema},
     metrics::{
-        APTOS_EXECUTOR_APPLY_CHUNK_SECONDS, APTOS_EXECUTOR_COMMIT_CHUNK_SECONDS,
-        APTOS_EXECUTOR_EXECUTE_CHUNK_SECONDS, APTOS_EXECUTOR_VM_EXECUTE_CHUNK_SECONDS,
+        record_execution_summary, APTOS_EXECUTOR_APPLY_CHUNK_SECONDS,
+        APTOS_EXECUTOR_COMMIT_CHUNK_SECONDS, APTOS_EXECUTOR_EXECUTE_CHUNK_SECONDS,
+        APTOS_EXECUTOR_PROOF_CACHE_LOOKUPS, APTOS_EXECUTOR_PROOF_CACHE_SIZE,
+        APTOS_EXECUTOR_VM_EXECUTE_CHUNK_SECONDS,
     },
 };
 use anyhow::Result;
@@ -22,23 +25,27 @@ use aptos_state_view::StateViewId;
 use aptos_types::{
     ledger_info::LedgerInfoWithSignatures,
     transaction::{
-        Transaction, TransactionInfo, TransactionListWithProof, TransactionOutputListWithProof,
+        Transaction, TransactionInfo, TransactionListWithProof, TransactionOutput,
+        TransactionOutputListWithProof,
     },
 };
 use aptos_vm::VMExecutor;
 use executor_types::{
-    ChunkCommitNotification, ChunkExecutorTrait, ExecutedChunk, TransactionReplayer,
+    ChunkCommitNotification, ChunkExecutionSummary, ChunkExecutorTrait, ChunkStageTimings,
+    ExecutedChunk, TransactionReplayer,
 };
 use fail::fail_point;
-use std::{marker::PhantomData, sync::Arc};
+use std::{marker::PhantomData, sync::Arc, time::Instant};
 use storage_interface::{
-    cached_state_view::CachedStateView, sync_proof_fetcher::SyncProofFetcher, DbReaderWriter,
-    ExecutedTrees,
+    cached_state_view::CachedStateView, proof_cache::ProofCache,
+    sync_proof_fetcher::SyncProofFetcher, DbReaderWriter, ExecutedTrees,
 };
 
 pub struct ChunkExecutor<V> {
     db: DbReaderWriter,
     inner: RwLock<Option<ChunkExecutorInner<V>>>,
+    proof_cache: Option<Arc<ProofCache>>,
+    sub_chunk_size: Option<usize>,
 }
 
 impl<V: VMExecutor> ChunkExecutor<V> {
@@ -46,9 +53,27 @@ impl<V: VMExecutor> ChunkExecutor<V> {
         Self {
             db,
             inner: RwLock::new(None),
+            proof_cache: None,
+            sub_chunk_size: None,
         }
     }
 
+    /// Keeps the proofs fetched while executing a chunk, so that the following chunks don't fetch
+    /// them again as long as the persisted state snapshot they were fetched against is current.
+    pub fn with_proof_cache(mut self) -> Self {
+        self.proof_cache = Some(Arc::new(ProofCache::new()));
+        self
+    }
+
+    /// Executes (or applies) and commits the chunks given to `execute_and_commit_chunk` (or
+    /// `apply_and_commit_chunk`) in sub-chunks of about `sub_chunk_size` transactions, each ending
+    /// at a state checkpoint, so that a huge chunk isn't held in memory at once and a failure
+    /// midway only loses the sub-chunk being processed.
+    pub fn with_sub_chunk_size(mut self, sub_chunk_size: usize) -> Self {
+        self.sub_chunk_size = Some(sub_chunk_size);
+        self
+    }
+
     fn maybe_initialize(&self) -> Result<()> {
         if self.inner.read().is_none() {
             self.reset()?;
@@ -128,41 +153,80 @@ impl<V: VMExecutor> ChunkExecutorTrait for ChunkExecutor<V> {
     }
 
     fn reset(&self) -> Result<()> {
-        *self.inner.write() = Some(ChunkExecutorInner::new(self.db.clone())?);
+        *self.inner.write() = Some(ChunkExecutorInner::new(
+            self.db.clone(),
+            self.proof_cache.clone(),
+            self.sub_chunk_size,
+        )?);
         Ok(())
     }
 
     fn finish(&self) {
         *self.inner.write() = None;
     }
+
+    fn last_chunk_stage_timings(&self) -> Option<ChunkStageTimings> {
+        self.inner
+            .read()
+            .as_ref()
+            .and_then(|inner| *inner.last_stage_timings.lock())
+    }
 }
 
 struct ChunkExecutorInner<V> {
     db: DbReaderWriter,
     commit_queue: Mutex<ChunkCommitQueue>,
+    last_stage_timings: Mutex<Option<ChunkStageTimings>>,
+    proof_cache: Option<Arc<ProofCache>>,
+    sub_chunk_size: Option<usize>,
     _phantom: PhantomData<V>,
 }
 
 impl<V: VMExecutor> ChunkExecutorInner<V> {
-    pub fn new(db: DbReaderWriter) -> Result<Self> {
+    pub fn new(
+        db: DbReaderWriter,
+        proof_cache: Option<Arc<ProofCache>>,
+        sub_chunk_size: Option<usize>,
+    ) -> Result<Self> {
         let commit_queue = Mutex::new(ChunkCommitQueue::new_from_db(&db.reader)?);
         Ok(Self {
             db,
             commit_queue,
+            last_stage_timings: Mutex::new(None),
+            proof_cache,
+            sub_chunk_size,
             _phantom: PhantomData,
         })
     }
 
     fn state_view(&self, latest_view: &ExecutedTrees) -> Result<CachedStateView> {
+        let mut proof_fetcher = SyncProofFetcher::new(self.db.reader.clone());
+        if let Some(proof_cache) = &self.proof_cache {
+            proof_fetcher = proof_fetcher.with_proof_cache(proof_cache.clone());
+        }
         latest_view.verified_state_view(
             StateViewId::ChunkExecution {
                 first_version: latest_view.txn_accumulator().num_leaves(),
             },
             Arc::clone(&self.db.reader),
-            Arc::new(SyncProofFetcher::new(self.db.reader.clone())),
+            Arc::new(proof_fetcher),
         )
     }
 
+    /// Exports the lookups in the proof cache since the last chunk was executed (or applied).
+    fn record_proof_cache_lookups(&self) {
+        if let Some(proof_cache) = &self.proof_cache {
+            let (num_hits, num_misses) = proof_cache.take_lookup_counts();
+            APTOS_EXECUTOR_PROOF_CACHE_LOOKUPS
+                .with_label_values(&["hit"])
+                .inc_by(num_hits as u64);
+            APTOS_EXECUTOR_PROOF_CACHE_LOOKUPS
+                .with_label_values(&["miss"])
+                .inc_by(num_misses as u64);
+            APTOS_EXECUTOR_PROOF_CACHE_SIZE.set(proof_cache.len() as i64);
+        }
+    }
+
     fn apply_chunk_output_for_state_sync(
         verified_target_li: &LedgerInfoWithSignatures,
         epoch_change_li: Option<&LedgerInfoWithSignatures>,
@@ -171,16 +235,32 @@ impl<V: VMExecutor> ChunkExecutorInner<V> {
         transaction_infos: &[TransactionInfo],
     ) -> Result<ExecutedChunk> {
         let (mut executed_chunk, to_discard, to_retry) =
-            chunk_output.apply_to_ledger(latest_view)?;
+            chunk_output.apply_to_ledger_against(latest_view, transaction_infos)?;
+        record_execution_summary("chunk", &executed_chunk.execution_summary);
         ensure_no_discard(to_discard)?;
         ensure_no_retry(to_retry)?;
-        executed_chunk.ledger_info = executed_chunk
-            .maybe_select_chunk_ending_ledger_info(verified_target_li, epoch_change_li)?;
+        executed_chunk.ledger_info =
+            select_chunk_ending_ledger_info(&executed_chunk, verified_target_li, epoch_change_li)?;
         executed_chunk.ensure_transaction_infos_match(transaction_infos)?;
 
         Ok(executed_chunk)
     }
 
+    /// Records how long the stages of the chunk that was just executed (or applied) took, given
+    /// the instants at which each stage started.
+    fn record_stage_timings(
+        &self,
+        verification_start: Instant,
+        execution_start: Instant,
+        state_tree_update_start: Instant,
+    ) {
+        *self.last_stage_timings.lock() = Some(ChunkStageTimings {
+            verification: execution_start.duration_since(verification_start),
+            execution: state_tree_update_start.duration_since(execution_start),
+            state_tree_update: state_tree_update_start.elapsed(),
+        });
+    }
+
     fn commit_chunk_impl(&self) -> Result<Arc<ExecutedChunk>> {
         let (base_view, to_commit) = self.commit_queue.lock().next_chunk_to_commit()?;
         let txns_to_commit = to_commit.transactions_to_commit()?;
@@ -197,21 +277,22 @@ impl<V: VMExecutor> ChunkExecutorInner<V> {
                 false, /* sync_commit */
                 to_commit.result_view.state().clone(),
             )?;
+            fail_point!(executor_types::failpoints::COMMIT_CHUNK_AFTER_SAVE, |_| {
+                Err(anyhow::anyhow!("Injected error after saving the chunk"))
+            });
         }
 
         self.commit_queue.lock().dequeue()?;
         Ok(to_commit)
     }
 
-    // ************************* Block Executor Implementation *************************
-    fn execute_chunk(
+    /// Verifies the transaction list against the target ledger info, and drops the transactions
+    /// that are already in the ledger. Returns the rest, with their transaction infos.
+    fn verify_transaction_list(
         &self,
         txn_list_with_proof: TransactionListWithProof,
         verified_target_li: &LedgerInfoWithSignatures,
-        epoch_change_li: Option<&LedgerInfoWithSignatures>,
-    ) -> Result<()> {
-        let _timer = APTOS_EXECUTOR_EXECUTE_CHUNK_SECONDS.start_timer();
-
+    ) -> Result<(Vec<Transaction>, Vec<TransactionInfo>)> {
         let num_txns = txn_list_with_proof.transactions.len();
         let first_version_in_request = txn_list_with_proof.first_transaction_version;
         let (_persisted_view, latest_view) = self.commit_queue.lock().persisted_and_latest_view();
@@ -227,6 +308,8 @@ impl<V: VMExecutor> ChunkExecutorInner<V> {
         )?;
         let mut transactions = txn_list_with_proof.transactions;
         transactions.drain(..txns_to_skip as usize);
+        let mut transaction_infos = txn_list_with_proof.proof.transaction_infos;
+        transaction_infos.drain(..txns_to_skip as usize);
         if txns_to_skip == num_txns {
             info!(
                 "Skipping all transactions in the given chunk! Num transactions: {:?}",
@@ -234,19 +317,68 @@ impl<V: VMExecutor> ChunkExecutorInner<V> {
             );
         }
 
+        Ok((transactions, transaction_infos))
+    }
+
+    /// Verifies the transaction output list against the target ledger info, and drops the
+    /// transactions that are already in the ledger. Returns the rest, with their transaction
+    /// infos.
+    fn verify_transaction_output_list(
+        &self,
+        txn_output_list_with_proof: TransactionOutputListWithProof,
+        verified_target_li: &LedgerInfoWithSignatures,
+    ) -> Result<(Vec<(Transaction, TransactionOutput)>, Vec<TransactionInfo>)> {
+        let first_version_in_request = txn_output_list_with_proof.first_transaction_output_version;
+        let (_persisted_view, latest_view) = self.commit_queue.lock().persisted_and_latest_view();
+
+        // Verify input transaction list.
+        txn_output_list_with_proof
+            .verify(verified_target_li.ledger_info(), first_version_in_request)?;
+
+        // Skip transactions already in ledger.
+        let txns_to_skip = txn_output_list_with_proof.proof.verify_extends_ledger(
+            latest_view.txn_accumulator().num_leaves(),
+            latest_view.txn_accumulator().root_hash(),
+            first_version_in_request,
+        )?;
+        let mut txns_and_outputs = txn_output_list_with_proof.transactions_and_outputs;
+        txns_and_outputs.drain(..txns_to_skip as usize);
+        let mut transaction_infos = txn_output_list_with_proof.proof.transaction_infos;
+        transaction_infos.drain(..txns_to_skip as usize);
+
+        Ok((txns_and_outputs, transaction_infos))
+    }
+
+    /// Executes verified transactions on top of the latest view, checks the results against
+    /// their transaction infos, and queues them to be committed.
+    fn execute_verified_chunk(
+        &self,
+        verification_start: Instant,
+        transactions: Vec<Transaction>,
+        transaction_infos: &[TransactionInfo],
+        verified_target_li: &LedgerInfoWithSignatures,
+        epoch_change_li: Option<&LedgerInfoWithSignatures>,
+    ) -> Result<()> {
+        let num_txns = transactions.len();
+        let (_persisted_view, latest_view) = self.commit_queue.lock().persisted_and_latest_view();
+
         // Execute transactions.
+        let execution_start = Instant::now();
         let state_view = self.state_view(&latest_view)?;
         let chunk_output = {
             let _timer = APTOS_EXECUTOR_VM_EXECUTE_CHUNK_SECONDS.start_timer();
             ChunkOutput::by_transaction_execution::<V>(transactions, state_view)?
         };
+        self.record_proof_cache_lookups();
+        let state_tree_update_start = Instant::now();
         let executed_chunk = Self::apply_chunk_output_for_state_sync(
             verified_target_li,
             epoch_change_li,
             &latest_view,
             chunk_output,
-            &txn_list_with_proof.proof.transaction_infos[txns_to_skip..],
+            transaction_infos,
         )?;
+        self.record_stage_timings(verification_start, execution_start, state_tree_update_start);
 
         // Add result to commit queue.
         self.commit_queue.lock().enqueue(executed_chunk);
@@ -254,7 +386,7 @@ impl<V: VMExecutor> ChunkExecutorInner<V> {
         info!(
             LogSchema::new(LogEntry::ChunkExecutor)
                 .local_synced_version(latest_view.version().unwrap_or(0))
-                .first_version_in_request(first_version_in_request)
+                .first_version_in_request(Some(latest_view.txn_accumulator().num_leaves()))
                 .num_txns_in_request(num_txns),
             "Executed transaction chunk!",
         );
@@ -262,41 +394,33 @@ impl<V: VMExecutor> ChunkExecutorInner<V> {
         Ok(())
     }
 
-    fn apply_chunk(
+    /// Applies verified transaction outputs on top of the latest view, checks the results against
+    /// their transaction infos, and queues them to be committed.
+    fn apply_verified_chunk(
         &self,
-        txn_output_list_with_proof: TransactionOutputListWithProof,
+        verification_start: Instant,
+        txns_and_outputs: Vec<(Transaction, TransactionOutput)>,
+        transaction_infos: &[TransactionInfo],
         verified_target_li: &LedgerInfoWithSignatures,
         epoch_change_li: Option<&LedgerInfoWithSignatures>,
     ) -> Result<()> {
-        let _timer = APTOS_EXECUTOR_APPLY_CHUNK_SECONDS.start_timer();
-
-        let num_txns = txn_output_list_with_proof.transactions_and_outputs.len();
-        let first_version_in_request = txn_output_list_with_proof.first_transaction_output_version;
+        let num_txns = txns_and_outputs.len();
         let (_persisted_view, latest_view) = self.commit_queue.lock().persisted_and_latest_view();
 
-        // Verify input transaction list.
-        txn_output_list_with_proof
-            .verify(verified_target_li.ledger_info(), first_version_in_request)?;
-
-        // Skip transactions already in ledger.
-        let txns_to_skip = txn_output_list_with_proof.proof.verify_extends_ledger(
-            latest_view.txn_accumulator().num_leaves(),
-            latest_view.txn_accumulator().root_hash(),
-            first_version_in_request,
-        )?;
-        let mut txns_and_outputs = txn_output_list_with_proof.transactions_and_outputs;
-        txns_and_outputs.drain(..txns_to_skip as usize);
-
         // Apply transaction outputs.
+        let execution_start = Instant::now();
         let state_view = self.state_view(&latest_view)?;
         let chunk_output = ChunkOutput::by_transaction_output(txns_and_outputs, state_view)?;
+        self.record_proof_cache_lookups();
+        let state_tree_update_start = Instant::now();
         let executed_chunk = Self::apply_chunk_output_for_state_sync(
             verified_target_li,
             epoch_change_li,
             &latest_view,
             chunk_output,
-            &txn_output_list_with_proof.proof.transaction_infos[txns_to_skip..],
+            transaction_infos,
         )?;
+        self.record_stage_timings(verification_start, execution_start, state_tree_update_start);
 
         // Add result to commit queue.
         self.commit_queue.lock().enqueue(executed_chunk);
@@ -304,7 +428,7 @@ impl<V: VMExecutor> ChunkExecutorInner<V> {
         info!(
             LogSchema::new(LogEntry::ChunkExecutor)
                 .local_synced_version(latest_view.version().unwrap_or(0))
-                .first_version_in_request(first_version_in_request)
+                .first_version_in_request(Some(latest_view.txn_accumulator().num_leaves()))
                 .num_txns_in_request(num_txns),
             "Applied transaction output chunk!",
         );
@@ -312,6 +436,103 @@ impl<V: VMExecutor> ChunkExecutorInner<V> {
         Ok(())
     }
 
+    /// Processes (executes or applies) and commits the sub-chunks one after the other, combining
+    /// what was committed into a single notification. Only the last sub-chunk can end the epoch,
+    /// so the epoch change ledger info is only handed to that one.
+    fn process_and_commit_sub_chunks<T>(
+        &self,
+        verification_start: Instant,
+        sub_chunks: Vec<(Vec<T>, Vec<TransactionInfo>)>,
+        epoch_change_li: Option<&LedgerInfoWithSignatures>,
+        process: impl Fn(
+            Instant,
+            Vec<T>,
+            &[TransactionInfo],
+            Option<&LedgerInfoWithSignatures>,
+        ) -> Result<()>,
+    ) -> Result<ChunkCommitNotification> {
+        let num_sub_chunks = sub_chunks.len();
+        let mut verification_start = Some(verification_start);
+        let mut notification = ChunkCommitNotification {
+            committed_events: vec![],
+            committed_transactions: vec![],
+            reconfiguration_occurred: false,
+            execution_summary: ChunkExecutionSummary::default(),
+        };
+        for (idx, (items, transaction_infos)) in sub_chunks.into_iter().enumerate() {
+            let is_last = idx + 1 == num_sub_chunks;
+            process(
+                // The verification of the whole chunk is attributed to the first sub-chunk.
+                verification_start.take().unwrap_or_else(Instant::now),
+                items,
+                &transaction_infos,
+                if is_last { epoch_change_li } else { None },
+            )?;
+            let committed = self.commit_chunk()?;
+            info!(
+                LogSchema::new(LogEntry::ChunkExecutor)
+                    .num(idx as u64 + 1)
+                    .num_txns_in_request(committed.committed_transactions.len()),
+                "Committed sub-chunk {} of {}.",
+                idx + 1,
+                num_sub_chunks,
+            );
+            notification
+                .committed_events
+                .extend(committed.committed_events);
+            notification
+                .committed_transactions
+                .extend(committed.committed_transactions);
+            notification.reconfiguration_occurred |= committed.reconfiguration_occurred;
+            notification.execution_summary = notification
+                .execution_summary
+                .combine(committed.execution_summary);
+        }
+
+        Ok(notification)
+    }
+
+    // ************************* Block Executor Implementation *************************
+    fn execute_chunk(
+        &self,
+        txn_list_with_proof: TransactionListWithProof,
+        verified_target_li: &LedgerInfoWithSignatures,
+        epoch_change_li: Option<&LedgerInfoWithSignatures>,
+    ) -> Result<()> {
+        let _timer = APTOS_EXECUTOR_EXECUTE_CHUNK_SECONDS.start_timer();
+        let verification_start = Instant::now();
+
+        let (transactions, transaction_infos) =
+            self.verify_transaction_list(txn_list_with_proof, verified_target_li)?;
+        self.execute_verified_chunk(
+            verification_start,
+            transactions,
+            &transaction_infos,
+            verified_target_li,
+            epoch_change_li,
+        )
+    }
+
+    fn apply_chunk(
+        &self,
+        txn_output_list_with_proof: TransactionOutputListWithProof,
+        verified_target_li: &LedgerInfoWithSignatures,
+        epoch_change_li: Option<&LedgerInfoWithSignatures>,
+    ) -> Result<()> {
+        let _timer = APTOS_EXECUTOR_APPLY_CHUNK_SECONDS.start_timer();
+        let verification_start = Instant::now();
+
+        let (txns_and_outputs, transaction_infos) =
+            self.verify_transaction_output_list(txn_output_list_with_proof, verified_target_li)?;
+        self.apply_verified_chunk(
+            verification_start,
+            txns_and_outputs,
+            &transaction_infos,
+            verified_target_li,
+            epoch_change_li,
+        )
+    }
+
     fn commit_chunk(&self) -> Result<ChunkCommitNotification> {
         let _timer = APTOS_EXECUTOR_COMMIT_CHUNK_SECONDS.start_timer();
         let executed_chunk = self.commit_chunk_impl()?;
@@ -319,6 +540,7 @@ impl<V: VMExecutor> ChunkExecutorInner<V> {
             committed_events: executed_chunk.events_to_commit(),
             committed_transactions: executed_chunk.transactions(),
             reconfiguration_occurred: executed_chunk.has_reconfiguration(),
+            execution_summary: executed_chunk.execution_summary,
         })
     }
 
@@ -328,8 +550,34 @@ impl<V: VMExecutor> ChunkExecutorInner<V> {
         verified_target_li: &LedgerInfoWithSignatures,
         epoch_change_li: Option<&LedgerInfoWithSignatures>,
     ) -> Result<ChunkCommitNotification> {
-        self.execute_chunk(txn_list_with_proof, verified_target_li, epoch_change_li)?;
-        self.commit_chunk()
+        let sub_chunk_size = match self.sub_chunk_size {
+            Some(sub_chunk_size) => sub_chunk_size,
+            None => {
+                self.execute_chunk(txn_list_with_proof, verified_target_li, epoch_change_li)?;
+                return self.commit_chunk();
+            }
+        };
+
+        // The whole chunk is verified against the proof upfront, while each sub-chunk is checked
+        // against its part of the transaction infos as it's executed.
+        let verification_start = Instant::now();
+        let (transactions, transaction_infos) =
+            self.verify_transaction_list(txn_list_with_proof, verified_target_li)?;
+        self.process_and_commit_sub_chunks(
+            verification_start,
+            split_at_checkpoints(transactions, transaction_infos, sub_chunk_size),
+            epoch_change_li,
+            |verification_start, transactions, transaction_infos, epoch_change_li| {
+                let _timer = APTOS_EXECUTOR_EXECUTE_CHUNK_SECONDS.start_timer();
+                self.execute_verified_chunk(
+                    verification_start,
+                    transactions,
+                    transaction_infos,
+                    verified_target_li,
+                    epoch_change_li,
+                )
+            },
+        )
     }
 
     fn apply_and_commit_chunk(
@@ -338,13 +586,73 @@ impl<V: VMExecutor> ChunkExecutorInner<V> {
         verified_target_li: &LedgerInfoWithSignatures,
         epoch_change_li: Option<&LedgerInfoWithSignatures>,
     ) -> Result<ChunkCommitNotification> {
-        self.apply_chunk(
-            txn_output_list_with_proof,
-            verified_target_li,
+        let sub_chunk_size = match self.sub_chunk_size {
+            Some(sub_chunk_size) => sub_chunk_size,
+            None => {
+                self.apply_chunk(
+                    txn_output_list_with_proof,
+                    verified_target_li,
+                    epoch_change_li,
+                )?;
+                return self.commit_chunk();
+            }
+        };
+
+        // The whole chunk is verified against the proof upfront, while each sub-chunk is checked
+        // against its part of the transaction infos as it's applied.
+        let verification_start = Instant::now();
+        let (txns_and_outputs, transaction_infos) =
+            self.verify_transaction_output_list(txn_output_list_with_proof, verified_target_li)?;
+        self.process_and_commit_sub_chunks(
+            verification_start,
+            split_at_checkpoints(txns_and_outputs, transaction_infos, sub_chunk_size),
             epoch_change_li,
-        )?;
-        self.commit_chunk()
+            |verification_start, txns_and_outputs, transaction_infos, epoch_change_li| {
+                let _timer = APTOS_EXECUTOR_APPLY_CHUNK_SECONDS.start_timer();
+                self.apply_verified_chunk(
+                    verification_start,
+                    txns_and_outputs,
+                    transaction_infos,
+                    verified_target_li,
+                    epoch_change_li,
+                )
+            },
+        )
+    }
+}
+
+/// Splits the verified items of a chunk (and their transaction infos) into sub-chunks of at least
+/// `target_size` transactions, except for the last one. Sub-chunks only end at state checkpoints,
+/// i.e. at the end of a block or at a reconfiguration, so no block is split across sub-chunks.
+fn split_at_checkpoints<T>(
+    mut items: Vec<T>,
+    mut transaction_infos: Vec<TransactionInfo>,
+    target_size: usize,
+) -> Vec<(Vec<T>, Vec<TransactionInfo>)> {
+    let mut split_points = vec![];
+    let mut sub_chunk_start = 0;
+    for (idx, txn_info) in transaction_infos.iter().enumerate() {
+        let end = idx + 1;
+        if end - sub_chunk_start >= target_size
+            && end < transaction_infos.len()
+            && txn_info.state_checkpoint_hash().is_some()
+        {
+            split_points.push(end);
+            sub_chunk_start = end;
+        }
+    }
+
+    let mut sub_chunks = vec![];
+    // Split from the back, so that the split points stay valid.
+    for split_point in split_points.into_iter().rev() {
+        sub_chunks.push((
+            items.split_off(split_point),
+            transaction_infos.split_off(split_point),
+        ));
     }
+    sub_chunks.push((items, transaction_infos));
+    sub_chunks.reverse();
+    sub_chunks
 }
 
 impl<V: VMExecutor> TransactionReplayer for ChunkExecutor<V> {
@@ -383,7 +691,9 @@ impl<V: VMExecutor> TransactionReplayer for ChunkExecutorInner<V> {
             let txns = to_run.take().unwrap();
             let (executed, to_discard, to_retry) =
                 ChunkOutput::by_transaction_execution::<V>(txns, state_view)?
-                    .apply_to_ledger(&latest_view)?;
+                    .apply_to_ledger_against(&latest_view, &transaction_infos)?;
+            record_execution_summary("chunk", &executed.execution_summary);
+            self.record_proof_cache_lookups();
 
             // Accumulate result and deal with retry
             ensure_no_discard(to_discard)?;
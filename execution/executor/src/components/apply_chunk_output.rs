@@ -3,7 +3,10 @@
 
 #![forbid(unsafe_code)]
 
-use crate::{components::chunk_output::ChunkOutput, metrics::APTOS_EXECUTOR_ERRORS};
+use crate::{
+    components::{chunk_output::ChunkOutput, epoch_ending::EpochEndingInfo},
+    metrics::APTOS_EXECUTOR_ERRORS,
+};
 use anyhow::{ensure, Result};
 use aptos_crypto::{
     hash::{CryptoHash, EventAccumulatorHasher},
@@ -16,9 +19,10 @@ use aptos_types::{
     transaction::{Transaction, TransactionInfo, TransactionOutput, TransactionStatus},
 };
 use executor_types::{
-    in_memory_state_calculator::InMemoryStateCalculator, ExecutedChunk, ParsedTransactionOutput,
-    TransactionData,
+    in_memory_state_calculator::InMemoryStateCalculator, ChunkExecutionSummary, ExecutedChunk,
+    ParsedTransactionOutput, TransactionData,
 };
+use fail::fail_point;
 use std::{collections::HashMap, iter::repeat, sync::Arc};
 use storage_interface::ExecutedTrees;
 
@@ -28,6 +32,7 @@ impl ApplyChunkOutput {
     pub fn apply(
         chunk_output: ChunkOutput,
         base_view: &ExecutedTrees,
+        transaction_infos: Option<&[TransactionInfo]>,
     ) -> Result<(ExecutedChunk, Vec<Transaction>, Vec<Transaction>)> {
         let ChunkOutput {
             state_cache,
@@ -35,29 +40,57 @@ impl ApplyChunkOutput {
             transaction_outputs,
         } = chunk_output;
         // Separate transactions with different VM statuses.
-        let (new_epoch, status, to_keep, to_discard, to_retry) =
+        let (new_epoch, status, to_keep, to_discard, to_retry, mut execution_summary) =
             Self::sort_transactions(transactions, transaction_outputs)?;
+        execution_summary.state_cache = state_cache.stats;
+        // The infos are of the transactions committed, which the kept ones are a prefix of (the
+        // rest are to be retried).
+        let expected_state_checkpoint_hashes = transaction_infos.map(|infos| {
+            infos
+                .iter()
+                .take(to_keep.len())
+                .map(TransactionInfo::state_checkpoint_hash)
+                .collect()
+        });
 
         // Apply the write set, get the latest state.
-        let (state_updates_vec, state_checkpoint_hashes, result_state, next_epoch_state) =
-            InMemoryStateCalculator::new(base_view.state(), state_cache)
-                .calculate_for_transaction_chunk(&to_keep, new_epoch)?;
+        let mut state_update_result = InMemoryStateCalculator::new_at_version(
+            base_view.state(),
+            state_cache,
+            base_view.num_transactions(),
+        )?
+        .calculate_for_transaction_chunk(
+            &to_keep,
+            new_epoch,
+            expected_state_checkpoint_hashes,
+        )?;
+        fail_point!(executor_types::failpoints::APPLY_CHUNK_OUTPUT, |_| {
+            Err(anyhow::anyhow!("Injected error in apply_chunk_output"))
+        });
 
         // Calculate TransactionData and TransactionInfo, i.e. the ledger history diff.
-        let (to_commit, transaction_info_hashes) =
-            Self::assemble_ledger_diff(to_keep, state_updates_vec, state_checkpoint_hashes);
-        let result_view = ExecutedTrees::new(
-            result_state,
-            Arc::new(base_view.txn_accumulator().append(&transaction_info_hashes)),
+        let (to_commit, transaction_info_hashes) = Self::assemble_ledger_diff(
+            to_keep,
+            std::mem::take(&mut state_update_result.per_txn_state_updates),
+            state_update_result.per_txn_state_checkpoint_hashes.clone(),
         );
+        let txn_accumulator =
+            Arc::new(base_view.txn_accumulator().append(&transaction_info_hashes));
+        let epoch_ending =
+            EpochEndingInfo::from_state_update_result(&state_update_result, &txn_accumulator)?;
+        let state_update_stats =
+            std::mem::take(&mut state_update_result.per_txn_state_update_stats);
+        let result_view = ExecutedTrees::new(state_update_result.result_state, txn_accumulator);
 
         Ok((
             ExecutedChunk {
                 status,
                 to_commit,
                 result_view,
-                next_epoch_state,
+                next_epoch_state: epoch_ending.map(|epoch_ending| epoch_ending.next_epoch_state),
                 ledger_info: None,
+                execution_summary,
+                state_update_stats,
             },
             to_discard,
             to_retry,
@@ -73,6 +106,7 @@ impl ApplyChunkOutput {
         Vec<(Transaction, ParsedTransactionOutput)>,
         Vec<Transaction>,
         Vec<Transaction>,
+        ChunkExecutionSummary,
     )> {
         let num_txns = transactions.len();
         let mut transaction_outputs: Vec<ParsedTransactionOutput> =
@@ -91,6 +125,15 @@ impl ApplyChunkOutput {
             vec![]
         };
 
+        let mut execution_summary = ChunkExecutionSummary {
+            num_retried: to_retry.len(),
+            num_retried_for_new_epoch: to_retry.len(),
+            ..Default::default()
+        };
+        for output in &transaction_outputs {
+            execution_summary.add_output(output);
+        }
+
         // N.B. Transaction status after the epoch marker are ignored and set to Retry forcibly.
         let status = transaction_outputs
             .iter()
@@ -106,6 +149,14 @@ impl ApplyChunkOutput {
                 .partition::<Vec<(Transaction, ParsedTransactionOutput)>, _>(|(_, o)| {
                     matches!(o.status(), TransactionStatus::Keep(_))
                 });
+        debug_assert!(
+            to_keep
+                .iter()
+                .rev()
+                .skip(1)
+                .all(|(_, output)| !output.is_reconfig()),
+            "A transaction following a reconfiguration in the same chunk is kept.",
+        );
 
         // Sanity check transactions with the Discard status:
         let to_discard = to_discard
@@ -135,12 +186,13 @@ impl ApplyChunkOutput {
             to_keep,
             to_discard,
             to_retry,
+            execution_summary,
         ))
     }
 
     fn assemble_ledger_diff(
         to_keep: Vec<(Transaction, ParsedTransactionOutput)>,
-        state_updates_vec: Vec<HashMap<StateKey, StateValue>>,
+        state_updates_vec: Vec<HashMap<StateKey, Option<Arc<StateValue>>>>,
         state_checkpoint_hashes: Vec<Option<HashValue>>,
     ) -> (Vec<(Transaction, TransactionData)>, Vec<HashValue>) {
         let mut to_commit = vec![];
@@ -0,0 +1,101 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+#![forbid(unsafe_code)]
+
+use crate::metrics::{
+    APTOS_EXECUTOR_BLOCK_RESULT_CACHE_LOOKUPS, APTOS_EXECUTOR_BLOCK_RESULT_CACHE_SIZE,
+};
+use aptos_crypto::HashValue;
+use aptos_infallible::Mutex;
+use executor_types::ExecutedChunk;
+use lru::LruCache;
+use std::{collections::HashSet, sync::Arc};
+use storage_interface::ExecutedTrees;
+
+/// Outputs of the blocks executed lately, kept across resets of the block tree so that a block
+/// executed again on top of the same parent state (consensus retrying after a reset, or state
+/// sync handing back a block that was dropped from the tree) doesn't go through the VM and the
+/// state calculator a second time.
+///
+/// Outputs are keyed by the root hash of the parent's current state and the block id. A hit also
+/// requires the parent's transaction accumulator to match, since the output extends it.
+pub struct BlockResultCache {
+    inner: Mutex<LruCache<(HashValue, HashValue), CachedBlockResult>>,
+}
+
+struct CachedBlockResult {
+    parent_accumulator_root_hash: HashValue,
+    output: Arc<ExecutedChunk>,
+}
+
+impl BlockResultCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Returns the output of block `block_id` executed on top of `parent_view`, if it's cached.
+    pub fn get(
+        &self,
+        parent_view: &ExecutedTrees,
+        block_id: HashValue,
+    ) -> Option<Arc<ExecutedChunk>> {
+        let output = self
+            .inner
+            .lock()
+            .get(&(parent_view.state().current.root_hash(), block_id))
+            .filter(|cached| {
+                cached.parent_accumulator_root_hash == parent_view.txn_accumulator().root_hash()
+            })
+            .map(|cached| cached.output.clone());
+        APTOS_EXECUTOR_BLOCK_RESULT_CACHE_LOOKUPS
+            .with_label_values(&[if output.is_some() { "hit" } else { "miss" }])
+            .inc();
+        output
+    }
+
+    /// Remembers the output of block `block_id` executed on top of `parent_view`.
+    pub fn insert(
+        &self,
+        parent_view: &ExecutedTrees,
+        block_id: HashValue,
+        output: Arc<ExecutedChunk>,
+    ) {
+        let mut inner = self.inner.lock();
+        inner.put(
+            (parent_view.state().current.root_hash(), block_id),
+            CachedBlockResult {
+                parent_accumulator_root_hash: parent_view.txn_accumulator().root_hash(),
+                output,
+            },
+        );
+        APTOS_EXECUTOR_BLOCK_RESULT_CACHE_SIZE.set(inner.len() as i64);
+    }
+
+    /// Drops the outputs computed on top of a parent state that isn't any of
+    /// `live_state_root_hashes`, i.e. one that can't be executed on again.
+    pub fn retain_live(&self, live_state_root_hashes: &HashSet<HashValue>) {
+        let mut inner = self.inner.lock();
+        let stale_keys: Vec<_> = inner
+            .iter()
+            .map(|(key, _)| *key)
+            .filter(|(parent_state_root_hash, _)| {
+                !live_state_root_hashes.contains(parent_state_root_hash)
+            })
+            .collect();
+        for key in stale_keys {
+            inner.pop(&key);
+        }
+        APTOS_EXECUTOR_BLOCK_RESULT_CACHE_SIZE.set(inner.len() as i64);
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -15,14 +15,14 @@ use aptos_types::{ledger_info::LedgerInfo, proof::definition::LeafCount};
 use consensus_types::block::Block as ConsensusBlock;
 use executor_types::{Error, ExecutedChunk};
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    collections::{hash_map::Entry, HashMap, HashSet},
     sync::{Arc, Weak},
 };
 use storage_interface::DbReader;
 
 pub struct Block {
     pub id: HashValue,
-    pub output: ExecutedChunk,
+    pub output: Arc<ExecutedChunk>,
     children: Mutex<Vec<Arc<Block>>>,
     block_lookup: Arc<BlockLookup>,
 }
@@ -87,7 +87,7 @@ impl BlockLookupInner {
     fn fetch_or_add_block(
         &mut self,
         id: HashValue,
-        output: ExecutedChunk,
+        output: Arc<ExecutedChunk>,
         parent_id: Option<HashValue>,
         block_lookup: &Arc<BlockLookup>,
     ) -> Result<(Arc<Block>, bool, Option<Arc<Block>>)> {
@@ -146,7 +146,7 @@ impl BlockLookup {
     fn fetch_or_add_block(
         self: &Arc<Self>,
         id: HashValue,
-        output: ExecutedChunk,
+        output: Arc<ExecutedChunk>,
         parent_id: Option<HashValue>,
     ) -> Result<Arc<Block>> {
         let (block, existing, parent_block) = self
@@ -165,6 +165,13 @@ impl BlockLookup {
         Ok(block)
     }
 
+    fn live_blocks(&self) -> Vec<Arc<Block>> {
+        // Upgraded after releasing the lock, since dropping the last reference to a block
+        // removes it from the lookup.
+        let weak_blocks: Vec<_> = self.inner.lock().0.values().cloned().collect();
+        weak_blocks.iter().filter_map(Weak::upgrade).collect()
+    }
+
     fn remove(&self, id: HashValue) {
         self.inner.lock().0.remove(&id);
     }
@@ -217,7 +224,7 @@ impl BlockTree {
         };
 
         let result_view = startup_info.committed_trees;
-        block_lookup.fetch_or_add_block(id, ExecutedChunk::new_empty(result_view), None)
+        block_lookup.fetch_or_add_block(id, Arc::new(ExecutedChunk::new_empty(result_view)), None)
     }
 
     pub fn prune(&self, ledger_info: &LedgerInfo) -> Result<()> {
@@ -234,7 +241,9 @@ impl BlockTree {
             );
             self.block_lookup.fetch_or_add_block(
                 epoch_genesis_id,
-                ExecutedChunk::new_empty(last_committed_block.output.result_view.clone()),
+                Arc::new(ExecutedChunk::new_empty(
+                    last_committed_block.output.result_view.clone(),
+                )),
                 None,
             )?
         } else {
@@ -252,7 +261,7 @@ impl BlockTree {
         &self,
         parent_block_id: HashValue,
         id: HashValue,
-        output: ExecutedChunk,
+        output: Arc<ExecutedChunk>,
     ) -> Result<Arc<Block>> {
         self.block_lookup
             .fetch_or_add_block(id, output, Some(parent_block_id))
@@ -261,4 +270,14 @@ impl BlockTree {
     pub fn root_block(&self) -> Arc<Block> {
         self.root.lock().clone()
     }
+
+    /// Root hashes of the current states of all the blocks in the tree, i.e. the states a block
+    /// can still be executed on top of.
+    pub fn live_state_root_hashes(&self) -> HashSet<HashValue> {
+        self.block_lookup
+            .live_blocks()
+            .iter()
+            .map(|block| block.output.result_view.state().current.root_hash())
+            .collect()
+    }
 }
@@ -16,7 +16,7 @@ impl BlockTree {
         let root = block_lookup
             .fetch_or_add_block(
                 *PRE_GENESIS_BLOCK_ID,
-                ExecutedChunk::new_empty(result_view),
+                Arc::new(ExecutedChunk::new_empty(result_view)),
                 None,
             )
             .unwrap();
@@ -39,8 +39,8 @@ fn id(index: u64) -> HashValue {
     HashValue::new(buf)
 }
 
-fn empty_chunk() -> ExecutedChunk {
-    ExecutedChunk::new_empty(ExecutedTrees::new_empty())
+fn empty_chunk() -> Arc<ExecutedChunk> {
+    Arc::new(ExecutedChunk::new_empty(ExecutedTrees::new_empty()))
 }
 
 fn gen_ledger_info(block_id: HashValue, reconfig: bool) -> LedgerInfo {
@@ -7,7 +7,7 @@ use crate::components::apply_chunk_output::ApplyChunkOutput;
 use anyhow::Result;
 use aptos_logger::trace;
 use aptos_state_view::StateView;
-use aptos_types::transaction::{Transaction, TransactionOutput};
+use aptos_types::transaction::{Transaction, TransactionInfo, TransactionOutput};
 use aptos_vm::VMExecutor;
 use executor_types::ExecutedChunk;
 use fail::fail_point;
@@ -76,7 +76,20 @@ impl ChunkOutput {
         fail_point!("executor::vm_execute_chunk", |_| {
             Err(anyhow::anyhow!("Injected error in apply_to_ledger."))
         });
-        ApplyChunkOutput::apply(self, base_view)
+        ApplyChunkOutput::apply(self, base_view, None)
+    }
+
+    /// Like `apply_to_ledger`, but fails as soon as a transaction's state checkpoint hash differs
+    /// from the one in its (verified) `TransactionInfo`.
+    pub fn apply_to_ledger_against(
+        self,
+        base_view: &ExecutedTrees,
+        transaction_infos: &[TransactionInfo],
+    ) -> Result<(ExecutedChunk, Vec<Transaction>, Vec<Transaction>)> {
+        fail_point!("executor::vm_execute_chunk", |_| {
+            Err(anyhow::anyhow!("Injected error in apply_to_ledger."))
+        });
+        ApplyChunkOutput::apply(self, base_view, Some(transaction_infos))
     }
 
     pub fn trace_log_transaction_status(&self) {
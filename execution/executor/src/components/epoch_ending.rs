@@ -0,0 +1,170 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+#![forbid(unsafe_code)]
+
+use anyhow::{anyhow, ensure, Result};
+use aptos_crypto::{hash::TransactionAccumulatorHasher, HashValue};
+use aptos_types::{
+    block_info::{BlockInfo, Round},
+    epoch_state::EpochState,
+    ledger_info::{LedgerInfo, LedgerInfoWithSignatures},
+    proof::accumulator::InMemoryAccumulator,
+    transaction::Version,
+};
+use executor_types::{in_memory_state_calculator::ChunkStateUpdateResult, ExecutedChunk};
+
+/// What a ledger info ending the epoch with a chunk (or block) commits to, as computed locally.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EpochEndingInfo {
+    /// The version of the reconfiguration, the last transaction committed in the chunk.
+    pub version: Version,
+    /// The root hash of the transaction accumulator up to the reconfiguration.
+    pub executed_state_id: HashValue,
+    /// The root hash of the state checkpoint made at the reconfiguration.
+    pub state_checkpoint_hash: HashValue,
+    pub next_epoch_state: EpochState,
+}
+
+impl EpochEndingInfo {
+    /// The epoch ending info of the chunk the calculator produced `result` for, given the
+    /// transaction accumulator with the chunk's transactions appended, or `None` if the chunk
+    /// doesn't end the epoch.
+    pub fn from_state_update_result(
+        result: &ChunkStateUpdateResult,
+        txn_accumulator: &InMemoryAccumulator<TransactionAccumulatorHasher>,
+    ) -> Result<Option<Self>> {
+        Self::new(
+            result.next_epoch_state.as_ref(),
+            result.checkpoint_roots.last().copied(),
+            txn_accumulator,
+        )
+    }
+
+    /// The epoch ending info of a chunk already executed (or applied), or `None` if it doesn't
+    /// end the epoch.
+    pub fn from_executed_chunk(executed_chunk: &ExecutedChunk) -> Result<Option<Self>> {
+        let txn_accumulator = executed_chunk.result_view.txn_accumulator();
+        let last_checkpoint_root = executed_chunk.to_commit.last().and_then(|(_, txn_data)| {
+            txn_data
+                .txn_info
+                .state_checkpoint_hash()
+                .map(|hash| (txn_accumulator.version(), hash))
+        });
+        Self::new(
+            executed_chunk.next_epoch_state.as_ref(),
+            last_checkpoint_root,
+            txn_accumulator,
+        )
+    }
+
+    // Fails unless the last transaction, the reconfiguration, made the last checkpoint.
+    fn new(
+        next_epoch_state: Option<&EpochState>,
+        last_checkpoint_root: Option<(Version, HashValue)>,
+        txn_accumulator: &InMemoryAccumulator<TransactionAccumulatorHasher>,
+    ) -> Result<Option<Self>> {
+        let next_epoch_state = match next_epoch_state {
+            Some(next_epoch_state) => next_epoch_state.clone(),
+            None => return Ok(None),
+        };
+        ensure!(
+            txn_accumulator.num_leaves() > 0,
+            "Epoch ending without any transaction."
+        );
+        let version = txn_accumulator.version();
+        let state_checkpoint_hash = match last_checkpoint_root {
+            Some((checkpoint_version, hash)) if checkpoint_version == version => hash,
+            _ => {
+                return Err(anyhow!(
+                    "No state checkpoint at the reconfiguration at version {}, last one: {:?}",
+                    version,
+                    last_checkpoint_root,
+                ))
+            }
+        };
+        Ok(Some(Self {
+            version,
+            executed_state_id: txn_accumulator.root_hash(),
+            state_checkpoint_hash,
+            next_epoch_state,
+        }))
+    }
+
+    /// The `BlockInfo` of the block ending the epoch, which is the block `id` of `round` in
+    /// `epoch`, proposed at `timestamp_usecs`.
+    pub fn block_info(
+        &self,
+        epoch: u64,
+        round: Round,
+        id: HashValue,
+        timestamp_usecs: u64,
+    ) -> BlockInfo {
+        BlockInfo::new(
+            epoch,
+            round,
+            id,
+            self.executed_state_id,
+            self.version,
+            timestamp_usecs,
+            Some(self.next_epoch_state.clone()),
+        )
+    }
+
+    /// Makes sure `ledger_info` ends the epoch with the chunk, as computed locally.
+    pub fn ensure_matches(&self, ledger_info: &LedgerInfo) -> Result<()> {
+        ensure!(
+            ledger_info.transaction_accumulator_hash() == self.executed_state_id,
+            "Root hash of a given epoch LI does not match local computation."
+        );
+        ensure!(
+            ledger_info.version() == self.version,
+            "Version of a given epoch LI does not match local computation."
+        );
+        ensure!(
+            ledger_info.ends_epoch(),
+            "Epoch change LI does not carry validator set"
+        );
+        ensure!(
+            ledger_info.next_epoch_state() == Some(&self.next_epoch_state),
+            "New validator set of a given epoch LI does not match local computation"
+        );
+        Ok(())
+    }
+}
+
+/// The ledger info the chunk `executed_chunk` ends with, if any: `verified_target_li` if the chunk
+/// reaches it, otherwise `epoch_change_li`, which the chunk must end the epoch with if given.
+pub fn select_chunk_ending_ledger_info(
+    executed_chunk: &ExecutedChunk,
+    verified_target_li: &LedgerInfoWithSignatures,
+    epoch_change_li: Option<&LedgerInfoWithSignatures>,
+) -> Result<Option<LedgerInfoWithSignatures>> {
+    let result_accumulator = executed_chunk.result_view.txn_accumulator();
+
+    if verified_target_li.ledger_info().version() + 1 == result_accumulator.num_leaves() {
+        // If the chunk corresponds to the target LI, the target LI can be added to storage.
+        ensure!(
+            verified_target_li
+                .ledger_info()
+                .transaction_accumulator_hash()
+                == result_accumulator.root_hash(),
+            "Root hash in target ledger info does not match local computation."
+        );
+        Ok(Some(verified_target_li.clone()))
+    } else if let Some(epoch_change_li) = epoch_change_li {
+        // If the epoch change LI is present, it must match the version of the chunk.
+        let epoch_ending =
+            EpochEndingInfo::from_executed_chunk(executed_chunk)?.ok_or_else(|| {
+                anyhow!("Epoch change LI given, but the chunk doesn't end the epoch.")
+            })?;
+        epoch_ending.ensure_matches(epoch_change_li.ledger_info())?;
+        Ok(Some(epoch_change_li.clone()))
+    } else {
+        ensure!(
+            !executed_chunk.has_reconfiguration(),
+            "End of epoch chunk based on local computation but no EoE LedgerInfo provided."
+        );
+        Ok(None)
+    }
+}
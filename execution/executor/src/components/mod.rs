@@ -4,6 +4,8 @@
 #![forbid(unsafe_code)]
 
 pub mod apply_chunk_output;
+pub mod block_result_cache;
 pub mod block_tree;
 pub mod chunk_commit_queue;
 pub mod chunk_output;
+pub mod epoch_ending;
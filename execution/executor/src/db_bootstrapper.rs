@@ -3,7 +3,7 @@
 
 #![forbid(unsafe_code)]
 
-use crate::components::chunk_output::ChunkOutput;
+use crate::components::{chunk_output::ChunkOutput, epoch_ending::EpochEndingInfo};
 use anyhow::{anyhow, ensure, format_err, Result};
 use aptos_crypto::HashValue;
 use aptos_logger::prelude::*;
@@ -11,7 +11,7 @@ use aptos_state_view::{StateView, StateViewId};
 use aptos_types::{
     access_path::AccessPath,
     account_config::CORE_CODE_ADDRESS,
-    block_info::{BlockInfo, GENESIS_EPOCH, GENESIS_ROUND, GENESIS_TIMESTAMP_USECS},
+    block_info::{GENESIS_EPOCH, GENESIS_ROUND, GENESIS_TIMESTAMP_USECS},
     ledger_info::{LedgerInfo, LedgerInfoWithSignatures},
     on_chain_config::ConfigurationResource,
     state_store::state_key::StateKey,
@@ -160,22 +160,18 @@ pub fn calculate_genesis<V: VMExecutor>(
         );
         get_state_timestamp(&state_view)?
     };
+    let epoch_ending = EpochEndingInfo::from_executed_chunk(&output)?
+        .ok_or_else(|| format_err!("Genesis txn didn't output reconfig event."))?;
     ensure!(
-        output.next_epoch_state.is_some(),
-        "Genesis txn didn't output reconfig event."
+        epoch_ending.version == genesis_version,
+        "Genesis txn committed at version {}, expected {}.",
+        epoch_ending.version,
+        genesis_version,
     );
 
     let ledger_info_with_sigs = LedgerInfoWithSignatures::new(
         LedgerInfo::new(
-            BlockInfo::new(
-                epoch,
-                GENESIS_ROUND,
-                genesis_block_id(),
-                output.result_view.txn_accumulator().root_hash(),
-                genesis_version,
-                timestamp_usecs,
-                output.next_epoch_state.clone(),
-            ),
+            epoch_ending.block_info(epoch, GENESIS_ROUND, genesis_block_id(), timestamp_usecs),
             genesis_block_id(), /* consensus_data_hash */
         ),
         BTreeMap::default(), /* signatures */
@@ -16,6 +16,9 @@ use aptos_vm::VMExecutor;
 use executor_types::{BlockExecutorTrait, ChunkExecutorTrait};
 use storage_interface::{state_delta::StateDelta, DbReader, DbReaderWriter, DbWriter, StartupInfo};
 
+// To replay a failing case deterministically, run it with the state calculator's
+// `DETERMINISTIC_ENV_VAR` set (and `TRACE_WRITES_ENV_VAR` to log every write), which every
+// calculator the executors construct picks up.
 fn create_test_executor() -> BlockExecutor<FakeVM> {
     // setup fake db
     let fake_db = FakeDb {};
@@ -2,8 +2,11 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use aptos_metrics_core::{
-    exponential_buckets, register_histogram, register_int_counter, Histogram, IntCounter,
+    exponential_buckets, register_histogram, register_histogram_vec, register_int_counter,
+    register_int_counter_vec, register_int_gauge, Histogram, HistogramVec, IntCounter,
+    IntCounterVec, IntGauge,
 };
+use executor_types::{in_memory_state_calculator::StateUpdateStats, ChunkExecutionSummary};
 use once_cell::sync::Lazy;
 
 pub static APTOS_EXECUTOR_EXECUTE_CHUNK_SECONDS: Lazy<Histogram> = Lazy::new(|| {
@@ -107,3 +110,198 @@ pub static APTOS_EXECUTOR_TRANSACTIONS_SAVED: Lazy<Histogram> = Lazy::new(|| {
     )
     .unwrap()
 });
+
+pub static APTOS_EXECUTOR_TRANSACTIONS_BY_STATUS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        // metric name
+        "aptos_executor_transactions_by_status",
+        // metric description
+        "The number of transaction outputs applied in Aptos executor, by executor and status",
+        // metric labels (dimensions)
+        &["executor", "status"]
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_TRANSACTIONS_RETRIED_FOR_NEW_EPOCH: Lazy<IntCounterVec> =
+    Lazy::new(|| {
+        register_int_counter_vec!(
+            // metric name
+            "aptos_executor_transactions_retried_for_new_epoch",
+            // metric description
+            "The number of transactions retried in the next epoch in Aptos executor, by executor",
+            // metric labels (dimensions)
+            &["executor"]
+        )
+        .unwrap()
+    });
+
+pub static APTOS_EXECUTOR_GAS_USED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        // metric name
+        "aptos_executor_gas_used",
+        // metric description
+        "The gas used by the transactions kept in Aptos executor, by executor",
+        // metric labels (dimensions)
+        &["executor"]
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_EVENTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        // metric name
+        "aptos_executor_events",
+        // metric description
+        "The number of events emitted by the transactions kept in Aptos executor, by executor",
+        // metric labels (dimensions)
+        &["executor"]
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_WRITE_OPS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        // metric name
+        "aptos_executor_write_ops",
+        // metric description
+        "The number of write ops of the transactions kept in Aptos executor, by executor",
+        // metric labels (dimensions)
+        &["executor"]
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_WRITE_SET_BYTES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        // metric name
+        "aptos_executor_write_set_bytes",
+        // metric description
+        "The bytes written by the transactions kept in Aptos executor, by executor",
+        // metric labels (dimensions)
+        &["executor"]
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_RECONFIGS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        // metric name
+        "aptos_executor_reconfigs",
+        // metric description
+        "The number of chunks (or blocks) ending in a reconfiguration in Aptos executor, by executor",
+        // metric labels (dimensions)
+        &["executor"]
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_PROOF_CACHE_LOOKUPS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        // metric name
+        "aptos_executor_proof_cache_lookups",
+        // metric description
+        "The number of lookups in the proof cache shared by the chunks in Aptos executor, by result",
+        // metric labels (dimensions)
+        &["result"]
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_PROOF_CACHE_SIZE: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        // metric name
+        "aptos_executor_proof_cache_size",
+        // metric description
+        "The number of proofs in the proof cache shared by the chunks in Aptos executor"
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_BLOCK_RESULT_CACHE_LOOKUPS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        // metric name
+        "aptos_executor_block_result_cache_lookups",
+        // metric description
+        "The number of lookups in the cache of executed blocks in Aptos executor, by result",
+        // metric labels (dimensions)
+        &["result"]
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_BLOCK_RESULT_CACHE_SIZE: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        // metric name
+        "aptos_executor_block_result_cache_size",
+        // metric description
+        "The number of block outputs in the cache of executed blocks in Aptos executor"
+    )
+    .unwrap()
+});
+
+pub static APTOS_EXECUTOR_TXN_STATE_WRITE_BYTES: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        // metric name
+        "aptos_executor_txn_state_write_bytes",
+        // metric description
+        "The bytes of state items a transaction in a block created, overwrote or freed, by kind",
+        // metric labels (dimensions)
+        &["kind"],
+        exponential_buckets(/*start=*/ 1.0, /*factor=*/ 2.0, /*count=*/ 24).unwrap(),
+    )
+    .unwrap()
+});
+
+/// Exports the bytes of state items each transaction created, overwrote and freed, leaving out
+/// the transactions that didn't.
+pub fn record_state_write_bytes(state_update_stats: &[StateUpdateStats]) {
+    for stats in state_update_stats {
+        let overwritten_bytes_delta = stats.overwritten_bytes_delta;
+        for (kind, bytes) in [
+            ("new_keys", stats.new_key_bytes as i64),
+            ("overwrite_growth", overwritten_bytes_delta.max(0)),
+            ("overwrite_shrinkage", (-overwritten_bytes_delta).max(0)),
+            ("freed", stats.freed_bytes as i64),
+        ] {
+            if bytes > 0 {
+                APTOS_EXECUTOR_TXN_STATE_WRITE_BYTES
+                    .with_label_values(&[kind])
+                    .observe(bytes as f64);
+            }
+        }
+    }
+}
+
+/// Exports the totals of a chunk (or block) the `executor` ("chunk" or "block") just applied.
+pub fn record_execution_summary(executor: &str, summary: &ChunkExecutionSummary) {
+    for (status, num_txns) in [
+        ("keep", summary.num_kept),
+        ("discard", summary.num_discarded),
+        ("retry", summary.num_retried),
+    ] {
+        APTOS_EXECUTOR_TRANSACTIONS_BY_STATUS
+            .with_label_values(&[executor, status])
+            .inc_by(num_txns as u64);
+    }
+    APTOS_EXECUTOR_TRANSACTIONS_RETRIED_FOR_NEW_EPOCH
+        .with_label_values(&[executor])
+        .inc_by(summary.num_retried_for_new_epoch as u64);
+    APTOS_EXECUTOR_GAS_USED
+        .with_label_values(&[executor])
+        .inc_by(summary.gas_used);
+    APTOS_EXECUTOR_EVENTS
+        .with_label_values(&[executor])
+        .inc_by(summary.num_events as u64);
+    APTOS_EXECUTOR_WRITE_OPS
+        .with_label_values(&[executor])
+        .inc_by(summary.num_write_ops as u64);
+    APTOS_EXECUTOR_WRITE_SET_BYTES
+        .with_label_values(&[executor])
+        .inc_by(summary.write_set_bytes as u64);
+    if summary.has_reconfig {
+        APTOS_EXECUTOR_RECONFIGS
+            .with_label_values(&[executor])
+            .inc();
+    }
+}
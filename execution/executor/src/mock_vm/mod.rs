@@ -182,6 +182,26 @@ impl VMExecutor for MockVM {
 
         Ok(outputs)
     }
+
+    fn keys_to_prefetch(transactions: &[Transaction]) -> Vec<StateKey> {
+        transactions
+            .iter()
+            .filter_map(|txn| txn.as_signed_user_txn().ok())
+            .flat_map(|txn| match decode_transaction(txn) {
+                MockVMTransaction::Mint { sender, .. } => {
+                    vec![balance_ap(sender), seqnum_ap(sender)]
+                }
+                MockVMTransaction::Payment {
+                    sender, recipient, ..
+                } => vec![balance_ap(sender), seqnum_ap(sender), balance_ap(recipient)],
+                MockVMTransaction::Reconfiguration => vec![
+                    access_path_for_config(ValidatorSet::CONFIG_ID),
+                    AccessPath::new(CORE_CODE_ADDRESS, ConfigurationResource::resource_path()),
+                ],
+            })
+            .map(StateKey::AccessPath)
+            .collect()
+    }
 }
 
 fn read_balance(
@@ -184,6 +184,110 @@ fn test_executor_execute_or_apply_and_commit_chunk() {
     assert_eq!(li, ledger_info);
 }
 
+/// Commits `num_blocks` blocks of `block_size` transactions (each followed by a state checkpoint)
+/// in a separate DB, and returns all of them as a single chunk, with the ledger info ending it.
+fn create_chunk_of_blocks(
+    num_blocks: u64,
+    block_size: u64,
+) -> (TransactionListWithProof, LedgerInfoWithSignatures) {
+    let executor = tests::TestExecutor::new();
+    let mut parent_block_id = executor.committed_block_id();
+    let mut ledger_info = None;
+    for i in 0..num_blocks {
+        let txns = (0..block_size)
+            .map(|j| encode_mint_transaction(tests::gen_address(i * block_size + j), 100))
+            .collect();
+        let block_id = tests::gen_block_id(i + 1);
+        let output = executor
+            .execute_block((block_id, block(txns)), parent_block_id)
+            .unwrap();
+        let li = tests::gen_ledger_info(output.version(), output.root_hash(), block_id, i + 1);
+        executor.commit_blocks(vec![block_id], li.clone()).unwrap();
+        parent_block_id = block_id;
+        ledger_info = Some(li);
+    }
+
+    let ledger_info = ledger_info.unwrap();
+    let version = ledger_info.ledger_info().version();
+    let chunk = executor
+        .db
+        .reader
+        .get_transactions(1, version, version, false /* fetch_events */)
+        .unwrap();
+    (chunk, ledger_info)
+}
+
+fn assert_same_storage(db: &DbReaderWriter, expected_db: &DbReaderWriter) {
+    assert_eq!(
+        db.reader.get_latest_ledger_info().unwrap(),
+        expected_db.reader.get_latest_ledger_info().unwrap()
+    );
+    let trees = db.reader.get_latest_executed_trees().unwrap();
+    let expected_trees = expected_db.reader.get_latest_executed_trees().unwrap();
+    assert_eq!(
+        trees.txn_accumulator().root_hash(),
+        expected_trees.txn_accumulator().root_hash()
+    );
+    assert_eq!(
+        trees.state().current.root_hash(),
+        expected_trees.state().current.root_hash()
+    );
+}
+
+#[test]
+fn test_executor_execute_or_apply_and_commit_chunk_in_sub_chunks() {
+    // 10 blocks of 11 transactions, so sub-chunks of at least 25 transactions hold 3 blocks.
+    let (chunk, ledger_info) = create_chunk_of_blocks(10, 10);
+    let num_txns = chunk.transactions.len();
+
+    let TestExecutor {
+        _path: _expected_path,
+        db: expected_db,
+        executor,
+    } = TestExecutor::new();
+    let expected_notification = executor
+        .execute_and_commit_chunk(chunk.clone(), &ledger_info, None)
+        .unwrap();
+    assert_eq!(expected_notification.committed_transactions.len(), num_txns);
+
+    // Executing the chunk in sub-chunks ends up in the same state.
+    {
+        let TestExecutor { _path, db, .. } = TestExecutor::new();
+        let executor = ChunkExecutor::<MockVM>::new(db.clone()).with_sub_chunk_size(25);
+        let notification = executor
+            .execute_and_commit_chunk(chunk, &ledger_info, None)
+            .unwrap();
+        assert_eq!(
+            notification.committed_transactions,
+            expected_notification.committed_transactions
+        );
+        assert_eq!(
+            notification.committed_events,
+            expected_notification.committed_events
+        );
+        assert_same_storage(&db, &expected_db);
+    }
+
+    // So does applying the outputs in sub-chunks.
+    {
+        let version = ledger_info.ledger_info().version();
+        let outputs = expected_db
+            .reader
+            .get_transaction_outputs(1, version, version)
+            .unwrap();
+        let TestExecutor { _path, db, .. } = TestExecutor::new();
+        let executor = ChunkExecutor::<MockVM>::new(db.clone()).with_sub_chunk_size(25);
+        let notification = executor
+            .apply_and_commit_chunk(outputs, &ledger_info, None)
+            .unwrap();
+        assert_eq!(
+            notification.committed_transactions,
+            expected_notification.committed_transactions
+        );
+        assert_same_storage(&db, &expected_db);
+    }
+}
+
 #[test]
 fn test_executor_execute_and_commit_chunk_restart() {
     let first_batch_size = 30;
@@ -271,3 +375,84 @@ fn test_executor_execute_and_commit_chunk_local_result_mismatch() {
         .execute_and_commit_chunk(chunks[0].clone(), &ledger_info, None)
         .is_err());
 }
+
+#[cfg(feature = "failpoints")]
+#[test]
+fn test_executor_recovers_from_failpoints() {
+    use executor_types::failpoints::{COMMIT_CHUNK_AFTER_SAVE, STATE_CALCULATOR_FINISH};
+
+    let (chunks, ledger_info) = tests::create_transaction_chunks(vec![1..21, 21..41]);
+    let TestExecutor {
+        _path,
+        db,
+        executor,
+    } = TestExecutor::new();
+    let scenario = fail::FailScenario::setup();
+
+    // Failing while calculating the state leaves nothing behind.
+    fail::cfg(STATE_CALCULATOR_FINISH, "return").unwrap();
+    assert!(executor
+        .execute_and_commit_chunk(chunks[0].clone(), &ledger_info, None)
+        .is_err());
+    fail::remove(STATE_CALCULATOR_FINISH);
+    assert_eq!(db.reader.get_latest_version().unwrap(), 0);
+
+    // Failing right after the chunk is saved leaves it in the DB, but not in the executor.
+    fail::cfg(COMMIT_CHUNK_AFTER_SAVE, "return").unwrap();
+    assert!(executor
+        .execute_and_commit_chunk(chunks[0].clone(), &ledger_info, None)
+        .is_err());
+    fail::remove(COMMIT_CHUNK_AFTER_SAVE);
+    assert_eq!(db.reader.get_latest_version().unwrap(), 20);
+
+    // A restarted executor picks up from the DB, and syncs to the ledger info.
+    let executor = ChunkExecutor::<MockVM>::new(db.clone());
+    executor
+        .execute_and_commit_chunk(chunks[0].clone(), &ledger_info, None)
+        .unwrap();
+    executor
+        .execute_and_commit_chunk(chunks[1].clone(), &ledger_info, None)
+        .unwrap();
+    let li = db.reader.get_latest_ledger_info().unwrap();
+    assert_eq!(li, ledger_info);
+    assert_eq!(
+        db.reader
+            .get_latest_executed_trees()
+            .unwrap()
+            .txn_accumulator()
+            .root_hash(),
+        ledger_info.ledger_info().transaction_accumulator_hash()
+    );
+
+    scenario.teardown();
+}
+
+#[cfg(feature = "failpoints")]
+#[test]
+fn test_executor_resumes_after_failed_sub_chunk() {
+    // 10 blocks of 11 transactions, so sub-chunks of at least 25 transactions hold 3 blocks.
+    let (chunk, ledger_info) = create_chunk_of_blocks(10, 10);
+    let TestExecutor { _path, db, .. } = TestExecutor::new();
+    let executor = ChunkExecutor::<MockVM>::new(db.clone()).with_sub_chunk_size(25);
+    let scenario = fail::FailScenario::setup();
+
+    // Committing the second sub-chunk fails, after the first one was committed.
+    fail::cfg("executor::commit_chunk", "1*off->return").unwrap();
+    assert!(executor
+        .execute_and_commit_chunk(chunk.clone(), &ledger_info, None)
+        .is_err());
+    fail::remove("executor::commit_chunk");
+    assert_eq!(db.reader.get_latest_version().unwrap(), 33);
+
+    // Retrying only executes and commits the rest of the chunk.
+    let notification = executor
+        .execute_and_commit_chunk(chunk.clone(), &ledger_info, None)
+        .unwrap();
+    assert_eq!(
+        notification.committed_transactions,
+        chunk.transactions[33..].to_vec()
+    );
+    assert_eq!(db.reader.get_latest_ledger_info().unwrap(), ledger_info);
+
+    scenario.teardown();
+}
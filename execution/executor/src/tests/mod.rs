@@ -1,18 +1,25 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use std::{collections::BTreeMap, iter::once, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashSet},
+    iter::once,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use proptest::prelude::*;
 
 use aptos_crypto::{ed25519::Ed25519PrivateKey, HashValue, PrivateKey, SigningKey, Uniform};
-use aptos_state_view::StateViewId;
+use aptos_state_view::{StateView, StateViewId};
 use aptos_types::{
     account_address::AccountAddress,
     block_info::BlockInfo,
     chain_id::ChainId,
     ledger_info::{LedgerInfo, LedgerInfoWithSignatures},
-    proof::definition::LeafCount,
+    proof::{definition::LeafCount, SparseMerkleProof},
     state_store::{state_key::StateKey, state_value::StateValue},
     test_helpers::transaction_test_helpers::block,
     transaction::{
@@ -22,15 +29,24 @@ use aptos_types::{
     },
     write_set::{WriteOp, WriteSet, WriteSetMut},
 };
+use aptos_vm::VMExecutor;
 use aptosdb::AptosDB;
-use executor_types::{BlockExecutorTrait, ChunkExecutorTrait, TransactionReplayer};
-use storage_interface::{sync_proof_fetcher::SyncProofFetcher, DbReaderWriter, ExecutedTrees};
+use executor_types::{
+    BlockExecutorTrait, ChunkExecutionSummary, ChunkExecutorTrait, TransactionReplayer,
+};
+use storage_interface::{
+    sync_proof_fetcher::SyncProofFetcher, DbReader, DbReaderWriter, ExecutedTrees,
+};
 
 use crate::{
     block_executor::BlockExecutor,
     chunk_executor::ChunkExecutor,
-    components::chunk_output::ChunkOutput,
+    components::{
+        chunk_output::ChunkOutput,
+        epoch_ending::{select_chunk_ending_ledger_info, EpochEndingInfo},
+    },
     db_bootstrapper::{generate_waypoint, maybe_bootstrap},
+    metrics::APTOS_EXECUTOR_BLOCK_RESULT_CACHE_LOOKUPS,
     mock_vm::{
         encode_mint_transaction, encode_reconfiguration_transaction, encode_transfer_transaction,
         MockVM, DISCARD_STATUS, KEEP_STATUS,
@@ -156,6 +172,485 @@ fn test_executor_status() {
     );
 }
 
+#[test]
+fn test_chunk_execution_summary() {
+    let TestExecutor { _path, db, .. } = TestExecutor::new();
+    let ledger_view: ExecutedTrees = db.reader.get_latest_executed_trees().unwrap();
+    let state_view = || {
+        ledger_view
+            .verified_state_view(
+                StateViewId::Miscellaneous,
+                Arc::clone(&db.reader),
+                Arc::new(SyncProofFetcher::new(db.reader.clone())),
+            )
+            .unwrap()
+    };
+
+    // Kept, discarded, reconfiguration, and (following the reconfiguration) retried.
+    let transactions = vec![
+        encode_mint_transaction(gen_address(0), 100),
+        encode_transfer_transaction(gen_address(0), gen_address(1), 500),
+        encode_reconfiguration_transaction(gen_address(0)),
+        encode_mint_transaction(gen_address(1), 100),
+    ];
+    // The mock VM uses no gas, so charge each transaction some.
+    let outputs: Vec<TransactionOutput> =
+        <MockVM as VMExecutor>::execute_block(transactions.clone(), &state_view())
+            .unwrap()
+            .into_iter()
+            .zip(1..)
+            .map(|(output, gas_used)| {
+                TransactionOutput::new(
+                    output.write_set().clone(),
+                    output.events().to_vec(),
+                    gas_used * 10,
+                    output.status().clone(),
+                )
+            })
+            .collect();
+    let kept = [&outputs[0], &outputs[2]];
+
+    let (executed, _, to_retry) = ChunkOutput::by_transaction_output(
+        transactions.into_iter().zip(outputs.clone()).collect(),
+        state_view(),
+    )
+    .unwrap()
+    .apply_to_ledger(&ledger_view)
+    .unwrap();
+    assert_eq!(to_retry.len(), 1);
+
+    assert_eq!(
+        executed.execution_summary,
+        ChunkExecutionSummary {
+            num_kept: 2,
+            num_discarded: 1,
+            num_retried: 1,
+            num_retried_for_new_epoch: 1,
+            gas_used: 10 + 30,
+            num_events: kept.iter().map(|o| o.events().len()).sum(),
+            num_write_ops: kept.iter().map(|o| o.write_set().iter().count()).sum(),
+            write_set_bytes: kept
+                .iter()
+                .flat_map(|o| o.write_set().iter())
+                .map(|(_, write_op)| match write_op {
+                    WriteOp::Value(value) => value.len(),
+                    _ => 0,
+                })
+                .sum(),
+            has_reconfig: true,
+            state_cache: executed.execution_summary.state_cache,
+        }
+    );
+}
+
+#[test]
+fn test_reconfig_suffix_classification() {
+    let TestExecutor { _path, db, .. } = TestExecutor::new();
+    let ledger_view: ExecutedTrees = db.reader.get_latest_executed_trees().unwrap();
+    let state_view = || {
+        ledger_view
+            .verified_state_view(
+                StateViewId::Miscellaneous,
+                Arc::clone(&db.reader),
+                Arc::new(SyncProofFetcher::new(db.reader.clone())),
+            )
+            .unwrap()
+    };
+
+    // Kept, discarded, kept and kept, with a reconfiguration inserted at the start, in the middle
+    // and at the end.
+    for (reconfig_index, num_kept, num_discarded) in [(0, 1, 0), (2, 2, 1), (4, 4, 1)] {
+        let mut transactions = vec![
+            encode_mint_transaction(gen_address(0), 100),
+            encode_transfer_transaction(gen_address(0), gen_address(1), 500),
+            encode_mint_transaction(gen_address(1), 100),
+            encode_mint_transaction(gen_address(2), 100),
+        ];
+        transactions.insert(
+            reconfig_index,
+            encode_reconfiguration_transaction(gen_address(0)),
+        );
+        let num_retried = transactions.len() - reconfig_index - 1;
+
+        let (executed, to_discard, to_retry) =
+            ChunkOutput::by_transaction_execution::<MockVM>(transactions, state_view())
+                .unwrap()
+                .apply_to_ledger(&ledger_view)
+                .unwrap();
+        let summary = executed.execution_summary;
+        assert_eq!(summary.num_kept, num_kept);
+        assert_eq!(summary.num_discarded, num_discarded);
+        assert_eq!(summary.num_retried, num_retried);
+        assert_eq!(summary.num_retried_for_new_epoch, num_retried);
+        assert_eq!(executed.to_commit.len(), num_kept);
+        assert_eq!(to_discard.len(), num_discarded);
+        assert_eq!(to_retry.len(), num_retried);
+        assert!(executed.has_reconfiguration());
+    }
+}
+
+#[test]
+fn test_epoch_ending_info() {
+    let TestExecutor { _path, db, .. } = TestExecutor::new();
+    let ledger_view: ExecutedTrees = db.reader.get_latest_executed_trees().unwrap();
+    let execute = |transactions| {
+        let state_view = ledger_view
+            .verified_state_view(
+                StateViewId::Miscellaneous,
+                Arc::clone(&db.reader),
+                Arc::new(SyncProofFetcher::new(db.reader.clone())),
+            )
+            .unwrap();
+        let (executed, _, _) =
+            ChunkOutput::by_transaction_execution::<MockVM>(transactions, state_view)
+                .unwrap()
+                .apply_to_ledger(&ledger_view)
+                .unwrap();
+        executed
+    };
+
+    let executed = execute(vec![encode_mint_transaction(gen_address(0), 100)]);
+    assert_eq!(
+        EpochEndingInfo::from_executed_chunk(&executed).unwrap(),
+        None
+    );
+
+    let executed = execute(vec![
+        encode_mint_transaction(gen_address(0), 100),
+        encode_reconfiguration_transaction(gen_address(0)),
+    ]);
+    let epoch_ending = EpochEndingInfo::from_executed_chunk(&executed)
+        .unwrap()
+        .unwrap();
+    // The genesis transaction, then the two above.
+    let version = 2;
+    let (_, reconfig_data) = executed.to_commit.last().unwrap();
+    assert_eq!(
+        epoch_ending.state_checkpoint_hash,
+        reconfig_data.txn_info.state_checkpoint_hash().unwrap()
+    );
+    let block_id = gen_block_id(1);
+    assert_eq!(
+        epoch_ending.block_info(1, 3, block_id, 4),
+        BlockInfo::new(
+            1,
+            3,
+            block_id,
+            executed.result_view.txn_accumulator().root_hash(),
+            version,
+            4,
+            executed.next_epoch_state.clone(),
+        )
+    );
+    assert!(executed.next_epoch_state.is_some());
+
+    // An epoch change ledger info with that block info is accepted, one at another version isn't.
+    let ledger_info = |block_info| {
+        LedgerInfoWithSignatures::new(
+            LedgerInfo::new(block_info, HashValue::zero()),
+            BTreeMap::new(),
+        )
+    };
+    let epoch_change_li = ledger_info(epoch_ending.block_info(1, 3, block_id, 4));
+    let target_li = gen_ledger_info(version + 10, HashValue::zero(), block_id, 4);
+    assert_eq!(
+        select_chunk_ending_ledger_info(&executed, &target_li, Some(&epoch_change_li)).unwrap(),
+        Some(epoch_change_li)
+    );
+    let mut other_epoch_ending = epoch_ending;
+    other_epoch_ending.version -= 1;
+    let other_epoch_change_li = ledger_info(other_epoch_ending.block_info(1, 3, block_id, 4));
+    assert!(
+        select_chunk_ending_ledger_info(&executed, &target_li, Some(&other_epoch_change_li))
+            .is_err()
+    );
+}
+
+#[test]
+fn test_state_cache_stats() {
+    let TestExecutor { _path, db, .. } = TestExecutor::new();
+    let ledger_view: ExecutedTrees = db.reader.get_latest_executed_trees().unwrap();
+    let state_view = || {
+        ledger_view
+            .verified_state_view(
+                StateViewId::Miscellaneous,
+                Arc::clone(&db.reader),
+                Arc::new(SyncProofFetcher::new(db.reader.clone())),
+            )
+            .unwrap()
+    };
+
+    // Three keys read from storage, then two of them again, from the cache. None of them exist.
+    let view = state_view();
+    let keys: Vec<_> = (0..3)
+        .map(|i| StateKey::Raw(format!("key_{}", i).into_bytes()))
+        .collect();
+    for key in keys.iter().chain(&keys[..2]) {
+        assert_eq!(view.get_state_value(key).unwrap(), None);
+    }
+    let stats = view.stats();
+    assert_eq!(stats.num_cache_hits, 2);
+    assert_eq!(stats.num_speculative_reads, 0);
+    assert_eq!(stats.num_storage_reads, 3);
+    assert_eq!(stats.num_cached_keys, 3);
+    assert_eq!(stats.cached_bytes, 0);
+    // The proofs are counted once the fetcher is done.
+    assert_eq!(stats.num_proofs, 0);
+    assert_eq!(view.into_state_cache().stats.num_proofs, 3);
+
+    // Applying the outputs of a block reads each key written once, and the stats are attached to
+    // the block's summary.
+    let transactions: Vec<_> = (0..3)
+        .map(|i| encode_mint_transaction(gen_address(i), 100))
+        .collect();
+    let outputs = MockVM::execute_block(transactions.clone(), &state_view()).unwrap();
+    let num_keys = outputs
+        .iter()
+        .flat_map(|output| output.write_set().iter())
+        .map(|(key, _)| key)
+        .collect::<HashSet<_>>()
+        .len();
+    let (executed, _, _) = ChunkOutput::by_transaction_output(
+        transactions.into_iter().zip(outputs).collect(),
+        state_view(),
+    )
+    .unwrap()
+    .apply_to_ledger(&ledger_view)
+    .unwrap();
+    let stats = executed.execution_summary.state_cache;
+    assert_eq!(stats.num_cache_hits, 0);
+    assert_eq!(stats.num_storage_reads, num_keys);
+    assert_eq!(stats.num_proofs, num_keys);
+    assert_eq!(stats.num_cached_keys, num_keys);
+}
+
+/// Counts the calls reading state values with proofs, one at a time or in batches.
+struct CountingReader {
+    reader: Arc<dyn DbReader>,
+    num_single_reads: AtomicUsize,
+    num_batch_reads: AtomicUsize,
+    num_value_reads: AtomicUsize,
+}
+
+impl CountingReader {
+    fn new(reader: Arc<dyn DbReader>) -> Self {
+        Self {
+            reader,
+            num_single_reads: AtomicUsize::new(0),
+            num_batch_reads: AtomicUsize::new(0),
+            num_value_reads: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl DbReader for CountingReader {
+    fn get_latest_executed_trees(&self) -> anyhow::Result<ExecutedTrees> {
+        self.reader.get_latest_executed_trees()
+    }
+
+    fn get_state_snapshot_before(
+        &self,
+        next_version: Version,
+    ) -> anyhow::Result<Option<(Version, HashValue)>> {
+        self.reader.get_state_snapshot_before(next_version)
+    }
+
+    fn get_state_value_with_proof_by_version(
+        &self,
+        state_key: &StateKey,
+        version: Version,
+    ) -> anyhow::Result<(Option<StateValue>, SparseMerkleProof)> {
+        self.num_single_reads.fetch_add(1, Ordering::Relaxed);
+        self.reader
+            .get_state_value_with_proof_by_version(state_key, version)
+    }
+
+    fn get_state_values_with_proof_by_version(
+        &self,
+        state_keys: &[StateKey],
+        version: Version,
+    ) -> anyhow::Result<Vec<(Option<StateValue>, SparseMerkleProof)>> {
+        self.num_batch_reads.fetch_add(1, Ordering::Relaxed);
+        self.reader
+            .get_state_values_with_proof_by_version(state_keys, version)
+    }
+
+    fn get_state_value_by_version(
+        &self,
+        state_key: &StateKey,
+        version: Version,
+    ) -> anyhow::Result<Option<StateValue>> {
+        self.num_value_reads.fetch_add(1, Ordering::Relaxed);
+        self.reader.get_state_value_by_version(state_key, version)
+    }
+}
+
+#[test]
+fn test_prefetch() {
+    let TestExecutor { _path, db, .. } = TestExecutor::new();
+    let ledger_view: ExecutedTrees = db.reader.get_latest_executed_trees().unwrap();
+    let transactions: Vec<_> = (0..5)
+        .map(|i| encode_mint_transaction(gen_address(i), 100))
+        .chain((0..5).map(|i| encode_transfer_transaction(gen_address(i), gen_address(i + 1), 50)))
+        .collect();
+
+    // Returns the executed block and the numbers of single and batch reads.
+    let execute = |prefetch: bool| {
+        let reader = Arc::new(CountingReader::new(db.reader.clone()));
+        let state_view = ledger_view
+            .verified_state_view(
+                StateViewId::Miscellaneous,
+                reader.clone(),
+                Arc::new(SyncProofFetcher::new(reader.clone())),
+            )
+            .unwrap();
+        if prefetch {
+            state_view
+                .prefetch(&MockVM::keys_to_prefetch(&transactions))
+                .unwrap();
+        }
+        let (executed, _, _) =
+            ChunkOutput::by_transaction_execution::<MockVM>(transactions.clone(), state_view)
+                .unwrap()
+                .apply_to_ledger(&ledger_view)
+                .unwrap();
+        (
+            executed,
+            reader.num_single_reads.load(Ordering::Relaxed),
+            reader.num_batch_reads.load(Ordering::Relaxed),
+        )
+    };
+
+    let (executed, num_single_reads, num_batch_reads) = execute(false);
+    // The balances and sequence numbers of the five senders, and the balance of the last recipient.
+    assert_eq!((num_single_reads, num_batch_reads), (11, 0));
+    let (prefetched, num_single_reads, num_batch_reads) = execute(true);
+    assert_eq!((num_single_reads, num_batch_reads), (0, 1));
+
+    assert_eq!(prefetched.status, executed.status);
+    assert_eq!(
+        prefetched.result_view.state().root_hash(),
+        executed.result_view.state().root_hash()
+    );
+    assert_eq!(
+        prefetched.result_view.txn_accumulator().root_hash(),
+        executed.result_view.txn_accumulator().root_hash()
+    );
+}
+
+#[test]
+fn test_proof_cache() {
+    let (chunks, ledger_info) = create_transaction_chunks(vec![1..11, 11..21, 21..31]);
+
+    // Executes every chunk twice, resetting the executor in between as state sync does when a
+    // chunk fails, then commits it. Returns the resulting ledger and the numbers of reads of
+    // values with proofs and of values alone.
+    let execute = |proof_cache: bool| {
+        let TestExecutor { _path, db, .. } = TestExecutor::new();
+        let reader = Arc::new(CountingReader::new(db.reader.clone()));
+        let executor = ChunkExecutor::<MockVM>::new(DbReaderWriter {
+            reader: reader.clone(),
+            writer: db.writer.clone(),
+        });
+        let executor = if proof_cache {
+            executor.with_proof_cache()
+        } else {
+            executor
+        };
+        for chunk in &chunks {
+            executor
+                .execute_chunk(chunk.clone(), &ledger_info, None)
+                .unwrap();
+            executor.reset().unwrap();
+            executor
+                .execute_chunk(chunk.clone(), &ledger_info, None)
+                .unwrap();
+            executor.commit_chunk().unwrap();
+        }
+        (
+            db.reader.get_latest_executed_trees().unwrap(),
+            reader.num_single_reads.load(Ordering::Relaxed),
+            reader.num_value_reads.load(Ordering::Relaxed),
+        )
+    };
+
+    let (executed, num_proof_reads, num_value_reads) = execute(false);
+    assert!(num_proof_reads > 0);
+    assert_eq!(num_value_reads, 0);
+    // The second execution of every chunk finds the proofs of the first one in the cache.
+    let (cached, num_cached_proof_reads, num_cached_value_reads) = execute(true);
+    assert_eq!(num_cached_proof_reads * 2, num_proof_reads);
+    assert_eq!(num_cached_value_reads, num_cached_proof_reads);
+
+    assert_eq!(cached.state().root_hash(), executed.state().root_hash());
+    assert_eq!(
+        cached.txn_accumulator().root_hash(),
+        executed.txn_accumulator().root_hash()
+    );
+}
+
+#[test]
+fn test_block_result_cache() {
+    let blocks: Vec<_> = (1..=2)
+        .map(|i| {
+            let txn = encode_mint_transaction(gen_address(i), 100);
+            (gen_block_id(i), block(vec![txn]))
+        })
+        .collect();
+
+    // Executes the blocks twice, resetting the executor in between as consensus does when
+    // execution fails, then commits them. Returns the resulting ledger.
+    let execute = |result_cache: bool| {
+        let TestExecutor { _path, db, .. } = TestExecutor::new();
+        let executor = BlockExecutor::<MockVM>::new(db.clone());
+        let executor = if result_cache {
+            executor.with_result_cache(10)
+        } else {
+            executor
+        };
+        let execute_blocks = || {
+            let mut parent_block_id = executor.committed_block_id();
+            let mut outputs = Vec::new();
+            for (block_id, txns) in &blocks {
+                outputs.push(
+                    executor
+                        .execute_block((*block_id, txns.clone()), parent_block_id)
+                        .unwrap(),
+                );
+                parent_block_id = *block_id;
+            }
+            outputs
+        };
+        let outputs = execute_blocks();
+        executor.reset();
+        assert_eq!(execute_blocks(), outputs);
+
+        let output = outputs.last().unwrap();
+        let ledger_info = gen_ledger_info(output.version(), output.root_hash(), gen_block_id(2), 2);
+        executor
+            .commit_blocks(vec![gen_block_id(1), gen_block_id(2)], ledger_info)
+            .unwrap();
+        db.reader.get_latest_executed_trees().unwrap()
+    };
+
+    let num_hits = || {
+        APTOS_EXECUTOR_BLOCK_RESULT_CACHE_LOOKUPS
+            .with_label_values(&["hit"])
+            .get()
+    };
+    let executed = execute(false);
+    // Both blocks are served from the cache the second time.
+    let num_hits_before = num_hits();
+    let cached = execute(true);
+    assert_eq!(num_hits() - num_hits_before, 2);
+
+    assert_eq!(cached.state().root_hash(), executed.state().root_hash());
+    assert_eq!(
+        cached.txn_accumulator().root_hash(),
+        executed.txn_accumulator().root_hash()
+    );
+}
+
 #[test]
 fn test_executor_one_block() {
     let executor = TestExecutor::new();
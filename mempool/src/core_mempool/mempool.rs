@@ -123,6 +123,20 @@ impl Mempool {
         self.transactions.get_by_hash(hash)
     }
 
+    pub(crate) fn get_mempool_txn_by_hash(&self, hash: HashValue) -> Option<MempoolTransaction> {
+        self.transactions.get_mempool_txn_by_hash(hash)
+    }
+
+    /// Fetches up to `limit` of the account's transactions, ordered by sequence number.
+    pub(crate) fn get_mempool_txns_by_account(
+        &self,
+        address: &AccountAddress,
+        limit: usize,
+    ) -> Vec<MempoolTransaction> {
+        self.transactions
+            .get_mempool_txns_by_account(address, limit)
+    }
+
     /// Used to add a transaction to the Mempool.
     /// Performs basic validation: checks account's sequence number.
     pub(crate) fn add_txn(
@@ -7,7 +7,7 @@ use aptos_types::{
     transaction::SignedTransaction,
 };
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
 #[derive(Clone, Debug)]
 pub struct MempoolTransaction {
@@ -17,6 +17,8 @@ pub struct MempoolTransaction {
     pub ranking_score: u64,
     pub timeline_state: TimelineState,
     pub sequence_info: SequenceInfo,
+    // When the transaction was inserted into mempool.
+    pub insertion_time: SystemTime,
 }
 
 impl MempoolTransaction {
@@ -36,6 +38,7 @@ impl MempoolTransaction {
             expiration_time,
             ranking_score,
             timeline_state,
+            insertion_time: SystemTime::now(),
         }
     }
     pub(crate) fn get_sender(&self) -> AccountAddress {
@@ -114,6 +114,25 @@ impl TransactionStore {
             .cloned()
     }
 
+    /// Fetch mempool transaction by committed hash.
+    pub(crate) fn get_mempool_txn_by_hash(&self, hash: HashValue) -> Option<MempoolTransaction> {
+        self.hash_index
+            .get(&hash)
+            .and_then(|(address, seq)| self.get_mempool_txn(address, *seq))
+    }
+
+    /// Fetch up to `limit` of the account's mempool transactions, ordered by sequence number.
+    pub(crate) fn get_mempool_txns_by_account(
+        &self,
+        address: &AccountAddress,
+        limit: usize,
+    ) -> Vec<MempoolTransaction> {
+        self.transactions
+            .get(address)
+            .map(|txns| txns.values().take(limit).cloned().collect())
+            .unwrap_or_default()
+    }
+
     /// Insert transaction into TransactionStore. Performs validation checks and updates indexes.
     pub(crate) fn insert(&mut self, txn: MempoolTransaction) -> MempoolStatus {
         let address = txn.get_sender();
@@ -8,8 +8,8 @@ use crate::{
     logging::{LogEntry, LogEvent, LogSchema},
     network::{BroadcastError, MempoolSyncMsg},
     shared_mempool::types::{
-        notify_subscribers, ScheduledBroadcast, SharedMempool, SharedMempoolNotification,
-        SubmissionStatusBundle,
+        notify_subscribers, PendingTransactionInfo, ScheduledBroadcast, SharedMempool,
+        SharedMempoolNotification, SubmissionStatusBundle,
     },
     QuorumStoreRequest, QuorumStoreResponse, SubmissionStatus,
 };
@@ -20,6 +20,7 @@ use aptos_infallible::{Mutex, RwLock};
 use aptos_logger::prelude::*;
 use aptos_metrics_core::HistogramTimer;
 use aptos_types::{
+    account_address::AccountAddress,
     mempool_status::{MempoolStatus, MempoolStatusCode},
     on_chain_config::OnChainConfigPayload,
     transaction::SignedTransaction,
@@ -142,6 +143,59 @@ pub(crate) async fn process_client_get_transaction<V>(
     }
 }
 
+pub(crate) async fn process_client_get_pending_transaction<V>(
+    smp: SharedMempool<V>,
+    hash: HashValue,
+    callback: oneshot::Sender<Option<PendingTransactionInfo>>,
+    timer: HistogramTimer,
+) where
+    V: TransactionValidation,
+{
+    timer.stop_and_record();
+    let _timer = counters::process_get_txn_latency_timer_client();
+    let txn = smp
+        .mempool
+        .lock()
+        .get_mempool_txn_by_hash(hash)
+        .map(PendingTransactionInfo::from);
+
+    if callback.send(txn).is_err() {
+        error!(LogSchema::event_log(
+            LogEntry::GetTransaction,
+            LogEvent::CallbackFail
+        ));
+        counters::CLIENT_CALLBACK_FAIL.inc();
+    }
+}
+
+pub(crate) async fn process_client_get_account_pending_transactions<V>(
+    smp: SharedMempool<V>,
+    address: AccountAddress,
+    limit: usize,
+    callback: oneshot::Sender<Vec<PendingTransactionInfo>>,
+    timer: HistogramTimer,
+) where
+    V: TransactionValidation,
+{
+    timer.stop_and_record();
+    let _timer = counters::process_get_txn_latency_timer_client();
+    let txns = smp
+        .mempool
+        .lock()
+        .get_mempool_txns_by_account(&address, limit)
+        .into_iter()
+        .map(PendingTransactionInfo::from)
+        .collect();
+
+    if callback.send(txns).is_err() {
+        error!(LogSchema::event_log(
+            LogEntry::GetTransaction,
+            LogEvent::CallbackFail
+        ));
+        counters::CLIENT_CALLBACK_FAIL.inc();
+    }
+}
+
 /// Processes transactions from other nodes.
 pub(crate) async fn process_transaction_broadcast<V>(
     smp: SharedMempool<V>,
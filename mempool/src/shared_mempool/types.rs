@@ -3,7 +3,8 @@
 
 //! Objects used by/related to shared mempool
 use crate::{
-    core_mempool::CoreMempool, network::MempoolNetworkInterface,
+    core_mempool::{CoreMempool, MempoolTransaction},
+    network::MempoolNetworkInterface,
     shared_mempool::network::MempoolNetworkSender,
 };
 use anyhow::Result;
@@ -14,7 +15,8 @@ use aptos_config::{
 use aptos_crypto::HashValue;
 use aptos_infallible::{Mutex, RwLock};
 use aptos_types::{
-    mempool_status::MempoolStatus, transaction::SignedTransaction, vm_status::DiscardedVMStatus,
+    account_address::AccountAddress, mempool_status::MempoolStatus, transaction::SignedTransaction,
+    vm_status::DiscardedVMStatus,
 };
 use consensus_types::common::TransactionSummary;
 use futures::{
@@ -205,9 +207,37 @@ pub type SubmissionStatus = (MempoolStatus, Option<DiscardedVMStatus>);
 
 pub type SubmissionStatusBundle = (SignedTransaction, SubmissionStatus);
 
+/// A transaction waiting in mempool, along with how mempool is treating it.
+#[derive(Clone, Debug)]
+pub struct PendingTransactionInfo {
+    pub txn: SignedTransaction,
+    /// When the transaction was inserted into mempool
+    pub insertion_time: SystemTime,
+    /// The score mempool orders transactions by when forming blocks
+    pub ranking_score: u64,
+}
+
+impl From<MempoolTransaction> for PendingTransactionInfo {
+    fn from(txn: MempoolTransaction) -> Self {
+        Self {
+            txn: txn.txn,
+            insertion_time: txn.insertion_time,
+            ranking_score: txn.ranking_score,
+        }
+    }
+}
+
 pub enum MempoolClientRequest {
     SubmitTransaction(SignedTransaction, oneshot::Sender<Result<SubmissionStatus>>),
     GetTransactionByHash(HashValue, oneshot::Sender<Option<SignedTransaction>>),
+    /// Looks up a transaction by hash, along with its mempool metadata
+    GetPendingTransactionByHash(HashValue, oneshot::Sender<Option<PendingTransactionInfo>>),
+    /// Lists up to the given number of an account's transactions, ordered by sequence number
+    GetPendingTransactionsByAccount(
+        AccountAddress,
+        usize,
+        oneshot::Sender<Vec<PendingTransactionInfo>>,
+    ),
 }
 
 pub type MempoolClientSender = mpsc::Sender<MempoolClientRequest>;
@@ -672,3 +672,45 @@ fn test_get_transaction_by_hash_after_the_txn_is_updated() {
     let txn_by_new_hash = pool.get_by_hash(new_txn_hash);
     assert_eq!(txn_by_new_hash, Some(new_txn));
 }
+
+#[test]
+fn test_get_mempool_transactions_by_account() {
+    let mut pool = setup_mempool().0;
+    // Sequence number 0 is missing, so all of the account's txns are parked
+    let txns = add_txns_to_mempool(
+        &mut pool,
+        vec![
+            TestTransaction::new(0, 3, 1),
+            TestTransaction::new(0, 1, 7),
+            TestTransaction::new(0, 2, 5),
+            TestTransaction::new(1, 0, 1),
+        ],
+    );
+
+    let mempool_txn = pool
+        .get_mempool_txn_by_hash(txns[1].clone().committed_hash())
+        .unwrap();
+    assert_eq!(mempool_txn.txn, txns[1]);
+    assert_eq!(mempool_txn.ranking_score, 7);
+    assert!(mempool_txn.insertion_time <= SystemTime::now());
+    assert!(pool.get_mempool_txn_by_hash(HashValue::random()).is_none());
+
+    // Ordered by sequence number, and limited
+    let account_txns: Vec<_> = pool
+        .get_mempool_txns_by_account(&TestTransaction::get_address(0), 10)
+        .into_iter()
+        .map(|txn| txn.txn)
+        .collect();
+    assert_eq!(
+        account_txns,
+        vec![txns[1].clone(), txns[2].clone(), txns[0].clone()]
+    );
+    assert_eq!(
+        pool.get_mempool_txns_by_account(&TestTransaction::get_address(0), 2)
+            .len(),
+        2
+    );
+    assert!(pool
+        .get_mempool_txns_by_account(&TestTransaction::get_address(2), 10)
+        .is_empty());
+}
@@ -744,24 +744,32 @@ impl DataStreamEngine for ContinuousTransactionStreamEngine {
             }
 
             // Create the client requests for the target
-            let optimal_chunk_sizes = match &self.request {
-                StreamRequest::ContinuouslyStreamTransactions(_) => {
+            let (optimal_chunk_size, max_chunk_size) = match &self.request {
+                StreamRequest::ContinuouslyStreamTransactions(request) => (
                     global_data_summary
                         .optimal_chunk_sizes
-                        .transaction_chunk_size
-                }
-                StreamRequest::ContinuouslyStreamTransactionOutputs(_) => {
+                        .transaction_chunk_size,
+                    request.max_chunk_size,
+                ),
+                StreamRequest::ContinuouslyStreamTransactionOutputs(request) => (
                     global_data_summary
                         .optimal_chunk_sizes
-                        .transaction_output_chunk_size
-                }
+                        .transaction_output_chunk_size,
+                    request.max_chunk_size,
+                ),
                 request => invalid_stream_request!(request),
             };
+            let chunk_size = match max_chunk_size {
+                Some(max_chunk_size) if max_chunk_size > 0 => {
+                    cmp::min(optimal_chunk_size, max_chunk_size)
+                }
+                _ => optimal_chunk_size,
+            };
             let client_requests = create_data_client_requests(
                 next_request_version,
                 target_ledger_info.ledger_info().version(),
                 max_number_of_requests,
-                optimal_chunk_sizes,
+                chunk_size,
                 self.clone().into(),
             )?;
             self.update_request_tracking(&client_requests, &target_ledger_info)?;
@@ -77,11 +77,15 @@ pub trait DataStreamingClient {
     ///
     /// Note: if a `target` is provided, the stream will terminate once it reaches
     /// the target. Otherwise, it will continue indefinitely.
+    ///
+    /// If `max_chunk_size` is provided, transaction outputs will be requested
+    /// in chunks no larger than `max_chunk_size` (when syncing to a target).
     async fn continuously_stream_transaction_outputs(
         &self,
         known_version: u64,
         known_epoch: u64,
         target: Option<LedgerInfoWithSignatures>,
+        max_chunk_size: Option<u64>,
     ) -> Result<DataStreamListener, Error>;
 
     /// Continuously streams transactions with proofs as the blockchain
@@ -96,12 +100,16 @@ pub trait DataStreamingClient {
     ///
     /// Note: if a `target` is provided, the stream will terminate once it reaches
     /// the target. Otherwise, it will continue indefinitely.
+    ///
+    /// If `max_chunk_size` is provided, transactions will be requested in
+    /// chunks no larger than `max_chunk_size` (when syncing to a target).
     async fn continuously_stream_transactions(
         &self,
         start_version: Version,
         start_epoch: Epoch,
         include_events: bool,
         target: Option<LedgerInfoWithSignatures>,
+        max_chunk_size: Option<u64>,
     ) -> Result<DataStreamListener, Error>;
 
     /// Terminates the stream that sent the notification with the given
@@ -196,6 +204,7 @@ pub struct ContinuouslyStreamTransactionsRequest {
     pub known_epoch: Epoch,
     pub include_events: bool,
     pub target: Option<LedgerInfoWithSignatures>,
+    pub max_chunk_size: Option<u64>,
 }
 
 /// A client request for continuously streaming transaction outputs with proofs
@@ -204,6 +213,7 @@ pub struct ContinuouslyStreamTransactionOutputsRequest {
     pub known_version: Version,
     pub known_epoch: Epoch,
     pub target: Option<LedgerInfoWithSignatures>,
+    pub max_chunk_size: Option<u64>,
 }
 
 /// A client request for terminating a stream and providing payload feedback.
@@ -333,12 +343,14 @@ impl DataStreamingClient for StreamingServiceClient {
         known_version: u64,
         known_epoch: u64,
         target: Option<LedgerInfoWithSignatures>,
+        max_chunk_size: Option<u64>,
     ) -> Result<DataStreamListener, Error> {
         let client_request = StreamRequest::ContinuouslyStreamTransactionOutputs(
             ContinuouslyStreamTransactionOutputsRequest {
                 known_version,
                 known_epoch,
                 target,
+                max_chunk_size,
             },
         );
         self.send_request_and_await_response(client_request).await
@@ -350,6 +362,7 @@ impl DataStreamingClient for StreamingServiceClient {
         known_epoch: u64,
         include_events: bool,
         target: Option<LedgerInfoWithSignatures>,
+        max_chunk_size: Option<u64>,
     ) -> Result<DataStreamListener, Error> {
         let client_request =
             StreamRequest::ContinuouslyStreamTransactions(ContinuouslyStreamTransactionsRequest {
@@ -357,6 +370,7 @@ impl DataStreamingClient for StreamingServiceClient {
                 known_epoch,
                 include_events,
                 target,
+                max_chunk_size,
             });
         self.send_request_and_await_response(client_request).await
     }
@@ -2,11 +2,18 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    data_notification::{DataClientRequest, EpochEndingLedgerInfosRequest},
+    data_notification::{
+        DataClientRequest, EpochEndingLedgerInfosRequest, TransactionOutputsWithProofRequest,
+    },
     error::Error,
-    stream_engine::{DataStreamEngine, EpochEndingStreamEngine, StreamEngine},
-    streaming_client::{GetAllEpochEndingLedgerInfosRequest, StreamRequest},
-    tests::utils::initialize_logger,
+    stream_engine::{
+        ContinuousTransactionStreamEngine, DataStreamEngine, EpochEndingStreamEngine, StreamEngine,
+    },
+    streaming_client::{
+        ContinuouslyStreamTransactionOutputsRequest, GetAllEpochEndingLedgerInfosRequest,
+        StreamRequest,
+    },
+    tests::utils::{create_ledger_info, initialize_logger},
 };
 use aptos_data_client::{GlobalDataSummary, OptimalChunkSizes, ResponsePayload};
 use aptos_id_generator::U64IdGenerator;
@@ -130,6 +137,35 @@ fn test_create_epoch_ending_requests_dynamic() {
     assert_ok!(client_requests);
 }
 
+#[test]
+fn test_create_continuous_output_requests_max_chunk_size() {
+    // Create a batch of client requests without a max chunk size and verify the result
+    let target_version = 1000;
+    let optimal_chunk_size = 100;
+    let mut stream_engine = create_continuous_output_stream_engine(0, target_version, None);
+    let client_requests = stream_engine
+        .create_data_client_requests(3, &create_output_chunk_sizes(optimal_chunk_size))
+        .unwrap();
+    verify_output_requests(client_requests, 1, optimal_chunk_size, target_version);
+
+    // Create a batch of client requests with a smaller max chunk size and verify the result
+    let max_chunk_size = 10;
+    let mut stream_engine =
+        create_continuous_output_stream_engine(0, target_version, Some(max_chunk_size));
+    let client_requests = stream_engine
+        .create_data_client_requests(3, &create_output_chunk_sizes(optimal_chunk_size))
+        .unwrap();
+    verify_output_requests(client_requests, 1, max_chunk_size, target_version);
+
+    // Create a batch of client requests with a larger max chunk size and verify the result
+    let mut stream_engine =
+        create_continuous_output_stream_engine(0, target_version, Some(optimal_chunk_size * 2));
+    let client_requests = stream_engine
+        .create_data_client_requests(3, &create_output_chunk_sizes(optimal_chunk_size))
+        .unwrap();
+    verify_output_requests(client_requests, 1, optimal_chunk_size, target_version);
+}
+
 #[test]
 fn test_epoch_ending_stream_engine() {
     // Create an epoch ending stream request
@@ -265,6 +301,66 @@ fn create_epoch_ending_stream_engine(start_epoch: u64, end_epoch: u64) -> EpochE
     }
 }
 
+fn create_continuous_output_stream_engine(
+    known_version: u64,
+    target_version: u64,
+    max_chunk_size: Option<u64>,
+) -> ContinuousTransactionStreamEngine {
+    // Create a continuous transaction output stream request
+    let stream_request = StreamRequest::ContinuouslyStreamTransactionOutputs(
+        ContinuouslyStreamTransactionOutputsRequest {
+            known_version,
+            known_epoch: 0,
+            target: None,
+            max_chunk_size,
+        },
+    );
+
+    // Create a new continuous stream engine and set the target
+    match StreamEngine::new(&stream_request, &GlobalDataSummary::empty().advertised_data).unwrap() {
+        StreamEngine::ContinuousTransactionStreamEngine(mut stream_engine) => {
+            stream_engine.current_target_ledger_info =
+                Some(create_ledger_info(target_version, 0, false));
+            stream_engine
+        }
+        unexpected_engine => {
+            panic!(
+                "Expected continuous transaction stream engine but got {:?}",
+                unexpected_engine
+            );
+        }
+    }
+}
+
+fn create_output_chunk_sizes(transaction_output_chunk_size: u64) -> GlobalDataSummary {
+    let mut optimal_chunk_sizes = OptimalChunkSizes::empty();
+    optimal_chunk_sizes.transaction_output_chunk_size = transaction_output_chunk_size;
+
+    let mut global_data_summary = GlobalDataSummary::empty();
+    global_data_summary.optimal_chunk_sizes = optimal_chunk_sizes;
+
+    global_data_summary
+}
+
+fn verify_output_requests(
+    client_requests: Vec<DataClientRequest>,
+    start_version: u64,
+    chunk_size: u64,
+    proof_version: u64,
+) {
+    assert!(!client_requests.is_empty());
+    for (i, client_request) in client_requests.iter().enumerate() {
+        let request_start_version = start_version + (i as u64 * chunk_size);
+        let expected_request =
+            DataClientRequest::TransactionOutputsWithProof(TransactionOutputsWithProofRequest {
+                start_version: request_start_version,
+                end_version: request_start_version + chunk_size - 1,
+                proof_version,
+            });
+        assert_eq!(*client_request, expected_request);
+    }
+}
+
 fn create_epoch_ending_chunk_sizes(epoch_chunk_size: u64) -> GlobalDataSummary {
     let mut optimal_chunk_sizes = OptimalChunkSizes::empty();
     optimal_chunk_sizes.epoch_chunk_size = epoch_chunk_size;
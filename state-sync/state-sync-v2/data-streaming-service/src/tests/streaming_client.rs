@@ -155,6 +155,7 @@ fn test_continuously_stream_transactions() {
             known_epoch,
             include_events,
             target: target.clone(),
+            max_chunk_size: None,
         });
 
     // Spawn a new server thread to handle any continuous transaction stream requests
@@ -166,6 +167,7 @@ fn test_continuously_stream_transactions() {
         known_epoch,
         include_events,
         target,
+        None,
     ));
     assert_ok!(response);
 }
@@ -185,6 +187,7 @@ fn test_continuously_stream_transaction_outputs() {
             known_version: request_start_version,
             known_epoch: request_start_epoch,
             target: target.clone(),
+            max_chunk_size: None,
         },
     );
 
@@ -197,6 +200,7 @@ fn test_continuously_stream_transaction_outputs() {
             request_start_version,
             request_start_epoch,
             target,
+            None,
         ),
     );
     assert_ok!(response);
@@ -126,6 +126,7 @@ async fn test_notifications_continuous_outputs() {
             MIN_ADVERTISED_TRANSACTION_OUTPUT - 1,
             MIN_ADVERTISED_EPOCH_END,
             None,
+            None,
         )
         .await
         .unwrap();
@@ -179,6 +180,7 @@ async fn test_notifications_continuous_outputs_target() {
             MIN_ADVERTISED_TRANSACTION_OUTPUT - 1,
             MIN_ADVERTISED_EPOCH_END,
             Some(target),
+            None,
         )
         .await
         .unwrap();
@@ -232,6 +234,7 @@ async fn test_notifications_continuous_outputs_multiple_streams() {
             next_expected_version - 1,
             next_expected_epoch,
             None,
+            None,
         )
         .await
         .unwrap();
@@ -271,6 +274,7 @@ async fn test_notifications_continuous_outputs_multiple_streams() {
                             next_expected_version - 1,
                             next_expected_epoch,
                             None,
+                            None,
                         )
                         .await
                         .unwrap();
@@ -296,6 +300,7 @@ async fn test_notifications_continuous_transactions() {
             MIN_ADVERTISED_EPOCH_END,
             true,
             None,
+            None,
         )
         .await
         .unwrap();
@@ -356,6 +361,7 @@ async fn test_notifications_continuous_transactions_target() {
             MIN_ADVERTISED_EPOCH_END,
             true,
             Some(target),
+            None,
         )
         .await
         .unwrap();
@@ -486,6 +492,7 @@ async fn test_notifications_subscribe_outputs() {
             MIN_ADVERTISED_TRANSACTION_OUTPUT - 1,
             MIN_ADVERTISED_EPOCH_END,
             None,
+            None,
         )
         .await
         .unwrap();
@@ -542,6 +549,7 @@ async fn test_notifications_subscribe_transactions() {
             MIN_ADVERTISED_EPOCH_END,
             false,
             None,
+            None,
         )
         .await
         .unwrap();
@@ -756,6 +764,7 @@ async fn test_stream_continuous_outputs() {
             MIN_ADVERTISED_TRANSACTION_OUTPUT - 1,
             MIN_ADVERTISED_EPOCH_END,
             None,
+            None,
         )
         .await;
     assert_ok!(result);
@@ -766,6 +775,7 @@ async fn test_stream_continuous_outputs() {
             MIN_ADVERTISED_TRANSACTION_OUTPUT - 2,
             MIN_ADVERTISED_EPOCH_END,
             None,
+            None,
         )
         .await;
     assert_matches!(result, Err(Error::DataIsUnavailable(_)));
@@ -776,6 +786,7 @@ async fn test_stream_continuous_outputs() {
             MAX_ADVERTISED_TRANSACTION_OUTPUT + 1,
             MIN_ADVERTISED_EPOCH_END,
             None,
+            None,
         )
         .await;
     assert_matches!(result, Err(Error::DataIsUnavailable(_)));
@@ -796,6 +807,7 @@ async fn test_stream_continuous_outputs_target() {
                 MAX_ADVERTISED_EPOCH_END,
                 true,
             )),
+            None,
         )
         .await;
     assert_ok!(result);
@@ -812,6 +824,7 @@ async fn test_stream_continuous_outputs_target() {
                 MAX_ADVERTISED_EPOCH_END,
                 true,
             )),
+            None,
         )
         .await;
     assert_ok!(result);
@@ -829,6 +842,7 @@ async fn test_stream_continuous_transactions() {
             MIN_ADVERTISED_EPOCH_END,
             true,
             None,
+            None,
         )
         .await;
     assert_ok!(result);
@@ -840,6 +854,7 @@ async fn test_stream_continuous_transactions() {
             MIN_ADVERTISED_EPOCH_END,
             true,
             None,
+            None,
         )
         .await;
     assert_matches!(result, Err(Error::DataIsUnavailable(_)));
@@ -851,6 +866,7 @@ async fn test_stream_continuous_transactions() {
             MIN_ADVERTISED_EPOCH_END,
             true,
             None,
+            None,
         )
         .await;
     assert_matches!(result, Err(Error::DataIsUnavailable(_)));
@@ -872,6 +888,7 @@ async fn test_stream_continuous_transactions_target() {
                 MAX_ADVERTISED_EPOCH_END,
                 true,
             )),
+            None,
         )
         .await;
     assert_ok!(result);
@@ -889,6 +906,7 @@ async fn test_stream_continuous_transactions_target() {
                 MAX_ADVERTISED_EPOCH_END,
                 false,
             )),
+            None,
         )
         .await;
     assert_ok!(result);
@@ -0,0 +1,136 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use aptos_infallible::Mutex;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// The stage of bootstrapping the node is currently in
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BootstrapStage {
+    Initializing,           // Bootstrapping has not yet started fetching data
+    EpochEndingLedgerInfos, // Fetching the epoch ending ledger infos (progress is in epochs)
+    StateSnapshot,          // Fetching the state snapshot (progress is in state value indices)
+    TransactionOutputs,     // Applying transaction outputs (progress is in versions)
+    Transactions,           // Executing transactions (progress is in versions)
+    Complete,               // Bootstrapping has completed (this is the final notification)
+}
+
+/// A snapshot of the bootstrapping progress of the node
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BootstrapProgress {
+    // The current stage of bootstrapping
+    pub stage: BootstrapStage,
+
+    // The epochs, state value indices or versions completed for the stage
+    pub num_completed: u64,
+
+    // The target epoch or version for the stage (if known). This is None
+    // when syncing a state snapshot (the number of state values is unknown).
+    pub target: Option<u64>,
+
+    // An estimate (from 0 to 100) of how much of the stage has completed
+    pub completion_percent: u8,
+}
+
+impl BootstrapProgress {
+    pub fn new(stage: BootstrapStage, num_completed: u64, target: Option<u64>) -> Self {
+        let completion_percent = match stage {
+            BootstrapStage::Initializing => 0,
+            BootstrapStage::Complete => 100,
+            _ => calculate_completion_percent(num_completed, target).unwrap_or(0),
+        };
+        Self {
+            stage,
+            num_completed,
+            target,
+            completion_percent,
+        }
+    }
+
+    /// Returns the progress notification sent once bootstrapping completes
+    pub fn complete() -> Self {
+        Self::new(BootstrapStage::Complete, 0, None)
+    }
+}
+
+/// Returns the percentage (from 0 to 100) of the target that has completed.
+/// If there is no target, None is returned.
+fn calculate_completion_percent(num_completed: u64, target: Option<u64>) -> Option<u8> {
+    target.map(|target| {
+        if target == 0 || num_completed >= target {
+            100
+        } else {
+            ((num_completed as u128 * 100) / target as u128) as u8
+        }
+    })
+}
+
+/// A cheaply cloneable notifier through which the driver (and the storage
+/// synchronizer) publish bootstrapping progress. Once bootstrapping completes,
+/// a final `Complete` notification is sent and the channel is closed.
+#[derive(Clone)]
+pub struct BootstrapProgressNotifier {
+    progress_sender: Arc<Mutex<Option<watch::Sender<BootstrapProgress>>>>,
+}
+
+impl BootstrapProgressNotifier {
+    /// Returns a new notifier alongside a receiver for progress notifications
+    pub fn new() -> (Self, watch::Receiver<BootstrapProgress>) {
+        let initial_progress = BootstrapProgress::new(BootstrapStage::Initializing, 0, None);
+        let (progress_sender, progress_receiver) = watch::channel(initial_progress);
+        let notifier = Self {
+            progress_sender: Arc::new(Mutex::new(Some(progress_sender))),
+        };
+        (notifier, progress_receiver)
+    }
+
+    /// Publishes the given progress (if bootstrapping hasn't completed)
+    pub fn update_progress(&self, progress: BootstrapProgress) {
+        if let Some(progress_sender) = self.progress_sender.lock().as_ref() {
+            let _ = progress_sender.send(progress); // Ignore errors if there are no receivers
+        }
+    }
+
+    /// Updates the number of epochs or versions completed for the given stage.
+    /// If bootstrapping is at a different stage, nothing is published.
+    pub fn update_num_completed(&self, stage: BootstrapStage, num_completed: u64) {
+        self.update_num_completed_if(num_completed, |current_stage| current_stage == stage);
+    }
+
+    /// Updates the synced version of the current transaction data stage. This
+    /// is used by the storage synchronizer as new chunks are committed. If
+    /// we're not syncing transaction data, nothing is published.
+    pub fn update_synced_version(&self, synced_version: u64) {
+        self.update_num_completed_if(synced_version, |current_stage| {
+            matches!(
+                current_stage,
+                BootstrapStage::TransactionOutputs | BootstrapStage::Transactions
+            )
+        });
+    }
+
+    /// Updates the number of completed epochs or versions if the current stage
+    /// satisfies the given predicate (and the progress has moved forward).
+    fn update_num_completed_if<F: Fn(BootstrapStage) -> bool>(
+        &self,
+        num_completed: u64,
+        stage_predicate: F,
+    ) {
+        if let Some(progress_sender) = self.progress_sender.lock().as_ref() {
+            let progress = *progress_sender.borrow();
+            if stage_predicate(progress.stage) && num_completed > progress.num_completed {
+                let progress =
+                    BootstrapProgress::new(progress.stage, num_completed, progress.target);
+                let _ = progress_sender.send(progress);
+            }
+        }
+    }
+
+    /// Publishes the final `Complete` notification and closes the channel
+    pub fn notify_complete(&self) {
+        if let Some(progress_sender) = self.progress_sender.lock().take() {
+            let _ = progress_sender.send(BootstrapProgress::complete());
+        }
+    }
+}
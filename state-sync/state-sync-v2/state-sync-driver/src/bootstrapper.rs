@@ -2,20 +2,26 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
+    bootstrap_progress::{BootstrapProgress, BootstrapProgressNotifier, BootstrapStage},
     driver::DriverConfiguration,
     error::Error,
     logging::{LogEntry, LogSchema},
+    metadata_storage::MetadataStorageInterface,
+    metrics,
+    metrics::ExecutingComponent,
     notification_handlers::CommittedStates,
     storage_synchronizer::StorageSynchronizerInterface,
     utils,
-    utils::{SpeculativeStreamState, PENDING_DATA_LOG_FREQ_SECS},
+    utils::{OutputFallbackHandler, SpeculativeStreamState, PENDING_DATA_LOG_FREQ_SECS},
 };
-use aptos_config::config::BootstrappingMode;
+use aptos_config::config::{BootstrappingMode, StateSyncDriverConfig};
+use aptos_crypto::HashValue;
 use aptos_data_client::GlobalDataSummary;
 use aptos_logger::{
     prelude::*,
     sample::{SampleRate, Sampling},
 };
+use aptos_time_service::{TimeService, TimeServiceTrait};
 use aptos_types::{
     epoch_change::Verifier,
     epoch_state::EpochState,
@@ -29,10 +35,24 @@ use data_streaming_service::{
     data_stream::DataStreamListener,
     streaming_client::{DataStreamingClient, NotificationFeedback},
 };
-use futures::channel::oneshot;
-use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use futures::{channel::oneshot, FutureExt, StreamExt};
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use storage_interface::DbReader;
 
+/// The thread pool used to verify the signatures of epoch ending ledger infos
+static EPOCH_ENDING_VERIFICATION_POOL: Lazy<rayon::ThreadPool> = Lazy::new(|| {
+    rayon::ThreadPoolBuilder::new()
+        .thread_name(|index| format!("epoch_ending_verifier_{}", index))
+        .build()
+        .unwrap()
+});
+
 /// A simple container for verified epoch states and epoch ending ledger infos
 /// that have been fetched from the network.
 pub(crate) struct VerifiedEpochStates {
@@ -91,13 +111,52 @@ impl VerifiedEpochStates {
         epoch_ending_ledger_info: &LedgerInfoWithSignatures,
         waypoint: &Waypoint,
     ) -> Result<(), Error> {
-        // Verify the ledger info against the latest epoch state
-        self.latest_epoch_state
-            .verify(epoch_ending_ledger_info)
-            .map_err(|error| {
-                Error::VerificationError(format!("Ledger info failed verification: {:?}", error))
-            })?;
+        utils::verify_ledger_info_signatures(&self.latest_epoch_state, epoch_ending_ledger_info)?;
+        self.commit_epoch_ending_ledger_info(epoch_ending_ledger_info, waypoint)
+    }
+
+    /// Verifies the signatures of the given (consecutive) epoch ending ledger
+    /// infos in parallel. Each ledger info is verified against the next epoch
+    /// state of the ledger info before it (and the first against our latest
+    /// epoch state), so the results can only be trusted if the ledger infos
+    /// are committed in order (see `commit_epoch_ending_ledger_info()`).
+    pub fn verify_epoch_ending_signatures(
+        &self,
+        epoch_ending_ledger_infos: &[&LedgerInfoWithSignatures],
+    ) -> Vec<Result<(), Error>> {
+        EPOCH_ENDING_VERIFICATION_POOL.install(|| {
+            (0..epoch_ending_ledger_infos.len())
+                .into_par_iter()
+                .map(|index| {
+                    let epoch_state = if index == 0 {
+                        Some(&self.latest_epoch_state)
+                    } else {
+                        epoch_ending_ledger_infos[index - 1]
+                            .ledger_info()
+                            .next_epoch_state()
+                    };
+                    match epoch_state {
+                        Some(epoch_state) => utils::verify_ledger_info_signatures(
+                            epoch_state,
+                            epoch_ending_ledger_infos[index],
+                        ),
+                        None => Err(Error::VerificationError(
+                            "The previous ledger info was not epoch ending!".into(),
+                        )),
+                    }
+                })
+                .collect()
+        })
+    }
 
+    /// Commits the given epoch ending ledger info (whose signatures have
+    /// already been verified), updates our latest trusted epoch state and
+    /// attempts to verify any given waypoint.
+    pub fn commit_epoch_ending_ledger_info(
+        &mut self,
+        epoch_ending_ledger_info: &LedgerInfoWithSignatures,
+        waypoint: &Waypoint,
+    ) -> Result<(), Error> {
         // Update the latest epoch state with the next epoch
         if let Some(next_epoch_state) = epoch_ending_ledger_info.ledger_info().next_epoch_state() {
             self.highest_fetched_epoch_ending_version =
@@ -144,10 +203,22 @@ impl VerifiedEpochStates {
                 match waypoint.verify(ledger_info) {
                     Ok(()) => self.verified_waypoint = true,
                     Err(error) => {
-                        return Err(Error::VerificationError(
-                            format!("Failed to verify the waypoint: {:?}! Waypoint: {:?}, given ledger info: {:?}",
-                                    error, waypoint, ledger_info)
-                        ));
+                        let ledger_info_waypoint = Waypoint::new_any(ledger_info);
+                        error!(LogSchema::new(LogEntry::Bootstrapper)
+                            .epoch(ledger_info.epoch())
+                            .ledger_info_waypoint(ledger_info_waypoint)
+                            .waypoint(*waypoint)
+                            .message(&format!(
+                                "The waypoint does not match the fetched ledger info! Check the configured waypoint. Error: {:?}",
+                                error
+                            )));
+                        return Err(Error::WaypointMismatch {
+                            waypoint_version,
+                            waypoint_hash: waypoint.value(),
+                            ledger_info_version,
+                            ledger_info_hash: ledger_info_waypoint.value(),
+                            epoch: ledger_info.epoch(),
+                        });
                     }
                 }
             }
@@ -228,7 +299,6 @@ impl VerifiedEpochStates {
     }
 }
 
-// TODO(joshlind): persist the index (e.g., in case we crash mid-download)?
 /// A simple container to manage data related to state value snapshot syncing
 struct StateValueSyncer {
     // Whether or not a state snapshot receiver has been initialized
@@ -248,6 +318,10 @@ struct StateValueSyncer {
     // processed -- i.e., sent to the storage synchronizer).
     next_state_index_to_process: u64,
 
+    // The last hashed state key processed. State values are streamed in key
+    // hash order, so this gives us a rough estimate of our progress.
+    last_processed_state_key: Option<HashValue>,
+
     // The transaction output (inc. info and proof) for the version we're syncing
     transaction_output_to_sync: Option<TransactionOutputListWithProof>,
 }
@@ -260,10 +334,31 @@ impl StateValueSyncer {
             ledger_info_to_sync: None,
             next_state_index_to_commit: 0,
             next_state_index_to_process: 0,
+            last_processed_state_key: None,
             transaction_output_to_sync: None,
         }
     }
 
+    /// Returns an estimate (from 0 to 100) of how much of the state snapshot
+    /// has been processed. Returns None if we're not syncing a snapshot.
+    pub fn snapshot_sync_progress_percent(&self) -> Option<u8> {
+        if !self.initialized_state_snapshot_receiver {
+            return None;
+        }
+        if self.is_sync_complete {
+            return Some(100);
+        }
+
+        // Use the top bytes of the key hash as a fraction of the key space
+        let progress = self.last_processed_state_key.map_or(0, |last_key| {
+            let mut prefix = [0u8; 8];
+            prefix.copy_from_slice(&last_key[..8]);
+            let fraction = u64::from_be_bytes(prefix) as f64 / u64::MAX as f64;
+            (fraction * 100.0).floor() as u8
+        });
+        Some(progress.min(99))
+    }
+
     /// Resets all speculative state related to state value syncing (i.e., all
     /// speculative data that has not been successfully committed to storage)
     pub fn reset_speculative_state(&mut self) {
@@ -271,20 +366,61 @@ impl StateValueSyncer {
     }
 }
 
+/// The stages of bootstrapping that are served by a data stream
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BootstrappingStage {
+    EpochEndingLedgerInfos, // Fetching the epoch ending ledger infos
+    StateSnapshot,          // Fetching the state snapshot (and the target output)
+    TransactionData,        // Fetching transactions or transaction outputs
+}
+
+impl BootstrappingStage {
+    pub fn get_label(&self) -> &'static str {
+        match self {
+            BootstrappingStage::EpochEndingLedgerInfos => "epoch_ending_ledger_infos",
+            BootstrappingStage::StateSnapshot => "state_snapshot",
+            BootstrappingStage::TransactionData => "transaction_data",
+        }
+    }
+}
+
 /// A simple component that manages the bootstrapping of the node
-pub struct Bootstrapper<StorageSyncer, StreamingClient> {
+pub struct Bootstrapper<MetadataStorage, StorageSyncer, StreamingClient> {
     // The currently active data stream (provided by the data streaming service)
     active_data_stream: Option<DataStreamListener>,
 
+    // The bootstrapping stage served by the active data stream and the time
+    // at which the stream last made progress (i.e., sent a notification).
+    active_stream_progress: Option<(BootstrappingStage, Instant)>,
+
     // The channel used to notify a listener of successful bootstrapping
     bootstrap_notifier_channel: Option<oneshot::Sender<Result<(), Error>>>,
 
+    // The notifier used to publish the bootstrapping progress of the node
+    bootstrap_progress_notifier: BootstrapProgressNotifier,
+
     // If the node has completed bootstrapping
     bootstrapped: bool,
 
     // The config of the state sync driver
     driver_configuration: DriverConfiguration,
 
+    // If the active transaction data stream executes transactions because
+    // syncing transaction outputs has fallen back (e.g., outputs are pruned).
+    executing_fallback_transactions: bool,
+
+    // A non-transient error that prevents bootstrapping (e.g., a waypoint mismatch)
+    fatal_error: Option<Error>,
+
+    // The storage used to persist state sync metadata (e.g., snapshot progress)
+    metadata_storage: MetadataStorage,
+
+    // The handler used to fall back from syncing outputs to executing transactions
+    output_fallback_handler: OutputFallbackHandler,
+
+    // The bootstrapping mode selected for this node (if the mode is auto selected)
+    selected_bootstrapping_mode: Option<BootstrappingMode>,
+
     // The speculative state tracking the active data stream
     speculative_stream_state: Option<SpeculativeStreamState>,
 
@@ -300,36 +436,64 @@ pub struct Bootstrapper<StorageSyncer, StreamingClient> {
     // The storage synchronizer used to update local storage
     storage_synchronizer: StorageSyncer,
 
+    // The channel used to notify a listener once the bootstrapping target version is reached
+    target_notifier_channel: Option<oneshot::Sender<Result<Version, Error>>>,
+
+    // The time service used to identify stalled data streams
+    time_service: TimeService,
+
     // The epoch states verified by this node (held in memory)
     verified_epoch_states: VerifiedEpochStates,
 }
 
 impl<
+        MetadataStorage: MetadataStorageInterface + Clone,
         StorageSyncer: StorageSynchronizerInterface + Clone,
         StreamingClient: DataStreamingClient + Clone,
-    > Bootstrapper<StorageSyncer, StreamingClient>
+    > Bootstrapper<MetadataStorage, StorageSyncer, StreamingClient>
 {
     pub fn new(
         driver_configuration: DriverConfiguration,
+        metadata_storage: MetadataStorage,
         streaming_client: StreamingClient,
         storage: Arc<dyn DbReader>,
         storage_synchronizer: StorageSyncer,
+        time_service: TimeService,
+        bootstrap_progress_notifier: BootstrapProgressNotifier,
     ) -> Self {
         // Load the latest epoch state from storage
         let latest_epoch_state = utils::fetch_latest_epoch_state(storage.clone())
             .expect("Unable to fetch latest epoch state!");
         let verified_epoch_states = VerifiedEpochStates::new(latest_epoch_state);
 
+        // Create the output fallback handler
+        let config = &driver_configuration.config;
+        let output_fallback_handler = OutputFallbackHandler::new(
+            ExecutingComponent::Bootstrapper.get_label(),
+            Duration::from_secs(config.output_fallback_duration_secs),
+            config.output_fallback_threshold,
+            time_service.clone(),
+        );
+
         Self {
             state_value_syncer: StateValueSyncer::new(),
             active_data_stream: None,
+            active_stream_progress: None,
             bootstrap_notifier_channel: None,
+            bootstrap_progress_notifier,
             bootstrapped: false,
             driver_configuration,
+            executing_fallback_transactions: false,
+            fatal_error: None,
+            metadata_storage,
+            output_fallback_handler,
+            selected_bootstrapping_mode: None,
             speculative_stream_state: None,
             streaming_client,
             storage,
             storage_synchronizer,
+            target_notifier_channel: None,
+            time_service,
             verified_epoch_states,
         }
     }
@@ -339,6 +503,11 @@ impl<
         self.bootstrapped
     }
 
+    /// Returns the progress of the state snapshot sync (if one is running)
+    pub fn snapshot_sync_progress_percent(&self) -> Option<u8> {
+        self.state_value_syncer.snapshot_sync_progress_percent()
+    }
+
     /// Marks bootstrapping as complete and notifies any listeners
     pub fn bootstrapping_complete(&mut self) -> Result<(), Error> {
         info!(LogSchema::new(LogEntry::Bootstrapper)
@@ -347,6 +516,46 @@ impl<
         self.notify_listeners_if_bootstrapped()
     }
 
+    /// Halts bootstrapping because of a non-transient error (e.g., the synced
+    /// state snapshot doesn't match the target) and notifies any listeners
+    pub fn handle_fatal_error(&mut self, error: Error) -> Result<(), Error> {
+        error!(LogSchema::new(LogEntry::Bootstrapper)
+            .error(&error)
+            .message("Bootstrapping has failed with a fatal error!"));
+        self.fatal_error = Some(error);
+        self.notify_listeners_if_bootstrapped()
+    }
+
+    /// Completes bootstrapping using only the data already committed to local
+    /// storage (i.e., genesis). This is used by air-gapped and single-node
+    /// networks, where there are no peers to stream data from.
+    pub fn bootstrap_from_local_genesis(&mut self) -> Result<(), Error> {
+        if self.is_bootstrapped() {
+            return Err(Error::AlreadyBootstrapped(
+                "The bootstrapper should not attempt to bootstrap from genesis!".into(),
+            ));
+        }
+
+        // Verify that genesis has been committed and that it satisfies our waypoint
+        let latest_synced_version = utils::fetch_latest_synced_version(self.storage.clone())?;
+        let latest_ledger_info = utils::fetch_latest_synced_ledger_info(self.storage.clone())?;
+        let waypoint_version = self.driver_configuration.waypoint.version();
+        if latest_ledger_info.ledger_info().version() < waypoint_version {
+            return Err(Error::VerificationError(format!(
+                "Unable to bootstrap from local genesis! The waypoint is higher than storage. \
+                Waypoint version: {:?}, latest synced version: {:?}",
+                waypoint_version, latest_synced_version
+            )));
+        }
+
+        info!(LogSchema::new(LogEntry::Bootstrapper).message(&format!(
+            "Bootstrapping from local genesis! Latest synced version: {:?}",
+            latest_synced_version
+        )));
+        self.verified_epoch_states.set_verified_waypoint();
+        self.bootstrapping_complete()
+    }
+
     /// Subscribes the specified channel to bootstrap completion notifications
     pub fn subscribe_to_bootstrap_notifications(
         &mut self,
@@ -360,9 +569,61 @@ impl<
         self.notify_listeners_if_bootstrapped()
     }
 
+    /// Subscribes the specified channel to a notification for when the
+    /// bootstrapping target version is reached.
+    pub fn subscribe_to_target_notifications(
+        &mut self,
+        target_notifier_channel: oneshot::Sender<Result<Version, Error>>,
+    ) -> Result<(), Error> {
+        if self.target_notifier_channel.is_some() {
+            panic!("Only one target subscriber is supported at a time!");
+        }
+
+        self.target_notifier_channel = Some(target_notifier_channel);
+        if self
+            .driver_configuration
+            .config
+            .bootstrapping_target_version
+            .is_none()
+        {
+            return self.notify_target_listener(Err(Error::InvalidBootstrappingTarget(
+                "No bootstrapping target version is configured!".into(),
+            )));
+        }
+        self.notify_listeners_if_bootstrapped()
+    }
+
+    /// Notifies any target listener of the given result
+    fn notify_target_listener(&mut self, result: Result<Version, Error>) -> Result<(), Error> {
+        if let Some(notifier_channel) = self.target_notifier_channel.take() {
+            if let Err(error) = notifier_channel.send(result) {
+                return Err(Error::CallbackSendFailed(format!(
+                    "Target reached notification error: {:?}",
+                    error
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
     /// Notifies any listeners if we've now bootstrapped
     fn notify_listeners_if_bootstrapped(&mut self) -> Result<(), Error> {
+        // If bootstrapping has failed, notify the listeners of the error
+        if let Some(fatal_error) = self.fatal_error.clone() {
+            if let Some(notifier_channel) = self.bootstrap_notifier_channel.take() {
+                if let Err(error) = notifier_channel.send(Err(fatal_error.clone())) {
+                    return Err(Error::CallbackSendFailed(format!(
+                        "Bootstrap notification error: {:?}",
+                        error
+                    )));
+                }
+            }
+            return self.notify_target_listener(Err(fatal_error));
+        }
+
         if self.bootstrapped {
+            self.bootstrap_progress_notifier.notify_complete();
             if let Some(notifier_channel) = self.bootstrap_notifier_channel.take() {
                 if let Err(error) = notifier_channel.send(Ok(())) {
                     return Err(Error::CallbackSendFailed(format!(
@@ -371,6 +632,13 @@ impl<
                     )));
                 }
             }
+            if let Some(target_version) = self
+                .driver_configuration
+                .config
+                .bootstrapping_target_version
+            {
+                self.notify_target_listener(Ok(target_version))?;
+            }
             self.reset_active_stream();
             self.storage_synchronizer.finish_chunk_executor(); // The bootstrapper is now complete
         }
@@ -389,6 +657,15 @@ impl<
             ));
         }
 
+        // Don't retry if we've hit a non-transient error (e.g., the waypoint
+        // doesn't match). Otherwise, we'd keep refetching the same data.
+        if let Some(fatal_error) = &self.fatal_error {
+            return Err(fatal_error.clone());
+        }
+
+        // Reset the active data stream if it has stalled for too long
+        self.reset_active_stream_if_stalled();
+
         if self.active_data_stream.is_some() {
             // We have an active data stream. Process any notifications!
             self.process_active_stream_notifications().await?;
@@ -422,6 +699,14 @@ impl<
         &mut self,
         global_data_summary: &GlobalDataSummary,
     ) -> Result<(), Error> {
+        // If storage is already up-to-date with the network, there's nothing to fetch
+        if self.is_storage_ahead_of_network(global_data_summary)? {
+            info!(LogSchema::new(LogEntry::Bootstrapper).message(
+                "Storage is already at (or beyond) the highest advertised version! Skipping bootstrapping."
+            ));
+            return self.bootstrapping_complete();
+        }
+
         // Reset the chunk executor to flush any invalid state currently held in-memory
         self.storage_synchronizer.reset_chunk_executor()?;
 
@@ -432,21 +717,67 @@ impl<
                 .await;
         }
 
-        // Get the highest synced and known ledger info versions
+        // Get the highest synced version and the ledger info to sync to. If we're
+        // bootstrapping to a target version, sync to that instead of the highest
+        // known ledger info.
         let highest_synced_version = utils::fetch_latest_synced_version(self.storage.clone())?;
-        let highest_known_ledger_info = self.get_highest_known_ledger_info()?;
-        let highest_known_ledger_version = highest_known_ledger_info.ledger_info().version();
+
+        // Select the bootstrapping mode (if it hasn't been selected already)
+        self.select_bootstrapping_mode_if_required(global_data_summary, highest_synced_version)?;
+
+        let (highest_known_ledger_info, highest_known_ledger_version) =
+            if let Some(target_version) = self
+                .driver_configuration
+                .config
+                .bootstrapping_target_version
+            {
+                if highest_synced_version >= target_version {
+                    return self
+                        .handle_synced_to_target_version(highest_synced_version, target_version);
+                }
+                (
+                    self.get_target_proof_ledger_info(target_version)?,
+                    target_version,
+                )
+            } else {
+                let highest_known_ledger_info = self.get_highest_known_ledger_info()?;
+                let highest_known_ledger_version =
+                    highest_known_ledger_info.ledger_info().version();
+                (highest_known_ledger_info, highest_known_ledger_version)
+            };
 
         // Check if we've already fetched the required data for bootstrapping.
         // If not, bootstrap according to the mode.
-        match self.driver_configuration.config.bootstrapping_mode {
+        match self.get_bootstrapping_mode() {
             BootstrappingMode::DownloadLatestStates => {
-                if (self.state_value_syncer.ledger_info_to_sync.is_none()
-                    && highest_synced_version >= highest_known_ledger_version)
-                    || self.state_value_syncer.is_sync_complete
-                {
+                if self.state_value_syncer.is_sync_complete {
                     return self.bootstrapping_complete();
                 }
+                if self.state_value_syncer.ledger_info_to_sync.is_none() {
+                    // Check if a previous run already started syncing a snapshot
+                    if let Some(target) = self.metadata_storage.previous_snapshot_sync_target()? {
+                        let bootstrapping_to_target = self
+                            .driver_configuration
+                            .config
+                            .bootstrapping_target_version
+                            .is_some();
+                        if bootstrapping_to_target
+                            && target.ledger_info().version() != highest_known_ledger_version
+                        {
+                            return Err(Error::InvalidBootstrappingTarget(format!(
+                                "A state snapshot sync to a different version is already in progress! Target version: {:?}, snapshot version: {:?}",
+                                highest_known_ledger_version, target.ledger_info().version()
+                            )));
+                        }
+                        if self.metadata_storage.is_snapshot_sync_complete(&target)? {
+                            return self.bootstrapping_complete();
+                        }
+                        return self.resume_state_value_sync(target).await;
+                    }
+                    if highest_synced_version >= highest_known_ledger_version {
+                        return self.bootstrapping_complete();
+                    }
+                }
                 self.fetch_all_state_values(highest_known_ledger_info).await
             }
             _ => {
@@ -456,6 +787,7 @@ impl<
                 self.fetch_missing_transaction_data(
                     highest_synced_version,
                     highest_known_ledger_info,
+                    highest_known_ledger_version,
                 )
                 .await
             }
@@ -472,19 +804,100 @@ impl<
             // If the stream has timed out too many times, we need to reset it
             warn!("Resetting the currently active data stream due to too many timeouts!");
             self.reset_active_stream();
+        } else if result.is_ok() {
+            self.update_active_stream_progress();
         }
         result
     }
 
+    /// Returns the next data notification on the active stream (if one is
+    /// already available). This does not wait for new notifications.
+    fn next_ready_data_notification(&mut self) -> Option<DataNotification> {
+        let data_notification = self
+            .active_data_stream
+            .as_mut()
+            .and_then(|active_data_stream| active_data_stream.next().now_or_never())
+            .flatten();
+        if data_notification.is_some() {
+            self.update_active_stream_progress();
+        }
+        data_notification
+    }
+
+    /// Sets the active data stream for the given bootstrapping stage
+    fn set_active_data_stream(
+        &mut self,
+        data_stream: DataStreamListener,
+        bootstrapping_stage: BootstrappingStage,
+    ) {
+        self.active_data_stream = Some(data_stream);
+        self.active_stream_progress = Some((bootstrapping_stage, self.time_service.now()));
+    }
+
+    /// Records that the active data stream has just made progress
+    fn update_active_stream_progress(&mut self) {
+        if let Some((_, last_progress_time)) = self.active_stream_progress.as_mut() {
+            *last_progress_time = self.time_service.now();
+        }
+    }
+
+    /// Returns the max time the data stream for the given bootstrapping stage
+    /// can go without making progress (before it is considered stalled).
+    fn get_stream_timeout(&self, bootstrapping_stage: BootstrappingStage) -> Duration {
+        let config = &self.driver_configuration.config;
+        let timeout_ms = match bootstrapping_stage {
+            BootstrappingStage::EpochEndingLedgerInfos => config.epoch_ending_stream_timeout_ms,
+            BootstrappingStage::StateSnapshot => config.state_snapshot_stream_timeout_ms,
+            BootstrappingStage::TransactionData => config.transaction_data_stream_timeout_ms,
+        };
+        Duration::from_millis(timeout_ms)
+    }
+
+    /// Resets the active data stream if it hasn't made any progress within the
+    /// timeout of the current bootstrapping stage. A new stream will then be
+    /// initialized to replace it.
+    fn reset_active_stream_if_stalled(&mut self) {
+        if let Some((bootstrapping_stage, last_progress_time)) = self.active_stream_progress {
+            let stream_timeout = self.get_stream_timeout(bootstrapping_stage);
+            let time_since_progress = self
+                .time_service
+                .now()
+                .saturating_duration_since(last_progress_time);
+            if time_since_progress >= stream_timeout {
+                warn!(LogSchema::new(LogEntry::Bootstrapper).message(&format!(
+                    "The data stream has stalled! Resetting the stream. Stage: {:?}, time since progress: {:?}",
+                    bootstrapping_stage, time_since_progress
+                )));
+                metrics::increment_counter(
+                    &metrics::BOOTSTRAPPER_STREAM_RESETS,
+                    bootstrapping_stage.get_label(),
+                );
+                self.reset_active_stream();
+            }
+        }
+    }
+
     /// Processes any notifications already pending on the active stream
     async fn process_active_stream_notifications(&mut self) -> Result<(), Error> {
-        for _ in 0..self
+        let max_notifications = self
             .driver_configuration
             .config
-            .max_consecutive_stream_notifications
-        {
-            // Fetch and process any data notifications
-            let data_notification = self.fetch_next_data_notification().await?;
+            .max_consecutive_stream_notifications;
+        let mut num_notifications = 0;
+        let mut pending_data_notification = None;
+        loop {
+            // Fetch and process any data notifications. If too many data chunks
+            // are pending, stop polling the stream until storage has caught up.
+            let data_notification = match pending_data_notification.take() {
+                Some(data_notification) => data_notification,
+                None if num_notifications < max_notifications
+                    && !self.storage_synchronizer.pending_data_limit_reached() =>
+                {
+                    self.fetch_next_data_notification().await?
+                }
+                None => break,
+            };
+            num_notifications += 1;
             match data_notification.data_payload {
                 DataPayload::StateValuesWithProof(state_value_chunk_with_proof) => {
                     self.process_state_values_payload(
@@ -494,11 +907,27 @@ impl<
                     .await?;
                 }
                 DataPayload::EpochEndingLedgerInfos(epoch_ending_ledger_infos) => {
-                    self.process_epoch_ending_payload(
-                        data_notification.notification_id,
-                        epoch_ending_ledger_infos,
-                    )
-                    .await?;
+                    // Gather any other epoch ending payloads already pending on
+                    // the stream so that they can be verified together.
+                    let mut epoch_ending_payloads =
+                        vec![(data_notification.notification_id, epoch_ending_ledger_infos)];
+                    while num_notifications < max_notifications {
+                        match self.next_ready_data_notification() {
+                            Some(DataNotification {
+                                notification_id,
+                                data_payload: DataPayload::EpochEndingLedgerInfos(ledger_infos),
+                            }) => {
+                                num_notifications += 1;
+                                epoch_ending_payloads.push((notification_id, ledger_infos));
+                            }
+                            data_notification => {
+                                pending_data_notification = data_notification;
+                                break;
+                            }
+                        }
+                    }
+                    self.process_epoch_ending_payloads(epoch_ending_payloads)
+                        .await?;
                 }
                 DataPayload::TransactionsWithProof(transactions_with_proof) => {
                     let payload_start_version = transactions_with_proof.first_transaction_version;
@@ -532,40 +961,70 @@ impl<
         Ok(())
     }
 
+    /// Resumes a state snapshot sync (to the given target) that was started
+    /// by a previous run of the node, but never completed.
+    async fn resume_state_value_sync(
+        &mut self,
+        target_ledger_info: LedgerInfoWithSignatures,
+    ) -> Result<(), Error> {
+        // Verify the target still matches the epoch ending ledger info we've
+        // verified at the same version. Otherwise, we can't resume the sync.
+        let target_version = target_ledger_info.ledger_info().version();
+        let verified_ledger_info = self
+            .verified_epoch_states
+            .get_epoch_ending_ledger_info(target_version);
+        if verified_ledger_info.as_ref() != Some(&target_ledger_info) {
+            return Err(Error::SnapshotTargetMismatch(format!(
+                "Unable to resume the state snapshot sync! Persisted target: {:?}, \
+                verified ledger info: {:?}",
+                target_ledger_info, verified_ledger_info
+            )));
+        }
+
+        // The state snapshot receiver only persists the merkle leaf of the last
+        // state value in a chunk once the next value arrives. Thus, we resume at
+        // the last persisted index (i.e., we overlap the chunks by a single value).
+        let next_state_index = self
+            .metadata_storage
+            .get_last_persisted_state_value_index(&target_ledger_info)?;
+        info!(LogSchema::new(LogEntry::Bootstrapper).message(&format!(
+            "Resuming the state snapshot sync at version: {:?}. Next state value index: {:?}",
+            target_ledger_info.ledger_info().version(),
+            next_state_index
+        )));
+
+        self.state_value_syncer.next_state_index_to_commit = next_state_index;
+        self.state_value_syncer.next_state_index_to_process = next_state_index;
+        self.fetch_all_state_values(target_ledger_info).await
+    }
+
     /// Fetches state values (as required to bootstrap the node)
     async fn fetch_all_state_values(
         &mut self,
-        highest_known_ledger_info: LedgerInfoWithSignatures,
+        target_ledger_info: LedgerInfoWithSignatures,
     ) -> Result<(), Error> {
-        // Verify we're trying to sync to an unchanging ledger info
-        if let Some(ledger_info_to_sync) = &self.state_value_syncer.ledger_info_to_sync {
-            if ledger_info_to_sync != &highest_known_ledger_info {
-                panic!(
-                    "Mismatch in ledger info to sync! Highest: {:?}, target: {:?}",
-                    highest_known_ledger_info, ledger_info_to_sync
-                );
-            }
-        } else {
-            self.state_value_syncer.ledger_info_to_sync = Some(highest_known_ledger_info.clone());
-        }
+        // The target is fixed once the snapshot sync starts (the highest known
+        // ledger info may move on, but we continue syncing the same target).
+        let target_ledger_info = self
+            .state_value_syncer
+            .ledger_info_to_sync
+            .get_or_insert(target_ledger_info)
+            .clone();
 
         // Fetch the transaction info first, before the states
-        let highest_known_ledger_version = highest_known_ledger_info.ledger_info().version();
+        let target_version = target_ledger_info.ledger_info().version();
         let data_stream = if self.state_value_syncer.transaction_output_to_sync.is_none() {
             self.streaming_client
-                .get_all_transaction_outputs(
-                    highest_known_ledger_version,
-                    highest_known_ledger_version,
-                    highest_known_ledger_version,
-                )
+                .get_all_transaction_outputs(target_version, target_version, target_version)
                 .await?
         } else {
             let start_index = Some(self.state_value_syncer.next_state_index_to_commit);
             self.streaming_client
-                .get_all_state_values(highest_known_ledger_version, start_index)
+                .get_all_state_values(target_version, start_index)
                 .await?
         };
-        self.active_data_stream = Some(data_stream);
+        self.set_active_data_stream(data_stream, BootstrappingStage::StateSnapshot);
+        self.publish_state_snapshot_progress();
 
         Ok(())
     }
@@ -575,6 +1034,7 @@ impl<
         &mut self,
         highest_synced_version: Version,
         highest_known_ledger_info: LedgerInfoWithSignatures,
+        target_version: Version,
     ) -> Result<(), Error> {
         let highest_known_ledger_version = highest_known_ledger_info.ledger_info().version();
         let next_version = highest_synced_version.checked_add(1).ok_or_else(|| {
@@ -583,16 +1043,26 @@ impl<
         let end_version = self
             .verified_epoch_states
             .next_epoch_ending_version(highest_synced_version)
-            .expect("No higher epoch ending version known!");
-        let data_stream = match self.driver_configuration.config.bootstrapping_mode {
+            .expect("No higher epoch ending version known!")
+            .min(target_version); // Don't sync beyond the target version (if one is set)
+
+        // Determine if we should execute transactions instead of syncing outputs
+        self.executing_fallback_transactions = self.get_bootstrapping_mode()
+            == BootstrappingMode::ApplyTransactionOutputsFromGenesis
+            && self.output_fallback_handler.in_fallback_mode();
+
+        let data_stream = match self.get_transaction_sync_mode() {
             BootstrappingMode::ApplyTransactionOutputsFromGenesis => {
-                self.streaming_client
+                let result = self
+                    .streaming_client
                     .get_all_transaction_outputs(
                         next_version,
                         end_version,
                         highest_known_ledger_version,
                     )
-                    .await?
+                    .await;
+                self.output_fallback_handler
+                    .handle_output_stream_result(result)?
             }
             BootstrappingMode::ExecuteTransactionsFromGenesis => {
                 self.streaming_client
@@ -613,7 +1083,19 @@ impl<
             Some(highest_known_ledger_info),
             highest_synced_version,
         ));
-        self.active_data_stream = Some(data_stream);
+        self.set_active_data_stream(data_stream, BootstrappingStage::TransactionData);
+
+        // Publish the bootstrapping progress
+        let bootstrap_stage = match self.get_transaction_sync_mode() {
+            BootstrappingMode::ExecuteTransactionsFromGenesis => BootstrapStage::Transactions,
+            _ => BootstrapStage::TransactionOutputs,
+        };
+        self.bootstrap_progress_notifier
+            .update_progress(BootstrapProgress::new(
+                bootstrap_stage,
+                highest_synced_version,
+                Some(target_version),
+            ));
 
         Ok(())
     }
@@ -673,7 +1155,16 @@ impl<
                 .streaming_client
                 .get_all_epoch_ending_ledger_infos(next_epoch_end)
                 .await?;
-            self.active_data_stream = Some(epoch_ending_stream);
+            self.set_active_data_stream(
+                epoch_ending_stream,
+                BootstrappingStage::EpochEndingLedgerInfos,
+            );
+            self.bootstrap_progress_notifier
+                .update_progress(BootstrapProgress::new(
+                    BootstrapStage::EpochEndingLedgerInfos,
+                    highest_local_epoch_end,
+                    Some(highest_advertised_epoch_end),
+                ));
         } else if self.verified_epoch_states.verified_waypoint() {
             info!(LogSchema::new(LogEntry::Bootstrapper).message(
                 "No new epoch ending ledger infos to fetch! All peers are in the same epoch!"
@@ -687,6 +1178,50 @@ impl<
         Ok(())
     }
 
+    /// Returns true iff the locally synced ledger info is already at (or beyond)
+    /// the highest ledger info advertised by the network (i.e., there's nothing
+    /// newer to fetch). If the local ledger info ends an epoch and isn't strictly
+    /// ahead of the network, we still need to fetch the epoch ending ledger infos
+    /// for the next epoch (if any) before we can be sure that we're up-to-date.
+    fn is_storage_ahead_of_network(
+        &self,
+        global_data_summary: &GlobalDataSummary,
+    ) -> Result<bool, Error> {
+        // Bootstrapping to a specific target is handled separately
+        if self
+            .driver_configuration
+            .config
+            .bootstrapping_target_version
+            .is_some()
+        {
+            return Ok(false);
+        }
+
+        // If we haven't synced to our waypoint, we must still make progress
+        let latest_ledger_info = utils::fetch_latest_synced_ledger_info(self.storage.clone())?;
+        let latest_ledger_info = latest_ledger_info.ledger_info();
+        let latest_synced_version = latest_ledger_info.version();
+        if latest_synced_version < self.driver_configuration.waypoint.version() {
+            return Ok(false);
+        }
+
+        // Compare the latest synced version to the highest advertised version
+        let highest_advertised_version = match global_data_summary
+            .advertised_data
+            .highest_synced_ledger_info()
+        {
+            Some(ledger_info) => ledger_info.ledger_info().version(),
+            None => return Ok(false), // We don't know what the network has
+        };
+        if latest_synced_version > highest_advertised_version {
+            Ok(true)
+        } else if latest_synced_version == highest_advertised_version {
+            Ok(!latest_ledger_info.ends_epoch())
+        } else {
+            Ok(false)
+        }
+    }
+
     /// Verifies that connected peers have advertised data beyond our waypoint
     /// or that our waypoint is trivially satisfiable.
     fn verify_waypoint_is_satisfiable(
@@ -787,7 +1322,7 @@ impl<
         state_value_chunk_with_proof: StateValueChunkWithProof,
     ) -> Result<(), Error> {
         // Verify that we're expecting state value payloads
-        let bootstrapping_mode = self.driver_configuration.config.bootstrapping_mode;
+        let bootstrapping_mode = self.get_bootstrapping_mode();
         if self.should_fetch_epoch_ending_ledger_infos()
             || !matches!(bootstrapping_mode, BootstrappingMode::DownloadLatestStates)
         {
@@ -847,6 +1382,7 @@ impl<
 
         // Process the state values chunk and proof
         let last_state_value_index = state_value_chunk_with_proof.last_index;
+        let last_state_value_key = state_value_chunk_with_proof.last_key;
         if let Err(error) = self
             .storage_synchronizer
             .save_state_values(notification_id, state_value_chunk_with_proof)
@@ -866,18 +1402,22 @@ impl<
                     "The next state value index to process has overflown!".into(),
                 )
             })?;
+        self.state_value_syncer.last_processed_state_key = Some(last_state_value_key);
 
         Ok(())
     }
 
-    /// Process a single epoch ending payload
-    async fn process_epoch_ending_payload(
+    /// Processes the given epoch ending payloads (ordered by notification).
+    /// The signatures of all ledger infos are verified in parallel, after
+    /// which the ledger infos are committed in order. If any ledger info is
+    /// invalid, the stream is terminated and no later epochs are committed.
+    async fn process_epoch_ending_payloads(
         &mut self,
-        notification_id: NotificationId,
-        epoch_ending_ledger_infos: Vec<LedgerInfoWithSignatures>,
+        epoch_ending_payloads: Vec<(NotificationId, Vec<LedgerInfoWithSignatures>)>,
     ) -> Result<(), Error> {
         // Verify that we're expecting epoch ending ledger info payloads
         if !self.should_fetch_epoch_ending_ledger_infos() {
+            let (notification_id, _) = epoch_ending_payloads[0];
             self.terminate_active_stream(notification_id, NotificationFeedback::InvalidPayloadData)
                 .await?;
             return Err(Error::InvalidPayload(
@@ -885,28 +1425,64 @@ impl<
             ));
         }
 
-        // Verify the payload isn't empty
-        if epoch_ending_ledger_infos.is_empty() {
-            self.terminate_active_stream(notification_id, NotificationFeedback::EmptyPayloadData)
-                .await?;
-            return Err(Error::VerificationError(
-                "The epoch ending payload was empty!".into(),
-            ));
-        }
-
-        // Verify the epoch change proofs, update our latest epoch state and
-        // verify our waypoint.
-        for epoch_ending_ledger_info in epoch_ending_ledger_infos {
-            if let Err(error) = self.verified_epoch_states.verify_epoch_ending_ledger_info(
-                &epoch_ending_ledger_info,
-                &self.driver_configuration.waypoint,
-            ) {
+        // Verify the signatures of all epoch change proofs in parallel
+        let all_epoch_ending_ledger_infos: Vec<_> = epoch_ending_payloads
+            .iter()
+            .flat_map(|(_, epoch_ending_ledger_infos)| epoch_ending_ledger_infos)
+            .collect();
+        let mut verification_results = self
+            .verified_epoch_states
+            .verify_epoch_ending_signatures(&all_epoch_ending_ledger_infos)
+            .into_iter();
+
+        // Commit the epoch change proofs in order, update our latest epoch
+        // state and verify our waypoint.
+        for (notification_id, epoch_ending_ledger_infos) in epoch_ending_payloads {
+            // Verify the payload isn't empty
+            if epoch_ending_ledger_infos.is_empty() {
                 self.terminate_active_stream(
                     notification_id,
-                    NotificationFeedback::PayloadProofFailed,
+                    NotificationFeedback::EmptyPayloadData,
                 )
                 .await?;
-                return Err(error);
+                return Err(Error::VerificationError(
+                    "The epoch ending payload was empty!".into(),
+                ));
+            }
+
+            for epoch_ending_ledger_info in epoch_ending_ledger_infos {
+                let verification_result = verification_results
+                    .next()
+                    .expect("A verification result should exist for every ledger info!")
+                    .and_then(|()| {
+                        self.verified_epoch_states.commit_epoch_ending_ledger_info(
+                            &epoch_ending_ledger_info,
+                            &self.driver_configuration.waypoint,
+                        )
+                    });
+                if let Err(error) = verification_result {
+                    metrics::increment_counter(
+                        &metrics::EPOCH_ENDING_VERIFICATIONS,
+                        metrics::EPOCH_ENDING_VERIFICATION_FAILED,
+                    );
+                    self.terminate_active_stream(
+                        notification_id,
+                        NotificationFeedback::PayloadProofFailed,
+                    )
+                    .await?;
+                    if matches!(error, Error::WaypointMismatch { .. }) {
+                        self.handle_fatal_error(error.clone())?;
+                    }
+                    return Err(error);
+                }
+                metrics::increment_counter(
+                    &metrics::EPOCH_ENDING_VERIFICATIONS,
+                    metrics::EPOCH_ENDING_VERIFIED,
+                );
+                self.bootstrap_progress_notifier.update_num_completed(
+                    BootstrapStage::EpochEndingLedgerInfos,
+                    epoch_ending_ledger_info.ledger_info().epoch(),
+                );
             }
         }
 
@@ -925,7 +1501,7 @@ impl<
         payload_start_version: Option<Version>,
     ) -> Result<(), Error> {
         // Verify that we're expecting transaction or output payloads
-        let bootstrapping_mode = self.driver_configuration.config.bootstrapping_mode;
+        let bootstrapping_mode = self.get_bootstrapping_mode();
         if self.should_fetch_epoch_ending_ledger_infos()
             || (matches!(bootstrapping_mode, BootstrappingMode::DownloadLatestStates)
                 && self.state_value_syncer.transaction_output_to_sync.is_some())
@@ -974,7 +1550,7 @@ impl<
             .await?;
 
         // Execute/apply and commit the transactions/outputs
-        let num_transactions_or_outputs = match bootstrapping_mode {
+        let num_transactions_or_outputs = match self.get_transaction_sync_mode() {
             BootstrappingMode::ApplyTransactionOutputsFromGenesis => {
                 if let Some(transaction_outputs_with_proof) = transaction_outputs_with_proof {
                     let num_transaction_outputs = transaction_outputs_with_proof
@@ -1145,7 +1721,7 @@ impl<
         transaction_outputs_with_proof: Option<&TransactionOutputListWithProof>,
     ) -> Result<Option<LedgerInfoWithSignatures>, Error> {
         // Calculate the payload end version
-        let num_versions = match self.driver_configuration.config.bootstrapping_mode {
+        let num_versions = match self.get_transaction_sync_mode() {
             BootstrappingMode::ApplyTransactionOutputsFromGenesis => {
                 if let Some(transaction_outputs_with_proof) = transaction_outputs_with_proof {
                     transaction_outputs_with_proof
@@ -1193,6 +1769,135 @@ impl<
             .get_epoch_ending_ledger_info(payload_end_version))
     }
 
+    /// Completes bootstrapping if we've synced exactly to the target version.
+    /// Otherwise, we've already synced beyond the target and it is rejected.
+    fn handle_synced_to_target_version(
+        &mut self,
+        highest_synced_version: Version,
+        target_version: Version,
+    ) -> Result<(), Error> {
+        if highest_synced_version == target_version {
+            return self.bootstrapping_complete();
+        }
+
+        let error = Error::InvalidBootstrappingTarget(format!(
+            "The target version is lower than the synced version! Target version: {:?}, synced version: {:?}",
+            target_version, highest_synced_version
+        ));
+        self.notify_target_listener(Err(error.clone()))?;
+        Err(error)
+    }
+
+    /// Returns the verified epoch ending ledger info that proves the given
+    /// target version (i.e., the first epoch ending ledger info at or after
+    /// the target). State snapshots can only be synced at epoch endings.
+    fn get_target_proof_ledger_info(
+        &self,
+        target_version: Version,
+    ) -> Result<LedgerInfoWithSignatures, Error> {
+        let proof_ledger_info = target_version
+            .checked_sub(1)
+            .and_then(|version| self.verified_epoch_states.next_epoch_ending_version(version))
+            .and_then(|version| self.verified_epoch_states.get_epoch_ending_ledger_info(version))
+            .ok_or_else(|| {
+                Error::InvalidBootstrappingTarget(format!(
+                    "No verified epoch ending ledger info was found at or after the target version: {:?}",
+                    target_version
+                ))
+            })?;
+
+        if self.get_bootstrapping_mode() == BootstrappingMode::DownloadLatestStates
+            && proof_ledger_info.ledger_info().version() != target_version
+        {
+            return Err(Error::InvalidBootstrappingTarget(format!(
+                "State snapshots can only be synced at epoch ending versions! Target version: {:?}",
+                target_version
+            )));
+        }
+
+        Ok(proof_ledger_info)
+    }
+
+    /// Returns the bootstrapping mode used to sync transaction data. This is
+    /// the bootstrapping mode, unless syncing transaction outputs has fallen
+    /// back to executing transactions.
+    fn get_transaction_sync_mode(&self) -> BootstrappingMode {
+        if self.executing_fallback_transactions {
+            BootstrappingMode::ExecuteTransactionsFromGenesis
+        } else {
+            self.get_bootstrapping_mode()
+        }
+    }
+
+    /// Returns the bootstrapping mode of the node. This is the configured
+    /// mode, unless the mode is auto selected (in which case, it is the
+    /// selected mode).
+    fn get_bootstrapping_mode(&self) -> BootstrappingMode {
+        self.selected_bootstrapping_mode
+            .unwrap_or(self.driver_configuration.config.bootstrapping_mode)
+    }
+
+    /// Selects the bootstrapping mode if the mode should be auto selected and
+    /// hasn't been selected yet. The selected mode is persisted so that the
+    /// node doesn't change modes if it restarts mid-bootstrap.
+    fn select_bootstrapping_mode_if_required(
+        &mut self,
+        global_data_summary: &GlobalDataSummary,
+        highest_synced_version: Version,
+    ) -> Result<(), Error> {
+        let config = &self.driver_configuration.config;
+        if config.bootstrapping_mode != BootstrappingMode::AutoSelect
+            || self.selected_bootstrapping_mode.is_some()
+        {
+            return Ok(());
+        }
+
+        // Use the previously selected mode (if one exists)
+        let bootstrapping_mode = if let Some(bootstrapping_mode) =
+            self.metadata_storage.get_selected_bootstrapping_mode()?
+        {
+            info!(LogSchema::new(LogEntry::Bootstrapper).message(&format!(
+                "Using the previously selected bootstrapping mode: {:?}",
+                bootstrapping_mode
+            )));
+            bootstrapping_mode
+        } else {
+            // Identify the version to sync to (i.e., the target or highest advertised version)
+            let highest_version = match config.bootstrapping_target_version {
+                Some(target_version) => target_version,
+                None => global_data_summary
+                    .advertised_data
+                    .highest_synced_ledger_info()
+                    .map(|ledger_info| ledger_info.ledger_info().version())
+                    .ok_or_else(|| {
+                        Error::AdvertisedDataError(
+                            "No highest advertised ledger info found! Unable to select the bootstrapping mode."
+                                .into(),
+                        )
+                    })?,
+            };
+
+            // Select the mode and persist it
+            let num_versions_behind = highest_version.saturating_sub(highest_synced_version);
+            let bootstrapping_mode = select_bootstrapping_mode(config, num_versions_behind);
+            self.metadata_storage
+                .update_selected_bootstrapping_mode(bootstrapping_mode)?;
+            info!(LogSchema::new(LogEntry::Bootstrapper).message(&format!(
+                "Selected the bootstrapping mode: {:?}. Number of versions behind: {:?}",
+                bootstrapping_mode, num_versions_behind
+            )));
+            bootstrapping_mode
+        };
+
+        metrics::set_gauge(
+            &metrics::BOOTSTRAPPING_MODE,
+            bootstrapping_mode.to_label(),
+            1,
+        );
+        self.selected_bootstrapping_mode = Some(bootstrapping_mode);
+        Ok(())
+    }
+
     /// Returns the highest known ledger info (including the newly fetch ones)
     fn get_highest_known_ledger_info(&self) -> Result<LedgerInfoWithSignatures, Error> {
         // Fetch the highest synced ledger info from storage
@@ -1268,10 +1973,24 @@ impl<
             )));
             self.state_value_syncer.is_sync_complete = true;
         }
+        self.publish_state_snapshot_progress();
 
         Ok(())
     }
 
+    /// Publishes the progress of the state snapshot sync. The total number of
+    /// state values is unknown, so the completion percentage is an estimate.
+    fn publish_state_snapshot_progress(&self) {
+        let state_snapshot_progress = BootstrapProgress {
+            stage: BootstrapStage::StateSnapshot,
+            num_completed: self.state_value_syncer.next_state_index_to_commit,
+            target: None,
+            completion_percent: self.snapshot_sync_progress_percent().unwrap_or(0),
+        };
+        self.bootstrap_progress_notifier
+            .update_progress(state_snapshot_progress);
+    }
+
     /// Returns the speculative stream state. Assumes that the state exists.
     fn get_speculative_stream_state(&mut self) -> &mut SpeculativeStreamState {
         self.speculative_stream_state
@@ -1284,6 +2003,7 @@ impl<
         self.state_value_syncer.reset_speculative_state();
         self.speculative_stream_state = None;
         self.active_data_stream = None;
+        self.active_stream_progress = None;
     }
 
     /// Returns the verified epoch states struct for testing purposes.
@@ -1292,3 +2012,21 @@ impl<
         &mut self.verified_epoch_states
     }
 }
+
+/// Selects the bootstrapping mode for a node that is `num_versions_behind` the
+/// version to sync to: state snapshots are downloaded if the node is more than
+/// `auto_select_snapshot_threshold` versions behind, transaction outputs are
+/// applied if it is more than `auto_select_output_threshold` versions behind,
+/// and transactions are executed otherwise.
+pub fn select_bootstrapping_mode(
+    config: &StateSyncDriverConfig,
+    num_versions_behind: u64,
+) -> BootstrappingMode {
+    if num_versions_behind > config.auto_select_snapshot_threshold {
+        BootstrappingMode::DownloadLatestStates
+    } else if num_versions_behind > config.auto_select_output_threshold {
+        BootstrappingMode::ApplyTransactionOutputsFromGenesis
+    } else {
+        BootstrappingMode::ExecuteTransactionsFromGenesis
+    }
+}
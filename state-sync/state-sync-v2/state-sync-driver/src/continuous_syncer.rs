@@ -4,15 +4,22 @@
 use crate::{
     driver::DriverConfiguration,
     error::Error,
+    metrics,
+    metrics::ExecutingComponent,
     notification_handlers::ConsensusSyncRequest,
     storage_synchronizer::StorageSynchronizerInterface,
     utils,
-    utils::{SpeculativeStreamState, PENDING_DATA_LOG_FREQ_SECS},
+    utils::{
+        ChunkSizeController, OutputFallbackHandler, SpeculativeStreamState,
+        PENDING_DATA_LOG_FREQ_SECS,
+    },
 };
-use aptos_config::config::ContinuousSyncingMode;
+use aptos_config::config::{ContinuousStreamingMode, ContinuousSyncingMode};
 use aptos_infallible::Mutex;
 use aptos_logger::{prelude::*, sample, sample::SampleRate};
+use aptos_time_service::{TimeService, TimeServiceTrait};
 use aptos_types::{
+    epoch_state::EpochState,
     ledger_info::LedgerInfoWithSignatures,
     transaction::{TransactionListWithProof, TransactionOutputListWithProof, Version},
 };
@@ -21,17 +28,95 @@ use data_streaming_service::{
     data_stream::DataStreamListener,
     streaming_client::{DataStreamingClient, Epoch, NotificationFeedback},
 };
-use std::{sync::Arc, time::Duration};
+use futures::channel::oneshot;
+use once_cell::sync::Lazy;
+use std::{
+    cmp,
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use storage_interface::DbReader;
 
+/// The thread pool used to verify the proof ledger infos of synced chunks
+static CHUNK_VERIFICATION_POOL: Lazy<rayon::ThreadPool> = Lazy::new(|| {
+    rayon::ThreadPoolBuilder::new()
+        .thread_name(|index| format!("chunk_verifier_{}", index))
+        .build()
+        .unwrap()
+});
+
+/// The function used to verify a proof ledger info against an epoch state
+type LedgerInfoVerifier = fn(&EpochState, &LedgerInfoWithSignatures) -> Result<(), Error>;
+
+/// A chunk of transactions or outputs whose proof ledger info is being verified
+/// on the verification pool. Chunks are only sent to the storage synchronizer
+/// once verification has succeeded (and always in stream order).
+struct PendingVerificationChunk {
+    // The ledger info that proves the chunk
+    ledger_info_with_signatures: LedgerInfoWithSignatures,
+
+    // The id of the data notification that contained the chunk
+    notification_id: NotificationId,
+
+    // The first version of the chunk
+    payload_start_version: Version,
+
+    // The transactions of the chunk (if the chunk contains transactions)
+    transaction_list_with_proof: Option<TransactionListWithProof>,
+
+    // The transaction outputs of the chunk (if the chunk contains outputs)
+    transaction_outputs_with_proof: Option<TransactionOutputListWithProof>,
+
+    // The receiver for the verification result of the proof ledger info
+    verification_receiver: oneshot::Receiver<Result<(), Error>>,
+}
+
+/// A ledger timestamp to sync to (set at runtime, e.g., by tooling that
+/// reproduces historical incidents). Syncing halts at the last version
+/// with a ledger timestamp at or below the target timestamp.
+struct TimestampTarget {
+    // The channel through which to notify the client once the target is reached
+    notifier_channel: Option<oneshot::Sender<Result<Version, Error>>>,
+
+    // The ledger info proving the data up to the target version. This is
+    // only required if the target version is in the middle of a chunk.
+    proof_ledger_info: Option<LedgerInfoWithSignatures>,
+
+    // True iff the target has been reached (and syncing has halted)
+    reached: bool,
+
+    // The target ledger timestamp (usecs)
+    target_timestamp_usecs: u64,
+
+    // The last version with a timestamp at or below the target (once identified)
+    target_version: Option<Version>,
+}
+
 /// A simple component that manages the continuous syncing of the node
 pub struct ContinuousSyncer<StorageSyncer, StreamingClient> {
     // The currently active data stream (provided by the data streaming service)
     active_data_stream: Option<DataStreamListener>,
 
+    // The controller that adapts the requested chunk size to storage latencies
+    chunk_size_controller: ChunkSizeController,
+
     // The config of the state sync driver
     driver_configuration: DriverConfiguration,
 
+    // If the active data stream executes transactions because syncing
+    // transaction outputs has fallen back (e.g., outputs are pruned).
+    executing_fallback_transactions: bool,
+
+    // The function used to verify the proof ledger infos of chunks
+    ledger_info_verifier: LedgerInfoVerifier,
+
+    // The handler used to fall back from syncing outputs to executing transactions
+    output_fallback_handler: OutputFallbackHandler,
+
+    // The chunks (in stream order) whose proof ledger infos are being verified
+    pending_verification_chunks: VecDeque<PendingVerificationChunk>,
+
     // The speculative state tracking the active data stream
     speculative_stream_state: Option<SpeculativeStreamState>,
 
@@ -43,6 +128,12 @@ pub struct ContinuousSyncer<StorageSyncer, StreamingClient> {
 
     // The storage synchronizer used to update local storage
     storage_synchronizer: StorageSyncer,
+
+    // The time service used to calculate the subscription lag
+    time_service: TimeService,
+
+    // The ledger timestamp to sync to (if one has been set)
+    timestamp_target: Option<TimestampTarget>,
 }
 
 impl<
@@ -55,14 +146,38 @@ impl<
         streaming_client: StreamingClient,
         storage: Arc<dyn DbReader>,
         storage_synchronizer: StorageSyncer,
+        time_service: TimeService,
     ) -> Self {
+        // Create the output fallback handler
+        let config = &driver_configuration.config;
+        let output_fallback_handler = OutputFallbackHandler::new(
+            ExecutingComponent::ContinuousSyncer.get_label(),
+            Duration::from_secs(config.output_fallback_duration_secs),
+            config.output_fallback_threshold,
+            time_service.clone(),
+        );
+
+        // Create the chunk size controller
+        let chunk_size_controller = ChunkSizeController::new(
+            config.min_continuous_chunk_size,
+            config.max_continuous_chunk_size,
+            Duration::from_millis(config.target_chunk_latency_ms),
+        );
+
         Self {
             active_data_stream: None,
+            chunk_size_controller,
             driver_configuration,
+            executing_fallback_transactions: false,
+            ledger_info_verifier: utils::verify_ledger_info_signatures,
+            output_fallback_handler,
+            pending_verification_chunks: VecDeque::new(),
             speculative_stream_state: None,
             streaming_client,
             storage,
             storage_synchronizer,
+            time_service,
+            timestamp_target: None,
         }
     }
 
@@ -71,10 +186,34 @@ impl<
         &mut self,
         consensus_sync_request: Arc<Mutex<Option<ConsensusSyncRequest>>>,
     ) -> Result<(), Error> {
+        // If we've reached the timestamp target, we halt (i.e., never sync any further)
+        if let Some(timestamp_target) = self.timestamp_target.as_ref() {
+            if timestamp_target.reached {
+                sample!(
+                    SampleRate::Duration(Duration::from_secs(PENDING_DATA_LOG_FREQ_SECS)),
+                    info!(
+                        "Reached the target timestamp: {:?}. Not syncing any further!",
+                        timestamp_target.target_timestamp_usecs
+                    )
+                );
+                return Ok(());
+            }
+        }
+
+        // Send any chunks that have already been verified to storage
+        self.apply_verified_chunks(false).await?;
+
         if self.active_data_stream.is_some() {
             // We have an active data stream. Process any notifications!
             self.process_active_stream_notifications(consensus_sync_request)
                 .await
+        } else if !self.pending_verification_chunks.is_empty() {
+            // Wait for any pending chunks to be verified
+            sample!(
+                SampleRate::Duration(Duration::from_secs(PENDING_DATA_LOG_FREQ_SECS)),
+                info!("Waiting for the pending chunks to be verified!")
+            );
+            Ok(())
         } else if self.storage_synchronizer.pending_storage_data() {
             // Wait for any pending data to be processed
             sample!(
@@ -82,6 +221,9 @@ impl<
                 info!("Waiting for the storage synchronizer to handle pending data!")
             );
             Ok(())
+        } else if self.check_timestamp_target_reached()? {
+            // We've synced to the timestamp target, so there's nothing left to do
+            Ok(())
         } else {
             // Fetch a new data stream to start streaming data
             self.initialize_active_data_stream(consensus_sync_request)
@@ -89,6 +231,154 @@ impl<
         }
     }
 
+    /// Sets the ledger timestamp (in usecs) to sync to. Once the last version
+    /// with a timestamp at or below the target has been synced, the continuous
+    /// syncer halts and notifies the given channel of the synced version.
+    ///
+    /// Note: only a single target can be set, and it must not have already
+    /// been passed by the latest synced ledger info. Any data already pending
+    /// in the storage synchronizer is not checked against the target.
+    pub fn set_timestamp_target(
+        &mut self,
+        target_timestamp_usecs: u64,
+        notifier_channel: oneshot::Sender<Result<Version, Error>>,
+    ) -> Result<(), Error> {
+        // Verify a timestamp target hasn't already been set
+        if self.timestamp_target.is_some() {
+            return notify_timestamp_target_listener(
+                notifier_channel,
+                Err(Error::InvalidTimestampTarget(
+                    "A timestamp target has already been set!".into(),
+                )),
+            );
+        }
+
+        // Verify we haven't already synced beyond the target
+        let synced_timestamp_usecs =
+            match utils::fetch_latest_synced_ledger_info(self.storage.clone()) {
+                Ok(ledger_info) => ledger_info.ledger_info().timestamp_usecs(),
+                Err(error) => {
+                    return notify_timestamp_target_listener(notifier_channel, Err(error))
+                }
+            };
+        if synced_timestamp_usecs > target_timestamp_usecs {
+            return notify_timestamp_target_listener(
+                notifier_channel,
+                Err(Error::InvalidTimestampTarget(format!(
+                    "Already synced beyond the target timestamp! Synced timestamp: {:?}, target: {:?}",
+                    synced_timestamp_usecs, target_timestamp_usecs
+                ))),
+            );
+        }
+
+        // Reset the active stream so that all new data is checked against the target
+        self.reset_active_stream();
+        self.timestamp_target = Some(TimestampTarget {
+            notifier_channel: Some(notifier_channel),
+            proof_ledger_info: None,
+            reached: false,
+            target_timestamp_usecs,
+            target_version: None,
+        });
+
+        Ok(())
+    }
+
+    /// Returns true iff storage has been synced to the timestamp target
+    /// version. If so, the target is marked as reached and the client is
+    /// notified of the synced version.
+    fn check_timestamp_target_reached(&mut self) -> Result<bool, Error> {
+        let target_version = match self
+            .timestamp_target
+            .as_ref()
+            .and_then(|timestamp_target| timestamp_target.target_version)
+        {
+            Some(target_version) => target_version,
+            None => return Ok(false), // The target version hasn't been identified yet
+        };
+
+        let synced_version = utils::fetch_latest_synced_version(self.storage.clone())?;
+        if synced_version < target_version {
+            return Ok(false);
+        }
+
+        // Halt syncing and notify the client
+        info!(
+            "Synced to the timestamp target! Target version: {:?}, synced version: {:?}",
+            target_version, synced_version
+        );
+        let timestamp_target = self
+            .timestamp_target
+            .as_mut()
+            .expect("The timestamp target should exist!");
+        timestamp_target.reached = true;
+        if let Some(notifier_channel) = timestamp_target.notifier_channel.take() {
+            notify_timestamp_target_listener(notifier_channel, Ok(synced_version))?;
+        }
+
+        Ok(true)
+    }
+
+    /// Checks if the given payload crosses the timestamp target (if one is
+    /// set and the target version hasn't yet been identified). If so, the
+    /// target version is returned and stored (alongside the proof ledger info).
+    fn check_timestamp_target_crossed(
+        &mut self,
+        payload_start_version: Version,
+        ledger_info_with_signatures: &LedgerInfoWithSignatures,
+        transaction_list_with_proof: Option<&TransactionListWithProof>,
+        transaction_outputs_with_proof: Option<&TransactionOutputListWithProof>,
+    ) -> Option<Version> {
+        let timestamp_target = self
+            .timestamp_target
+            .as_mut()
+            .filter(|timestamp_target| timestamp_target.target_version.is_none())?;
+
+        // Identify the target version (if the payload crosses the target timestamp)
+        let target_timestamp_usecs = timestamp_target.target_timestamp_usecs;
+        let target_version = if let Some(transaction_list_with_proof) = transaction_list_with_proof
+        {
+            utils::get_timestamp_target_version(
+                transaction_list_with_proof.transactions.iter(),
+                payload_start_version,
+                target_timestamp_usecs,
+            )
+        } else {
+            utils::get_timestamp_target_version(
+                transaction_outputs_with_proof?
+                    .transactions_and_outputs
+                    .iter()
+                    .map(|(transaction, _)| transaction),
+                payload_start_version,
+                target_timestamp_usecs,
+            )
+        }?;
+
+        // If the target version is within the payload, we'll need the proof
+        // ledger info to sync the data up to (and including) the target version.
+        timestamp_target.target_version = Some(target_version);
+        if target_version >= payload_start_version {
+            timestamp_target.proof_ledger_info = Some(ledger_info_with_signatures.clone());
+        }
+
+        Some(target_version)
+    }
+
+    /// Returns the proof ledger info for syncing up to the timestamp target
+    /// version (if the target version is in the middle of a chunk).
+    fn get_timestamp_target_proof(&self) -> Option<LedgerInfoWithSignatures> {
+        self.timestamp_target
+            .as_ref()
+            .and_then(|timestamp_target| timestamp_target.proof_ledger_info.clone())
+    }
+
+    /// Returns the timestamp target version (if it has been identified)
+    fn get_timestamp_target_version(&self) -> Option<Version> {
+        self.timestamp_target
+            .as_ref()
+            .and_then(|timestamp_target| timestamp_target.target_version)
+    }
+
     /// Initializes an active data stream so that we can begin to process notifications
     async fn initialize_active_data_stream(
         &mut self,
@@ -110,28 +400,71 @@ impl<
             .as_ref()
             .map(|sync_request| sync_request.get_sync_target());
 
-        // Initialize a new active data stream
-        let active_data_stream = match self.driver_configuration.config.continuous_syncing_mode {
-            ContinuousSyncingMode::ApplyTransactionOutputs => {
-                self.streaming_client
-                    .continuously_stream_transaction_outputs(
-                        highest_synced_version,
-                        highest_synced_epoch,
-                        sync_request_target,
-                    )
-                    .await?
-            }
-            ContinuousSyncingMode::ExecuteTransactions => {
-                self.streaming_client
-                    .continuously_stream_transactions(
-                        highest_synced_version,
-                        highest_synced_epoch,
-                        false,
-                        sync_request_target,
+        // If we've already reached the sync target, there's nothing to stream
+        // (the driver will respond to consensus once storage has been drained).
+        if let Some(sync_request_target) = &sync_request_target {
+            let sync_request_version = sync_request_target.ledger_info().version();
+            if sync_request_version <= highest_synced_version {
+                sample!(
+                    SampleRate::Duration(Duration::from_secs(PENDING_DATA_LOG_FREQ_SECS)),
+                    info!(
+                        "Already synced to the sync request target! Target version: {:?}, synced version: {:?}",
+                        sync_request_version, highest_synced_version
                     )
-                    .await?
+                );
+                return Ok(());
             }
-        };
+        }
+
+        // Determine if we should execute transactions instead of syncing outputs
+        self.executing_fallback_transactions =
+            self.driver_configuration.config.continuous_syncing_mode
+                == ContinuousSyncingMode::ApplyTransactionOutputs
+                && self.output_fallback_handler.in_fallback_mode();
+
+        // Initialize a new active data stream. If we're syncing to a timestamp
+        // target version (in the middle of a chunk), we only stream the data up
+        // to the target version (using the proof ledger info of the chunk).
+        let max_chunk_size = Some(self.chunk_size_controller.get_chunk_size());
+        let timestamp_target_bounds = self
+            .get_timestamp_target_version()
+            .zip(self.get_timestamp_target_proof());
+        let active_data_stream =
+            if let Some((target_version, proof_ledger_info)) = timestamp_target_bounds {
+                self.stream_to_timestamp_target_version(
+                    highest_synced_version,
+                    target_version,
+                    &proof_ledger_info,
+                )
+                .await?
+            } else {
+                match self.get_continuous_syncing_mode() {
+                    ContinuousSyncingMode::ApplyTransactionOutputs => {
+                        let result = self
+                            .streaming_client
+                            .continuously_stream_transaction_outputs(
+                                highest_synced_version,
+                                highest_synced_epoch,
+                                sync_request_target,
+                                max_chunk_size,
+                            )
+                            .await;
+                        self.output_fallback_handler
+                            .handle_output_stream_result(result)?
+                    }
+                    ContinuousSyncingMode::ExecuteTransactions => {
+                        self.streaming_client
+                            .continuously_stream_transactions(
+                                highest_synced_version,
+                                highest_synced_epoch,
+                                false,
+                                sync_request_target,
+                                max_chunk_size,
+                            )
+                            .await?
+                    }
+                }
+            };
         self.speculative_stream_state = Some(SpeculativeStreamState::new(
             highest_epoch_state,
             None,
@@ -142,9 +475,55 @@ impl<
         Ok(())
     }
 
-    /// Attempts to fetch a data notification from the active stream
-    async fn fetch_next_data_notification(&mut self) -> Result<DataNotification, Error> {
+    /// Fetches a data stream for all data from the highest synced version up to
+    /// (and including) the timestamp target version, using the given proof.
+    async fn stream_to_timestamp_target_version(
+        &mut self,
+        highest_synced_version: Version,
+        target_version: Version,
+        proof_ledger_info: &LedgerInfoWithSignatures,
+    ) -> Result<DataStreamListener, Error> {
+        let start_version = highest_synced_version
+            .checked_add(1)
+            .ok_or_else(|| Error::IntegerOverflow("The start version has overflown!".into()))?;
+        let proof_version = proof_ledger_info.ledger_info().version();
+        let active_data_stream = match self.get_continuous_syncing_mode() {
+            ContinuousSyncingMode::ApplyTransactionOutputs => {
+                let result = self
+                    .streaming_client
+                    .get_all_transaction_outputs(start_version, target_version, proof_version)
+                    .await;
+                self.output_fallback_handler
+                    .handle_output_stream_result(result)?
+            }
+            ContinuousSyncingMode::ExecuteTransactions => {
+                self.streaming_client
+                    .get_all_transactions(start_version, target_version, proof_version, false)
+                    .await?
+            }
+        };
+
+        Ok(active_data_stream)
+    }
+
+    /// Attempts to fetch a data notification from the active stream. If we're
+    /// streaming via subscriptions and no new data has arrived, None is returned.
+    async fn fetch_next_data_notification(&mut self) -> Result<Option<DataNotification>, Error> {
         let max_stream_wait_time_ms = self.driver_configuration.config.max_stream_wait_time_ms;
+        if self.is_subscription_streaming() {
+            let result = utils::get_subscription_notification(
+                max_stream_wait_time_ms,
+                self.active_data_stream.as_mut(),
+            )
+            .await;
+            if result.is_err() {
+                // The subscription stream has terminated, so we need to reset it
+                warn!("Resetting the subscription stream as it has terminated!");
+                self.reset_active_stream();
+            }
+            return result;
+        }
+
         let result =
             utils::get_data_notification(max_stream_wait_time_ms, self.active_data_stream.as_mut())
                 .await;
@@ -153,7 +532,7 @@ impl<
             warn!("Resetting the currently active data stream due to too many timeouts!");
             self.reset_active_stream();
         }
-        result
+        result.map(Some)
     }
 
     /// Processes any notifications already pending on the active stream
@@ -166,9 +545,19 @@ impl<
             .config
             .max_consecutive_stream_notifications
         {
+            // If too many data chunks are pending, stop polling the stream
+            // until the verification pool and storage synchronizer have caught up.
+            if self.pending_data_limit_reached() {
+                return Ok(());
+            }
+
             // Fetch and process any data notifications
-            let data_notification = self.fetch_next_data_notification().await?;
-            match data_notification.data_payload {
+            let data_notification = match self.fetch_next_data_notification().await? {
+                Some(data_notification) => data_notification,
+                None => return Ok(()), // No new data has been committed by our peers
+            };
+            let timestamp_target_proof = self.get_timestamp_target_proof();
+            let epoch_changed = match data_notification.data_payload {
                 DataPayload::ContinuousTransactionOutputsWithProof(
                     ledger_info_with_sigs,
                     transaction_outputs_with_proof,
@@ -183,7 +572,7 @@ impl<
                         Some(transaction_outputs_with_proof),
                         payload_start_version,
                     )
-                    .await?;
+                    .await?
                 }
                 DataPayload::ContinuousTransactionsWithProof(
                     ledger_info_with_sigs,
@@ -198,19 +587,105 @@ impl<
                         None,
                         payload_start_version,
                     )
-                    .await?;
+                    .await?
+                }
+                DataPayload::TransactionOutputsWithProof(transaction_outputs_with_proof)
+                    if timestamp_target_proof.is_some() =>
+                {
+                    // We're syncing outputs up to the timestamp target version
+                    let payload_start_version =
+                        transaction_outputs_with_proof.first_transaction_output_version;
+                    self.process_transaction_or_output_payload(
+                        consensus_sync_request.clone(),
+                        data_notification.notification_id,
+                        timestamp_target_proof.expect("The proof should exist!"),
+                        None,
+                        Some(transaction_outputs_with_proof),
+                        payload_start_version,
+                    )
+                    .await?
+                }
+                DataPayload::TransactionsWithProof(transactions_with_proof)
+                    if timestamp_target_proof.is_some() =>
+                {
+                    // We're syncing transactions up to the timestamp target version
+                    let payload_start_version = transactions_with_proof.first_transaction_version;
+                    self.process_transaction_or_output_payload(
+                        consensus_sync_request.clone(),
+                        data_notification.notification_id,
+                        timestamp_target_proof.expect("The proof should exist!"),
+                        Some(transactions_with_proof),
+                        None,
+                        payload_start_version,
+                    )
+                    .await?
                 }
                 _ => {
                     return self
                         .handle_end_of_stream_or_invalid_payload(data_notification)
                         .await;
                 }
+            };
+
+            // Send any chunks that have been verified in the meantime to storage
+            // (without waiting for the chunk that was just queued). If one of
+            // them crossed the timestamp target, the stream has been reset.
+            self.apply_verified_chunks(false).await?;
+            if self.active_data_stream.is_none() {
+                return Ok(());
+            }
+
+            // If we've streamed up to the timestamp target version, there's nothing left to stream
+            if self.timestamp_target_version_synced()? {
+                self.end_active_stream();
+                return Ok(());
+            }
+
+            if self.is_subscription_streaming() {
+                // Subscription streams are long-lived, so any chunk size change
+                // only applies to the next stream. The stream is only reset
+                // when the epoch changes.
+                self.update_chunk_size();
+                if epoch_changed {
+                    self.end_active_stream();
+                    return Ok(());
+                }
+            } else if self.update_chunk_size() {
+                // If the chunk size has changed, end the stream so that the
+                // next stream is created with the new chunk size.
+                self.end_active_stream();
+                return Ok(());
             }
         }
 
         Ok(())
     }
 
+    /// Updates the chunk size using the latest chunk latencies of the
+    /// storage synchronizer. Returns true iff the chunk size changed.
+    fn update_chunk_size(&mut self) -> bool {
+        let chunk_latency_average = self.storage_synchronizer.get_chunk_latency_average();
+        self.chunk_size_controller
+            .update_chunk_size(chunk_latency_average)
+    }
+
+    /// Returns true iff new data is streamed via long-lived subscription streams
+    fn is_subscription_streaming(&self) -> bool {
+        self.driver_configuration.config.continuous_streaming_mode
+            == ContinuousStreamingMode::Subscription
+    }
+
+    /// Returns the continuous syncing mode used by the active data stream. This
+    /// is the configured mode, unless syncing transaction outputs has fallen
+    /// back to executing transactions.
+    fn get_continuous_syncing_mode(&self) -> ContinuousSyncingMode {
+        if self.executing_fallback_transactions {
+            ContinuousSyncingMode::ExecuteTransactions
+        } else {
+            self.driver_configuration.config.continuous_syncing_mode
+        }
+    }
+
     /// Returns the highest synced version and epoch in storage
     fn get_highest_synced_version_and_epoch(&self) -> Result<(Version, Epoch), Error> {
         let highest_synced_version = utils::fetch_latest_synced_version(self.storage.clone())?;
@@ -219,7 +694,8 @@ impl<
         Ok((highest_synced_version, highest_synced_epoch))
     }
 
-    /// Process a single transaction or transaction output data payload
+    /// Process a single transaction or transaction output data payload.
+    /// Returns true iff the payload ended the current epoch.
     async fn process_transaction_or_output_payload(
         &mut self,
         consensus_sync_request: Arc<Mutex<Option<ConsensusSyncRequest>>>,
@@ -228,7 +704,7 @@ impl<
         transaction_list_with_proof: Option<TransactionListWithProof>,
         transaction_outputs_with_proof: Option<TransactionOutputListWithProof>,
         payload_start_version: Option<Version>,
-    ) -> Result<(), Error> {
+    ) -> Result<bool, Error> {
         // Verify the payload starting version
         let payload_start_version = self
             .verify_payload_start_version(notification_id, payload_start_version)
@@ -242,65 +718,268 @@ impl<
         )
         .await?;
 
-        // Execute/apply and commit the transactions/outputs
-        let num_transactions_or_outputs =
-            match self.driver_configuration.config.continuous_syncing_mode {
-                ContinuousSyncingMode::ApplyTransactionOutputs => {
-                    if let Some(transaction_outputs_with_proof) = transaction_outputs_with_proof {
-                        let num_transaction_outputs = transaction_outputs_with_proof
-                            .transactions_and_outputs
-                            .len();
-                        self.storage_synchronizer.apply_transaction_outputs(
-                            notification_id,
-                            transaction_outputs_with_proof,
-                            ledger_info_with_signatures.clone(),
-                            None,
-                        )?;
-                        num_transaction_outputs
-                    } else {
-                        self.terminate_active_stream(
-                            notification_id,
-                            NotificationFeedback::PayloadTypeIsIncorrect,
-                        )
-                        .await?;
-                        return Err(Error::InvalidPayload(
-                            "Did not receive transaction outputs with proof!".into(),
-                        ));
-                    }
+        // Verify the payload type and count the transactions/outputs
+        let num_transactions_or_outputs = match self.get_continuous_syncing_mode() {
+            ContinuousSyncingMode::ApplyTransactionOutputs => {
+                if let Some(transaction_outputs_with_proof) = &transaction_outputs_with_proof {
+                    transaction_outputs_with_proof
+                        .transactions_and_outputs
+                        .len()
+                } else {
+                    self.terminate_active_stream(
+                        notification_id,
+                        NotificationFeedback::PayloadTypeIsIncorrect,
+                    )
+                    .await?;
+                    return Err(Error::InvalidPayload(
+                        "Did not receive transaction outputs with proof!".into(),
+                    ));
                 }
-                ContinuousSyncingMode::ExecuteTransactions => {
-                    if let Some(transaction_list_with_proof) = transaction_list_with_proof {
-                        let num_transactions = transaction_list_with_proof.transactions.len();
-                        self.storage_synchronizer.execute_transactions(
-                            notification_id,
-                            transaction_list_with_proof,
-                            ledger_info_with_signatures.clone(),
-                            None,
-                        )?;
-                        num_transactions
-                    } else {
-                        self.terminate_active_stream(
-                            notification_id,
-                            NotificationFeedback::PayloadTypeIsIncorrect,
-                        )
-                        .await?;
-                        return Err(Error::InvalidPayload(
-                            "Did not receive transactions with proof!".into(),
-                        ));
-                    }
+            }
+            ContinuousSyncingMode::ExecuteTransactions => {
+                if let Some(transaction_list_with_proof) = &transaction_list_with_proof {
+                    transaction_list_with_proof.transactions.len()
+                } else {
+                    self.terminate_active_stream(
+                        notification_id,
+                        NotificationFeedback::PayloadTypeIsIncorrect,
+                    )
+                    .await?;
+                    return Err(Error::InvalidPayload(
+                        "Did not receive transactions with proof!".into(),
+                    ));
                 }
-            };
+            }
+        };
+
+        // Verify the proof ledger info off the driver loop. The chunk will only
+        // be executed/applied once the verification has succeeded.
+        self.verify_chunk_in_background(
+            notification_id,
+            ledger_info_with_signatures.clone(),
+            transaction_list_with_proof,
+            transaction_outputs_with_proof,
+            payload_start_version,
+        );
         let synced_version = payload_start_version
             .checked_add(num_transactions_or_outputs as u64)
             .and_then(|version| version.checked_sub(1)) // synced_version = start + num txns/outputs - 1
             .ok_or_else(|| Error::IntegerOverflow("The synced version has overflown!".into()))?;
+        if self.is_subscription_streaming() {
+            self.update_subscription_lag_metrics(&ledger_info_with_signatures, synced_version);
+        }
         let speculative_stream_state = self.get_speculative_stream_state();
         speculative_stream_state.update_synced_version(synced_version);
-        speculative_stream_state.maybe_update_epoch_state(ledger_info_with_signatures);
+        let epoch_changed =
+            speculative_stream_state.maybe_update_epoch_state(ledger_info_with_signatures);
+
+        Ok(epoch_changed)
+    }
+
+    /// Verifies the proof ledger info of the given chunk on the verification
+    /// pool (against the speculative epoch state) and adds the chunk to the
+    /// queue of chunks pending verification.
+    fn verify_chunk_in_background(
+        &mut self,
+        notification_id: NotificationId,
+        ledger_info_with_signatures: LedgerInfoWithSignatures,
+        transaction_list_with_proof: Option<TransactionListWithProof>,
+        transaction_outputs_with_proof: Option<TransactionOutputListWithProof>,
+        payload_start_version: Version,
+    ) {
+        let verification_label = if transaction_list_with_proof.is_some() {
+            metrics::VERIFIED_TRANSACTIONS
+        } else {
+            metrics::VERIFIED_TRANSACTION_OUTPUTS
+        };
+
+        // Spawn the verification on the verification pool
+        let epoch_state = self.get_speculative_stream_state().get_epoch_state();
+        let ledger_info_verifier = self.ledger_info_verifier;
+        let ledger_info = ledger_info_with_signatures.clone();
+        let (verification_sender, verification_receiver) = oneshot::channel();
+        CHUNK_VERIFICATION_POOL.spawn(move || {
+            let verification_start_time = Instant::now();
+            let verification_result = ledger_info_verifier(&epoch_state, &ledger_info);
+            metrics::observe_duration(
+                &metrics::CONTINUOUS_SYNCER_VERIFICATION_LATENCIES,
+                verification_label,
+                verification_start_time.elapsed(),
+            );
+
+            // The chunk may have already been dropped (e.g., if an earlier chunk
+            // failed verification or the stream was reset), so we ignore any
+            // send errors.
+            let _ = verification_sender.send(verification_result);
+        });
+
+        self.pending_verification_chunks
+            .push_back(PendingVerificationChunk {
+                ledger_info_with_signatures,
+                notification_id,
+                payload_start_version,
+                transaction_list_with_proof,
+                transaction_outputs_with_proof,
+                verification_receiver,
+            });
+    }
+
+    /// Sends the chunks that have been successfully verified to the storage
+    /// synchronizer (in stream order). If a chunk fails verification, the
+    /// active stream is terminated and all subsequent chunks are dropped. If
+    /// `wait_for_verification` is false, this won't wait for chunks that are
+    /// still being verified (i.e., it won't block the driver loop).
+    async fn apply_verified_chunks(&mut self, wait_for_verification: bool) -> Result<(), Error> {
+        while let Some(pending_chunk) = self.pending_verification_chunks.front_mut() {
+            // If too many data chunks are pending in storage, wait for the
+            // storage synchronizer to catch up.
+            if self.storage_synchronizer.pending_data_limit_reached() {
+                return Ok(());
+            }
+
+            // Fetch the verification result of the next chunk
+            let verification_result = if wait_for_verification {
+                (&mut pending_chunk.verification_receiver).await
+            } else {
+                match pending_chunk.verification_receiver.try_recv() {
+                    Ok(Some(verification_result)) => Ok(verification_result),
+                    Ok(None) => return Ok(()), // The chunk is still being verified
+                    Err(canceled) => Err(canceled),
+                }
+            };
+            let pending_chunk = self
+                .pending_verification_chunks
+                .pop_front()
+                .expect("The pending chunk should exist!");
+
+            // If verification failed, drop all subsequent chunks and terminate the stream
+            let verification_result = match verification_result {
+                Ok(verification_result) => verification_result,
+                Err(canceled) => Err(canceled.into()),
+            };
+            if let Err(error) = verification_result {
+                self.terminate_active_stream(
+                    pending_chunk.notification_id,
+                    NotificationFeedback::PayloadProofFailed,
+                )
+                .await?;
+                return Err(error);
+            }
+
+            // Execute/apply and commit the verified chunk
+            self.apply_verified_chunk(pending_chunk)?;
+        }
 
         Ok(())
     }
 
+    /// Sends the given verified chunk to the storage synchronizer (unless
+    /// the chunk crosses the timestamp target).
+    fn apply_verified_chunk(
+        &mut self,
+        pending_chunk: PendingVerificationChunk,
+    ) -> Result<(), Error> {
+        let PendingVerificationChunk {
+            ledger_info_with_signatures,
+            notification_id,
+            payload_start_version,
+            transaction_list_with_proof,
+            transaction_outputs_with_proof,
+            ..
+        } = pending_chunk;
+
+        // If the chunk crosses the timestamp target, we stop streaming. The
+        // data up to the target version will be synced using a new stream.
+        if let Some(target_version) = self.check_timestamp_target_crossed(
+            payload_start_version,
+            &ledger_info_with_signatures,
+            transaction_list_with_proof.as_ref(),
+            transaction_outputs_with_proof.as_ref(),
+        ) {
+            info!(
+                "The data stream has crossed the timestamp target! Target version: {:?}",
+                target_version
+            );
+            self.reset_active_stream();
+            return Ok(());
+        }
+
+        // Execute/apply and commit the transactions/outputs
+        match (transaction_list_with_proof, transaction_outputs_with_proof) {
+            (Some(transaction_list_with_proof), _) => {
+                self.storage_synchronizer.execute_transactions(
+                    notification_id,
+                    transaction_list_with_proof,
+                    ledger_info_with_signatures,
+                    None,
+                )
+            }
+            (None, Some(transaction_outputs_with_proof)) => {
+                self.storage_synchronizer.apply_transaction_outputs(
+                    notification_id,
+                    transaction_outputs_with_proof,
+                    ledger_info_with_signatures,
+                    None,
+                )
+            }
+            (None, None) => Err(Error::InvalidPayload(
+                "The verified chunk contains no transactions or outputs!".into(),
+            )),
+        }
+    }
+
+    /// Returns true iff the number of data chunks pending verification or
+    /// storage has reached the max pipeline depth (i.e., `max_pending_data_chunks`).
+    fn pending_data_limit_reached(&self) -> bool {
+        let max_pending_data_chunks =
+            cmp::max(self.driver_configuration.config.max_pending_data_chunks, 1);
+        let num_pending_data_chunks = self.storage_synchronizer.get_num_pending_data_chunks()
+            + self.pending_verification_chunks.len() as u64;
+        num_pending_data_chunks >= max_pending_data_chunks
+    }
+
+    /// Returns true iff the active stream has synced up to (or beyond)
+    /// the timestamp target version (if one has been identified).
+    fn timestamp_target_version_synced(&mut self) -> Result<bool, Error> {
+        if let Some(target_version) = self.get_timestamp_target_version() {
+            let synced_version = self
+                .get_speculative_stream_state()
+                .expected_next_version()?
+                - 1;
+            Ok(synced_version >= target_version)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Updates the subscription lag metrics, i.e., the number of versions the
+    /// synced version lags behind the given ledger info and the time since the
+    /// ledger info was committed by the network.
+    fn update_subscription_lag_metrics(
+        &self,
+        ledger_info_with_signatures: &LedgerInfoWithSignatures,
+        synced_version: Version,
+    ) {
+        let ledger_info = ledger_info_with_signatures.ledger_info();
+        let version_lag = ledger_info.version().saturating_sub(synced_version);
+        metrics::set_gauge(
+            &metrics::SUBSCRIPTION_LAG,
+            metrics::SUBSCRIPTION_VERSION_LAG,
+            version_lag,
+        );
+
+        let commit_time = Duration::from_micros(ledger_info.timestamp_usecs());
+        let time_lag = self
+            .time_service
+            .now_unix_time()
+            .saturating_sub(commit_time);
+        metrics::set_gauge(
+            &metrics::SUBSCRIPTION_LAG,
+            metrics::SUBSCRIPTION_TIME_LAG_MS,
+            time_lag.as_millis() as u64,
+        );
+    }
+
     /// Verifies the first payload version matches the version we wish to sync
     async fn verify_payload_start_version(
         &mut self,
@@ -334,8 +1013,11 @@ impl<
         }
     }
 
-    /// Verifies the given ledger info to be used as a transaction or transaction
-    /// output chunk proof. If verification fails, the active stream is terminated.
+    /// Verifies that the proof ledger info of a transaction or transaction
+    /// output chunk isn't beyond the sync target (if any). Only the target
+    /// bound is checked here: the ledger info signatures are verified off the
+    /// driver loop (see `verify_chunk_in_background()`). If the check fails,
+    /// the active stream is terminated.
     async fn verify_proof_ledger_info(
         &mut self,
         consensus_sync_request: Arc<Mutex<Option<ConsensusSyncRequest>>>,
@@ -363,17 +1045,7 @@ impl<
             }
         }
 
-        // Verify the ledger info state and signatures
-        if let Err(error) = self
-            .get_speculative_stream_state()
-            .verify_ledger_info_with_signatures(ledger_info_with_signatures)
-        {
-            self.terminate_active_stream(notification_id, NotificationFeedback::PayloadProofFailed)
-                .await?;
-            Err(error)
-        } else {
-            Ok(())
-        }
+        Ok(())
     }
 
     /// Handles the end of stream notification or an invalid payload by
@@ -382,7 +1054,13 @@ impl<
         &mut self,
         data_notification: DataNotification,
     ) -> Result<(), Error> {
-        self.reset_active_stream();
+        // The chunks of a completed stream are still valid, so only drop
+        // the pending chunks if the payload was invalid.
+        if matches!(data_notification.data_payload, DataPayload::EndOfStream) {
+            self.end_active_stream();
+        } else {
+            self.reset_active_stream();
+        }
 
         utils::handle_end_of_stream_or_invalid_payload(
             &mut self.streaming_client,
@@ -414,9 +1092,47 @@ impl<
             .expect("Speculative stream state does not exist!")
     }
 
-    /// Resets the currently active data stream and speculative state
-    pub fn reset_active_stream(&mut self) {
+    /// Sets the function used to verify proof ledger infos (for testing)
+    #[cfg(test)]
+    pub(crate) fn set_ledger_info_verifier(&mut self, ledger_info_verifier: LedgerInfoVerifier) {
+        self.ledger_info_verifier = ledger_info_verifier;
+    }
+
+    /// Returns the number of chunks pending verification
+    pub fn get_num_pending_verification_chunks(&self) -> usize {
+        self.pending_verification_chunks.len()
+    }
+
+    /// Waits for the pending chunks to be verified and sends them to storage.
+    /// Returns early if too many data chunks are pending in storage.
+    pub async fn wait_for_verified_chunks(&mut self) -> Result<(), Error> {
+        self.apply_verified_chunks(true).await
+    }
+
+    /// Ends the currently active data stream (e.g., to create a new stream
+    /// with a different chunk size), but keeps the chunks of the stream that
+    /// are still pending verification. No new stream is created until those
+    /// have been sent to storage.
+    fn end_active_stream(&mut self) {
         self.speculative_stream_state = None;
         self.active_data_stream = None;
     }
+
+    /// Resets the currently active data stream and speculative state, and
+    /// drops any chunks still pending verification. This drops the receivers
+    /// of their verification results, so none of them will reach storage.
+    pub fn reset_active_stream(&mut self) {
+        self.end_active_stream();
+        self.pending_verification_chunks.clear();
+    }
+}
+
+/// Notifies the timestamp target listener of the given result
+fn notify_timestamp_target_listener(
+    notifier_channel: oneshot::Sender<Result<Version, Error>>,
+    result: Result<Version, Error>,
+) -> Result<(), Error> {
+    notifier_channel.send(result).map_err(|error| {
+        Error::CallbackSendFailed(format!("Timestamp target notification error: {:?}", error))
+    })
 }
@@ -2,11 +2,13 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
+    bootstrap_progress::BootstrapProgressNotifier,
     bootstrapper::Bootstrapper,
     continuous_syncer::ContinuousSyncer,
     driver_client::{ClientNotificationListener, DriverNotification},
     error::Error,
     logging::{LogEntry, LogSchema},
+    metadata_storage::MetadataStorageInterface,
     metrics,
     metrics::ExecutingComponent,
     notification_handlers::{
@@ -15,13 +17,16 @@ use crate::{
         MempoolNotificationHandler,
     },
     storage_synchronizer::StorageSynchronizerInterface,
+    sync_lag::SyncLagMonitor,
+    sync_status::{DriverState, SyncStatus, SyncStatusHandle},
     utils,
     utils::PENDING_DATA_LOG_FREQ_SECS,
 };
 use aptos_config::config::{RoleType, StateSyncDriverConfig};
-use aptos_data_client::AptosDataClient;
+use aptos_data_client::{AptosDataClient, GlobalDataSummary};
 use aptos_infallible::Mutex;
 use aptos_logger::prelude::*;
+use aptos_time_service::TimeService;
 use aptos_types::waypoint::Waypoint;
 use consensus_notifications::{
     ConsensusCommitNotification, ConsensusNotification, ConsensusSyncNotification,
@@ -62,9 +67,15 @@ impl DriverConfiguration {
 }
 
 /// The state sync driver that drives synchronization progress
-pub struct StateSyncDriver<DataClient, MempoolNotifier, StorageSyncer, StreamingClient> {
+pub struct StateSyncDriver<
+    DataClient,
+    MempoolNotifier,
+    MetadataStorage,
+    StorageSyncer,
+    StreamingClient,
+> {
     // The component that manages the initial bootstrapping of the node
-    bootstrapper: Bootstrapper<StorageSyncer, StreamingClient>,
+    bootstrapper: Bootstrapper<MetadataStorage, StorageSyncer, StreamingClient>,
 
     // The listener for client notifications
     client_notification_listener: ClientNotificationListener,
@@ -101,16 +112,25 @@ pub struct StateSyncDriver<DataClient, MempoolNotifier, StorageSyncer, Streaming
 
     // The storage synchronizer used to update local storage
     storage_synchronizer: StorageSyncer,
+
+    // The monitor that raises alarms when the node falls too far behind
+    sync_lag_monitor: SyncLagMonitor,
+
+    // The handle through which the driver publishes its sync status
+    sync_status_handle: SyncStatusHandle,
 }
 
 impl<
         DataClient: AptosDataClient + Send + Clone + 'static,
         MempoolNotifier: MempoolNotificationSender,
+        MetadataStorage: MetadataStorageInterface + Clone,
         StorageSyncer: StorageSynchronizerInterface + Clone,
         StreamingClient: DataStreamingClient + Clone,
-    > StateSyncDriver<DataClient, MempoolNotifier, StorageSyncer, StreamingClient>
+    >
+    StateSyncDriver<DataClient, MempoolNotifier, MetadataStorage, StorageSyncer, StreamingClient>
 {
     pub fn new(
+        bootstrap_progress_notifier: BootstrapProgressNotifier,
         client_notification_listener: ClientNotificationListener,
         commit_notification_listener: CommitNotificationListener,
         consensus_notification_handler: ConsensusNotificationHandler,
@@ -118,22 +138,30 @@ impl<
         error_notification_listener: ErrorNotificationListener,
         event_subscription_service: Arc<Mutex<EventSubscriptionService>>,
         mempool_notification_handler: MempoolNotificationHandler<MempoolNotifier>,
+        metadata_storage: MetadataStorage,
         storage_synchronizer: StorageSyncer,
         aptos_data_client: DataClient,
         streaming_client: StreamingClient,
         storage: Arc<dyn DbReader>,
+        sync_lag_monitor: SyncLagMonitor,
+        sync_status_handle: SyncStatusHandle,
+        time_service: TimeService,
     ) -> Self {
         let bootstrapper = Bootstrapper::new(
             driver_configuration.clone(),
+            metadata_storage,
             streaming_client.clone(),
             storage.clone(),
             storage_synchronizer.clone(),
+            time_service.clone(),
+            bootstrap_progress_notifier,
         );
         let continuous_syncer = ContinuousSyncer::new(
             driver_configuration.clone(),
             streaming_client,
             storage.clone(),
             storage_synchronizer.clone(),
+            time_service,
         );
 
         Self {
@@ -150,6 +178,8 @@ impl<
             start_time: None,
             storage,
             storage_synchronizer,
+            sync_lag_monitor,
+            sync_status_handle,
         }
     }
 
@@ -337,32 +367,70 @@ impl<
         // Initialize a new sync request
         let latest_synced_ledger_info =
             utils::fetch_latest_synced_ledger_info(self.storage.clone())?;
+        let latest_epoch_state = utils::fetch_latest_epoch_state(self.storage.clone())?;
         self.consensus_notification_handler
-            .initialize_sync_request(sync_notification, latest_synced_ledger_info)
+            .initialize_sync_request(
+                sync_notification,
+                latest_synced_ledger_info,
+                latest_epoch_state,
+            )
             .await
     }
 
     /// Handles a client notification sent by the driver client
     fn handle_client_notification(&mut self, notification: DriverNotification) {
-        debug!(LogSchema::new(LogEntry::ClientNotification)
-            .message("Received a notify bootstrap notification from the client!"));
         metrics::increment_counter(
             &metrics::DRIVER_COUNTERS,
             metrics::DRIVER_CLIENT_NOTIFICATION,
         );
 
-        // TODO(joshlind): refactor this if the client only supports one notification type!
-        // Extract the bootstrap notifier channel
-        let DriverNotification::NotifyOnceBootstrapped(notifier_channel) = notification;
+        match notification {
+            DriverNotification::NotifyOnceBootstrapped(notifier_channel) => {
+                debug!(LogSchema::new(LogEntry::ClientNotification)
+                    .message("Received a notify bootstrap notification from the client!"));
+
+                // Subscribe the bootstrap notifier channel
+                if let Err(error) = self
+                    .bootstrapper
+                    .subscribe_to_bootstrap_notifications(notifier_channel)
+                {
+                    error!(LogSchema::new(LogEntry::ClientNotification)
+                        .error(&error)
+                        .message("Failed to subscribe to bootstrap notifications!"));
+                }
+            }
+            DriverNotification::NotifyOnceTargetReached(notifier_channel) => {
+                debug!(LogSchema::new(LogEntry::ClientNotification)
+                    .message("Received a notify target reached notification from the client!"));
+
+                // Subscribe the target notifier channel
+                if let Err(error) = self
+                    .bootstrapper
+                    .subscribe_to_target_notifications(notifier_channel)
+                {
+                    error!(LogSchema::new(LogEntry::ClientNotification)
+                        .error(&error)
+                        .message("Failed to subscribe to target reached notifications!"));
+                }
+            }
+            DriverNotification::SyncToTimestamp(target_timestamp_usecs, notifier_channel) => {
+                debug!(
+                    LogSchema::new(LogEntry::ClientNotification).message(&format!(
+                        "Received a sync to timestamp notification from the client! Target: {:?}",
+                        target_timestamp_usecs
+                    ))
+                );
 
-        // Subscribe the bootstrap notifier channel
-        if let Err(error) = self
-            .bootstrapper
-            .subscribe_to_bootstrap_notifications(notifier_channel)
-        {
-            error!(LogSchema::new(LogEntry::ClientNotification)
-                .error(&error)
-                .message("Failed to subscribe to bootstrap notifications!"));
+                // Set the timestamp target for the continuous syncer
+                if let Err(error) = self
+                    .continuous_syncer
+                    .set_timestamp_target(target_timestamp_usecs, notifier_channel)
+                {
+                    error!(LogSchema::new(LogEntry::ClientNotification)
+                        .error(&error)
+                        .message("Failed to set the timestamp target!"));
+                }
+            }
         }
     }
 
@@ -441,6 +509,16 @@ impl<
                 error
             );
         };
+
+        // If the synced state snapshot can't be trusted, halt bootstrapping
+        let error = error_notification.error;
+        if matches!(error, Error::SnapshotRootHashMismatch(_)) {
+            if let Err(error) = self.bootstrapper.handle_fatal_error(error) {
+                error!(LogSchema::new(LogEntry::Driver)
+                    .error(&error)
+                    .message("Failed to notify the listeners of the fatal bootstrapping error!"));
+            }
+        }
     }
 
     /// Checks if the node has successfully reached the sync target
@@ -450,29 +528,33 @@ impl<
         }
 
         // There's an active sync request. Before checking if we've hit the target,
-        // wait for the storage synchronizer to drain first (to avoid preemptively
-        // notifying consensus).
-        while self.storage_synchronizer.pending_storage_data() {
+        // wait for the chunks pending verification and the storage synchronizer
+        // to drain first (to avoid preemptively notifying consensus).
+        while self.continuous_syncer.get_num_pending_verification_chunks() > 0
+            || self.storage_synchronizer.pending_storage_data()
+        {
+            self.continuous_syncer.wait_for_verified_chunks().await?;
             sample!(
                 SampleRate::Duration(Duration::from_secs(PENDING_DATA_LOG_FREQ_SECS)),
-                info!("Waiting for the storage synchronizer to handle pending data!")
+                info!("Waiting for the pending chunks and storage data to be handled!")
             );
         }
 
         // Check if we've hit the target
         let latest_synced_ledger_info =
             utils::fetch_latest_synced_ledger_info(self.storage.clone())?;
-        self.consensus_notification_handler
+        let result = self
+            .consensus_notification_handler
             .check_sync_request_progress(latest_synced_ledger_info)
-            .await?;
+            .await;
 
-        // If the sync request was successfully handled, reset the continuous syncer
+        // If the sync request was handled (or has failed), reset the continuous syncer
         // so that in the event another sync request occurs, we have a fresh state.
         if !self.active_sync_request() {
             self.continuous_syncer.reset_active_stream();
             self.storage_synchronizer.finish_chunk_executor(); // Consensus is now in control
         }
-        Ok(())
+        result
     }
 
     /// Returns true iff there's an active sync request from consensus
@@ -493,8 +575,28 @@ impl<
     /// Checks if the connection deadline has passed. If so, validators with
     /// genesis waypoints will be automatically marked as bootstrapped. This
     /// helps in the case of single node deployments, where there are no peers
-    /// and state sync is trivial.
+    /// and state sync is trivial. If genesis-only bootstrapping is enabled,
+    /// the node is bootstrapped immediately (without waiting for the deadline).
     fn check_auto_bootstrapping(&mut self) {
+        // If genesis-only bootstrapping is enabled, bootstrap immediately
+        // using the genesis data already committed to storage.
+        if !self.bootstrapper.is_bootstrapped()
+            && self
+                .driver_configuration
+                .config
+                .enable_genesis_only_bootstrapping
+        {
+            if let Err(error) = self.bootstrapper.bootstrap_from_local_genesis() {
+                sample!(
+                    SampleRate::Duration(Duration::from_secs(DRIVER_ERROR_LOG_FREQ_SECS)),
+                    error!(LogSchema::new(LogEntry::AutoBootstrapping)
+                        .error(&error)
+                        .message("Failed to bootstrap from local genesis!"));
+                );
+            }
+            return;
+        }
+
         if !self.bootstrapper.is_bootstrapped()
             && self.is_validator()
             && self.driver_configuration.waypoint.version() == 0
@@ -526,10 +628,80 @@ impl<
         }
     }
 
+    /// Publishes the current sync status to the sync status handle
+    fn publish_sync_status(&self, global_data_summary: &GlobalDataSummary) {
+        let latest_synced_ledger_info =
+            match utils::fetch_latest_synced_ledger_info(self.storage.clone()) {
+                Ok(latest_synced_ledger_info) => latest_synced_ledger_info,
+                Err(error) => {
+                    sample!(
+                        SampleRate::Duration(Duration::from_secs(DRIVER_ERROR_LOG_FREQ_SECS)),
+                        error!(LogSchema::new(LogEntry::Driver)
+                            .error(&error)
+                            .message("Unable to publish the sync status!"));
+                    );
+                    return;
+                }
+            };
+        let latest_synced_ledger_info = latest_synced_ledger_info.ledger_info();
+        let synced_version = latest_synced_ledger_info.version();
+
+        // Identify the highest version advertised by our peers
+        let highest_advertised_version = global_data_summary
+            .advertised_data
+            .highest_synced_ledger_info()
+            .map(|ledger_info| ledger_info.ledger_info().version());
+
+        // Determine the state of the driver
+        let driver_state = if !self.bootstrapper.is_bootstrapped() {
+            DriverState::Bootstrapping
+        } else if highest_advertised_version.map_or(false, |version| version > synced_version) {
+            DriverState::CatchingUp
+        } else {
+            DriverState::CaughtUp
+        };
+
+        self.sync_status_handle.update_status(SyncStatus {
+            driver_state,
+            synced_version,
+            synced_epoch: latest_synced_ledger_info.epoch(),
+            synced_timestamp_usecs: latest_synced_ledger_info.timestamp_usecs(),
+            highest_advertised_version,
+            snapshot_sync_progress_percent: self.bootstrapper.snapshot_sync_progress_percent(),
+        });
+    }
+
+    /// Checks how far the node lags behind its peers and raises an alarm if
+    /// the lag is too large. This is only done once the node has bootstrapped.
+    fn check_sync_lag(&mut self, global_data_summary: &GlobalDataSummary) {
+        if !self.bootstrapper.is_bootstrapped() {
+            return;
+        }
+
+        match utils::fetch_latest_synced_ledger_info(self.storage.clone()) {
+            Ok(latest_synced_ledger_info) => {
+                self.sync_lag_monitor
+                    .check_sync_lag(latest_synced_ledger_info.ledger_info(), global_data_summary);
+            }
+            Err(error) => {
+                sample!(
+                    SampleRate::Duration(Duration::from_secs(DRIVER_ERROR_LOG_FREQ_SECS)),
+                    error!(LogSchema::new(LogEntry::Driver)
+                        .error(&error)
+                        .message("Unable to check the sync lag!"));
+                );
+            }
+        }
+    }
+
     /// Checks that state sync is making progress
     async fn drive_progress(&mut self) {
-        // Fetch the global data summary and verify we have active peers
+        // Fetch the global data summary, publish our sync status and check the sync lag
         let global_data_summary = self.aptos_data_client.get_global_data_summary();
+        self.publish_sync_status(&global_data_summary);
+        self.check_sync_lag(&global_data_summary);
+
+        // Verify we have active peers
         if global_data_summary.is_empty() {
             trace!(LogSchema::new(LogEntry::Driver).message(
                 "The global data summary is empty! It's likely that we have no active peers."
@@ -557,6 +729,22 @@ impl<
 
         // Drive progress depending on if we're bootstrapping or continuously syncing
         if self.bootstrapper.is_bootstrapped() {
+            // If we bootstrapped to a target version, we halt (i.e., never continuously sync)
+            if let Some(target_version) = self
+                .driver_configuration
+                .config
+                .bootstrapping_target_version
+            {
+                sample!(
+                    SampleRate::Duration(Duration::from_secs(DRIVER_ERROR_LOG_FREQ_SECS)),
+                    info!(LogSchema::new(LogEntry::Driver).message(&format!(
+                        "Reached the bootstrapping target version: {:?}. Not syncing any further!",
+                        target_version
+                    )));
+                );
+                return;
+            }
+
             // Fetch any consensus sync requests
             let consensus_sync_request = self
                 .consensus_notification_handler
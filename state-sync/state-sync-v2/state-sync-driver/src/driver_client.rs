@@ -1,7 +1,8 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::error::Error;
+use crate::{bootstrap_progress::BootstrapProgress, error::Error};
+use aptos_types::transaction::Version;
 use futures::{
     channel::{mpsc, oneshot},
     future::Future,
@@ -12,24 +13,38 @@ use std::{
     pin::Pin,
     task::{Context, Poll},
 };
+use tokio::sync::watch;
 
 /// Notifications that can be sent to the state sync driver
 pub enum DriverNotification {
     NotifyOnceBootstrapped(oneshot::Sender<Result<(), Error>>),
+    NotifyOnceTargetReached(oneshot::Sender<Result<Version, Error>>),
+    SyncToTimestamp(u64, oneshot::Sender<Result<Version, Error>>),
 }
 
 /// A client for sending notifications to the state sync driver
 pub struct DriverClient {
+    bootstrap_progress_receiver: watch::Receiver<BootstrapProgress>,
     notification_sender: mpsc::UnboundedSender<DriverNotification>,
 }
 
 impl DriverClient {
-    pub fn new(notification_sender: mpsc::UnboundedSender<DriverNotification>) -> Self {
+    pub fn new(
+        bootstrap_progress_receiver: watch::Receiver<BootstrapProgress>,
+        notification_sender: mpsc::UnboundedSender<DriverNotification>,
+    ) -> Self {
         Self {
+            bootstrap_progress_receiver,
             notification_sender,
         }
     }
 
+    /// Returns a receiver for the bootstrapping progress of the node. The
+    /// channel is closed after the final `Complete` progress is published.
+    pub fn subscribe_to_bootstrap_progress(&self) -> watch::Receiver<BootstrapProgress> {
+        self.bootstrap_progress_receiver.clone()
+    }
+
     /// Notifies the caller once the driver has successfully bootstrapped the node
     pub fn notify_once_bootstrapped(&self) -> impl Future<Output = Result<(), Error>> {
         let mut notification_sender = self.notification_sender.clone();
@@ -42,6 +57,42 @@ impl DriverClient {
             callback_receiver.await?
         }
     }
+
+    /// Notifies the caller once the driver has bootstrapped the node to the
+    /// configured target version (and halted). The synced version is returned.
+    pub fn notify_once_target_reached(&self) -> impl Future<Output = Result<Version, Error>> {
+        let mut notification_sender = self.notification_sender.clone();
+        let (callback_sender, callback_receiver) = oneshot::channel();
+
+        async move {
+            notification_sender
+                .send(DriverNotification::NotifyOnceTargetReached(callback_sender))
+                .await?;
+            callback_receiver.await?
+        }
+    }
+
+    /// Sets a target ledger timestamp (in usecs) for the continuous syncer and
+    /// notifies the caller once the target has been reached. The driver stops
+    /// syncing at the last version with a timestamp at or below the target
+    /// (and halts). The synced version is returned.
+    pub fn sync_to_timestamp(
+        &self,
+        target_timestamp_usecs: u64,
+    ) -> impl Future<Output = Result<Version, Error>> {
+        let mut notification_sender = self.notification_sender.clone();
+        let (callback_sender, callback_receiver) = oneshot::channel();
+
+        async move {
+            notification_sender
+                .send(DriverNotification::SyncToTimestamp(
+                    target_timestamp_usecs,
+                    callback_sender,
+                ))
+                .await?;
+            callback_receiver.await?
+        }
+    }
 }
 
 /// A simple listener for client notifications
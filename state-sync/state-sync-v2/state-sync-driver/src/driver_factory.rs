@@ -2,17 +2,22 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
+    bootstrap_progress::{BootstrapProgress, BootstrapProgressNotifier},
     driver::{DriverConfiguration, StateSyncDriver},
     driver_client::{ClientNotificationListener, DriverClient, DriverNotification},
+    metadata_storage::MetadataStorageInterface,
     notification_handlers::{
         CommitNotificationListener, ConsensusNotificationHandler, ErrorNotificationListener,
         MempoolNotificationHandler,
     },
     storage_synchronizer::StorageSynchronizer,
+    sync_lag::{SyncLagListener, SyncLagMonitor},
+    sync_status::SyncStatusHandle,
 };
 use aptos_config::config::NodeConfig;
 use aptos_data_client::aptosnet::AptosNetDataClient;
 use aptos_infallible::Mutex;
+use aptos_time_service::TimeService;
 use aptos_types::waypoint::Waypoint;
 use consensus_notifications::ConsensusNotificationListener;
 use data_streaming_service::streaming_client::StreamingServiceClient;
@@ -22,12 +27,18 @@ use futures::channel::mpsc;
 use mempool_notifications::MempoolNotificationSender;
 use std::sync::Arc;
 use storage_interface::DbReaderWriter;
-use tokio::runtime::{Builder, Runtime};
+use tokio::{
+    runtime::{Builder, Runtime},
+    sync::watch,
+};
 
 /// Creates a new state sync driver and client
 pub struct DriverFactory {
+    bootstrap_progress_receiver: watch::Receiver<BootstrapProgress>,
     client_notification_sender: mpsc::UnboundedSender<DriverNotification>,
     _driver_runtime: Option<Runtime>,
+    sync_lag_listener: Option<SyncLagListener>,
+    sync_status_handle: SyncStatusHandle,
 }
 
 impl DriverFactory {
@@ -35,6 +46,7 @@ impl DriverFactory {
     pub fn create_and_spawn_driver<
         ChunkExecutor: ChunkExecutorTrait + 'static,
         MempoolNotifier: MempoolNotificationSender + 'static,
+        MetadataStorage: MetadataStorageInterface + Clone + Send + Sync + 'static,
     >(
         create_runtime: bool,
         node_config: &NodeConfig,
@@ -42,6 +54,7 @@ impl DriverFactory {
         storage: DbReaderWriter,
         chunk_executor: Arc<ChunkExecutor>,
         mempool_notification_sender: MempoolNotifier,
+        metadata_storage: MetadataStorage,
         consensus_listener: ConsensusNotificationListener,
         event_subscription_service: EventSubscriptionService,
         aptos_data_client: AptosNetDataClient,
@@ -53,11 +66,21 @@ impl DriverFactory {
             ClientNotificationListener::new(client_notification_receiver);
         let (commit_notification_sender, commit_notification_listener) =
             CommitNotificationListener::new();
-        let consensus_notification_handler = ConsensusNotificationHandler::new(consensus_listener);
+        let time_service = TimeService::real();
+        let consensus_notification_handler = ConsensusNotificationHandler::new(
+            consensus_listener,
+            node_config
+                .state_sync
+                .state_sync_driver
+                .sync_request_timeout_ms,
+            time_service.clone(),
+        );
         let (error_notification_sender, error_notification_listener) =
             ErrorNotificationListener::new();
         let mempool_notification_handler =
             MempoolNotificationHandler::new(mempool_notification_sender);
+        let (bootstrap_progress_notifier, bootstrap_progress_receiver) =
+            BootstrapProgressNotifier::new();
 
         // Create a new runtime (if required)
         let driver_runtime = if create_runtime {
@@ -76,11 +99,13 @@ impl DriverFactory {
         let event_subscription_service = Arc::new(Mutex::new(event_subscription_service));
         let (storage_synchronizer, _, _) = StorageSynchronizer::new(
             node_config.state_sync.state_sync_driver,
+            bootstrap_progress_notifier.clone(),
             chunk_executor,
             commit_notification_sender,
             error_notification_sender,
             event_subscription_service.clone(),
             mempool_notification_handler.clone(),
+            metadata_storage.clone(),
             storage.clone(),
             driver_runtime.as_ref(),
         );
@@ -92,8 +117,14 @@ impl DriverFactory {
             waypoint,
         );
 
+        // Create the sync lag monitor
+        let (sync_lag_monitor, sync_lag_listener) =
+            SyncLagMonitor::new(&node_config.state_sync.state_sync_driver);
+
         // Create the state sync driver
+        let sync_status_handle = SyncStatusHandle::new();
         let state_sync_driver = StateSyncDriver::new(
+            bootstrap_progress_notifier,
             client_notification_listener,
             commit_notification_listener,
             consensus_notification_handler,
@@ -101,10 +132,14 @@ impl DriverFactory {
             error_notification_listener,
             event_subscription_service,
             mempool_notification_handler,
+            metadata_storage,
             storage_synchronizer,
             aptos_data_client,
             streaming_service_client,
             storage.reader,
+            sync_lag_monitor,
+            sync_status_handle.clone(),
+            time_service,
         );
 
         // Spawn the driver
@@ -115,13 +150,31 @@ impl DriverFactory {
         }
 
         Self {
+            bootstrap_progress_receiver,
             client_notification_sender,
             _driver_runtime: driver_runtime,
+            sync_lag_listener: Some(sync_lag_listener),
+            sync_status_handle,
         }
     }
 
+    /// Returns the listener for sync lag alarms (i.e., notifications sent when
+    /// the node falls too far behind its peers). The listener can only be
+    /// taken once, so subsequent calls return None.
+    pub fn take_sync_lag_listener(&mut self) -> Option<SyncLagListener> {
+        self.sync_lag_listener.take()
+    }
+
+    /// Returns a handle through which the driver's sync status can be read
+    pub fn sync_status_handle(&self) -> SyncStatusHandle {
+        self.sync_status_handle.clone()
+    }
+
     /// Returns a new client that can be used to communicate with the driver
     pub fn create_driver_client(&self) -> DriverClient {
-        DriverClient::new(self.client_notification_sender.clone())
+        DriverClient::new(
+            self.bootstrap_progress_receiver.clone(),
+            self.client_notification_sender.clone(),
+        )
     }
 }
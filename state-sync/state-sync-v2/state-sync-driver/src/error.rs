@@ -1,6 +1,7 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
+use aptos_crypto::HashValue;
 use aptos_types::transaction::Version;
 use futures::channel::{mpsc::SendError, oneshot::Canceled};
 use serde::{Deserialize, Serialize};
@@ -18,6 +19,8 @@ pub enum Error {
     CallbackSendFailed(String),
     #[error("Timed-out waiting for a data stream too many times.")]
     CriticalDataStreamTimeout(String),
+    #[error("The requested data is unavailable in the network: {0}")]
+    DataIsUnavailable(String),
     #[error("Timed-out waiting for a notification from the data stream. Timeout: {0}")]
     DataStreamNotificationTimeout(String),
     #[error("Error encountered in the event subscription service: {0}")]
@@ -26,20 +29,40 @@ pub enum Error {
     FullNodeConsensusNotification(String),
     #[error("An integer overflow has occurred: {0}")]
     IntegerOverflow(String),
+    #[error("The bootstrapping target version is invalid: {0}")]
+    InvalidBootstrappingTarget(String),
     #[error("An invalid payload was received: {0}")]
     InvalidPayload(String),
+    #[error("The sync target timestamp is invalid: {0}")]
+    InvalidTimestampTarget(String),
     #[error("Failed to notify mempool of the new commit: {0}")]
     NotifyMempoolError(String),
     #[error("Received an old sync request for version {0}, but our committed version is: {1}")]
     OldSyncRequest(Version, Version),
+    #[error("The sync target is in an old epoch: {0}, but our current epoch is: {1}")]
+    OldSyncTargetEpoch(u64, u64),
     #[error("Received oneshot::canceled. The sender of a channel was dropped: {0}")]
     SenderDroppedError(String),
+    #[error("The committed state snapshot root hash does not match the target: {0}")]
+    SnapshotRootHashMismatch(String),
+    #[error("The state snapshot sync target does not match the verified target: {0}")]
+    SnapshotTargetMismatch(String),
     #[error("Unexpected storage error: {0}")]
     StorageError(String),
+    #[error("The consensus sync request has expired: {0}")]
+    SyncRequestExpired(String),
     #[error("Synced beyond the target version. Committed version: {0}, target version: {1}")]
     SyncedBeyondTarget(Version, Version),
     #[error("Verification error: {0}")]
     VerificationError(String),
+    #[error("The waypoint does not match the fetched ledger info! Waypoint version: {waypoint_version}, waypoint hash: {waypoint_hash}, ledger info version: {ledger_info_version}, ledger info hash: {ledger_info_hash}, epoch: {epoch}")]
+    WaypointMismatch {
+        waypoint_version: Version,
+        waypoint_hash: HashValue,
+        ledger_info_version: Version,
+        ledger_info_hash: HashValue,
+        epoch: u64,
+    },
     #[error("Unexpected error: {0}")]
     UnexpectedError(String),
 }
@@ -53,17 +76,25 @@ impl Error {
             Error::BootstrapNotComplete(_) => "bootstrap_not_complete",
             Error::CallbackSendFailed(_) => "callback_send_failed",
             Error::CriticalDataStreamTimeout(_) => "critical_data_stream_timeout",
+            Error::DataIsUnavailable(_) => "data_is_unavailable",
             Error::DataStreamNotificationTimeout(_) => "data_stream_notification_timeout",
             Error::EventNotificationError(_) => "event_notification_error",
             Error::FullNodeConsensusNotification(_) => "full_node_consensus_notification",
             Error::IntegerOverflow(_) => "integer_overflow",
+            Error::InvalidBootstrappingTarget(_) => "invalid_bootstrapping_target",
             Error::InvalidPayload(_) => "invalid_payload",
+            Error::InvalidTimestampTarget(_) => "invalid_timestamp_target",
             Error::NotifyMempoolError(_) => "notify_mempool_error",
             Error::OldSyncRequest(_, _) => "old_sync_request",
+            Error::OldSyncTargetEpoch(_, _) => "old_sync_target_epoch",
             Error::SenderDroppedError(_) => "sender_dropped_error",
+            Error::SnapshotRootHashMismatch(_) => "snapshot_root_hash_mismatch",
+            Error::SnapshotTargetMismatch(_) => "snapshot_target_mismatch",
             Error::StorageError(_) => "storage_error",
+            Error::SyncRequestExpired(_) => "sync_request_expired",
             Error::SyncedBeyondTarget(_, _) => "synced_beyond_target",
             Error::VerificationError(_) => "verification_error",
+            Error::WaypointMismatch { .. } => "waypoint_mismatch",
             Error::UnexpectedError(_) => "unexpected_error",
         }
     }
@@ -77,7 +108,12 @@ impl From<Canceled> for Error {
 
 impl From<data_streaming_service::error::Error> for Error {
     fn from(error: data_streaming_service::error::Error) -> Self {
-        Error::UnexpectedError(error.to_string())
+        match error {
+            data_streaming_service::error::Error::DataIsUnavailable(_) => {
+                Error::DataIsUnavailable(error.to_string())
+            }
+            error => Error::UnexpectedError(error.to_string()),
+        }
     }
 }
 
@@ -3,6 +3,7 @@
 
 #![forbid(unsafe_code)]
 
+pub mod bootstrap_progress;
 mod bootstrapper;
 mod continuous_syncer;
 mod driver;
@@ -10,9 +11,12 @@ mod driver_client;
 pub mod driver_factory;
 mod error;
 mod logging;
+pub mod metadata_storage;
 pub mod metrics;
 mod notification_handlers;
 mod storage_synchronizer;
+pub mod sync_lag;
+pub mod sync_status;
 mod utils;
 
 #[cfg(test)]
@@ -1,25 +1,36 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{error::Error, notification_handlers::ErrorNotification};
+use crate::{
+    error::Error, notification_handlers::ErrorNotification, storage_synchronizer::ChunkTimings,
+};
 use aptos_logger::Schema;
+use aptos_types::waypoint::Waypoint;
 use serde::Serialize;
 
 #[derive(Schema)]
 pub struct LogSchema<'a> {
     name: LogEntry,
+    chunk_timings: Option<&'a ChunkTimings>,
+    epoch: Option<u64>,
     error: Option<&'a Error>,
     error_notification: Option<ErrorNotification>,
+    ledger_info_waypoint: Option<Waypoint>,
     message: Option<&'a str>,
+    waypoint: Option<Waypoint>,
 }
 
 impl<'a> LogSchema<'a> {
     pub fn new(name: LogEntry) -> Self {
         Self {
             name,
+            chunk_timings: None,
+            epoch: None,
             error: None,
             error_notification: None,
+            ledger_info_waypoint: None,
             message: None,
+            waypoint: None,
         }
     }
 }
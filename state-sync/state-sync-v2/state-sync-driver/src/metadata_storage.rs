@@ -0,0 +1,295 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::error::Error;
+use anyhow::Result;
+use aptos_config::config::BootstrappingMode;
+use aptos_logger::prelude::*;
+use aptos_types::ledger_info::LedgerInfoWithSignatures;
+use schemadb::{
+    define_schema,
+    schema::{KeyCodec, ValueCodec},
+    ColumnFamilyName, Options, SchemaBatch, DB, DEFAULT_COLUMN_FAMILY_NAME,
+};
+use serde::{Deserialize, Serialize};
+use std::{path::Path, sync::Arc, time::Instant};
+
+/// The name of the state sync db file
+pub const STATE_SYNC_DB_NAME: &str = "state_sync_db";
+
+/// The name of the metadata column family
+const METADATA_CF_NAME: ColumnFamilyName = "metadata";
+
+/// A generic interface for persisting state sync metadata (e.g., the progress
+/// of a state snapshot sync) across node restarts.
+pub trait MetadataStorageInterface {
+    /// Returns true iff a state snapshot was successfully synced for the given
+    /// target ledger info.
+    fn is_snapshot_sync_complete(
+        &self,
+        target_ledger_info: &LedgerInfoWithSignatures,
+    ) -> Result<bool, Error>;
+
+    /// Gets the last persisted state value index for the snapshot sync at the
+    /// specified target ledger info. If no index is found, an error is returned.
+    fn get_last_persisted_state_value_index(
+        &self,
+        target_ledger_info: &LedgerInfoWithSignatures,
+    ) -> Result<u64, Error>;
+
+    /// Returns the target ledger info of any previous (or currently running)
+    /// state snapshot sync. If no snapshot sync was ever started, None is returned.
+    fn previous_snapshot_sync_target(&self) -> Result<Option<LedgerInfoWithSignatures>, Error>;
+
+    /// Updates the last persisted state value index for the state snapshot
+    /// sync at the specified target ledger info.
+    fn update_last_persisted_state_value_index(
+        &self,
+        target_ledger_info: &LedgerInfoWithSignatures,
+        last_persisted_state_value_index: u64,
+        snapshot_sync_completed: bool,
+    ) -> Result<(), Error>;
+
+    /// Deletes any previous (or currently running) state snapshot sync
+    /// progress. This is required if the synced snapshot can't be trusted.
+    fn delete_snapshot_sync_progress(&self) -> Result<(), Error>;
+
+    /// Returns the bootstrapping mode that was automatically selected by a
+    /// previous (or the currently running) bootstrapper. If no mode was ever
+    /// selected, None is returned.
+    fn get_selected_bootstrapping_mode(&self) -> Result<Option<BootstrappingMode>, Error>;
+
+    /// Updates the automatically selected bootstrapping mode. This ensures
+    /// the mode remains fixed across node restarts.
+    fn update_selected_bootstrapping_mode(
+        &self,
+        bootstrapping_mode: BootstrappingMode,
+    ) -> Result<(), Error>;
+}
+
+/// The metadata storage implementation used by state sync (backed by a dedicated db)
+#[derive(Clone)]
+pub struct PersistentMetadataStorage {
+    database: Arc<DB>,
+}
+
+impl PersistentMetadataStorage {
+    pub fn new<P: AsRef<Path> + Clone>(db_root_path: P) -> Self {
+        let column_families = vec![
+            /* UNUSED CF = */ DEFAULT_COLUMN_FAMILY_NAME,
+            METADATA_CF_NAME,
+        ];
+
+        let path = db_root_path.as_ref().join(STATE_SYNC_DB_NAME);
+        let instant = Instant::now();
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+        let database = DB::open(path.clone(), "state_sync", column_families, &options)
+            .expect("StateSyncDB open failed; unable to continue");
+
+        info!(
+            "Opened the state sync metadata db at {:?} in {} ms",
+            path,
+            instant.elapsed().as_millis()
+        );
+
+        Self {
+            database: Arc::new(database),
+        }
+    }
+
+    /// Returns the state snapshot progress for the given target (if one exists)
+    fn get_snapshot_progress_at_target(
+        &self,
+        target_ledger_info: &LedgerInfoWithSignatures,
+    ) -> Result<Option<StateSnapshotProgress>, Error> {
+        Ok(self
+            .get_snapshot_progress()?
+            .filter(|progress| &progress.target_ledger_info == target_ledger_info))
+    }
+
+    /// Returns the latest state snapshot progress (if one exists)
+    fn get_snapshot_progress(&self) -> Result<Option<StateSnapshotProgress>, Error> {
+        let metadata_value = self
+            .database
+            .get::<MetadataSchema>(&MetadataKey::StateSnapshotSync)
+            .map_err(|error| {
+                Error::StorageError(format!(
+                    "Failed to read the state snapshot progress! Error: {:?}",
+                    error
+                ))
+            })?;
+        match metadata_value {
+            Some(MetadataValue::StateSnapshotSync(snapshot_progress)) => {
+                Ok(Some(snapshot_progress))
+            }
+            Some(metadata_value) => Err(Error::StorageError(format!(
+                "Found an unexpected value for the state snapshot progress: {:?}",
+                metadata_value
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    /// Write the key value pair to the database
+    fn commit_key_value(
+        &self,
+        metadata_key: MetadataKey,
+        metadata_value: MetadataValue,
+    ) -> Result<(), Error> {
+        let batch = SchemaBatch::new();
+        batch
+            .put::<MetadataSchema>(&metadata_key, &metadata_value)
+            .map_err(|error| {
+                Error::StorageError(format!(
+                    "Failed to batch put the metadata key and value. Key: {:?}, Value: {:?}. Error: {:?}",
+                    metadata_key, metadata_value, error
+                ))
+            })?;
+        self.database.write_schemas(batch).map_err(|error| {
+            Error::StorageError(format!(
+                "Failed to write the metadata schema. Error: {:?}",
+                error
+            ))
+        })
+    }
+}
+
+impl MetadataStorageInterface for PersistentMetadataStorage {
+    fn is_snapshot_sync_complete(
+        &self,
+        target_ledger_info: &LedgerInfoWithSignatures,
+    ) -> Result<bool, Error> {
+        Ok(self
+            .get_snapshot_progress_at_target(target_ledger_info)?
+            .map_or(false, |progress| progress.snapshot_sync_completed))
+    }
+
+    fn get_last_persisted_state_value_index(
+        &self,
+        target_ledger_info: &LedgerInfoWithSignatures,
+    ) -> Result<u64, Error> {
+        self.get_snapshot_progress_at_target(target_ledger_info)?
+            .map(|progress| progress.last_persisted_state_value_index)
+            .ok_or_else(|| {
+                Error::StorageError(format!(
+                    "No state snapshot progress was found for the target: {:?}",
+                    target_ledger_info
+                ))
+            })
+    }
+
+    fn previous_snapshot_sync_target(&self) -> Result<Option<LedgerInfoWithSignatures>, Error> {
+        Ok(self
+            .get_snapshot_progress()?
+            .map(|progress| progress.target_ledger_info))
+    }
+
+    fn update_last_persisted_state_value_index(
+        &self,
+        target_ledger_info: &LedgerInfoWithSignatures,
+        last_persisted_state_value_index: u64,
+        snapshot_sync_completed: bool,
+    ) -> Result<(), Error> {
+        let metadata_value = MetadataValue::StateSnapshotSync(StateSnapshotProgress {
+            target_ledger_info: target_ledger_info.clone(),
+            last_persisted_state_value_index,
+            snapshot_sync_completed,
+        });
+        self.commit_key_value(MetadataKey::StateSnapshotSync, metadata_value)
+    }
+
+    fn delete_snapshot_sync_progress(&self) -> Result<(), Error> {
+        let metadata_key = MetadataKey::StateSnapshotSync;
+        let batch = SchemaBatch::new();
+        batch
+            .delete::<MetadataSchema>(&metadata_key)
+            .map_err(|error| {
+                Error::StorageError(format!(
+                    "Failed to batch delete the metadata key: {:?}. Error: {:?}",
+                    metadata_key, error
+                ))
+            })?;
+        self.database.write_schemas(batch).map_err(|error| {
+            Error::StorageError(format!(
+                "Failed to delete the state snapshot progress. Error: {:?}",
+                error
+            ))
+        })
+    }
+
+    fn get_selected_bootstrapping_mode(&self) -> Result<Option<BootstrappingMode>, Error> {
+        let metadata_value = self
+            .database
+            .get::<MetadataSchema>(&MetadataKey::SelectedBootstrappingMode)
+            .map_err(|error| {
+                Error::StorageError(format!(
+                    "Failed to read the selected bootstrapping mode! Error: {:?}",
+                    error
+                ))
+            })?;
+        match metadata_value {
+            Some(MetadataValue::SelectedBootstrappingMode(bootstrapping_mode)) => {
+                Ok(Some(bootstrapping_mode))
+            }
+            Some(metadata_value) => Err(Error::StorageError(format!(
+                "Found an unexpected value for the selected bootstrapping mode: {:?}",
+                metadata_value
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    fn update_selected_bootstrapping_mode(
+        &self,
+        bootstrapping_mode: BootstrappingMode,
+    ) -> Result<(), Error> {
+        let metadata_value = MetadataValue::SelectedBootstrappingMode(bootstrapping_mode);
+        self.commit_key_value(MetadataKey::SelectedBootstrappingMode, metadata_value)
+    }
+}
+
+/// A metadata key that can be inserted into the database
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum MetadataKey {
+    StateSnapshotSync,         // The state snapshot sync that was started
+    SelectedBootstrappingMode, // The bootstrapping mode that was automatically selected
+}
+
+/// A metadata value that can be inserted into the database
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum MetadataValue {
+    StateSnapshotSync(StateSnapshotProgress), // A state snapshot sync progress marker
+    SelectedBootstrappingMode(BootstrappingMode), // The automatically selected bootstrapping mode
+}
+
+/// A state snapshot progress marker
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct StateSnapshotProgress {
+    pub target_ledger_info: LedgerInfoWithSignatures,
+    pub last_persisted_state_value_index: u64,
+    pub snapshot_sync_completed: bool,
+}
+
+define_schema!(MetadataSchema, MetadataKey, MetadataValue, METADATA_CF_NAME);
+
+impl KeyCodec<MetadataSchema> for MetadataKey {
+    fn encode_key(&self) -> Result<Vec<u8>> {
+        Ok(bcs::to_bytes(self)?)
+    }
+
+    fn decode_key(data: &[u8]) -> Result<Self> {
+        Ok(bcs::from_bytes::<MetadataKey>(data)?)
+    }
+}
+
+impl ValueCodec<MetadataSchema> for MetadataValue {
+    fn encode_value(&self) -> Result<Vec<u8>> {
+        Ok(bcs::to_bytes(self)?)
+    }
+
+    fn decode_value(data: &[u8]) -> Result<Self> {
+        Ok(bcs::from_bytes::<MetadataValue>(data)?)
+    }
+}
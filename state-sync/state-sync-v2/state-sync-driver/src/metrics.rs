@@ -2,15 +2,35 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use aptos_metrics_core::{
-    register_int_counter_vec, register_int_gauge_vec, IntCounterVec, IntGaugeVec,
+    register_histogram_vec, register_int_counter_vec, register_int_gauge_vec, HistogramVec,
+    IntCounterVec, IntGaugeVec,
 };
 use once_cell::sync::Lazy;
+use std::time::Duration;
 
 /// Useful metric labels
+pub const COMMIT_NOTIFICATION_DUPLICATE: &str = "duplicate";
+pub const COMMIT_NOTIFICATION_MERGED: &str = "merged";
+pub const COMMIT_NOTIFICATION_OUT_OF_ORDER: &str = "out_of_order";
+pub const CONTINUOUS_SYNCER_CHUNK_SIZE: &str = "chunk_size";
 pub const DRIVER_CLIENT_NOTIFICATION: &str = "driver_client_notification";
 pub const DRIVER_CONSENSUS_COMMIT_NOTIFICATION: &str = "driver_consensus_commit_notification";
 pub const DRIVER_CONSENSUS_SYNC_NOTIFICATION: &str = "driver_consensus_sync_notification";
+pub const EPOCH_ENDING_VERIFICATION_FAILED: &str = "verification_failed";
+pub const EPOCH_ENDING_VERIFIED: &str = "verified";
+pub const STORAGE_SYNCHRONIZER_COMMITTER: &str = "committer";
+pub const STORAGE_SYNCHRONIZER_EXECUTOR: &str = "executor";
+pub const STORAGE_SYNCHRONIZER_MAX_PENDING_DATA: &str = "storage_synchronizer_max_pending_data";
 pub const STORAGE_SYNCHRONIZER_PENDING_DATA: &str = "storage_synchronizer_pending_data";
+pub const SYNC_REQUEST_EXPIRED: &str = "expired";
+pub const SYNC_REQUEST_FAILED: &str = "failed";
+pub const SYNC_REQUEST_SERVED: &str = "served";
+pub const SUBSCRIPTION_TIME_LAG_MS: &str = "time_lag_ms";
+pub const SYNC_LAG_SECONDS_BEHIND: &str = "seconds_behind";
+pub const SYNC_LAG_VERSIONS_BEHIND: &str = "versions_behind";
+pub const SUBSCRIPTION_VERSION_LAG: &str = "version_lag";
+pub const VERIFIED_TRANSACTION_OUTPUTS: &str = "transaction_outputs";
+pub const VERIFIED_TRANSACTIONS: &str = "transactions";
 
 /// An enum representing the component currently executing
 pub enum ExecutingComponent {
@@ -62,6 +82,36 @@ pub static BOOTSTRAPPER_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Counter for the data streams reset by the bootstrapper (e.g., due to stalls)
+pub static BOOTSTRAPPER_STREAM_RESETS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_state_sync_bootstrapper_stream_resets",
+        "Counters for the data streams reset by the bootstrapper",
+        &["stage"]
+    )
+    .unwrap()
+});
+
+/// Gauge for the bootstrapping mode selected by the bootstrapper
+pub static BOOTSTRAPPING_MODE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "aptos_state_sync_bootstrapping_mode",
+        "Gauge for the bootstrapping mode selected by the bootstrapper",
+        &["mode"]
+    )
+    .unwrap()
+});
+
+/// Gauges related to the continuous syncer
+pub static CONTINUOUS_SYNCER_GAUGES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "aptos_state_sync_continuous_syncer_gauges",
+        "Gauges related to the continuous syncer",
+        &["label"]
+    )
+    .unwrap()
+});
+
 /// Counter for state sync continuous syncer errors
 pub static CONTINUOUS_SYNCER_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
     register_int_counter_vec!(
@@ -72,6 +122,37 @@ pub static CONTINUOUS_SYNCER_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Time (secs) taken to verify the proof ledger info of each chunk synced
+/// by the continuous syncer (off the driver loop).
+pub static CONTINUOUS_SYNCER_VERIFICATION_LATENCIES: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "aptos_state_sync_continuous_syncer_verification_latencies",
+        "Latencies of verifying the chunks synced by the continuous syncer",
+        &["type"]
+    )
+    .unwrap()
+});
+
+/// Counters for the commit notifications that overlapped with those already handled
+pub static COMMIT_NOTIFICATIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_state_sync_commit_notifications",
+        "Counters for the commit notifications that overlapped with those already handled",
+        &["reconciliation"]
+    )
+    .unwrap()
+});
+
+/// Counters for the consensus sync requests handled by state sync
+pub static CONSENSUS_SYNC_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_state_sync_consensus_sync_requests",
+        "Counters for the consensus sync requests handled by state sync",
+        &["result"]
+    )
+    .unwrap()
+});
+
 /// Counters related to the state sync driver
 pub static DRIVER_COUNTERS: Lazy<IntCounterVec> = Lazy::new(|| {
     register_int_counter_vec!(
@@ -82,6 +163,26 @@ pub static DRIVER_COUNTERS: Lazy<IntCounterVec> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Counters for the epoch ending ledger infos verified by the bootstrapper
+pub static EPOCH_ENDING_VERIFICATIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_state_sync_epoch_ending_verifications",
+        "Counters for the epoch ending ledger infos verified by the bootstrapper",
+        &["result"]
+    )
+    .unwrap()
+});
+
+/// Counters for the times output syncing fell back to transaction execution
+pub static OUTPUT_SYNC_FALLBACKS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_state_sync_output_sync_fallbacks",
+        "Counters for the times output syncing fell back to transaction execution",
+        &["component"]
+    )
+    .unwrap()
+});
+
 /// Gauges related to the current epoch state
 pub static EPOCH_STATE: Lazy<IntGaugeVec> = Lazy::new(|| {
     register_int_gauge_vec!(
@@ -122,6 +223,39 @@ pub static STORAGE_SYNCHRONIZER_GAUGES: Lazy<IntGaugeVec> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Counters for the speculatively executed chunks retired by each stage of
+/// the storage synchronizer (i.e., after a preceding commit failed).
+pub static STORAGE_SYNCHRONIZER_RETIRED_CHUNKS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "aptos_state_sync_storage_synchronizer_retired_chunks",
+        "Counters for the speculative chunks retired by the storage synchronizer",
+        &["stage"]
+    )
+    .unwrap()
+});
+
+/// Time (secs) each stage of processing a chunk took in the storage
+/// synchronizer. Only observed when chunk timing logs are enabled.
+pub static STORAGE_SYNCHRONIZER_STAGE_LATENCIES: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "aptos_state_sync_storage_synchronizer_stage_latencies",
+        "Latencies of the stages of processing a chunk in the storage synchronizer",
+        &["stage"]
+    )
+    .unwrap()
+});
+
+/// Gauges for the utilization (percentage of time busy) of the executor and
+/// committer of the storage synchronizer.
+pub static STORAGE_SYNCHRONIZER_UTILIZATION: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "aptos_state_sync_storage_synchronizer_utilization",
+        "Gauges for the utilization of the storage synchronizer executor and committer",
+        &["stage"]
+    )
+    .unwrap()
+});
+
 /// Gauges for the storage synchronizer operations.
 /// Note: we keep this named "aptos_state_sync_version" to maintain backward
 /// compatibility with the metrics used by state sync v1.
@@ -134,11 +268,39 @@ pub static STORAGE_SYNCHRONIZER_OPERATIONS: Lazy<IntGaugeVec> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Gauges for how far the continuous syncer lags behind the data committed
+/// by peers (only updated when streaming data via subscriptions).
+pub static SUBSCRIPTION_LAG: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "aptos_state_sync_subscription_lag",
+        "Gauges for how far the continuous syncer lags behind subscription data",
+        &["label"]
+    )
+    .unwrap()
+});
+
+/// Gauges for how far the node lags behind the data advertised by its peers
+pub static SYNC_LAG: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "aptos_state_sync_sync_lag",
+        "Gauges for how far the node lags behind the data advertised by its peers",
+        &["label"]
+    )
+    .unwrap()
+});
+
 /// Increments the given counter with the provided label values.
 pub fn increment_counter(counter: &Lazy<IntCounterVec>, label: &str) {
     counter.with_label_values(&[label]).inc();
 }
 
+/// Observes the given duration (in secs) in the histogram with the specific label
+pub fn observe_duration(histogram: &Lazy<HistogramVec>, label: &str, duration: Duration) {
+    histogram
+        .with_label_values(&[label])
+        .observe(duration.as_secs_f64());
+}
+
 /// Increments the gauge with the specific label by the given delta
 pub fn increment_gauge(gauge: &Lazy<IntGaugeVec>, label: &str, delta: u64) {
     gauge.with_label_values(&[label]).add(delta as i64);
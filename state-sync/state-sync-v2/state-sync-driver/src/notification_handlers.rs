@@ -4,11 +4,17 @@
 use crate::{
     error::Error,
     logging::{LogEntry, LogSchema},
+    metrics,
 };
+use aptos_crypto::{hash::CryptoHash, HashValue};
 use aptos_infallible::Mutex;
 use aptos_logger::prelude::*;
+use aptos_time_service::{TimeService, TimeServiceTrait};
 use aptos_types::{
     contract_event::ContractEvent,
+    epoch_change::Verifier,
+    epoch_state::EpochState,
+    event::EventKey,
     ledger_info::LedgerInfoWithSignatures,
     transaction::{Transaction, Version},
 };
@@ -22,9 +28,11 @@ use futures::{channel::mpsc, stream::FusedStream, Stream};
 use mempool_notifications::MempoolNotificationSender;
 use serde::Serialize;
 use std::{
+    cmp::Ordering,
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
+    time::{Duration, Instant},
 };
 
 // TODO(joshlind): make these configurable!
@@ -69,7 +77,10 @@ impl CommitNotification {
     }
 
     /// Handles the commit notification by notifying mempool and the event
-    /// subscription service.
+    /// subscription service. Notifications for transactions that have already
+    /// been handled (e.g., duplicate or out-of-order notifications) are dropped,
+    /// and already handled transactions and events are trimmed from overlapping
+    /// notifications.
     pub async fn handle_transaction_notification<M: MempoolNotificationSender>(
         events: Vec<ContractEvent>,
         transactions: Vec<Transaction>,
@@ -78,6 +89,14 @@ impl CommitNotification {
         mut mempool_notification_handler: MempoolNotificationHandler<M>,
         event_subscription_service: Arc<Mutex<EventSubscriptionService>>,
     ) -> Result<(), Error> {
+        // Reconcile the transactions and events with those already handled
+        let (events, transactions) = match mempool_notification_handler
+            .reconcile_committed_transactions(events, transactions, latest_synced_version)
+        {
+            Some(events_and_transactions) => events_and_transactions,
+            None => return Ok(()),
+        };
+
         // Notify mempool of the committed transactions
         debug!(
             LogSchema::new(LogEntry::NotificationHandler).message(&format!(
@@ -102,7 +121,7 @@ impl CommitNotification {
         );
         event_subscription_service
             .lock()
-            .notify_events(latest_synced_version, events.clone())
+            .notify_events(latest_synced_version, events)
             .map_err(|error| error.into())
     }
 }
@@ -143,12 +162,17 @@ impl FusedStream for CommitNotificationListener {
 /// A consensus sync request for a specified target ledger info
 pub struct ConsensusSyncRequest {
     consensus_sync_notification: ConsensusSyncNotification,
+    start_time: Instant, // The time at which the sync request was received
 }
 
 impl ConsensusSyncRequest {
-    pub fn new(consensus_sync_notification: ConsensusSyncNotification) -> Self {
+    pub fn new(
+        consensus_sync_notification: ConsensusSyncNotification,
+        start_time: Instant,
+    ) -> Self {
         Self {
             consensus_sync_notification,
+            start_time,
         }
     }
 
@@ -164,13 +188,25 @@ pub struct ConsensusNotificationHandler {
 
     // The latest consensus sync request that has been received
     consensus_sync_request: Arc<Mutex<Option<ConsensusSyncRequest>>>,
+
+    // The max time to satisfy a sync request before it expires
+    sync_request_timeout: Duration,
+
+    // The time service used to track sync request expiry
+    time_service: TimeService,
 }
 
 impl ConsensusNotificationHandler {
-    pub fn new(consensus_listener: ConsensusNotificationListener) -> Self {
+    pub fn new(
+        consensus_listener: ConsensusNotificationListener,
+        sync_request_timeout_ms: u64,
+        time_service: TimeService,
+    ) -> Self {
         Self {
             consensus_listener,
             consensus_sync_request: Arc::new(Mutex::new(None)),
+            sync_request_timeout: Duration::from_millis(sync_request_timeout_ms),
+            time_service,
         }
     }
 
@@ -184,11 +220,15 @@ impl ConsensusNotificationHandler {
         self.consensus_sync_request.clone()
     }
 
-    /// Initializes the sync request received from consensus
+    /// Initializes the sync request received from consensus. If the target is
+    /// old (or has already been reached), consensus is notified immediately.
+    /// Otherwise, the target is checked against the latest epoch state (see
+    /// `verify_sync_target`).
     pub async fn initialize_sync_request(
         &mut self,
         sync_notification: ConsensusSyncNotification,
         latest_synced_ledger_info: LedgerInfoWithSignatures,
+        latest_epoch_state: EpochState,
     ) -> Result<(), Error> {
         // Get the latest committed version and the target sync version
         let sync_target_version = sync_notification.target.ledger_info().version();
@@ -215,63 +255,100 @@ impl ConsensusNotificationHandler {
             return result;
         }
 
+        // Verify the sync target against the latest epoch state
+        if let Err(error) = verify_sync_target(&sync_notification.target, &latest_epoch_state) {
+            self.respond_to_sync_notification(sync_notification, Err(error.clone()))
+                .await?;
+            return Err(error);
+        }
+
         // Save the request so we can notify consensus once we've hit the target
-        let consensus_sync_request = ConsensusSyncRequest::new(sync_notification);
+        let consensus_sync_request =
+            ConsensusSyncRequest::new(sync_notification, self.time_service.now());
         self.consensus_sync_request = Arc::new(Mutex::new(Some(consensus_sync_request)));
 
         Ok(())
     }
 
-    /// Checks to see if the sync request has been successfully fulfilled
+    /// Checks to see if the sync request has been successfully fulfilled. If
+    /// we've synced beyond the target, or the request has expired, consensus
+    /// is notified of the error and the request is dropped.
     pub async fn check_sync_request_progress(
         &mut self,
         latest_synced_ledger_info: LedgerInfoWithSignatures,
     ) -> Result<(), Error> {
-        // Fetch the sync target version
+        // Fetch the sync target version and start time
         let consensus_sync_request = self.get_consensus_sync_request();
-        let sync_target_version = consensus_sync_request.lock().as_ref().map(|sync_request| {
-            sync_request
-                .consensus_sync_notification
-                .target
-                .ledger_info()
-                .version()
-        });
+        let sync_target_version_and_start_time =
+            consensus_sync_request.lock().as_ref().map(|sync_request| {
+                let sync_target_version = sync_request
+                    .consensus_sync_notification
+                    .target
+                    .ledger_info()
+                    .version();
+                (sync_target_version, sync_request.start_time)
+            });
 
         // Compare our local state to the target version
-        if let Some(sync_target_version) = sync_target_version {
+        if let Some((sync_target_version, start_time)) = sync_target_version_and_start_time {
             let latest_committed_version = latest_synced_ledger_info.ledger_info().version();
 
             // Check if we've synced beyond the target
             if latest_committed_version > sync_target_version {
-                return Err(Error::SyncedBeyondTarget(
+                let error = Err(Error::SyncedBeyondTarget(
                     latest_committed_version,
                     sync_target_version,
                 ));
+                return self.finish_sync_request(error).await;
             }
 
             // Check if we've hit the target
             if latest_committed_version == sync_target_version {
-                let consensus_sync_request = self.get_consensus_sync_request().lock().take();
-                if let Some(consensus_sync_request) = consensus_sync_request {
-                    self.respond_to_sync_notification(
-                        consensus_sync_request.consensus_sync_notification,
-                        Ok(()),
-                    )
-                    .await?;
-                }
-                return Ok(());
+                return self.finish_sync_request(Ok(())).await;
+            }
+
+            // Check if the sync request has expired (e.g., no peers can serve the data)
+            let time_elapsed = self.time_service.now().duration_since(start_time);
+            if time_elapsed >= self.sync_request_timeout {
+                let error = Err(Error::SyncRequestExpired(format!(
+                    "Target version: {:?}, synced version: {:?}, time elapsed: {:?}",
+                    sync_target_version, latest_committed_version, time_elapsed
+                )));
+                return self.finish_sync_request(error).await;
             }
         }
 
         Ok(())
     }
 
+    /// Removes the active sync request and responds to consensus with the
+    /// given result. The result is then returned to the caller.
+    async fn finish_sync_request(&mut self, result: Result<(), Error>) -> Result<(), Error> {
+        let consensus_sync_request = self.get_consensus_sync_request().lock().take();
+        if let Some(consensus_sync_request) = consensus_sync_request {
+            self.respond_to_sync_notification(
+                consensus_sync_request.consensus_sync_notification,
+                result.clone(),
+            )
+            .await?;
+        }
+        result
+    }
+
     /// Responds to consensus for a sync notification using the specified result
     pub async fn respond_to_sync_notification(
         &mut self,
         sync_notification: ConsensusSyncNotification,
         result: Result<(), Error>,
     ) -> Result<(), Error> {
+        // Update the sync request metrics
+        let result_label = match &result {
+            Ok(()) => metrics::SYNC_REQUEST_SERVED,
+            Err(Error::SyncRequestExpired(_)) => metrics::SYNC_REQUEST_EXPIRED,
+            Err(_) => metrics::SYNC_REQUEST_FAILED,
+        };
+        metrics::increment_counter(&metrics::CONSENSUS_SYNC_REQUESTS, result_label);
+
         // Wrap the result in an error that consensus can process
         let message = result.map_err(|error| {
             consensus_notifications::Error::UnexpectedErrorEncountered(format!("{:?}", error))
@@ -324,6 +401,28 @@ impl ConsensusNotificationHandler {
     }
 }
 
+/// Verifies the given sync target against the latest epoch state. Targets in
+/// an older epoch are rejected. Targets in the current epoch must be signed by
+/// its validators. Targets in a future epoch (e.g., the last ledger info of an
+/// epoch change proof) can't be verified yet, so they're left to the
+/// verification of the data streamed to reach them.
+fn verify_sync_target(
+    sync_target: &LedgerInfoWithSignatures,
+    latest_epoch_state: &EpochState,
+) -> Result<(), Error> {
+    let sync_target_epoch = sync_target.ledger_info().epoch();
+    match sync_target_epoch.cmp(&latest_epoch_state.epoch) {
+        Ordering::Less => Err(Error::OldSyncTargetEpoch(
+            sync_target_epoch,
+            latest_epoch_state.epoch,
+        )),
+        Ordering::Equal => latest_epoch_state.verify(sync_target).map_err(|error| {
+            Error::VerificationError(format!("The sync target failed verification: {:?}", error))
+        }),
+        Ordering::Greater => Ok(()),
+    }
+}
+
 impl Stream for ConsensusNotificationHandler {
     type Item = ConsensusNotification;
 
@@ -379,18 +478,108 @@ impl FusedStream for ErrorNotificationListener {
     }
 }
 
-/// A simple handler for sending notifications to mempool
+/// The last committed transaction and event handled by a mempool
+/// notification handler
+#[derive(Default)]
+struct LastHandledCommit {
+    // The version and hash of the last committed transaction that was handled
+    transaction: Option<(Version, HashValue)>,
+
+    // The key and sequence number of the last committed event that was handled
+    event: Option<(EventKey, u64)>,
+}
+
+/// A simple handler for sending notifications to mempool. The handler also
+/// tracks the last committed transaction and event it has handled (across all
+/// clones) so that the same transactions and events are never forwarded twice.
 #[derive(Clone)]
 pub struct MempoolNotificationHandler<M> {
+    last_handled_commit: Arc<Mutex<LastHandledCommit>>,
+
     mempool_notification_sender: M,
 }
 
 impl<M: MempoolNotificationSender> MempoolNotificationHandler<M> {
     pub fn new(mempool_notification_sender: M) -> Self {
         Self {
+            last_handled_commit: Arc::new(Mutex::new(LastHandledCommit::default())),
             mempool_notification_sender,
         }
     }
+
+    /// Reconciles the given committed events and transactions (the last of
+    /// which is at `last_committed_version`) with the last transaction and
+    /// event already handled. Returns the events and transactions that still
+    /// need to be handled, or None if the notification is a duplicate or
+    /// arrived out of order.
+    ///
+    /// Note: if the notification overlaps with the handled transactions (i.e.,
+    /// the last handled transaction is found in the notification), the already
+    /// handled transactions are trimmed. Events can't be attributed to individual
+    /// transactions, so the events up to (and including) the last handled event
+    /// are trimmed instead. Event keys and sequence numbers are unique, so the
+    /// last handled event is only found in the notification if it overlaps.
+    pub fn reconcile_committed_transactions(
+        &self,
+        mut events: Vec<ContractEvent>,
+        mut transactions: Vec<Transaction>,
+        last_committed_version: Version,
+    ) -> Option<(Vec<ContractEvent>, Vec<Transaction>)> {
+        let last_transaction_hash = match transactions.last() {
+            Some(last_transaction) => last_transaction.hash(),
+            None => return Some((events, transactions)), // There's nothing to reconcile
+        };
+
+        let mut last_handled_commit = self.last_handled_commit.lock();
+        if let Some((handled_version, handled_hash)) = last_handled_commit.transaction {
+            // Drop the notification if it's older than the last handled transaction
+            if last_committed_version < handled_version {
+                drop_commit_notification(
+                    metrics::COMMIT_NOTIFICATION_OUT_OF_ORDER,
+                    last_committed_version,
+                    handled_version,
+                );
+                return None;
+            }
+
+            // Trim (or drop) the transactions that have already been handled
+            let num_new_transactions = last_committed_version - handled_version;
+            if num_new_transactions < transactions.len() as u64 {
+                let handled_index = transactions.len() - 1 - num_new_transactions as usize;
+                if transactions[handled_index].hash() == handled_hash {
+                    if num_new_transactions == 0 {
+                        drop_commit_notification(
+                            metrics::COMMIT_NOTIFICATION_DUPLICATE,
+                            last_committed_version,
+                            handled_version,
+                        );
+                        return None;
+                    }
+                    transactions.drain(..=handled_index);
+                    metrics::increment_counter(
+                        &metrics::COMMIT_NOTIFICATIONS,
+                        metrics::COMMIT_NOTIFICATION_MERGED,
+                    );
+                }
+            }
+        }
+        last_handled_commit.transaction = Some((last_committed_version, last_transaction_hash));
+
+        // Trim the events that have already been handled
+        if let Some((handled_key, handled_sequence_number)) = last_handled_commit.event {
+            if let Some(handled_index) = events.iter().position(|event| {
+                *event.key() == handled_key && event.sequence_number() == handled_sequence_number
+            }) {
+                events.drain(..=handled_index);
+            }
+        }
+        if let Some(last_event) = events.last() {
+            last_handled_commit.event = Some((*last_event.key(), last_event.sequence_number()));
+        }
+
+        Some((events, transactions))
+    }
+
     /// Notifies mempool that transactions have been committed.
     pub async fn notify_mempool_of_committed_transactions(
         &mut self,
@@ -417,3 +606,19 @@ impl<M: MempoolNotificationSender> MempoolNotificationHandler<M> {
         }
     }
 }
+
+/// Logs and counts a dropped commit notification (with the given reason)
+fn drop_commit_notification(
+    reason: &str,
+    last_committed_version: Version,
+    handled_version: Version,
+) {
+    debug!(
+        LogSchema::new(LogEntry::NotificationHandler).message(&format!(
+            "Dropping a commit notification ({})! Last committed version: {:?}, \
+            last handled version: {:?}",
+            reason, last_committed_version, handled_version
+        ))
+    );
+    metrics::increment_counter(&metrics::COMMIT_NOTIFICATIONS, reason);
+}
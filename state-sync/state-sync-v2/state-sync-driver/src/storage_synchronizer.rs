@@ -2,8 +2,10 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
+    bootstrap_progress::BootstrapProgressNotifier,
     error::Error,
     logging::{LogEntry, LogSchema},
+    metadata_storage::MetadataStorageInterface,
     metrics,
     notification_handlers::{
         CommitNotification, CommittedTransactions, ErrorNotification, MempoolNotificationHandler,
@@ -11,6 +13,7 @@ use crate::{
     utils,
 };
 use aptos_config::config::StateSyncDriverConfig;
+use aptos_crypto::HashValue;
 use aptos_infallible::Mutex;
 use aptos_logger::prelude::*;
 use aptos_types::{
@@ -18,19 +21,23 @@ use aptos_types::{
     state_store::state_value::StateValueChunkWithProof,
     transaction::{
         Transaction, TransactionListWithProof, TransactionOutput, TransactionOutputListWithProof,
+        Version,
     },
 };
 use data_streaming_service::data_notification::NotificationId;
 use event_notifications::EventSubscriptionService;
-use executor_types::ChunkExecutorTrait;
+use executor_types::{ChunkExecutorTrait, ChunkStageTimings};
 use futures::{channel::mpsc, SinkExt, StreamExt};
 use mempool_notifications::MempoolNotificationSender;
+use serde::{Serialize, Serializer};
 use std::{
+    cmp,
     future::Future,
     sync::{
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         Arc,
     },
+    time::{Duration, Instant},
 };
 use storage_interface::{DbReader, DbReaderWriter};
 use tokio::{
@@ -80,6 +87,12 @@ pub trait StorageSynchronizerInterface {
     /// to be executed/applied or committed.
     fn pending_storage_data(&self) -> bool;
 
+    /// Returns true iff the number of data chunks still waiting to be
+    /// executed/applied or committed has reached the max pipeline depth
+    /// (i.e., `max_pending_data_chunks`). If so, no new data chunks
+    /// should be sent to the storage synchronizer.
+    fn pending_data_limit_reached(&self) -> bool;
+
     /// Saves the given state values to storage.
     ///
     /// Note: this requires that `initialize_state_synchronizer` has been
@@ -97,13 +110,27 @@ pub trait StorageSynchronizerInterface {
     /// Finish the chunk executor at this round of state sync by releasing
     /// any in-memory resources to prevent memory leak.
     fn finish_chunk_executor(&self);
+
+    /// Returns the moving average of how long recent chunks of transactions
+    /// (or outputs) took to execute/apply and commit.
+    fn get_chunk_latency_average(&self) -> ChunkLatencyAverage;
+
+    /// Returns the number of data chunks still waiting to be
+    /// executed/applied or committed.
+    fn get_num_pending_data_chunks(&self) -> u64;
 }
 
 /// The implementation of the `StorageSynchronizerInterface` used by state sync
-pub struct StorageSynchronizer<ChunkExecutor> {
+pub struct StorageSynchronizer<ChunkExecutor, MetadataStorage> {
     // The executor for transaction and transaction output chunks
     chunk_executor: Arc<ChunkExecutor>,
 
+    // The moving average of the latencies of executing/applying and committing chunks
+    chunk_latency_average: Arc<Mutex<ChunkLatencyAverage>>,
+
+    // True iff a chunk commit failed (and speculatively executed chunks must be retired)
+    commit_failed: Arc<AtomicBool>,
+
     // A channel through which to notify the driver of committed data
     commit_notification_sender: mpsc::UnboundedSender<CommitNotification>,
 
@@ -116,6 +143,9 @@ pub struct StorageSynchronizer<ChunkExecutor> {
     // A channel through which to notify the executor of new data chunks
     executor_notifier: mpsc::Sender<StorageDataChunk>,
 
+    // The storage used to persist state sync metadata (e.g., snapshot progress)
+    metadata_storage: MetadataStorage,
+
     // The number of storage data chunks pending execute/apply, or commit
     pending_data_chunks: Arc<AtomicU64>,
 
@@ -131,14 +161,21 @@ pub struct StorageSynchronizer<ChunkExecutor> {
 
 // TODO(joshlind): this cannot currently be derived because of limitations around
 // how deriving `Clone` works. See: https://github.com/rust-lang/rust/issues/26925.
-impl<ChunkExecutor: ChunkExecutorTrait + 'static> Clone for StorageSynchronizer<ChunkExecutor> {
+impl<
+        ChunkExecutor: ChunkExecutorTrait + 'static,
+        MetadataStorage: MetadataStorageInterface + Clone,
+    > Clone for StorageSynchronizer<ChunkExecutor, MetadataStorage>
+{
     fn clone(&self) -> Self {
         Self {
             chunk_executor: self.chunk_executor.clone(),
+            chunk_latency_average: self.chunk_latency_average.clone(),
+            commit_failed: self.commit_failed.clone(),
             commit_notification_sender: self.commit_notification_sender.clone(),
             driver_config: self.driver_config,
             error_notification_sender: self.error_notification_sender.clone(),
             executor_notifier: self.executor_notifier.clone(),
+            metadata_storage: self.metadata_storage.clone(),
             pending_data_chunks: self.pending_data_chunks.clone(),
             runtime: self.runtime.clone(),
             state_snapshot_notifier: self.state_snapshot_notifier.clone(),
@@ -147,20 +184,32 @@ impl<ChunkExecutor: ChunkExecutorTrait + 'static> Clone for StorageSynchronizer<
     }
 }
 
-impl<ChunkExecutor: ChunkExecutorTrait + 'static> StorageSynchronizer<ChunkExecutor> {
+impl<
+        ChunkExecutor: ChunkExecutorTrait + 'static,
+        MetadataStorage: MetadataStorageInterface + Clone + Send + Sync + 'static,
+    > StorageSynchronizer<ChunkExecutor, MetadataStorage>
+{
     /// Returns a new storage synchronizer alongside the executor and committer handles
     pub fn new<MempoolNotifier: MempoolNotificationSender>(
         driver_config: StateSyncDriverConfig,
+        bootstrap_progress_notifier: BootstrapProgressNotifier,
         chunk_executor: Arc<ChunkExecutor>,
         commit_notification_sender: mpsc::UnboundedSender<CommitNotification>,
         error_notification_sender: mpsc::UnboundedSender<ErrorNotification>,
         event_subscription_service: Arc<Mutex<EventSubscriptionService>>,
         mempool_notification_handler: MempoolNotificationHandler<MempoolNotifier>,
+        metadata_storage: MetadataStorage,
         storage: DbReaderWriter,
         runtime: Option<&Runtime>,
     ) -> (Self, JoinHandle<()>, JoinHandle<()>) {
         // Create a channel to notify the executor when data chunks are ready
-        let max_pending_data_chunks = driver_config.max_pending_data_chunks as usize;
+        let max_pending_data_chunks = get_max_pending_data_chunks(&driver_config);
+        metrics::set_gauge(
+            &metrics::STORAGE_SYNCHRONIZER_GAUGES,
+            metrics::STORAGE_SYNCHRONIZER_MAX_PENDING_DATA,
+            max_pending_data_chunks,
+        );
+        let max_pending_data_chunks = max_pending_data_chunks as usize;
         let (executor_notifier, executor_listener) = mpsc::channel(max_pending_data_chunks);
 
         // Create a channel to notify the committer when executed chunks are ready
@@ -169,10 +218,18 @@ impl<ChunkExecutor: ChunkExecutorTrait + 'static> StorageSynchronizer<ChunkExecu
         // Create a shared pending data chunk counter
         let pending_transaction_chunks = Arc::new(AtomicU64::new(0));
 
+        // Create a shared moving average of the chunk latencies
+        let chunk_latency_average = Arc::new(Mutex::new(ChunkLatencyAverage::default()));
+
+        // Create a shared flag to retire speculatively executed chunks (if a commit fails)
+        let commit_failed = Arc::new(AtomicBool::new(false));
+
         // Spawn the executor that executes/applies storage data chunks
         let runtime = runtime.map(|runtime| runtime.handle().clone());
         let executor_handle = spawn_executor(
             chunk_executor.clone(),
+            commit_failed.clone(),
+            driver_config.enable_chunk_timing_logs,
             error_notification_sender.clone(),
             executor_listener,
             committer_notifier,
@@ -182,7 +239,10 @@ impl<ChunkExecutor: ChunkExecutorTrait + 'static> StorageSynchronizer<ChunkExecu
 
         // Spawn the committer that commits executed (but pending) chunks
         let committer_handle = spawn_committer(
+            bootstrap_progress_notifier,
             chunk_executor.clone(),
+            chunk_latency_average.clone(),
+            commit_failed.clone(),
             committer_listener,
             error_notification_sender.clone(),
             event_subscription_service,
@@ -198,10 +258,13 @@ impl<ChunkExecutor: ChunkExecutorTrait + 'static> StorageSynchronizer<ChunkExecu
 
         let storage_synchronizer = Self {
             chunk_executor,
+            chunk_latency_average,
+            commit_failed,
             commit_notification_sender,
             driver_config,
             error_notification_sender,
             executor_notifier,
+            metadata_storage,
             pending_data_chunks: pending_transaction_chunks,
             runtime,
             state_snapshot_notifier: None,
@@ -225,8 +288,10 @@ impl<ChunkExecutor: ChunkExecutorTrait + 'static> StorageSynchronizer<ChunkExecu
     }
 }
 
-impl<ChunkExecutor: ChunkExecutorTrait + 'static> StorageSynchronizerInterface
-    for StorageSynchronizer<ChunkExecutor>
+impl<
+        ChunkExecutor: ChunkExecutorTrait + 'static,
+        MetadataStorage: MetadataStorageInterface + Clone + Send + Sync + 'static,
+    > StorageSynchronizerInterface for StorageSynchronizer<ChunkExecutor, MetadataStorage>
 {
     fn apply_transaction_outputs(
         &mut self,
@@ -267,7 +332,7 @@ impl<ChunkExecutor: ChunkExecutorTrait + 'static> StorageSynchronizerInterface
         target_output_with_proof: TransactionOutputListWithProof,
     ) -> Result<JoinHandle<()>, Error> {
         // Create a channel to notify the state snapshot receiver when data chunks are ready
-        let max_pending_data_chunks = self.driver_config.max_pending_data_chunks as usize;
+        let max_pending_data_chunks = get_max_pending_data_chunks(&self.driver_config) as usize;
         let (state_snapshot_notifier, state_snapshot_listener) =
             mpsc::channel(max_pending_data_chunks);
 
@@ -278,6 +343,7 @@ impl<ChunkExecutor: ChunkExecutorTrait + 'static> StorageSynchronizerInterface
             self.commit_notification_sender.clone(),
             self.error_notification_sender.clone(),
             self.pending_data_chunks.clone(),
+            self.metadata_storage.clone(),
             self.storage.clone(),
             epoch_change_proofs,
             target_ledger_info,
@@ -293,6 +359,11 @@ impl<ChunkExecutor: ChunkExecutorTrait + 'static> StorageSynchronizerInterface
         load_pending_data_chunks(self.pending_data_chunks.clone()) > 0
     }
 
+    fn pending_data_limit_reached(&self) -> bool {
+        load_pending_data_chunks(self.pending_data_chunks.clone())
+            >= get_max_pending_data_chunks(&self.driver_config)
+    }
+
     fn save_state_values(
         &mut self,
         notification_id: NotificationId,
@@ -321,12 +392,24 @@ impl<ChunkExecutor: ChunkExecutorTrait + 'static> StorageSynchronizerInterface
                 "Failed to reset the chunk executor! Error: {:?}",
                 error
             ))
-        })
+        })?;
+
+        // The ledger state has been reset, so chunks no longer need to be retired
+        self.commit_failed.store(false, Ordering::Relaxed);
+        Ok(())
     }
 
     fn finish_chunk_executor(&self) {
         self.chunk_executor.finish()
     }
+
+    fn get_chunk_latency_average(&self) -> ChunkLatencyAverage {
+        *self.chunk_latency_average.lock()
+    }
+
+    fn get_num_pending_data_chunks(&self) -> u64 {
+        load_pending_data_chunks(self.pending_data_chunks.clone())
+    }
 }
 
 /// A chunk of data to be executed and/or committed to storage (i.e., states,
@@ -349,76 +432,210 @@ enum StorageDataChunk {
     ),
 }
 
-/// Spawns a dedicated executor that executes/applies storage data chunks
+impl StorageDataChunk {
+    /// Returns the notification ID of the data chunk
+    fn notification_id(&self) -> NotificationId {
+        match self {
+            StorageDataChunk::States(notification_id, ..)
+            | StorageDataChunk::Transactions(notification_id, ..)
+            | StorageDataChunk::TransactionOutputs(notification_id, ..) => *notification_id,
+        }
+    }
+}
+
+/// How long each stage of processing a chunk of transactions (or outputs)
+/// took, from verifying it to notifying others of the commit. Only measured
+/// when chunk timing logs are enabled.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct ChunkTimings {
+    #[serde(rename = "verification_usecs", serialize_with = "serialize_usecs")]
+    verification: Duration,
+    #[serde(rename = "execution_usecs", serialize_with = "serialize_usecs")]
+    execution: Duration,
+    #[serde(rename = "state_tree_update_usecs", serialize_with = "serialize_usecs")]
+    state_tree_update: Duration,
+    #[serde(rename = "commit_usecs", serialize_with = "serialize_usecs")]
+    commit: Duration,
+    #[serde(rename = "notification_usecs", serialize_with = "serialize_usecs")]
+    notification: Duration,
+}
+
+impl ChunkTimings {
+    /// Creates the timings of a chunk from how long the chunk executor took.
+    /// If the executor doesn't break its time down by stage, it's all
+    /// counted as execution.
+    fn new(stage_timings: Option<ChunkStageTimings>, execute_duration: Duration) -> Self {
+        let stage_timings = stage_timings.unwrap_or(ChunkStageTimings {
+            execution: execute_duration,
+            ..ChunkStageTimings::default()
+        });
+        Self {
+            verification: stage_timings.verification,
+            execution: stage_timings.execution,
+            state_tree_update: stage_timings.state_tree_update,
+            ..Self::default()
+        }
+    }
+
+    /// Returns each stage's label and duration, in the order they happen
+    pub fn stages(&self) -> [(&'static str, Duration); 5] {
+        [
+            ("verification", self.verification),
+            ("execution", self.execution),
+            ("state_tree_update", self.state_tree_update),
+            ("commit", self.commit),
+            ("notification", self.notification),
+        ]
+    }
+
+    /// Logs the timings as a single structured line, and updates the stage
+    /// latency histograms.
+    fn report(&self, num_transactions: usize) {
+        info!(LogSchema::new(LogEntry::StorageSynchronizer)
+            .chunk_timings(self)
+            .message(&format!(
+                "Committed a chunk of {} transactions",
+                num_transactions
+            )));
+        for (stage, duration) in self.stages().iter() {
+            metrics::observe_duration(
+                &metrics::STORAGE_SYNCHRONIZER_STAGE_LATENCIES,
+                stage,
+                *duration,
+            );
+        }
+    }
+}
+
+/// The weight of the existing average when a new chunk latency is recorded,
+/// i.e., each new chunk contributes 1 / CHUNK_LATENCY_AVERAGE_WEIGHT.
+const CHUNK_LATENCY_AVERAGE_WEIGHT: u32 = 4;
+
+/// An exponential moving average of how long chunks of transactions (or
+/// outputs) take to execute/apply and commit. This is always measured
+/// (regardless of whether chunk timing logs are enabled).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ChunkLatencyAverage {
+    pub average_latency: Duration, // The moving average of the chunk latencies
+    pub num_chunks: u64,           // The total number of chunks recorded
+}
+
+impl ChunkLatencyAverage {
+    /// Updates the moving average with the latency of a newly committed chunk
+    pub fn record_chunk_latency(&mut self, chunk_latency: Duration) {
+        self.average_latency = if self.num_chunks == 0 {
+            chunk_latency
+        } else {
+            (self.average_latency * (CHUNK_LATENCY_AVERAGE_WEIGHT - 1) + chunk_latency)
+                / CHUNK_LATENCY_AVERAGE_WEIGHT
+        };
+        self.num_chunks = self.num_chunks.saturating_add(1);
+    }
+}
+
+/// The window (secs) over which the executor and committer utilizations are measured
+const UTILIZATION_WINDOW_SECS: u64 = 10;
+
+/// Tracks the percentage of time a stage of the storage synchronizer (i.e.,
+/// the executor or the committer) spends busy processing chunks.
+struct StageUtilization {
+    busy_time: Duration,   // The time spent busy in the current window
+    label: &'static str,   // The label of the stage (for the metrics)
+    window_start: Instant, // The start time of the current window
+}
+
+impl StageUtilization {
+    fn new(label: &'static str) -> Self {
+        Self {
+            busy_time: Duration::from_secs(0),
+            label,
+            window_start: Instant::now(),
+        }
+    }
+
+    /// Records the time the stage was busy and updates the utilization
+    /// metrics once the current window has elapsed.
+    fn record_busy_time(&mut self, busy_time: Duration) {
+        self.busy_time += busy_time;
+
+        let window_duration = self.window_start.elapsed();
+        if window_duration >= Duration::from_secs(UTILIZATION_WINDOW_SECS) {
+            let utilization = self.busy_time.as_secs_f64() / window_duration.as_secs_f64();
+            metrics::set_gauge(
+                &metrics::STORAGE_SYNCHRONIZER_UTILIZATION,
+                self.label,
+                (utilization.min(1.0) * 100.0) as u64,
+            );
+            self.busy_time = Duration::from_secs(0);
+            self.window_start = Instant::now();
+        }
+    }
+}
+
+fn serialize_usecs<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_micros() as u64)
+}
+
+/// Spawns a dedicated executor that executes/applies storage data chunks.
+///
+/// Note: chunks are executed speculatively, i.e., the executor starts the next
+/// chunk as soon as the previous chunk has been executed (without waiting for
+/// it to be committed). Commits are still strictly ordered by the committer.
 fn spawn_executor<ChunkExecutor: ChunkExecutorTrait + 'static>(
     chunk_executor: Arc<ChunkExecutor>,
+    commit_failed: Arc<AtomicBool>,
+    enable_chunk_timing_logs: bool,
     error_notification_sender: mpsc::UnboundedSender<ErrorNotification>,
     mut executor_listener: mpsc::Receiver<StorageDataChunk>,
-    mut committer_notifier: mpsc::Sender<NotificationId>,
+    mut committer_notifier: mpsc::Sender<(NotificationId, Duration, Option<ChunkTimings>)>,
     pending_transaction_chunks: Arc<AtomicU64>,
     runtime: Option<Handle>,
 ) -> JoinHandle<()> {
     // Create an executor
     let executor = async move {
+        let mut executor_utilization =
+            StageUtilization::new(metrics::STORAGE_SYNCHRONIZER_EXECUTOR);
         loop {
             ::futures::select! {
                 storage_data_chunk = executor_listener.select_next_some() => {
-                    // Execute/apply the storage data chunk
-                    let (notification_id, result) = match storage_data_chunk {
-                        StorageDataChunk::Transactions(notification_id, transactions_with_proof, target_ledger_info, end_of_epoch_ledger_info) => {
-                            let num_transactions = transactions_with_proof.transactions.len();
-                            let result = chunk_executor
-                               .execute_chunk(
-                                    transactions_with_proof,
-                                    &target_ledger_info,
-                                    end_of_epoch_ledger_info.as_ref(),
-                                );
-                            if result.is_ok() {
-                                metrics::increment_gauge(
-                                    &metrics::STORAGE_SYNCHRONIZER_OPERATIONS,
-                                    metrics::StorageSynchronizerOperations::ExecutedTransactions
-                                        .get_label(),
-                                    num_transactions as u64,
-                                );
-                            }
-                            (notification_id, result)
-                        },
-                        StorageDataChunk::TransactionOutputs(notification_id, outputs_with_proof, target_ledger_info, end_of_epoch_ledger_info) => {
-                            let num_outputs = outputs_with_proof.transactions_and_outputs.len();
-                            let result = chunk_executor
-                                .apply_chunk(
-                                    outputs_with_proof,
-                                    &target_ledger_info,
-                                    end_of_epoch_ledger_info.as_ref(),
-                                );
-                            if result.is_ok() {
-                                metrics::increment_gauge(
-                                    &metrics::STORAGE_SYNCHRONIZER_OPERATIONS,
-                                    metrics::StorageSynchronizerOperations::AppliedTransactionOutputs
-                                        .get_label(),
-                                    num_outputs as u64,
-                                );
-                            }
-                            (notification_id, result)
-                        }
-                        storage_data_chunk => {
-                            panic!("Invalid storage data chunk sent to executor: {:?}", storage_data_chunk);
-                        }
-                    };
+                    // If a preceding commit failed, the ledger state the chunk
+                    // would be executed on is invalid, so the chunk must be retired.
+                    if commit_failed.load(Ordering::Relaxed) {
+                        retire_speculative_chunk(
+                            metrics::STORAGE_SYNCHRONIZER_EXECUTOR,
+                            storage_data_chunk.notification_id(),
+                            pending_transaction_chunks.clone(),
+                        );
+                    } else {
+                        // Execute/apply the storage data chunk (on a blocking thread, so
+                        // that the committer can keep committing chunks in parallel)
+                        let execute_start = Instant::now();
+                        let execute_chunk_executor = chunk_executor.clone();
+                        let (notification_id, result) = tokio::task::spawn_blocking(move || {
+                            execute_storage_data_chunk(execute_chunk_executor, storage_data_chunk)
+                        })
+                        .await
+                        .expect("The chunk execution task should not panic!");
+                        let execute_duration = execute_start.elapsed();
+                        executor_utilization.record_busy_time(execute_duration);
 
-                    // Notify the committer of new executed chunks
-                    match result {
-                        Ok(()) => {
-                            if let Err(error) = committer_notifier.try_send(notification_id) {
-                                let error = format!("Failed to notify the committer! Error: {:?}", error);
+                        // Notify the committer of new executed chunks
+                        match result {
+                            Ok(()) => {
+                                let chunk_timings = enable_chunk_timing_logs.then(|| {
+                                    ChunkTimings::new(chunk_executor.last_chunk_stage_timings(), execute_duration)
+                                });
+                                if let Err(error) = committer_notifier.try_send((notification_id, execute_duration, chunk_timings)) {
+                                    let error = format!("Failed to notify the committer! Error: {:?}", error);
+                                    send_storage_synchronizer_error(error_notification_sender.clone(), notification_id, error).await;
+                                    decrement_pending_data_chunks(pending_transaction_chunks.clone());
+                                }
+                            },
+                            Err(error) => {
+                                let error = format!("Failed to execute/apply the storage data chunk! Error: {:?}", error);
                                 send_storage_synchronizer_error(error_notification_sender.clone(), notification_id, error).await;
                                 decrement_pending_data_chunks(pending_transaction_chunks.clone());
                             }
-                        },
-                        Err(error) => {
-                            let error = format!("Failed to execute/apply the storage data chunk! Error: {:?}", error);
-                            send_storage_synchronizer_error(error_notification_sender.clone(), notification_id, error).await;
-                            decrement_pending_data_chunks(pending_transaction_chunks.clone());
                         }
                     }
                 }
@@ -430,13 +647,73 @@ fn spawn_executor<ChunkExecutor: ChunkExecutorTrait + 'static>(
     spawn(runtime, executor)
 }
 
+/// Executes/applies the given storage data chunk using the chunk executor
+fn execute_storage_data_chunk<ChunkExecutor: ChunkExecutorTrait + 'static>(
+    chunk_executor: Arc<ChunkExecutor>,
+    storage_data_chunk: StorageDataChunk,
+) -> (NotificationId, anyhow::Result<()>) {
+    match storage_data_chunk {
+        StorageDataChunk::Transactions(
+            notification_id,
+            transactions_with_proof,
+            target_ledger_info,
+            end_of_epoch_ledger_info,
+        ) => {
+            let num_transactions = transactions_with_proof.transactions.len();
+            let result = chunk_executor.execute_chunk(
+                transactions_with_proof,
+                &target_ledger_info,
+                end_of_epoch_ledger_info.as_ref(),
+            );
+            if result.is_ok() {
+                metrics::increment_gauge(
+                    &metrics::STORAGE_SYNCHRONIZER_OPERATIONS,
+                    metrics::StorageSynchronizerOperations::ExecutedTransactions.get_label(),
+                    num_transactions as u64,
+                );
+            }
+            (notification_id, result)
+        }
+        StorageDataChunk::TransactionOutputs(
+            notification_id,
+            outputs_with_proof,
+            target_ledger_info,
+            end_of_epoch_ledger_info,
+        ) => {
+            let num_outputs = outputs_with_proof.transactions_and_outputs.len();
+            let result = chunk_executor.apply_chunk(
+                outputs_with_proof,
+                &target_ledger_info,
+                end_of_epoch_ledger_info.as_ref(),
+            );
+            if result.is_ok() {
+                metrics::increment_gauge(
+                    &metrics::STORAGE_SYNCHRONIZER_OPERATIONS,
+                    metrics::StorageSynchronizerOperations::AppliedTransactionOutputs.get_label(),
+                    num_outputs as u64,
+                );
+            }
+            (notification_id, result)
+        }
+        storage_data_chunk => {
+            panic!(
+                "Invalid storage data chunk sent to executor: {:?}",
+                storage_data_chunk
+            );
+        }
+    }
+}
+
 /// Spawns a dedicated committer that commits executed (but pending) chunks
 fn spawn_committer<
     ChunkExecutor: ChunkExecutorTrait + 'static,
     MempoolNotifier: MempoolNotificationSender,
 >(
+    bootstrap_progress_notifier: BootstrapProgressNotifier,
     chunk_executor: Arc<ChunkExecutor>,
-    mut committer_listener: mpsc::Receiver<NotificationId>,
+    chunk_latency_average: Arc<Mutex<ChunkLatencyAverage>>,
+    commit_failed: Arc<AtomicBool>,
+    mut committer_listener: mpsc::Receiver<(NotificationId, Duration, Option<ChunkTimings>)>,
     error_notification_sender: mpsc::UnboundedSender<ErrorNotification>,
     event_subscription_service: Arc<Mutex<EventSubscriptionService>>,
     mempool_notification_handler: MempoolNotificationHandler<MempoolNotifier>,
@@ -446,50 +723,97 @@ fn spawn_committer<
 ) -> JoinHandle<()> {
     // Create a committer
     let committer = async move {
+        let mut committer_utilization =
+            StageUtilization::new(metrics::STORAGE_SYNCHRONIZER_COMMITTER);
         loop {
             ::futures::select! {
-                notification_id = committer_listener.select_next_some() => {
-                    // Commit the executed chunk
-                    match chunk_executor.commit_chunk() {
-                        Ok(notification) => {
-                             // Log the event and update the metrics
-                             debug!(
-                                LogSchema::new(LogEntry::StorageSynchronizer).message(&format!(
-                                    "Committed a new transaction chunk! \
-                                    Transaction total: {:?}, event total: {:?}",
-                                   notification.committed_transactions.len(),
-                                   notification.committed_events.len()
-                                ))
-                            );
-                            metrics::increment_gauge(
-                                &metrics::STORAGE_SYNCHRONIZER_OPERATIONS,
-                                metrics::StorageSynchronizerOperations::Synced
-                                    .get_label(),
-                                notification.committed_transactions.len() as u64,
-                            );
-                            if notification.reconfiguration_occurred {
-                                utils::update_new_epoch_metrics(storage.clone());
-                            }
+                (notification_id, execute_duration, mut chunk_timings) = committer_listener.select_next_some() => {
+                    // If a preceding commit failed, the chunk was speculatively
+                    // executed on an invalid ledger state and must be retired.
+                    if commit_failed.load(Ordering::Relaxed) {
+                        retire_speculative_chunk(
+                            metrics::STORAGE_SYNCHRONIZER_COMMITTER,
+                            notification_id,
+                            pending_transaction_chunks.clone(),
+                        );
+                    } else {
+                        // Commit the executed chunk (on a blocking thread, so that the
+                        // executor can keep executing the next chunks in parallel)
+                        let commit_start = Instant::now();
+                        let commit_chunk_executor = chunk_executor.clone();
+                        let commit_result = tokio::task::spawn_blocking(move || commit_chunk_executor.commit_chunk())
+                            .await
+                            .expect("The chunk commit task should not panic!");
+                        match commit_result {
+                            Ok(notification) => {
+                                // Record how long the chunk took to execute/apply and commit
+                                let commit_duration = commit_start.elapsed();
+                                chunk_latency_average.lock().record_chunk_latency(execute_duration + commit_duration);
+                                if let Some(chunk_timings) = chunk_timings.as_mut() {
+                                    chunk_timings.commit = commit_duration;
+                                }
+                                let notification_start = chunk_timings.map(|_| Instant::now());
+                                let num_transactions = notification.committed_transactions.len();
 
-                            // Handle the committed transaction notification (e.g., notify mempool).
-                            // We do this here due to synchronization issues with mempool and
-                            // storage. See: https://github.com/aptos-labs/aptos-core/issues/553
-                            let committed_transactions = CommittedTransactions {
-                                events: notification.committed_events,
-                                transactions: notification.committed_transactions,
-                            };
-                            utils::handle_committed_transactions(committed_transactions,
-                                storage.clone(),
-                                mempool_notification_handler.clone(),
-                                event_subscription_service.clone(),
-                            ).await;
-                        }
-                        Err(error) => {
-                            let error = format!("Failed to commit executed chunk! Error: {:?}", error);
-                            send_storage_synchronizer_error(error_notification_sender.clone(), notification_id, error).await;
-                        }
-                    };
-                    decrement_pending_data_chunks(pending_transaction_chunks.clone());
+                                 // Log the event and update the metrics
+                                 debug!(
+                                    LogSchema::new(LogEntry::StorageSynchronizer).message(&format!(
+                                        "Committed a new transaction chunk! \
+                                        Transaction total: {:?}, event total: {:?}, \
+                                        kept: {:?}, discarded: {:?}, retried: {:?} \
+                                        (for the new epoch: {:?})",
+                                       notification.committed_transactions.len(),
+                                       notification.committed_events.len(),
+                                       notification.execution_summary.num_kept,
+                                       notification.execution_summary.num_discarded,
+                                       notification.execution_summary.num_retried,
+                                       notification.execution_summary.num_retried_for_new_epoch
+                                    ))
+                                );
+                                metrics::increment_gauge(
+                                    &metrics::STORAGE_SYNCHRONIZER_OPERATIONS,
+                                    metrics::StorageSynchronizerOperations::Synced
+                                        .get_label(),
+                                    notification.committed_transactions.len() as u64,
+                                );
+                                if notification.reconfiguration_occurred {
+                                    utils::update_new_epoch_metrics(storage.clone());
+                                }
+
+                                // Handle the committed transaction notification (e.g., notify mempool).
+                                // We do this here due to synchronization issues with mempool and
+                                // storage. See: https://github.com/aptos-labs/aptos-core/issues/553
+                                let committed_transactions = CommittedTransactions {
+                                    events: notification.committed_events,
+                                    transactions: notification.committed_transactions,
+                                };
+                                utils::handle_committed_transactions(committed_transactions,
+                                    storage.clone(),
+                                    mempool_notification_handler.clone(),
+                                    event_subscription_service.clone(),
+                                ).await;
+
+                                // Update the bootstrapping progress (if we're still bootstrapping)
+                                if let Ok(synced_version) = utils::fetch_latest_synced_version(storage.clone()) {
+                                    bootstrap_progress_notifier.update_synced_version(synced_version);
+                                }
+
+                                // Report the chunk's timings (if enabled)
+                                if let (Some(mut chunk_timings), Some(notification_start)) = (chunk_timings, notification_start) {
+                                    chunk_timings.notification = notification_start.elapsed();
+                                    chunk_timings.report(num_transactions);
+                                }
+                            }
+                            Err(error) => {
+                                // Retire all speculatively executed chunks until the executor is reset
+                                commit_failed.store(true, Ordering::Relaxed);
+                                let error = format!("Failed to commit executed chunk! Error: {:?}", error);
+                                send_storage_synchronizer_error(error_notification_sender.clone(), notification_id, error).await;
+                            }
+                        };
+                        committer_utilization.record_busy_time(commit_start.elapsed());
+                        decrement_pending_data_chunks(pending_transaction_chunks.clone());
+                    }
                 }
             }
         }
@@ -500,12 +824,16 @@ fn spawn_committer<
 }
 
 /// Spawns a dedicated receiver that commits state values from a state snapshot
-fn spawn_state_snapshot_receiver<ChunkExecutor: ChunkExecutorTrait + 'static>(
+fn spawn_state_snapshot_receiver<
+    ChunkExecutor: ChunkExecutorTrait + 'static,
+    MetadataStorage: MetadataStorageInterface + Clone + Send + Sync + 'static,
+>(
     chunk_executor: Arc<ChunkExecutor>,
     mut state_snapshot_listener: mpsc::Receiver<StorageDataChunk>,
     mut commit_notification_sender: mpsc::UnboundedSender<CommitNotification>,
     error_notification_sender: mpsc::UnboundedSender<ErrorNotification>,
     pending_transaction_chunks: Arc<AtomicU64>,
+    metadata_storage: MetadataStorage,
     storage: DbReaderWriter,
     epoch_change_proofs: Vec<LedgerInfoWithSignatures>,
     target_ledger_info: LedgerInfoWithSignatures,
@@ -556,6 +884,12 @@ fn spawn_state_snapshot_receiver<ChunkExecutor: ChunkExecutorTrait + 'static>(
                                     );
 
                                     if !all_states_synced {
+                                        // Update the snapshot sync progress (in case we restart)
+                                        if let Err(error) = metadata_storage.update_last_persisted_state_value_index(&target_ledger_info, last_committed_state_index, false) {
+                                            let error = format!("Failed to update the last persisted state index! Error: {:?}", error);
+                                            send_storage_synchronizer_error(error_notification_sender.clone(), notification_id, error).await;
+                                        }
+
                                         // Send a commit notification to the listener
                                         let commit_notification = CommitNotification::new_committed_states(all_states_synced, last_committed_state_index, None);
                                         if let Err(error) = commit_notification_sender.send(commit_notification).await {
@@ -574,6 +908,17 @@ fn spawn_state_snapshot_receiver<ChunkExecutor: ChunkExecutorTrait + 'static>(
                                     // notification to the listener.
                                     let finalized_result = if let Err(error) = state_snapshot_receiver.finish_box() {
                                         Err(format!("Failed to finish the state value synchronization! Error: {:?}", error))
+                                    } else if let Err(error) = verify_state_snapshot_root_hash(&storage, version, expected_root_hash) {
+                                        // The synced snapshot can't be trusted. Wipe the progress
+                                        // so that we don't resume from it, and notify the driver.
+                                        if let Err(error) = metadata_storage.delete_snapshot_sync_progress() {
+                                            error!(LogSchema::new(LogEntry::StorageSynchronizer).message(&format!(
+                                                "Failed to delete the state snapshot progress! Error: {:?}", error
+                                            )));
+                                        }
+                                        send_storage_synchronizer_error_notification(error_notification_sender.clone(), notification_id, error).await;
+                                        decrement_pending_data_chunks(pending_transaction_chunks.clone());
+                                        return; // The snapshot must be synced again
                                     } else if let Err(error) = storage.writer.finalize_state_snapshot(version, target_output_with_proof) {
                                         Err(format!("Failed to finalize the state snapshot! Error: {:?}", error))
                                     } else if let Err(error) = storage.writer.save_ledger_infos(&epoch_change_proofs) {
@@ -582,6 +927,8 @@ fn spawn_state_snapshot_receiver<ChunkExecutor: ChunkExecutorTrait + 'static>(
                                         Err(format!("Failed to delete the genesis transaction! Error: {:?}", error))
                                     } else if let Err(error) = chunk_executor.reset() {
                                         Err(format!("Failed to reset the chunk executor after states synchronization! Error: {:?}", error))
+                                    } else if let Err(error) = metadata_storage.update_last_persisted_state_value_index(&target_ledger_info, last_committed_state_index, true) {
+                                        Err(format!("Failed to mark the state snapshot sync as complete! Error: {:?}", error))
                                     } else if let Err(error) = commit_notification_sender.send(commit_notification).await {
                                        Err(format!("Failed to send the final state commit notification! Error: {:?}", error))
                                     } else if let Err(error) = utils::initialize_sync_gauges(storage.reader) {
@@ -617,6 +964,35 @@ fn spawn_state_snapshot_receiver<ChunkExecutor: ChunkExecutorTrait + 'static>(
     spawn(runtime, receiver)
 }
 
+/// Verifies that the root hash of the committed state snapshot (at the
+/// specified version) matches the expected root hash of the target.
+fn verify_state_snapshot_root_hash(
+    storage: &DbReaderWriter,
+    version: Version,
+    expected_root_hash: HashValue,
+) -> Result<(), Error> {
+    let committed_snapshot = storage
+        .reader
+        .get_state_snapshot_before(version + 1)
+        .map_err(|error| {
+            Error::StorageError(format!(
+                "Failed to read the committed state snapshot! Error: {:?}",
+                error
+            ))
+        })?;
+    match committed_snapshot {
+        Some((committed_version, committed_root_hash))
+            if committed_version == version && committed_root_hash == expected_root_hash =>
+        {
+            Ok(())
+        }
+        committed_snapshot => Err(Error::SnapshotRootHashMismatch(format!(
+            "Expected root hash: {:?} at version: {:?}, but found committed snapshot: {:?}",
+            expected_root_hash, version, committed_snapshot
+        ))),
+    }
+}
+
 /// Creates a final commit notification for the last states chunk
 fn create_final_commit_notification(
     target_output_with_proof: &TransactionOutputListWithProof,
@@ -656,6 +1032,12 @@ fn spawn(
     }
 }
 
+/// Returns the max number of data chunks that can be pending execution/apply
+/// or commit. This is at least 1 (otherwise no data could be synced).
+fn get_max_pending_data_chunks(driver_config: &StateSyncDriverConfig) -> u64 {
+    cmp::max(driver_config.max_pending_data_chunks, 1)
+}
+
 /// Returns the value currently held by the pending chunk counter
 fn load_pending_data_chunks(pending_data_chunks: Arc<AtomicU64>) -> u64 {
     pending_data_chunks.load(Ordering::Relaxed)
@@ -683,6 +1065,24 @@ fn decrement_pending_data_chunks(atomic_u64: Arc<AtomicU64>) {
     );
 }
 
+/// Retires a chunk that was speculatively sent to the given stage of the
+/// storage synchronizer after a preceding commit failed.
+fn retire_speculative_chunk(
+    stage: &str,
+    notification_id: NotificationId,
+    pending_data_chunks: Arc<AtomicU64>,
+) {
+    debug!(
+        LogSchema::new(LogEntry::StorageSynchronizer).message(&format!(
+            "Retiring a speculative data chunk (a preceding commit failed)! \
+            Stage: {}, notification ID: {:?}",
+            stage, notification_id
+        ))
+    );
+    metrics::increment_counter(&metrics::STORAGE_SYNCHRONIZER_RETIRED_CHUNKS, stage);
+    decrement_pending_data_chunks(pending_data_chunks);
+}
+
 /// Sends an error notification to the notification listener
 async fn send_storage_synchronizer_error(
     mut error_notification_sender: mpsc::UnboundedSender<ErrorNotification>,
@@ -690,10 +1090,22 @@ async fn send_storage_synchronizer_error(
     error_message: String,
 ) {
     let error_message = format!("Storage synchronizer error: {:?}", error_message);
-    error!(LogSchema::new(LogEntry::StorageSynchronizer).message(&error_message));
+    let error = Error::UnexpectedError(error_message);
+    send_storage_synchronizer_error_notification(error_notification_sender, notification_id, error)
+        .await;
+}
+
+/// Sends the given error to the state sync driver and updates the metrics
+async fn send_storage_synchronizer_error_notification(
+    mut error_notification_sender: mpsc::UnboundedSender<ErrorNotification>,
+    notification_id: NotificationId,
+    error: Error,
+) {
+    error!(LogSchema::new(LogEntry::StorageSynchronizer)
+        .error(&error)
+        .message("The storage synchronizer encountered an error!"));
 
     // Send an error notification
-    let error = Error::UnexpectedError(error_message);
     let error_notification = ErrorNotification {
         error: error.clone(),
         notification_id,
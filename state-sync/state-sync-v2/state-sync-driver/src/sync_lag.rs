@@ -0,0 +1,174 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    logging::{LogEntry, LogSchema},
+    metrics,
+};
+use aptos_config::config::StateSyncDriverConfig;
+use aptos_data_client::GlobalDataSummary;
+use aptos_logger::{prelude::*, sample, sample::SampleRate};
+use aptos_types::{ledger_info::LedgerInfo, transaction::Version};
+use futures::{channel::mpsc, stream::FusedStream, Stream};
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+// The frequency (secs) at which to log sync lag warnings
+const SYNC_LAG_LOG_FREQ_SECS: u64 = 10;
+
+/// The lag between the locally synced ledger info and the highest
+/// ledger info advertised by our peers.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SyncLag {
+    // The latest version synced to storage
+    pub synced_version: Version,
+
+    // The highest version advertised by our peers
+    pub highest_advertised_version: Version,
+
+    // The number of versions the node is behind its peers
+    pub versions_behind: u64,
+
+    // The number of seconds (by ledger info timestamps) the node is behind its peers
+    pub seconds_behind: u64,
+}
+
+impl SyncLag {
+    pub fn new(
+        synced_ledger_info: &LedgerInfo,
+        highest_advertised_ledger_info: &LedgerInfo,
+    ) -> Self {
+        let synced_version = synced_ledger_info.version();
+        let highest_advertised_version = highest_advertised_ledger_info.version();
+        let usecs_behind = highest_advertised_ledger_info
+            .timestamp_usecs()
+            .saturating_sub(synced_ledger_info.timestamp_usecs());
+
+        Self {
+            synced_version,
+            highest_advertised_version,
+            versions_behind: highest_advertised_version.saturating_sub(synced_version),
+            seconds_behind: Duration::from_micros(usecs_behind).as_secs(),
+        }
+    }
+}
+
+/// Monitors how far the node lags behind its peers (once bootstrapped). If the
+/// lag exceeds the configured thresholds, a sync lag notification is sent to
+/// the listener. Only a single notification is sent each time the thresholds
+/// are crossed (i.e., the node must first fall back under the thresholds).
+pub struct SyncLagMonitor {
+    // The max number of seconds the node can lag behind before raising an alarm
+    max_sync_lag_secs: u64,
+
+    // The max number of versions the node can lag behind before raising an alarm
+    max_sync_lag_versions: u64,
+
+    // True iff the sync lag currently exceeds the thresholds
+    sync_lag_alarm_raised: bool,
+
+    // The channel through which to notify listeners of the sync lag alarm
+    sync_lag_sender: mpsc::UnboundedSender<SyncLag>,
+}
+
+impl SyncLagMonitor {
+    pub fn new(driver_config: &StateSyncDriverConfig) -> (Self, SyncLagListener) {
+        let (sync_lag_sender, sync_lag_listener) = SyncLagListener::new();
+        let sync_lag_monitor = Self {
+            max_sync_lag_secs: driver_config.max_sync_lag_secs,
+            max_sync_lag_versions: driver_config.max_sync_lag_versions,
+            sync_lag_alarm_raised: false,
+            sync_lag_sender,
+        };
+        (sync_lag_monitor, sync_lag_listener)
+    }
+
+    /// Calculates the sync lag of the given synced ledger info (compared to the
+    /// highest ledger info advertised in the global data summary), updates the
+    /// sync lag metrics and raises an alarm if the lag exceeds the thresholds.
+    /// If no ledger infos are advertised, None is returned.
+    pub fn check_sync_lag(
+        &mut self,
+        synced_ledger_info: &LedgerInfo,
+        global_data_summary: &GlobalDataSummary,
+    ) -> Option<SyncLag> {
+        // Calculate the sync lag and update the metrics
+        let highest_advertised_ledger_info = global_data_summary
+            .advertised_data
+            .highest_synced_ledger_info()?;
+        let sync_lag = SyncLag::new(
+            synced_ledger_info,
+            highest_advertised_ledger_info.ledger_info(),
+        );
+        metrics::set_gauge(
+            &metrics::SYNC_LAG,
+            metrics::SYNC_LAG_VERSIONS_BEHIND,
+            sync_lag.versions_behind,
+        );
+        metrics::set_gauge(
+            &metrics::SYNC_LAG,
+            metrics::SYNC_LAG_SECONDS_BEHIND,
+            sync_lag.seconds_behind,
+        );
+
+        // Raise (or clear) the sync lag alarm
+        if sync_lag.versions_behind > self.max_sync_lag_versions
+            || sync_lag.seconds_behind > self.max_sync_lag_secs
+        {
+            sample!(
+                SampleRate::Duration(Duration::from_secs(SYNC_LAG_LOG_FREQ_SECS)),
+                warn!(LogSchema::new(LogEntry::Driver).message(&format!(
+                    "The node is falling too far behind its peers! Sync lag: {:?}",
+                    sync_lag
+                )))
+            );
+            if !self.sync_lag_alarm_raised {
+                self.sync_lag_alarm_raised = true;
+                // Notify any listeners (ignoring errors if there are none)
+                let _ = self.sync_lag_sender.unbounded_send(sync_lag);
+            }
+        } else if self.sync_lag_alarm_raised {
+            info!(LogSchema::new(LogEntry::Driver).message(&format!(
+                "The node is no longer too far behind its peers. Sync lag: {:?}",
+                sync_lag
+            )));
+            self.sync_lag_alarm_raised = false;
+        }
+
+        Some(sync_lag)
+    }
+}
+
+/// A simple wrapper for a sync lag notification listener
+pub struct SyncLagListener {
+    // The listener for sync lag notifications
+    sync_lag_listener: mpsc::UnboundedReceiver<SyncLag>,
+}
+
+impl SyncLagListener {
+    fn new() -> (mpsc::UnboundedSender<SyncLag>, Self) {
+        // Create a channel to send and receive sync lag notifications
+        let (sync_lag_sender, sync_lag_listener) = mpsc::unbounded();
+
+        // Create and return the sender and listener
+        let sync_lag_listener = Self { sync_lag_listener };
+        (sync_lag_sender, sync_lag_listener)
+    }
+}
+
+impl Stream for SyncLagListener {
+    type Item = SyncLag;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.get_mut().sync_lag_listener).poll_next(cx)
+    }
+}
+
+impl FusedStream for SyncLagListener {
+    fn is_terminated(&self) -> bool {
+        self.sync_lag_listener.is_terminated()
+    }
+}
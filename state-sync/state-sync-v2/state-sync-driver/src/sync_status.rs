@@ -0,0 +1,59 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use aptos_infallible::RwLock;
+use aptos_types::transaction::Version;
+use std::sync::Arc;
+
+/// The high level state of the state sync driver
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DriverState {
+    Bootstrapping, // The node is still bootstrapping
+    CatchingUp,    // The node has bootstrapped but peers advertise newer data
+    CaughtUp,      // The node is synced to the highest advertised version
+}
+
+/// A snapshot of the state sync driver's progress, as last published by the
+/// driver.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyncStatus {
+    // The high level state of the driver
+    pub driver_state: DriverState,
+
+    // The latest version and epoch synced to storage
+    pub synced_version: Version,
+    pub synced_epoch: u64,
+
+    // The timestamp (in microseconds) of the latest synced ledger info
+    pub synced_timestamp_usecs: u64,
+
+    // The highest version advertised by our peers (if we know of any peers)
+    pub highest_advertised_version: Option<Version>,
+
+    // The progress (0 to 100) of the state snapshot sync (only set while
+    // downloading a state snapshot).
+    pub snapshot_sync_progress_percent: Option<u8>,
+}
+
+/// A cheaply cloneable handle through which the driver publishes its status
+/// and other components (e.g., the API) read it.
+#[derive(Clone, Debug, Default)]
+pub struct SyncStatusHandle {
+    status: Arc<RwLock<Option<SyncStatus>>>,
+}
+
+impl SyncStatusHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the latest published status (if the driver has published one)
+    pub fn get_status(&self) -> Option<SyncStatus> {
+        self.status.read().clone()
+    }
+
+    /// Publishes a new status, replacing the previous one
+    pub fn update_status(&self, status: SyncStatus) {
+        *self.status.write() = Some(status);
+    }
+}
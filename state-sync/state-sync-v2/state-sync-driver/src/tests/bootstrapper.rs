@@ -2,25 +2,31 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    bootstrapper::Bootstrapper,
+    bootstrap_progress::{BootstrapProgress, BootstrapProgressNotifier, BootstrapStage},
+    bootstrapper::{select_bootstrapping_mode, Bootstrapper, BootstrappingStage},
     driver::DriverConfiguration,
     error::Error,
+    metrics,
     tests::{
         mocks::{
-            create_mock_db_reader, create_mock_streaming_client, create_ready_storage_synchronizer,
+            create_empty_metadata_storage, create_mock_db_reader, create_mock_metadata_storage,
+            create_mock_streaming_client, create_ready_storage_synchronizer, MockMetadataStorage,
             MockStorageSynchronizer, MockStreamingClient,
         },
         utils::{
             create_data_stream_listener, create_empty_epoch_state, create_epoch_ending_ledger_info,
             create_full_node_driver_configuration, create_global_summary,
-            create_output_list_with_proof, create_random_epoch_ending_ledger_info,
-            create_transaction_info, create_transaction_list_with_proof,
+            create_ledger_info_at_version, create_output_list_with_proof,
+            create_random_epoch_ending_ledger_info, create_transaction_info,
+            create_transaction_list_with_proof,
         },
     },
 };
 use aptos_config::config::BootstrappingMode;
 use aptos_data_client::GlobalDataSummary;
+use aptos_time_service::TimeService;
 use aptos_types::{
+    ledger_info::LedgerInfoWithSignatures,
     transaction::{TransactionOutputListWithProof, Version},
     waypoint::Waypoint,
 };
@@ -33,6 +39,219 @@ use futures::{channel::oneshot, FutureExt};
 use mockall::{predicate::eq, Sequence};
 use std::sync::Arc;
 
+#[test]
+fn test_auto_select_bootstrapping_mode_thresholds() {
+    // Create a driver configuration with auto selection thresholds
+    let mut driver_configuration = create_full_node_driver_configuration();
+    let output_threshold = 1000;
+    let snapshot_threshold = 50_000;
+    driver_configuration.config.auto_select_output_threshold = output_threshold;
+    driver_configuration.config.auto_select_snapshot_threshold = snapshot_threshold;
+    let config = &driver_configuration.config;
+
+    // Verify transactions are executed up to (and including) the output threshold
+    for num_versions_behind in [0, 1, output_threshold - 1, output_threshold] {
+        assert_eq!(
+            select_bootstrapping_mode(config, num_versions_behind),
+            BootstrappingMode::ExecuteTransactionsFromGenesis
+        );
+    }
+
+    // Verify outputs are applied up to (and including) the snapshot threshold
+    for num_versions_behind in [output_threshold + 1, snapshot_threshold] {
+        assert_eq!(
+            select_bootstrapping_mode(config, num_versions_behind),
+            BootstrappingMode::ApplyTransactionOutputsFromGenesis
+        );
+    }
+
+    // Verify states are downloaded beyond the snapshot threshold
+    for num_versions_behind in [snapshot_threshold + 1, u64::MAX] {
+        assert_eq!(
+            select_bootstrapping_mode(config, num_versions_behind),
+            BootstrappingMode::DownloadLatestStates
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_auto_select_bootstrapping_mode() {
+    // Create test data
+    let highest_version = 45;
+    let highest_ledger_info = create_random_epoch_ending_ledger_info(highest_version, 1);
+
+    // Create a driver configuration that auto selects the bootstrapping mode
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.bootstrapping_mode = BootstrappingMode::AutoSelect;
+    driver_configuration.config.auto_select_output_threshold = highest_version - 1;
+    driver_configuration.config.auto_select_snapshot_threshold = highest_version;
+
+    // Create the mock streaming client and expect an output stream
+    let mut mock_streaming_client = create_mock_streaming_client();
+    let (_notification_sender, data_stream_listener) = create_data_stream_listener();
+    mock_streaming_client
+        .expect_get_all_transaction_outputs()
+        .times(1)
+        .with(eq(1), eq(highest_version), eq(highest_version))
+        .return_once(move |_, _, _| Ok(data_stream_listener));
+
+    // Create the mock metadata storage and expect the selected mode to be persisted
+    let mut mock_metadata_storage = create_empty_metadata_storage();
+    mock_metadata_storage
+        .expect_get_selected_bootstrapping_mode()
+        .returning(|| Ok(None));
+    mock_metadata_storage
+        .expect_update_selected_bootstrapping_mode()
+        .times(1)
+        .with(eq(BootstrappingMode::ApplyTransactionOutputsFromGenesis))
+        .return_const(Ok(()));
+
+    // Create the bootstrapper
+    let mut bootstrapper = create_bootstrapper_with_storage(
+        driver_configuration,
+        mock_streaming_client,
+        mock_metadata_storage,
+        true,
+    );
+
+    // Insert an epoch ending ledger info into the verified states of the bootstrapper
+    manipulate_verified_epoch_states(&mut bootstrapper, true, true, Some(highest_version));
+
+    // Create a global data summary
+    let mut global_data_summary = create_global_summary(1);
+    global_data_summary.advertised_data.synced_ledger_infos = vec![highest_ledger_info];
+
+    // Drive progress to select the mode and initialize the transaction output stream
+    drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap();
+
+    // Verify the selected mode is exposed in the metrics
+    let mode_label = BootstrappingMode::ApplyTransactionOutputsFromGenesis.to_label();
+    assert_eq!(
+        metrics::read_gauge(&metrics::BOOTSTRAPPING_MODE, mode_label),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_auto_select_bootstrapping_mode_persisted() {
+    // Create test data
+    let highest_version = 45;
+    let highest_ledger_info = create_random_epoch_ending_ledger_info(highest_version, 1);
+
+    // Create a driver configuration that would auto select output syncing
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.bootstrapping_mode = BootstrappingMode::AutoSelect;
+    driver_configuration.config.auto_select_output_threshold = 0;
+
+    // Create the mock streaming client and expect a transaction stream
+    let mut mock_streaming_client = create_mock_streaming_client();
+    let (_notification_sender, data_stream_listener) = create_data_stream_listener();
+    mock_streaming_client
+        .expect_get_all_transactions()
+        .times(1)
+        .with(eq(1), eq(highest_version), eq(highest_version), eq(false))
+        .return_once(move |_, _, _, _| Ok(data_stream_listener));
+
+    // Create the mock metadata storage with a previously selected mode
+    let mut mock_metadata_storage = create_empty_metadata_storage();
+    mock_metadata_storage
+        .expect_get_selected_bootstrapping_mode()
+        .returning(|| Ok(Some(BootstrappingMode::ExecuteTransactionsFromGenesis)));
+
+    // Create the bootstrapper
+    let mut bootstrapper = create_bootstrapper_with_storage(
+        driver_configuration,
+        mock_streaming_client,
+        mock_metadata_storage,
+        true,
+    );
+
+    // Insert an epoch ending ledger info into the verified states of the bootstrapper
+    manipulate_verified_epoch_states(&mut bootstrapper, true, true, Some(highest_version));
+
+    // Create a global data summary
+    let mut global_data_summary = create_global_summary(1);
+    global_data_summary.advertised_data.synced_ledger_infos = vec![highest_ledger_info];
+
+    // Drive progress and verify the previously selected mode is used (not outputs)
+    drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_bootstrap_progress_notifications() {
+    // Create test data
+    let highest_version = 45;
+    let highest_ledger_info = create_random_epoch_ending_ledger_info(highest_version, 1);
+
+    // Create a driver configuration with a genesis waypoint and output syncing
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.bootstrapping_mode =
+        BootstrappingMode::ApplyTransactionOutputsFromGenesis;
+
+    // Create the mock streaming client
+    let mut mock_streaming_client = create_mock_streaming_client();
+    let (_notification_sender, data_stream_listener) = create_data_stream_listener();
+    mock_streaming_client
+        .expect_get_all_transaction_outputs()
+        .times(1)
+        .with(eq(1), eq(highest_version), eq(highest_version))
+        .return_once(move |_, _, _| Ok(data_stream_listener));
+
+    // Create the bootstrapper and subscribe to progress notifications
+    let (bootstrap_progress_notifier, mut bootstrap_progress_receiver) =
+        BootstrapProgressNotifier::new();
+    let mut bootstrapper = create_bootstrapper_with_components(
+        driver_configuration,
+        mock_streaming_client,
+        create_empty_metadata_storage(),
+        create_ready_storage_synchronizer(true),
+        0,
+        create_epoch_ending_ledger_info(),
+        TimeService::mock(),
+        bootstrap_progress_notifier.clone(),
+    );
+    assert_eq!(
+        bootstrap_progress_receiver.borrow().stage,
+        BootstrapStage::Initializing
+    );
+
+    // Insert an epoch ending ledger info into the verified states of the bootstrapper
+    manipulate_verified_epoch_states(&mut bootstrapper, true, true, Some(highest_version));
+
+    // Create a global data summary
+    let mut global_data_summary = create_global_summary(1);
+    global_data_summary.advertised_data.synced_ledger_infos = vec![highest_ledger_info];
+
+    // Drive progress to initialize the transaction output stream
+    drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap();
+
+    // Verify the progress notification for the transaction outputs
+    let expected_progress =
+        BootstrapProgress::new(BootstrapStage::TransactionOutputs, 0, Some(highest_version));
+    assert_eq!(*bootstrap_progress_receiver.borrow(), expected_progress);
+
+    // Simulate the storage synchronizer committing a chunk and verify the progress
+    bootstrap_progress_notifier.update_synced_version(9);
+    let progress = *bootstrap_progress_receiver.borrow();
+    assert_eq!(progress.num_completed, 9);
+    assert_eq!(progress.completion_percent, 20);
+
+    // Complete bootstrapping and verify the final notification closes the channel
+    bootstrapper.bootstrapping_complete().unwrap();
+    bootstrap_progress_receiver.changed().await.unwrap();
+    assert_eq!(
+        *bootstrap_progress_receiver.borrow(),
+        BootstrapProgress::complete()
+    );
+    assert!(bootstrap_progress_receiver.changed().await.is_err());
+}
+
 #[tokio::test]
 async fn test_bootstrap_genesis_waypoint() {
     // Create a driver configuration with a genesis waypoint
@@ -130,6 +349,269 @@ async fn test_bootstrap_no_notification() {
     assert_none!(bootstrap_notification_receiver.now_or_never());
 }
 
+#[tokio::test]
+async fn test_bootstrap_storage_ahead_of_network() {
+    // Create a driver configuration with a genesis waypoint
+    let driver_configuration = create_full_node_driver_configuration();
+
+    // Create the bootstrapper (storage has synced to version 1000)
+    let mock_streaming_client = create_mock_streaming_client();
+    let latest_ledger_info = create_ledger_info_at_version(1000);
+    let mut bootstrapper = create_bootstrapper_with_ledger_info(
+        driver_configuration,
+        mock_streaming_client,
+        latest_ledger_info,
+    );
+
+    // Create a global data summary where the highest synced version is 500
+    let mut global_data_summary = create_global_summary(1);
+    global_data_summary.advertised_data.synced_ledger_infos =
+        vec![create_ledger_info_at_version(500)];
+
+    // Subscribe to a bootstrapped notification
+    let (bootstrap_notification_sender, bootstrap_notification_receiver) = oneshot::channel();
+    bootstrapper
+        .subscribe_to_bootstrap_notifications(bootstrap_notification_sender)
+        .unwrap();
+
+    // Drive progress once and verify we bootstrap immediately (without any streams)
+    drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap();
+    assert!(bootstrapper.is_bootstrapped());
+    verify_bootstrap_notification(bootstrap_notification_receiver);
+}
+
+#[tokio::test]
+async fn test_bootstrap_storage_at_epoch_boundary() {
+    // Create a driver configuration with a genesis waypoint
+    let driver_configuration = create_full_node_driver_configuration();
+
+    // Create the mock streaming client (we expect to fetch the next epoch end)
+    let mut mock_streaming_client = create_mock_streaming_client();
+    let (_notification_sender, data_stream_listener) = create_data_stream_listener();
+    mock_streaming_client
+        .expect_get_all_epoch_ending_ledger_infos()
+        .times(1)
+        .with(eq(2))
+        .return_once(move |_| Ok(data_stream_listener));
+
+    // Create the bootstrapper (storage has synced to the end of epoch 1)
+    let latest_ledger_info = create_random_epoch_ending_ledger_info(1000, 1);
+    let mut bootstrapper = create_bootstrapper_with_ledger_info(
+        driver_configuration,
+        mock_streaming_client,
+        latest_ledger_info.clone(),
+    );
+
+    // Create a global data summary where the highest synced ledger info is
+    // the same as ours, but epoch 2 has also ended.
+    let mut global_data_summary = create_global_summary(2);
+    global_data_summary.advertised_data.synced_ledger_infos = vec![latest_ledger_info];
+
+    // Subscribe to a bootstrapped notification
+    let (bootstrap_notification_sender, bootstrap_notification_receiver) = oneshot::channel();
+    bootstrapper
+        .subscribe_to_bootstrap_notifications(bootstrap_notification_sender)
+        .unwrap();
+
+    // Drive progress and verify we fetch the next epoch ending ledger info
+    // (instead of declaring bootstrapping complete).
+    drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap();
+    assert!(!bootstrapper.is_bootstrapped());
+    assert_none!(bootstrap_notification_receiver.now_or_never());
+}
+
+#[tokio::test]
+async fn test_bootstrap_from_local_genesis() {
+    // Create a driver configuration with a genesis waypoint
+    let driver_configuration = create_full_node_driver_configuration();
+
+    // Create the bootstrapper (no data streams should be created)
+    let mock_streaming_client = create_mock_streaming_client();
+    let mut bootstrapper = create_bootstrapper(driver_configuration, mock_streaming_client, true);
+
+    // Subscribe to a bootstrapped notification
+    let (bootstrap_notification_sender, bootstrap_notification_receiver) = oneshot::channel();
+    bootstrapper
+        .subscribe_to_bootstrap_notifications(bootstrap_notification_sender)
+        .unwrap();
+
+    // Bootstrap from genesis and verify the notification is sent
+    bootstrapper.bootstrap_from_local_genesis().unwrap();
+    assert!(bootstrapper.is_bootstrapped());
+    verify_bootstrap_notification(bootstrap_notification_receiver);
+
+    // Verify we can't bootstrap again
+    let error = bootstrapper.bootstrap_from_local_genesis().unwrap_err();
+    assert_matches!(error, Error::AlreadyBootstrapped(_));
+}
+
+#[tokio::test]
+async fn test_bootstrap_from_local_genesis_unsatisfied_waypoint() {
+    // Create a driver configuration with a non-genesis waypoint
+    let mut driver_configuration = create_full_node_driver_configuration();
+    let waypoint = create_random_epoch_ending_ledger_info(10, 1);
+    driver_configuration.waypoint = Waypoint::new_any(waypoint.ledger_info());
+
+    // Create the bootstrapper
+    let mock_streaming_client = create_mock_streaming_client();
+    let mut bootstrapper = create_bootstrapper(driver_configuration, mock_streaming_client, true);
+
+    // Verify we can't bootstrap from genesis (storage is behind the waypoint)
+    let error = bootstrapper.bootstrap_from_local_genesis().unwrap_err();
+    assert_matches!(error, Error::VerificationError(_));
+    assert!(!bootstrapper.is_bootstrapped());
+}
+
+#[tokio::test]
+async fn test_bootstrap_to_target_mid_epoch() {
+    // Create test data
+    let epoch_ending_version = 1000;
+    let target_version = 500;
+    let epoch_ending_ledger_info = create_random_epoch_ending_ledger_info(epoch_ending_version, 1);
+
+    // Create a driver configuration with output syncing and a mid-epoch target
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.bootstrapping_mode =
+        BootstrappingMode::ApplyTransactionOutputsFromGenesis;
+    driver_configuration.config.bootstrapping_target_version = Some(target_version);
+
+    // Create the mock streaming client (the outputs are proven by the epoch ending ledger info)
+    let mut mock_streaming_client = create_mock_streaming_client();
+    let (_notification_sender, data_stream_listener) = create_data_stream_listener();
+    mock_streaming_client
+        .expect_get_all_transaction_outputs()
+        .times(1)
+        .with(eq(1), eq(target_version), eq(epoch_ending_version))
+        .return_once(move |_, _, _| Ok(data_stream_listener));
+
+    // Create the bootstrapper
+    let mut bootstrapper = create_bootstrapper(driver_configuration, mock_streaming_client, true);
+    manipulate_verified_epoch_states(&mut bootstrapper, true, true, Some(epoch_ending_version));
+
+    // Subscribe to the target notification
+    let (target_notifier, mut target_listener) = oneshot::channel();
+    bootstrapper
+        .subscribe_to_target_notifications(target_notifier)
+        .unwrap();
+
+    // Create a global data summary
+    let mut global_data_summary = create_global_summary(1);
+    global_data_summary.advertised_data.synced_ledger_infos = vec![epoch_ending_ledger_info];
+
+    // Drive progress to initialize the transaction output stream (up to the target)
+    drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap();
+    assert!(!bootstrapper.is_bootstrapped());
+    assert_none!(target_listener.try_recv().unwrap());
+}
+
+#[tokio::test]
+async fn test_bootstrap_to_target_at_epoch_end() {
+    // Create test data
+    let target_version = 1000;
+    let epoch_ending_ledger_info = create_random_epoch_ending_ledger_info(target_version, 1);
+
+    // Create a driver configuration with output syncing and an epoch ending target
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.bootstrapping_mode =
+        BootstrappingMode::ApplyTransactionOutputsFromGenesis;
+    driver_configuration.config.bootstrapping_target_version = Some(target_version);
+
+    // Create the mock streaming client
+    let mut mock_streaming_client = create_mock_streaming_client();
+    let (_notification_sender, data_stream_listener) = create_data_stream_listener();
+    mock_streaming_client
+        .expect_get_all_transaction_outputs()
+        .times(1)
+        .with(eq(1), eq(target_version), eq(target_version))
+        .return_once(move |_, _, _| Ok(data_stream_listener));
+
+    // Create the bootstrapper
+    let mut bootstrapper = create_bootstrapper(driver_configuration, mock_streaming_client, true);
+    manipulate_verified_epoch_states(&mut bootstrapper, true, true, Some(target_version));
+
+    // Create a global data summary
+    let mut global_data_summary = create_global_summary(1);
+    global_data_summary.advertised_data.synced_ledger_infos = vec![epoch_ending_ledger_info];
+
+    // Drive progress to initialize the transaction output stream (up to the target)
+    drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap();
+    assert!(!bootstrapper.is_bootstrapped());
+}
+
+#[tokio::test]
+async fn test_bootstrap_target_reached() {
+    // Create a driver configuration with a target equal to the synced version
+    let synced_version = 100;
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.bootstrapping_target_version = Some(synced_version);
+
+    // Create the bootstrapper
+    let mut bootstrapper = create_bootstrapper_with_synced_version(
+        driver_configuration,
+        create_mock_streaming_client(),
+        create_empty_metadata_storage(),
+        synced_version,
+    );
+    manipulate_verified_epoch_states(&mut bootstrapper, true, true, Some(1000));
+
+    // Subscribe to the target notification
+    let (target_notifier, target_listener) = oneshot::channel();
+    bootstrapper
+        .subscribe_to_target_notifications(target_notifier)
+        .unwrap();
+
+    // Drive progress and verify we're bootstrapped at the target
+    let global_data_summary = create_global_summary(1);
+    drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap();
+    assert!(bootstrapper.is_bootstrapped());
+    assert_eq!(target_listener.await.unwrap(), Ok(synced_version));
+}
+
+#[tokio::test]
+async fn test_bootstrap_target_below_synced_version() {
+    // Create a driver configuration with a target below the synced version
+    let synced_version = 100;
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.bootstrapping_target_version = Some(synced_version - 1);
+
+    // Create the bootstrapper
+    let mut bootstrapper = create_bootstrapper_with_synced_version(
+        driver_configuration,
+        create_mock_streaming_client(),
+        create_empty_metadata_storage(),
+        synced_version,
+    );
+    manipulate_verified_epoch_states(&mut bootstrapper, true, true, Some(1000));
+
+    // Subscribe to the target notification
+    let (target_notifier, target_listener) = oneshot::channel();
+    bootstrapper
+        .subscribe_to_target_notifications(target_notifier)
+        .unwrap();
+
+    // Drive progress and verify the target is rejected
+    let global_data_summary = create_global_summary(1);
+    let error = drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::InvalidBootstrappingTarget(_));
+    assert!(!bootstrapper.is_bootstrapped());
+    assert_matches!(
+        target_listener.await.unwrap(),
+        Err(Error::InvalidBootstrappingTarget(_))
+    );
+}
+
 #[tokio::test]
 async fn test_critical_timeout() {
     // Create a driver configuration with a genesis waypoint and a stream timeout of 1 second
@@ -187,6 +669,73 @@ async fn test_critical_timeout() {
     assert_matches!(error, Error::DataStreamNotificationTimeout(_));
 }
 
+#[tokio::test]
+async fn test_stalled_stream_reset() {
+    // Create a driver configuration with a short stream wait time and a
+    // timeout of 10 seconds for the epoch ending stream.
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.max_stream_wait_time_ms = 100;
+    driver_configuration.config.epoch_ending_stream_timeout_ms = 10_000;
+
+    // Create the mock streaming client (the first stream never responds)
+    let mut mock_streaming_client = create_mock_streaming_client();
+    let mut expectation_sequence = Sequence::new();
+    let (_notification_sender_1, data_stream_listener_1) = create_data_stream_listener();
+    let (_notification_sender_2, data_stream_listener_2) = create_data_stream_listener();
+    for data_stream_listener in [data_stream_listener_1, data_stream_listener_2] {
+        mock_streaming_client
+            .expect_get_all_epoch_ending_ledger_infos()
+            .times(1)
+            .with(eq(1))
+            .return_once(move |_| Ok(data_stream_listener))
+            .in_sequence(&mut expectation_sequence);
+    }
+
+    // Create the bootstrapper with a mock time service
+    let time_service = TimeService::mock();
+    let mut bootstrapper = create_bootstrapper_with_components(
+        driver_configuration,
+        mock_streaming_client,
+        create_empty_metadata_storage(),
+        create_ready_storage_synchronizer(true),
+        0,
+        create_epoch_ending_ledger_info(),
+        time_service.clone(),
+        BootstrapProgressNotifier::new().0,
+    );
+
+    // Create a global data summary where epoch 0 and 1 have ended
+    let global_data_summary = create_global_summary(1);
+
+    // Drive progress to initialize the epoch ending data stream
+    drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap();
+
+    // Drive progress and verify we get a non-critical timeout
+    let error = drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::DataStreamNotificationTimeout(_));
+
+    // Elapse the stage timeout and verify the stalled stream is reset (and
+    // that a new epoch ending stream is initialized in its place).
+    let stage_label = BootstrappingStage::EpochEndingLedgerInfos.get_label();
+    let num_stream_resets = get_stream_reset_count(stage_label);
+    time_service.into_mock().advance_ms(10_000);
+    drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap();
+    assert_eq!(get_stream_reset_count(stage_label), num_stream_resets + 1);
+
+    // Drive progress again and verify the new stream is used (without a reset)
+    let error = drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::DataStreamNotificationTimeout(_));
+    assert_eq!(get_stream_reset_count(stage_label), num_stream_resets + 1);
+}
+
 #[tokio::test]
 async fn test_data_stream_state_values() {
     // Create test data
@@ -257,6 +806,153 @@ async fn test_data_stream_state_values() {
         .unwrap();
 }
 
+#[tokio::test]
+async fn test_snapshot_sync_already_complete() {
+    // Create test data
+    let highest_version = 10000;
+    let highest_ledger_info = create_random_epoch_ending_ledger_info(highest_version, 1);
+    let snapshot_ledger_info = create_random_epoch_ending_ledger_info(5000, 1);
+
+    // Create a driver configuration with a genesis waypoint and state syncing
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.bootstrapping_mode = BootstrappingMode::DownloadLatestStates;
+
+    // Create a metadata storage that has already completed a snapshot sync
+    let mut mock_metadata_storage = create_mock_metadata_storage();
+    mock_metadata_storage
+        .expect_previous_snapshot_sync_target()
+        .returning(move || Ok(Some(snapshot_ledger_info.clone())));
+    mock_metadata_storage
+        .expect_is_snapshot_sync_complete()
+        .returning(|_| Ok(true));
+
+    // Create the bootstrapper
+    let mut bootstrapper = create_bootstrapper_with_storage(
+        driver_configuration,
+        create_mock_streaming_client(),
+        mock_metadata_storage,
+        true,
+    );
+    manipulate_verified_epoch_states(&mut bootstrapper, true, true, Some(highest_version));
+
+    // Create a global data summary
+    let mut global_data_summary = create_global_summary(1);
+    global_data_summary.advertised_data.synced_ledger_infos = vec![highest_ledger_info];
+
+    // Drive progress and verify we're bootstrapped without syncing any new states
+    drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap();
+    assert!(bootstrapper.is_bootstrapped());
+}
+
+#[tokio::test]
+async fn test_snapshot_sync_resumes_previous_target() {
+    // Create test data
+    let highest_version = 10000;
+    let highest_ledger_info = create_random_epoch_ending_ledger_info(highest_version, 1);
+    let snapshot_version = 5000;
+    let snapshot_ledger_info = create_random_epoch_ending_ledger_info(snapshot_version, 0);
+    let last_persisted_index = 1234;
+
+    // Create a driver configuration with a genesis waypoint and state syncing
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.bootstrapping_mode = BootstrappingMode::DownloadLatestStates;
+
+    // Create a metadata storage with an incomplete snapshot sync
+    let mut mock_metadata_storage = create_mock_metadata_storage();
+    let previous_target = snapshot_ledger_info.clone();
+    mock_metadata_storage
+        .expect_previous_snapshot_sync_target()
+        .returning(move || Ok(Some(previous_target.clone())));
+    mock_metadata_storage
+        .expect_is_snapshot_sync_complete()
+        .with(eq(snapshot_ledger_info.clone()))
+        .returning(|_| Ok(false));
+    mock_metadata_storage
+        .expect_get_last_persisted_state_value_index()
+        .times(1)
+        .with(eq(snapshot_ledger_info.clone()))
+        .returning(move |_| Ok(last_persisted_index));
+
+    // Create the mock streaming client (the previous target should be synced)
+    let mut mock_streaming_client = create_mock_streaming_client();
+    let (_notification_sender, data_stream_listener) = create_data_stream_listener();
+    mock_streaming_client
+        .expect_get_all_transaction_outputs()
+        .times(1)
+        .with(
+            eq(snapshot_version),
+            eq(snapshot_version),
+            eq(snapshot_version),
+        )
+        .return_once(move |_, _, _| Ok(data_stream_listener));
+
+    // Create the bootstrapper
+    let mut bootstrapper = create_bootstrapper_with_storage(
+        driver_configuration,
+        mock_streaming_client,
+        mock_metadata_storage,
+        true,
+    );
+    manipulate_verified_epoch_states(&mut bootstrapper, true, true, Some(highest_version));
+    bootstrapper
+        .get_verified_epoch_states()
+        .verify_epoch_ending_ledger_info(&snapshot_ledger_info, &Waypoint::default())
+        .unwrap();
+
+    // Create a global data summary
+    let mut global_data_summary = create_global_summary(1);
+    global_data_summary.advertised_data.synced_ledger_infos = vec![highest_ledger_info];
+
+    // Drive progress to initialize the transaction output stream
+    drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap();
+    assert!(!bootstrapper.is_bootstrapped());
+}
+
+#[tokio::test]
+async fn test_snapshot_sync_target_mismatch() {
+    // Create test data
+    let highest_version = 10000;
+    let highest_ledger_info = create_random_epoch_ending_ledger_info(highest_version, 1);
+    let snapshot_ledger_info = create_random_epoch_ending_ledger_info(5000, 0);
+
+    // Create a driver configuration with a genesis waypoint and state syncing
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.bootstrapping_mode = BootstrappingMode::DownloadLatestStates;
+
+    // Create a metadata storage with an incomplete snapshot sync
+    let mut mock_metadata_storage = create_mock_metadata_storage();
+    mock_metadata_storage
+        .expect_previous_snapshot_sync_target()
+        .returning(move || Ok(Some(snapshot_ledger_info.clone())));
+    mock_metadata_storage
+        .expect_is_snapshot_sync_complete()
+        .returning(|_| Ok(false));
+
+    // Create the bootstrapper (the snapshot target is never verified)
+    let mut bootstrapper = create_bootstrapper_with_storage(
+        driver_configuration,
+        create_mock_streaming_client(),
+        mock_metadata_storage,
+        true,
+    );
+    manipulate_verified_epoch_states(&mut bootstrapper, true, true, Some(highest_version));
+
+    // Create a global data summary
+    let mut global_data_summary = create_global_summary(1);
+    global_data_summary.advertised_data.synced_ledger_infos = vec![highest_ledger_info];
+
+    // Drive progress and verify the snapshot sync is not resumed
+    let error = drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::SnapshotTargetMismatch(_));
+    assert!(!bootstrapper.is_bootstrapped());
+}
+
 #[tokio::test]
 async fn test_data_stream_transactions() {
     // Create test data
@@ -429,22 +1125,153 @@ async fn test_fetch_epoch_ending_ledger_infos() {
     assert_matches!(error, Error::DataStreamNotificationTimeout(_));
 }
 
+#[tokio::test]
+async fn test_fetch_epoch_ending_ledger_infos_pipelined() {
+    // Create a driver configuration with a genesis waypoint and a stream timeout of 1 second
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.max_stream_wait_time_ms = 1000;
+
+    // Create the mock streaming client
+    let mut mock_streaming_client = create_mock_streaming_client();
+    let (notification_sender, data_stream_listener) = create_data_stream_listener();
+    mock_streaming_client
+        .expect_get_all_epoch_ending_ledger_infos()
+        .with(eq(1))
+        .return_once(move |_| Ok(data_stream_listener));
+
+    // Create the bootstrapper
+    let mut bootstrapper = create_bootstrapper(driver_configuration, mock_streaming_client, true);
+
+    // Set the waypoint as already having been verified (but no fetched ledger infos)
+    manipulate_verified_epoch_states(&mut bootstrapper, false, true, None);
+
+    // Create a global data summary where epoch 0 and 1 have ended
+    let global_data_summary = create_global_summary(1);
+
+    // Drive progress to initialize the epoch ending data stream
+    drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap();
+
+    // Send several epoch ending payloads along the stream
+    let epoch_ending_versions = vec![vec![10, 20], vec![30], vec![40, 50]];
+    for (notification_id, versions) in epoch_ending_versions.iter().enumerate() {
+        let epoch_ending_ledger_infos = versions
+            .iter()
+            .map(|version| create_random_epoch_ending_ledger_info(*version, 0))
+            .collect();
+        let data_notification = DataNotification {
+            notification_id: notification_id as u64,
+            data_payload: DataPayload::EpochEndingLedgerInfos(epoch_ending_ledger_infos),
+        };
+        notification_sender.push((), data_notification).unwrap();
+    }
+
+    // Drive progress and verify we time out waiting for more ledger infos
+    let error = drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::DataStreamNotificationTimeout(_));
+
+    // Verify all epoch ending ledger infos were committed
+    let verified_epoch_states = bootstrapper.get_verified_epoch_states();
+    for version in epoch_ending_versions.into_iter().flatten() {
+        assert!(verified_epoch_states
+            .get_epoch_ending_ledger_info(version)
+            .is_some());
+    }
+    let highest_known_ledger_info = verified_epoch_states
+        .get_highest_known_ledger_info()
+        .unwrap();
+    assert_eq!(highest_known_ledger_info.ledger_info().version(), 50);
+}
+
+#[tokio::test]
+async fn test_fetch_epoch_ending_ledger_infos_invalid_proof() {
+    // Create a driver configuration with a genesis waypoint and a stream timeout of 1 second
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.max_stream_wait_time_ms = 1000;
+
+    // Create the mock streaming client (expecting the invalid payload to be rejected)
+    let mut mock_streaming_client = create_mock_streaming_client();
+    let (notification_sender, data_stream_listener) = create_data_stream_listener();
+    mock_streaming_client
+        .expect_get_all_epoch_ending_ledger_infos()
+        .with(eq(1))
+        .return_once(move |_| Ok(data_stream_listener));
+    let invalid_notification_id = 101;
+    mock_streaming_client
+        .expect_terminate_stream_with_feedback()
+        .times(1)
+        .with(
+            eq(invalid_notification_id),
+            eq(NotificationFeedback::PayloadProofFailed),
+        )
+        .return_const(Ok(()));
+
+    // Create the bootstrapper
+    let mut bootstrapper = create_bootstrapper(driver_configuration, mock_streaming_client, true);
+
+    // Set the waypoint as already having been verified (but no fetched ledger infos)
+    manipulate_verified_epoch_states(&mut bootstrapper, false, true, None);
+
+    // Create a global data summary where epoch 0 and 1 have ended
+    let global_data_summary = create_global_summary(1);
+
+    // Drive progress to initialize the epoch ending data stream
+    drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap();
+
+    // Send a valid payload, an invalid payload (wrong epoch) and another valid payload
+    let epoch_ending_ledger_infos = vec![
+        (100, create_random_epoch_ending_ledger_info(10, 0)),
+        (
+            invalid_notification_id,
+            create_random_epoch_ending_ledger_info(20, 5),
+        ),
+        (102, create_random_epoch_ending_ledger_info(30, 0)),
+    ];
+    for (notification_id, epoch_ending_ledger_info) in epoch_ending_ledger_infos {
+        let data_notification = DataNotification {
+            notification_id,
+            data_payload: DataPayload::EpochEndingLedgerInfos(vec![epoch_ending_ledger_info]),
+        };
+        notification_sender.push((), data_notification).unwrap();
+    }
+
+    // Drive progress and verify we get a verification error
+    let error = drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::VerificationError(_));
+
+    // Verify only the ledger infos before the invalid payload were committed
+    let verified_epoch_states = bootstrapper.get_verified_epoch_states();
+    assert!(verified_epoch_states
+        .get_epoch_ending_ledger_info(10)
+        .is_some());
+    assert_none!(verified_epoch_states.get_epoch_ending_ledger_info(20));
+    assert_none!(verified_epoch_states.get_epoch_ending_ledger_info(30));
+}
+
 #[tokio::test]
 async fn test_waypoint_mismatch() {
-    // Create a waypoint
+    // Create a waypoint (the ledger info is signed by the genesis epoch state)
     let waypoint_version = 1;
-    let waypoint_epoch = 1;
+    let waypoint_epoch = 0;
     let waypoint = create_random_epoch_ending_ledger_info(waypoint_version, waypoint_epoch);
 
     // Create a driver configuration with the specified waypoint
     let mut driver_configuration = create_full_node_driver_configuration();
     driver_configuration.waypoint = Waypoint::new_any(waypoint.ledger_info());
 
-    // Create the mock streaming client
+    // Create the mock streaming client (the stream should only be created once)
     let mut mock_streaming_client = create_mock_streaming_client();
     let (notification_sender, data_stream_listener) = create_data_stream_listener();
     mock_streaming_client
         .expect_get_all_epoch_ending_ledger_infos()
+        .times(1)
         .with(eq(1))
         .return_once(move |_| Ok(data_stream_listener));
     let notification_id = 100;
@@ -459,8 +1286,14 @@ async fn test_waypoint_mismatch() {
     // Create the bootstrapper
     let mut bootstrapper = create_bootstrapper(driver_configuration, mock_streaming_client, true);
 
+    // Subscribe to the bootstrap notification
+    let (bootstrap_notification_sender, bootstrap_notification_receiver) = oneshot::channel();
+    bootstrapper
+        .subscribe_to_bootstrap_notifications(bootstrap_notification_sender)
+        .unwrap();
+
     // Create a global data summary up to the waypoint
-    let mut global_data_summary = create_global_summary(waypoint_epoch);
+    let mut global_data_summary = create_global_summary(1);
     global_data_summary.advertised_data.synced_ledger_infos = vec![waypoint.clone()];
 
     // Drive progress to initialize the epoch ending data stream
@@ -468,22 +1301,40 @@ async fn test_waypoint_mismatch() {
         .await
         .unwrap();
 
-    // Send an invalid epoch ending payload along the stream (invalid waypoint hash)
-    let invalid_ledger_info = vec![create_random_epoch_ending_ledger_info(
-        waypoint_version,
-        waypoint_epoch,
-    )];
+    // Send an epoch ending payload along the stream that doesn't match the waypoint hash
+    let invalid_ledger_info =
+        create_random_epoch_ending_ledger_info(waypoint_version, waypoint_epoch);
     let data_notification = DataNotification {
         notification_id,
-        data_payload: DataPayload::EpochEndingLedgerInfos(invalid_ledger_info),
+        data_payload: DataPayload::EpochEndingLedgerInfos(vec![invalid_ledger_info.clone()]),
     };
     notification_sender.push((), data_notification).unwrap();
 
-    // Drive progress again and ensure we get a verification error
+    // Drive progress again and ensure we get a waypoint mismatch error
     let error = drive_progress(&mut bootstrapper, &global_data_summary, false)
         .await
         .unwrap_err();
-    assert_matches!(error, Error::VerificationError(_));
+    let expected_error = Error::WaypointMismatch {
+        waypoint_version,
+        waypoint_hash: Waypoint::new_any(waypoint.ledger_info()).value(),
+        ledger_info_version: waypoint_version,
+        ledger_info_hash: Waypoint::new_any(invalid_ledger_info.ledger_info()).value(),
+        epoch: waypoint_epoch,
+    };
+    assert_eq!(error, expected_error);
+
+    // Verify the bootstrap subscriber was notified of the error
+    assert_eq!(
+        bootstrap_notification_receiver.await.unwrap(),
+        Err(expected_error.clone())
+    );
+
+    // Drive progress again and verify the bootstrapper doesn't retry
+    let error = drive_progress(&mut bootstrapper, &global_data_summary, false)
+        .await
+        .unwrap_err();
+    assert_eq!(error, expected_error);
+    assert!(!bootstrapper.is_bootstrapped());
 }
 
 #[tokio::test]
@@ -560,12 +1411,87 @@ fn create_bootstrapper(
     driver_configuration: DriverConfiguration,
     mock_streaming_client: MockStreamingClient,
     expect_reset_executor: bool,
-) -> Bootstrapper<MockStorageSynchronizer, MockStreamingClient> {
-    // Initialize the logger for tests
-    aptos_logger::Logger::init_for_testing();
+) -> Bootstrapper<MockMetadataStorage, MockStorageSynchronizer, MockStreamingClient> {
+    create_bootstrapper_with_storage(
+        driver_configuration,
+        mock_streaming_client,
+        create_empty_metadata_storage(),
+        expect_reset_executor,
+    )
+}
 
-    // Create the mock storage synchronizer
+/// Creates a bootstrapper for testing with the given metadata storage
+fn create_bootstrapper_with_storage(
+    driver_configuration: DriverConfiguration,
+    mock_streaming_client: MockStreamingClient,
+    mock_metadata_storage: MockMetadataStorage,
+    expect_reset_executor: bool,
+) -> Bootstrapper<MockMetadataStorage, MockStorageSynchronizer, MockStreamingClient> {
     let mock_storage_synchronizer = create_ready_storage_synchronizer(expect_reset_executor);
+    create_bootstrapper_with_components(
+        driver_configuration,
+        mock_streaming_client,
+        mock_metadata_storage,
+        mock_storage_synchronizer,
+        0,
+        create_epoch_ending_ledger_info(),
+        TimeService::mock(),
+        BootstrapProgressNotifier::new().0,
+    )
+}
+
+/// Creates a bootstrapper for testing where storage has synced to the given version
+fn create_bootstrapper_with_synced_version(
+    driver_configuration: DriverConfiguration,
+    mock_streaming_client: MockStreamingClient,
+    mock_metadata_storage: MockMetadataStorage,
+    synced_version: Version,
+) -> Bootstrapper<MockMetadataStorage, MockStorageSynchronizer, MockStreamingClient> {
+    let mock_storage_synchronizer = create_ready_storage_synchronizer(true);
+    create_bootstrapper_with_components(
+        driver_configuration,
+        mock_streaming_client,
+        mock_metadata_storage,
+        mock_storage_synchronizer,
+        synced_version,
+        create_epoch_ending_ledger_info(),
+        TimeService::mock(),
+        BootstrapProgressNotifier::new().0,
+    )
+}
+
+/// Creates a bootstrapper for testing where storage has synced to the given ledger info
+fn create_bootstrapper_with_ledger_info(
+    driver_configuration: DriverConfiguration,
+    mock_streaming_client: MockStreamingClient,
+    latest_ledger_info: LedgerInfoWithSignatures,
+) -> Bootstrapper<MockMetadataStorage, MockStorageSynchronizer, MockStreamingClient> {
+    let synced_version = latest_ledger_info.ledger_info().version();
+    create_bootstrapper_with_components(
+        driver_configuration,
+        mock_streaming_client,
+        create_empty_metadata_storage(),
+        create_ready_storage_synchronizer(true),
+        synced_version,
+        latest_ledger_info,
+        TimeService::mock(),
+        BootstrapProgressNotifier::new().0,
+    )
+}
+
+/// Creates a bootstrapper for testing with the given components
+fn create_bootstrapper_with_components(
+    driver_configuration: DriverConfiguration,
+    mock_streaming_client: MockStreamingClient,
+    mock_metadata_storage: MockMetadataStorage,
+    mock_storage_synchronizer: MockStorageSynchronizer,
+    synced_version: Version,
+    latest_ledger_info: LedgerInfoWithSignatures,
+    time_service: TimeService,
+    bootstrap_progress_notifier: BootstrapProgressNotifier,
+) -> Bootstrapper<MockMetadataStorage, MockStorageSynchronizer, MockStreamingClient> {
+    // Initialize the logger for tests
+    aptos_logger::Logger::init_for_testing();
 
     // Create the mock db reader with only genesis loaded
     let mut mock_database_reader = create_mock_db_reader();
@@ -574,16 +1500,19 @@ fn create_bootstrapper(
         .returning(|| Ok(create_empty_epoch_state()));
     mock_database_reader
         .expect_get_latest_ledger_info()
-        .returning(|| Ok(create_epoch_ending_ledger_info()));
+        .returning(move || Ok(latest_ledger_info.clone()));
     mock_database_reader
         .expect_get_latest_transaction_info_option()
-        .returning(|| Ok(Some((0, create_transaction_info()))));
+        .returning(move || Ok(Some((synced_version, create_transaction_info()))));
 
     Bootstrapper::new(
         driver_configuration,
+        mock_metadata_storage,
         mock_streaming_client,
         Arc::new(mock_database_reader),
         mock_storage_synchronizer,
+        time_service,
+        bootstrap_progress_notifier,
     )
 }
 
@@ -591,7 +1520,11 @@ fn create_bootstrapper(
 /// is true this method will continue to drive the bootstrapper until
 /// bootstrapping is complete.
 async fn drive_progress(
-    bootstrapper: &mut Bootstrapper<MockStorageSynchronizer, MockStreamingClient>,
+    bootstrapper: &mut Bootstrapper<
+        MockMetadataStorage,
+        MockStorageSynchronizer,
+        MockStreamingClient,
+    >,
     global_data_summary: &GlobalDataSummary,
     until_bootstrapped: bool,
 ) -> Result<(), Error> {
@@ -606,11 +1539,22 @@ async fn drive_progress(
     }
 }
 
+/// Returns the number of stream resets for the given bootstrapping stage
+fn get_stream_reset_count(stage_label: &str) -> u64 {
+    metrics::BOOTSTRAPPER_STREAM_RESETS
+        .with_label_values(&[stage_label])
+        .get()
+}
+
 /// Manipulates the internal state of the verified epoch states used by
 /// the given bootstrapper and inserts a verified epoch ending ledger
 /// info at the specified `highest_version_to_insert` (if provided).
 fn manipulate_verified_epoch_states(
-    bootstrapper: &mut Bootstrapper<MockStorageSynchronizer, MockStreamingClient>,
+    bootstrapper: &mut Bootstrapper<
+        MockMetadataStorage,
+        MockStorageSynchronizer,
+        MockStreamingClient,
+    >,
     fetched_epochs: bool,
     verified_waypoint: bool,
     highest_version_to_insert: Option<Version>,
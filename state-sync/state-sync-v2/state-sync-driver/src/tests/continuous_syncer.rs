@@ -6,30 +6,222 @@ use crate::{
     driver::DriverConfiguration,
     error::Error,
     notification_handlers::ConsensusSyncRequest,
+    storage_synchronizer::ChunkLatencyAverage,
     tests::{
         mocks::{
-            create_mock_db_reader, create_mock_streaming_client, create_ready_storage_synchronizer,
-            MockStorageSynchronizer, MockStreamingClient,
+            create_mock_db_reader, create_mock_storage_synchronizer, create_mock_streaming_client,
+            create_ready_storage_synchronizer, MockStorageSynchronizer, MockStreamingClient,
         },
         utils::{
             create_data_stream_listener, create_epoch_ending_ledger_info, create_epoch_state,
-            create_full_node_driver_configuration, create_transaction_info,
+            create_full_node_driver_configuration, create_ledger_info_at_version,
+            create_output_list_with_proof, create_transaction, create_transaction_info,
+            create_transaction_info_list_with_proof, create_transaction_list_with_proof,
         },
     },
+    utils::ChunkSizeController,
 };
-use aptos_config::config::ContinuousSyncingMode;
+use aptos_config::config::{ContinuousStreamingMode, ContinuousSyncingMode};
+use aptos_crypto::HashValue;
 use aptos_infallible::Mutex;
-use aptos_types::transaction::{TransactionOutputListWithProof, Version};
+use aptos_time_service::{TimeService, TimeServiceTrait};
+use aptos_types::{
+    account_address::AccountAddress,
+    block_metadata::BlockMetadata,
+    epoch_state::EpochState,
+    ledger_info::LedgerInfoWithSignatures,
+    transaction::{Transaction, TransactionListWithProof, TransactionOutputListWithProof, Version},
+};
+use channel::aptos_channel::Sender;
 use claim::assert_matches;
 use consensus_notifications::ConsensusSyncNotification;
 use data_streaming_service::{
     data_notification::{DataNotification, DataPayload},
     streaming_client::NotificationFeedback,
 };
-use mockall::{predicate::eq, Sequence};
-use std::sync::Arc;
+use futures::{channel::oneshot, FutureExt};
+use mockall::{
+    predicate::{always, eq},
+    Sequence,
+};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use storage_service_types::Epoch;
 
+// Useful test constants
+const SLOW_VERIFICATION_MS: u64 = 500;
+const SLOW_VERIFICATION_VERSION: Version = 11;
+
+#[tokio::test]
+async fn test_adaptive_chunk_sizing() {
+    // Create test data
+    let current_synced_epoch = 0;
+    let current_synced_version = 10;
+    let min_chunk_size = 10;
+    let max_chunk_size = 40;
+    let target_chunk_latency_ms = 100;
+
+    // Create a driver configuration with adaptive chunk sizing
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.continuous_syncing_mode =
+        ContinuousSyncingMode::ApplyTransactionOutputs;
+    driver_configuration
+        .config
+        .max_consecutive_stream_notifications = 1;
+    driver_configuration.config.min_continuous_chunk_size = min_chunk_size;
+    driver_configuration.config.max_continuous_chunk_size = max_chunk_size;
+    driver_configuration.config.target_chunk_latency_ms = target_chunk_latency_ms;
+
+    // Create the mock streaming client (the chunk size should shrink and then grow)
+    let mut mock_streaming_client = create_mock_streaming_client();
+    let mut expectation_sequence = Sequence::new();
+    let (notification_sender_1, data_stream_listener_1) = create_data_stream_listener();
+    let (notification_sender_2, data_stream_listener_2) = create_data_stream_listener();
+    let (_notification_sender_3, data_stream_listener_3) = create_data_stream_listener();
+    for (data_stream_listener, chunk_size) in [
+        (data_stream_listener_1, max_chunk_size),
+        (data_stream_listener_2, max_chunk_size / 2),
+        (data_stream_listener_3, max_chunk_size),
+    ] {
+        mock_streaming_client
+            .expect_continuously_stream_transaction_outputs()
+            .times(1)
+            .with(
+                eq(current_synced_version),
+                eq(current_synced_epoch),
+                eq(None),
+                eq(Some(chunk_size)),
+            )
+            .return_once(move |_, _, _, _| Ok(data_stream_listener))
+            .in_sequence(&mut expectation_sequence);
+    }
+
+    // Create the mock storage synchronizer with controllable chunk latencies
+    let chunk_latency_average = Arc::new(Mutex::new(ChunkLatencyAverage::default()));
+    let mut mock_storage_synchronizer = create_mock_storage_synchronizer();
+    mock_storage_synchronizer
+        .expect_pending_storage_data()
+        .return_const(false);
+    mock_storage_synchronizer
+        .expect_pending_data_limit_reached()
+        .return_const(false);
+    mock_storage_synchronizer
+        .expect_get_num_pending_data_chunks()
+        .return_const(0u64);
+    mock_storage_synchronizer
+        .expect_reset_chunk_executor()
+        .return_const(Ok(()));
+    mock_storage_synchronizer
+        .expect_apply_transaction_outputs()
+        .times(2)
+        .return_const(Ok(()));
+    let latency_average = chunk_latency_average.clone();
+    mock_storage_synchronizer
+        .expect_get_chunk_latency_average()
+        .returning(move || *latency_average.lock());
+
+    // Create the continuous syncer
+    let mut continuous_syncer = create_continuous_syncer_with_components(
+        driver_configuration,
+        mock_streaming_client,
+        mock_storage_synchronizer,
+        current_synced_version,
+        current_synced_epoch,
+        TimeService::mock(),
+    );
+
+    // Drive progress to initialize the transaction output stream
+    let no_sync_request = Arc::new(Mutex::new(None));
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+
+    // Delay the chunk commits, send outputs along the stream and drive progress
+    *chunk_latency_average.lock() = ChunkLatencyAverage {
+        average_latency: Duration::from_millis(target_chunk_latency_ms * 2),
+        num_chunks: 4,
+    };
+    send_output_notification(&notification_sender_1, current_synced_version + 1);
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+    continuous_syncer.wait_for_verified_chunks().await.unwrap();
+
+    // Drive progress and verify a new stream is created with a smaller chunk size
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+
+    // Speed up the chunk commits, send outputs along the stream and drive progress
+    *chunk_latency_average.lock() = ChunkLatencyAverage {
+        average_latency: Duration::from_millis(target_chunk_latency_ms / 10),
+        num_chunks: 8,
+    };
+    send_output_notification(&notification_sender_2, current_synced_version + 1);
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+    continuous_syncer.wait_for_verified_chunks().await.unwrap();
+
+    // Drive progress and verify a new stream is created with a larger chunk size
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+}
+
+#[test]
+fn test_chunk_size_controller() {
+    // Create a chunk size controller
+    let min_chunk_size = 100;
+    let max_chunk_size = 1000;
+    let target_chunk_latency = Duration::from_millis(500);
+    let mut chunk_size_controller =
+        ChunkSizeController::new(min_chunk_size, max_chunk_size, target_chunk_latency);
+    assert_eq!(chunk_size_controller.get_chunk_size(), max_chunk_size);
+
+    // Verify the chunk size doesn't change until enough chunks have been recorded
+    let slow_chunk_latency = target_chunk_latency * 2;
+    let latency_average = create_latency_average(slow_chunk_latency, 3);
+    assert!(!chunk_size_controller.update_chunk_size(latency_average));
+    assert_eq!(chunk_size_controller.get_chunk_size(), max_chunk_size);
+
+    // Verify the chunk size shrinks when commits are slow (bounded by the min)
+    let mut num_chunks = 4;
+    for expected_chunk_size in [500, 250, 125, 100] {
+        let latency_average = create_latency_average(slow_chunk_latency, num_chunks);
+        assert!(chunk_size_controller.update_chunk_size(latency_average));
+        assert_eq!(chunk_size_controller.get_chunk_size(), expected_chunk_size);
+        num_chunks += 4;
+    }
+    let latency_average = create_latency_average(slow_chunk_latency, num_chunks);
+    assert!(!chunk_size_controller.update_chunk_size(latency_average));
+    assert_eq!(chunk_size_controller.get_chunk_size(), min_chunk_size);
+
+    // Verify the chunk size doesn't change when commits are close to the target
+    let latency_average = create_latency_average(target_chunk_latency, num_chunks);
+    assert!(!chunk_size_controller.update_chunk_size(latency_average));
+    assert_eq!(chunk_size_controller.get_chunk_size(), min_chunk_size);
+
+    // Verify the chunk size grows when commits are fast (bounded by the max)
+    let fast_chunk_latency = target_chunk_latency / 4;
+    for expected_chunk_size in [200, 400, 800, 1000] {
+        let latency_average = create_latency_average(fast_chunk_latency, num_chunks);
+        assert!(chunk_size_controller.update_chunk_size(latency_average));
+        assert_eq!(chunk_size_controller.get_chunk_size(), expected_chunk_size);
+        num_chunks += 4;
+    }
+    let latency_average = create_latency_average(fast_chunk_latency, num_chunks);
+    assert!(!chunk_size_controller.update_chunk_size(latency_average));
+    assert_eq!(chunk_size_controller.get_chunk_size(), max_chunk_size);
+}
+
 #[tokio::test]
 async fn test_critical_timeout() {
     // Create test data
@@ -55,8 +247,9 @@ async fn test_critical_timeout() {
                 eq(current_synced_version),
                 eq(current_synced_epoch),
                 eq(None),
+                always(),
             )
-            .return_once(move |_, _, _| Ok(data_stream_listener))
+            .return_once(move |_, _, _, _| Ok(data_stream_listener))
             .in_sequence(&mut expectation_sequence);
     }
 
@@ -133,8 +326,9 @@ async fn test_data_stream_transactions_with_target() {
                 eq(current_synced_epoch),
                 eq(false),
                 eq(Some(target_ledger_info.clone())),
+                always(),
             )
-            .return_once(move |_, _, _, _| Ok(data_stream_listener))
+            .return_once(move |_, _, _, _, _| Ok(data_stream_listener))
             .in_sequence(&mut expectation_sequence);
     }
     mock_streaming_client
@@ -158,6 +352,7 @@ async fn test_data_stream_transactions_with_target() {
     let (consensus_sync_notification, _) = ConsensusSyncNotification::new(target_ledger_info);
     let sync_request = Arc::new(Mutex::new(Some(ConsensusSyncRequest::new(
         consensus_sync_notification,
+        Instant::now(),
     ))));
     continuous_syncer
         .drive_progress(sync_request.clone())
@@ -213,8 +408,9 @@ async fn test_data_stream_transaction_outputs() {
                 eq(current_synced_version),
                 eq(current_synced_epoch),
                 eq(None),
+                always(),
             )
-            .return_once(move |_, _, _| Ok(data_stream_listener))
+            .return_once(move |_, _, _, _| Ok(data_stream_listener))
             .in_sequence(&mut expectation_sequence);
     }
     mock_streaming_client
@@ -268,6 +464,880 @@ async fn test_data_stream_transaction_outputs() {
         .unwrap();
 }
 
+#[tokio::test]
+async fn test_output_fallback_to_transactions() {
+    // Create test data
+    let current_synced_epoch = 0;
+    let current_synced_version = 10;
+    let notification_id = 9000;
+    let fallback_duration_secs = 30;
+    let fallback_threshold = 2;
+
+    // Create a driver configuration that syncs outputs
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.continuous_syncing_mode =
+        ContinuousSyncingMode::ApplyTransactionOutputs;
+    driver_configuration
+        .config
+        .max_consecutive_stream_notifications = 1;
+    driver_configuration.config.output_fallback_duration_secs = fallback_duration_secs;
+    driver_configuration.config.output_fallback_threshold = fallback_threshold;
+
+    // Create the mock streaming client (peers only serve transactions)
+    let mut mock_streaming_client = create_mock_streaming_client();
+    let mut expectation_sequence = Sequence::new();
+    mock_streaming_client
+        .expect_continuously_stream_transaction_outputs()
+        .times(fallback_threshold as usize)
+        .with(
+            eq(current_synced_version),
+            eq(current_synced_epoch),
+            eq(None),
+            always(),
+        )
+        .returning(|_, _, _, _| {
+            Err(data_streaming_service::error::Error::DataIsUnavailable(
+                "Outputs have been pruned!".into(),
+            ))
+        })
+        .in_sequence(&mut expectation_sequence);
+    let (notification_sender_1, data_stream_listener_1) = create_data_stream_listener();
+    mock_streaming_client
+        .expect_continuously_stream_transactions()
+        .times(1)
+        .with(
+            eq(current_synced_version),
+            eq(current_synced_epoch),
+            eq(false),
+            eq(None),
+            always(),
+        )
+        .return_once(move |_, _, _, _, _| Ok(data_stream_listener_1))
+        .in_sequence(&mut expectation_sequence);
+    let (_notification_sender_2, data_stream_listener_2) = create_data_stream_listener();
+    mock_streaming_client
+        .expect_continuously_stream_transaction_outputs()
+        .times(1)
+        .return_once(move |_, _, _, _| Ok(data_stream_listener_2))
+        .in_sequence(&mut expectation_sequence);
+    mock_streaming_client
+        .expect_terminate_stream_with_feedback()
+        .with(
+            eq(notification_id + 1),
+            eq(NotificationFeedback::InvalidPayloadData),
+        )
+        .return_const(Ok(()));
+
+    // Create the mock storage synchronizer and verify transactions are executed
+    let mut mock_storage_synchronizer = create_ready_storage_synchronizer(true);
+    mock_storage_synchronizer
+        .expect_execute_transactions()
+        .times(1)
+        .with(eq(notification_id), always(), always(), eq(None))
+        .return_const(Ok(()));
+
+    // Create the continuous syncer
+    let time_service = TimeService::mock();
+    let mut continuous_syncer = create_continuous_syncer_with_components(
+        driver_configuration,
+        mock_streaming_client,
+        mock_storage_synchronizer,
+        current_synced_version,
+        current_synced_epoch,
+        time_service.clone(),
+    );
+
+    // Drive progress and verify the outputs are unavailable
+    let no_sync_request = Arc::new(Mutex::new(None));
+    for _ in 0..fallback_threshold {
+        let error = continuous_syncer
+            .drive_progress(no_sync_request.clone())
+            .await
+            .unwrap_err();
+        assert_matches!(error, Error::DataIsUnavailable(_));
+    }
+
+    // Drive progress to initialize the fallback transaction stream
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+
+    // Send transactions along the stream and verify they're executed
+    let mut transaction_list_with_proof = create_transaction_list_with_proof();
+    transaction_list_with_proof.first_transaction_version = Some(current_synced_version + 1);
+    let data_notification = DataNotification {
+        notification_id,
+        data_payload: DataPayload::ContinuousTransactionsWithProof(
+            create_ledger_info_at_version(current_synced_version + 1),
+            transaction_list_with_proof,
+        ),
+    };
+    notification_sender_1.push((), data_notification).unwrap();
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+    continuous_syncer.wait_for_verified_chunks().await.unwrap();
+
+    // Elapse the fallback duration and send an invalid payload to reset the stream
+    time_service
+        .into_mock()
+        .advance_secs(fallback_duration_secs);
+    let mut transaction_list_with_proof = create_transaction_list_with_proof();
+    transaction_list_with_proof.first_transaction_version = Some(current_synced_version);
+    let data_notification = DataNotification {
+        notification_id: notification_id + 1,
+        data_payload: DataPayload::ContinuousTransactionsWithProof(
+            create_ledger_info_at_version(current_synced_version + 1),
+            transaction_list_with_proof,
+        ),
+    };
+    notification_sender_1.push((), data_notification).unwrap();
+    let error = continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::VerificationError(_));
+
+    // Drive progress and verify transaction outputs are streamed again
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_pending_data_limit() {
+    // Create a continuous syncer with a pipeline depth of 1
+    let current_synced_version = 10;
+    let (mut continuous_syncer, notification_sender, num_pending_chunks, num_applied_chunks) =
+        create_pipelined_continuous_syncer(1, current_synced_version);
+
+    // Drive progress to initialize the transaction output stream
+    let no_sync_request = Arc::new(Mutex::new(None));
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+
+    // Send several outputs along the stream
+    for version in current_synced_version + 1..=current_synced_version + 3 {
+        send_output_notification(&notification_sender, version);
+    }
+
+    // Verify the stream is only polled after each chunk is committed
+    for num_chunks in 1..=3 {
+        for _ in 0..2 {
+            continuous_syncer
+                .drive_progress(no_sync_request.clone())
+                .await
+                .unwrap();
+            continuous_syncer.wait_for_verified_chunks().await.unwrap();
+            assert_eq!(*num_applied_chunks.lock(), num_chunks);
+            assert_eq!(*num_pending_chunks.lock(), 1);
+        }
+        *num_pending_chunks.lock() -= 1; // Commit the pending chunk
+    }
+
+    // Create a continuous syncer with a pipeline depth of 4
+    let (mut continuous_syncer, notification_sender, num_pending_chunks, num_applied_chunks) =
+        create_pipelined_continuous_syncer(4, current_synced_version);
+
+    // Drive progress to initialize the transaction output stream
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+
+    // Send several outputs along the stream
+    for version in current_synced_version + 1..=current_synced_version + 6 {
+        send_output_notification(&notification_sender, version);
+    }
+
+    // Verify that up to four chunks are queued (and no more are polled)
+    for _ in 0..2 {
+        continuous_syncer
+            .drive_progress(no_sync_request.clone())
+            .await
+            .unwrap();
+        continuous_syncer.wait_for_verified_chunks().await.unwrap();
+        assert_eq!(*num_applied_chunks.lock(), 4);
+        assert_eq!(*num_pending_chunks.lock(), 4);
+    }
+
+    // Commit a single chunk and verify another chunk is queued
+    *num_pending_chunks.lock() -= 1;
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+    continuous_syncer.wait_for_verified_chunks().await.unwrap();
+    assert_eq!(*num_applied_chunks.lock(), 5);
+    assert_eq!(*num_pending_chunks.lock(), 4);
+}
+
+#[tokio::test]
+async fn test_sync_to_timestamp_straddling_chunk() {
+    // Create test data
+    let current_synced_epoch = 0;
+    let synced_version = Arc::new(Mutex::new(100));
+    let target_timestamp_usecs = 20;
+    let proof_version = 105;
+
+    // Create a chunk that straddles the target timestamp (at version 104)
+    let transactions = vec![
+        create_block_metadata_transaction(target_timestamp_usecs - 10),
+        create_transaction(),
+        create_transaction(),
+        create_block_metadata_transaction(target_timestamp_usecs + 10),
+        create_transaction(),
+    ];
+
+    // Create the mock streaming client (a bounded stream should be
+    // created to sync the data up to the target version, i.e., 103).
+    let mut mock_streaming_client = create_mock_streaming_client();
+    let mut expectation_sequence = Sequence::new();
+    let (notification_sender_1, data_stream_listener_1) = create_data_stream_listener();
+    let (notification_sender_2, data_stream_listener_2) = create_data_stream_listener();
+    mock_streaming_client
+        .expect_continuously_stream_transactions()
+        .times(1)
+        .with(
+            eq(100),
+            eq(current_synced_epoch),
+            eq(false),
+            eq(None),
+            always(),
+        )
+        .return_once(move |_, _, _, _, _| Ok(data_stream_listener_1))
+        .in_sequence(&mut expectation_sequence);
+    mock_streaming_client
+        .expect_get_all_transactions()
+        .times(1)
+        .with(eq(101), eq(103), eq(proof_version), eq(false))
+        .return_once(move |_, _, _, _| Ok(data_stream_listener_2))
+        .in_sequence(&mut expectation_sequence);
+
+    // Create the mock storage synchronizer (only the truncated chunk should be executed)
+    let mut mock_storage_synchronizer = create_ready_storage_synchronizer(true);
+    mock_storage_synchronizer
+        .expect_execute_transactions()
+        .times(1)
+        .withf(|_, transaction_list_with_proof, _, _| {
+            transaction_list_with_proof.transactions.len() == 3
+        })
+        .return_const(Ok(()));
+
+    // Create the continuous syncer and set the timestamp target
+    let mut continuous_syncer = create_timestamp_target_syncer(
+        mock_streaming_client,
+        mock_storage_synchronizer,
+        synced_version.clone(),
+    );
+    let (notifier_channel, target_reached_receiver) = oneshot::channel();
+    continuous_syncer
+        .set_timestamp_target(target_timestamp_usecs, notifier_channel)
+        .unwrap();
+
+    // Drive progress to initialize the transaction stream
+    let no_sync_request = Arc::new(Mutex::new(None));
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+
+    // Send the straddling chunk along the stream and drive progress
+    send_transaction_notification(
+        &notification_sender_1,
+        transactions.clone(),
+        101,
+        proof_version,
+        true,
+    );
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+    continuous_syncer.wait_for_verified_chunks().await.unwrap();
+
+    // Drive progress to initialize the bounded stream and send the truncated chunk
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+    send_transaction_notification(
+        &notification_sender_2,
+        transactions[0..3].to_vec(),
+        101,
+        proof_version,
+        false,
+    );
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+    continuous_syncer.wait_for_verified_chunks().await.unwrap();
+
+    // Commit the data and verify the target is reached
+    *synced_version.lock() = 103;
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+    let synced_target_version = target_reached_receiver
+        .now_or_never()
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(synced_target_version, 103);
+
+    // Verify no new streams are created once the target is reached
+    for _ in 0..3 {
+        continuous_syncer
+            .drive_progress(no_sync_request.clone())
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_sync_to_timestamp_chunk_start() {
+    // Create test data
+    let current_synced_epoch = 0;
+    let synced_version = Arc::new(Mutex::new(100));
+    let target_timestamp_usecs = 20;
+
+    // Create the mock streaming client
+    let mut mock_streaming_client = create_mock_streaming_client();
+    let (notification_sender, data_stream_listener) = create_data_stream_listener();
+    mock_streaming_client
+        .expect_continuously_stream_transactions()
+        .times(1)
+        .with(
+            eq(100),
+            eq(current_synced_epoch),
+            eq(false),
+            eq(None),
+            always(),
+        )
+        .return_once(move |_, _, _, _, _| Ok(data_stream_listener));
+
+    // Create the mock storage synchronizer (no data should be executed)
+    let mock_storage_synchronizer = create_ready_storage_synchronizer(true);
+
+    // Create the continuous syncer and set the timestamp target
+    let mut continuous_syncer = create_timestamp_target_syncer(
+        mock_streaming_client,
+        mock_storage_synchronizer,
+        synced_version,
+    );
+    let (notifier_channel, target_reached_receiver) = oneshot::channel();
+    continuous_syncer
+        .set_timestamp_target(target_timestamp_usecs, notifier_channel)
+        .unwrap();
+
+    // Drive progress to initialize the transaction stream
+    let no_sync_request = Arc::new(Mutex::new(None));
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+
+    // Send a chunk that starts beyond the target timestamp and drive progress
+    let transactions = vec![
+        create_block_metadata_transaction(target_timestamp_usecs + 1),
+        create_transaction(),
+    ];
+    send_transaction_notification(&notification_sender, transactions, 101, 102, true);
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+    continuous_syncer.wait_for_verified_chunks().await.unwrap();
+    for _ in 0..3 {
+        continuous_syncer
+            .drive_progress(no_sync_request.clone())
+            .await
+            .unwrap();
+    }
+
+    // Verify the target is reached at the currently synced version
+    let synced_target_version = target_reached_receiver
+        .now_or_never()
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(synced_target_version, 100);
+}
+
+#[tokio::test]
+async fn test_slow_chunk_verification() {
+    // Create test data
+    let current_synced_epoch = 0;
+    let current_synced_version = SLOW_VERIFICATION_VERSION - 1;
+
+    // Create a driver configuration that syncs outputs
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.continuous_syncing_mode =
+        ContinuousSyncingMode::ApplyTransactionOutputs;
+    driver_configuration
+        .config
+        .max_consecutive_stream_notifications = 2;
+
+    // Create the mock streaming client
+    let mut mock_streaming_client = create_mock_streaming_client();
+    let (notification_sender, data_stream_listener) = create_data_stream_listener();
+    mock_streaming_client
+        .expect_continuously_stream_transaction_outputs()
+        .times(1)
+        .with(
+            eq(current_synced_version),
+            eq(current_synced_epoch),
+            eq(None),
+            always(),
+        )
+        .return_once(move |_, _, _, _| Ok(data_stream_listener));
+
+    // Create the mock storage synchronizer and verify the chunks are applied in order
+    let mut mock_storage_synchronizer = create_ready_storage_synchronizer(true);
+    let mut expectation_sequence = Sequence::new();
+    for notification_id in [SLOW_VERIFICATION_VERSION, SLOW_VERIFICATION_VERSION + 1] {
+        mock_storage_synchronizer
+            .expect_apply_transaction_outputs()
+            .times(1)
+            .with(eq(notification_id), always(), always(), eq(None))
+            .return_const(Ok(()))
+            .in_sequence(&mut expectation_sequence);
+    }
+
+    // Create the continuous syncer (with a slow verifier for the first chunk)
+    let mut continuous_syncer = create_continuous_syncer_with_components(
+        driver_configuration,
+        mock_streaming_client,
+        mock_storage_synchronizer,
+        current_synced_version,
+        current_synced_epoch,
+        TimeService::mock(),
+    );
+    continuous_syncer.set_ledger_info_verifier(slow_ledger_info_verifier);
+
+    // Drive progress to initialize the transaction output stream
+    let no_sync_request = Arc::new(Mutex::new(None));
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+
+    // Send two chunks along the stream
+    for version in [SLOW_VERIFICATION_VERSION, SLOW_VERIFICATION_VERSION + 1] {
+        send_output_notification(&notification_sender, version);
+    }
+
+    // Drive progress and verify both chunks are received without
+    // waiting for the slow verification of the first chunk.
+    let drive_progress_start = Instant::now();
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+    assert!(drive_progress_start.elapsed() < Duration::from_millis(SLOW_VERIFICATION_MS));
+    assert_eq!(continuous_syncer.get_num_pending_verification_chunks(), 2);
+
+    // Wait for the verifications and verify the chunks are applied in order
+    continuous_syncer.wait_for_verified_chunks().await.unwrap();
+    assert_eq!(continuous_syncer.get_num_pending_verification_chunks(), 0);
+}
+
+#[tokio::test]
+async fn test_stream_reset_during_chunk_verification() {
+    // Create test data
+    let current_synced_epoch = 0;
+    let current_synced_version = SLOW_VERIFICATION_VERSION - 1;
+
+    // Create a driver configuration that syncs outputs
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.continuous_syncing_mode =
+        ContinuousSyncingMode::ApplyTransactionOutputs;
+    driver_configuration
+        .config
+        .max_consecutive_stream_notifications = 1;
+
+    // Create the mock streaming client (expecting two streams)
+    let mut mock_streaming_client = create_mock_streaming_client();
+    let mut expectation_sequence = Sequence::new();
+    let (notification_sender_1, data_stream_listener_1) = create_data_stream_listener();
+    let (notification_sender_2, data_stream_listener_2) = create_data_stream_listener();
+    for data_stream_listener in [data_stream_listener_1, data_stream_listener_2] {
+        mock_streaming_client
+            .expect_continuously_stream_transaction_outputs()
+            .times(1)
+            .with(
+                eq(current_synced_version),
+                eq(current_synced_epoch),
+                eq(None),
+                always(),
+            )
+            .return_once(move |_, _, _, _| Ok(data_stream_listener))
+            .in_sequence(&mut expectation_sequence);
+    }
+    mock_streaming_client
+        .expect_terminate_stream_with_feedback()
+        .times(1)
+        .with(
+            eq(SLOW_VERIFICATION_VERSION),
+            eq(NotificationFeedback::InvalidPayloadData),
+        )
+        .return_const(Ok(()));
+
+    // Create the mock storage synchronizer and verify no chunks are applied
+    let mut mock_storage_synchronizer = create_ready_storage_synchronizer(true);
+    mock_storage_synchronizer
+        .expect_apply_transaction_outputs()
+        .times(0);
+
+    // Create the continuous syncer (with a slow verifier)
+    let mut continuous_syncer = create_continuous_syncer_with_components(
+        driver_configuration,
+        mock_streaming_client,
+        mock_storage_synchronizer,
+        current_synced_version,
+        current_synced_epoch,
+        TimeService::mock(),
+    );
+    continuous_syncer.set_ledger_info_verifier(slow_ledger_info_verifier);
+
+    // Drive progress to initialize the first stream and send a chunk along it
+    let no_sync_request = Arc::new(Mutex::new(None));
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+    send_output_notification(&notification_sender_1, SLOW_VERIFICATION_VERSION);
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+    assert_eq!(continuous_syncer.get_num_pending_verification_chunks(), 1);
+
+    // Terminate the stream (e.g., as the driver does on an error notification)
+    // and verify the chunk being verified is dropped.
+    continuous_syncer
+        .terminate_active_stream(
+            SLOW_VERIFICATION_VERSION,
+            NotificationFeedback::InvalidPayloadData,
+        )
+        .await
+        .unwrap();
+    assert_eq!(continuous_syncer.get_num_pending_verification_chunks(), 0);
+
+    // Drive progress to initialize the second stream and send a chunk along it
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+    send_output_notification(&notification_sender_2, SLOW_VERIFICATION_VERSION);
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+    assert_eq!(continuous_syncer.get_num_pending_verification_chunks(), 1);
+
+    // Reset the stream (e.g., as the driver does once consensus is back in
+    // control) and verify the chunk being verified is dropped.
+    continuous_syncer.reset_active_stream();
+    assert_eq!(continuous_syncer.get_num_pending_verification_chunks(), 0);
+
+    // Wait for the verifications to finish and verify nothing reaches storage
+    tokio::time::sleep(Duration::from_millis(2 * SLOW_VERIFICATION_MS)).await;
+    continuous_syncer.wait_for_verified_chunks().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_subscription_streaming_latency() {
+    // Measure the notification latency when using request/response streams
+    let request_response_latency =
+        measure_notification_latency(ContinuousStreamingMode::RequestResponse).await;
+
+    // Measure the notification latency when using subscription streams
+    let subscription_latency =
+        measure_notification_latency(ContinuousStreamingMode::Subscription).await;
+
+    // Verify the subscription latency is lower
+    assert!(subscription_latency < request_response_latency);
+}
+
+/// Creates a chunk latency average with the given values
+fn create_latency_average(average_latency: Duration, num_chunks: u64) -> ChunkLatencyAverage {
+    ChunkLatencyAverage {
+        average_latency,
+        num_chunks,
+    }
+}
+
+/// Measures the time between new data being committed by peers (after a
+/// period without any new data) and the data being applied to storage when
+/// using the given streaming mode.
+async fn measure_notification_latency(
+    continuous_streaming_mode: ContinuousStreamingMode,
+) -> Duration {
+    // Create test data
+    let current_synced_epoch = 0;
+    let current_synced_version = 100;
+    let progress_check_interval = Duration::from_millis(100);
+
+    // Create a driver configuration with the given streaming mode
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.continuous_streaming_mode = continuous_streaming_mode;
+    driver_configuration.config.continuous_syncing_mode =
+        ContinuousSyncingMode::ApplyTransactionOutputs;
+    driver_configuration
+        .config
+        .max_consecutive_stream_notifications = 1;
+    driver_configuration.config.max_stream_wait_time_ms = 10;
+
+    // Create the mock streaming client (request/response streams are
+    // recreated after the stream times out, but subscriptions are not).
+    let is_subscription = continuous_streaming_mode == ContinuousStreamingMode::Subscription;
+    let num_expected_streams = if is_subscription { 1 } else { 2 };
+    let (notification_sender_1, data_stream_listener_1) = create_data_stream_listener();
+    let (notification_sender_2, data_stream_listener_2) = create_data_stream_listener();
+    let data_stream_listeners = Arc::new(Mutex::new(vec![
+        data_stream_listener_1,
+        data_stream_listener_2,
+    ]));
+    let mut mock_streaming_client = create_mock_streaming_client();
+    mock_streaming_client
+        .expect_continuously_stream_transaction_outputs()
+        .times(num_expected_streams)
+        .with(
+            eq(current_synced_version),
+            eq(current_synced_epoch),
+            eq(None),
+            always(),
+        )
+        .returning(move |_, _, _, _| Ok(data_stream_listeners.lock().remove(0)));
+
+    // Create the mock storage synchronizer and record when outputs are applied
+    let time_service = TimeService::mock();
+    let apply_time = Arc::new(Mutex::new(None));
+    let mut mock_storage_synchronizer = create_ready_storage_synchronizer(true);
+    let (apply_time_clone, time_service_clone) = (apply_time.clone(), time_service.clone());
+    mock_storage_synchronizer
+        .expect_apply_transaction_outputs()
+        .times(1)
+        .returning(move |_, _, _, _| {
+            *apply_time_clone.lock() = Some(time_service_clone.now_unix_time());
+            Ok(())
+        });
+
+    // Create the continuous syncer
+    let mut continuous_syncer = create_continuous_syncer_with_components(
+        driver_configuration,
+        mock_streaming_client,
+        mock_storage_synchronizer,
+        current_synced_version,
+        current_synced_epoch,
+        time_service.clone(),
+    );
+
+    // Drive progress to initialize the transaction output stream
+    let no_sync_request = Arc::new(Mutex::new(None));
+    continuous_syncer
+        .drive_progress(no_sync_request.clone())
+        .await
+        .unwrap();
+
+    // Drive progress while no new data is committed by peers
+    for _ in 0..3 {
+        time_service
+            .clone()
+            .into_mock()
+            .advance(progress_check_interval);
+        let result = continuous_syncer
+            .drive_progress(no_sync_request.clone())
+            .await;
+        assert_eq!(result.is_ok(), is_subscription);
+    }
+
+    // Commit new data and send it along the streams
+    let commit_time = time_service.now_unix_time();
+    for notification_sender in [&notification_sender_1, &notification_sender_2] {
+        send_output_notification(notification_sender, current_synced_version + 1);
+    }
+
+    // Drive progress until the new data is applied to storage
+    while apply_time.lock().is_none() {
+        time_service
+            .clone()
+            .into_mock()
+            .advance(progress_check_interval);
+        continuous_syncer
+            .drive_progress(no_sync_request.clone())
+            .await
+            .unwrap();
+        continuous_syncer.wait_for_verified_chunks().await.unwrap();
+    }
+
+    // Return the notification latency
+    let apply_time = apply_time.lock().unwrap();
+    apply_time - commit_time
+}
+
+/// Creates a block metadata transaction with the given timestamp
+fn create_block_metadata_transaction(timestamp_usecs: u64) -> Transaction {
+    Transaction::BlockMetadata(BlockMetadata::new(
+        HashValue::random(),
+        0,
+        0,
+        vec![],
+        AccountAddress::random(),
+        vec![],
+        timestamp_usecs,
+    ))
+}
+
+/// A ledger info verifier that is slow to verify the ledger info
+/// at `SLOW_VERIFICATION_VERSION` (all ledger infos are valid).
+fn slow_ledger_info_verifier(
+    _epoch_state: &EpochState,
+    ledger_info_with_signatures: &LedgerInfoWithSignatures,
+) -> Result<(), Error> {
+    if ledger_info_with_signatures.ledger_info().version() == SLOW_VERIFICATION_VERSION {
+        std::thread::sleep(Duration::from_millis(SLOW_VERIFICATION_MS));
+    }
+    Ok(())
+}
+
+/// Sends the given transactions (starting at the specified version) along the
+/// stream. If `continuous` is true, a continuous transaction payload is sent.
+fn send_transaction_notification(
+    notification_sender: &Sender<(), DataNotification>,
+    transactions: Vec<Transaction>,
+    start_version: Version,
+    proof_version: Version,
+    continuous: bool,
+) {
+    let transaction_list_with_proof = TransactionListWithProof::new(
+        transactions,
+        None,
+        Some(start_version),
+        create_transaction_info_list_with_proof(),
+    );
+    let data_payload = if continuous {
+        DataPayload::ContinuousTransactionsWithProof(
+            create_ledger_info_at_version(proof_version),
+            transaction_list_with_proof,
+        )
+    } else {
+        DataPayload::TransactionsWithProof(transaction_list_with_proof)
+    };
+    let data_notification = DataNotification {
+        notification_id: start_version,
+        data_payload,
+    };
+    notification_sender.push((), data_notification).unwrap();
+}
+
+/// Sends a single transaction output (at the given version) along the stream
+fn send_output_notification(
+    notification_sender: &Sender<(), DataNotification>,
+    output_version: Version,
+) {
+    let mut output_list_with_proof = create_output_list_with_proof();
+    output_list_with_proof.first_transaction_output_version = Some(output_version);
+    let data_notification = DataNotification {
+        notification_id: output_version,
+        data_payload: DataPayload::ContinuousTransactionOutputsWithProof(
+            create_ledger_info_at_version(output_version),
+            output_list_with_proof,
+        ),
+    };
+    notification_sender.push((), data_notification).unwrap();
+}
+
+/// Creates a continuous syncer (for testing) that syncs outputs with the given
+/// pipeline depth. Returns the syncer, the sender for the output stream and the
+/// number of pending and applied chunks (tracked by the storage synchronizer).
+fn create_pipelined_continuous_syncer(
+    max_pending_data_chunks: u64,
+    current_synced_version: Version,
+) -> (
+    ContinuousSyncer<MockStorageSynchronizer, MockStreamingClient>,
+    Sender<(), DataNotification>,
+    Arc<Mutex<u64>>,
+    Arc<Mutex<u64>>,
+) {
+    // Create a driver configuration with the given pipeline depth
+    let current_synced_epoch = 0;
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.continuous_syncing_mode =
+        ContinuousSyncingMode::ApplyTransactionOutputs;
+    driver_configuration.config.max_pending_data_chunks = max_pending_data_chunks;
+
+    // Create the mock streaming client
+    let mut mock_streaming_client = create_mock_streaming_client();
+    let (notification_sender, data_stream_listener) = create_data_stream_listener();
+    mock_streaming_client
+        .expect_continuously_stream_transaction_outputs()
+        .times(1)
+        .with(
+            eq(current_synced_version),
+            eq(current_synced_epoch),
+            eq(None),
+            always(),
+        )
+        .return_once(move |_, _, _, _| Ok(data_stream_listener));
+
+    // Create the mock storage synchronizer that tracks the pending chunks
+    let num_pending_chunks = Arc::new(Mutex::new(0));
+    let num_applied_chunks = Arc::new(Mutex::new(0));
+    let mut mock_storage_synchronizer = create_mock_storage_synchronizer();
+    mock_storage_synchronizer
+        .expect_reset_chunk_executor()
+        .return_const(Ok(()));
+    mock_storage_synchronizer
+        .expect_get_chunk_latency_average()
+        .return_const(ChunkLatencyAverage::default());
+    let pending_chunks = num_pending_chunks.clone();
+    mock_storage_synchronizer
+        .expect_pending_storage_data()
+        .returning(move || *pending_chunks.lock() > 0);
+    let pending_chunks = num_pending_chunks.clone();
+    mock_storage_synchronizer
+        .expect_pending_data_limit_reached()
+        .returning(move || *pending_chunks.lock() >= max_pending_data_chunks);
+    let pending_chunks = num_pending_chunks.clone();
+    mock_storage_synchronizer
+        .expect_get_num_pending_data_chunks()
+        .returning(move || *pending_chunks.lock());
+    let (pending_chunks, applied_chunks) = (num_pending_chunks.clone(), num_applied_chunks.clone());
+    mock_storage_synchronizer
+        .expect_apply_transaction_outputs()
+        .returning(move |_, _, _, _| {
+            *pending_chunks.lock() += 1;
+            *applied_chunks.lock() += 1;
+            Ok(())
+        });
+
+    // Create the continuous syncer
+    let continuous_syncer = create_continuous_syncer_with_components(
+        driver_configuration,
+        mock_streaming_client,
+        mock_storage_synchronizer,
+        current_synced_version,
+        current_synced_epoch,
+        TimeService::mock(),
+    );
+
+    (
+        continuous_syncer,
+        notification_sender,
+        num_pending_chunks,
+        num_applied_chunks,
+    )
+}
+
 /// Creates a continuous syncer for testing
 fn create_continuous_syncer(
     driver_configuration: DriverConfiguration,
@@ -276,12 +1346,31 @@ fn create_continuous_syncer(
     synced_version: Version,
     current_epoch: Epoch,
 ) -> ContinuousSyncer<MockStorageSynchronizer, MockStreamingClient> {
-    // Initialize the logger for tests
-    aptos_logger::Logger::init_for_testing();
-
     // Create the mock storage synchronizer
     let mock_storage_synchronizer = create_ready_storage_synchronizer(expect_reset_executor);
 
+    create_continuous_syncer_with_components(
+        driver_configuration,
+        mock_streaming_client,
+        mock_storage_synchronizer,
+        synced_version,
+        current_epoch,
+        TimeService::mock(),
+    )
+}
+
+/// Creates a continuous syncer for testing with the given components
+fn create_continuous_syncer_with_components(
+    driver_configuration: DriverConfiguration,
+    mock_streaming_client: MockStreamingClient,
+    mock_storage_synchronizer: MockStorageSynchronizer,
+    synced_version: Version,
+    current_epoch: Epoch,
+    time_service: TimeService,
+) -> ContinuousSyncer<MockStorageSynchronizer, MockStreamingClient> {
+    // Initialize the logger for tests
+    aptos_logger::Logger::init_for_testing();
+
     // Create the mock db reader with the given synced version
     let mut mock_database_reader = create_mock_db_reader();
     mock_database_reader
@@ -296,5 +1385,45 @@ fn create_continuous_syncer(
         mock_streaming_client,
         Arc::new(mock_database_reader),
         mock_storage_synchronizer,
+        time_service,
+    )
+}
+
+/// Creates a continuous syncer (for testing) that executes transactions. The
+/// synced version (in storage) is read from the given shared version.
+fn create_timestamp_target_syncer(
+    mock_streaming_client: MockStreamingClient,
+    mock_storage_synchronizer: MockStorageSynchronizer,
+    synced_version: Arc<Mutex<Version>>,
+) -> ContinuousSyncer<MockStorageSynchronizer, MockStreamingClient> {
+    // Initialize the logger for tests
+    aptos_logger::Logger::init_for_testing();
+
+    // Create a driver configuration
+    let mut driver_configuration = create_full_node_driver_configuration();
+    driver_configuration.config.continuous_syncing_mode =
+        ContinuousSyncingMode::ExecuteTransactions;
+    driver_configuration
+        .config
+        .max_consecutive_stream_notifications = 1;
+
+    // Create the mock db reader with the shared synced version
+    let mut mock_database_reader = create_mock_db_reader();
+    mock_database_reader
+        .expect_get_latest_transaction_info_option()
+        .returning(move || Ok(Some((*synced_version.lock(), create_transaction_info()))));
+    mock_database_reader
+        .expect_get_latest_epoch_state()
+        .returning(|| Ok(create_epoch_state(0)));
+    mock_database_reader
+        .expect_get_latest_ledger_info()
+        .returning(|| Ok(create_ledger_info_at_version(0)));
+
+    ContinuousSyncer::new(
+        driver_configuration,
+        mock_streaming_client,
+        Arc::new(mock_database_reader),
+        mock_storage_synchronizer,
+        TimeService::mock(),
     )
 }
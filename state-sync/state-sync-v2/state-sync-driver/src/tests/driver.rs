@@ -2,43 +2,82 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
+    bootstrap_progress::{BootstrapProgress, BootstrapProgressNotifier},
+    driver::{DriverConfiguration, StateSyncDriver},
+    driver_client::{ClientNotificationListener, DriverClient},
     driver_factory::DriverFactory,
+    metadata_storage::{MetadataStorageInterface, PersistentMetadataStorage},
+    notification_handlers::{
+        CommitNotificationListener, ConsensusNotificationHandler, ErrorNotificationListener,
+        MempoolNotificationHandler,
+    },
+    storage_synchronizer::StorageSynchronizer,
+    sync_lag::SyncLagMonitor,
+    sync_status::SyncStatusHandle,
     tests::utils::{
-        create_event, create_ledger_info_at_version, create_transaction,
-        verify_mempool_and_event_notification,
+        create_data_stream_listener, create_event, create_global_summary,
+        create_ledger_info_at_version, create_transaction, verify_mempool_and_event_notification,
     },
 };
-use aptos_config::config::{NodeConfig, RoleType};
-use aptos_data_client::aptosnet::AptosNetDataClient;
-use aptos_infallible::RwLock;
+use aptos_config::config::{BootstrappingMode, NodeConfig, RoleType};
+use aptos_data_client::{
+    aptosnet::AptosNetDataClient, AptosDataClient, GlobalDataSummary, Response,
+};
+use aptos_infallible::{Mutex, RwLock};
+use aptos_temppath::TempPath;
 use aptos_time_service::TimeService;
 use aptos_types::{
     event::EventKey,
+    ledger_info::LedgerInfoWithSignatures,
     move_resource::MoveStorage,
     on_chain_config::{new_epoch_event_key, ON_CHAIN_CONFIG_REGISTRY},
-    transaction::{Transaction, WriteSetPayload},
+    state_store::state_value::StateValueChunkWithProof,
+    transaction::{
+        Transaction, TransactionListWithProof, TransactionOutputListWithProof, Version,
+        WriteSetPayload,
+    },
     waypoint::Waypoint,
 };
 use aptos_vm::AptosVM;
 use aptosdb::AptosDB;
+use async_trait::async_trait;
+use channel::aptos_channel::Sender;
 use claim::{assert_err, assert_none};
 use consensus_notifications::{ConsensusNotificationSender, ConsensusNotifier};
-use data_streaming_service::streaming_client::new_streaming_service_client_listener_pair;
+use data_streaming_service::{
+    data_notification::{DataNotification, DataPayload, NotificationId},
+    data_stream::DataStreamListener,
+    streaming_client::{
+        new_streaming_service_client_listener_pair, DataStreamingClient, Epoch,
+        NotificationFeedback,
+    },
+};
 use event_notifications::{
     EventNotificationListener, EventNotificationSender, EventSubscriptionService,
     ReconfigNotificationListener,
 };
 use executor::chunk_executor::ChunkExecutor;
-use executor_test_helpers::bootstrap_genesis;
-use futures::{FutureExt, StreamExt};
+use executor_test_helpers::{bootstrap_genesis, integration_test_impl};
+use futures::{channel::mpsc, FutureExt, StreamExt};
 use mempool_notifications::MempoolNotificationListener;
 use network::application::{interface::MultiNetworkSender, storage::PeerMetadataStorage};
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use storage_interface::{DbReader, DbReaderWriter};
 use storage_service_client::StorageServiceClient;
+use tokio::runtime::{Builder, Runtime};
 
 // TODO(joshlind): extend these tests to cover more functionality!
 
+// The max time (secs) to wait for a node to bootstrap without any peers
+const MAX_BOOTSTRAP_WAIT_SECS: u64 = 10;
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_auto_bootstrapping() {
     // Create a driver for a validator with a waypoint at version 0
@@ -49,6 +88,49 @@ async fn test_auto_bootstrapping() {
     driver_client.notify_once_bootstrapped().await.unwrap();
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_bootstrap_progress_notifications() {
+    // Create a driver for a validator with a waypoint at version 0
+    let (validator_driver, _, _, _, _) = create_validator_driver(None).await;
+
+    // Subscribe to the bootstrap progress notifications
+    let driver_client = validator_driver.create_driver_client();
+    let mut bootstrap_progress_receiver = driver_client.subscribe_to_bootstrap_progress();
+
+    // Wait until the validator is bootstrapped
+    driver_client.notify_once_bootstrapped().await.unwrap();
+
+    // Verify the final notification is sent and the channel is closed
+    while bootstrap_progress_receiver.changed().await.is_ok() {}
+    assert_eq!(
+        *bootstrap_progress_receiver.borrow(),
+        BootstrapProgress::complete()
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_genesis_only_bootstrapping() {
+    // Create a driver for a full node with genesis-only bootstrapping enabled
+    let mut node_config = NodeConfig::default();
+    node_config.base.role = RoleType::FullNode;
+    node_config
+        .state_sync
+        .state_sync_driver
+        .enable_genesis_only_bootstrapping = true;
+    let (full_node_driver, _, _, _, _) =
+        create_driver_for_tests(node_config, Waypoint::default(), None).await;
+
+    // Verify the node bootstraps (without any peers) in bounded time
+    let driver_client = full_node_driver.create_driver_client();
+    tokio::time::timeout(
+        Duration::from_secs(MAX_BOOTSTRAP_WAIT_SECS),
+        driver_client.notify_once_bootstrapped(),
+    )
+    .await
+    .expect("The node should have bootstrapped from genesis!")
+    .unwrap();
+}
+
 #[tokio::test]
 async fn test_consensus_commit_notification() {
     // Create a driver for a full node
@@ -182,6 +264,160 @@ async fn test_consensus_sync_request() {
     assert_err!(result);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_bootstrap_from_state_snapshot() {
+    // Create the source and target databases
+    let (
+        source_db,
+        target_ledger_info,
+        db_path,
+        db,
+        db_rw,
+        driver_configuration,
+        aptos_data_client,
+    ) = create_state_snapshot_environment();
+
+    // Create the metadata storage for the target
+    let metadata_storage = PersistentMetadataStorage::new(db_path.path());
+
+    // Create the driver and wait for it to bootstrap
+    let driver_client = spawn_snapshot_driver(
+        db_rw,
+        db.clone(),
+        driver_configuration,
+        metadata_storage.clone(),
+        aptos_data_client,
+        SnapshotStreamingClient::new(source_db.clone(), None),
+        None,
+    );
+    driver_client.notify_once_bootstrapped().await.unwrap();
+
+    // Verify the target database was synced to the snapshot
+    verify_state_snapshot_synced(source_db, db, &metadata_storage, &target_ledger_info);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_bootstrap_to_target_version() {
+    // Create the source and target databases
+    let (
+        source_db,
+        target_ledger_info,
+        db_path,
+        db,
+        db_rw,
+        mut driver_configuration,
+        aptos_data_client,
+    ) = create_state_snapshot_environment();
+
+    // Choose a target version that falls in the middle of the last epoch
+    let epoch_ending_ledger_infos = source_db
+        .get_epoch_ending_ledger_infos(0, target_ledger_info.ledger_info().next_block_epoch())
+        .unwrap()
+        .ledger_info_with_sigs;
+    let epoch_start_version = epoch_ending_ledger_infos[epoch_ending_ledger_infos.len() - 2]
+        .ledger_info()
+        .version();
+    let epoch_end_version = target_ledger_info.ledger_info().version();
+    let target_version = (epoch_start_version + epoch_end_version) / 2;
+    assert!(epoch_start_version < target_version && target_version < epoch_end_version);
+
+    // Bootstrap by applying transaction outputs up to the target version
+    driver_configuration.config.bootstrapping_mode =
+        BootstrappingMode::ApplyTransactionOutputsFromGenesis;
+    driver_configuration.config.bootstrapping_target_version = Some(target_version);
+
+    // Create the driver and wait for it to reach the target
+    let driver_client = spawn_snapshot_driver(
+        db_rw,
+        db.clone(),
+        driver_configuration,
+        PersistentMetadataStorage::new(db_path.path()),
+        aptos_data_client,
+        SnapshotStreamingClient::new(source_db, None),
+        None,
+    );
+    assert_eq!(
+        driver_client.notify_once_target_reached().await,
+        Ok(target_version)
+    );
+
+    // Verify the target database was synced exactly to the target version
+    let (synced_version, _) = db.get_latest_transaction_info_option().unwrap().unwrap();
+    assert_eq!(synced_version, target_version);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_bootstrap_from_interrupted_state_snapshot() {
+    // Create the source and target databases
+    let (
+        source_db,
+        target_ledger_info,
+        db_path,
+        db,
+        db_rw,
+        driver_configuration,
+        aptos_data_client,
+    ) = create_state_snapshot_environment();
+
+    // Create the metadata storage for the target
+    let metadata_storage = PersistentMetadataStorage::new(db_path.path());
+
+    // Create a driver (on a dedicated runtime) that only receives two state value chunks
+    let runtime = Builder::new_multi_thread().enable_all().build().unwrap();
+    let _driver_client = spawn_snapshot_driver(
+        db_rw.clone(),
+        db.clone(),
+        driver_configuration.clone(),
+        metadata_storage.clone(),
+        aptos_data_client.clone(),
+        SnapshotStreamingClient::new(source_db.clone(), Some(2)),
+        Some(&runtime),
+    );
+
+    // Wait until both chunks have been persisted and kill the driver
+    let last_persisted_index = 19;
+    loop {
+        if let Some(target_ledger_info) = metadata_storage.previous_snapshot_sync_target().unwrap()
+        {
+            if let Ok(index) =
+                metadata_storage.get_last_persisted_state_value_index(&target_ledger_info)
+            {
+                if index >= last_persisted_index {
+                    break;
+                }
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    runtime.shutdown_background();
+    assert!(!metadata_storage
+        .is_snapshot_sync_complete(&target_ledger_info)
+        .unwrap());
+
+    // Restart the driver and wait for it to bootstrap
+    let streaming_client = SnapshotStreamingClient::new(source_db.clone(), None);
+    let driver_client = spawn_snapshot_driver(
+        db_rw,
+        db.clone(),
+        driver_configuration,
+        metadata_storage.clone(),
+        aptos_data_client,
+        streaming_client.clone(),
+        None,
+    );
+    driver_client.notify_once_bootstrapped().await.unwrap();
+
+    // Verify the restarted driver resumed the snapshot sync (instead of starting again)
+    let requested_state_indices = streaming_client.requested_state_indices.lock().clone();
+    assert!(!requested_state_indices.is_empty());
+    for start_index in requested_state_indices {
+        assert!(start_index >= last_persisted_index);
+    }
+
+    // Verify the target database was synced to the snapshot
+    verify_state_snapshot_synced(source_db, db, &metadata_storage, &target_ledger_info);
+}
+
 /// Creates a state sync driver for a validator node
 async fn create_validator_driver(
     event_key_subscriptions: Option<Vec<EventKey>>,
@@ -227,7 +463,7 @@ async fn create_driver_for_tests(
     EventNotificationListener,
 ) {
     // Create test aptos database
-    let db_path = aptos_temppath::TempPath::new();
+    let db_path = TempPath::new();
     db_path.create_as_dir().unwrap();
     let (db, db_rw) = DbReaderWriter::wrap(AptosDB::new_for_test(db_path.path()));
 
@@ -284,6 +520,9 @@ async fn create_driver_for_tests(
         None,
     );
 
+    // Create the metadata storage
+    let metadata_storage = PersistentMetadataStorage::new(db_path.path());
+
     // Create and spawn the driver
     let driver_factory = DriverFactory::create_and_spawn_driver(
         false,
@@ -292,6 +531,7 @@ async fn create_driver_for_tests(
         db_rw,
         chunk_executor,
         mempool_notifier,
+        metadata_storage,
         consensus_listener,
         event_subscription_service,
         aptos_data_client,
@@ -306,3 +546,412 @@ async fn create_driver_for_tests(
         event_subscriber,
     )
 }
+
+/// Creates and spawns a state sync driver (for a full node) that bootstraps
+/// using the given data client and streaming client. Returns a driver client.
+/// Creates a source database (with several committed blocks and epochs), the
+/// epoch ending target to sync to and a fresh target database (bootstrapped
+/// with the same genesis).
+fn create_state_snapshot_environment() -> (
+    Arc<dyn DbReader>,
+    LedgerInfoWithSignatures,
+    TempPath,
+    Arc<AptosDB>,
+    DbReaderWriter,
+    DriverConfiguration,
+    SnapshotDataClient,
+) {
+    // Create a source database with several committed blocks and epochs
+    let source_db: Arc<dyn DbReader> = integration_test_impl::test_execution_with_storage_impl();
+    let latest_ledger_info = source_db.get_latest_ledger_info().unwrap();
+    let epoch_ending_ledger_infos = source_db
+        .get_epoch_ending_ledger_infos(0, latest_ledger_info.ledger_info().next_block_epoch())
+        .unwrap()
+        .ledger_info_with_sigs;
+    let target_ledger_info = epoch_ending_ledger_infos.last().unwrap().clone();
+
+    // Create a fresh target database (bootstrapped with the same genesis)
+    let db_path = TempPath::new();
+    db_path.create_as_dir().unwrap();
+    let (db, db_rw) = DbReaderWriter::wrap(AptosDB::new_for_test(db_path.path()));
+    let (genesis, _) = vm_genesis::test_genesis_change_set_and_validators(Some(1));
+    let genesis_txn = Transaction::GenesisTransaction(WriteSetPayload::Direct(genesis));
+    bootstrap_genesis::<AptosVM>(&db_rw, &genesis_txn).unwrap();
+
+    // Create a driver configuration that bootstraps by downloading states
+    let mut node_config = NodeConfig::default();
+    node_config.base.role = RoleType::FullNode;
+    node_config.state_sync.state_sync_driver.bootstrapping_mode =
+        BootstrappingMode::DownloadLatestStates;
+    let driver_configuration = DriverConfiguration::new(
+        node_config.state_sync.state_sync_driver,
+        node_config.base.role,
+        Waypoint::default(),
+    );
+
+    // Create a data client that advertises the source data
+    let mut global_data_summary = create_global_summary(target_ledger_info.ledger_info().epoch());
+    global_data_summary.advertised_data.synced_ledger_infos = vec![latest_ledger_info];
+    let aptos_data_client = SnapshotDataClient {
+        global_data_summary,
+    };
+
+    (
+        source_db,
+        target_ledger_info,
+        db_path,
+        db,
+        db_rw,
+        driver_configuration,
+        aptos_data_client,
+    )
+}
+
+/// Verifies that the target database was synced to the state snapshot at the
+/// given target and that the snapshot sync was marked as complete.
+fn verify_state_snapshot_synced(
+    source_db: Arc<dyn DbReader>,
+    db: Arc<AptosDB>,
+    metadata_storage: &PersistentMetadataStorage,
+    target_ledger_info: &LedgerInfoWithSignatures,
+) {
+    // Verify the target database was synced to the snapshot
+    let target_version = target_ledger_info.ledger_info().version();
+    let synced_ledger_info = db.get_latest_ledger_info().unwrap();
+    assert_eq!(synced_ledger_info.ledger_info().version(), target_version);
+    assert_eq!(
+        db.get_latest_state_checkpoint_version().unwrap(),
+        Some(target_version)
+    );
+    assert_eq!(
+        db.get_state_leaf_count(target_version).unwrap(),
+        source_db.get_state_leaf_count(target_version).unwrap()
+    );
+
+    // Verify the snapshot sync was marked as complete
+    assert_eq!(
+        metadata_storage.previous_snapshot_sync_target().unwrap(),
+        Some(target_ledger_info.clone())
+    );
+    assert!(metadata_storage
+        .is_snapshot_sync_complete(target_ledger_info)
+        .unwrap());
+}
+
+fn spawn_snapshot_driver(
+    db_rw: DbReaderWriter,
+    storage: Arc<dyn DbReader>,
+    driver_configuration: DriverConfiguration,
+    metadata_storage: PersistentMetadataStorage,
+    aptos_data_client: SnapshotDataClient,
+    streaming_client: SnapshotStreamingClient,
+    runtime: Option<&Runtime>,
+) -> DriverClient {
+    // Create the notification handlers
+    let (client_notification_sender, client_notification_receiver) = mpsc::unbounded();
+    let client_notification_listener =
+        ClientNotificationListener::new(client_notification_receiver);
+    let (commit_notification_sender, commit_notification_listener) =
+        CommitNotificationListener::new();
+    let (_, consensus_listener) = consensus_notifications::new_consensus_notifier_listener_pair(0);
+    let consensus_notification_handler = ConsensusNotificationHandler::new(
+        consensus_listener,
+        driver_configuration.config.sync_request_timeout_ms,
+        TimeService::mock(),
+    );
+    let (error_notification_sender, error_notification_listener) = ErrorNotificationListener::new();
+    let (mempool_notifier, _) = mempool_notifications::new_mempool_notifier_listener_pair();
+    let mempool_notification_handler = MempoolNotificationHandler::new(mempool_notifier);
+
+    // Create the bootstrap progress notifier
+    let (bootstrap_progress_notifier, bootstrap_progress_receiver) =
+        BootstrapProgressNotifier::new();
+
+    // Create the storage synchronizer
+    let event_subscription_service = Arc::new(Mutex::new(EventSubscriptionService::new(
+        ON_CHAIN_CONFIG_REGISTRY,
+        Arc::new(RwLock::new(db_rw.clone())),
+    )));
+    let (storage_synchronizer, _, _) = StorageSynchronizer::new(
+        driver_configuration.config,
+        bootstrap_progress_notifier.clone(),
+        Arc::new(ChunkExecutor::<AptosVM>::new(db_rw.clone())),
+        commit_notification_sender,
+        error_notification_sender,
+        event_subscription_service.clone(),
+        mempool_notification_handler.clone(),
+        metadata_storage.clone(),
+        db_rw,
+        runtime,
+    );
+
+    // Create and spawn the driver
+    let (sync_lag_monitor, _) = SyncLagMonitor::new(&driver_configuration.config);
+    let state_sync_driver = StateSyncDriver::new(
+        bootstrap_progress_notifier,
+        client_notification_listener,
+        commit_notification_listener,
+        consensus_notification_handler,
+        driver_configuration,
+        error_notification_listener,
+        event_subscription_service,
+        mempool_notification_handler,
+        metadata_storage,
+        storage_synchronizer,
+        aptos_data_client,
+        streaming_client,
+        storage,
+        sync_lag_monitor,
+        SyncStatusHandle::new(),
+        TimeService::mock(),
+    );
+    if let Some(runtime) = runtime {
+        runtime.spawn(state_sync_driver.start_driver());
+    } else {
+        tokio::spawn(state_sync_driver.start_driver());
+    }
+
+    DriverClient::new(bootstrap_progress_receiver, client_notification_sender)
+}
+
+/// A simple data client that only advertises a fixed global data summary
+#[derive(Clone)]
+struct SnapshotDataClient {
+    global_data_summary: GlobalDataSummary,
+}
+
+#[async_trait]
+impl AptosDataClient for SnapshotDataClient {
+    fn get_global_data_summary(&self) -> GlobalDataSummary {
+        self.global_data_summary.clone()
+    }
+
+    async fn get_epoch_ending_ledger_infos(
+        &self,
+        _start_epoch: Epoch,
+        _expected_end_epoch: Epoch,
+    ) -> aptos_data_client::Result<Response<Vec<LedgerInfoWithSignatures>>> {
+        unimplemented!()
+    }
+
+    async fn get_new_transaction_outputs_with_proof(
+        &self,
+        _known_version: Version,
+        _known_epoch: Epoch,
+    ) -> aptos_data_client::Result<
+        Response<(TransactionOutputListWithProof, LedgerInfoWithSignatures)>,
+    > {
+        unimplemented!()
+    }
+
+    async fn get_new_transactions_with_proof(
+        &self,
+        _known_version: Version,
+        _known_epoch: Epoch,
+        _include_events: bool,
+    ) -> aptos_data_client::Result<Response<(TransactionListWithProof, LedgerInfoWithSignatures)>>
+    {
+        unimplemented!()
+    }
+
+    async fn get_number_of_states(
+        &self,
+        _version: Version,
+    ) -> aptos_data_client::Result<Response<u64>> {
+        unimplemented!()
+    }
+
+    async fn get_state_values_with_proof(
+        &self,
+        _version: u64,
+        _start_index: u64,
+        _end_index: u64,
+    ) -> aptos_data_client::Result<Response<StateValueChunkWithProof>> {
+        unimplemented!()
+    }
+
+    async fn get_transaction_outputs_with_proof(
+        &self,
+        _proof_version: Version,
+        _start_version: Version,
+        _end_version: Version,
+    ) -> aptos_data_client::Result<Response<TransactionOutputListWithProof>> {
+        unimplemented!()
+    }
+
+    async fn get_transactions_with_proof(
+        &self,
+        _proof_version: Version,
+        _start_version: Version,
+        _end_version: Version,
+        _include_events: bool,
+    ) -> aptos_data_client::Result<Response<TransactionListWithProof>> {
+        unimplemented!()
+    }
+}
+
+/// A simple streaming client that serves all bootstrapping streams directly
+/// from a source database (each stream ends with an end of stream notification).
+/// If a maximum number of state value chunks is given, state value streams are
+/// truncated and never end (i.e., the snapshot sync stalls).
+#[derive(Clone)]
+struct SnapshotStreamingClient {
+    max_state_chunks: Option<usize>,
+    next_notification_id: Arc<AtomicU64>,
+    notification_senders: Arc<Mutex<Vec<Sender<(), DataNotification>>>>,
+    requested_state_indices: Arc<Mutex<Vec<u64>>>,
+    source_db: Arc<dyn DbReader>,
+}
+
+impl SnapshotStreamingClient {
+    fn new(source_db: Arc<dyn DbReader>, max_state_chunks: Option<usize>) -> Self {
+        Self {
+            max_state_chunks,
+            next_notification_id: Arc::new(AtomicU64::new(0)),
+            notification_senders: Arc::new(Mutex::new(vec![])),
+            requested_state_indices: Arc::new(Mutex::new(vec![])),
+            source_db,
+        }
+    }
+
+    /// Creates a new data stream containing the given payloads
+    fn create_data_stream(
+        &self,
+        data_payloads: Vec<DataPayload>,
+        end_of_stream: bool,
+    ) -> Result<DataStreamListener, data_streaming_service::error::Error> {
+        let (notification_sender, data_stream_listener) = create_data_stream_listener();
+        let end_of_stream = end_of_stream.then(|| DataPayload::EndOfStream);
+        for data_payload in data_payloads.into_iter().chain(end_of_stream) {
+            let notification_id = self.next_notification_id.fetch_add(1, Ordering::Relaxed);
+            let data_notification = DataNotification {
+                notification_id,
+                data_payload,
+            };
+            notification_sender.push((), data_notification).unwrap();
+        }
+
+        // Keep the sender alive so that the stream isn't closed
+        self.notification_senders.lock().push(notification_sender);
+        Ok(data_stream_listener)
+    }
+}
+
+#[async_trait]
+impl DataStreamingClient for SnapshotStreamingClient {
+    async fn get_all_state_values(
+        &self,
+        version: Version,
+        start_index: Option<u64>,
+    ) -> Result<DataStreamListener, data_streaming_service::error::Error> {
+        let start_index = start_index.unwrap_or(0);
+        self.requested_state_indices.lock().push(start_index);
+
+        let chunk_size = 10;
+        let num_state_values = self.source_db.get_state_leaf_count(version).unwrap();
+        let mut data_payloads = vec![];
+        let mut next_index = start_index as usize;
+        while next_index < num_state_values {
+            if Some(data_payloads.len()) == self.max_state_chunks {
+                return self.create_data_stream(data_payloads, false);
+            }
+            let state_value_chunk = self
+                .source_db
+                .get_state_value_chunk_with_proof(version, next_index, chunk_size)
+                .unwrap();
+            next_index = state_value_chunk.last_index as usize + 1;
+            data_payloads.push(DataPayload::StateValuesWithProof(state_value_chunk));
+        }
+        self.create_data_stream(data_payloads, true)
+    }
+
+    async fn get_all_epoch_ending_ledger_infos(
+        &self,
+        start_epoch: Epoch,
+    ) -> Result<DataStreamListener, data_streaming_service::error::Error> {
+        let latest_ledger_info = self.source_db.get_latest_ledger_info().unwrap();
+        let epoch_ending_ledger_infos = self
+            .source_db
+            .get_epoch_ending_ledger_infos(
+                start_epoch,
+                latest_ledger_info.ledger_info().next_block_epoch(),
+            )
+            .unwrap()
+            .ledger_info_with_sigs;
+        self.create_data_stream(
+            vec![DataPayload::EpochEndingLedgerInfos(
+                epoch_ending_ledger_infos,
+            )],
+            true,
+        )
+    }
+
+    async fn get_all_transaction_outputs(
+        &self,
+        start_version: Version,
+        end_version: Version,
+        proof_version: Version,
+    ) -> Result<DataStreamListener, data_streaming_service::error::Error> {
+        let transaction_outputs_with_proof = self
+            .source_db
+            .get_transaction_outputs(
+                start_version,
+                end_version - start_version + 1,
+                proof_version,
+            )
+            .unwrap();
+        self.create_data_stream(
+            vec![DataPayload::TransactionOutputsWithProof(
+                transaction_outputs_with_proof,
+            )],
+            true,
+        )
+    }
+
+    async fn get_all_transactions(
+        &self,
+        _start_version: Version,
+        _end_version: Version,
+        _proof_version: Version,
+        _include_events: bool,
+    ) -> Result<DataStreamListener, data_streaming_service::error::Error> {
+        unimplemented!()
+    }
+
+    async fn continuously_stream_transaction_outputs(
+        &self,
+        _start_version: Version,
+        _start_epoch: Epoch,
+        _target: Option<LedgerInfoWithSignatures>,
+        _max_chunk_size: Option<u64>,
+    ) -> Result<DataStreamListener, data_streaming_service::error::Error> {
+        Err(
+            data_streaming_service::error::Error::UnsupportedRequestEncountered(
+                "Continuous syncing is not supported by the snapshot streaming client!".into(),
+            ),
+        )
+    }
+
+    async fn continuously_stream_transactions(
+        &self,
+        _start_version: Version,
+        _start_epoch: Epoch,
+        _include_events: bool,
+        _target: Option<LedgerInfoWithSignatures>,
+        _max_chunk_size: Option<u64>,
+    ) -> Result<DataStreamListener, data_streaming_service::error::Error> {
+        Err(
+            data_streaming_service::error::Error::UnsupportedRequestEncountered(
+                "Continuous syncing is not supported by the snapshot streaming client!".into(),
+            ),
+        )
+    }
+
+    async fn terminate_stream_with_feedback(
+        &self,
+        _notification_id: NotificationId,
+        _notification_feedback: NotificationFeedback,
+    ) -> Result<(), data_streaming_service::error::Error> {
+        Ok(())
+    }
+}
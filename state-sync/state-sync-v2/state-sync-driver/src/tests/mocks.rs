@@ -3,9 +3,12 @@
 
 use crate::tests::utils::{create_empty_epoch_state, create_epoch_ending_ledger_info};
 use crate::{
-    storage_synchronizer::StorageSynchronizerInterface, tests::utils::create_transaction_info,
+    metadata_storage::MetadataStorageInterface,
+    storage_synchronizer::{ChunkLatencyAverage, StorageSynchronizerInterface},
+    tests::utils::create_transaction_info,
 };
 use anyhow::Result;
+use aptos_config::config::BootstrappingMode;
 use aptos_crypto::HashValue;
 use aptos_types::epoch_state::EpochState;
 use aptos_types::{
@@ -34,7 +37,7 @@ use data_streaming_service::{
     data_stream::DataStreamListener,
     streaming_client::{DataStreamingClient, Epoch, NotificationFeedback},
 };
-use executor_types::{ChunkCommitNotification, ChunkExecutorTrait};
+use executor_types::{ChunkCommitNotification, ChunkExecutorTrait, ChunkStageTimings};
 use mockall::mock;
 use std::sync::Arc;
 use storage_interface::{
@@ -84,6 +87,32 @@ pub fn create_mock_reader_writer(
     }
 }
 
+/// Creates a mock metadata storage
+pub fn create_mock_metadata_storage() -> MockMetadataStorage {
+    MockMetadataStorage::new()
+}
+
+/// Creates a mock metadata storage that has never seen a state snapshot sync
+pub fn create_empty_metadata_storage() -> MockMetadataStorage {
+    let mut metadata_storage = create_mock_metadata_storage();
+    metadata_storage
+        .expect_previous_snapshot_sync_target()
+        .returning(|| Ok(None));
+    metadata_storage
+}
+
+/// Creates a mock metadata storage (and clones) that accept all progress updates
+pub fn create_ready_metadata_storage() -> MockMetadataStorage {
+    let mut metadata_storage = create_mock_metadata_storage();
+    metadata_storage
+        .expect_update_last_persisted_state_value_index()
+        .returning(|_, _, _| Ok(()));
+    metadata_storage
+        .expect_clone()
+        .returning(create_ready_metadata_storage);
+    metadata_storage
+}
+
 /// Creates a mock state snapshot receiver
 pub fn create_mock_receiver() -> MockSnapshotReceiver {
     MockSnapshotReceiver::new()
@@ -106,6 +135,15 @@ pub fn create_ready_storage_synchronizer(expect_reset_executor: bool) -> MockSto
     mock_storage_synchronizer
         .expect_pending_storage_data()
         .return_const(false);
+    mock_storage_synchronizer
+        .expect_pending_data_limit_reached()
+        .return_const(false);
+    mock_storage_synchronizer
+        .expect_get_chunk_latency_average()
+        .return_const(ChunkLatencyAverage::default());
+    mock_storage_synchronizer
+        .expect_get_num_pending_data_chunks()
+        .return_const(0u64);
     if expect_reset_executor {
         mock_storage_synchronizer
             .expect_finish_chunk_executor()
@@ -155,6 +193,8 @@ mock! {
         fn reset(&self) -> Result<()>;
 
         fn finish(&self);
+
+        fn last_chunk_stage_timings(&self) -> Option<ChunkStageTimings>;
     }
 }
 
@@ -289,6 +329,11 @@ mock! {
         ) -> Result<StateValueChunkWithProof>;
 
         fn get_state_prune_window(&self) -> Result<Option<usize>>;
+
+        fn get_state_snapshot_before(
+            &self,
+            next_version: Version,
+        ) -> Result<Option<(Version, HashValue)>>;
     }
 }
 
@@ -324,6 +369,47 @@ mock! {
     }
 }
 
+// This automatically creates a MockMetadataStorage.
+mock! {
+    pub MetadataStorage {}
+    impl MetadataStorageInterface for MetadataStorage {
+        fn is_snapshot_sync_complete(
+            &self,
+            target_ledger_info: &LedgerInfoWithSignatures,
+        ) -> Result<bool, crate::error::Error>;
+
+        fn get_last_persisted_state_value_index(
+            &self,
+            target_ledger_info: &LedgerInfoWithSignatures,
+        ) -> Result<u64, crate::error::Error>;
+
+        fn previous_snapshot_sync_target(
+            &self,
+        ) -> Result<Option<LedgerInfoWithSignatures>, crate::error::Error>;
+
+        fn update_last_persisted_state_value_index(
+            &self,
+            target_ledger_info: &LedgerInfoWithSignatures,
+            last_persisted_state_value_index: u64,
+            snapshot_sync_completed: bool,
+        ) -> Result<(), crate::error::Error>;
+
+        fn delete_snapshot_sync_progress(&self) -> Result<(), crate::error::Error>;
+
+        fn get_selected_bootstrapping_mode(
+            &self,
+        ) -> Result<Option<BootstrappingMode>, crate::error::Error>;
+
+        fn update_selected_bootstrapping_mode(
+            &self,
+            bootstrapping_mode: BootstrappingMode,
+        ) -> Result<(), crate::error::Error>;
+    }
+    impl Clone for MetadataStorage {
+        fn clone(&self) -> Self;
+    }
+}
+
 // This automatically creates a MockSnapshotReceiver.
 mock! {
     pub SnapshotReceiver {}
@@ -372,6 +458,7 @@ mock! {
             start_version: Version,
             start_epoch: Epoch,
             target: Option<LedgerInfoWithSignatures>,
+            max_chunk_size: Option<u64>,
         ) -> Result<DataStreamListener, data_streaming_service::error::Error>;
 
         async fn continuously_stream_transactions(
@@ -380,6 +467,7 @@ mock! {
             start_epoch: Epoch,
             include_events: bool,
             target: Option<LedgerInfoWithSignatures>,
+            max_chunk_size: Option<u64>,
         ) -> Result<DataStreamListener, data_streaming_service::error::Error>;
 
         async fn terminate_stream_with_feedback(
@@ -422,6 +510,8 @@ mock! {
 
         fn pending_storage_data(&self) -> bool;
 
+        fn pending_data_limit_reached(&self) -> bool;
+
         fn save_state_values(
             &mut self,
             notification_id: NotificationId,
@@ -431,6 +521,10 @@ mock! {
         fn reset_chunk_executor(&self) -> Result<(), crate::error::Error>;
 
         fn finish_chunk_executor(&self);
+
+        fn get_chunk_latency_average(&self) -> ChunkLatencyAverage;
+
+        fn get_num_pending_data_chunks(&self) -> u64;
     }
     impl Clone for StorageSynchronizer {
         fn clone(&self) -> Self;
@@ -5,5 +5,7 @@ mod bootstrapper;
 mod continuous_syncer;
 mod driver;
 mod mocks;
+mod notification_handlers;
 mod storage_synchronizer;
+mod sync_lag;
 mod utils;
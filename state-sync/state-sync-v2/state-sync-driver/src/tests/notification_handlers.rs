@@ -0,0 +1,548 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    error::Error,
+    notification_handlers::{
+        CommitNotification, ConsensusNotificationHandler, MempoolNotificationHandler,
+    },
+    tests::{
+        mocks::create_mock_reader_writer,
+        utils::{
+            create_epoch_state, create_ledger_info_at_version, create_transaction,
+            verify_mempool_and_event_notification,
+        },
+    },
+};
+use aptos_crypto::{bls12381, HashValue, PrivateKey, Uniform};
+use aptos_infallible::{Mutex, RwLock};
+use aptos_time_service::TimeService;
+use aptos_types::{
+    account_address::AccountAddress,
+    block_info::BlockInfo,
+    contract_event::ContractEvent,
+    epoch_state::EpochState,
+    event::EventKey,
+    ledger_info::{LedgerInfo, LedgerInfoWithSignatures},
+    on_chain_config::ON_CHAIN_CONFIG_REGISTRY,
+    transaction::{Transaction, Version},
+    validator_verifier::ValidatorVerifier,
+};
+use claim::{assert_err, assert_matches, assert_ok};
+use consensus_notifications::{
+    ConsensusNotification, ConsensusNotificationSender, ConsensusNotifier,
+    ConsensusSyncNotification,
+};
+use event_notifications::EventSubscriptionService;
+use futures::{FutureExt, StreamExt};
+use mempool_notifications::{MempoolNotificationListener, MempoolNotifier};
+use move_deps::move_core_types::language_storage::TypeTag;
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
+
+#[tokio::test]
+async fn test_commit_notification_reconciliation() {
+    // Create the mempool notification handler and listener
+    let (mempool_notifier, mut mempool_listener) =
+        mempool_notifications::new_mempool_notifier_listener_pair();
+    let mempool_notification_handler = MempoolNotificationHandler::new(mempool_notifier);
+
+    // Create the transactions to commit (versions 101 to 112)
+    let transactions: Vec<Transaction> = (0..12).map(|_| create_transaction()).collect();
+
+    // Handle a syncer commit (versions 101 to 103) and verify mempool is notified
+    handle_and_verify_commit(
+        &mempool_notification_handler,
+        &mut mempool_listener,
+        transactions[0..3].to_vec(),
+        103,
+        transactions[0..3].to_vec(),
+    )
+    .await;
+
+    // Handle a consensus commit (versions 104 to 105) and verify mempool is notified
+    handle_and_verify_commit(
+        &mempool_notification_handler,
+        &mut mempool_listener,
+        transactions[3..5].to_vec(),
+        105,
+        transactions[3..5].to_vec(),
+    )
+    .await;
+
+    // Handle a duplicate consensus commit and verify it is dropped
+    handle_and_verify_dropped_commit(
+        &mempool_notification_handler,
+        &mut mempool_listener,
+        transactions[3..5].to_vec(),
+        105,
+    )
+    .await;
+
+    // Handle an out-of-order syncer commit and verify it is dropped
+    handle_and_verify_dropped_commit(
+        &mempool_notification_handler,
+        &mut mempool_listener,
+        transactions[0..3].to_vec(),
+        103,
+    )
+    .await;
+
+    // Handle an overlapping syncer commit (versions 104 to 108) and verify
+    // mempool is only notified of the new transactions.
+    handle_and_verify_commit(
+        &mempool_notification_handler,
+        &mut mempool_listener,
+        transactions[3..8].to_vec(),
+        108,
+        transactions[5..8].to_vec(),
+    )
+    .await;
+
+    // Handle a consensus commit from a clone of the handler (versions 109 to 110)
+    handle_and_verify_commit(
+        &mempool_notification_handler.clone(),
+        &mut mempool_listener,
+        transactions[8..10].to_vec(),
+        110,
+        transactions[8..10].to_vec(),
+    )
+    .await;
+
+    // Verify a duplicate commit is dropped by the original handler
+    handle_and_verify_dropped_commit(
+        &mempool_notification_handler,
+        &mut mempool_listener,
+        transactions[8..10].to_vec(),
+        110,
+    )
+    .await;
+
+    // Handle new transactions at the same version (e.g., if the synced version
+    // read from storage is stale) and verify mempool is still notified.
+    handle_and_verify_commit(
+        &mempool_notification_handler,
+        &mut mempool_listener,
+        transactions[10..12].to_vec(),
+        110,
+        transactions[10..12].to_vec(),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_commit_notification_event_reconciliation() {
+    // Create the mempool notification handler and listener
+    let (mempool_notifier, mut mempool_listener) =
+        mempool_notifications::new_mempool_notifier_listener_pair();
+    let mempool_notification_handler = MempoolNotificationHandler::new(mempool_notifier);
+
+    // Subscribe to the events of a single event key
+    let event_subscription_service = create_event_subscription_service();
+    let event_key = EventKey::random();
+    let mut event_listener = event_subscription_service
+        .lock()
+        .subscribe_to_events(vec![event_key])
+        .unwrap();
+
+    // Create the transactions to commit (versions 101 to 106) and their events
+    let transactions: Vec<Transaction> = (0..6).map(|_| create_transaction()).collect();
+    let events: Vec<ContractEvent> = (0..4)
+        .map(|sequence_number| create_event_at_sequence_number(event_key, sequence_number))
+        .collect();
+
+    // Handle a consensus commit (versions 101 to 103) and verify the events are delivered
+    let (result, _) = futures::join!(
+        handle_transaction_notification_with_events(
+            &mempool_notification_handler,
+            event_subscription_service.clone(),
+            events[0..2].to_vec(),
+            transactions[0..3].to_vec(),
+            103
+        ),
+        verify_mempool_and_event_notification(
+            Some(&mut event_listener),
+            &mut mempool_listener,
+            transactions[0..3].to_vec(),
+            events[0..2].to_vec()
+        )
+    );
+    result.unwrap();
+
+    // Handle an overlapping syncer commit (versions 101 to 106) and verify
+    // only the new events are delivered.
+    let (result, _) = futures::join!(
+        handle_transaction_notification_with_events(
+            &mempool_notification_handler,
+            event_subscription_service.clone(),
+            events.clone(),
+            transactions.clone(),
+            106
+        ),
+        verify_mempool_and_event_notification(
+            Some(&mut event_listener),
+            &mut mempool_listener,
+            transactions[3..6].to_vec(),
+            events[2..4].to_vec()
+        )
+    );
+    result.unwrap();
+
+    // Handle a duplicate commit and verify no events are delivered twice
+    handle_transaction_notification_with_events(
+        &mempool_notification_handler,
+        event_subscription_service.clone(),
+        events[2..4].to_vec(),
+        transactions[3..6].to_vec(),
+        106,
+    )
+    .await
+    .unwrap();
+    assert!(event_listener.select_next_some().now_or_never().is_none());
+    assert!(mempool_listener.select_next_some().now_or_never().is_none());
+}
+
+#[tokio::test]
+async fn test_sync_request_expired() {
+    // Create the consensus notification handler
+    let sync_request_timeout_ms = 1000;
+    let time_service = TimeService::mock();
+    let (consensus_notifier, mut consensus_notification_handler) =
+        create_consensus_notification_handler(sync_request_timeout_ms, time_service.clone());
+
+    // Initialize a sync request for a target ahead of the synced version
+    let synced_version = 100;
+    let (join_handle, sync_notification) = send_sync_request(
+        consensus_notifier,
+        &mut consensus_notification_handler,
+        create_ledger_info_at_version(synced_version + 10),
+    )
+    .await;
+    consensus_notification_handler
+        .initialize_sync_request(
+            sync_notification,
+            create_ledger_info_at_version(synced_version),
+            create_epoch_state(0),
+        )
+        .await
+        .unwrap();
+    assert!(consensus_notification_handler.active_sync_request());
+
+    // Verify the request is still active before the timeout elapses
+    consensus_notification_handler
+        .check_sync_request_progress(create_ledger_info_at_version(synced_version))
+        .await
+        .unwrap();
+    assert!(consensus_notification_handler.active_sync_request());
+
+    // Elapse the timeout and verify the request expires
+    time_service
+        .into_mock()
+        .advance(Duration::from_millis(sync_request_timeout_ms));
+    let error = consensus_notification_handler
+        .check_sync_request_progress(create_ledger_info_at_version(synced_version))
+        .await
+        .unwrap_err();
+    assert_matches!(error, Error::SyncRequestExpired(_));
+    assert!(!consensus_notification_handler.active_sync_request());
+
+    // Verify consensus was notified of the error
+    assert_err!(join_handle.await.unwrap());
+}
+
+#[tokio::test]
+async fn test_sync_request_stale_target() {
+    // Create the consensus notification handler
+    let (consensus_notifier, mut consensus_notification_handler) =
+        create_consensus_notification_handler(1000, TimeService::mock());
+
+    // Initialize a sync request for a target behind the synced version (in an old epoch)
+    let synced_version = 100;
+    let (join_handle, sync_notification) = send_sync_request(
+        consensus_notifier,
+        &mut consensus_notification_handler,
+        create_ledger_info_at_epoch(0, synced_version - 1),
+    )
+    .await;
+    let error = consensus_notification_handler
+        .initialize_sync_request(
+            sync_notification,
+            create_ledger_info_at_epoch(1, synced_version),
+            create_epoch_state(1),
+        )
+        .await
+        .unwrap_err();
+
+    // Verify the request was rejected immediately (without checking the epoch)
+    assert_eq!(
+        error,
+        Error::OldSyncRequest(synced_version - 1, synced_version)
+    );
+    assert!(!consensus_notification_handler.active_sync_request());
+    assert_err!(join_handle.await.unwrap());
+
+    // Initialize a sync request for a target at the synced version
+    let (consensus_notifier, mut consensus_notification_handler) =
+        create_consensus_notification_handler(1000, TimeService::mock());
+    let (join_handle, sync_notification) = send_sync_request(
+        consensus_notifier,
+        &mut consensus_notification_handler,
+        create_ledger_info_at_epoch(0, synced_version),
+    )
+    .await;
+    consensus_notification_handler
+        .initialize_sync_request(
+            sync_notification,
+            create_ledger_info_at_epoch(0, synced_version),
+            create_epoch_state(1),
+        )
+        .await
+        .unwrap();
+
+    // Verify consensus was notified of the success immediately
+    assert!(!consensus_notification_handler.active_sync_request());
+    assert_ok!(join_handle.await.unwrap());
+}
+
+#[tokio::test]
+async fn test_sync_request_unverifiable_target() {
+    // Create the consensus notification handler
+    let (consensus_notifier, mut consensus_notification_handler) =
+        create_consensus_notification_handler(1000, TimeService::mock());
+
+    // Initialize a sync request for a target in an old epoch
+    let synced_version = 100;
+    let (join_handle, sync_notification) = send_sync_request(
+        consensus_notifier,
+        &mut consensus_notification_handler,
+        create_ledger_info_at_epoch(1, synced_version + 1),
+    )
+    .await;
+    let error = consensus_notification_handler
+        .initialize_sync_request(
+            sync_notification,
+            create_ledger_info_at_epoch(2, synced_version),
+            create_epoch_state(2),
+        )
+        .await
+        .unwrap_err();
+
+    // Verify the request was rejected and consensus was notified
+    assert_eq!(error, Error::OldSyncTargetEpoch(1, 2));
+    assert!(!consensus_notification_handler.active_sync_request());
+    assert_err!(join_handle.await.unwrap());
+
+    // Initialize a sync request for a target with missing signatures
+    let (consensus_notifier, mut consensus_notification_handler) =
+        create_consensus_notification_handler(1000, TimeService::mock());
+    let (join_handle, sync_notification) = send_sync_request(
+        consensus_notifier,
+        &mut consensus_notification_handler,
+        create_ledger_info_at_epoch(1, synced_version + 1),
+    )
+    .await;
+    let error = consensus_notification_handler
+        .initialize_sync_request(
+            sync_notification,
+            create_ledger_info_at_epoch(1, synced_version),
+            create_epoch_state_with_validator(1),
+        )
+        .await
+        .unwrap_err();
+
+    // Verify the request was rejected and consensus was notified
+    assert_matches!(error, Error::VerificationError(_));
+    assert!(!consensus_notification_handler.active_sync_request());
+    assert_err!(join_handle.await.unwrap());
+}
+
+#[tokio::test]
+async fn test_sync_request_future_epoch_target() {
+    // Create the consensus notification handler
+    let (consensus_notifier, mut consensus_notification_handler) =
+        create_consensus_notification_handler(1000, TimeService::mock());
+
+    // Initialize a sync request for a target two epochs ahead (e.g., the last
+    // ledger info of an epoch change proof). Its signatures can't be verified
+    // by the current validators, so they're left to the streamed data.
+    let synced_version = 100;
+    let (join_handle, sync_notification) = send_sync_request(
+        consensus_notifier,
+        &mut consensus_notification_handler,
+        create_ledger_info_at_epoch(3, synced_version + 10),
+    )
+    .await;
+    consensus_notification_handler
+        .initialize_sync_request(
+            sync_notification,
+            create_ledger_info_at_epoch(1, synced_version),
+            create_epoch_state_with_validator(1),
+        )
+        .await
+        .unwrap();
+    assert!(consensus_notification_handler.active_sync_request());
+
+    // Verify consensus is notified once the target is reached
+    consensus_notification_handler
+        .check_sync_request_progress(create_ledger_info_at_epoch(3, synced_version + 10))
+        .await
+        .unwrap();
+    assert!(!consensus_notification_handler.active_sync_request());
+    assert_ok!(join_handle.await.unwrap());
+}
+
+/// Creates a consensus notification handler (and the notifier for consensus)
+fn create_consensus_notification_handler(
+    sync_request_timeout_ms: u64,
+    time_service: TimeService,
+) -> (ConsensusNotifier, ConsensusNotificationHandler) {
+    let (consensus_notifier, consensus_listener) =
+        consensus_notifications::new_consensus_notifier_listener_pair(5000);
+    let consensus_notification_handler = ConsensusNotificationHandler::new(
+        consensus_listener,
+        sync_request_timeout_ms,
+        time_service,
+    );
+    (consensus_notifier, consensus_notification_handler)
+}
+
+/// Creates an epoch state (at the given epoch) with a single validator
+fn create_epoch_state_with_validator(epoch: u64) -> EpochState {
+    let private_key = bls12381::PrivateKey::generate_for_testing();
+    let verifier =
+        ValidatorVerifier::new_single(AccountAddress::random(), private_key.public_key());
+    EpochState { epoch, verifier }
+}
+
+/// Creates an event subscription service for testing
+fn create_event_subscription_service() -> Arc<Mutex<EventSubscriptionService>> {
+    Arc::new(Mutex::new(EventSubscriptionService::new(
+        ON_CHAIN_CONFIG_REGISTRY,
+        Arc::new(RwLock::new(create_mock_reader_writer(None, None))),
+    )))
+}
+
+/// Creates a ledger info (without signatures) at the given epoch and version
+fn create_ledger_info_at_epoch(epoch: u64, version: Version) -> LedgerInfoWithSignatures {
+    let block_info = BlockInfo::new(
+        epoch,
+        0,
+        HashValue::zero(),
+        HashValue::zero(),
+        version,
+        0,
+        None,
+    );
+    let ledger_info = LedgerInfo::new(block_info, HashValue::random());
+    LedgerInfoWithSignatures::new(ledger_info, BTreeMap::new())
+}
+
+/// Handles a commit notification for the given transactions (ending at the
+/// specified version) and verifies that mempool is notified of exactly the
+/// expected transactions.
+async fn handle_and_verify_commit(
+    mempool_notification_handler: &MempoolNotificationHandler<MempoolNotifier>,
+    mempool_listener: &mut MempoolNotificationListener,
+    transactions: Vec<Transaction>,
+    last_committed_version: Version,
+    expected_transactions: Vec<Transaction>,
+) {
+    let (result, _) = futures::join!(
+        handle_transaction_notification(
+            mempool_notification_handler,
+            transactions,
+            last_committed_version
+        ),
+        verify_mempool_and_event_notification(
+            None,
+            mempool_listener,
+            expected_transactions,
+            vec![]
+        )
+    );
+    result.unwrap();
+}
+
+/// Handles a commit notification for the given transactions (ending at the
+/// specified version) and verifies that mempool is not notified.
+async fn handle_and_verify_dropped_commit(
+    mempool_notification_handler: &MempoolNotificationHandler<MempoolNotifier>,
+    mempool_listener: &mut MempoolNotificationListener,
+    transactions: Vec<Transaction>,
+    last_committed_version: Version,
+) {
+    handle_transaction_notification(
+        mempool_notification_handler,
+        transactions,
+        last_committed_version,
+    )
+    .await
+    .unwrap();
+    assert!(mempool_listener.select_next_some().now_or_never().is_none());
+}
+
+/// Handles a commit notification for the given transactions (ending at the
+/// specified version) using the given mempool notification handler.
+async fn handle_transaction_notification(
+    mempool_notification_handler: &MempoolNotificationHandler<MempoolNotifier>,
+    transactions: Vec<Transaction>,
+    last_committed_version: Version,
+) -> Result<(), Error> {
+    handle_transaction_notification_with_events(
+        mempool_notification_handler,
+        create_event_subscription_service(),
+        vec![],
+        transactions,
+        last_committed_version,
+    )
+    .await
+}
+
+/// Handles a commit notification for the given events and transactions
+/// (ending at the specified version) using the given mempool notification
+/// handler and event subscription service.
+async fn handle_transaction_notification_with_events(
+    mempool_notification_handler: &MempoolNotificationHandler<MempoolNotifier>,
+    event_subscription_service: Arc<Mutex<EventSubscriptionService>>,
+    events: Vec<ContractEvent>,
+    transactions: Vec<Transaction>,
+    last_committed_version: Version,
+) -> Result<(), Error> {
+    CommitNotification::handle_transaction_notification(
+        events,
+        transactions,
+        last_committed_version,
+        create_ledger_info_at_version(last_committed_version),
+        mempool_notification_handler.clone(),
+        event_subscription_service,
+    )
+    .await
+}
+
+/// Creates an event for the given event key at the given sequence number
+fn create_event_at_sequence_number(event_key: EventKey, sequence_number: u64) -> ContractEvent {
+    ContractEvent::new(
+        event_key,
+        sequence_number,
+        TypeTag::Bool,
+        bcs::to_bytes(&sequence_number).unwrap(),
+    )
+}
+
+/// Sends a sync request (for the given target) from consensus and returns the
+/// handle for the consensus response alongside the received sync notification
+async fn send_sync_request(
+    consensus_notifier: ConsensusNotifier,
+    consensus_notification_handler: &mut ConsensusNotificationHandler,
+    sync_target: LedgerInfoWithSignatures,
+) -> (
+    JoinHandle<Result<(), consensus_notifications::Error>>,
+    ConsensusSyncNotification,
+) {
+    let join_handle =
+        tokio::spawn(async move { consensus_notifier.sync_to_target(sync_target).await });
+    match consensus_notification_handler.select_next_some().await {
+        ConsensusNotification::SyncToTarget(sync_notification) => (join_handle, sync_notification),
+        notification => panic!("Unexpected consensus notification: {:?}", notification),
+    }
+}
@@ -2,16 +2,21 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
+    bootstrap_progress::BootstrapProgressNotifier,
     error::Error,
+    metrics,
     notification_handlers::{
         CommitNotification, CommitNotificationListener, CommittedTransactions,
         ErrorNotificationListener, MempoolNotificationHandler,
     },
-    storage_synchronizer::{StorageSynchronizer, StorageSynchronizerInterface},
+    storage_synchronizer::{
+        ChunkLatencyAverage, ChunkTimings, StorageSynchronizer, StorageSynchronizerInterface,
+    },
     tests::{
         mocks::{
-            create_mock_db_writer, create_mock_executor, create_mock_reader_writer,
-            create_mock_receiver, MockChunkExecutor,
+            create_mock_db_reader, create_mock_db_writer, create_mock_executor,
+            create_mock_metadata_storage, create_mock_reader_writer, create_mock_receiver,
+            create_ready_metadata_storage, MockChunkExecutor, MockMetadataStorage,
         },
         utils::{
             create_epoch_ending_ledger_info, create_event, create_output_list_with_proof,
@@ -22,18 +27,24 @@ use crate::{
 };
 use anyhow::format_err;
 use aptos_config::config::StateSyncDriverConfig;
+use aptos_crypto::HashValue;
 use aptos_infallible::{Mutex, RwLock};
 use aptos_types::{
-    ledger_info::LedgerInfoWithSignatures, on_chain_config::ON_CHAIN_CONFIG_REGISTRY,
+    ledger_info::LedgerInfoWithSignatures,
+    on_chain_config::ON_CHAIN_CONFIG_REGISTRY,
+    transaction::{Transaction, TransactionOutputListWithProof},
 };
 use claim::assert_matches;
 use data_streaming_service::data_notification::NotificationId;
 use event_notifications::EventSubscriptionService;
-use executor_types::ChunkCommitNotification;
-use futures::StreamExt;
+use executor_types::{ChunkCommitNotification, ChunkExecutionSummary, ChunkStageTimings};
+use futures::{FutureExt, StreamExt};
 use mempool_notifications::MempoolNotificationListener;
 use mockall::predicate::{always, eq};
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use storage_interface::DbReaderWriter;
 use tokio::task::JoinHandle;
 
@@ -53,6 +64,7 @@ async fn test_apply_transaction_outputs() {
         committed_events: vec![event_to_commit.clone()],
         committed_transactions: vec![transaction_to_commit.clone()],
         reconfiguration_occurred: false,
+        execution_summary: ChunkExecutionSummary::default(),
     });
     chunk_executor
         .expect_commit_chunk()
@@ -150,6 +162,256 @@ async fn test_commit_chunk_error() {
     verify_no_pending_data(&storage_synchronizer);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_commit_chunk_error_retires_speculative_chunks() {
+    // Create test data
+    let transaction_to_commit = create_transaction();
+
+    // Setup the mock executor, where the first (slow) commit fails
+    let mut chunk_executor = create_mock_executor();
+    chunk_executor
+        .expect_execute_chunk()
+        .with(always(), always(), always())
+        .returning(|_, _, _| Ok(()));
+    let committed_transaction = transaction_to_commit.clone();
+    let mut first_commit = true;
+    chunk_executor.expect_commit_chunk().returning(move || {
+        if first_commit {
+            first_commit = false;
+            std::thread::sleep(Duration::from_millis(200));
+            return Err(format_err!("Failed to commit chunk!"));
+        }
+        Ok(ChunkCommitNotification {
+            committed_events: vec![],
+            committed_transactions: vec![committed_transaction.clone()],
+            reconfiguration_occurred: false,
+            execution_summary: ChunkExecutionSummary::default(),
+        })
+    });
+    chunk_executor.expect_reset().returning(|| Ok(()));
+
+    // Create the storage synchronizer
+    let (_, mut error_listener, _, mut mempool_listener, mut storage_synchronizer, _, _) =
+        create_storage_synchronizer(chunk_executor, create_mock_reader_writer(None, None));
+
+    // Execute several chunks of transactions (the first of which will fail to commit)
+    let retired_chunks_before = retired_chunks();
+    for notification_id in 0..3 {
+        storage_synchronizer
+            .execute_transactions(
+                notification_id,
+                create_transaction_list_with_proof(),
+                create_epoch_ending_ledger_info(),
+                None,
+            )
+            .unwrap();
+    }
+
+    // Verify we only get an error notification for the first chunk, and
+    // that the speculatively executed chunks are retired.
+    verify_error_notification(&mut error_listener, 0).await;
+    verify_no_pending_data(&storage_synchronizer);
+    assert!(error_listener.select_next_some().now_or_never().is_none());
+    assert_eq!(retired_chunks(), retired_chunks_before + 2);
+
+    // Reset the chunk executor and verify new chunks are committed
+    storage_synchronizer.reset_chunk_executor().unwrap();
+    storage_synchronizer
+        .execute_transactions(
+            3,
+            create_transaction_list_with_proof(),
+            create_epoch_ending_ledger_info(),
+            None,
+        )
+        .unwrap();
+    verify_mempool_and_event_notification(
+        None,
+        &mut mempool_listener,
+        vec![transaction_to_commit],
+        vec![],
+    )
+    .await;
+    verify_no_pending_data(&storage_synchronizer);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_chunk_timing_logs() {
+    // Create test data (a unique transaction for each chunk)
+    let transactions_to_commit = vec![create_transaction(), create_transaction()];
+    let stage_latencies = || {
+        ChunkTimings::default()
+            .stages()
+            .iter()
+            .map(|(stage, _)| {
+                let histogram =
+                    metrics::STORAGE_SYNCHRONIZER_STAGE_LATENCIES.with_label_values(&[stage]);
+                (
+                    *stage,
+                    histogram.get_sample_count(),
+                    histogram.get_sample_sum(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // Setup the mock executor, where every stage takes a little while
+    let mut chunk_executor = create_mock_executor();
+    chunk_executor
+        .expect_execute_chunk()
+        .with(always(), always(), always())
+        .returning(|_, _, _| Ok(()));
+    chunk_executor
+        .expect_apply_chunk()
+        .with(always(), always(), always())
+        .returning(|_, _, _| Ok(()));
+    chunk_executor
+        .expect_last_chunk_stage_timings()
+        .return_const(Some(ChunkStageTimings {
+            verification: Duration::from_millis(1),
+            execution: Duration::from_millis(2),
+            state_tree_update: Duration::from_millis(3),
+        }));
+    let mut committed_transactions = transactions_to_commit.clone().into_iter();
+    chunk_executor.expect_commit_chunk().returning(move || {
+        std::thread::sleep(Duration::from_millis(1));
+        Ok(ChunkCommitNotification {
+            committed_events: vec![],
+            committed_transactions: vec![committed_transactions.next().unwrap()],
+            reconfiguration_occurred: false,
+            execution_summary: ChunkExecutionSummary::default(),
+        })
+    });
+
+    // Create the storage synchronizer with chunk timing logs enabled
+    let driver_config = StateSyncDriverConfig {
+        enable_chunk_timing_logs: true,
+        ..Default::default()
+    };
+    let (_, _, _, mut mempool_listener, mut storage_synchronizer, _, _) =
+        create_storage_synchronizer_with_config(
+            driver_config,
+            chunk_executor,
+            create_ready_metadata_storage(),
+            create_mock_reader_writer(None, None),
+        );
+
+    // Execute a chunk of transactions and apply a chunk of outputs
+    let latencies_before = stage_latencies();
+    storage_synchronizer
+        .execute_transactions(
+            0,
+            create_transaction_list_with_proof(),
+            create_epoch_ending_ledger_info(),
+            None,
+        )
+        .unwrap();
+    storage_synchronizer
+        .apply_transaction_outputs(
+            1,
+            create_output_list_with_proof(),
+            create_epoch_ending_ledger_info(),
+            None,
+        )
+        .unwrap();
+    for transaction_to_commit in transactions_to_commit {
+        verify_mempool_and_event_notification(
+            None,
+            &mut mempool_listener,
+            vec![transaction_to_commit],
+            vec![],
+        )
+        .await;
+    }
+    verify_no_pending_data(&storage_synchronizer);
+
+    // Verify every stage was timed for both chunks, and took some time
+    let latencies_after = stage_latencies();
+    for ((stage, count_before, sum_before), (_, count_after, sum_after)) in
+        latencies_before.into_iter().zip(latencies_after)
+    {
+        assert_eq!(count_after, count_before + 2, "stage: {}", stage);
+        assert!(sum_after > sum_before, "stage: {}", stage);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_chunk_latency_average() {
+    // Create test data (a unique transaction for each chunk)
+    let num_chunks = 3;
+    let transactions_to_commit: Vec<Transaction> =
+        (0..num_chunks).map(|_| create_transaction()).collect();
+    let commit_delay = Duration::from_millis(50);
+
+    // Setup the mock executor, where commits are artificially delayed
+    let mut chunk_executor = create_mock_executor();
+    chunk_executor
+        .expect_apply_chunk()
+        .with(always(), always(), always())
+        .returning(|_, _, _| Ok(()));
+    let mut committed_transactions = transactions_to_commit.clone().into_iter();
+    chunk_executor.expect_commit_chunk().returning(move || {
+        std::thread::sleep(commit_delay);
+        Ok(ChunkCommitNotification {
+            committed_events: vec![],
+            committed_transactions: vec![committed_transactions.next().unwrap()],
+            reconfiguration_occurred: false,
+            execution_summary: ChunkExecutionSummary::default(),
+        })
+    });
+
+    // Create the storage synchronizer and verify no chunk latencies are recorded
+    let (_, _, _, mut mempool_listener, mut storage_synchronizer, _, _) =
+        create_storage_synchronizer(chunk_executor, create_mock_reader_writer(None, None));
+    assert_eq!(
+        storage_synchronizer.get_chunk_latency_average(),
+        ChunkLatencyAverage::default()
+    );
+
+    // Apply several chunks of outputs
+    for notification_id in 0..num_chunks {
+        storage_synchronizer
+            .apply_transaction_outputs(
+                notification_id,
+                create_output_list_with_proof(),
+                create_epoch_ending_ledger_info(),
+                None,
+            )
+            .unwrap();
+        verify_mempool_and_event_notification(
+            None,
+            &mut mempool_listener,
+            vec![transactions_to_commit[notification_id as usize].clone()],
+            vec![],
+        )
+        .await;
+    }
+    verify_no_pending_data(&storage_synchronizer);
+
+    // Verify the chunk latencies were recorded (and include the commit delay)
+    let chunk_latency_average = storage_synchronizer.get_chunk_latency_average();
+    assert_eq!(chunk_latency_average.num_chunks, num_chunks);
+    assert!(chunk_latency_average.average_latency >= commit_delay);
+}
+
+#[test]
+fn test_chunk_latency_average_updates() {
+    // Record a single chunk latency and verify it becomes the average
+    let mut chunk_latency_average = ChunkLatencyAverage::default();
+    chunk_latency_average.record_chunk_latency(Duration::from_millis(100));
+    assert_eq!(
+        chunk_latency_average.average_latency,
+        Duration::from_millis(100)
+    );
+
+    // Record a slower chunk latency and verify the average moves towards it
+    chunk_latency_average.record_chunk_latency(Duration::from_millis(500));
+    assert_eq!(
+        chunk_latency_average.average_latency,
+        Duration::from_millis(200)
+    );
+    assert_eq!(chunk_latency_average.num_chunks, 2);
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_execute_transactions() {
     // Create test data
@@ -166,6 +428,7 @@ async fn test_execute_transactions() {
         committed_events: vec![event_to_commit.clone()],
         committed_transactions: vec![transaction_to_commit.clone()],
         reconfiguration_occurred: false,
+        execution_summary: ChunkExecutionSummary::default(),
     });
     chunk_executor
         .expect_commit_chunk()
@@ -231,6 +494,67 @@ async fn test_execute_transactions_error() {
     verify_no_pending_data(&storage_synchronizer);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_execute_transactions_overlap_commits() {
+    // Create test data (a unique transaction for each chunk)
+    let num_chunks = 4;
+    let transactions_to_commit: Vec<Transaction> =
+        (0..num_chunks).map(|_| create_transaction()).collect();
+    let stage_delay = Duration::from_millis(100);
+
+    // Setup the mock executor, where execution and commits are artificially delayed
+    let mut chunk_executor = create_mock_executor();
+    chunk_executor
+        .expect_execute_chunk()
+        .with(always(), always(), always())
+        .returning(move |_, _, _| {
+            std::thread::sleep(stage_delay);
+            Ok(())
+        });
+    let mut committed_transactions = transactions_to_commit.clone().into_iter();
+    chunk_executor.expect_commit_chunk().returning(move || {
+        std::thread::sleep(stage_delay);
+        Ok(ChunkCommitNotification {
+            committed_events: vec![],
+            committed_transactions: vec![committed_transactions.next().unwrap()],
+            reconfiguration_occurred: false,
+            execution_summary: ChunkExecutionSummary::default(),
+        })
+    });
+
+    // Create the storage synchronizer
+    let (_, _, _, mut mempool_listener, mut storage_synchronizer, _, _) =
+        create_storage_synchronizer(chunk_executor, create_mock_reader_writer(None, None));
+
+    // Execute several chunks of transactions and verify they're all committed
+    let sync_start = Instant::now();
+    for notification_id in 0..num_chunks {
+        storage_synchronizer
+            .execute_transactions(
+                notification_id,
+                create_transaction_list_with_proof(),
+                create_epoch_ending_ledger_info(),
+                None,
+            )
+            .unwrap();
+    }
+    for transaction_to_commit in transactions_to_commit {
+        verify_mempool_and_event_notification(
+            None,
+            &mut mempool_listener,
+            vec![transaction_to_commit],
+            vec![],
+        )
+        .await;
+    }
+    verify_no_pending_data(&storage_synchronizer);
+
+    // Verify execution overlapped with the commits (i.e., the total time
+    // is less than the sum of all execution and commit delays).
+    let sum_of_stages = stage_delay * 2 * num_chunks as u32;
+    assert!(sync_start.elapsed() < sum_of_stages);
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_initialize_state_synchronizer() {
     // Create test data
@@ -381,11 +705,41 @@ async fn test_save_states_completion() {
         .returning(|_| Ok(()));
     db_writer.expect_delete_genesis().returning(|| Ok(()));
 
+    // Setup the mock db reader to return the expected state snapshot
+    let target_version = target_ledger_info.ledger_info().version();
+    let expected_root_hash = get_expected_root_hash(&output_list_with_proof);
+    let mut db_reader = create_mock_db_reader();
+    db_reader
+        .expect_get_state_snapshot_before()
+        .with(eq(target_version + 1))
+        .returning(move |_| Ok(Some((target_version, expected_root_hash))));
+
+    // Setup the mock metadata storage (the snapshot receiver uses a clone)
+    let mut metadata_storage = create_mock_metadata_storage();
+    let target_ledger_info_clone = target_ledger_info.clone();
+    metadata_storage.expect_clone().return_once(move || {
+        let mut metadata_storage = create_mock_metadata_storage();
+        for snapshot_sync_completed in [false, true] {
+            metadata_storage
+                .expect_update_last_persisted_state_value_index()
+                .times(1)
+                .with(
+                    eq(target_ledger_info_clone.clone()),
+                    always(),
+                    eq(snapshot_sync_completed),
+                )
+                .returning(|_, _, _| Ok(()));
+        }
+        metadata_storage
+    });
+
     // Create the storage synchronizer
     let (mut commit_listener, _, _, _, mut storage_synchronizer, _, _) =
-        create_storage_synchronizer(
+        create_storage_synchronizer_with_config(
+            StateSyncDriverConfig::default(),
             chunk_executor,
-            create_mock_reader_writer(None, Some(db_writer)),
+            metadata_storage,
+            create_mock_reader_writer(Some(db_reader), Some(db_writer)),
         );
 
     // Subscribe to the expected event
@@ -432,6 +786,92 @@ async fn test_save_states_completion() {
     verify_no_pending_data(&storage_synchronizer);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_save_states_root_hash_mismatch() {
+    // Create test data
+    let target_ledger_info = create_epoch_ending_ledger_info();
+    let output_list_with_proof = create_output_list_with_proof();
+
+    // Setup the mock snapshot receiver
+    let mut snapshot_receiver = create_mock_receiver();
+    snapshot_receiver
+        .expect_add_chunk()
+        .with(always(), always())
+        .returning(|_, _| Ok(()));
+    snapshot_receiver.expect_finish_box().returning(|| Ok(()));
+
+    // Setup the mock db writer (the snapshot should never be finalized)
+    let mut db_writer = create_mock_db_writer();
+    db_writer
+        .expect_get_state_snapshot_receiver()
+        .with(always(), always())
+        .return_once(move |_, _| Ok(Box::new(snapshot_receiver)));
+    db_writer.expect_finalize_state_snapshot().times(0);
+
+    // Setup the mock db reader to return a snapshot with a different root
+    // hash (e.g., because one of the state value chunks was corrupted).
+    let target_version = target_ledger_info.ledger_info().version();
+    let mut db_reader = create_mock_db_reader();
+    db_reader
+        .expect_get_state_snapshot_before()
+        .with(eq(target_version + 1))
+        .returning(move |_| Ok(Some((target_version, HashValue::random()))));
+
+    // Setup the mock metadata storage to expect the progress to be deleted
+    let mut metadata_storage = create_mock_metadata_storage();
+    let target_ledger_info_clone = target_ledger_info.clone();
+    metadata_storage.expect_clone().return_once(move || {
+        let mut metadata_storage = create_mock_metadata_storage();
+        metadata_storage
+            .expect_update_last_persisted_state_value_index()
+            .times(1)
+            .with(eq(target_ledger_info_clone), always(), eq(false))
+            .returning(|_, _, _| Ok(()));
+        metadata_storage
+            .expect_delete_snapshot_sync_progress()
+            .times(1)
+            .returning(|| Ok(()));
+        metadata_storage
+    });
+
+    // Create the storage synchronizer
+    let (mut commit_listener, mut error_listener, _, _, mut storage_synchronizer, _, _) =
+        create_storage_synchronizer_with_config(
+            StateSyncDriverConfig::default(),
+            create_mock_executor(),
+            metadata_storage,
+            create_mock_reader_writer(Some(db_reader), Some(db_writer)),
+        );
+
+    // Initialize the state synchronizer
+    let state_synchronizer_handle = storage_synchronizer
+        .initialize_state_synchronizer(
+            vec![target_ledger_info.clone()],
+            target_ledger_info,
+            output_list_with_proof,
+        )
+        .unwrap();
+
+    // Save a state chunk and verify we get a commit notification
+    storage_synchronizer
+        .save_state_values(0, create_state_value_chunk_with_proof(false))
+        .unwrap();
+    verify_state_commit_notification(&mut commit_listener, false, None).await;
+
+    // Save the last state chunk and verify we get a root hash mismatch error
+    let notification_id = 1;
+    storage_synchronizer
+        .save_state_values(notification_id, create_state_value_chunk_with_proof(true))
+        .unwrap();
+    let error_notification = error_listener.select_next_some().await;
+    assert_eq!(error_notification.notification_id, notification_id);
+    assert_matches!(error_notification.error, Error::SnapshotRootHashMismatch(_));
+
+    // The handler should return as the snapshot must be synced again
+    state_synchronizer_handle.await.unwrap();
+    verify_no_pending_data(&storage_synchronizer);
+}
+
 #[tokio::test(flavor = "multi_thread")]
 #[should_panic]
 async fn test_save_states_dropped_error_listener() {
@@ -536,7 +976,31 @@ fn create_storage_synchronizer(
     ErrorNotificationListener,
     Arc<Mutex<EventSubscriptionService>>,
     MempoolNotificationListener,
-    StorageSynchronizer<MockChunkExecutor>,
+    StorageSynchronizer<MockChunkExecutor, MockMetadataStorage>,
+    JoinHandle<()>,
+    JoinHandle<()>,
+) {
+    create_storage_synchronizer_with_config(
+        StateSyncDriverConfig::default(),
+        mock_chunk_executor,
+        create_ready_metadata_storage(),
+        mock_reader_writer,
+    )
+}
+
+/// Creates a storage synchronizer for testing with the given driver config
+/// and metadata storage.
+fn create_storage_synchronizer_with_config(
+    driver_config: StateSyncDriverConfig,
+    mock_chunk_executor: MockChunkExecutor,
+    mock_metadata_storage: MockMetadataStorage,
+    mock_reader_writer: DbReaderWriter,
+) -> (
+    CommitNotificationListener,
+    ErrorNotificationListener,
+    Arc<Mutex<EventSubscriptionService>>,
+    MempoolNotificationListener,
+    StorageSynchronizer<MockChunkExecutor, MockMetadataStorage>,
     JoinHandle<()>,
     JoinHandle<()>,
 ) {
@@ -560,12 +1024,14 @@ fn create_storage_synchronizer(
 
     // Create the storage synchronizer
     let (storage_synchronizer, executor_handle, committer_handle) = StorageSynchronizer::new(
-        StateSyncDriverConfig::default(),
+        driver_config,
+        BootstrapProgressNotifier::new().0,
         Arc::new(mock_chunk_executor),
         commit_notification_sender,
         error_notification_sender,
         event_subscription_service.clone(),
         mempool_notification_handler,
+        mock_metadata_storage,
         mock_reader_writer,
         None,
     );
@@ -606,10 +1072,34 @@ async fn verify_error_notification(
     assert_matches!(error_notification.error, Error::UnexpectedError(_));
 }
 
+/// Returns the total number of chunks retired by the storage synchronizer
+fn retired_chunks() -> u64 {
+    [
+        metrics::STORAGE_SYNCHRONIZER_EXECUTOR,
+        metrics::STORAGE_SYNCHRONIZER_COMMITTER,
+    ]
+    .iter()
+    .map(|stage| {
+        metrics::STORAGE_SYNCHRONIZER_RETIRED_CHUNKS
+            .with_label_values(&[stage])
+            .get()
+    })
+    .sum()
+}
+
+/// Returns the expected state snapshot root hash for the given target output
+fn get_expected_root_hash(output_list_with_proof: &TransactionOutputListWithProof) -> HashValue {
+    output_list_with_proof.proof.transaction_infos[0]
+        .state_checkpoint_hash()
+        .unwrap()
+}
+
 /// Verifies that no pending data remains in the storage synchronizer.
 /// Note: due to asynchronous execution, we might need to wait some
 /// time for the pipelines to drain.
-fn verify_no_pending_data(storage_synchronizer: &StorageSynchronizer<MockChunkExecutor>) {
+fn verify_no_pending_data(
+    storage_synchronizer: &StorageSynchronizer<MockChunkExecutor, MockMetadataStorage>,
+) {
     let max_drain_time_secs = 10;
     for _ in 0..max_drain_time_secs {
         if !storage_synchronizer.pending_storage_data() {
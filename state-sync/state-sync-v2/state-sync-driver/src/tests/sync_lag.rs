@@ -0,0 +1,147 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::sync_lag::{SyncLag, SyncLagListener, SyncLagMonitor};
+use aptos_config::config::StateSyncDriverConfig;
+use aptos_crypto::HashValue;
+use aptos_data_client::GlobalDataSummary;
+use aptos_types::{
+    block_info::BlockInfo,
+    ledger_info::{LedgerInfo, LedgerInfoWithSignatures},
+    transaction::Version,
+};
+use futures::{FutureExt, StreamExt};
+use std::{collections::BTreeMap, time::Duration};
+
+// Useful test constants
+const MAX_SYNC_LAG_SECS: u64 = 60;
+const MAX_SYNC_LAG_VERSIONS: u64 = 100;
+
+#[test]
+fn test_sync_lag_alarm() {
+    // Create the sync lag monitor
+    let (mut sync_lag_monitor, mut sync_lag_listener) = create_sync_lag_monitor();
+
+    // Verify no sync lag is calculated if nothing is advertised
+    let synced_ledger_info = create_ledger_info(1000, 100);
+    let sync_lag = sync_lag_monitor.check_sync_lag(
+        synced_ledger_info.ledger_info(),
+        &GlobalDataSummary::empty(),
+    );
+    assert_eq!(sync_lag, None);
+
+    // Verify no alarm is raised when the node is within the thresholds
+    let global_data_summary = create_global_summary(1000 + MAX_SYNC_LAG_VERSIONS, 100);
+    let sync_lag = sync_lag_monitor
+        .check_sync_lag(synced_ledger_info.ledger_info(), &global_data_summary)
+        .unwrap();
+    assert_eq!(sync_lag.versions_behind, MAX_SYNC_LAG_VERSIONS);
+    verify_no_sync_lag_alarm(&mut sync_lag_listener);
+
+    // Cross the version threshold and verify an alarm is raised (only once)
+    let global_data_summary = create_global_summary(1000 + MAX_SYNC_LAG_VERSIONS + 1, 100);
+    for _ in 0..3 {
+        sync_lag_monitor.check_sync_lag(synced_ledger_info.ledger_info(), &global_data_summary);
+    }
+    let sync_lag_alarm = verify_sync_lag_alarm(&mut sync_lag_listener);
+    assert_eq!(sync_lag_alarm.versions_behind, MAX_SYNC_LAG_VERSIONS + 1);
+    verify_no_sync_lag_alarm(&mut sync_lag_listener);
+
+    // Fall back under the thresholds and verify no alarm is raised
+    let synced_ledger_info = create_ledger_info(1050, 100);
+    sync_lag_monitor.check_sync_lag(synced_ledger_info.ledger_info(), &global_data_summary);
+    verify_no_sync_lag_alarm(&mut sync_lag_listener);
+
+    // Cross the time threshold and verify a new alarm is raised
+    let global_data_summary = create_global_summary(1050, 100 + MAX_SYNC_LAG_SECS + 1);
+    sync_lag_monitor.check_sync_lag(synced_ledger_info.ledger_info(), &global_data_summary);
+    let sync_lag_alarm = verify_sync_lag_alarm(&mut sync_lag_listener);
+    assert_eq!(sync_lag_alarm.versions_behind, 0);
+    assert_eq!(sync_lag_alarm.seconds_behind, MAX_SYNC_LAG_SECS + 1);
+
+    // Catch up to our peers and verify no alarm is raised
+    let synced_ledger_info = create_ledger_info(1050, 100 + MAX_SYNC_LAG_SECS + 1);
+    let sync_lag = sync_lag_monitor
+        .check_sync_lag(synced_ledger_info.ledger_info(), &global_data_summary)
+        .unwrap();
+    assert_eq!(sync_lag.versions_behind, 0);
+    assert_eq!(sync_lag.seconds_behind, 0);
+    verify_no_sync_lag_alarm(&mut sync_lag_listener);
+}
+
+#[test]
+fn test_sync_lag_calculation() {
+    // Verify the lag when the node is behind
+    let sync_lag = SyncLag::new(
+        create_ledger_info(100, 10).ledger_info(),
+        create_ledger_info(350, 25).ledger_info(),
+    );
+    assert_eq!(
+        sync_lag,
+        SyncLag {
+            synced_version: 100,
+            highest_advertised_version: 350,
+            versions_behind: 250,
+            seconds_behind: 15,
+        }
+    );
+
+    // Verify the lag when the node is ahead of the advertised data
+    let sync_lag = SyncLag::new(
+        create_ledger_info(500, 50).ledger_info(),
+        create_ledger_info(350, 25).ledger_info(),
+    );
+    assert_eq!(sync_lag.versions_behind, 0);
+    assert_eq!(sync_lag.seconds_behind, 0);
+}
+
+/// Creates a global data summary that advertises a ledger info with the given
+/// version and timestamp (in seconds).
+fn create_global_summary(version: Version, timestamp_secs: u64) -> GlobalDataSummary {
+    let mut global_data_summary = GlobalDataSummary::empty();
+    global_data_summary.advertised_data.synced_ledger_infos =
+        vec![create_ledger_info(version, timestamp_secs)];
+    global_data_summary
+}
+
+/// Creates a ledger info at the given version and timestamp (in seconds)
+fn create_ledger_info(version: Version, timestamp_secs: u64) -> LedgerInfoWithSignatures {
+    let timestamp_usecs = Duration::from_secs(timestamp_secs).as_micros() as u64;
+    let block_info = BlockInfo::new(
+        0,
+        0,
+        HashValue::zero(),
+        HashValue::zero(),
+        version,
+        timestamp_usecs,
+        None,
+    );
+    let ledger_info = LedgerInfo::new(block_info, HashValue::random());
+    LedgerInfoWithSignatures::new(ledger_info, BTreeMap::new())
+}
+
+/// Creates a sync lag monitor (and listener) with the test thresholds
+fn create_sync_lag_monitor() -> (SyncLagMonitor, SyncLagListener) {
+    let driver_config = StateSyncDriverConfig {
+        max_sync_lag_secs: MAX_SYNC_LAG_SECS,
+        max_sync_lag_versions: MAX_SYNC_LAG_VERSIONS,
+        ..Default::default()
+    };
+    SyncLagMonitor::new(&driver_config)
+}
+
+/// Verifies that no sync lag alarm is pending on the listener
+fn verify_no_sync_lag_alarm(sync_lag_listener: &mut SyncLagListener) {
+    assert!(sync_lag_listener
+        .select_next_some()
+        .now_or_never()
+        .is_none());
+}
+
+/// Verifies that a sync lag alarm is pending on the listener and returns it
+fn verify_sync_lag_alarm(sync_lag_listener: &mut SyncLagListener) -> SyncLag {
+    sync_lag_listener
+        .select_next_some()
+        .now_or_never()
+        .expect("Expected a sync lag alarm!")
+}
@@ -8,12 +8,16 @@ use crate::{
     notification_handlers::{
         CommitNotification, CommittedTransactions, MempoolNotificationHandler,
     },
+    storage_synchronizer::ChunkLatencyAverage,
 };
 use aptos_infallible::Mutex;
 use aptos_logger::prelude::*;
+use aptos_time_service::{TimeService, TimeServiceTrait};
 use aptos_types::{
-    epoch_change::Verifier, epoch_state::EpochState, ledger_info::LedgerInfoWithSignatures,
-    transaction::Version,
+    epoch_change::Verifier,
+    epoch_state::EpochState,
+    ledger_info::LedgerInfoWithSignatures,
+    transaction::{Transaction, Version},
 };
 use data_streaming_service::{
     data_notification::{DataNotification, DataPayload, NotificationId},
@@ -21,9 +25,13 @@ use data_streaming_service::{
     streaming_client::{DataStreamingClient, NotificationFeedback},
 };
 use event_notifications::EventSubscriptionService;
-use futures::StreamExt;
+use futures::{stream::FusedStream, StreamExt};
 use mempool_notifications::MempoolNotificationSender;
-use std::{sync::Arc, time::Duration};
+use std::{
+    cmp,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use storage_interface::DbReader;
 use tokio::time::timeout;
 
@@ -78,28 +86,212 @@ impl SpeculativeStreamState {
     }
 
     /// Updates the epoch state if we've hit the specified target ledger
-    /// info version and the ledger info has a new epoch state.
+    /// info version and the ledger info has a new epoch state. Returns
+    /// true iff the epoch state was updated.
     pub fn maybe_update_epoch_state(
         &mut self,
         ledger_info_with_signatures: LedgerInfoWithSignatures,
-    ) {
+    ) -> bool {
         if let Some(epoch_state) = ledger_info_with_signatures.ledger_info().next_epoch_state() {
             if ledger_info_with_signatures.ledger_info().version() == self.synced_version {
                 self.epoch_state = epoch_state.clone();
+                return true;
             }
         }
+        false
+    }
+
+    /// Returns the current epoch state (used to verify new ledger infos)
+    pub fn get_epoch_state(&self) -> EpochState {
+        self.epoch_state.clone()
+    }
+}
+
+/// Tracks when syncing transaction outputs should fall back to executing
+/// transactions (e.g., because all peers have pruned the required outputs).
+/// To avoid flapping between modes, the fallback is only triggered after
+/// several consecutive unavailable output streams, and lasts for a minimum
+/// duration before outputs are retried.
+pub struct OutputFallbackHandler {
+    // The label of the component using the handler (for logs and metrics)
+    component_label: &'static str,
+
+    // The time at which we started executing transactions (if we've fallen back)
+    fallback_start_time: Option<Instant>,
+
+    // The min duration to execute transactions for (after falling back)
+    fallback_duration: Duration,
+
+    // The number of consecutive unavailable output streams before falling back
+    fallback_threshold: u64,
+
+    // The number of consecutive output streams that were unavailable
+    num_consecutive_unavailable_streams: u64,
+
+    // The time service used to track the fallback duration
+    time_service: TimeService,
+}
+
+impl OutputFallbackHandler {
+    pub fn new(
+        component_label: &'static str,
+        fallback_duration: Duration,
+        fallback_threshold: u64,
+        time_service: TimeService,
+    ) -> Self {
+        Self {
+            component_label,
+            fallback_start_time: None,
+            fallback_duration,
+            fallback_threshold,
+            num_consecutive_unavailable_streams: 0,
+            time_service,
+        }
     }
 
-    /// Verifies the given ledger info with signatures against the current epoch state
-    pub fn verify_ledger_info_with_signatures(
+    /// Returns true iff we've fallen back to executing transactions. This
+    /// should only be checked when creating a new stream (so that the mode
+    /// remains fixed for the lifetime of each stream).
+    pub fn in_fallback_mode(&mut self) -> bool {
+        if let Some(fallback_start_time) = self.fallback_start_time {
+            let fallback_end_time = fallback_start_time + self.fallback_duration;
+            if self.time_service.now() >= fallback_end_time {
+                info!(LogSchema::new(LogEntry::Driver).message(&format!(
+                    "The output fallback has expired for the {}! Retrying transaction outputs.",
+                    self.component_label
+                )));
+                self.fallback_start_time = None;
+            }
+        }
+        self.fallback_start_time.is_some()
+    }
+
+    /// Handles the result of creating an output stream and records if the
+    /// outputs were unavailable (e.g., because they've been pruned by peers).
+    pub fn handle_output_stream_result(
         &mut self,
-        ledger_info_with_signatures: &LedgerInfoWithSignatures,
-    ) -> Result<(), Error> {
-        self.epoch_state
-            .verify(ledger_info_with_signatures)
-            .map_err(|error| {
-                Error::VerificationError(format!("Ledger info failed verification: {:?}", error))
-            })
+        result: Result<DataStreamListener, data_streaming_service::error::Error>,
+    ) -> Result<DataStreamListener, Error> {
+        match result {
+            Ok(data_stream) => {
+                self.num_consecutive_unavailable_streams = 0;
+                Ok(data_stream)
+            }
+            Err(error) => {
+                let error = Error::from(error);
+                if matches!(error, Error::DataIsUnavailable(_)) {
+                    self.record_unavailable_outputs();
+                }
+                Err(error)
+            }
+        }
+    }
+
+    /// Records that an output stream couldn't be created because the outputs
+    /// are unavailable. If this happens too many times, we fall back.
+    fn record_unavailable_outputs(&mut self) {
+        self.num_consecutive_unavailable_streams += 1;
+        if self.num_consecutive_unavailable_streams >= self.fallback_threshold {
+            warn!(LogSchema::new(LogEntry::Driver).message(&format!(
+                "Transaction outputs are unavailable! Falling back to executing transactions \
+                for the {}. Consecutive unavailable streams: {:?}, fallback duration: {:?}",
+                self.component_label,
+                self.num_consecutive_unavailable_streams,
+                self.fallback_duration
+            )));
+            metrics::increment_counter(&metrics::OUTPUT_SYNC_FALLBACKS, self.component_label);
+            self.fallback_start_time = Some(self.time_service.now());
+            self.num_consecutive_unavailable_streams = 0;
+        }
+    }
+}
+
+/// The number of chunks that must be recorded (after a chunk size change)
+/// before the chunk size can be changed again. This gives the chunk latency
+/// moving average time to reflect the new chunk size.
+const CHUNK_SIZE_UPDATE_INTERVAL: u64 = 4;
+
+/// A simple controller that adapts the chunk size requested from the network
+/// to how long storage takes to execute/apply and commit each chunk. The chunk
+/// size is halved when the average chunk latency exceeds the target, and is
+/// doubled when the average latency is below half the target.
+pub struct ChunkSizeController {
+    // The chunk size to request from the network
+    chunk_size: u64,
+
+    // The max chunk size that can be requested
+    max_chunk_size: u64,
+
+    // The min chunk size that can be requested
+    min_chunk_size: u64,
+
+    // The number of chunks recorded when the chunk size last changed
+    num_chunks_at_last_update: u64,
+
+    // The target time for storage to execute/apply and commit a single chunk
+    target_chunk_latency: Duration,
+}
+
+impl ChunkSizeController {
+    pub fn new(min_chunk_size: u64, max_chunk_size: u64, target_chunk_latency: Duration) -> Self {
+        // Start at the max chunk size (this is reduced if storage is slow)
+        let min_chunk_size = cmp::max(min_chunk_size, 1);
+        let max_chunk_size = cmp::max(max_chunk_size, min_chunk_size);
+        metrics::set_gauge(
+            &metrics::CONTINUOUS_SYNCER_GAUGES,
+            metrics::CONTINUOUS_SYNCER_CHUNK_SIZE,
+            max_chunk_size,
+        );
+
+        Self {
+            chunk_size: max_chunk_size,
+            max_chunk_size,
+            min_chunk_size,
+            num_chunks_at_last_update: 0,
+            target_chunk_latency,
+        }
+    }
+
+    /// Returns the chunk size to request from the network
+    pub fn get_chunk_size(&self) -> u64 {
+        self.chunk_size
+    }
+
+    /// Updates the chunk size using the given chunk latency average. Returns
+    /// true iff the chunk size changed.
+    pub fn update_chunk_size(&mut self, chunk_latency_average: ChunkLatencyAverage) -> bool {
+        // Wait until enough chunks have been recorded since the last change
+        let num_chunks = chunk_latency_average.num_chunks;
+        if num_chunks < self.num_chunks_at_last_update + CHUNK_SIZE_UPDATE_INTERVAL {
+            return false;
+        }
+
+        // Calculate the new chunk size
+        let average_latency = chunk_latency_average.average_latency;
+        let new_chunk_size = if average_latency > self.target_chunk_latency {
+            cmp::max(self.chunk_size / 2, self.min_chunk_size)
+        } else if average_latency < self.target_chunk_latency / 2 {
+            cmp::min(self.chunk_size.saturating_mul(2), self.max_chunk_size)
+        } else {
+            self.chunk_size
+        };
+        if new_chunk_size == self.chunk_size {
+            return false;
+        }
+
+        // Update the chunk size
+        info!(LogSchema::new(LogEntry::Driver).message(&format!(
+            "Updating the chunk size from {:?} to {:?}. Average chunk latency: {:?}, target: {:?}",
+            self.chunk_size, new_chunk_size, average_latency, self.target_chunk_latency
+        )));
+        metrics::set_gauge(
+            &metrics::CONTINUOUS_SYNCER_GAUGES,
+            metrics::CONTINUOUS_SYNCER_CHUNK_SIZE,
+            new_chunk_size,
+        );
+        self.chunk_size = new_chunk_size;
+        self.num_chunks_at_last_update = num_chunks;
+        true
     }
 }
 
@@ -140,6 +332,29 @@ pub async fn get_data_notification(
     }
 }
 
+/// Fetches a data notification from the given subscription stream. Unlike
+/// `get_data_notification`, timeouts are expected (i.e., no new data has been
+/// committed by peers) and so None is returned instead of an error. An error
+/// is only returned if the stream has terminated.
+///
+/// Note: this assumes the `active_data_stream` exists.
+pub async fn get_subscription_notification(
+    max_stream_wait_time_ms: u64,
+    active_data_stream: Option<&mut DataStreamListener>,
+) -> Result<Option<DataNotification>, Error> {
+    let active_data_stream = active_data_stream.expect("The active data stream should exist!");
+    if active_data_stream.is_terminated() {
+        return Err(Error::UnexpectedError(
+            "The subscription stream has terminated!".into(),
+        ));
+    }
+
+    let timeout_ms = Duration::from_millis(max_stream_wait_time_ms);
+    Ok(timeout(timeout_ms, active_data_stream.select_next_some())
+        .await
+        .ok())
+}
+
 /// Terminates the stream with the provided notification ID and feedback
 pub async fn terminate_stream_with_feedback<StreamingClient: DataStreamingClient + Clone>(
     streaming_client: &mut StreamingClient,
@@ -222,6 +437,37 @@ pub fn fetch_latest_synced_version(storage: Arc<dyn DbReader>) -> Result<Version
         .map(|(latest_synced_version, _)| latest_synced_version)
 }
 
+/// Returns the last version (in the given transactions, starting at the specified
+/// version) with a ledger timestamp at or below the target timestamp. If no
+/// transaction moves the ledger timestamp beyond the target, None is returned.
+/// Note: the ledger timestamp is only updated by block metadata transactions.
+pub fn get_timestamp_target_version<'a>(
+    transactions: impl Iterator<Item = &'a Transaction>,
+    start_version: Version,
+    target_timestamp_usecs: u64,
+) -> Option<Version> {
+    transactions
+        .enumerate()
+        .find_map(|(index, transaction)| match transaction {
+            Transaction::BlockMetadata(block_metadata)
+                if block_metadata.timestamp_usecs() > target_timestamp_usecs =>
+            {
+                Some(start_version.saturating_add(index as u64).saturating_sub(1))
+            }
+            _ => None,
+        })
+}
+
+/// Verifies the given ledger info against the given epoch state
+pub fn verify_ledger_info_signatures(
+    epoch_state: &EpochState,
+    ledger_info: &LedgerInfoWithSignatures,
+) -> Result<(), Error> {
+    epoch_state.verify(ledger_info).map_err(|error| {
+        Error::VerificationError(format!("Ledger info failed verification: {:?}", error))
+    })
+}
+
 /// Initializes all relevant metric gauges (e.g., after a reboot
 /// or after a state snapshot has been restored).
 pub fn initialize_sync_gauges(storage: Arc<dyn DbReader>) -> Result<(), Error> {
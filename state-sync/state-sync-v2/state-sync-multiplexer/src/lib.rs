@@ -13,7 +13,10 @@ use executor_types::ChunkExecutorTrait;
 use futures::executor::block_on;
 use mempool_notifications::MempoolNotificationSender;
 use network::protocols::network::AppConfig;
-use state_sync_driver::driver_factory::DriverFactory;
+use state_sync_driver::{
+    driver_factory::DriverFactory, metadata_storage::MetadataStorageInterface,
+    sync_status::SyncStatusHandle,
+};
 use state_sync_v1::{
     bootstrapper::StateSyncBootstrapper,
     network::{StateSyncEvents, StateSyncSender},
@@ -50,6 +53,10 @@ impl StateSyncRuntimes {
     pub fn block_until_initialized(&self) {
         self.state_sync.block_until_initialized()
     }
+
+    pub fn sync_status_handle(&self) -> SyncStatusHandle {
+        self.state_sync.sync_status_handle()
+    }
 }
 
 /// A multiplexer allowing multiple versions of state sync to operate
@@ -64,9 +71,11 @@ impl StateSyncMultiplexer {
     pub fn new<
         ChunkExecutor: ChunkExecutorTrait + 'static,
         MempoolNotifier: MempoolNotificationSender + 'static,
+        MetadataStorage: MetadataStorageInterface + Clone + Send + Sync + 'static,
     >(
         network: Vec<(NetworkId, StateSyncSender, StateSyncEvents)>,
         mempool_notifier: MempoolNotifier,
+        metadata_storage: MetadataStorage,
         consensus_listener: ConsensusNotificationListener,
         storage: DbReaderWriter,
         chunk_executor: Arc<ChunkExecutor>,
@@ -108,6 +117,7 @@ impl StateSyncMultiplexer {
                 storage,
                 chunk_executor,
                 mempool_notifier,
+                metadata_storage,
                 consensus_listener,
                 event_subscription_service,
                 aptos_data_client,
@@ -135,6 +145,15 @@ impl StateSyncMultiplexer {
         }
     }
 
+    /// Returns a handle to the sync status published by state sync. State
+    /// sync v1 doesn't publish a status, so the handle will remain empty.
+    pub fn sync_status_handle(&self) -> SyncStatusHandle {
+        self.state_sync_v2
+            .as_ref()
+            .map(|driver_factory| driver_factory.sync_status_handle())
+            .unwrap_or_default()
+    }
+
     pub fn block_until_initialized(&self) {
         if self.activate_state_sync_v2 {
             let state_sync_v2_client = self
@@ -189,6 +208,7 @@ mod tests {
     use futures::{FutureExt, StreamExt};
     use mempool_notifications::new_mempool_notifier_listener_pair;
     use network::application::{interface::MultiNetworkSender, storage::PeerMetadataStorage};
+    use state_sync_driver::metadata_storage::PersistentMetadataStorage;
     use std::{collections::HashMap, sync::Arc};
     use storage_interface::DbReaderWriter;
     use storage_service_client::StorageServiceClient;
@@ -242,10 +262,15 @@ mod tests {
             None,
         );
 
+        // Create the state sync metadata storage
+        let metadata_storage_dir = TempPath::new();
+        let metadata_storage = PersistentMetadataStorage::new(metadata_storage_dir.path());
+
         // Create the multiplexer
         let _ = StateSyncMultiplexer::new(
             vec![],
             mempool_notifier,
+            metadata_storage,
             consensus_listener,
             db_rw.clone(),
             Arc::new(ChunkExecutor::<AptosVM>::new(db_rw)),
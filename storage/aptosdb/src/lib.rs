@@ -93,6 +93,7 @@ use aptosdb_indexer::Indexer;
 use itertools::zip_eq;
 use move_deps::move_resource_viewer::MoveValueAnnotator;
 use once_cell::sync::Lazy;
+use rayon::prelude::*;
 use schemadb::db_options::gen_rocksdb_options;
 use schemadb::DB;
 use std::{
@@ -1223,7 +1224,9 @@ impl DbReader for AptosDB {
                                                                                      executed_trees.state().base_version,
                                                                                      executed_trees.state().base.clone(),
                                                                                      executed_trees.state().base_version,
-                                HashMap::new()
+                                HashMap::new(),
+                                executed_trees.state().base_usage,
+                                executed_trees.state().base_usage,
                             ), transaction_accumulator);
                             StartupInfo::new(
                                 latest_ledger_info,
@@ -1256,6 +1259,29 @@ impl DbReader for AptosDB {
         })
     }
 
+    fn get_state_values_with_proof_by_version(
+        &self,
+        state_keys: &[StateKey],
+        version: Version,
+    ) -> Result<Vec<(Option<StateValue>, SparseMerkleProof)>> {
+        gauged_api("get_state_values_with_proof_by_version", || {
+            error_if_version_is_pruned(
+                &self.pruner,
+                PrunerIndex::StateStorePrunerIndex,
+                "State",
+                version,
+            )?;
+
+            state_keys
+                .par_iter()
+                .map(|state_key| {
+                    self.state_store
+                        .get_state_value_with_proof_by_version(state_key, version)
+                })
+                .collect()
+        })
+    }
+
     fn get_latest_epoch_state(&self) -> Result<EpochState> {
         gauged_api("get_latest_epoch_state", || {
             let latest_ledger_info = self.ledger_store.get_latest_ledger_info()?;
@@ -102,7 +102,9 @@ impl BufferedState {
 
     pub fn update(
         &mut self,
-        updates_until_next_checkpoint_since_current_option: Option<HashMap<StateKey, StateValue>>,
+        updates_until_next_checkpoint_since_current_option: Option<
+            HashMap<StateKey, Option<Arc<StateValue>>>,
+        >,
         mut new_state_after_checkpoint: StateDelta,
         sync_commit: bool,
     ) -> Result<()> {
@@ -113,10 +115,11 @@ impl BufferedState {
             updates_until_next_checkpoint_since_current_option
         {
             self.state_after_checkpoint
-                .updates_since_base
+                .updates_since_base_mut()
                 .extend(updates_until_next_checkpoint_since_current);
             self.state_after_checkpoint.current = new_state_after_checkpoint.base.clone();
             self.state_after_checkpoint.current_version = new_state_after_checkpoint.base_version;
+            self.state_after_checkpoint.current_usage = new_state_after_checkpoint.base_usage;
             swap(
                 &mut self.state_after_checkpoint,
                 &mut new_state_after_checkpoint,
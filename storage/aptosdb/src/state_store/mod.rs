@@ -28,9 +28,9 @@ use aptos_types::{
 use executor_types::in_memory_state_calculator::InMemoryStateCalculator;
 use schemadb::{ReadOptions, SchemaBatch, DB};
 use std::ops::Deref;
-use std::{collections::HashMap, sync::Arc};
+use std::{borrow::Borrow, collections::HashMap, sync::Arc};
 use storage_interface::{
-    cached_state_view::CachedStateView, state_delta::StateDelta,
+    cached_state_view::CachedStateView, state_delta::StateDelta, state_value_or_deleted,
     sync_proof_fetcher::SyncProofFetcher, DbReader, StateSnapshotReceiver,
 };
 
@@ -299,10 +299,11 @@ impl StateStore {
                 .last()
                 .map(|(idx, _)| idx);
             latest_snapshot_state_view.prime_cache_by_write_set(&write_sets)?;
-            let calculator = InMemoryStateCalculator::new(
+            let calculator = InMemoryStateCalculator::new_at_version(
                 buffered_state.current_state(),
                 latest_snapshot_state_view.into_state_cache(),
-            );
+                snapshot_next_version,
+            )?;
             let (updates_until_last_checkpoint, state_after_last_checkpoint) = calculator
                 .calculate_for_write_sets_after_snapshot(last_checkpoint_index, &write_sets)?;
 
@@ -400,22 +401,26 @@ impl StateStore {
         self.state_merkle_db.get_range_proof(rightmost_key, version)
     }
 
-    /// Put the `value_state_sets` into its own CF.
-    pub fn put_value_sets(
+    /// Put the `value_state_sets` into its own CF, with deleted items (`None`) stored as the empty
+    /// value. The values are serialized straight from the given sets, so callers holding them
+    /// behind `Arc`s don't need to copy them.
+    pub fn put_value_sets<V: Borrow<StateValue>>(
         &self,
-        value_state_sets: Vec<&HashMap<StateKey, StateValue>>,
+        value_state_sets: Vec<&HashMap<StateKey, Option<V>>>,
         first_version: Version,
         cs: &mut ChangeSet,
     ) -> Result<()> {
-        let kv_batch = value_state_sets
+        value_state_sets
             .iter()
             .enumerate()
             .flat_map(|(i, kvs)| {
                 kvs.iter()
-                    .map(move |(k, v)| ((k.clone(), first_version + i as Version), v.clone()))
+                    .map(move |(k, v)| ((k.clone(), first_version + i as Version), v))
+            })
+            .try_for_each(|(k, v)| {
+                cs.batch
+                    .put::<StateValueSchema>(&k, state_value_or_deleted(v))
             })
-            .collect::<HashMap<_, _>>();
-        add_kv_batch(&mut cs.batch, &kv_batch)
     }
 
     /// Merklize the results generated by `value_state_sets` to `batch` and return the result root
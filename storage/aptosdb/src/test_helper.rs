@@ -18,6 +18,7 @@ use aptos_types::{
 use executor_types::ProofReader;
 use proptest::{collection::vec, prelude::*};
 use scratchpad::SparseMerkleTree;
+use storage_interface::state_value_or_deleted;
 
 pub fn update_in_memory_state(state: &mut StateDelta, txns_to_commit: &[TransactionToCommit]) {
     let mut next_version = state.current_version.map_or(0, |v| v + 1);
@@ -26,7 +27,9 @@ pub fn update_in_memory_state(state: &mut StateDelta, txns_to_commit: &[Transact
             .state_updates()
             .iter()
             .for_each(|(key, value)| {
-                state.updates_since_base.insert(key.clone(), value.clone());
+                state
+                    .updates_since_base_mut()
+                    .insert(key.clone(), value.clone());
             });
         next_version += 1;
         if txn_to_commit.is_state_checkpoint() {
@@ -38,7 +41,7 @@ pub fn update_in_memory_state(state: &mut StateDelta, txns_to_commit: &[Transact
                     state
                         .updates_since_base
                         .iter()
-                        .map(|(k, v)| (k.hash(), v))
+                        .map(|(k, v)| (k.hash(), state_value_or_deleted(v)))
                         .collect(),
                     &ProofReader::new_empty(),
                 )
@@ -47,7 +50,7 @@ pub fn update_in_memory_state(state: &mut StateDelta, txns_to_commit: &[Transact
             state.current_version = next_version.checked_sub(1);
             state.base = state.current.clone();
             state.base_version = state.current_version;
-            state.updates_since_base.clear();
+            state.updates_since_base = Arc::default();
         }
     }
     if next_version.checked_sub(1) != state.current_version {
@@ -59,7 +62,7 @@ pub fn update_in_memory_state(state: &mut StateDelta, txns_to_commit: &[Transact
                 state
                     .updates_since_base
                     .iter()
-                    .map(|(k, v)| (k.hash(), v))
+                    .map(|(k, v)| (k.hash(), state_value_or_deleted(v)))
                     .collect(),
                 &ProofReader::new_empty(),
             )
@@ -203,7 +206,7 @@ fn verify_epochs(db: &AptosDB, ledger_infos_with_sigs: &[LedgerInfoWithSignature
 }
 
 fn gen_snapshot_version(
-    updates: &mut HashMap<StateKey, StateValue>,
+    updates: &mut HashMap<StateKey, Option<Arc<StateValue>>>,
     txns_to_commit: &[TransactionToCommit],
     cur_ver: Version,
     threshold: usize,
@@ -345,7 +348,7 @@ fn verify_snapshots(
     txns_to_commit: Vec<&TransactionToCommit>,
 ) {
     let mut cur_version = start_version;
-    let mut updates: HashMap<&StateKey, &StateValue> = HashMap::new();
+    let mut updates: HashMap<&StateKey, &Option<Arc<StateValue>>> = HashMap::new();
     for snapshot_version in snapshot_versions {
         let start = (cur_version - start_version) as usize;
         let end = (snapshot_version - start_version) as usize;
@@ -360,13 +363,16 @@ fn verify_snapshots(
             txns_to_commit[start..=end]
                 .iter()
                 .flat_map(|x| x.state_updates().iter())
-                .collect::<HashMap<&StateKey, &StateValue>>(),
+                .collect::<HashMap<&StateKey, &Option<Arc<StateValue>>>>(),
         );
         for (state_key, state_value) in &updates {
             let (state_value_in_db, proof) = db
                 .get_state_value_with_proof_by_version(state_key, snapshot_version)
                 .unwrap();
-            assert_eq!(state_value_in_db, Some((*state_value).clone()));
+            assert_eq!(
+                state_value_in_db.as_ref(),
+                Some(state_value_or_deleted(*state_value))
+            );
             proof
                 .verify(
                     expected_root_hash,
@@ -621,7 +627,10 @@ pub fn verify_committed_transactions(
         for (state_key, state_value) in txn_to_commit.state_updates() {
             updates.insert(state_key, state_value);
             let state_value_in_db = db.get_state_value_by_version(state_key, cur_ver).unwrap();
-            assert_eq!(state_value_in_db, Some(state_value.clone()));
+            assert_eq!(
+                state_value_in_db.as_ref(),
+                Some(state_value_or_deleted(state_value))
+            );
         }
 
         if !txn_to_commit.is_state_checkpoint() {
@@ -732,7 +741,9 @@ pub fn put_as_state_root(db: &AptosDB, version: Version, key: StateKey, value: S
         .clone();
     in_memory_state.current = smt;
     in_memory_state.current_version = Some(version);
-    in_memory_state.updates_since_base.insert(key, value);
+    in_memory_state
+        .updates_since_base_mut()
+        .insert(key, Some(Arc::new(value)));
     db.state_store
         .buffered_state()
         .lock()
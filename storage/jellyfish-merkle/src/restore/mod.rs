@@ -297,6 +297,19 @@ where
     ) -> Result<()> {
         ensure!(!chunk.is_empty(), "Should not add empty chunks.");
 
+        // If the restoration was interrupted and resumed, the beginning of the chunk may have
+        // already been restored (e.g., the chunk was resent). Skip all those keys.
+        let chunk: Vec<_> = match self.previous_leaf {
+            Some(ref prev_leaf) => chunk
+                .into_iter()
+                .skip_while(|(key, _)| key.hash() <= prev_leaf.account_key())
+                .collect(),
+            None => chunk,
+        };
+        if chunk.is_empty() {
+            return Ok(());
+        }
+
         for (key, value_hash) in chunk {
             let hashed_key = key.hash();
             if let Some(ref prev_leaf) = self.previous_leaf {
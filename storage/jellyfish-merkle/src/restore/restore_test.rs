@@ -176,6 +176,44 @@ proptest! {
         assert_success(&restore_db, expected_root_hash, &all, version);
     }
 
+    #[test]
+    fn test_restore_with_resent_chunk(
+        (all, batch1_size) in arb_btree_map(2)
+            .prop_flat_map(|btree| {
+                let len = btree.len();
+                (Just(btree), 1..len)
+            })
+    ) {
+        let (db, version) = init_mock_store(&all.clone().into_iter().map(|(_, kv)| kv).collect());
+        let tree = JellyfishMerkleTree::new(&db);
+        let expected_root_hash = tree.get_root_hash(version).unwrap();
+        let batch1: Vec<_> = all.clone().into_iter().take(batch1_size).collect();
+
+        let restore_db = Arc::new(MockSnapshotStore::default());
+        {
+            let mut restore =
+                StateSnapshotRestore::new(&restore_db, &restore_db, version, expected_root_hash).unwrap();
+            let proof = tree
+                .get_range_proof(batch1.last().map(|(key, _value)| *key).unwrap(), version)
+                .unwrap();
+            restore.add_chunk(batch1.into_iter().map(|(_, kv)| kv).collect(), proof).unwrap();
+            // Do not call `finish`.
+        }
+
+        {
+            // Resend everything, including the keys that have already been restored.
+            let mut restore =
+                StateSnapshotRestore::new(&restore_db, &restore_db, version, expected_root_hash).unwrap();
+            let proof = tree
+                .get_range_proof(all.keys().last().cloned().unwrap(), version)
+                .unwrap();
+            restore.add_chunk(all.clone().into_iter().map(|(_, kv)| kv).collect(), proof).unwrap();
+            restore.finish().unwrap();
+        }
+
+        assert_success(&restore_db, expected_root_hash, &all, version);
+    }
+
     #[test]
     fn test_overwrite(
         btree1 in arb_btree_map(1),
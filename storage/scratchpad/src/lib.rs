@@ -6,7 +6,8 @@
 mod sparse_merkle;
 
 pub use crate::sparse_merkle::{
-    FrozenSparseMerkleTree, ProofRead, SparseMerkleTree, StateStoreStatus,
+    FrozenSparseMerkleTree, NewNodeCounts, ProofRead, SparseMerkleTree, StateStoreStatus,
+    UpdateError,
 };
 
 #[cfg(any(test, feature = "bench", feature = "fuzzing"))]
@@ -81,8 +81,8 @@ pub mod test_utils;
 
 use crate::sparse_merkle::{
     metrics::{LATEST_GENERATION, OLDEST_GENERATION, TIMER},
-    node::{NodeInner, SubTree},
-    updater::SubTreeUpdater,
+    node::{LeafNode, Node, NodeInner, SubTree},
+    updater::{SubTreeHasher, SubTreeUpdater},
 };
 use aptos_crypto::{
     hash::{CryptoHash, SPARSE_MERKLE_PLACEHOLDER_HASH},
@@ -92,13 +92,34 @@ use aptos_infallible::Mutex;
 use aptos_types::{nibble::nibble_path::NibblePath, proof::SparseMerkleProof};
 use std::{
     borrow::Borrow,
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     sync::{Arc, Weak},
 };
 use thiserror::Error;
 
 type NodePosition = bitvec::vec::BitVec<bitvec::order::Msb0, u8>;
 
+/// How many nodes an SMT created in memory since an older one, see
+/// `FrozenSparseMerkleTree::new_node_counts_since`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NewNodeCounts {
+    /// The number of hashes `new_node_hashes_since` would return.
+    pub num_node_hashes: usize,
+    /// The number of internal nodes, whether or not they're at a nibble boundary.
+    pub num_internal_nodes: usize,
+    pub num_leaves: usize,
+}
+
+impl NewNodeCounts {
+    pub fn combine(self, other: Self) -> Self {
+        Self {
+            num_node_hashes: self.num_node_hashes + other.num_node_hashes,
+            num_internal_nodes: self.num_internal_nodes + other.num_internal_nodes,
+            num_leaves: self.num_leaves + other.num_leaves,
+        }
+    }
+}
+
 /// To help finding the oldest ancestor of any SMT, a branch tracker is created each time
 /// the chain of SMTs forked (two or more SMTs updating the same parent).
 #[derive(Debug)]
@@ -375,11 +396,14 @@ where
             .map(|(hashes, smt)| (hashes, smt.unfreeze()))
     }
 
-    pub fn batch_update(
+    pub fn batch_update<'a>(
         &self,
-        updates: Vec<(HashValue, &V)>,
+        updates: impl IntoIterator<Item = (HashValue, &'a V)>,
         proof_reader: &impl ProofRead,
-    ) -> Result<Self, UpdateError> {
+    ) -> Result<Self, UpdateError>
+    where
+        V: 'a,
+    {
         self.clone()
             .freeze()
             .batch_update(updates, proof_reader)
@@ -389,6 +413,10 @@ where
     pub fn get(&self, key: HashValue) -> StateStoreStatus<V> {
         self.clone().freeze().get(key)
     }
+
+    pub fn batch_get(&self, keys: &[HashValue]) -> Vec<StateStoreStatus<V>> {
+        self.clone().freeze().batch_get(keys)
+    }
 }
 
 impl<V> Default for SparseMerkleTree<V>
@@ -474,6 +502,70 @@ where
         Ok((result, cur))
     }
 
+    /// The number of nodes in memory which freezing keeps alive on top of the base tree: those of
+    /// every tree spawned from the base so far, including trees already superseded by later
+    /// updates and other branches. The nodes of the base tree itself aren't counted.
+    ///
+    /// This walks all of those nodes, so it's for tests and diagnostics. Callers updating the tree
+    /// can count the nodes of each update with `new_node_counts_since` instead.
+    pub fn num_retained_nodes(&self) -> usize {
+        let since_generation = self.base_generation + 1;
+        let mut seen = HashSet::new();
+        let mut inners = vec![self.base_smt.inner.clone()];
+        while let Some(inner) = inners.pop() {
+            let mut subtrees = vec![inner.root.weak()];
+            while let Some(subtree) = subtrees.pop() {
+                if let Some(node) = subtree.get_node_if_in_mem(since_generation) {
+                    if !seen.insert(Arc::as_ptr(&node)) {
+                        continue;
+                    }
+                    if let NodeInner::Internal(internal_node) = node.inner() {
+                        subtrees.push(internal_node.left.weak());
+                        subtrees.push(internal_node.right.weak());
+                    }
+                }
+            }
+            inners.extend(inner.links.lock().children.iter().cloned());
+        }
+        seen.len()
+    }
+
+    /// The number of generations of trees kept alive by freezing: those spawned from the base
+    /// tree up to this one.
+    pub fn num_retained_generations(&self) -> u64 {
+        self.smt.generation() - self.base_generation
+    }
+
+    /// Counts the nodes `new_node_hashes_since` would find, without building the map of their
+    /// positions.
+    pub fn new_node_counts_since(&self, since_smt: &Self) -> NewNodeCounts {
+        assert!(self.base_smt.is_the_same(&since_smt.base_smt));
+        let since_generation = since_smt.smt.generation() + 1;
+        let mut counts = NewNodeCounts::default();
+        let mut subtrees = vec![(self.smt.root_weak(), 0)];
+        while let Some((subtree, depth)) = subtrees.pop() {
+            if let Some(node) = subtree.get_node_if_in_mem(since_generation) {
+                // Like `new_node_hashes_since`, a node at a nibble boundary has its own hash, and
+                // a leaf has one more under its key.
+                if depth % 4 == 0 {
+                    counts.num_node_hashes += 1;
+                }
+                match node.inner() {
+                    NodeInner::Internal(internal_node) => {
+                        counts.num_internal_nodes += 1;
+                        subtrees.push((internal_node.left.weak(), depth + 1));
+                        subtrees.push((internal_node.right.weak(), depth + 1));
+                    }
+                    NodeInner::Leaf(_) => {
+                        counts.num_leaves += 1;
+                        counts.num_node_hashes += 1;
+                    }
+                }
+            }
+        }
+        counts
+    }
+
     /// Compares an old and a new SMTs and return the newly created node hashes in between.
     pub fn new_node_hashes_since(&self, since_smt: &Self) -> HashMap<NibblePath, HashValue> {
         let _timer = TIMER
@@ -563,11 +655,14 @@ where
     /// intermediate results.
     /// Since the tree is immutable, existing tree remains the same and may share parts with the
     /// new, returned tree.
-    pub fn batch_update(
+    pub fn batch_update<'a>(
         &self,
-        updates: Vec<(HashValue, &V)>,
+        updates: impl IntoIterator<Item = (HashValue, &'a V)>,
         proof_reader: &impl ProofRead,
-    ) -> Result<Self, UpdateError> {
+    ) -> Result<Self, UpdateError>
+    where
+        V: 'a,
+    {
         // Flatten, dedup and sort the updates with a btree map since the updates between different
         // versions may overlap on the same address in which case the latter always overwrites.
         let kvs = updates
@@ -590,6 +685,31 @@ where
         }
     }
 
+    /// The root hash `batch_update` would give the new tree, without creating it: no nodes are
+    /// created and no generation is added, so it's cheap to discard, e.g. for a speculative
+    /// checkpoint.
+    pub fn compute_root_hash_for_updates<'a>(
+        &self,
+        updates: impl IntoIterator<Item = (HashValue, &'a V)>,
+        proof_reader: &impl ProofRead,
+    ) -> Result<HashValue, UpdateError>
+    where
+        V: 'a,
+    {
+        // Dedup and sort the updates as `batch_update` does.
+        let kvs = updates
+            .into_iter()
+            .collect::<BTreeMap<_, _>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        if kvs.is_empty() {
+            Ok(self.smt.root_hash())
+        } else {
+            SubTreeHasher::root_hash(self.smt.root_weak(), &kvs[..], proof_reader)
+        }
+    }
+
     /// Queries a `key` in this `SparseMerkleTree`.
     pub fn get(&self, key: HashValue) -> StateStoreStatus<V> {
         let mut subtree = self.smt.root_weak();
@@ -611,16 +731,7 @@ where
                                 continue;
                             } // end NodeInner::Internal
                             NodeInner::Leaf(leaf_node) => {
-                                return if leaf_node.key == key {
-                                    match &leaf_node.value.data.get_if_in_mem() {
-                                        Some(value) => StateStoreStatus::ExistsInScratchPad(
-                                            value.as_ref().clone(),
-                                        ),
-                                        None => StateStoreStatus::ExistsInDB,
-                                    }
-                                } else {
-                                    StateStoreStatus::DoesNotExist
-                                };
+                                return Self::leaf_status(leaf_node, key);
                             } // end NodeInner::Leaf
                         }, // end Some(node) got from mem
                     }
@@ -628,6 +739,186 @@ where
             }
         } // end loop
     }
+
+    /// Queries `keys` in this `SparseMerkleTree`, returning the same as `get` would for each, in
+    /// the same order. The keys are sorted and the tree is walked once, so the nodes on the way to
+    /// neighboring keys are visited once rather than once per key.
+    pub fn batch_get(&self, keys: &[HashValue]) -> Vec<StateStoreStatus<V>> {
+        let mut sorted_keys: Vec<_> = keys.iter().copied().zip(0..).collect();
+        sorted_keys.sort_unstable();
+        let mut statuses: Vec<_> = keys.iter().map(|_| None).collect();
+        self.batch_get_in_subtree(self.smt.root_weak(), 0, &sorted_keys, &mut statuses);
+        statuses
+            .into_iter()
+            .map(|status| status.expect("Every key is queried."))
+            .collect()
+    }
+
+    // Queries `sorted_keys` (paired with where their statuses go), which are all under `subtree`
+    // at `depth`.
+    fn batch_get_in_subtree(
+        &self,
+        subtree: SubTree<V>,
+        depth: usize,
+        sorted_keys: &[(HashValue, usize)],
+        statuses: &mut [Option<StateStoreStatus<V>>],
+    ) {
+        if sorted_keys.is_empty() {
+            return;
+        }
+        let node = match subtree {
+            SubTree::Empty => None,
+            SubTree::NonEmpty { .. } => match subtree.get_node_if_in_mem(self.base_generation) {
+                None => {
+                    for (_, idx) in sorted_keys {
+                        statuses[*idx] = Some(StateStoreStatus::Unknown);
+                    }
+                    return;
+                }
+                Some(node) => Some(node),
+            },
+        };
+        match node.as_ref().map(|node| node.inner()) {
+            None => {
+                for (_, idx) in sorted_keys {
+                    statuses[*idx] = Some(StateStoreStatus::DoesNotExist);
+                }
+            }
+            Some(NodeInner::Internal(internal_node)) => {
+                assert!(depth < HashValue::LENGTH_IN_BITS, "Tree is too deep.");
+                // Keys with the bit at `depth` unset sort before the ones with it set.
+                let pivot = sorted_keys.partition_point(|(key, _)| !key.bit(depth));
+                let (left_keys, right_keys) = sorted_keys.split_at(pivot);
+                self.batch_get_in_subtree(
+                    internal_node.left.weak(),
+                    depth + 1,
+                    left_keys,
+                    statuses,
+                );
+                self.batch_get_in_subtree(
+                    internal_node.right.weak(),
+                    depth + 1,
+                    right_keys,
+                    statuses,
+                );
+            }
+            Some(NodeInner::Leaf(leaf_node)) => {
+                for (key, idx) in sorted_keys {
+                    statuses[*idx] = Some(Self::leaf_status(leaf_node, *key));
+                }
+            }
+        }
+    }
+
+    /// Returns the keys of the leaves that differ between this tree and `other`, i.e. the ones
+    /// that exist in only one of them or have different value hashes, in key order. Subtrees with
+    /// the same hash on both sides are skipped, so this is cheap for trees that share most of
+    /// their nodes. Returns `None` if telling the trees apart requires a subtree that's not in
+    /// memory.
+    pub fn diff_leaf_keys(&self, other: &Self) -> Option<Vec<HashValue>> {
+        let mut keys = Vec::new();
+        Self::diff_subtrees(
+            (self.smt.root_weak(), self.base_generation),
+            (other.smt.root_weak(), other.base_generation),
+            &mut keys,
+        )?;
+        Some(keys)
+    }
+
+    // Pushes the keys of the leaves that differ between the two subtrees (each paired with the
+    // generation its tree is frozen at), which are at the same position in their trees.
+    fn diff_subtrees(
+        (left, left_generation): (SubTree<V>, u64),
+        (right, right_generation): (SubTree<V>, u64),
+        keys: &mut Vec<HashValue>,
+    ) -> Option<()> {
+        if left.hash() == right.hash() {
+            return Some(());
+        }
+        let left_node = Self::node_if_in_mem(&left, left_generation)?;
+        let right_node = Self::node_if_in_mem(&right, right_generation)?;
+        if let (
+            Some(NodeInner::Internal(left_internal)),
+            Some(NodeInner::Internal(right_internal)),
+        ) = (
+            left_node.as_ref().map(|node| node.inner()),
+            right_node.as_ref().map(|node| node.inner()),
+        ) {
+            Self::diff_subtrees(
+                (left_internal.left.weak(), left_generation),
+                (right_internal.left.weak(), right_generation),
+                keys,
+            )?;
+            return Self::diff_subtrees(
+                (left_internal.right.weak(), left_generation),
+                (right_internal.right.weak(), right_generation),
+                keys,
+            );
+        }
+
+        // At least one side is empty or a single leaf, so there are few leaves to compare.
+        let mut left_leaves = Vec::new();
+        Self::collect_leaves(left, left_generation, &mut left_leaves)?;
+        let mut right_leaves = Vec::new();
+        Self::collect_leaves(right, right_generation, &mut right_leaves)?;
+        let (mut left_leaves, mut right_leaves) = (
+            left_leaves.into_iter().peekable(),
+            right_leaves.into_iter().peekable(),
+        );
+        loop {
+            let key = match (left_leaves.peek(), right_leaves.peek()) {
+                (None, None) => return Some(()),
+                (Some((left_key, _)), None) => *left_key,
+                (None, Some((right_key, _))) => *right_key,
+                (Some((left_key, _)), Some((right_key, _))) => *left_key.min(right_key),
+            };
+            let left_hash = left_leaves.next_if(|(leaf_key, _)| *leaf_key == key);
+            let right_hash = right_leaves.next_if(|(leaf_key, _)| *leaf_key == key);
+            if left_hash != right_hash {
+                keys.push(key);
+            }
+        }
+    }
+
+    // Returns `Some(None)` for an empty subtree and `None` if the root of the subtree is not in
+    // memory.
+    fn node_if_in_mem(subtree: &SubTree<V>, min_generation: u64) -> Option<Option<Arc<Node<V>>>> {
+        match subtree {
+            SubTree::Empty => Some(None),
+            SubTree::NonEmpty { .. } => subtree.get_node_if_in_mem(min_generation).map(Some),
+        }
+    }
+
+    // Pushes the keys and value hashes of all leaves under `subtree`, in key order.
+    fn collect_leaves(
+        subtree: SubTree<V>,
+        min_generation: u64,
+        leaves: &mut Vec<(HashValue, HashValue)>,
+    ) -> Option<()> {
+        match Self::node_if_in_mem(&subtree, min_generation)?
+            .as_ref()
+            .map(|node| node.inner())
+        {
+            None => {}
+            Some(NodeInner::Internal(internal_node)) => {
+                Self::collect_leaves(internal_node.left.weak(), min_generation, leaves)?;
+                Self::collect_leaves(internal_node.right.weak(), min_generation, leaves)?;
+            }
+            Some(NodeInner::Leaf(leaf_node)) => leaves.push((leaf_node.key, leaf_node.value.hash)),
+        }
+        Some(())
+    }
+
+    fn leaf_status(leaf_node: &LeafNode<V>, key: HashValue) -> StateStoreStatus<V> {
+        if leaf_node.key == key {
+            match &leaf_node.value.data.get_if_in_mem() {
+                Some(value) => StateStoreStatus::ExistsInScratchPad(value.as_ref().clone()),
+                None => StateStoreStatus::ExistsInDB,
+            }
+        } else {
+            StateStoreStatus::DoesNotExist
+        }
+    }
 }
 
 /// A type that implements `ProofRead` can provide proof for keys in persistent storage.
@@ -641,8 +932,8 @@ pub trait ProofRead: Sync {
 pub enum UpdateError {
     /// The update intends to insert a key that does not exist in the tree, so the operation needs
     /// proof to get more information about the tree, but no proof is provided.
-    #[error("Missing Proof")]
-    MissingProof,
+    #[error("Missing proof for key {}", key)]
+    MissingProof { key: HashValue },
     /// At `depth` a persisted subtree was encountered and a proof was requested to assist finding
     /// details about the subtree, but the result proof indicates the subtree is empty.
     #[error(
@@ -16,7 +16,7 @@ use aptos_types::{
 };
 use once_cell::sync::Lazy;
 use proptest::prelude::*;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 
 fn update_byte(original_key: &HashValue, n: usize, byte: u8) -> HashValue {
     let mut key = original_key.to_vec();
@@ -130,6 +130,51 @@ fn test_split_persisted_leaf() {
     assert_eq!(updated.root_hash(), root_hash);
 }
 
+#[test]
+fn test_compute_root_hash_for_updates() {
+    // A persisted leaf, split by a new key, and an in-mem leaf replaced.
+    let key1 = HashValue::from_slice(&[0; 32]).unwrap();
+    let leaf1 = SparseMerkleLeafNode::new(key1, b"hello".test_only_hash());
+    let key2 = HashValue::from_slice(&[0xff; 32]).unwrap();
+    let proof = SparseMerkleProof::new(Some(leaf1), Vec::new());
+    let proof_reader = ProofReader::new(vec![(key2, proof)]);
+    let value2: StateValue = vec![1, 2, 3].into();
+    let value3: StateValue = vec![4, 5, 6].into();
+
+    let smt = SparseMerkleTree::new(leaf1.hash()).freeze();
+    let expected = smt
+        .batch_update(vec![(key2, &value2)], &proof_reader)
+        .unwrap();
+    assert_eq!(
+        smt.compute_root_hash_for_updates(vec![(key2, &value2)], &proof_reader)
+            .unwrap(),
+        expected.root_hash()
+    );
+
+    let num_retained_nodes = expected.num_retained_nodes();
+    let root_hash = expected
+        .compute_root_hash_for_updates(vec![(key2, &value3)], &proof_reader)
+        .unwrap();
+    assert_eq!(expected.num_retained_nodes(), num_retained_nodes);
+    assert_eq!(
+        root_hash,
+        hash_internal(leaf1.hash(), hash_leaf(key2, value3.hash()))
+    );
+
+    // Nothing to update.
+    assert_eq!(
+        expected
+            .compute_root_hash_for_updates(vec![], &proof_reader)
+            .unwrap(),
+        expected.root_hash()
+    );
+    // Without the proof, the same error as a real update.
+    assert_eq!(
+        smt.compute_root_hash_for_updates(vec![(key2, &value2)], &ProofReader::new(vec![])),
+        Err(UpdateError::MissingProof { key: key2 })
+    );
+}
+
 #[test]
 fn test_insert_at_persisted_empty() {
     let key1 = HashValue::from_slice(&[0; 32]).unwrap();
@@ -235,6 +280,73 @@ fn test_new_empty() {
     assert_eq!(smt.root_hash(), root_hash);
 }
 
+#[test]
+fn test_num_retained_nodes() {
+    let key1 = HashValue::from_slice(&[0; 32]).unwrap();
+    let key2 = HashValue::from_slice(&[0xff; 32]).unwrap();
+    let value1: StateValue = vec![1].into();
+    let value2: StateValue = vec![2].into();
+    let value3: StateValue = vec![3].into();
+
+    let base = SparseMerkleTree::new_empty();
+    let frozen = base.clone().freeze();
+    assert_eq!(frozen.num_retained_nodes(), 0);
+
+    // A root and two leaves.
+    let smt1 = frozen
+        .batch_update(
+            vec![(key1, &value1), (key2, &value2)],
+            &ProofReader::default(),
+        )
+        .unwrap();
+    assert_eq!(smt1.num_retained_nodes(), 3);
+
+    let smt2 = smt1
+        .batch_update(vec![(key1, &value3)], &ProofReader::default())
+        .unwrap();
+    let num_retained_nodes = smt2.num_retained_nodes();
+    assert!(num_retained_nodes > 3);
+
+    // The superseded nodes live as long as the base does.
+    drop(smt1);
+    assert_eq!(smt2.num_retained_nodes(), num_retained_nodes);
+
+    // Once the base is gone, freezing again retains nothing more.
+    drop(frozen);
+    drop(base);
+    let smt2 = smt2.unfreeze().freeze();
+    assert_eq!(smt2.num_retained_nodes(), 0);
+}
+
+#[test]
+fn test_new_node_counts_since() {
+    let keys: Vec<_> = (0..100).map(|_| HashValue::random()).collect();
+    let values: Vec<StateValue> = (0..100u8).map(|i| vec![i].into()).collect();
+
+    let base = SparseMerkleTree::new_empty().freeze();
+    let smt1 = base
+        .batch_update(keys.iter().cloned().zip(&values), &ProofReader::default())
+        .unwrap();
+    let smt2 = smt1
+        .batch_update(
+            keys[..10].iter().cloned().zip(values[10..20].iter()),
+            &ProofReader::default(),
+        )
+        .unwrap();
+    assert_eq!(smt1.new_node_counts_since(&base).num_leaves, 100);
+    assert_eq!(smt2.new_node_counts_since(&smt1).num_leaves, 10);
+    assert_eq!(smt2.num_retained_generations(), 2);
+
+    for (smt, since_smt) in vec![(&smt1, &base), (&smt2, &smt1), (&smt2, &base)] {
+        let counts = smt.new_node_counts_since(since_smt);
+        assert_eq!(
+            counts.num_node_hashes,
+            smt.new_node_hashes_since(since_smt).len()
+        );
+        assert!(counts.num_internal_nodes >= counts.num_leaves - 1);
+    }
+}
+
 #[test]
 fn test_update() {
     // Before the update, the tree was:
@@ -384,6 +496,16 @@ fn test_update() {
 
     // For smt2, only key1 should be available since smt2 was constructed by updating smt1 with
     // key1.
+    assert_eq!(
+        smt2.batch_get(&[key4, key3, key2, key1, key4]),
+        vec![
+            StateStoreStatus::ExistsInDB,
+            StateStoreStatus::Unknown,
+            StateStoreStatus::Unknown,
+            StateStoreStatus::ExistsInScratchPad(value1.clone()),
+            StateStoreStatus::ExistsInDB,
+        ]
+    );
     assert_eq!(smt2.get(key1), StateStoreStatus::ExistsInScratchPad(value1));
     assert_eq!(smt2.get(key2), StateStoreStatus::Unknown);
     assert_eq!(smt2.get(key3), StateStoreStatus::Unknown);
@@ -561,6 +683,115 @@ fn test_drop() {
     drop(root_smt)
 }
 
+#[test]
+fn test_batch_get_unknown_and_empty() {
+    let keys = [b"a".test_only_hash(), b"b".test_only_hash()];
+    assert_eq!(
+        SparseMerkleTree::new(b"root".test_only_hash()).batch_get(&keys),
+        vec![StateStoreStatus::Unknown, StateStoreStatus::Unknown]
+    );
+    assert_eq!(
+        SparseMerkleTree::new_empty().batch_get(&keys),
+        vec![
+            StateStoreStatus::DoesNotExist,
+            StateStoreStatus::DoesNotExist
+        ]
+    );
+    assert!(SparseMerkleTree::new_empty().batch_get(&[]).is_empty());
+}
+
+proptest! {
+    #[test]
+    fn test_batch_get_matches_get(
+        first_keys in prop::collection::vec(any::<[u8; 32]>(), 1..100),
+        second_keys in prop::collection::vec(any::<[u8; 32]>(), 0..100),
+        other_keys in prop::collection::vec(any::<[u8; 32]>(), 0..100),
+    ) {
+        let value = StateValue::from(b"value".to_vec());
+        let to_key = |bytes: &[u8; 32]| HashValue::new(*bytes);
+        // Two generations, to query nodes of both.
+        let proof_reader = ProofReader::new(vec![]);
+        let smt = SparseMerkleTree::new_empty()
+            .batch_update(first_keys.iter().map(|k| (to_key(k), &value)), &proof_reader)
+            .unwrap()
+            .batch_update(second_keys.iter().map(|k| (to_key(k), &value)), &proof_reader)
+            .unwrap();
+
+        // Keys in the tree, keys that aren't, keys sharing all but their last byte with ones that
+        // are, and a key queried twice.
+        let mut keys: Vec<_> = first_keys
+            .iter()
+            .chain(&second_keys)
+            .chain(&other_keys)
+            .map(to_key)
+            .collect();
+        keys.extend(first_keys.iter().map(|k| update_byte(&to_key(k), 31, !k[31])));
+        keys.push(to_key(&first_keys[0]));
+
+        let expected: Vec<_> = keys.iter().map(|key| smt.get(*key)).collect();
+        prop_assert_eq!(smt.batch_get(&keys), expected);
+    }
+}
+
+#[test]
+fn test_diff_leaf_keys_unknown() {
+    let key = b"a".test_only_hash();
+    let value = StateValue::from(b"value".to_vec());
+    let proof_reader = ProofReader::new(vec![]);
+    let persisted = SparseMerkleTree::new(b"root".test_only_hash()).freeze();
+    let empty = SparseMerkleTree::new_empty().freeze();
+    let updated = empty
+        .batch_update(vec![(key, &value)], &proof_reader)
+        .unwrap();
+
+    assert_eq!(persisted.diff_leaf_keys(&persisted), Some(vec![]));
+    assert_eq!(persisted.diff_leaf_keys(&empty), None);
+    assert_eq!(empty.diff_leaf_keys(&updated), Some(vec![key]));
+    assert_eq!(updated.diff_leaf_keys(&empty), Some(vec![key]));
+}
+
+proptest! {
+    #[test]
+    fn test_diff_leaf_keys(
+        base in prop::collection::btree_map(any::<[u8; 32]>(), 0..3u8, 0..50),
+        left_updates in prop::collection::btree_map(any::<[u8; 32]>(), 0..3u8, 0..50),
+        right_updates in prop::collection::btree_map(any::<[u8; 32]>(), 0..3u8, 0..50),
+    ) {
+        let values: Vec<_> = (0..3u8).map(|v| StateValue::from(vec![v])).collect();
+        let proof_reader = ProofReader::new(vec![]);
+        let update = |smt: &SparseMerkleTree, kvs: &BTreeMap<[u8; 32], u8>| {
+            smt.batch_update(
+                kvs.iter().map(|(k, v)| (HashValue::new(*k), &values[*v as usize])),
+                &proof_reader,
+            )
+            .unwrap()
+        };
+        // Two branches from a common base, updating some of the same keys.
+        let base_smt = update(&SparseMerkleTree::new_empty(), &base);
+        let mut right_updates = right_updates;
+        right_updates.extend(left_updates.iter().take(10));
+        let left = update(&base_smt, &left_updates).freeze();
+        let right = update(&base_smt, &right_updates).freeze();
+
+        let mut left_state = base.clone();
+        left_state.extend(&left_updates);
+        let mut right_state = base;
+        right_state.extend(&right_updates);
+        let mut expected: Vec<_> = left_state
+            .keys()
+            .chain(right_state.keys())
+            .filter(|k| left_state.get(*k) != right_state.get(*k))
+            .map(|k| HashValue::new(*k))
+            .collect();
+        expected.sort();
+        expected.dedup();
+
+        prop_assert_eq!(left.diff_leaf_keys(&right), Some(expected.clone()));
+        prop_assert_eq!(right.diff_leaf_keys(&left), Some(expected));
+        prop_assert_eq!(left.diff_leaf_keys(&left), Some(vec![]));
+    }
+}
+
 proptest! {
     #[test]
     fn test_correctness( input in arb_smt_correctness_case() ) {
@@ -108,6 +108,16 @@ pub fn test_smt_correctness_impl(input: Vec<Action>) {
                     .1;
                 serial_q.back().unwrap().assert_no_external_strong_ref();
 
+                // The dry run gives the same root, without adding any nodes.
+                let frozen = updater_q.back().unwrap().clone().freeze();
+                let num_retained_nodes = frozen.num_retained_nodes();
+                let dry_run_root_hash = frozen
+                    .compute_root_hash_for_updates(updates_flat_batch.clone(), &proof_reader)
+                    .unwrap();
+                assert_eq!(frozen.num_retained_nodes(), num_retained_nodes);
+                drop(frozen);
+                updater_q.back().unwrap().assert_no_external_strong_ref();
+
                 let updater_smt = updater_q
                     .back()
                     .unwrap()
@@ -117,6 +127,7 @@ pub fn test_smt_correctness_impl(input: Vec<Action>) {
 
                 assert_eq!(serial_smt.root_hash(), naive_smt.get_root_hash());
                 assert_eq!(updater_smt.root_hash(), naive_smt.get_root_hash());
+                assert_eq!(dry_run_root_hash, naive_smt.get_root_hash());
 
                 naive_q.push_back(naive_smt);
                 serial_q.push_back(serial_smt);
@@ -13,11 +13,16 @@ use aptos_crypto::{
     hash::{CryptoHash, SPARSE_MERKLE_PLACEHOLDER_HASH},
     HashValue,
 };
-use aptos_types::proof::{SparseMerkleLeafNode, SparseMerkleProof};
+use aptos_types::proof::{SparseMerkleInternalNode, SparseMerkleLeafNode, SparseMerkleProof};
 use std::cmp::Ordering;
 
 type Result<T> = std::result::Result<T, UpdateError>;
 
+// Limit total tasks that are potentially sent to other threads.
+const MAX_PARALLELIZABLE_DEPTH: usize = 8;
+// No point to introduce Rayon overhead if work is small.
+const MIN_PARALLELIZABLE_SIZE: usize = 2;
+
 type InMemSubTree<V> = super::node::SubTree<V>;
 type InMemInternal<V> = super::node::InternalNode<V>;
 
@@ -143,7 +148,9 @@ impl<'a, V: Clone + CryptoHash> SubTreeInfo<'a, V> {
     ) -> Result<Self> {
         let proof = proof_reader
             .get_proof(a_descendant_key)
-            .ok_or(UpdateError::MissingProof)?;
+            .ok_or(UpdateError::MissingProof {
+                key: a_descendant_key,
+            })?;
         if depth > proof.siblings().len() {
             return Err(UpdateError::ShortProof {
                 key: a_descendant_key,
@@ -154,18 +161,23 @@ impl<'a, V: Clone + CryptoHash> SubTreeInfo<'a, V> {
         Ok(Self::new_on_proof_path(proof, depth))
     }
 
-    fn from_in_mem(subtree: &InMemSubTree<V>, generation: u64) -> Self {
+    // With no `generation`, the subtree is only being hashed, so an in-mem leaf is taken as a
+    // persisted one, which has all that's needed to hash it, rather than copied into a new node.
+    fn from_in_mem(subtree: &InMemSubTree<V>, generation: Option<u64>) -> Self {
         match &subtree {
             InMemSubTree::Empty => SubTreeInfo::new_empty(),
             InMemSubTree::NonEmpty { root, .. } => match root.get_if_in_mem() {
-                Some(arc_node) => match arc_node.inner() {
-                    NodeInner::Internal(internal_node) => {
+                Some(arc_node) => match (arc_node.inner(), generation) {
+                    (NodeInner::Internal(internal_node), _) => {
                         SubTreeInfo::InMem(InMemSubTreeInfo::Internal {
                             node: internal_node.clone(),
                             subtree: subtree.weak(),
                         })
                     }
-                    NodeInner::Leaf(leaf_node) => {
+                    (NodeInner::Leaf(leaf_node), None) => SubTreeInfo::new_proof_leaf(
+                        SparseMerkleLeafNode::new(leaf_node.key, leaf_node.value.hash),
+                    ),
+                    (NodeInner::Leaf(leaf_node), Some(generation)) => {
                         // Create a new leaf node with the data pointing to previous version via
                         // weak ref (if exists). This is only necessary when this leaf node is "split"
                         // during update hence changed position in the tree. In contrast, if the
@@ -207,7 +219,7 @@ impl<'a, V: Clone + CryptoHash> SubTreeInfo<'a, V> {
         a_descendent_key: HashValue,
         depth: usize,
         proof_reader: &'a impl ProofRead,
-        generation: u64,
+        generation: Option<u64>,
     ) -> Result<(Self, Self)> {
         let myself = if self.is_unknown() {
             SubTreeInfo::from_persisted(a_descendent_key, depth, proof_reader)?
@@ -283,7 +295,7 @@ impl<'a, V: Send + Sync + Clone + CryptoHash> SubTreeUpdater<'a, V> {
     ) -> Result<InMemSubTree<V>> {
         let updater = Self {
             depth: 0,
-            info: SubTreeInfo::from_in_mem(&root, generation),
+            info: SubTreeInfo::from_in_mem(&root, Some(generation)),
             updates,
             generation,
         };
@@ -291,11 +303,6 @@ impl<'a, V: Send + Sync + Clone + CryptoHash> SubTreeUpdater<'a, V> {
     }
 
     fn run(self, proof_reader: &impl ProofRead) -> Result<InMemSubTreeInfo<V>> {
-        // Limit total tasks that are potentially sent to other threads.
-        const MAX_PARALLELIZABLE_DEPTH: usize = 8;
-        // No point to introduce Rayon overhead if work is small.
-        const MIN_PARALLELIZABLE_SIZE: usize = 2;
-
         let generation = self.generation;
         let depth = self.depth;
         match self.maybe_end_recursion() {
@@ -346,9 +353,12 @@ impl<'a, V: Send + Sync + Clone + CryptoHash> SubTreeUpdater<'a, V> {
         let pivot = partition(self.updates, self.depth);
         let (left_updates, right_updates) = self.updates.split_at(pivot);
         let generation = self.generation;
-        let (left_info, right_info) =
-            self.info
-                .into_children(self.updates[0].0, self.depth, proof_reader, generation)?;
+        let (left_info, right_info) = self.info.into_children(
+            self.updates[0].0,
+            self.depth,
+            proof_reader,
+            Some(generation),
+        )?;
 
         Ok((
             Self {
@@ -366,3 +376,147 @@ impl<'a, V: Send + Sync + Clone + CryptoHash> SubTreeUpdater<'a, V> {
         ))
     }
 }
+
+/// A subtree after the updates, as far as its hash goes.
+enum HashedSubTree {
+    Empty,
+    Leaf { hash: HashValue },
+    Other { hash: HashValue },
+}
+
+impl HashedSubTree {
+    fn hash(&self) -> HashValue {
+        match self {
+            Self::Empty => *SPARSE_MERKLE_PLACEHOLDER_HASH,
+            Self::Leaf { hash } | Self::Other { hash } => *hash,
+        }
+    }
+
+    fn new_leaf(key: HashValue, value_hash: HashValue) -> Self {
+        Self::Leaf {
+            hash: SparseMerkleLeafNode::new(key, value_hash).hash(),
+        }
+    }
+
+    // As `InMemSubTreeInfo::combine`.
+    fn combine(left: Self, right: Self) -> Self {
+        match (&left, &right) {
+            (Self::Empty, Self::Leaf { .. }) => right,
+            (Self::Leaf { .. }, Self::Empty) => left,
+            (Self::Empty, Self::Empty) => unreachable!(),
+            _ => Self::Other {
+                hash: SparseMerkleInternalNode::new(left.hash(), right.hash()).hash(),
+            },
+        }
+    }
+}
+
+/// Computes the root hash `SubTreeUpdater` would give the subtree, the same way it does, but
+/// without creating any nodes.
+pub struct SubTreeHasher<'a, V> {
+    depth: usize,
+    info: SubTreeInfo<'a, V>,
+    updates: &'a [(HashValue, &'a V)],
+}
+
+impl<'a, V: Send + Sync + Clone + CryptoHash> SubTreeHasher<'a, V> {
+    pub(crate) fn root_hash(
+        root: InMemSubTree<V>,
+        updates: &'a [(HashValue, &'a V)],
+        proof_reader: &'a impl ProofRead,
+    ) -> Result<HashValue> {
+        let hasher = Self {
+            depth: 0,
+            info: SubTreeInfo::from_in_mem(&root, None),
+            updates,
+        };
+        Ok(hasher.run(proof_reader)?.hash())
+    }
+
+    fn run(self, proof_reader: &impl ProofRead) -> Result<HashedSubTree> {
+        match self.maybe_end_recursion() {
+            Either::A(ended) => Ok(ended),
+            Either::B(myself) => {
+                let depth = myself.depth;
+                let (left, right) = myself.into_children(proof_reader)?;
+                let (left_ret, right_ret) = if depth <= MAX_PARALLELIZABLE_DEPTH
+                    && left.updates.len() >= MIN_PARALLELIZABLE_SIZE
+                    && right.updates.len() >= MIN_PARALLELIZABLE_SIZE
+                {
+                    rayon::join(|| left.run(proof_reader), || right.run(proof_reader))
+                } else {
+                    (left.run(proof_reader), right.run(proof_reader))
+                };
+
+                Ok(HashedSubTree::combine(left_ret?, right_ret?))
+            }
+        }
+    }
+
+    // As `SubTreeUpdater::maybe_end_recursion`. In-mem leaves are taken as persisted ones here.
+    fn maybe_end_recursion(self) -> Either<HashedSubTree, Self> {
+        match self.updates.len() {
+            0 => Either::A(Self::hashed(self.info)),
+            1 => {
+                let (key, value) = self.updates[0];
+                match &self.info {
+                    SubTreeInfo::InMem(InMemSubTreeInfo::Empty) => {
+                        Either::A(HashedSubTree::new_leaf(key, value.hash()))
+                    }
+                    SubTreeInfo::Persisted(PersistedSubTreeInfo::Leaf { leaf }) => Either::or(
+                        leaf.key() == key,
+                        HashedSubTree::new_leaf(key, value.hash()),
+                        self,
+                    ),
+                    _ => Either::B(self),
+                }
+            }
+            _ => Either::B(self),
+        }
+    }
+
+    // What the subtree hashes to with no updates under it, as `SubTreeInfo::materialize` would
+    // leave it.
+    fn hashed(info: SubTreeInfo<'a, V>) -> HashedSubTree {
+        match info {
+            SubTreeInfo::InMem(InMemSubTreeInfo::Empty) => HashedSubTree::Empty,
+            SubTreeInfo::InMem(InMemSubTreeInfo::Leaf { subtree, .. }) => HashedSubTree::Leaf {
+                hash: subtree.hash(),
+            },
+            SubTreeInfo::InMem(InMemSubTreeInfo::Internal { subtree, .. })
+            | SubTreeInfo::InMem(InMemSubTreeInfo::Unknown { subtree }) => HashedSubTree::Other {
+                hash: subtree.hash(),
+            },
+            SubTreeInfo::Persisted(PersistedSubTreeInfo::Leaf { leaf }) => {
+                HashedSubTree::Leaf { hash: leaf.hash() }
+            }
+            SubTreeInfo::Persisted(PersistedSubTreeInfo::ProofSibling { hash }) => {
+                HashedSubTree::Other { hash }
+            }
+            SubTreeInfo::Persisted(PersistedSubTreeInfo::ProofPathInternal { .. }) => {
+                unreachable!()
+            }
+        }
+    }
+
+    fn into_children(self, proof_reader: &'a impl ProofRead) -> Result<(Self, Self)> {
+        let pivot = partition(self.updates, self.depth);
+        let (left_updates, right_updates) = self.updates.split_at(pivot);
+        let (left_info, right_info) =
+            self.info
+                .into_children(self.updates[0].0, self.depth, proof_reader, None)?;
+
+        Ok((
+            Self {
+                depth: self.depth + 1,
+                info: left_info,
+                updates: left_updates,
+            },
+            Self {
+                depth: self.depth + 1,
+                info: right_info,
+                updates: right_updates,
+            },
+        ))
+    }
+}
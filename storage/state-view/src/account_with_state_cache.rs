@@ -5,17 +5,17 @@ use aptos_types::{
     account_view::AccountView,
     state_store::{state_key::StateKey, state_value::StateValue},
 };
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 pub struct AccountWithStateCache<'a> {
     account_address: &'a AccountAddress,
-    state_cache: &'a HashMap<StateKey, StateValue>,
+    state_cache: &'a HashMap<StateKey, Option<Arc<StateValue>>>,
 }
 
 impl<'a> AccountWithStateCache<'a> {
     pub fn new(
         account_address: &'a AccountAddress,
-        state_cache: &'a HashMap<StateKey, StateValue>,
+        state_cache: &'a HashMap<StateKey, Option<Arc<StateValue>>>,
     ) -> Self {
         Self {
             account_address,
@@ -29,7 +29,7 @@ impl<'a> AccountView for AccountWithStateCache<'a> {
         Ok(self
             .state_cache
             .get(state_key)
-            .and_then(|x| x.maybe_bytes.clone()))
+            .and_then(|x| x.as_ref().and_then(|value| value.maybe_bytes.clone())))
     }
 
     fn get_account_address(&self) -> anyhow::Result<Option<AccountAddress>> {
@@ -44,7 +44,7 @@ pub trait AsAccountWithStateCache<'a> {
     ) -> AccountWithStateCache;
 }
 
-impl<'a> AsAccountWithStateCache<'a> for HashMap<StateKey, StateValue> {
+impl<'a> AsAccountWithStateCache<'a> for HashMap<StateKey, Option<Arc<StateValue>>> {
     fn as_account_with_state_cache(
         &'a self,
         account_address: &'a AccountAddress,
@@ -16,7 +16,11 @@ use parking_lot::RwLock;
 use scratchpad::{FrozenSparseMerkleTree, SparseMerkleTree, StateStoreStatus};
 use std::{
     collections::{HashMap, HashSet},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
 /// `CachedStateView` is like a snapshot of the global state comprised of state view at two
@@ -70,10 +74,15 @@ pub struct CachedStateView {
     /// Cache of state key to state value, which is used in case of fine grained storage object.
     /// Eventually this should replace the `account_to_state_cache` as we deprecate account state blob
     /// completely and migrate to fine grained storage. A value of None in this cache reflects that
-    /// the corresponding key has been deleted. This is a temporary hack until we support deletion
-    /// in JMT node.
-    state_cache: RwLock<HashMap<StateKey, StateValue>>,
+    /// the corresponding key doesn't exist, or has been deleted.
+    state_cache: RwLock<HashMap<StateKey, Option<Arc<StateValue>>>>,
     proof_fetcher: Arc<dyn ProofFetcher>,
+
+    /// Counters for `StateCacheStats`.
+    num_cache_hits: AtomicUsize,
+    num_speculative_reads: AtomicUsize,
+    num_storage_reads: AtomicUsize,
+    storage_read_nanos: AtomicU64,
 }
 
 impl CachedStateView {
@@ -99,6 +108,10 @@ impl CachedStateView {
             speculative_state,
             state_cache: RwLock::new(HashMap::new()),
             proof_fetcher,
+            num_cache_hits: AtomicUsize::new(0),
+            num_speculative_reads: AtomicUsize::new(0),
+            num_storage_reads: AtomicUsize::new(0),
+            storage_read_nanos: AtomicU64::new(0),
         })
     }
 
@@ -112,11 +125,39 @@ impl CachedStateView {
             .try_for_each(|key| self.get_state_value(key).map(|_| ()))
     }
 
+    /// The reads served so far, and what's cached. The number of proofs is only known once the
+    /// proof fetcher is done, so it's left 0 until `into_state_cache()`.
+    pub fn stats(&self) -> StateCacheStats {
+        let state_cache = self.state_cache.read();
+        StateCacheStats {
+            num_cache_hits: self.num_cache_hits.load(Ordering::Relaxed),
+            num_speculative_reads: self.num_speculative_reads.load(Ordering::Relaxed),
+            num_storage_reads: self.num_storage_reads.load(Ordering::Relaxed),
+            storage_read_time: Duration::from_nanos(
+                self.storage_read_nanos.load(Ordering::Relaxed),
+            ),
+            num_proofs: 0,
+            num_cached_keys: state_cache.len(),
+            cached_bytes: state_cache
+                .values()
+                .flatten()
+                .filter_map(|value| value.maybe_bytes.as_ref())
+                .map(Vec::len)
+                .sum(),
+        }
+    }
+
     pub fn into_state_cache(self) -> StateCache {
+        let proofs = self.proof_fetcher.get_proof_cache();
+        let stats = StateCacheStats {
+            num_proofs: proofs.len(),
+            ..self.stats()
+        };
         StateCache {
             frozen_base: self.speculative_state,
             state_cache: self.state_cache.into_inner(),
-            proofs: self.proof_fetcher.get_proof_cache(),
+            proofs,
+            stats,
         }
     }
 
@@ -124,44 +165,165 @@ impl CachedStateView {
         // Do most of the work outside the write lock.
         let key_hash = state_key.hash();
         let state_value_option = match self.speculative_state.get(key_hash) {
-            StateStoreStatus::ExistsInScratchPad(value) => Some(value),
-            StateStoreStatus::DoesNotExist => None,
+            StateStoreStatus::ExistsInScratchPad(value) => {
+                self.num_speculative_reads.fetch_add(1, Ordering::Relaxed);
+                Some(value)
+            }
+            StateStoreStatus::DoesNotExist => {
+                self.num_speculative_reads.fetch_add(1, Ordering::Relaxed);
+                None
+            }
             // No matter it is in db or unknown, we have to query from db since even the
             // former case, we don't have the blob data but only its hash.
-            StateStoreStatus::ExistsInDB | StateStoreStatus::Unknown => {
-                match self.snapshot {
-                    Some((version, root_hash)) => {
-                        let (value, proof) = self
-                            .proof_fetcher
-                            .fetch_state_value_and_proof(state_key, version)?;
-                        // TODO: proof verification can be opted out, for performance
-                        if let Some(proof) = proof {
-                            proof
-                                .verify(root_hash, key_hash, value.as_ref())
-                                .map_err(|err| {
-                                    format_err!(
-                                    "Proof is invalid for key {:?} with state root hash {:?}: {}",
-                                    state_key,
-                                    root_hash,
-                                    err
-                                )
-                                })?;
-                        }
-                        value
-                    }
-                    None => None,
+            StateStoreStatus::ExistsInDB | StateStoreStatus::Unknown => match self.snapshot {
+                Some((version, root_hash)) => {
+                    let timer = Instant::now();
+                    let (value, proof) = self
+                        .proof_fetcher
+                        .fetch_state_value_and_proof(state_key, version)?;
+                    self.num_storage_reads.fetch_add(1, Ordering::Relaxed);
+                    self.storage_read_nanos
+                        .fetch_add(timer.elapsed().as_nanos() as u64, Ordering::Relaxed);
+                    Self::verify_proof(state_key, key_hash, root_hash, value.as_ref(), proof)?;
+                    value
                 }
-            }
+                None => {
+                    self.num_speculative_reads.fetch_add(1, Ordering::Relaxed);
+                    None
+                }
+            },
         };
 
         Ok(state_value_option)
     }
+
+    /// Reads `keys` into the cache ahead of execution. The ones the speculative state doesn't
+    /// resolve are fetched from storage along with their proofs in one batch, rather than one at
+    /// a time as execution first reads them.
+    pub fn prefetch(&self, keys: &[StateKey]) -> Result<()> {
+        let keys: Vec<_> = {
+            let state_cache = self.state_cache.read();
+            keys.iter()
+                .filter(|key| !state_cache.contains_key(key))
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .cloned()
+                .collect()
+        };
+        let key_hashes: Vec<_> = keys.iter().map(|key| key.hash()).collect();
+
+        let mut values = Vec::with_capacity(keys.len());
+        let mut to_fetch = Vec::new();
+        for ((state_key, key_hash), status) in keys
+            .into_iter()
+            .zip(key_hashes.iter().copied())
+            .zip(self.speculative_state.batch_get(&key_hashes))
+        {
+            match (status, self.snapshot) {
+                (StateStoreStatus::ExistsInScratchPad(value), _) => {
+                    values.push((state_key, Some(value)))
+                }
+                (StateStoreStatus::ExistsInDB | StateStoreStatus::Unknown, Some(_)) => {
+                    to_fetch.push((state_key, key_hash))
+                }
+                (StateStoreStatus::DoesNotExist, _)
+                | (StateStoreStatus::ExistsInDB | StateStoreStatus::Unknown, None) => {
+                    values.push((state_key, None))
+                }
+            }
+        }
+        self.num_speculative_reads
+            .fetch_add(values.len(), Ordering::Relaxed);
+
+        if !to_fetch.is_empty() {
+            let (version, root_hash) = self
+                .snapshot
+                .expect("Keys are only fetched from a snapshot.");
+            let state_keys: Vec<_> = to_fetch.iter().map(|(key, _)| key.clone()).collect();
+            let timer = Instant::now();
+            let fetched = self
+                .proof_fetcher
+                .fetch_state_values_and_proofs(&state_keys, version)?;
+            self.num_storage_reads
+                .fetch_add(state_keys.len(), Ordering::Relaxed);
+            self.storage_read_nanos
+                .fetch_add(timer.elapsed().as_nanos() as u64, Ordering::Relaxed);
+            for ((state_key, key_hash), (value, proof)) in to_fetch.into_iter().zip(fetched) {
+                Self::verify_proof(&state_key, key_hash, root_hash, value.as_ref(), proof)?;
+                values.push((state_key, value));
+            }
+        }
+
+        let mut cache = self.state_cache.write();
+        for (state_key, value) in values {
+            cache
+                .entry(state_key)
+                .or_insert_with(|| value.map(Arc::new));
+        }
+        Ok(())
+    }
+
+    fn verify_proof(
+        state_key: &StateKey,
+        key_hash: HashValue,
+        root_hash: HashValue,
+        value: Option<&StateValue>,
+        proof: Option<SparseMerkleProof>,
+    ) -> Result<()> {
+        // TODO: proof verification can be opted out, for performance
+        if let Some(proof) = proof {
+            proof.verify(root_hash, key_hash, value).map_err(|err| {
+                format_err!(
+                    "Proof is invalid for key {:?} with state root hash {:?}: {}",
+                    state_key,
+                    root_hash,
+                    err
+                )
+            })?;
+        }
+        Ok(())
+    }
 }
 
 pub struct StateCache {
     pub frozen_base: FrozenSparseMerkleTree<StateValue>,
-    pub state_cache: HashMap<StateKey, StateValue>,
+    pub state_cache: HashMap<StateKey, Option<Arc<StateValue>>>,
     pub proofs: HashMap<HashValue, SparseMerkleProof>,
+    pub stats: StateCacheStats,
+}
+
+/// How the reads through a `CachedStateView` were served, and how big its cache got, which is
+/// worth knowing when tuning block sizes.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct StateCacheStats {
+    /// Reads of keys already in the cache.
+    pub num_cache_hits: usize,
+    /// Reads resolved by the speculative state, including keys known not to exist.
+    pub num_speculative_reads: usize,
+    /// Reads that went to storage, through the proof fetcher.
+    pub num_storage_reads: usize,
+    /// The time spent in the proof fetcher on those reads.
+    pub storage_read_time: Duration,
+    /// The proofs fetched.
+    pub num_proofs: usize,
+    pub num_cached_keys: usize,
+    /// The bytes of the values in the cache.
+    pub cached_bytes: usize,
+}
+
+impl StateCacheStats {
+    /// The totals over both caches.
+    pub fn combine(self, rhs: Self) -> Self {
+        Self {
+            num_cache_hits: self.num_cache_hits + rhs.num_cache_hits,
+            num_speculative_reads: self.num_speculative_reads + rhs.num_speculative_reads,
+            num_storage_reads: self.num_storage_reads + rhs.num_storage_reads,
+            storage_read_time: self.storage_read_time + rhs.storage_read_time,
+            num_proofs: self.num_proofs + rhs.num_proofs,
+            num_cached_keys: self.num_cached_keys + rhs.num_cached_keys,
+            cached_bytes: self.cached_bytes + rhs.cached_bytes,
+        }
+    }
 }
 
 impl StateView for CachedStateView {
@@ -172,16 +334,21 @@ impl StateView for CachedStateView {
     fn get_state_value(&self, state_key: &StateKey) -> Result<Option<Vec<u8>>> {
         // First check if the cache has the state value.
         if let Some(contents) = self.state_cache.read().get(state_key) {
+            self.num_cache_hits.fetch_add(1, Ordering::Relaxed);
             // This can return None, which means the value has been deleted from the DB.
-            return Ok(contents.maybe_bytes.as_ref().cloned());
+            return Ok(contents
+                .as_ref()
+                .and_then(|value| value.maybe_bytes.clone()));
         }
         let state_value_option = self.get_state_value_internal(state_key)?;
         // Update the cache if still empty
         let mut cache = self.state_cache.write();
         let new_value = cache
             .entry(state_key.clone())
-            .or_insert_with(|| state_value_option.unwrap_or_default());
-        Ok(new_value.maybe_bytes.as_ref().cloned())
+            .or_insert_with(|| state_value_option.map(Arc::new));
+        Ok(new_value
+            .as_ref()
+            .and_then(|value| value.maybe_bytes.clone()))
     }
 
     fn is_genesis(&self) -> bool {
@@ -32,8 +32,9 @@ use aptos_types::{
     },
     write_set::WriteSet,
 };
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, sync::Arc};
+use std::{borrow::Borrow, collections::HashMap, sync::Arc};
 use thiserror::Error;
 
 pub mod async_proof_fetcher;
@@ -41,6 +42,7 @@ pub mod cached_state_view;
 mod executed_trees;
 #[cfg(any(feature = "fuzzing"))]
 pub mod mock;
+pub mod proof_cache;
 pub mod proof_fetcher;
 pub mod state_delta;
 pub mod state_view;
@@ -439,6 +441,21 @@ pub trait DbReader: Send + Sync {
         unimplemented!()
     }
 
+    /// Gets the state values of `state_keys` along with their proofs, as
+    /// `get_state_value_with_proof_by_version` does for each of them, in one call.
+    ///
+    /// This is used by aptos core (executor) internally, to prefetch the state a block reads.
+    fn get_state_values_with_proof_by_version(
+        &self,
+        state_keys: &[StateKey],
+        version: Version,
+    ) -> Result<Vec<(Option<StateValue>, SparseMerkleProof)>> {
+        state_keys
+            .iter()
+            .map(|state_key| self.get_state_value_with_proof_by_version(state_key, version))
+            .collect()
+    }
+
     /// Gets the latest ExecutedTrees no matter if db has been bootstrapped.
     /// Used by the Db-bootstrapper.
     fn get_latest_executed_trees(&self) -> Result<ExecutedTrees> {
@@ -737,12 +754,21 @@ impl SaveTransactionsRequest {
     }
 }
 
-pub fn jmt_updates(
-    state_updates: &HashMap<StateKey, StateValue>,
+/// Neither the JMT nor the in-memory SMT can remove a leaf yet, so a deleted state item is
+/// recorded in the trees (and in the DB) as a leaf holding the empty value.
+static DELETED_STATE_VALUE: Lazy<StateValue> = Lazy::new(StateValue::empty);
+
+/// The value to record in the state trees and the DB for an update, where `None` is a deletion.
+pub fn state_value_or_deleted<V: Borrow<StateValue>>(value: &Option<V>) -> &StateValue {
+    value.as_ref().map_or(&*DELETED_STATE_VALUE, Borrow::borrow)
+}
+
+pub fn jmt_updates<V: Borrow<StateValue>>(
+    state_updates: &HashMap<StateKey, Option<V>>,
 ) -> Vec<(HashValue, (HashValue, StateKey))> {
     state_updates
         .iter()
-        .map(|(k, v)| (k.hash(), (v.hash(), (*k).clone())))
+        .map(|(k, v)| (k.hash(), (state_value_or_deleted(v).hash(), (*k).clone())))
         .collect()
 }
 
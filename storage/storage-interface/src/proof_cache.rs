@@ -0,0 +1,135 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+use aptos_crypto::HashValue;
+use aptos_types::{proof::SparseMerkleProof, transaction::Version};
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Proofs fetched from storage, kept across the state views of consecutive chunks so that a key
+/// read by several of them is only proven once against the same persisted snapshot.
+///
+/// Proofs are keyed by the version of the snapshot they were fetched against. Once a lookup or an
+/// insertion refers to a later snapshot, the proofs against earlier ones can never be used again
+/// and are dropped.
+#[derive(Default)]
+pub struct ProofCache {
+    inner: Mutex<ProofCacheInner>,
+    num_hits: AtomicUsize,
+    num_misses: AtomicUsize,
+}
+
+#[derive(Default)]
+struct ProofCacheInner {
+    latest_version: Option<Version>,
+    proofs: HashMap<(Version, HashValue), SparseMerkleProof>,
+}
+
+impl ProofCacheInner {
+    /// Drops the proofs against snapshots before `version`, if it is the latest one seen so far.
+    /// Returns false if `version` is already behind the latest snapshot.
+    fn advance_to(&mut self, version: Version) -> bool {
+        match self.latest_version {
+            Some(latest_version) if version < latest_version => false,
+            Some(latest_version) if version == latest_version => true,
+            _ => {
+                self.proofs.retain(|(v, _), _| *v >= version);
+                self.latest_version = Some(version);
+                true
+            }
+        }
+    }
+}
+
+impl ProofCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the proof of the key with hash `key_hash` against the snapshot at `version`, if it
+    /// was fetched before.
+    pub fn get(&self, version: Version, key_hash: HashValue) -> Option<SparseMerkleProof> {
+        let proof = {
+            let mut inner = self.inner.lock();
+            if inner.advance_to(version) {
+                inner.proofs.get(&(version, key_hash)).cloned()
+            } else {
+                None
+            }
+        };
+        match proof {
+            Some(_) => self.num_hits.fetch_add(1, Ordering::Relaxed),
+            None => self.num_misses.fetch_add(1, Ordering::Relaxed),
+        };
+        proof
+    }
+
+    /// Remembers the proofs fetched against the snapshot at `version`. Ignored if a later
+    /// snapshot has been seen already.
+    pub fn extend(
+        &self,
+        version: Version,
+        proofs: impl IntoIterator<Item = (HashValue, SparseMerkleProof)>,
+    ) {
+        let mut inner = self.inner.lock();
+        if inner.advance_to(version) {
+            inner.proofs.extend(
+                proofs
+                    .into_iter()
+                    .map(|(key_hash, proof)| ((version, key_hash), proof)),
+            );
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().proofs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the numbers of lookups that hit and missed since the last call.
+    pub fn take_lookup_counts(&self) -> (usize, usize) {
+        (
+            self.num_hits.swap(0, Ordering::Relaxed),
+            self.num_misses.swap(0, Ordering::Relaxed),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proof() -> SparseMerkleProof {
+        SparseMerkleProof::new(None, vec![])
+    }
+
+    #[test]
+    fn test_invalidated_by_later_snapshot() {
+        let cache = ProofCache::new();
+        let (key1, key2) = (HashValue::random(), HashValue::random());
+
+        cache.extend(1, vec![(key1, proof())]);
+        assert!(cache.get(1, key1).is_some());
+        assert!(cache.get(1, key2).is_none());
+
+        // A lookup against a later snapshot drops the proofs against the earlier one.
+        assert!(cache.get(2, key1).is_none());
+        assert!(cache.is_empty());
+        assert!(cache.get(1, key1).is_none());
+
+        // Proofs against an earlier snapshot are no longer accepted.
+        cache.extend(1, vec![(key1, proof())]);
+        assert!(cache.is_empty());
+        cache.extend(2, vec![(key2, proof())]);
+        assert!(cache.get(2, key2).is_some());
+
+        assert_eq!(cache.take_lookup_counts(), (2, 3));
+        assert_eq!(cache.take_lookup_counts(), (0, 0));
+    }
+}
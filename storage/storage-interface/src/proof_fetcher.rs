@@ -18,6 +18,19 @@ pub trait ProofFetcher: Sync + Send {
         version: Version,
     ) -> anyhow::Result<(Option<StateValue>, Option<SparseMerkleProof>)>;
 
+    /// API to fetch the state values of many keys along with proofs, in as few storage round
+    /// trips as the implementation allows. By default, they're fetched one at a time.
+    fn fetch_state_values_and_proofs(
+        &self,
+        state_keys: &[StateKey],
+        version: Version,
+    ) -> anyhow::Result<Vec<(Option<StateValue>, Option<SparseMerkleProof>)>> {
+        state_keys
+            .iter()
+            .map(|state_key| self.fetch_state_value_and_proof(state_key, version))
+            .collect()
+    }
+
     /// API to return all the proofs fetched by the proof fetcher so far.
     fn get_proof_cache(&self) -> HashMap<HashValue, SparseMerkleProof>;
 }
@@ -1,13 +1,18 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use aptos_crypto::HashValue;
+use aptos_crypto::{hash::CryptoHash, HashValue};
 use aptos_types::{
-    state_store::{state_key::StateKey, state_value::StateValue},
+    state_store::{
+        state_key::StateKey, state_storage_usage::StateStorageUsage, state_value::StateValue,
+    },
     transaction::Version,
 };
-use scratchpad::SparseMerkleTree;
-use std::collections::HashMap;
+use scratchpad::{SparseMerkleTree, StateStoreStatus};
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+};
 
 /// This represents two state sparse merkle trees at their versions in memory with the updates
 /// reflecting the difference of `current` on top of `base`.
@@ -15,14 +20,38 @@ use std::collections::HashMap;
 /// The `base` is the state SMT that current is based on.
 /// The `current` is the state SMT that results from applying udpates_since_base on top of `base`.
 /// `updates_since_base` tracks all those key-value pairs that's changed since `base`, useful
-///  when the next checkpoint is calculated.
+///  when the next checkpoint is calculated. A `None` value means the key has been deleted. It's
+///  shared between clones and only copied when one of them changes it, see `branch()`.
+/// `base_usage` and `current_usage` are the storage usage of `base` and `current`.
 #[derive(Clone, Debug)]
 pub struct StateDelta {
     pub base: SparseMerkleTree<StateValue>,
     pub base_version: Option<Version>,
     pub current: SparseMerkleTree<StateValue>,
     pub current_version: Option<Version>,
-    pub updates_since_base: HashMap<StateKey, StateValue>,
+    pub updates_since_base: Arc<HashMap<StateKey, Option<Arc<StateValue>>>>,
+    pub base_usage: StateStorageUsage,
+    pub current_usage: StateStorageUsage,
+}
+
+/// How the current states of two `StateDelta`s differ, e.g. those of two speculative branches
+/// off the same block, as far as can be told from memory. See `StateDelta::diff`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StateDiff {
+    /// Keys updated since the base in `self` but not in `other`, sorted.
+    pub only_updated_in_self: Vec<StateKey>,
+    /// Keys updated since the base in `other` but not in `self`, sorted.
+    pub only_updated_in_other: Vec<StateKey>,
+    /// Keys updated since the base on either side whose current values differ, sorted.
+    pub different_values: Vec<StateKey>,
+    /// Keys updated since the base on either side whose current value on the other side is not
+    /// in memory, so they may or may not differ, sorted.
+    pub unresolved: Vec<StateKey>,
+    /// Hashes of the keys whose values differ between the two bases, sorted, or `None` if the
+    /// bases differ in subtrees that are not in memory.
+    pub base_key_hashes: Option<Vec<HashValue>>,
+    /// The root hashes of the current states of `self` and `other`.
+    pub root_hashes: (HashValue, HashValue),
 }
 
 impl StateDelta {
@@ -31,7 +60,9 @@ impl StateDelta {
         base_version: Option<Version>,
         current: SparseMerkleTree<StateValue>,
         current_version: Option<Version>,
-        updates_since_base: HashMap<StateKey, StateValue>,
+        updates_since_base: impl Into<Arc<HashMap<StateKey, Option<Arc<StateValue>>>>>,
+        base_usage: StateStorageUsage,
+        current_usage: StateStorageUsage,
     ) -> Self {
         assert!(base_version.map_or(0, |v| v + 1) <= current_version.map_or(0, |v| v + 1));
         Self {
@@ -39,31 +70,72 @@ impl StateDelta {
             base_version,
             current,
             current_version,
-            updates_since_base,
+            updates_since_base: updates_since_base.into(),
+            base_usage,
+            current_usage,
         }
     }
 
     pub fn new_empty() -> Self {
         let smt = SparseMerkleTree::new_empty();
-        Self::new(smt.clone(), None, smt, None, HashMap::new())
+        Self::new(
+            smt.clone(),
+            None,
+            smt,
+            None,
+            HashMap::new(),
+            StateStorageUsage::zero(),
+            StateStorageUsage::zero(),
+        )
     }
 
+    /// The usage of a state loaded from a checkpoint is unknown, unless it's the empty state.
     pub fn new_at_checkpoint(root_hash: HashValue, checkpoint_version: Option<Version>) -> Self {
         let smt = SparseMerkleTree::new(root_hash);
+        let usage = if checkpoint_version.is_none() {
+            StateStorageUsage::zero()
+        } else {
+            StateStorageUsage::new_untracked()
+        };
         Self::new(
             smt.clone(),
             checkpoint_version,
             smt,
             checkpoint_version,
             HashMap::new(),
+            usage,
+            usage,
         )
     }
 
+    /// A copy of this delta to build on independently of it and of its other branches, e.g. for
+    /// the calculator of one of several sibling blocks. It's cheap: the SMTs are shared, and so
+    /// are the updates since the base until either side changes them.
+    pub fn branch(&self) -> Self {
+        self.clone()
+    }
+
+    /// The updates since the base, to be changed. Copies them first if they are shared with
+    /// another branch.
+    pub fn updates_since_base_mut(&mut self) -> &mut HashMap<StateKey, Option<Arc<StateValue>>> {
+        Arc::make_mut(&mut self.updates_since_base)
+    }
+
     pub fn merge(&mut self, other: StateDelta) {
         assert!(other.follow(self));
-        self.updates_since_base.extend(other.updates_since_base);
+        if self.updates_since_base.is_empty() {
+            self.updates_since_base = other.updates_since_base;
+        } else {
+            self.updates_since_base_mut().extend(
+                other
+                    .updates_since_base
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.clone())),
+            );
+        }
         self.current = other.current;
         self.current_version = other.current_version;
+        self.current_usage = other.current_usage;
     }
 
     pub fn follow(&self, other: &StateDelta) -> bool {
@@ -75,6 +147,81 @@ impl StateDelta {
             && self.current.has_same_root_hash(&other.current)
     }
 
+    /// Compares the current state of this delta with that of `other`, typically a sibling
+    /// branch. Only the keys updated since the bases are compared by value, with the ones not
+    /// updated on one side resolved through that side's current SMT. If the bases differ, keys
+    /// changed between them are found by walking the two base SMTs, but are only known by hash.
+    pub fn diff(&self, other: &StateDelta) -> StateDiff {
+        let self_keys: BTreeSet<_> = self.updates_since_base.keys().collect();
+        let other_keys: BTreeSet<_> = other.updates_since_base.keys().collect();
+        let all_keys: Vec<_> = self_keys.union(&other_keys).copied().collect();
+        let self_values = self.resolve_current(&all_keys);
+        let other_values = other.resolve_current(&all_keys);
+
+        let mut different_values = Vec::new();
+        let mut unresolved = Vec::new();
+        for ((key, self_value), other_value) in all_keys.iter().zip(self_values).zip(other_values) {
+            match (self_value, other_value) {
+                (Some(self_value), Some(other_value)) => {
+                    if self_value != other_value {
+                        different_values.push((*key).clone());
+                    }
+                }
+                _ => unresolved.push((*key).clone()),
+            }
+        }
+
+        let base_key_hashes = if self.base.has_same_root_hash(&other.base) {
+            Some(Vec::new())
+        } else {
+            self.base
+                .clone()
+                .freeze()
+                .diff_leaf_keys(&other.base.clone().freeze())
+        };
+
+        StateDiff {
+            only_updated_in_self: self_keys
+                .difference(&other_keys)
+                .map(|key| (*key).clone())
+                .collect(),
+            only_updated_in_other: other_keys
+                .difference(&self_keys)
+                .map(|key| (*key).clone())
+                .collect(),
+            different_values,
+            unresolved,
+            base_key_hashes,
+            root_hashes: (self.root_hash(), other.root_hash()),
+        }
+    }
+
+    // Returns the current value of each key (`Some(None)` if it doesn't exist), or `None` if it's
+    // not in memory.
+    fn resolve_current(&self, keys: &[&StateKey]) -> Vec<Option<Option<StateValue>>> {
+        let not_updated: Vec<_> = keys
+            .iter()
+            .filter(|key| !self.updates_since_base.contains_key(**key))
+            .map(|key| key.hash())
+            .collect();
+        let mut statuses = self
+            .current
+            .clone()
+            .freeze()
+            .batch_get(&not_updated)
+            .into_iter();
+        keys.iter()
+            .map(|key| match self.updates_since_base.get(*key) {
+                Some(value) => Some(value.as_deref().cloned()),
+                None => match statuses.next().expect("Every key not updated is queried.") {
+                    StateStoreStatus::ExistsInScratchPad(value) => Some(Some(value)),
+                    StateStoreStatus::DoesNotExist => Some(None),
+                    StateStoreStatus::ExistsInDB | StateStoreStatus::Unknown => None,
+                },
+            })
+            .collect()
+    }
+
     pub fn base_root_hash(&self) -> HashValue {
         self.base.root_hash()
     }
@@ -83,3 +230,112 @@ impl StateDelta {
         self.current.root_hash()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_types::proof::SparseMerkleProof;
+    use scratchpad::ProofRead;
+
+    struct EmptyProofReader;
+
+    impl ProofRead for EmptyProofReader {
+        fn get_proof(&self, _key: HashValue) -> Option<&SparseMerkleProof> {
+            None
+        }
+    }
+
+    fn key(name: &str) -> StateKey {
+        StateKey::Raw(name.as_bytes().to_vec())
+    }
+
+    fn value(content: &str) -> StateValue {
+        StateValue::from(content.as_bytes().to_vec())
+    }
+
+    fn branch(
+        base: &StateDelta,
+        version: Version,
+        updates: Vec<(StateKey, StateValue)>,
+    ) -> StateDelta {
+        let current = base
+            .current
+            .clone()
+            .freeze()
+            .batch_update(
+                updates.iter().map(|(key, value)| (key.hash(), value)),
+                &EmptyProofReader,
+            )
+            .unwrap()
+            .unfreeze();
+        StateDelta::new(
+            base.base.clone(),
+            base.base_version,
+            current,
+            Some(version),
+            updates
+                .into_iter()
+                .map(|(key, value)| (key, Some(Arc::new(value))))
+                .collect(),
+            base.base_usage,
+            StateStorageUsage::new_untracked(),
+        )
+    }
+
+    #[test]
+    fn test_diff_branches() {
+        let base = branch(
+            &StateDelta::new_empty(),
+            0,
+            vec![(key("a"), value("1")), (key("b"), value("1"))],
+        );
+        let base = StateDelta::new(
+            base.current.clone(),
+            Some(0),
+            base.current,
+            Some(0),
+            HashMap::new(),
+            StateStorageUsage::zero(),
+            StateStorageUsage::zero(),
+        );
+        let left = branch(
+            &base,
+            1,
+            vec![(key("b"), value("2")), (key("c"), value("1"))],
+        );
+        let right = branch(
+            &base,
+            1,
+            vec![(key("c"), value("1")), (key("d"), value("2"))],
+        );
+
+        assert_eq!(
+            left.diff(&right),
+            StateDiff {
+                only_updated_in_self: vec![key("b")],
+                only_updated_in_other: vec![key("d")],
+                different_values: vec![key("b"), key("d")],
+                unresolved: vec![],
+                base_key_hashes: Some(vec![]),
+                root_hashes: (left.root_hash(), right.root_hash()),
+            }
+        );
+        assert_eq!(left.diff(&left).different_values, Vec::<StateKey>::new());
+
+        // A state whose nodes are not in memory.
+        let persisted = StateDelta::new_at_checkpoint(HashValue::random(), Some(0));
+        let diff = left.diff(&persisted);
+        assert_eq!(diff.different_values, Vec::<StateKey>::new());
+        assert_eq!(diff.unresolved, vec![key("b"), key("c")]);
+        assert_eq!(diff.base_key_hashes, None);
+
+        // A delta off a different (empty) base.
+        let other_base = branch(&StateDelta::new_empty(), 0, vec![(key("a"), value("2"))]);
+        let mut expected_base_key_hashes = vec![key("a").hash(), key("b").hash()];
+        expected_base_key_hashes.sort();
+        assert_eq!(
+            base.diff(&other_base).base_key_hashes,
+            Some(expected_base_key_hashes)
+        );
+    }
+}
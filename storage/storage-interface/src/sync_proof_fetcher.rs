@@ -1,7 +1,8 @@
 // Copyright (c) Aptos
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{proof_fetcher::ProofFetcher, DbReader};
+use crate::{proof_cache::ProofCache, proof_fetcher::ProofFetcher, DbReader};
+use anyhow::format_err;
 use aptos_crypto::{hash::CryptoHash, HashValue};
 use aptos_types::{
     proof::SparseMerkleProof,
@@ -16,6 +17,7 @@ use std::{collections::HashMap, sync::Arc};
 pub struct SyncProofFetcher {
     reader: Arc<dyn DbReader>,
     state_proof_cache: RwLock<HashMap<HashValue, SparseMerkleProof>>,
+    shared_proof_cache: Option<Arc<ProofCache>>,
 }
 
 impl SyncProofFetcher {
@@ -23,8 +25,26 @@ impl SyncProofFetcher {
         Self {
             reader,
             state_proof_cache: RwLock::new(HashMap::new()),
+            shared_proof_cache: None,
         }
     }
+
+    /// Consults `proof_cache` before fetching a proof from storage, and adds the proofs fetched
+    /// to it. On a hit only the value is read from storage.
+    pub fn with_proof_cache(mut self, proof_cache: Arc<ProofCache>) -> Self {
+        self.shared_proof_cache = Some(proof_cache);
+        self
+    }
+
+    fn get_cached_proof(
+        &self,
+        state_key: &StateKey,
+        version: Version,
+    ) -> Option<SparseMerkleProof> {
+        self.shared_proof_cache
+            .as_ref()
+            .and_then(|proof_cache| proof_cache.get(version, state_key.hash()))
+    }
 }
 
 impl ProofFetcher for SyncProofFetcher {
@@ -33,9 +53,21 @@ impl ProofFetcher for SyncProofFetcher {
         state_key: &StateKey,
         version: Version,
     ) -> anyhow::Result<(Option<StateValue>, Option<SparseMerkleProof>)> {
-        let (state_value, proof) = self
-            .reader
-            .get_state_value_with_proof_by_version(state_key, version)?;
+        let (state_value, proof) = match self.get_cached_proof(state_key, version) {
+            Some(proof) => (
+                self.reader.get_state_value_by_version(state_key, version)?,
+                proof,
+            ),
+            None => {
+                let (state_value, proof) = self
+                    .reader
+                    .get_state_value_with_proof_by_version(state_key, version)?;
+                if let Some(proof_cache) = &self.shared_proof_cache {
+                    proof_cache.extend(version, vec![(state_key.hash(), proof.clone())]);
+                }
+                (state_value, proof)
+            }
+        };
         // multiple threads may enter this code, and another thread might add
         // an address before this one. Thus the insertion might return a None here.
         self.state_proof_cache
@@ -45,6 +77,64 @@ impl ProofFetcher for SyncProofFetcher {
         Ok((state_value, Some(proof)))
     }
 
+    fn fetch_state_values_and_proofs(
+        &self,
+        state_keys: &[StateKey],
+        version: Version,
+    ) -> anyhow::Result<Vec<(Option<StateValue>, Option<SparseMerkleProof>)>> {
+        let cached_proofs: Vec<_> = state_keys
+            .iter()
+            .map(|state_key| self.get_cached_proof(state_key, version))
+            .collect();
+        let keys_to_fetch: Vec<_> = state_keys
+            .iter()
+            .zip(&cached_proofs)
+            .filter(|(_, proof)| proof.is_none())
+            .map(|(state_key, _)| state_key.clone())
+            .collect();
+        let fetched = if keys_to_fetch.is_empty() {
+            vec![]
+        } else {
+            self.reader
+                .get_state_values_with_proof_by_version(&keys_to_fetch, version)?
+        };
+        if let Some(proof_cache) = &self.shared_proof_cache {
+            proof_cache.extend(
+                version,
+                keys_to_fetch
+                    .iter()
+                    .zip(&fetched)
+                    .map(|(state_key, (_, proof))| (state_key.hash(), proof.clone())),
+            );
+        }
+
+        let mut fetched = fetched.into_iter();
+        let values_and_proofs = state_keys
+            .iter()
+            .zip(cached_proofs)
+            .map(|(state_key, cached_proof)| match cached_proof {
+                Some(proof) => Ok((
+                    self.reader.get_state_value_by_version(state_key, version)?,
+                    proof,
+                )),
+                None => fetched
+                    .next()
+                    .ok_or_else(|| format_err!("Missing fetched proof of {:?}.", state_key)),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.state_proof_cache.write().extend(
+            state_keys
+                .iter()
+                .zip(&values_and_proofs)
+                .map(|(state_key, (_, proof))| (state_key.hash(), proof.clone())),
+        );
+
+        Ok(values_and_proofs
+            .into_iter()
+            .map(|(state_value, proof)| (state_value, Some(proof)))
+            .collect())
+    }
+
     fn get_proof_cache(&self) -> HashMap<HashValue, SparseMerkleProof> {
         self.state_proof_cache
             .read()
@@ -45,6 +45,7 @@ pub async fn setup_test(
         tls_cert_path: None,
         tls_key_path: None,
         content_length_limit: None,
+        ..Default::default()
     };
 
     // Start the server
@@ -198,6 +198,11 @@ pub struct ConfigurationResource {
 }
 
 impl ConfigurationResource {
+    /// Where the resource is stored, under the core code account.
+    pub fn access_path() -> AccessPath {
+        AccessPath::new(CORE_CODE_ADDRESS, Self::resource_path())
+    }
+
     pub fn epoch(&self) -> u64 {
         self.epoch
     }
@@ -47,6 +47,7 @@ use std::{
     collections::{BTreeMap, BTreeSet, HashMap},
     convert::TryFrom,
     iter::Iterator,
+    sync::Arc,
 };
 
 impl WriteOp {
@@ -821,7 +822,10 @@ impl TransactionToCommitGen {
                     .map(move |(key, value)| {
                         let state_key = StateKey::AccessPath(AccessPath::new(address, key));
                         (
-                            (state_key.clone(), StateValue::from(value.clone())),
+                            (
+                                state_key.clone(),
+                                Some(Arc::new(StateValue::from(value.clone()))),
+                            ),
                             (state_key, WriteOp::Value(value)),
                         )
                     })
@@ -3,5 +3,6 @@
 
 pub mod state_key;
 pub mod state_key_prefix;
+pub mod state_storage_usage;
 pub mod state_value;
 pub mod table;
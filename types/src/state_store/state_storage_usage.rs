@@ -0,0 +1,50 @@
+// Copyright (c) Aptos
+// SPDX-License-Identifier: Apache-2.0
+
+/// The number of state items and the total bytes of their keys and values, which on-chain storage
+/// gas is charged by.
+///
+/// The usage can only be tracked incrementally from a state whose usage is known. When that's not
+/// the case (e.g. the state was loaded from a snapshot in the DB), it's `Untracked`, and stays so
+/// for the states derived from it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StateStorageUsage {
+    Tracked { items: usize, bytes: usize },
+    Untracked,
+}
+
+impl StateStorageUsage {
+    pub fn new(items: usize, bytes: usize) -> Self {
+        Self::Tracked { items, bytes }
+    }
+
+    pub fn zero() -> Self {
+        Self::new(0, 0)
+    }
+
+    pub fn new_untracked() -> Self {
+        Self::Untracked
+    }
+
+    pub fn is_untracked(&self) -> bool {
+        matches!(self, Self::Untracked)
+    }
+
+    pub fn add_item(&mut self, bytes_delta: usize) {
+        if let Self::Tracked { items, bytes } = self {
+            *items += 1;
+            *bytes += bytes_delta;
+        }
+    }
+
+    /// Removing more than was added means the usage this was tracked from was wrong, in which case
+    /// it becomes `Untracked`.
+    pub fn remove_item(&mut self, bytes_delta: usize) {
+        if let Self::Tracked { items, bytes } = *self {
+            *self = match (items.checked_sub(1), bytes.checked_sub(bytes_delta)) {
+                (Some(items), Some(bytes)) => Self::Tracked { items, bytes },
+                _ => Self::Untracked,
+            };
+        }
+    }
+}